@@ -73,6 +73,8 @@ const HEADER: [u8; 2] = [0x53, 0x59]; // "SY"
 const TAIL:   [u8; 2] = [0x54, 0x43]; // "TC"
 
 const TIMEOUT_TOTAL: Duration = Duration::from_secs(5);
+/// Value reported for a vital sign the sensor can't currently measure.
+const UNAVAILABLE: u8 = 0xFF;
 
 // ------------------------------------------------------------------------------------------------
 // Public enums (1‑to‑1 with Python constants)
@@ -178,6 +180,8 @@ pub enum Error {
 // ------------------------------------------------------------------------------------------------
 // Response for typical sleep request
 // ------------------------------------------------------------------------------------------------
+/// Presence, movement, and vital signs from [`C1001::poll_sleep_data`]; `None` when unavailable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct C1001SleepData {
     pub presence: Option<bool>,
     pub movement: Option<bool>,
@@ -328,24 +332,27 @@ impl C1001 {
         Ok(())
     }
 
-    /// Request most relevant sleep data. Any failed calls return None & print.
+    /// Request most relevant sleep data. Any failed calls return None & log a warning.
+    /// Heart and respiration rates the sensor reports as unavailable (0xFF) are also None.
     pub fn poll_sleep_data(&mut self) -> C1001SleepData {
         let presence = self.sleep_human_data(HumanPresence::Presence)
             .map(|v| v != 0)
-            .map_err(|e| eprintln!("{e}"))
+            .map_err(|e| tracing::warn!("C1001 presence error: {e}"))
             .ok();
         let movement = self.sleep_human_data(HumanPresence::Movement)
             .map(|v| v == 2)
-            .map_err(|e| eprintln!("{e}"))
+            .map_err(|e| tracing::warn!("C1001 movement error: {e}"))
             .ok();
         let heart_rate_bpm = self.heart_rate()
-            .map(|v| v.into())
-            .map_err(|e| eprintln!("{e}"))
-            .ok();
+            .map_err(|e| tracing::warn!("C1001 heart rate error: {e}"))
+            .ok()
+            .filter(|&v| v != UNAVAILABLE)
+            .map(u16::from);
         let resp_rate_bpm = self.breathe_value()
-            .map(|v| v.into())
-            .map_err(|e| eprintln!("{e}"))
-            .ok();
+            .map_err(|e| tracing::warn!("C1001 respiration error: {e}"))
+            .ok()
+            .filter(|&v| v != UNAVAILABLE)
+            .map(u16::from);
         C1001SleepData {presence, movement, heart_rate_bpm, resp_rate_bpm }
     }
 
@@ -9,7 +9,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     adc.set_resolution(Resolution::Bits16);
     adc.convert().unwrap();
     std::thread::sleep(std::time::Duration::from_millis(10));
-    let volts = adc.read(false).unwrap();
-    println!("Voltage: {:.3} V", volts);
+    let volts = adc.read().unwrap();
+    println!("Voltage: {:.3} V ({:.1} mV)", volts.volts(), volts.millivolts());
     Ok(())
 }
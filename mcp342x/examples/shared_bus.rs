@@ -0,0 +1,23 @@
+use std::sync::Mutex;
+
+use embedded_hal_bus::i2c::MutexDevice;
+use linux_embedded_hal::I2cdev;
+use mcp342x::{Channel, Gain, MCP342x, Resolution};
+
+/// Two ADCs on one I2C bus, opened only once and shared through `embedded-hal-bus`.
+/// Run with `cargo run --example shared_bus --features shared-bus`.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let bus = Mutex::new(I2cdev::new("/dev/i2c-1")?);
+
+    let mut adc_a = MCP342x::new_shared(&bus, 0x68);
+    let mut adc_b = MCP342x::new(MutexDevice::new(&bus), 0x69);
+    for adc in [&mut adc_a, &mut adc_b] {
+        adc.set_channel(Channel::Ch1);
+        adc.set_gain(Gain::G1);
+        adc.set_resolution(Resolution::Bits16);
+    }
+
+    println!("ADC 0x68: {}", adc_a.convert_and_read(true)?);
+    println!("ADC 0x69: {}", adc_b.convert_and_read(true)?);
+    Ok(())
+}
@@ -1,9 +1,16 @@
 //! MCP342x ADC driver for Linux using linux_embedded_hal and embedded-hal.
 
 use embedded_hal::i2c::I2c;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
+#[cfg(feature = "thermistor")]
+pub mod thermistor;
+#[cfg(feature = "tokio")]
+mod stream;
+#[cfg(feature = "tokio")]
+pub use stream::{SampleStream, TimedSample};
+
 /// Errors for the MCP342x driver.
 #[derive(Error, Debug)]
 pub enum Error<E: std::error::Error + 'static> {
@@ -11,7 +18,107 @@ pub enum Error<E: std::error::Error + 'static> {
     I2c(#[from] E),
     #[error("Configuration read back from device does not match driver config: used {used}, stored {stored}")]
     ConfigMismatch { used: u8, stored: u8 },
+    #[error("Conversion did not complete within {0:?}")]
+    Timeout(Duration),
+    #[error("Invalid configuration for {device:?}: {reason}")]
+    InvalidConfig { device: Device, reason: &'static str },
+}
+
+/// MCP342x family members, which differ in channel count and maximum resolution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Device {
+    /// 1 channel, up to 18 bits.
+    MCP3421,
+    /// 2 channels, up to 18 bits.
+    MCP3422,
+    /// 2 channels, up to 18 bits.
+    MCP3423,
+    /// 4 channels, up to 18 bits.
+    MCP3424,
+    /// 1 channel, up to 16 bits.
+    MCP3425,
+    /// 2 channels, up to 16 bits.
+    MCP3426,
+    /// 2 channels, up to 16 bits.
+    MCP3427,
+    /// 4 channels, up to 16 bits.
+    MCP3428,
+}
+
+impl Device {
+    /// Number of input channels.
+    pub fn channels(self) -> u8 {
+        match self {
+            Device::MCP3421 | Device::MCP3425 => 1,
+            Device::MCP3422 | Device::MCP3423 | Device::MCP3426 | Device::MCP3427 => 2,
+            Device::MCP3424 | Device::MCP3428 => 4,
+        }
+    }
+
+    /// Whether the 18-bit (3.75 SPS) resolution is available.
+    pub fn supports_18_bit(self) -> bool {
+        matches!(self, Device::MCP3421 | Device::MCP3422 | Device::MCP3423 | Device::MCP3424)
+    }
+}
+
+/// A voltage measured by the ADC, in volts.
+///
+/// Returned by [`MCP342x::read`] and [`MCP342x::convert_and_read`] so that converted
+/// voltages cannot be confused with the raw counts returned by [`MCP342x::read_count`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Volts(pub f32);
+
+impl Volts {
+    /// Voltage in volts.
+    pub fn volts(self) -> f32 {
+        self.0
+    }
+
+    /// Voltage in millivolts.
+    pub fn millivolts(self) -> f32 {
+        self.0 * 1e3
+    }
+}
+
+impl From<Volts> for f32 {
+    fn from(v: Volts) -> Self {
+        v.0
+    }
+}
+
+impl std::fmt::Display for Volts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} V", self.0)
+    }
+}
+
+/// One sample of a burst [`Capture`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CapturedSample {
+    /// Time the sample was read, relative to [`Capture::start`].
+    pub offset: Duration,
+    /// Measured voltage.
+    pub voltage: Volts,
+}
 
+/// A burst of samples captured by [`MCP342x::capture`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+    /// Wall-clock time the capture started.
+    pub start: SystemTime,
+    /// Samples in the order they were read.
+    pub samples: Vec<CapturedSample>,
+}
+
+/// A differential measurement, see [`MCP342x::read_differential`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Differential {
+    /// Signed input voltage CHn+ − CHn− in millivolts.
+    pub millivolts: f32,
+    /// Full-scale range for the active gain in millivolts; valid inputs are within ±`full_scale_mv`.
+    pub full_scale_mv: f32,
+    /// The input exceeded ±2.048 V / gain and the reading is clipped.
+    pub out_of_range: bool,
 }
 
 /// PGA gain settings.
@@ -41,10 +148,40 @@ pub enum Channel {
     Ch4 = 0b1100000,
 }
 
+/// State of an Adr0/Adr1 address selection pin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressPin {
+    /// Tied to VSS.
+    Low,
+    /// Tied to VDD.
+    High,
+    /// Left unconnected.
+    Float,
+}
+
+impl AddressPin {
+    /// I2C address for the given Adr0/Adr1 pin states (datasheet table 5-3, 0x68–0x6F).
+    pub fn address(adr0: AddressPin, adr1: AddressPin) -> u8 {
+        use AddressPin::*;
+        let bits = match (adr0, adr1) {
+            (Low, Low) | (Float, Float) => 0b000,
+            (Low, Float) => 0b001,
+            (Low, High) => 0b010,
+            (Float, Low) => 0b011,
+            (High, Low) => 0b100,
+            (High, Float) => 0b101,
+            (High, High) => 0b110,
+            (Float, High) => 0b111,
+        };
+        0x68 | bits
+    }
+}
+
 /// MCP342x driver struct.
 pub struct MCP342x<I2C> {
     i2c: I2C,
     address: u8,
+    device: Device,
     config: u8,
     scale_factor: f32,
     offset: f32,
@@ -60,10 +197,50 @@ where
     const CONT_MASK: u8 = 0b00010000;
     const CH_MASK: u8 = 0b01100000;
     const NOT_READY: u8 = 0b10000000;
+    /// Internal reference voltage in millivolts; the input range is ±VREF / gain.
+    const VREF_MV: f32 = 2048.0;
 
     /// Create a new ADC instance. Default config = 0.
+    ///
+    /// Assumes an MCP3424 (4 channels, 18 bits), which accepts every configuration;
+    /// use [`MCP342x::with_device`] to have the configuration checked against a smaller variant.
     pub fn new(i2c: I2C, address: u8) -> Self {
-        MCP342x { i2c, address, config: 0, scale_factor: 1.0, offset: 0.0 }
+        Self::with_device(i2c, address, Device::MCP3424)
+    }
+
+    /// Create a new ADC instance addressed by the state of its Adr0/Adr1 pins. Default config = 0.
+    pub fn with_address_pins(i2c: I2C, adr0: AddressPin, adr1: AddressPin) -> Self {
+        Self::new(i2c, AddressPin::address(adr0, adr1))
+    }
+
+    /// I2C address of the device.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Create a new ADC instance for a specific device variant. Default config = 0.
+    pub fn with_device(i2c: I2C, address: u8, device: Device) -> Self {
+        MCP342x { i2c, address, device, config: 0, scale_factor: 1.0, offset: 0.0 }
+    }
+
+    /// Device variant the configuration is checked against.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// Check the current channel and resolution against the device variant.
+    ///
+    /// Called before every write or read, so an unsupported setting (e.g. 18 bits on an MCP3425)
+    /// is reported as [`Error::InvalidConfig`] instead of producing misdecoded samples.
+    pub fn validate_config(&self) -> Result<(), Error<E>> {
+        let channel = (self.config & Self::CH_MASK) >> 5;
+        if channel >= self.device.channels() {
+            return Err(Error::InvalidConfig { device: self.device, reason: "channel not available on this device" });
+        }
+        if self.config & Self::RES_MASK == Resolution::Bits18 as u8 && !self.device.supports_18_bit() {
+            return Err(Error::InvalidConfig { device: self.device, reason: "18-bit resolution not available on this device" });
+        }
+        Ok(())
     }
 
     /// Select input channel.
@@ -102,11 +279,13 @@ where
 
     /// Write current config to device.
     pub fn configure(&mut self) -> Result<(), Error<E>> {
+        self.validate_config()?;
         self.i2c.write(self.address, &[self.config]).map_err(Error::I2c)
     }
 
     /// Initiate one-shot conversion (ignores continuous mode bit).
     pub fn convert(&mut self) -> Result<(), Error<E>> {
+        self.validate_config()?;
         let mut c = self.config & !Self::CONT_MASK;
         c |= Self::NOT_READY;
         self.i2c.write(self.address, &[c]).map_err(Error::I2c)
@@ -114,15 +293,10 @@ where
 
     /// Low-level raw read: returns (count, config_used).
     pub fn raw_read(&mut self) -> Result<(i32, u8), Error<E>> {
-        // Decode resolution bits to sample width
-        let res_bits = match self.config & Self::RES_MASK {
-            0b0000 => 12,
-            0b0100 => 14,
-            0b1000 => 16,
-            0b110000 => 18,
-            _ => unreachable!(),
-        }; // note: mask covers only two bits, last arm covers unused bits
-        let bytes = if res_bits == 18 { 4 } else { 3 };
+        self.validate_config()?;
+        // Decode resolution bits to sample width (18-bit samples have an extra data byte)
+        let res_bits = self.resolution_bits();
+        let bytes = self.read_len();
         let mut buf = [0u8; 4];
 
         loop {
@@ -138,44 +312,140 @@ where
                 for &b in &buf[..bytes - 1] {
                     count = (count << 8) | (b as i32);
                 }
-                // Sign extend
-                let sign_mask = 1 << (res_bits - 1);
-                let mag_mask = (sign_mask - 1) as i32;
-                if (count & sign_mask) != 0 {
-                    count = -((!count & mag_mask) + 1);
-                }
-                return Ok((count, config_used));
+                return Ok((sign_extend(count, res_bits), config_used));
             }
         }
     }
 
-    /// Read voltage (or raw count if `raw`=true).
-    pub fn read(&mut self, raw: bool) -> Result<f32, Error<E>> {
+    /// Read the raw conversion count, checking that the device used the driver config.
+    pub fn read_count(&mut self) -> Result<i32, Error<E>> {
         let (count, config_used) = self.raw_read()?;
         if config_used != self.config {
             return Err(Error::ConfigMismatch { used: config_used, stored: self.config });
         }
-        if raw {
-            return Ok(count as f32);
+        Ok(count)
+    }
+
+    /// Read the raw count as an `i16`, sign-extended from the configured 12, 14, or 16-bit resolution.
+    ///
+    /// Returns [`Error::InvalidConfig`] at 18 bits, where counts do not fit; use [`MCP342x::read_raw_i32`].
+    pub fn read_raw_i16(&mut self) -> Result<i16, Error<E>> {
+        if self.resolution_bits() > 16 {
+            return Err(Error::InvalidConfig { device: self.device, reason: "18-bit counts do not fit in i16" });
+        }
+        Ok(self.read_count()? as i16)
+    }
+
+    /// Read the raw count as an `i32`, sign-extended from the configured resolution (any width).
+    pub fn read_raw_i32(&mut self) -> Result<i32, Error<E>> {
+        self.read_count()
+    }
+
+    /// Read voltage, applying LSB size, PGA gain, scale factor and offset.
+    pub fn read(&mut self) -> Result<Volts, Error<E>> {
+        let count = self.read_count()?;
+        Ok(self.count_to_volts(count))
+    }
+
+    /// Convert a count at the current resolution and gain to volts, with scale factor and offset.
+    fn count_to_volts(&self, count: i32) -> Volts {
+        Volts((count as f32) * self.lsb() * self.scale_factor / self.gain_factor() + self.offset)
+    }
+
+    /// Capture a burst of `n_samples` readings as fast as the device allows (240 SPS).
+    ///
+    /// Switches to continuous 12-bit mode and then only issues plain reads, one per sample, each
+    /// returning as soon as a new conversion is available. Every sample is timestamped relative to
+    /// the start of the capture. The previous configuration is restored afterwards, so a one-shot
+    /// setup drops back to standby.
+    pub fn capture(&mut self, n_samples: usize) -> Result<Capture, Error<E>> {
+        let previous = self.config;
+        self.set_resolution(Resolution::Bits12);
+        self.set_continuous_mode(true);
+        let result = self.capture_continuous(n_samples);
+        self.config = previous;
+        self.configure()?;
+        result
+    }
+
+    fn capture_continuous(&mut self, n_samples: usize) -> Result<Capture, Error<E>> {
+        self.configure()?;
+        let start = SystemTime::now();
+        let t0 = Instant::now();
+        let timeout = self.ready_timeout();
+        let mut samples = Vec::with_capacity(n_samples);
+        let mut last_sample = t0;
+        let mut buf = [0u8; 3];
+
+        while samples.len() < n_samples {
+            self.i2c.read(self.address, &mut buf).map_err(Error::I2c)?;
+            let now = Instant::now();
+            // In continuous mode RDY=0 flags a conversion that hasn't been read yet
+            if buf[2] & Self::NOT_READY == 0 {
+                let count = sign_extend(((buf[0] as i32) << 8) | buf[1] as i32, 12);
+                samples.push(CapturedSample { offset: now - t0, voltage: self.count_to_volts(count) });
+                last_sample = now;
+            } else if now - last_sample > timeout {
+                return Err(Error::Timeout(timeout));
+            }
         }
-        // Determine LSB
-        let lsb = match config_used & Self::RES_MASK {
+        Ok(Capture { start, samples })
+    }
+
+    /// Read a differential measurement (CHn+ − CHn−) in signed millivolts.
+    ///
+    /// Polarity: the result is positive when CHn+ is above CHn−, negative otherwise. The count is
+    /// sign-extended from the active resolution, so no bit manipulation is needed by the caller.
+    /// The scale factor and offset are not applied: the result is the voltage at the input pins.
+    ///
+    /// The input range is ±2.048 V / gain. Inputs beyond that saturate at the output code limits,
+    /// which is reported with `out_of_range` rather than returning a clipped value silently.
+    pub fn read_differential(&mut self) -> Result<Differential, Error<E>> {
+        let count = self.read_count()?;
+        let half_range = 1i32 << (self.resolution_bits() - 1);
+        Ok(Differential {
+            millivolts: (count as f32) * self.lsb() / self.gain_factor() * 1e3,
+            full_scale_mv: Self::VREF_MV / self.gain_factor(),
+            out_of_range: count >= half_range - 1 || count <= -half_range,
+        })
+    }
+
+    /// Size of one count in volts at the current resolution (before gain).
+    fn lsb(&self) -> f32 {
+        match self.config & Self::RES_MASK {
             0b0000 => 1e-3,
             0b0100 => 250e-6,
             0b1000 => 62.5e-6,
             0b1100 => 15.625e-6,
             _ => unreachable!(),
-        };
-        // Determine gain
-        let gain = match config_used & Self::GAIN_MASK {
+        }
+    }
+
+    /// PGA gain as a multiplier.
+    fn gain_factor(&self) -> f32 {
+        match self.config & Self::GAIN_MASK {
             0b00 => 1.0,
             0b01 => 2.0,
             0b10 => 4.0,
             0b11 => 8.0,
             _ => unreachable!(),
-        };
-        let voltage = (count as f32) * lsb * self.scale_factor / gain + self.offset;
-        Ok(voltage)
+        }
+    }
+
+    /// Sample width in bits at the current resolution.
+    fn resolution_bits(&self) -> u8 {
+        match self.config & Self::RES_MASK {
+            0b0000 => 12,
+            0b0100 => 14,
+            0b1000 => 16,
+            0b1100 => 18,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Read voltage in millivolts.
+    pub fn read_millivolts(&mut self) -> Result<f32, Error<E>> {
+        self.read().map(Volts::millivolts)
     }
 
     /// Expected conversion time in seconds for current resolution.
@@ -189,14 +459,123 @@ where
         }
     }
 
-    /// Do a convert + read cycle, sleeping until conversion completes if `sleep`=true.
-    pub fn convert_and_read(&mut self, sleep: bool, raw: bool) -> Result<f32, Error<E>> {
+    /// Leave the device in standby, its lowest-power state.
+    ///
+    /// Switches to one-shot mode without starting a new conversion, then waits for any conversion
+    /// still in progress to finish. After this returns the device draws standby current until the
+    /// next [`MCP342x::convert`] or [`MCP342x::wake_and_convert`].
+    pub fn standby(&mut self) -> Result<(), Error<E>> {
+        self.set_continuous_mode(false);
+        // Writing the config with RDY=0 in one-shot mode does not start a conversion
+        self.configure()?;
+        self.wait_ready(self.ready_timeout())
+    }
+
+    /// Wake the device from standby with a one-shot conversion and return the result.
+    ///
+    /// Rather than sleeping for a fixed time, this polls the ready bit (bounded by a timeout) so the
+    /// longer first conversion after power-up or standby is handled. The device returns to standby
+    /// once the conversion is complete.
+    pub fn wake_and_convert(&mut self) -> Result<Volts, Error<E>> {
+        self.set_continuous_mode(false);
+        self.convert()?;
+        std::thread::sleep(Duration::from_secs_f32(self.conversion_time()));
+        self.wait_ready(self.ready_timeout())?;
+        self.read()
+    }
+
+    /// Poll the status byte until no conversion is pending, or `timeout` has elapsed.
+    fn wait_ready(&mut self, timeout: Duration) -> Result<(), Error<E>> {
+        let start = Instant::now();
+        let poll_interval = Duration::from_secs_f32(self.conversion_time() / 4.0);
+        let bytes = self.read_len();
+        let mut buf = [0u8; 4];
+        loop {
+            // Plain read: writing the config here could start another conversion
+            self.i2c.read(self.address, &mut buf[..bytes]).map_err(Error::I2c)?;
+            if buf[bytes - 1] & Self::NOT_READY == 0 {
+                return Ok(());
+            }
+            if start.elapsed() > timeout {
+                return Err(Error::Timeout(timeout));
+            }
+            std::thread::sleep(poll_interval);
+        }
+    }
+
+    /// Upper bound for a conversion to complete, including the first conversion after standby.
+    fn ready_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.conversion_time() * 3.0)
+    }
+
+    /// Number of bytes in a read (data + config) at the current resolution.
+    fn read_len(&self) -> usize {
+        if self.resolution_bits() == 18 { 4 } else { 3 }
+    }
+
+    /// Start a one-shot conversion and sleep until it should be complete, if `sleep`=true.
+    fn convert_and_wait(&mut self, sleep: bool) -> Result<(), Error<E>> {
         self.convert()?;
         if sleep {
             let delay = self.conversion_time() * 1.2;
             std::thread::sleep(Duration::from_secs_f32(delay));
         }
-        self.read(raw)
+        Ok(())
+    }
+
+    /// Do a convert + read cycle, sleeping until conversion completes if `sleep`=true.
+    pub fn convert_and_read(&mut self, sleep: bool) -> Result<Volts, Error<E>> {
+        self.convert_and_wait(sleep)?;
+        self.read()
+    }
+
+    /// Do a convert + read cycle and return millivolts.
+    pub fn convert_and_read_millivolts(&mut self, sleep: bool) -> Result<f32, Error<E>> {
+        self.convert_and_read(sleep).map(Volts::millivolts)
+    }
+
+    /// Do a convert + read cycle and return a differential measurement.
+    pub fn convert_and_read_differential(&mut self, sleep: bool) -> Result<Differential, Error<E>> {
+        self.convert_and_wait(sleep)?;
+        self.read_differential()
+    }
+
+    /// Do a convert + read cycle and return the raw conversion count.
+    pub fn convert_and_read_count(&mut self, sleep: bool) -> Result<i32, Error<E>> {
+        self.convert_and_wait(sleep)?;
+        self.read_count()
+    }
+}
+
+#[cfg(feature = "shared-bus")]
+impl<'a, I2C, E> MCP342x<embedded_hal_bus::i2c::MutexDevice<'a, I2C>>
+where
+    I2C: I2c<Error = E>,
+    I2C::Error: std::error::Error + 'static,
+{
+    /// Create a new ADC instance on an I2C bus shared with other drivers.
+    ///
+    /// Each driver gets its own `MutexDevice` handle to the same bus, so the bus only has to be
+    /// opened once:
+    ///
+    /// ```ignore
+    /// let bus = std::sync::Mutex::new(I2cdev::new("/dev/i2c-1")?);
+    /// let mut adc = MCP342x::new_shared(&bus, 0x68);
+    /// let mut bme280 = BME280::new_primary(MutexDevice::new(&bus));
+    /// ```
+    pub fn new_shared(bus: &'a std::sync::Mutex<I2C>, address: u8) -> Self {
+        Self::new(embedded_hal_bus::i2c::MutexDevice::new(bus), address)
+    }
+}
+
+/// Sign-extend a two's complement `count` that is `bits` wide.
+fn sign_extend(count: i32, bits: u8) -> i32 {
+    let sign_mask = 1 << (bits - 1);
+    let mag_mask = sign_mask - 1;
+    if (count & sign_mask) != 0 {
+        -((!count & mag_mask) + 1)
+    } else {
+        count & mag_mask
     }
 }
 
@@ -223,3 +602,188 @@ where
 {
     i2c.write(0, &[0x08])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::{ErrorKind, ErrorType, Operation};
+
+    #[derive(Debug)]
+    struct FakeError;
+
+    impl std::fmt::Display for FakeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "fake I2C error")
+        }
+    }
+
+    impl std::error::Error for FakeError {}
+
+    impl embedded_hal::i2c::Error for FakeError {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    /// Minimal fake bus: records written bytes and answers every read with `response`.
+    #[derive(Default)]
+    struct FakeI2c {
+        writes: Vec<Vec<u8>>,
+        response: Vec<u8>,
+    }
+
+    impl ErrorType for FakeI2c {
+        type Error = FakeError;
+    }
+
+    impl I2c for FakeI2c {
+        fn transaction(&mut self, _address: u8, operations: &mut [Operation<'_>]) -> Result<(), FakeError> {
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => self.writes.push(bytes.to_vec()),
+                    Operation::Read(buf) => {
+                        let n = buf.len();
+                        buf.copy_from_slice(&self.response[..n]);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rejects_18_bit_on_16_bit_device() {
+        let mut adc = MCP342x::with_device(FakeI2c::default(), 0x68, Device::MCP3425);
+        adc.set_resolution(Resolution::Bits18);
+        assert!(matches!(adc.configure(), Err(Error::InvalidConfig { device: Device::MCP3425, .. })));
+        assert!(adc.i2c.writes.is_empty());
+    }
+
+    #[test]
+    fn rejects_missing_channel() {
+        let mut adc = MCP342x::with_device(FakeI2c::default(), 0x68, Device::MCP3426);
+        adc.set_channel(Channel::Ch3);
+        assert!(matches!(adc.convert(), Err(Error::InvalidConfig { device: Device::MCP3426, .. })));
+        assert!(adc.i2c.writes.is_empty());
+    }
+
+    #[test]
+    fn differential_reports_sign_and_saturation() {
+        let mut adc = MCP342x::new(FakeI2c::default(), 0x68);
+        adc.set_resolution(Resolution::Bits16);
+        adc.set_gain(Gain::G2);
+        let config = adc.config;
+
+        // -1000 counts at 62.5 uV/count and gain 2 = -31.25 mV
+        let count = (-1000i16).to_be_bytes();
+        adc.i2c.response = vec![count[0], count[1], config];
+        let diff = adc.read_differential().unwrap();
+        assert!((diff.millivolts + 31.25).abs() < 1e-3, "Expected -31.25 mV, got {}", diff.millivolts);
+        assert_eq!(diff.full_scale_mv, 1024.0);
+        assert!(!diff.out_of_range);
+
+        // Most negative code means the input is clipped
+        adc.i2c.response = vec![0x80, 0x00, config];
+        let diff = adc.read_differential().unwrap();
+        assert!(diff.out_of_range);
+        assert!((diff.millivolts + 1024.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn decodes_18_bit_counts() {
+        let mut adc = MCP342x::new(FakeI2c::default(), 0x68);
+        adc.set_resolution(Resolution::Bits18);
+        let config = adc.config;
+
+        adc.i2c.response = vec![0x00, 0x01, 0x00, config];
+        assert_eq!(adc.read_raw_i32().unwrap(), 256);
+        // Upper bits of the first byte repeat the sign bit
+        adc.i2c.response = vec![0xFE, 0x00, 0x00, config];
+        assert_eq!(adc.read_raw_i32().unwrap(), -(1 << 17));
+        assert!(matches!(adc.read_raw_i16(), Err(Error::InvalidConfig { .. })));
+    }
+
+    #[test]
+    fn decodes_12_bit_counts_as_i16() {
+        let mut adc = MCP342x::new(FakeI2c::default(), 0x68);
+        adc.set_resolution(Resolution::Bits12);
+        // 0xF800 sign-extended from 12 bits is -2048
+        adc.i2c.response = vec![0xF8, 0x00, adc.config];
+        assert_eq!(adc.read_raw_i16().unwrap(), -2048);
+    }
+
+    #[test]
+    fn capture_reads_burst_and_restores_config() {
+        let mut adc = MCP342x::new(FakeI2c::default(), 0x68);
+        adc.set_resolution(Resolution::Bits16);
+        let previous = adc.config;
+        adc.i2c.response = vec![0x00, 0x10, 0x10];
+
+        let capture = adc.capture(5).unwrap();
+        assert_eq!(capture.samples.len(), 5);
+        assert!(capture.samples.iter().all(|s| (s.voltage.volts() - 0.016).abs() < 1e-6));
+        assert!(capture.samples.windows(2).all(|w| w[0].offset <= w[1].offset));
+        // Continuous 12-bit config at the start, original config restored at the end, nothing else
+        assert_eq!(adc.i2c.writes, vec![vec![0b0001_0000], vec![previous]]);
+        assert_eq!(adc.config, previous);
+    }
+
+    #[test]
+    fn address_pins_map_to_datasheet_table() {
+        use AddressPin::*;
+        let table = [
+            ((Low, Low), 0x68),
+            ((Low, Float), 0x69),
+            ((Low, High), 0x6A),
+            ((Float, Low), 0x6B),
+            ((High, Low), 0x6C),
+            ((High, Float), 0x6D),
+            ((High, High), 0x6E),
+            ((Float, High), 0x6F),
+            ((Float, Float), 0x68),
+        ];
+        for ((adr0, adr1), address) in table {
+            assert_eq!(AddressPin::address(adr0, adr1), address, "Adr0 {:?}, Adr1 {:?}", adr0, adr1);
+        }
+        let adc = MCP342x::with_address_pins(FakeI2c::default(), Float, High);
+        assert_eq!(adc.address(), 0x6F);
+    }
+
+    #[test]
+    fn standby_leaves_continuous_mode_without_converting() {
+        let mut adc = MCP342x::new(FakeI2c::default(), 0x68);
+        adc.set_continuous_mode(true);
+        adc.i2c.response = vec![0x00, 0x00, 0x00];
+        adc.standby().unwrap();
+        // Single config write, with both the continuous and ready (start conversion) bits cleared
+        assert_eq!(adc.i2c.writes.len(), 1);
+        assert_eq!(adc.i2c.writes[0][0] & 0b1001_0000, 0);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn stream_yields_timestamped_samples() {
+        use futures_core::Stream;
+        use std::pin::Pin;
+
+        let mut adc = MCP342x::new(FakeI2c::default(), 0x68);
+        // 256 counts at 1 mV/count (12-bit)
+        adc.i2c.response = vec![0x01, 0x00, 0x00];
+        let mut stream = adc.into_stream(Duration::from_millis(1));
+        for _ in 0..2 {
+            let sample = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+                .await
+                .expect("Stream ended")
+                .expect("Reading failed");
+            assert!((sample.voltage.volts() - 0.256).abs() < 1e-6);
+            assert!(sample.timestamp_s > 0);
+        }
+    }
+
+    #[test]
+    fn standby_times_out_if_conversion_never_completes() {
+        let mut adc = MCP342x::new(FakeI2c::default(), 0x68);
+        adc.i2c.response = vec![0x00, 0x00, 0x80];
+        assert!(matches!(adc.standby(), Err(Error::Timeout(_))));
+    }
+}
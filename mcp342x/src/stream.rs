@@ -0,0 +1,68 @@
+//! Tokio stream of periodic, timestamped readings.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use embedded_hal::i2c::I2c;
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::{Error, MCP342x, Volts};
+
+/// A voltage reading with the time it was taken.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimedSample {
+    /// Time of the reading in seconds since UNIX epoch.
+    pub timestamp_s: u64,
+    /// Measured voltage.
+    pub voltage: Volts,
+}
+
+/// Stream of readings taken every `period`, created by [`MCP342x::into_stream`].
+///
+/// The blocking I2C transfers run on a dedicated thread, so polling the stream never blocks the
+/// async executor. Dropping the stream stops the sampling thread after its current reading.
+pub struct SampleStream<E: std::error::Error + 'static> {
+    rx: mpsc::Receiver<Result<TimedSample, Error<E>>>,
+}
+
+impl<E: std::error::Error + 'static> Stream for SampleStream<E> {
+    type Item = Result<TimedSample, Error<E>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl<I2C, E> MCP342x<I2C>
+where
+    I2C: I2c<Error = E> + Send + 'static,
+    I2C::Error: std::error::Error + Send + 'static,
+{
+    /// Consume the driver and produce a stream of one-shot readings, one every `period`.
+    ///
+    /// The ADC should be configured (channel, gain, resolution) beforehand. Readings that fail are
+    /// yielded as errors and sampling continues on the next period.
+    pub fn into_stream(mut self, period: Duration) -> SampleStream<E> {
+        let (tx, rx) = mpsc::channel(16);
+        std::thread::spawn(move || {
+            let mut next = Instant::now();
+            loop {
+                let sample = self.convert_and_read(true).map(|voltage| TimedSample {
+                    timestamp_s: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                    voltage,
+                });
+                if tx.blocking_send(sample).is_err() {
+                    break; // stream dropped
+                }
+                next += period;
+                std::thread::sleep(next.saturating_duration_since(Instant::now()));
+            }
+        });
+        SampleStream { rx }
+    }
+}
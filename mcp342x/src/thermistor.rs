@@ -0,0 +1,114 @@
+//! Thermistor linearization helpers for an MCP342x channel.
+//!
+//! Assumes the thermistor sits in a voltage divider with a fixed resistor, with the ADC
+//! measuring the voltage across the fixed resistor:
+//!
+//! ```text
+//! Vss ── thermistor ──┬── R_divider ── GND
+//!                     └── ADC input
+//! ```
+
+use embedded_hal::i2c::I2c;
+
+use crate::{Error, MCP342x, Volts};
+
+/// Steinhart–Hart coefficients: `1/T = a + b ln(R) + c ln(R)^3`, with T in Kelvin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SteinhartHart {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+impl SteinhartHart {
+    /// Temperature in degrees Celsius for a thermistor resistance in Ohms.
+    pub fn temperature_c(&self, resistance_ohms: f64) -> f64 {
+        let ln_r = resistance_ohms.ln();
+        1.0 / (self.a + self.b * ln_r + self.c * ln_r.powi(3)) - 273.15
+    }
+}
+
+/// Voltage divider the thermistor is wired into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Divider {
+    /// Fixed divider resistor in Ohms.
+    pub resistance_ohms: f32,
+    /// Divider supply voltage in Volts.
+    pub supply_voltage: f32,
+}
+
+impl Divider {
+    /// Thermistor resistance in Ohms for the voltage measured across the fixed resistor.
+    pub fn thermistor_resistance(&self, voltage: Volts) -> f64 {
+        // R = (voltage divider resistor [Ohms]) * (Vss [V] / voltage [V] - 1)
+        (self.resistance_ohms * (self.supply_voltage / voltage.volts() - 1.0)).into()
+    }
+}
+
+/// An MCP342x channel with a thermistor attached, converting ADC voltage to °C.
+///
+/// The ADC should already be configured (channel, gain, resolution); each reading
+/// performs a one-shot conversion.
+pub struct ThermistorChannel<I2C> {
+    adc: MCP342x<I2C>,
+    divider: Divider,
+    coefficients: SteinhartHart,
+}
+
+impl<I2C, E> ThermistorChannel<I2C>
+where
+    I2C: I2c<Error = E>,
+    I2C::Error: std::error::Error + 'static,
+{
+    /// Wrap a configured ADC with the divider and Steinhart–Hart coefficients.
+    pub fn new(adc: MCP342x<I2C>, divider: Divider, coefficients: SteinhartHart) -> Self {
+        Self { adc, divider, coefficients }
+    }
+
+    /// Convert a measured divider voltage to a temperature in degrees Celsius.
+    pub fn voltage_to_celsius(&self, voltage: Volts) -> f32 {
+        let resistance = self.divider.thermistor_resistance(voltage);
+        self.coefficients.temperature_c(resistance) as f32
+    }
+
+    /// Run a one-shot conversion and return the divider voltage.
+    pub fn read_voltage(&mut self) -> Result<Volts, Error<E>> {
+        self.adc.convert_and_read(true)
+    }
+
+    /// Run a one-shot conversion and return the temperature in degrees Celsius.
+    pub fn read_temperature(&mut self) -> Result<f32, Error<E>> {
+        let voltage = self.read_voltage()?;
+        Ok(self.voltage_to_celsius(voltage))
+    }
+
+    /// Mutable access to the underlying ADC, e.g. to change gain or resolution.
+    pub fn adc_mut(&mut self) -> &mut MCP342x<I2C> {
+        &mut self.adc
+    }
+
+    /// Release the underlying ADC.
+    pub fn release(self) -> MCP342x<I2C> {
+        self.adc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steinhart_hart_10k_ntc_at_25c() {
+        // Typical 10k NTC (B = 3950) coefficients
+        let sh = SteinhartHart { a: 1.009249522e-3, b: 2.378405444e-4, c: 2.019202697e-7 };
+        let t = sh.temperature_c(10_000.0);
+        assert!((t - 25.0).abs() < 0.5, "Expected ~25 °C, got {}", t);
+    }
+
+    #[test]
+    fn divider_midpoint_equals_fixed_resistor() {
+        let divider = Divider { resistance_ohms: 3200.0, supply_voltage: 5.0 };
+        let r = divider.thermistor_resistance(Volts(2.5));
+        assert!((r - 3200.0).abs() < 1e-3);
+    }
+}
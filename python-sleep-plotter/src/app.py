@@ -43,6 +43,7 @@ def get_data():
     "thermistor_temp", 
     "image_path", 
     "image_motion",
+    "image_motion_live",
     "mmwave_presence",
     "mmwave_movement",
     "mmwave_heart_rate_bpm",
@@ -85,6 +86,11 @@ def get_data():
         traceback.print_exc()
         return jsonify({"error": str(e)}), 500
     
+    # Plot the live motion of sessions whose motion hasn't been analyzed offline
+    if "image_motion_live" in data:
+        live_motion = data.pop("image_motion_live")
+        data.setdefault("image_motion", live_motion)
+
     # Convert to Fahrenheit if the data is present
     if "temperature" in data:
         data["temperature"] = [None if x is None else (x * 9 / 5 + 32) for x in data["temperature"]]
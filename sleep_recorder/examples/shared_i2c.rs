@@ -0,0 +1,25 @@
+use bme280::i2c::BME280;
+use ens160_aq::Ens160;
+use linux_embedded_hal::Delay;
+use mcp342x::{Channel, Gain, MCP342x, Resolution};
+use sleep_recorder::sensor::shared_i2c;
+
+/// MCP342x, BME280, and ENS160 on /dev/i2c-1, sharing a single opened bus.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut adc = MCP342x::new(shared_i2c("/dev/i2c-1")?, 0x68);
+    adc.set_channel(Channel::Ch3);
+    adc.set_gain(Gain::G1);
+    adc.set_resolution(Resolution::Bits16);
+
+    let mut bme280 = BME280::new_primary(shared_i2c("/dev/i2c-1")?);
+    bme280.init(&mut Delay)?;
+
+    let mut ens = Ens160::new_secondary_address(shared_i2c("/dev/i2c-1")?, Delay);
+    ens.initialize().map_err(|e| format!("ENS160 initialization error: {:?}", e))?;
+
+    let env = bme280.measure(&mut Delay)?;
+    println!("BME280: {:.1} °C, {:.1} %RH", env.temperature, env.humidity);
+    println!("ADC: {}", adc.convert_and_read(true)?);
+    println!("ENS160 initialized on the same bus");
+    Ok(())
+}
@@ -0,0 +1,212 @@
+//! Outputs the application can switch during a session: relays for a fan, a humidifier, or an IR
+//! illuminator. Together with the sensors this closes the loop for environmental control at night.
+//!
+//! Actuators are configured in [`Config::actuators`] and switched through the
+//! [`ActuatorHandle`] returned by [`Recorder::actuators`](crate::Recorder::actuators). Every state
+//! change is logged as an [`ActuatorEvent`] in the session's `actuator_event_*` datasets, and all
+//! actuators are switched off when the session ends.
+
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::config::{ActuatorConfig, Config};
+use crate::data::ActuatorEvent;
+
+/// An output that can be switched on and off.
+pub trait Actuator: Send {
+    /// Name of the actuator, as configured.
+    fn name(&self) -> &str;
+
+    /// Switches the output on or off.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output could not be set; its state is then unknown.
+    fn set(&mut self, on: bool) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// A relay (or MOSFET) driven by a GPIO line.
+pub struct GpioRelay {
+    name: String,
+    line: LineHandle,
+    active_low: bool,
+}
+
+impl GpioRelay {
+    /// Requests the configured GPIO line as an output, initially off.
+    pub fn from_config(config: &ActuatorConfig) -> Result<Self, Box<dyn Error>> {
+        let mut chip = Chip::new(&config.chip)?;
+        let line = chip.get_line(config.line)?
+            .request(LineRequestFlags::OUTPUT, config.active_low as u8, "sleep-recorder-actuator")?;
+        Ok(Self { name: config.name.clone(), line, active_low: config.active_low })
+    }
+}
+
+impl Actuator for GpioRelay {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set(&mut self, on: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.line.set_value((on != self.active_low) as u8)?;
+        Ok(())
+    }
+}
+
+/// The actuators of a session and their current states. All actuators start off.
+pub struct Actuators {
+    actuators: Vec<(Box<dyn Actuator>, bool)>,
+}
+
+impl Actuators {
+    /// Creates the actuators from a list, all assumed to be off.
+    pub fn new(actuators: Vec<Box<dyn Actuator>>) -> Self {
+        Self { actuators: actuators.into_iter().map(|a| (a, false)).collect() }
+    }
+
+    /// Creates the actuators configured in `config.actuators`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a GPIO line cannot be requested.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn Error>> {
+        if config.simulation {
+            #[cfg(feature = "simulation")]
+            return Ok(crate::simulation::actuators(config));
+            #[cfg(not(feature = "simulation"))]
+            return Err("Simulated actuators require the simulation feature".into());
+        }
+        let mut actuators: Vec<Box<dyn Actuator>> = Vec::new();
+        for actuator in &config.actuators {
+            let relay = GpioRelay::from_config(actuator)
+                .map_err(|e| format!("Failed to set up actuator {}: {}", actuator.name, e))?;
+            actuators.push(Box::new(relay));
+        }
+        Ok(Self::new(actuators))
+    }
+
+    /// Names of the actuators.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.actuators.iter().map(|(a, _)| a.name())
+    }
+
+    /// Current state of the actuator `name`, or `None` if there is no such actuator.
+    pub fn is_on(&self, name: &str) -> Option<bool> {
+        self.actuators.iter().find(|(a, _)| a.name() == name).map(|(_, on)| *on)
+    }
+
+    /// Switches the actuator `name` and returns the resulting event, or `None` if it already was
+    /// in that state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such actuator or its output could not be set.
+    pub fn set(&mut self, name: &str, on: bool) -> Result<Option<ActuatorEvent>, Box<dyn Error>> {
+        let (actuator, state) = self.actuators.iter_mut()
+            .find(|(a, _)| a.name() == name)
+            .ok_or_else(|| format!("Unknown actuator {:?}", name))?;
+        if *state == on {
+            return Ok(None);
+        }
+        actuator.set(on).map_err(|e| format!("Failed to switch actuator {}: {}", name, e))?;
+        *state = on;
+        info!("Actuator {} switched {}", name, if on { "on" } else { "off" });
+        let timestamp_s = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Ok(Some(ActuatorEvent { timestamp_s, name: name.to_string(), on }))
+    }
+
+    /// Switches all actuators off, e.g. at the end of a session. Failures are logged.
+    pub fn all_off(&mut self) -> Vec<ActuatorEvent> {
+        let names: Vec<String> = self.names().map(str::to_string).collect();
+        names.iter()
+            .filter_map(|name| self.set(name, false).unwrap_or_else(|e| {
+                warn!("{}", e);
+                None
+            }))
+            .collect()
+    }
+}
+
+/// A request to switch an actuator, sent through an [`ActuatorHandle`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActuatorCommand {
+    /// Name of the actuator.
+    pub name: String,
+    /// Requested state.
+    pub on: bool,
+}
+
+/// Cloneable handle for switching the actuators of a running session.
+///
+/// Commands are queued and applied by the session in order; commands sent before the session
+/// starts are applied once it has set up the actuators.
+#[derive(Clone, Debug)]
+pub struct ActuatorHandle {
+    commands: mpsc::UnboundedSender<ActuatorCommand>,
+}
+
+impl ActuatorHandle {
+    /// Creates a handle and the receiving end of its commands.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ActuatorCommand>) {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        (Self { commands }, receiver)
+    }
+
+    /// Requests switching the actuator `name` on or off.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recorder has been dropped.
+    pub fn set(&self, name: &str, on: bool) -> Result<(), Box<dyn Error>> {
+        self.commands.send(ActuatorCommand { name: name.to_string(), on })
+            .map_err(|_| "The recorder is no longer running".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use test_log::test;
+
+    /// Actuator recording the values it was set to.
+    struct MockActuator {
+        name: String,
+        values: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl Actuator for MockActuator {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn set(&mut self, on: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.values.lock().unwrap().push(on);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_state_changes_produce_events() {
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let fan = MockActuator { name: "fan".to_string(), values: values.clone() };
+        let mut actuators = Actuators::new(vec![Box::new(fan)]);
+        assert_eq!(actuators.is_on("fan"), Some(false));
+
+        let event = actuators.set("fan", true).expect("Failed to switch fan").expect("Expected an event");
+        assert_eq!((event.name.as_str(), event.on), ("fan", true));
+        assert_eq!(actuators.is_on("fan"), Some(true));
+        // Already on: no event, and the output is not touched again
+        assert!(actuators.set("fan", true).expect("Failed to switch fan").is_none());
+        assert!(actuators.set("heater", true).is_err());
+
+        let events = actuators.all_off();
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].on);
+        assert_eq!(*values.lock().unwrap(), vec![true, false]);
+    }
+}
@@ -0,0 +1,160 @@
+//! Offline analysis pipeline for recorded sessions.
+//!
+//! The `Pipeline` runs the individual analysis passes (audio volume and noise statistics, image
+//! motion, and optionally audio respiration, event clips, silent audio trimming, and image archival) over a session group, so callers don't need to know which functions implement
+//! each pass.
+
+use std::error::Error;
+
+use tracing::info;
+
+use crate::audio_analysis::{
+    analyze_audio_entries_with_memory, extract_event_clips, trim_silent_audio, EventClips, SilentAudio, DEFAULT_ANALYSIS_MEMORY_MIB,
+};
+use crate::audio_respiration::estimate_audio_respiration;
+use crate::data::upgrade_session;
+use crate::encryption::Key;
+use crate::image_analysis::{analyze_motion, archive_images, ImageArchive};
+use crate::noise_stats::{write_noise_stats, DEFAULT_NOISE_EVENT_DB};
+
+/// Offline analysis of a recorded session. All analysis passes are enabled by default; audio
+/// respiration, event clips, silent audio trimming, and image archival are not.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::analysis::Pipeline;
+/// use sleep_recorder::ImageArchive;
+/// Pipeline::new()
+///     .with_audio(false)
+///     .with_image_archive(Some(ImageArchive::Reencode { quality: 60 }))
+///     .run("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31")
+///     .expect("Failed to analyze session");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    audio: bool,
+    motion: bool,
+    respiration: bool,
+    event_clips: Option<EventClips>,
+    silent_audio: Option<SilentAudio>,
+    image_archive: Option<ImageArchive>,
+    key: Option<Key>,
+    audio_memory_mib: u64,
+    noise_event_db: f32,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self { audio: true, motion: true, respiration: false, event_clips: None, silent_audio: None, image_archive: None, key: None, audio_memory_mib: DEFAULT_ANALYSIS_MEMORY_MIB, noise_event_db: DEFAULT_NOISE_EVENT_DB }
+    }
+}
+
+impl Pipeline {
+    /// Creates a pipeline with all analysis passes enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables or disables the audio volume pass (fills `audio_rms_db` for each recording, then
+    /// stores the session's noise statistics, see [`crate::noise_stats`]).
+    pub fn with_audio(mut self, enabled: bool) -> Self {
+        self.audio = enabled;
+        self
+    }
+
+    /// Sets how much memory (MiB) the audio pass decodes recordings into at once, analyzing as
+    /// many of them in parallel as fit. Defaults to [`DEFAULT_ANALYSIS_MEMORY_MIB`].
+    pub fn with_audio_memory_mib(mut self, memory_mib: u64) -> Self {
+        self.audio_memory_mib = memory_mib;
+        self
+    }
+
+    /// Sets the level (dBFS) from which the noise statistics count a noise event. Defaults to
+    /// [`DEFAULT_NOISE_EVENT_DB`].
+    pub fn with_noise_event_db(mut self, threshold_db: f32) -> Self {
+        self.noise_event_db = threshold_db;
+        self
+    }
+
+    /// Enables or disables the audio respiration pass (see
+    /// [`estimate_audio_respiration`]), which decodes the recordings again.
+    pub fn with_respiration(mut self, enabled: bool) -> Self {
+        self.respiration = enabled;
+        self
+    }
+
+    /// Cuts clips of the recordings around events after the audio pass (see
+    /// [`extract_event_clips`](crate::audio_analysis::extract_event_clips)), or cuts none with
+    /// `None`. Clips are cut before silent recordings are trimmed.
+    pub fn with_event_clips(mut self, clips: Option<EventClips>) -> Self {
+        self.event_clips = clips;
+        self
+    }
+
+    /// Trims the recordings that stayed silent after the audio pass (see
+    /// [`trim_silent_audio`](crate::audio_analysis::trim_silent_audio)), or leaves them as they
+    /// are with `None`. Only recordings analyzed, in this or an earlier run, are trimmed.
+    pub fn with_silent_audio(mut self, silent: Option<SilentAudio>) -> Self {
+        self.silent_audio = silent;
+        self
+    }
+
+    /// Enables or disables the image motion pass (writes the `image_motion` dataset).
+    pub fn with_motion(mut self, enabled: bool) -> Self {
+        self.motion = enabled;
+        self
+    }
+
+    /// Shrinks the stored images after the motion pass (see
+    /// [`archive_images`](crate::image_analysis::archive_images)), or leaves them as they are with
+    /// `None`. Archival requires the motion to be analyzed, in this or an earlier run.
+    pub fn with_image_archive(mut self, archive: Option<ImageArchive>) -> Self {
+        self.image_archive = archive;
+        self
+    }
+
+    /// Decrypts encrypted images and recordings (see [`crate::encryption`]) with `key`. Without a
+    /// key, the passes fail on encrypted files.
+    pub fn with_key(mut self, key: Option<Key>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Runs the enabled passes over `group_name` in the HDF5 file at `data_path/file_name`,
+    /// first upgrading the session to the current schema version.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session cannot be upgraded, or the first error from any enabled
+    /// pass; later passes are not run.
+    pub fn run(&self, data_path: &str, file_name: &str, group_name: &str) -> Result<(), Box<dyn Error>> {
+        upgrade_session(data_path, file_name, group_name)?;
+        if self.audio {
+            info!("Running audio analysis for {group_name}");
+            analyze_audio_entries_with_memory(data_path, file_name, group_name, self.key.as_ref(), self.audio_memory_mib)?;
+            write_noise_stats(data_path, file_name, group_name, self.noise_event_db)?;
+        }
+        if self.respiration {
+            info!("Estimating audio respiration for {group_name}");
+            estimate_audio_respiration(data_path, file_name, group_name, self.key.as_ref())?;
+        }
+        if let Some(clips) = &self.event_clips {
+            info!("Cutting event clips of {group_name}");
+            extract_event_clips(data_path, file_name, group_name, clips)?;
+        }
+        if let Some(silent) = self.silent_audio {
+            info!("Trimming silent audio of {group_name}");
+            trim_silent_audio(data_path, file_name, group_name, silent)?;
+        }
+        if self.motion {
+            info!("Running motion analysis for {group_name}");
+            analyze_motion(data_path, file_name, group_name, self.key.as_ref())?;
+        }
+        if let Some(archive) = self.image_archive {
+            info!("Archiving images of {group_name}");
+            archive_images(data_path, file_name, group_name, archive)?;
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,40 @@
-use hdf5::{File as H5File, types::VarLenArray};
-use minimp3::{Decoder, Frame, Error as Minimp3Error};
-use std::{error::Error, fs::File};
-use tracing::info;
+use hdf5::{File as H5File, types::{VarLenArray, VarLenUnicode}};
+use rayon::prelude::*;
+use std::{collections::HashSet, error::Error, fs::{self, File}, io::{Cursor, ErrorKind, Write}, ops::Range, path::Path, process::Command, str::FromStr, time::Duration};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::DecoderOptions,
+    errors::Error as SymphoniaError,
+    formats::FormatOptions,
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+use tracing::{info, warn};
 
-use crate::data::H5AudioMetadata;
+use crate::config::{AudioWeighting, CompressionConfig};
+use crate::data::{session_path, AppendableColumn, Event, H5AudioMetadata, H5Event, SessionReader};
+use crate::encryption::{self, Key};
+use crate::sound_events::{SoundEvent, SoundEventDetector, SOUND_EVENT_CATEGORY};
 
 /// Analyzes audio entries in an HDF5 file.
 /// 
-/// This function reads audio data from an HDF5 file, decodes the audio files, computes the volume in dBFS,
-/// and updates the HDF5 file with the computed volume and timestamps.
+/// This function reads the audio entries of a session with a [`SessionReader`], decodes the audio files, computes the volume in dBFS
+/// (A-weighted if the session's [`audio_weighting`](crate::data::SessionMetadata::audio_weighting) is), and updates the HDF5 file with the computed volume and timestamps. Entries whose volume was already computed (see
+/// [`H5AudioMetadata::is_analyzed`]), live (see [`StreamAnalyzer`]) or by an earlier run, are skipped.
+///
+/// The levels of the frequency [`BANDS`] are computed per window as well (see [`BandMeter`]), and
+/// appended to the band datasets with their times in [`BAND_TIMES_DATASET`], for the entries
+/// without band levels yet, and the sound events detected in the entries whose volume is computed
+/// are appended to the `events` dataset (see [`crate::sound_events`]).
+///
+/// Recordings are decoded in parallel, in at most [`DEFAULT_ANALYSIS_MEMORY_MIB`] MiB (see
+/// [`analyze_audio_entries_with_memory`]).
+///
+/// A recording that cannot be decoded (e.g. missing, or cut short by a crash before its first
+/// frame) is skipped with a warning, and the error is logged in the `events` dataset with the
+/// category [`AUDIO_ERROR_CATEGORY`] at the recording's start time, once per recording. It is
+/// tried again by later runs. Deleted recordings (with an empty path) are skipped.
 ///
 /// # Arguments
 /// * `data_path` - The path to the directory containing the HDF5 file.
@@ -26,118 +52,1050 @@ use crate::data::H5AudioMetadata;
 /// If any of the following operations fail, an error is returned:
 /// * Opening the HDF5 file.
 /// * Reading the audio dataset.
-/// * Decoding the audio files.
 /// * Writing the computed volume and timestamps back to the HDF5 file.
 ///
-#[tracing::instrument()]
 pub fn analyze_audio_entries(data_path: &str, file_name: &str, group_name: &str) -> Result<(), Box<dyn Error>> {
-    const WINDOW_SIZE_S: usize = 5;
+    analyze_audio_entries_with_key(data_path, file_name, group_name, None)
+}
+
+/// Like [`analyze_audio_entries`], decrypting encrypted recordings (see [`crate::encryption`]) in
+/// memory with `key`.
+///
+/// # Errors
+///
+/// As [`analyze_audio_entries`]. Encrypted recordings that `key` is `None` for or cannot decrypt
+/// are skipped as recordings that cannot be decoded are.
+pub fn analyze_audio_entries_with_key(data_path: &str, file_name: &str, group_name: &str, key: Option<&Key>) -> Result<(), Box<dyn Error>> {
+    analyze_audio_entries_with_memory(data_path, file_name, group_name, key, DEFAULT_ANALYSIS_MEMORY_MIB)
+}
+
+/// Like [`analyze_audio_entries_with_key`], decoding recordings in parallel as long as their
+/// decoded audio fits in `max_memory_mib` MiB (a longer recording is still analyzed, alone). The
+/// results are written to the HDF5 file from the calling thread.
+///
+/// # Errors
+///
+/// As [`analyze_audio_entries_with_key`]. The entries analyzed before the failed one are written.
+#[tracing::instrument(skip(key))]
+pub fn analyze_audio_entries_with_memory(
+    data_path: &str,
+    file_name: &str,
+    group_name: &str,
+    key: Option<&Key>,
+    max_memory_mib: u64,
+) -> Result<(), Box<dyn Error>> {
     info!("Analyzing audio entries...");
-    let file = H5File::append(data_path.to_string() + "/" + file_name)?;
+    let session = SessionReader::open(data_path, file_name, group_name)?;
+    let audio_data = session.audio_entries()?;
+    let weighting_name = session.metadata()?.audio_weighting;
+    let weighting = AudioWeighting::from_name(&weighting_name)
+        .ok_or_else(|| format!("Unknown audio weighting {:?} of session {}", weighting_name, group_name))?;
+    // Recordings whose decoding error is already logged
+    let mut failed_starts: HashSet<u64> = session.events()?
+        .into_iter()
+        .filter(|event| event.category == AUDIO_ERROR_CATEGORY)
+        .map(|event| event.timestamp_s)
+        .collect();
+    drop(session);
+    info!("Analyzing {} audio entries", audio_data.len());
 
+    let file = H5File::append(session_path(data_path, file_name, group_name))?;
     let group = file.group(group_name)?;
+    let audio_column = AppendableColumn::<H5AudioMetadata>::open(&group, "audio")?;
+    // Created here for sessions recorded before band levels were stored
+    let band_times = AppendableColumn::<u64>::open_or_create(&group, BAND_TIMES_DATASET, &CompressionConfig::default())?;
+    let band_columns = BANDS.iter()
+        .map(|band| AppendableColumn::<f32>::open_or_create(&group, band.dataset, &CompressionConfig::default()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let event_column = AppendableColumn::<H5Event>::open_or_create(&group, "events", &CompressionConfig::default())?;
+    // The band windows of a recording start at its start time
+    let mut analyzed_band_starts: HashSet<u64> = band_times.read()?.into_iter().collect();
+
+    let jobs: Vec<AnalysisJob> = audio_data.iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            if entry.path.is_empty() {
+                return None;
+            }
+            let job = AnalysisJob {
+                index,
+                path: entry.path.to_string(),
+                start_time_s: entry.start_time_s,
+                duration_s: entry.duration_s,
+                bands: analyzed_band_starts.insert(entry.start_time_s),
+                rms: !entry.is_analyzed(),
+            };
+            if !job.bands && !job.rms {
+                info!("Skipping analyzed entry {}", job.path);
+                return None;
+            }
+            Some(job)
+        })
+        .collect();
+
+    for batch in batches(jobs, max_memory_mib * 1024 * 1024, rayon::current_num_threads()) {
+        let results: Vec<Result<EntryLevels, String>> = batch.par_iter().map(|job| analyze_entry(job, key, weighting)).collect();
+        for (job, result) in batch.iter().zip(results) {
+            let levels = match result {
+                Ok(levels) => levels,
+                Err(e) => {
+                    warn!("Skipping recording: {}", e);
+                    if failed_starts.insert(job.start_time_s) {
+                        let event = Event { timestamp_s: job.start_time_s, category: AUDIO_ERROR_CATEGORY.to_string(), text: e };
+                        event_column.append(&[H5Event::from(&event)])?;
+                    }
+                    continue;
+                }
+            };
+            let times = |count: usize| -> Vec<u64> {
+                (0..count as u64).map(|i| job.start_time_s + i * ANALYSIS_WINDOW_S).collect()
+            };
+            if let Some(band_levels) = levels.bands {
+                band_times.append(&times(band_levels.len()))?;
+                for (band, column) in band_columns.iter().enumerate() {
+                    column.append(&band_levels.iter().map(|level| level[band]).collect::<Vec<f32>>())?;
+                }
+            }
+            if let Some(volume_db) = levels.rms {
+                let timestamps = times(volume_db.len());
+                info!("Processed {} samples from {}", volume_db.len(), job.path);
+                info!("Timestamps: {:?}", timestamps);
+                info!("Volume dB: {:?}", volume_db);
+                let updated_entry = H5AudioMetadata {
+                    audio_rms_db: VarLenArray::from_slice(&volume_db),
+                    audio_rms_t_s: VarLenArray::from_slice(&timestamps),
+                    ..audio_data[job.index].clone()
+                };
+                audio_column.write_at(job.index, &[updated_entry])?;
+            }
+            let events: Vec<H5Event> = levels.events.iter().map(|event| H5Event::from(&event.to_event())).collect();
+            event_column.append(&events)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// How the recordings that stayed silent throughout are stored once analyzed (see
+/// [`trim_silent_audio`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SilentAudio {
+    /// A recording is silent if the RMS level of each of its windows is below this, in dBFS.
+    pub threshold_db: f32,
+    pub action: SilentAudioAction,
+}
+
+/// What [`trim_silent_audio`] does with a silent recording.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SilentAudioAction {
+    /// Delete the recording, keeping clips of the windows within `keep_s` seconds of the sound
+    /// events detected in it (see [`crate::sound_events`]), in the recording's format.
+    Delete { keep_s: u64 },
+    /// Re-encode the recording as Opus at this bitrate (e.g. 16 kbps), if that makes it smaller.
+    /// The entry references the `.opus` file instead.
+    Reencode { bitrate_kbps: u32 },
+}
+
+/// Shrinks the recordings of `group_name` in the HDF5 file at `data_path/file_name` that are
+/// silent, as selected by `silent`, and updates their `audio` entries.
+///
+/// Most recordings of a night are near silent, but they dominate the disk usage of the audio.
+/// With [`SilentAudioAction::Delete`], the entry of a recording is split at the clips kept from
+/// it: the entry of each clip references the clip, the entries of the audio in between have an
+/// empty path, and each keeps its RMS windows, so the levels of the night don't change. The
+/// entries start at window times, so the band levels aren't computed again.
+///
+/// Recordings that haven't been analyzed yet, encrypted recordings, and deleted recordings are
+/// left as they are.
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be read or written, or if a recording cannot be cut or
+/// re-encoded (`ffmpeg` must be installed, except to cut WAV recordings). The recordings are only
+/// deleted once the files replacing them and the entries are written.
+#[tracing::instrument()]
+pub fn trim_silent_audio(data_path: &str, file_name: &str, group_name: &str, silent: SilentAudio) -> Result<(), Box<dyn Error>> {
+    let session = SessionReader::open(data_path, file_name, group_name)?;
+    let entries = session.audio_entries()?;
+    let event_times: Vec<u64> = session.events()?
+        .into_iter()
+        .filter(|event| event.category == SOUND_EVENT_CATEGORY)
+        .map(|event| event.timestamp_s)
+        .collect();
+    drop(session);
+
+    let mut updated = Vec::with_capacity(entries.len());
+    let mut replaced = Vec::new();
+    let mut saved_bytes = 0;
+    for entry in entries {
+        let path = entry.path.to_string();
+        if path.is_empty() || !entry.is_analyzed() || !entry.audio_rms_db.iter().all(|db| *db < silent.threshold_db) {
+            updated.push(entry);
+            continue;
+        }
+        if encryption::is_encrypted_path(&path) {
+            warn!("Skipping encrypted recording {}", path);
+            updated.push(entry);
+            continue;
+        }
+        let original_size = fs::metadata(&path)?.len();
+        match silent.action {
+            SilentAudioAction::Delete { keep_s } => {
+                let end_s = entry.start_time_s + entry.duration_s;
+                let events: Vec<u64> = event_times.iter().copied().filter(|t| (entry.start_time_s..end_s).contains(t)).collect();
+                let mut kept_bytes = 0;
+                for piece in silent_pieces(entry.start_time_s, entry.duration_s, &entry.audio_rms_t_s, &events, keep_s) {
+                    let piece_path = if piece.kept {
+                        let clip_path = clip_path(&path, piece.start_time_s);
+                        cut_clip(&path, &clip_path, piece.start_time_s - entry.start_time_s, piece.duration_s)?;
+                        kept_bytes += fs::metadata(&clip_path)?.len();
+                        clip_path
+                    } else {
+                        String::new()
+                    };
+                    updated.push(H5AudioMetadata {
+                        start_time_s: piece.start_time_s,
+                        duration_s: piece.duration_s,
+                        path: VarLenUnicode::from_str(&piece_path)?,
+                        audio_rms_db: VarLenArray::from_slice(&entry.audio_rms_db[piece.windows.clone()]),
+                        audio_rms_t_s: VarLenArray::from_slice(&entry.audio_rms_t_s[piece.windows]),
+                    });
+                }
+                saved_bytes += original_size.saturating_sub(kept_bytes);
+            }
+            SilentAudioAction::Reencode { bitrate_kbps } => {
+                let reencoded = Path::new(&path).with_extension(format!("{}k.opus", bitrate_kbps)).to_string_lossy().into_owned();
+                run_ffmpeg(&path, &reencoded, &["-c:a", "libopus", "-b:a", &format!("{}k", bitrate_kbps)])?;
+                let size = fs::metadata(&reencoded)?.len();
+                if size >= original_size {
+                    fs::remove_file(&reencoded)?;
+                    updated.push(entry);
+                    continue;
+                }
+                saved_bytes += original_size - size;
+                updated.push(H5AudioMetadata { path: VarLenUnicode::from_str(&reencoded)?, ..entry });
+            }
+        }
+        replaced.push(path);
+    }
+
+    let file = H5File::append(session_path(data_path, file_name, group_name))?;
+    AppendableColumn::<H5AudioMetadata>::open(&file.group(group_name)?, "audio")?.replace(&updated)?;
+    file.flush()?;
+    for path in &replaced {
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Failed to remove silent recording {}: {}", path, e);
+        }
+    }
+    info!("Trimmed {} silent recordings of {}, saving {} bytes", replaced.len(), group_name, saved_bytes);
+    Ok(())
+}
+
+/// Category of the events referencing the clips cut by [`extract_event_clips`]: the timestamp is
+/// the one of the clipped event, the text the path of the clip (empty once the audio was deleted
+/// by [`retention::prune`](crate::retention::prune)).
+pub const EVENT_CLIP_CATEGORY: &str = "event_clip";
+
+/// Which events [`extract_event_clips`] cuts clips around.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventClips {
+    /// Categories of the events clipped.
+    pub categories: Vec<String>,
+    /// Audio kept before and after each event, in seconds.
+    pub margin_s: u64,
+}
+
+impl EventClips {
+    /// Clips of `margin_s` seconds before and after the sound events (see
+    /// [`crate::sound_events`]) and, with the `ml` feature, the changes of sound class (see the
+    /// `sound_classifier` module).
+    pub fn new(margin_s: u64) -> Self {
+        #[cfg_attr(not(feature = "ml"), allow(unused_mut))]
+        let mut categories = vec![SOUND_EVENT_CATEGORY.to_string()];
+        #[cfg(feature = "ml")]
+        categories.push(crate::sound_classifier::SOUND_CLASS_CATEGORY.to_string());
+        Self { categories, margin_s }
+    }
+}
+
+/// Cuts clips of the recordings of `group_name` in the HDF5 file at `data_path/file_name` around
+/// the events selected by `clips`, into the `events/` directory of the session, so that an event
+/// can be listened to without seeking through its recording. Returns the number of clips cut.
+///
+/// Each clip is referenced by an event with the category [`EVENT_CLIP_CATEGORY`] per event it
+/// covers. Events close enough to share a clip get one clip, extended to cover them all; events
+/// that already have a clip, from an earlier run, are skipped. Clips are cut from a single
+/// recording, so an event near the start or end of a recording gets a shorter clip. Events outside
+/// the recordings, or in deleted or encrypted recordings, get no clip.
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be read or written, or if a recording cannot be cut
+/// (`ffmpeg` must be installed, except to cut WAV recordings). The clips cut before the failure
+/// are referenced.
+#[tracing::instrument()]
+pub fn extract_event_clips(data_path: &str, file_name: &str, group_name: &str, clips: &EventClips) -> Result<usize, Box<dyn Error>> {
+    let session = SessionReader::open(data_path, file_name, group_name)?;
+    let entries = session.audio_entries()?;
+    let events = session.events()?;
+    drop(session);
+
+    let clipped: HashSet<u64> = events.iter()
+        .filter(|event| event.category == EVENT_CLIP_CATEGORY)
+        .map(|event| event.timestamp_s)
+        .collect();
+    let mut event_times: Vec<u64> = events.iter()
+        .filter(|event| clips.categories.contains(&event.category) && !clipped.contains(&event.timestamp_s))
+        .map(|event| event.timestamp_s)
+        .collect();
+    event_times.sort_unstable();
+    event_times.dedup();
+    let recordings: Vec<(String, u64, u64)> = entries.into_iter()
+        .map(|entry| (entry.path.to_string(), entry.start_time_s, entry.duration_s))
+        .filter(|(path, _, _)| !path.is_empty() && !encryption::is_encrypted_path(path))
+        .collect();
+    let spans: Vec<(u64, u64)> = recordings.iter().map(|&(_, start_time_s, duration_s)| (start_time_s, duration_s)).collect();
+    let planned = event_clips(&spans, &event_times, clips.margin_s);
+    if planned.is_empty() {
+        return Ok(0);
+    }
+
+    let directory = Path::new(data_path).join(group_name).join("events");
+    fs::create_dir_all(&directory)?;
+    let file = H5File::append(session_path(data_path, file_name, group_name))?;
+    let event_column = AppendableColumn::<H5Event>::open_or_create(&file.group(group_name)?, "events", &CompressionConfig::default())?;
+    for clip in &planned {
+        let (path, start_time_s, _) = &recordings[clip.recording];
+        let name = Path::new(&clip_path(path, clip.start_time_s)).file_name().map(|name| name.to_owned()).unwrap_or_default();
+        let event_clip_path = directory.join(name).to_string_lossy().into_owned();
+        cut_clip(path, &event_clip_path, clip.start_time_s - start_time_s, clip.duration_s)?;
+        let references: Vec<H5Event> = clip.event_times.iter()
+            .map(|&timestamp_s| H5Event::from(&Event {
+                timestamp_s,
+                category: EVENT_CLIP_CATEGORY.to_string(),
+                text: event_clip_path.clone(),
+            }))
+            .collect();
+        event_column.append(&references)?;
+    }
+    file.flush()?;
+    info!("Cut {} clips around {} events of {}", planned.len(), event_times.len(), group_name);
+    Ok(planned.len())
+}
+
+/// Category of the events logging the recordings [`analyze_audio_entries`] couldn't decode; the
+/// text is the error.
+pub const AUDIO_ERROR_CATEGORY: &str = "audio_error";
+
+/// Memory the offline analysis decodes recordings into at most at once by default, in MiB.
+pub const DEFAULT_ANALYSIS_MEMORY_MIB: u64 = 512;
+
+/// Estimated memory taken by analyzing a second of a recording: its decoded 48 kHz samples.
+const ANALYSIS_BYTES_PER_S: u64 = 48_000 * 2;
+
+/// An audio entry to analyze, with what it lacks.
+#[derive(Clone, Debug, PartialEq)]
+struct AnalysisJob {
+    /// Index of the entry in the `audio` dataset.
+    index: usize,
+    path: String,
+    start_time_s: u64,
+    duration_s: u64,
+    /// Whether the band levels are computed.
+    bands: bool,
+    /// Whether the RMS levels are computed.
+    rms: bool,
+}
+
+/// The levels and sound events computed for an [`AnalysisJob`].
+struct EntryLevels {
+    bands: Option<Vec<[f32; BANDS.len()]>>,
+    rms: Option<Vec<f32>>,
+    /// Detected along with the RMS levels.
+    events: Vec<SoundEvent>,
+}
+
+/// Decodes and analyzes the recording of `job`. Runs on the rayon pool, so the error is a
+/// `String` (`Box<dyn Error>` isn't `Send`).
+fn analyze_entry(job: &AnalysisJob, key: Option<&Key>, weighting: AudioWeighting) -> Result<EntryLevels, String> {
+    let DecodedAudio { samples, sample_rate } = decode_audio(&job.path, key)
+        .map_err(|e| format!("Failed to decode {}: {}", job.path, e))?;
+    let bands = job.bands
+        .then(|| BandMeter::new(sample_rate, Duration::from_secs(ANALYSIS_WINDOW_S)).push(&samples));
+    let mut events = Vec::new();
+    if job.rms {
+        let mut detector = SoundEventDetector::new(job.start_time_s, sample_rate);
+        events = detector.push(&samples);
+        events.extend(detector.finish());
+    }
+    let rms = job.rms.then(|| window_volume_dbfs(samples, sample_rate, ANALYSIS_WINDOW_S as usize, weighting));
+    Ok(EntryLevels { bands, rms, events })
+}
+
+/// Splits `jobs` into batches analyzed at once: at most `threads` recordings, taking at most
+/// `max_memory_bytes` together, but at least one recording.
+fn batches(jobs: Vec<AnalysisJob>, max_memory_bytes: u64, threads: usize) -> Vec<Vec<AnalysisJob>> {
+    let mut batches: Vec<Vec<AnalysisJob>> = Vec::new();
+    let mut batch_bytes: u64 = 0;
+    for job in jobs {
+        let bytes = job.duration_s.saturating_mul(ANALYSIS_BYTES_PER_S);
+        match batches.last_mut() {
+            Some(batch) if batch.len() < threads && batch_bytes.saturating_add(bytes) <= max_memory_bytes => {
+                batch_bytes += bytes;
+                batch.push(job);
+            }
+            _ => {
+                batch_bytes = bytes;
+                batches.push(vec![job]);
+            }
+        }
+    }
+    batches
+}
+
+/// A contiguous part of a silent recording, cut from it or deleted by [`trim_silent_audio`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SilentPiece {
+    start_time_s: u64,
+    duration_s: u64,
+    /// Indices of the RMS windows of the recording within the piece.
+    windows: Range<usize>,
+    /// Whether the piece is kept as a clip, being within `keep_s` of a sound event.
+    kept: bool,
+}
 
-    let audio_dataset = group.dataset("audio")?;
-    let audio_data = audio_dataset.read_1d::<H5AudioMetadata>()?;
-
-    info!("Audio dataset shape: {:?}, size: {:?}", audio_dataset.shape(), audio_dataset.size());
-
-
-    for (index, entry) in audio_data.iter().enumerate() {
-        let audio_path: String = entry.path.to_string();
-        let samples = decode_mp3(&audio_path)?;
-        let volume_db = window_volume_dbfs(samples, WINDOW_SIZE_S);
-        let timestamps = (0..volume_db.len() as u64)
-            .map(|i| entry.start_time_s + i * WINDOW_SIZE_S as u64)
-            .collect::<Vec<u64>>();
-        info!("Processed {} samples from {}", volume_db.len(), audio_path);
-        info!("Timestamps: {:?}", timestamps);
-        info!("Volume dB: {:?}", volume_db);
-        let updated_entry = H5AudioMetadata {
-            audio_rms_db: VarLenArray::from_slice(&volume_db),
-            audio_rms_t_s: VarLenArray::from_slice(&timestamps),
-            ..entry.clone()
+/// Splits the recording starting at `start_time_s`, with RMS windows starting at `window_times`,
+/// into runs of windows kept or not: those within `keep_s` seconds of one of `event_times`. The
+/// first piece starts with the recording and the last ends with it.
+fn silent_pieces(start_time_s: u64, duration_s: u64, window_times: &[u64], event_times: &[u64], keep_s: u64) -> Vec<SilentPiece> {
+    let kept = |window_start: u64| event_times.iter().any(|event| {
+        window_start <= event + keep_s && window_start + ANALYSIS_WINDOW_S + keep_s > *event
+    });
+    let mut pieces: Vec<SilentPiece> = Vec::new();
+    for (index, window_start) in window_times.iter().copied().enumerate() {
+        let window_kept = kept(window_start);
+        match pieces.last_mut() {
+            Some(piece) if piece.kept == window_kept => piece.windows.end = index + 1,
+            _ => pieces.push(SilentPiece {
+                start_time_s: if pieces.is_empty() { start_time_s } else { window_start },
+                duration_s: 0,
+                windows: index..index + 1,
+                kept: window_kept,
+            }),
+        }
+    }
+    let end_s = start_time_s + duration_s;
+    for index in 0..pieces.len() {
+        let piece_end_s = pieces.get(index + 1).map_or(end_s, |next| next.start_time_s);
+        pieces[index].duration_s = piece_end_s.saturating_sub(pieces[index].start_time_s);
+    }
+    pieces
+}
+
+/// A clip around events, cut from a recording by [`extract_event_clips`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct EventClip {
+    /// Index of the recording the clip is cut from.
+    recording: usize,
+    start_time_s: u64,
+    duration_s: u64,
+    /// The events in the clip.
+    event_times: Vec<u64>,
+}
+
+/// Plans the clips of `margin_s` seconds before and after each of `event_times` (sorted), within
+/// the `recordings` they fall in, given as start time and duration. An event within the clip of
+/// the event before it extends that clip instead of getting its own.
+fn event_clips(recordings: &[(u64, u64)], event_times: &[u64], margin_s: u64) -> Vec<EventClip> {
+    let mut clips: Vec<EventClip> = Vec::new();
+    for &event in event_times {
+        let Some(recording) = recordings.iter().position(|&(start, duration)| (start..start + duration).contains(&event)) else {
+            continue;
         };
+        let (start, duration) = recordings[recording];
+        let clip_end_s = (event + margin_s).min(start + duration);
+        match clips.last_mut() {
+            Some(clip) if clip.recording == recording && event < clip.start_time_s + clip.duration_s => {
+                clip.duration_s = clip_end_s.max(clip.start_time_s + clip.duration_s) - clip.start_time_s;
+                clip.event_times.push(event);
+            }
+            _ => {
+                let clip_start_s = event.saturating_sub(margin_s).max(start);
+                clips.push(EventClip {
+                    recording,
+                    start_time_s: clip_start_s,
+                    duration_s: clip_end_s - clip_start_s,
+                    event_times: vec![event],
+                });
+            }
+        }
+    }
+    clips
+}
 
-        audio_dataset.write_slice(&[updated_entry], (index..index+1,))?;
+/// Path of the clip of the recording at `path` starting at `start_time_s`, next to it in the same
+/// format, e.g. `audio_1745873251_clip_1745873291.wav`.
+fn clip_path(path: &str, start_time_s: u64) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_clip_{}.{}", stem, start_time_s, extension.to_string_lossy()),
+        None => format!("{}_clip_{}", stem, start_time_s),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Writes `duration_s` seconds of the recording at `path` from `offset_s` on to `clip_path`:
+/// natively for WAV, with `ffmpeg` otherwise.
+fn cut_clip(path: &str, clip_path: &str, offset_s: u64, duration_s: u64) -> Result<(), Box<dyn Error>> {
+    if Path::new(path).extension().and_then(|extension| extension.to_str()) != Some("wav") {
+        return run_ffmpeg(path, clip_path, &["-ss", &offset_s.to_string(), "-t", &duration_s.to_string(), "-c", "copy"]);
+    }
+    let mut reader = hound::WavReader::open(path)
+        .map_err(|e| format!("Failed to open WAV file {}: {}", path, e))?;
+    let spec = reader.spec();
+    let rate = spec.sample_rate as u64;
+    reader.seek((offset_s * rate).min(reader.duration() as u64) as u32)?;
+    let count = (duration_s * rate * spec.channels as u64) as usize;
+    let mut writer = hound::WavWriter::create(clip_path, spec)?;
+    match spec.sample_format {
+        hound::SampleFormat::Int => for sample in reader.samples::<i32>().take(count) {
+            writer.write_sample(sample?)?;
+        },
+        hound::SampleFormat::Float => for sample in reader.samples::<f32>().take(count) {
+            writer.write_sample(sample?)?;
+        },
     }
+    writer.finalize()?;
+    Ok(())
+}
 
+/// Converts the recording at `input` to `output` with `ffmpeg`, passing `arguments` between them.
+fn run_ffmpeg(input: &str, output: &str, arguments: &[&str]) -> Result<(), Box<dyn Error>> {
+    let status = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i", input])
+        .args(arguments)
+        .arg(output)
+        .status()
+        .map_err(|e| format!("Failed to run ffmpeg on {}: {}", input, e))?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed to convert {} to {}: {}", input, output, status).into());
+    }
     Ok(())
 }
 
-/// Decodes an MP3 file and returns the audio samples as a vector of i16.
-/// 
-/// This function uses the `minimp3` crate to decode the MP3 file.
+/// Length in seconds of the windows the RMS and band levels of the recordings are computed over.
+pub const ANALYSIS_WINDOW_S: u64 = 5;
+
+/// Format of the 16-bit PCM WAV files written in-process, at `sample_rate` Hz.
+#[cfg(any(test, feature = "audio-loopback", feature = "native-audio", feature = "simulation"))]
+pub(crate) fn wav_spec(sample_rate: u32, channels: u16) -> hound::WavSpec {
+    hound::WavSpec { channels, sample_rate, bits_per_sample: 16, sample_format: hound::SampleFormat::Int }
+}
+
+/// Writes `samples` (interleaved if there are several `channels`) to a 16-bit PCM WAV file at `path`.
+#[cfg(any(test, feature = "audio-loopback"))]
+pub(crate) fn write_wav(path: impl AsRef<Path>, sample_rate: u32, channels: u16, samples: &[i16]) -> Result<(), hound::Error> {
+    let mut writer = hound::WavWriter::create(path, wav_spec(sample_rate, channels))?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()
+}
+
+/// Mono audio decoded from a recording.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DecodedAudio {
+    /// 16-bit samples, the channels of multi-channel recordings averaged.
+    pub samples: Vec<i16>,
+    /// Sample rate reported by the decoder, in Hz.
+    pub sample_rate: u32,
+}
+
+/// RMS level of the whole recording at `path` in dBFS, weighted by `weighting`, e.g. of a
+/// reference sound to calibrate the microphone with (see
+/// [`MicrophoneCalibration::from_reference`](crate::calibration::MicrophoneCalibration::from_reference)).
+/// Encrypted recordings are decrypted with `key`.
 ///
-/// # Arguments
-/// * `path` - The path to the MP3 file.
+/// # Errors
 ///
-/// # Example
-/// ```no_run
-/// use sleep_recorder::audio_analysis::decode_mp3;
-/// let samples = decode_mp3("/path/to/audio.mp3").expect("Failed to decode MP3 file");
-/// ```
-#[tracing::instrument(skip(path))]
-pub fn decode_mp3(path: &str) -> Result<Vec<i16>, Box<dyn Error>> {
-    let mut decoder = Decoder::new(
-        File::open(path)
-        .map_err(|e| format!("Failed to open file: {} with error {}", path, e))?);
+/// Returns an error if the recording cannot be decoded, or holds no audio.
+pub fn recording_level_dbfs(path: &str, key: Option<&Key>, weighting: AudioWeighting) -> Result<f32, Box<dyn Error>> {
+    let DecodedAudio { samples, sample_rate } = decode_audio(path, key)?;
+    let duration = Duration::from_secs_f64(samples.len() as f64 / sample_rate as f64);
+    let mut meter = LevelMeter::new(sample_rate, duration).with_weighting(weighting);
+    meter.push(&samples).first().copied().ok_or_else(|| format!("No audio in {}", path).into())
+}
 
-    let mut samples = Vec::new();
+/// Decodes a recording in any of the formats the recorder writes (MP3, WAV, FLAC, Opus),
+/// choosing the format from the file extension. Encrypted recordings (`.enc`) are decrypted in
+/// memory with `key`.
+///
+/// MP3, WAV, and FLAC are decoded by [`symphonia_decode`]. Symphonia has no Opus decoder, so Opus
+/// is decoded by `ffmpeg`, which must be installed.
+pub(crate) fn decode_audio(path: &str, key: Option<&Key>) -> Result<DecodedAudio, Box<dyn Error>> {
+    let encrypted = encryption::is_encrypted_path(path);
+    let plaintext_path = if encrypted { Path::new(path).with_extension("") } else { Path::new(path).to_path_buf() };
+    let extension = plaintext_path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let decoded = match (extension, encrypted) {
+        ("opus" | "ogg", false) => ffmpeg_decode(path, None)?,
+        ("opus" | "ogg", true) => ffmpeg_decode("pipe:0", Some(encryption::read(path, key)?))?,
+        (_, false) => {
+            let file = File::open(path).map_err(|e| format!("Failed to open file: {} with error {}", path, e))?;
+            symphonia_decode(Box::new(file), extension)?
+        }
+        (_, true) => symphonia_decode(Box::new(Cursor::new(encryption::read(path, key)?)), extension)?,
+    };
+    info!("Decoded file: {}", path);
+    Ok(decoded)
+}
 
-    info!("Opened file: {}", path);
+/// Averages interleaved multi-channel samples down to mono.
+fn to_mono(samples: Vec<i16>, channels: usize) -> Vec<i16> {
+    if channels <= 1 {
+        return samples;
+    }
+    samples
+        .chunks_exact(channels)
+        .map(|frame| (frame.iter().map(|&s| s as i32).sum::<i32>() / channels as i32) as i16)
+        .collect()
+}
 
+/// Decodes the default track of `source` with symphonia, whose format is probed with the file
+/// `extension` as a hint, to mono 16-bit samples: the channels are averaged, and other sample
+/// formats and bit depths converted.
+///
+/// Packets that cannot be decoded are skipped with a warning, and a stream cut short (e.g. by a
+/// crash) ends with the packets before it.
+///
+/// # Errors
+/// If the format isn't supported, or the stream has no audio.
+fn symphonia_decode(source: Box<dyn MediaSource>, extension: &str) -> Result<DecodedAudio, Box<dyn Error>> {
+    let mut hint = Hint::new();
+    hint.with_extension(extension);
+    let stream = MediaSourceStream::new(source, Default::default());
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())?
+        .format;
+    let track = format.default_track().ok_or("Stream has no audio track")?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+    let mut sample_rate = track.codec_params.sample_rate;
+    let mut samples = Vec::new();
+    let mut buffer: Option<SampleBuffer<i16>> = None;
     loop {
-        match decoder.next_frame() {
-            Ok(Frame { data, .. }) => {
-                // info!("Sample rate: {}, channels: {}", sample_rate, channels);
-                samples.extend_from_slice(&data);
-            },
-            Err(Minimp3Error::Eof) => break,
-            Err(e) => panic!("{:?}", e),
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => {
+                warn!("Stream ends with an error after {} samples: {}", samples.len(), e);
+                break;
+            }
+        };
+        if packet.track_id() != track_id {
+            continue;
         }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(e)) => {
+                warn!("Skipping undecodable packet after {} samples: {}", samples.len(), e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        sample_rate.get_or_insert(spec.rate);
+        let buffer = match &mut buffer {
+            Some(buffer) if buffer.capacity() >= decoded.capacity() * spec.channels.count() => buffer,
+            _ => buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend(to_mono(buffer.samples().to_vec(), spec.channels.count()));
     }
-    Ok(samples)
+    if samples.is_empty() {
+        return Err("Stream has no audio".into());
+    }
+    Ok(DecodedAudio { samples, sample_rate: sample_rate.ok_or("Stream has no sample rate")? })
+}
+
+/// Sample rate `ffmpeg` resamples the recordings it decodes to, in Hz.
+const FFMPEG_SAMPLE_RATE: u32 = 48_000;
+
+/// Decodes `input` with `ffmpeg`, writing `stdin` to it if given (with `input` "pipe:0").
+fn ffmpeg_decode(input: &str, stdin: Option<Vec<u8>>) -> Result<DecodedAudio, Box<dyn Error>> {
+    let sample_rate = FFMPEG_SAMPLE_RATE.to_string();
+    let mut child = std::process::Command::new("ffmpeg")
+        .args(["-v", "error", "-i", input, "-f", "s16le", "-ac", "1", "-ar", &sample_rate, "-"])
+        .stdin(if stdin.is_some() { std::process::Stdio::piped() } else { std::process::Stdio::null() })
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run ffmpeg to decode {}: {}", input, e))?;
+    // Written from another thread, so that ffmpeg's output can't fill up and block it
+    let writer = match (stdin, child.stdin.take()) {
+        (Some(data), Some(mut pipe)) => Some(std::thread::spawn(move || pipe.write_all(&data))),
+        _ => None,
+    };
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        writer.join().map_err(|_| "ffmpeg input writer panicked")??;
+    }
+    if !output.status.success() {
+        return Err(format!("ffmpeg failed to decode {}: {}", input, String::from_utf8_lossy(&output.stderr)).into());
+    }
+    let samples = output.stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    Ok(DecodedAudio { samples, sample_rate: FFMPEG_SAMPLE_RATE })
 }
 
 /// Computes the RMS volume in dBFS for a given window size.
-/// 
-/// This function takes a vector of audio samples and computes the RMS volume in dBFS.
-/// It first normalizes the samples, then computes the RMS for each chunk of audio data.
-/// Finally, it computes the dBFS for each window of audio data. Assumes a sample rate of 48kHz.
+///
+/// The windows are exact sample counts at `sample_rate`, as in [`LevelMeter`] (which computes
+/// them), so the window `i` starts `i * window_size_s` seconds into the recording at any sample
+/// rate, and the windows line up with those of [`BandMeter`].
 /// Only complete windows are considered (e.g. for a 31s recording & 5s windows, only 6 windows are returned).
 ///
 /// # Arguments
-/// * `samples` - A vector of audio samples.
+/// * `samples` - A vector of mono audio samples.
+/// * `sample_rate` - The sample rate of `samples` in Hz, as reported by the decoder.
 /// * `window_size_s` - The size of the window in seconds.
+/// * `weighting` - The frequency weighting applied to the samples first.
 ///
-#[tracing::instrument]
-fn window_volume_dbfs(samples: Vec<i16>, window_size_s: usize) -> Vec<f32> {
-    const CHUNK: usize = 2048;
-    const SAMPLE_RATE: usize = 48_000;
+#[tracing::instrument(skip(samples))]
+fn window_volume_dbfs(samples: Vec<i16>, sample_rate: u32, window_size_s: usize, weighting: AudioWeighting) -> Vec<f32> {
+    LevelMeter::new(sample_rate, Duration::from_secs(window_size_s as u64))
+        .with_weighting(weighting)
+        .push(&samples)
+}
 
-    let normalized_samples = samples.iter().map(|s| *s as f32 / i16::MAX as f32).collect::<Vec<f32>>();
+/// Incremental RMS level meter for live audio, producing one dBFS value per complete window.
+///
+/// Matches the levels computed offline by `analyze_audio_entries` for the same window length.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use sleep_recorder::audio_analysis::LevelMeter;
+/// let mut meter = LevelMeter::new(48_000, Duration::from_secs(1));
+/// assert!(meter.push(&[0; 24_000]).is_empty());
+/// assert_eq!(meter.push(&[i16::MAX; 24_000]).len(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LevelMeter {
+    sample_rate: u32,
+    window_len: usize,
+    weighting: Option<AWeighting>,
+    sum_squares: f64,
+    count: usize,
+}
 
-    let rms_downsample: Vec<f32> = normalized_samples
-        .chunks(CHUNK)
-        .map(rms_normalized)
-        .collect();
-    
-    let chunks_per_time: usize = SAMPLE_RATE * window_size_s / CHUNK;
-    let db_windows: Vec<f32> = rms_downsample
-        .chunks(chunks_per_time)
-        .filter(|w| w.len() == chunks_per_time) // Throw out incomplete chunks
-        .map(|w| 20.0_f32 * rms_normalized(w).log10())
-        .collect();
-    db_windows
+impl LevelMeter {
+    /// Creates a meter for audio at `sample_rate` Hz, reporting one level per `window`.
+    pub fn new(sample_rate: u32, window: Duration) -> Self {
+        let window_len = ((sample_rate as f64 * window.as_secs_f64()) as usize).max(1);
+        Self { sample_rate, window_len, weighting: None, sum_squares: 0.0, count: 0 }
+    }
+
+    /// Weights the levels by `weighting`. Defaults to [`AudioWeighting::Z`] (unweighted).
+    pub fn with_weighting(mut self, weighting: AudioWeighting) -> Self {
+        self.weighting = AWeighting::for_weighting(weighting, self.sample_rate);
+        self
+    }
+
+    /// Sample rate of the audio the meter was created for, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Adds samples and returns the levels (dBFS) of any windows completed by them.
+    pub fn push(&mut self, samples: &[i16]) -> Vec<f32> {
+        let mut levels = Vec::new();
+        for &sample in samples {
+            let mut normalized = sample as f64 / i16::MAX as f64;
+            if let Some(filter) = &mut self.weighting {
+                normalized = filter.process(normalized);
+            }
+            self.sum_squares += normalized * normalized;
+            self.count += 1;
+            if self.count == self.window_len {
+                let rms = (self.sum_squares / self.count as f64).sqrt();
+                levels.push(20.0 * rms.log10() as f32);
+                self.sum_squares = 0.0;
+                self.count = 0;
+            }
+        }
+        levels
+    }
+}
+
+/// A frequency band whose level is measured per window by [`BandMeter`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Band {
+    /// Name of the band.
+    pub name: &'static str,
+    /// Dataset of the band's levels (dBFS) in a session, parallel to [`BAND_TIMES_DATASET`].
+    pub dataset: &'static str,
+    /// Lower edge of the band in Hz.
+    pub low_hz: f32,
+    /// Upper edge of the band in Hz.
+    pub high_hz: f32,
+}
+
+/// The bands measured by [`BandMeter`]: low rumble (HVAC hum, traffic), the speech band (voices,
+/// snoring), and high frequencies (breathing, rustling sheets).
+pub const BANDS: [Band; 3] = [
+    Band { name: "low", dataset: "audio_band_low_db", low_hz: 20.0, high_hz: 250.0 },
+    Band { name: "speech", dataset: "audio_band_speech_db", low_hz: 250.0, high_hz: 4_000.0 },
+    Band { name: "high", dataset: "audio_band_high_db", low_hz: 4_000.0, high_hz: 16_000.0 },
+];
+
+/// Dataset of the start times (s since UNIX epoch) of the windows of the band levels.
+pub const BAND_TIMES_DATASET: &str = "audio_band_t_s";
+
+/// Incremental level meter of the [`BANDS`], producing the dBFS level of each band per complete
+/// window, like [`LevelMeter`] does for the whole signal.
+///
+/// Each band is isolated with 4th-order high- and low-pass filters at its edges; edges above the
+/// Nyquist frequency of `sample_rate` are left out.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use sleep_recorder::audio_analysis::BandMeter;
+/// let mut meter = BandMeter::new(48_000, Duration::from_secs(1));
+/// let hum: Vec<i16> = (0..48_000).map(|i| ((i as f32 * 60.0 / 48_000.0 * std::f32::consts::TAU).sin() * 10_000.0) as i16).collect();
+/// let levels = meter.push(&hum);
+/// assert!(levels[0][0] > levels[0][1] + 20.0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BandMeter {
+    filters: Vec<Vec<Biquad>>,
+    window_len: usize,
+    sum_squares: [f64; BANDS.len()],
+    count: usize,
+}
+
+impl BandMeter {
+    /// Creates a meter for audio at `sample_rate` Hz, reporting the band levels per `window`.
+    pub fn new(sample_rate: u32, window: Duration) -> Self {
+        let sample_rate = sample_rate as f64;
+        let nyquist_hz = sample_rate / 2.0;
+        let filters = BANDS.iter()
+            .map(|band| {
+                let mut filters = Vec::new();
+                if (band.low_hz as f64) < nyquist_hz {
+                    filters.extend([Biquad::high_pass(sample_rate, band.low_hz as f64); 2]);
+                }
+                if (band.high_hz as f64) < nyquist_hz {
+                    filters.extend([Biquad::low_pass(sample_rate, band.high_hz as f64); 2]);
+                }
+                filters
+            })
+            .collect();
+        let window_len = ((sample_rate * window.as_secs_f64()) as usize).max(1);
+        Self { filters, window_len, sum_squares: [0.0; BANDS.len()], count: 0 }
+    }
+
+    /// Adds samples and returns the levels (dBFS, in the order of [`BANDS`]) of any windows
+    /// completed by them.
+    pub fn push(&mut self, samples: &[i16]) -> Vec<[f32; BANDS.len()]> {
+        let mut levels = Vec::new();
+        for &sample in samples {
+            let normalized = sample as f64 / i16::MAX as f64;
+            for (filters, sum_squares) in self.filters.iter_mut().zip(&mut self.sum_squares) {
+                let filtered = filters.iter_mut().fold(normalized, |value, filter| filter.process(value));
+                *sum_squares += filtered * filtered;
+            }
+            self.count += 1;
+            if self.count == self.window_len {
+                levels.push(self.sum_squares.map(|sum_squares| 20.0 * (sum_squares / self.count as f64).sqrt().log10() as f32));
+                self.sum_squares = [0.0; BANDS.len()];
+                self.count = 0;
+            }
+        }
+        levels
+    }
+}
+
+/// The levels of one [`ANALYSIS_WINDOW_S`] window of a recording, computed by [`StreamAnalyzer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowFeatures {
+    /// Start of the window (s since UNIX epoch).
+    pub t_s: u64,
+    /// RMS level of the window in dBFS.
+    pub rms_db: f32,
+    /// Levels of the [`BANDS`] in dBFS, in their order.
+    pub band_db: [f32; BANDS.len()],
+}
+
+/// Live counterpart of [`analyze_audio_entries`]: computes the RMS and band levels of a
+/// recording's windows from its samples as they are captured, with the same window times, so
+/// that the recording needn't be decoded again after the night.
+///
+/// # Example
+///
+/// ```
+/// use sleep_recorder::audio_analysis::{StreamAnalyzer, ANALYSIS_WINDOW_S};
+/// let mut analyzer = StreamAnalyzer::new(1_745_873_251, 16_000);
+/// let second = [1_000i16; 16_000];
+/// let windows: Vec<_> = (0..2 * ANALYSIS_WINDOW_S).flat_map(|_| analyzer.push(&second)).collect();
+/// assert_eq!(windows.len(), 2);
+/// assert_eq!(windows[1].t_s, 1_745_873_251 + ANALYSIS_WINDOW_S);
+/// ```
+#[derive(Clone, Debug)]
+pub struct StreamAnalyzer {
+    start_time_s: u64,
+    windows: u64,
+    level_meter: LevelMeter,
+    band_meter: BandMeter,
+}
+
+impl StreamAnalyzer {
+    /// Creates an analyzer of the recording started at `start_time_s`, captured at `sample_rate` Hz.
+    pub fn new(start_time_s: u64, sample_rate: u32) -> Self {
+        let window = Duration::from_secs(ANALYSIS_WINDOW_S);
+        Self {
+            start_time_s,
+            windows: 0,
+            level_meter: LevelMeter::new(sample_rate, window),
+            band_meter: BandMeter::new(sample_rate, window),
+        }
+    }
+
+    /// Weights the RMS levels by `weighting`; the band levels aren't. Defaults to
+    /// [`AudioWeighting::Z`] (unweighted).
+    pub fn with_weighting(mut self, weighting: AudioWeighting) -> Self {
+        self.level_meter = self.level_meter.with_weighting(weighting);
+        self
+    }
+
+    /// Start of the analyzed recording (s since UNIX epoch).
+    pub fn start_time_s(&self) -> u64 {
+        self.start_time_s
+    }
+
+    /// Adds the next samples of the recording and returns the levels of any windows completed by
+    /// them.
+    pub fn push(&mut self, samples: &[i16]) -> Vec<WindowFeatures> {
+        let levels = self.level_meter.push(samples);
+        // Both meters complete their windows at the same sample
+        let band_levels = self.band_meter.push(samples);
+        levels.into_iter()
+            .zip(band_levels)
+            .map(|(rms_db, band_db)| {
+                let t_s = self.start_time_s + self.windows * ANALYSIS_WINDOW_S;
+                self.windows += 1;
+                WindowFeatures { t_s, rms_db, band_db }
+            })
+            .collect()
+    }
+}
+
+/// Second-order Butterworth filter section (from the Audio EQ Cookbook).
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn low_pass(sample_rate: f64, cutoff_hz: f64) -> Self {
+        let (cos, alpha) = Self::cos_alpha(sample_rate, cutoff_hz);
+        Self::new([(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0], cos, alpha)
+    }
+
+    fn high_pass(sample_rate: f64, cutoff_hz: f64) -> Self {
+        let (cos, alpha) = Self::cos_alpha(sample_rate, cutoff_hz);
+        Self::new([(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0], cos, alpha)
+    }
+
+    /// Digital counterpart (by the bilinear transform) of the analog section
+    /// `(b[0] s² + b[1] s + b[2]) / (a[0] s² + a[1] s + a[2])`.
+    fn bilinear(sample_rate: f64, b: [f64; 3], a: [f64; 3]) -> Self {
+        let k = 2.0 * sample_rate;
+        let transform = |p: [f64; 3]| [p[0] * k * k + p[1] * k + p[2], 2.0 * (p[2] - p[0] * k * k), p[0] * k * k - p[1] * k + p[2]];
+        let (b, a) = (transform(b), transform(a));
+        Self {
+            b: b.map(|b| b / a[0]),
+            a: [a[1] / a[0], a[2] / a[0]],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    /// Magnitude of the response at `hz`.
+    fn gain(&self, sample_rate: f64, hz: f64) -> f64 {
+        let w = std::f64::consts::TAU * hz / sample_rate;
+        let magnitude = |c: [f64; 3]| {
+            let re = c[0] + c[1] * w.cos() + c[2] * (2.0 * w).cos();
+            let im = c[1] * w.sin() + c[2] * (2.0 * w).sin();
+            re.hypot(im)
+        };
+        magnitude(self.b) / magnitude([1.0, self.a[0], self.a[1]])
+    }
+
+    fn cos_alpha(sample_rate: f64, cutoff_hz: f64) -> (f64, f64) {
+        let w0 = std::f64::consts::TAU * cutoff_hz / sample_rate;
+        (w0.cos(), w0.sin() / std::f64::consts::SQRT_2)
+    }
+
+    fn new(b: [f64; 3], cos: f64, alpha: f64) -> Self {
+        let a0 = 1.0 + alpha;
+        Self {
+            b: b.map(|b| b / a0),
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
 }
 
-fn rms_normalized<T: Into<f32> + Copy>(samples: &[T]) -> f32 {
-    (samples.iter()
-        .map(|s| Into::<f32>::into(*s).powi(2))
-        .sum::<f32>()
-        / samples.len() as f32)
-        .sqrt()
+/// A-weighting filter (IEC 61672): the analog weighting's poles at 20.6 Hz, 107.7 Hz, 737.9 Hz and
+/// 12.2 kHz through the bilinear transform, with 0 dB gain at 1 kHz. Close to the standard up to
+/// a few kHz; it falls off early towards the Nyquist frequency of low sample rates.
+#[derive(Clone, Debug)]
+struct AWeighting {
+    sections: [Biquad; 3],
+    gain: f64,
+}
+
+impl AWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f64;
+        let [w1, w2, w3, w4] = [20.598_997, 107.652_65, 737.862_23, 12_194.217].map(|hz| std::f64::consts::TAU * hz);
+        let sections = [
+            Biquad::bilinear(sample_rate, [1.0, 0.0, 0.0], [1.0, 2.0 * w1, w1 * w1]),
+            Biquad::bilinear(sample_rate, [1.0, 0.0, 0.0], [1.0, w2 + w3, w2 * w3]),
+            Biquad::bilinear(sample_rate, [0.0, 0.0, 1.0], [1.0, 2.0 * w4, w4 * w4]),
+        ];
+        let gain = 1.0 / sections.iter().map(|section| section.gain(sample_rate, 1_000.0)).product::<f64>();
+        Self { sections, gain }
+    }
+
+    /// The filter applying `weighting` at `sample_rate` Hz, or `None` if it applies none.
+    fn for_weighting(weighting: AudioWeighting, sample_rate: u32) -> Option<Self> {
+        match weighting {
+            AudioWeighting::Z => None,
+            AudioWeighting::A => Some(Self::new(sample_rate)),
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.gain * self.sections.iter_mut().fold(x, |value, section| section.process(value))
+    }
 }
 
 #[cfg(test)]
@@ -145,18 +1103,10 @@ mod tests {
     use super::*;
     use test_log::test;
 
-    // Helper constant matching the chunk size in block_rms.
+    // Block size the test signals are built from.
     const CHUNK: usize = 2048;
     const SAMPLE_RATE: usize = 48000;
 
-    // Test the RMS function with a simple constant signal.
-    #[test]
-    fn test_rms_norm_constant() {
-        let result = rms_normalized(&[2.0, 2.0, 2.0, 2.0]);
-
-        assert!((result - 2.0).abs() < 1e-5, "Expected near 2.0, got {}", result);
-    }
-
     // Test the window_volume_db function with a simple constant low signal.
     #[test]
     fn test_window_volume_db_constant_low_signal() {
@@ -170,7 +1120,7 @@ mod tests {
         let samples = vec![value; CHUNK * num_chunks];
         let window_size_s: usize = 1;
 
-        let result = window_volume_dbfs(samples, window_size_s);
+        let result = window_volume_dbfs(samples, SAMPLE_RATE as u32, window_size_s, AudioWeighting::Z);
 
         // We expect two smoothed RMS values.
         assert_eq!(result.len(), ((num_chunks * CHUNK) as f32 / (SAMPLE_RATE * window_size_s) as f32).floor() as usize);
@@ -192,7 +1142,7 @@ mod tests {
         let samples = vec![value; CHUNK * num_chunks];
         let window_size_s: usize = 1;
 
-        let result = window_volume_dbfs(samples, window_size_s);
+        let result = window_volume_dbfs(samples, SAMPLE_RATE as u32, window_size_s, AudioWeighting::Z);
 
         // We expect two smoothed RMS values.
         assert_eq!(result.len(), ((num_chunks * CHUNK) as f32 / (SAMPLE_RATE * window_size_s) as f32).floor() as usize);
@@ -214,13 +1164,13 @@ mod tests {
             .map(|i| i as i16)
             .collect();
         let window_size_s = 1;
-        let result = window_volume_dbfs(samples, window_size_s);
+        let result = window_volume_dbfs(samples, SAMPLE_RATE as u32, window_size_s, AudioWeighting::Z);
 
         // We check that result is non-empty and values are within [0.0, 1.0].
         assert!(!result.is_empty());
         println!("Result: {:?}", result);
         for rms in result {
-            assert!(rms <= 0.0 && rms >= -10.0);
+            assert!((-10.0..=0.0).contains(&rms));
         }
     }
 
@@ -236,13 +1186,252 @@ mod tests {
     #[test]
     fn test_decode_and_db_tone_file() {
         const AUDIO_PATH: &str = "test_data/test_audio_48kHz.mp3";
-        let samples = decode_mp3(AUDIO_PATH).expect("Failed to decode MP3 file");
-        let volume_db = window_volume_dbfs(samples, 10);
+        let decoded = decode_audio(AUDIO_PATH, None).expect("Failed to decode MP3 file");
+        assert_eq!(decoded.sample_rate, 48_000);
+        let volume_db = window_volume_dbfs(decoded.samples, decoded.sample_rate, 10, AudioWeighting::Z);
         println!("Volume dB: {:?}", volume_db);
         assert_eq!(volume_db.len(), 3, "Expected 3 windows, got {}", volume_db.len());
         assert!((volume_db[0] + 100.0).abs() < 10.0, "Expected -100 dBFS, got {}", volume_db[0]);
         assert!((volume_db[1] + 10.0).abs() < 1.5, "Expected -10 dBFS, got {}", volume_db[1]);
-        assert!((volume_db[2] + 23.633978952).abs() < 1.5, "Expected -23.633978952 dBFS, got {}", volume_db[2]);
+        assert!((volume_db[2] + 23.634).abs() < 1.5, "Expected -23.633978952 dBFS, got {}", volume_db[2]);
+    }
+
+    // The live meter reports the same level as the offline windowed measurement.
+    #[test]
+    fn test_level_meter_matches_window_volume() {
+        let samples: Vec<i16> = (0..SAMPLE_RATE * 2).map(|i| ((i % 200) as i16 - 100) * 50).collect();
+        let offline = window_volume_dbfs(samples.clone(), SAMPLE_RATE as u32, 1, AudioWeighting::Z);
+
+        let mut meter = LevelMeter::new(SAMPLE_RATE as u32, Duration::from_secs(1));
+        let live: Vec<f32> = samples.chunks(4800).flat_map(|c| meter.push(c)).collect();
+
+        assert_eq!(live.len(), 2);
+        for (l, o) in live.iter().zip(&offline) {
+            assert!((l - o).abs() < 0.1, "Expected near {}, got {}", o, l);
+        }
+    }
+
+    // A-weighting leaves 1 kHz unchanged and attenuates low frequencies as the standard does
+    // (-19.1 dB at 100 Hz), live and offline alike.
+    #[test]
+    fn test_a_weighting() {
+        let tone = |hz: f32| -> Vec<i16> {
+            (0..SAMPLE_RATE * 2).map(|i| ((i as f32 * hz / SAMPLE_RATE as f32 * std::f32::consts::TAU).sin() * 10_000.0) as i16).collect()
+        };
+        for (hz, expected_db) in [(1_000.0, 0.0), (100.0, -19.1), (2_000.0, 1.2)] {
+            let samples = tone(hz);
+            let flat = window_volume_dbfs(samples.clone(), SAMPLE_RATE as u32, 1, AudioWeighting::Z);
+            let weighted = window_volume_dbfs(samples.clone(), SAMPLE_RATE as u32, 1, AudioWeighting::A);
+            // The second window, after the filter has settled
+            assert!((weighted[1] - flat[1] - expected_db).abs() < 0.3, "Expected {} dB at {} Hz, got {}", expected_db, hz, weighted[1] - flat[1]);
+
+            let mut meter = LevelMeter::new(SAMPLE_RATE as u32, Duration::from_secs(1)).with_weighting(AudioWeighting::A);
+            let live: Vec<f32> = samples.chunks(4800).flat_map(|c| meter.push(c)).collect();
+            assert!((live[1] - weighted[1]).abs() < 0.1, "Expected near {}, got {}", weighted[1], live[1]);
+        }
+    }
+
+    // Tones land in their band: each band is loudest for the tone inside it, and a full-scale
+    // tone in the band measures close to its level (-3 dBFS for a sine).
+    #[test]
+    fn test_band_meter_separates_tones() {
+        let tone = |frequency_hz: f32, sample_rate: usize| -> Vec<i16> {
+            (0..sample_rate * 2)
+                .map(|i| ((i as f32 * frequency_hz / sample_rate as f32 * std::f32::consts::TAU).sin() * i16::MAX as f32) as i16)
+                .collect()
+        };
+        for (band, frequency_hz) in [60.0, 1_000.0, 8_000.0].into_iter().enumerate() {
+            let mut meter = BandMeter::new(SAMPLE_RATE as u32, Duration::from_secs(1));
+            let levels = meter.push(&tone(frequency_hz, SAMPLE_RATE));
+            assert_eq!(levels.len(), 2);
+            // The second window, once the filters have settled
+            let level = levels[1];
+            assert!((level[band] + 3.0).abs() < 1.5, "Expected near -3 dBFS in {}, got {:?}", BANDS[band].name, level);
+            for (other, &other_level) in level.iter().enumerate().filter(|&(other, _)| other != band) {
+                assert!(other_level < level[band] - 15.0, "{} Hz leaked into {}: {:?}", frequency_hz, BANDS[other].name, level);
+            }
+        }
+
+        // At 16 kHz, the high band extends up to the Nyquist frequency
+        let mut meter = BandMeter::new(16_000, Duration::from_secs(1));
+        let level = meter.push(&tone(6_000.0, 16_000))[1];
+        assert!((level[2] + 3.0).abs() < 1.5, "Expected near -3 dBFS in high, got {:?}", level);
+    }
+
+    // Streamed in capture-sized chunks, the analyzer matches the offline levels and window times.
+    #[test]
+    fn test_stream_analyzer_matches_offline() {
+        let samples: Vec<i16> = (0..SAMPLE_RATE * 12).map(|i| ((i % 200) as i16 - 100) * 50).collect();
+        let offline_rms = window_volume_dbfs(samples.clone(), SAMPLE_RATE as u32, ANALYSIS_WINDOW_S as usize, AudioWeighting::Z);
+        let offline_bands = BandMeter::new(SAMPLE_RATE as u32, Duration::from_secs(ANALYSIS_WINDOW_S)).push(&samples);
+
+        let mut analyzer = StreamAnalyzer::new(100, SAMPLE_RATE as u32);
+        let windows: Vec<WindowFeatures> = samples.chunks(4800).flat_map(|c| analyzer.push(c)).collect();
+
+        assert_eq!(windows.iter().map(|w| w.t_s).collect::<Vec<u64>>(), [100, 105]);
+        for (window, (rms_db, band_db)) in windows.iter().zip(offline_rms.iter().zip(&offline_bands)) {
+            assert!((window.rms_db - rms_db).abs() < 0.1, "Expected near {}, got {}", rms_db, window.rms_db);
+            assert_eq!(window.band_db, *band_db);
+        }
     }
 
+    // Batches are bounded by the thread count and the memory of the decoded recordings, and a
+    // recording too long for the budget is analyzed alone.
+    #[test]
+    fn test_analysis_batches() {
+        let job = |index, duration_s| AnalysisJob {
+            index,
+            path: format!("audio_{index}.mp3"),
+            start_time_s: index as u64 * 600,
+            duration_s,
+            bands: true,
+            rms: true,
+        };
+        let jobs = vec![job(0, 600), job(1, 600), job(2, 600), job(3, 3600), job(4, 60)];
+        let indices = |batches: Vec<Vec<AnalysisJob>>| -> Vec<Vec<usize>> {
+            batches.iter().map(|batch| batch.iter().map(|job| job.index).collect()).collect()
+        };
+        let budget = 1300 * ANALYSIS_BYTES_PER_S;
+        assert_eq!(indices(batches(jobs.clone(), budget, 4)), [vec![0, 1], vec![2], vec![3], vec![4]]);
+        assert_eq!(indices(batches(jobs.clone(), u64::MAX, 2)), [vec![0, 1], vec![2, 3], vec![4]]);
+        assert!(batches(Vec::new(), budget, 4).is_empty());
+        assert_eq!(indices(batches(jobs, u64::MAX, 8)), [vec![0, 1, 2, 3, 4]]);
+    }
+
+    // A silent recording is split into the runs of windows around its sound events, the first
+    // and last pieces spanning to the recording's bounds.
+    #[test]
+    fn test_silent_pieces() {
+        let windows = [100, 105, 110, 115];
+        let piece = |start_time_s, duration_s, windows: Range<usize>, kept| SilentPiece { start_time_s, duration_s, windows, kept };
+        assert_eq!(silent_pieces(100, 22, &windows, &[], 2), [piece(100, 22, 0..4, false)]);
+        assert_eq!(
+            silent_pieces(100, 22, &windows, &[107], 2),
+            [piece(100, 5, 0..1, false), piece(105, 5, 1..2, true), piece(110, 12, 2..4, false)],
+        );
+        // Events near a window boundary keep both windows, and overlapping windows merge
+        assert_eq!(
+            silent_pieces(100, 22, &windows, &[109, 113], 2),
+            [piece(100, 5, 0..1, false), piece(105, 17, 1..4, true)],
+        );
+        assert_eq!(silent_pieces(100, 22, &windows, &[101], 0), [piece(100, 5, 0..1, true), piece(105, 17, 1..4, false)]);
+        assert_eq!(clip_path("/data/audio/audio_100.wav", 105), "/data/audio/audio_100_clip_105.wav");
+    }
+
+    // Clips are cut within the recording of their event, and events close together share one.
+    #[test]
+    fn test_event_clips() {
+        let recordings = [(1_000, 600), (1_600, 600)];
+        let clips = event_clips(&recordings, &[990, 1_005, 1_300, 1_310, 1_350, 1_595, 1_610], 15);
+        let found: Vec<(usize, u64, u64, Vec<u64>)> = clips.into_iter()
+            .map(|clip| (clip.recording, clip.start_time_s, clip.duration_s, clip.event_times))
+            .collect();
+        assert_eq!(found, [
+            (0, 1_000, 20, vec![1_005]),
+            (0, 1_285, 40, vec![1_300, 1_310]),
+            (0, 1_335, 30, vec![1_350]),
+            (0, 1_580, 20, vec![1_595]),
+            (1, 1_600, 25, vec![1_610]),
+        ]);
+    }
+
+    // WAV clips are cut natively, in the recording's format.
+    #[test]
+    fn test_cut_wav_clip() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("audio_0.wav");
+        let samples: Vec<i16> = (0..10).flat_map(|second| [second; 8000]).collect();
+        write_wav(&path, 8000, 1, &samples).expect("Failed to write WAV");
+
+        let clip = clip_path(path.to_str().unwrap(), 3);
+        cut_clip(path.to_str().unwrap(), &clip, 3, 5).expect("Failed to cut clip");
+        let reader = hound::WavReader::open(&clip).expect("Failed to open clip");
+        assert_eq!(reader.spec(), wav_spec(8000, 1));
+        let samples: Vec<i16> = reader.into_samples().map(|s| s.unwrap()).collect();
+        assert_eq!(samples.len(), 5 * 8000);
+        assert_eq!((samples[0], samples[samples.len() - 1]), (3, 7));
+
+        // A clip past the end of the recording is cut short
+        cut_clip(path.to_str().unwrap(), &clip, 8, 5).expect("Failed to cut clip");
+        assert_eq!(hound::WavReader::open(&clip).expect("Failed to open clip").duration(), 2 * 8000);
+    }
+
+    // The level of a 1 kHz calibration tone at half scale is its RMS, -9 dBFS, with or without
+    // A-weighting (0 dB at 1 kHz).
+    #[test]
+    fn test_recording_level() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("calibration.wav");
+        let tone: Vec<i16> = (0..48_000 * 3)
+            .map(|i| ((std::f32::consts::TAU * 1_000.0 * i as f32 / 48_000.0).sin() * 16_384.0) as i16)
+            .collect();
+        write_wav(&path, 48_000, 1, &tone).expect("Failed to write WAV");
+
+        for weighting in [AudioWeighting::Z, AudioWeighting::A] {
+            let level = recording_level_dbfs(path.to_str().unwrap(), None, weighting).expect("Failed to measure level");
+            assert!((level + 9.03).abs() < 0.2, "{:?}: {}", weighting, level);
+        }
+    }
+
+    // Stereo WAV files (e.g. from a different capture device) are downmixed to mono.
+    #[test]
+    fn test_decode_wav_downmixes_stereo() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("audio_0.wav");
+        write_wav(&path, SAMPLE_RATE as u32, 2, &[1000, 3000].repeat(100)).expect("Failed to write WAV");
+
+        let decoded = decode_audio(path.to_str().unwrap(), None).expect("Failed to decode WAV file");
+        assert_eq!(decoded, DecodedAudio { samples: vec![2000i16; 100], sample_rate: SAMPLE_RATE as u32 });
+
+        // Encrypted recordings are decoded with the key
+        let key = Key::new([7; 32]);
+        let encrypted = encryption::encrypt_file(&key, path.to_str().unwrap()).expect("Failed to encrypt WAV");
+        assert_eq!(decode_audio(&encrypted, Some(&key)).expect("Failed to decode encrypted WAV").samples, vec![2000i16; 100]);
+        assert!(decode_audio(&encrypted, None).is_err());
+    }
+
+    // A recording cut short by a crash decodes up to where it ends, and one without any audio is
+    // an error rather than a panic.
+    #[test]
+    fn test_decode_truncated_wav() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("audio_0.wav");
+        write_wav(&path, SAMPLE_RATE as u32, 1, &[1000; 100]).expect("Failed to write WAV");
+        let length = std::fs::metadata(&path).unwrap().len();
+        File::options().write(true).open(&path).unwrap().set_len(length - 50).unwrap();
+        assert_eq!(decode_audio(path.to_str().unwrap(), None).expect("Failed to decode truncated WAV").samples, vec![1000i16; 75]);
+
+        std::fs::write(&path, b"RIFF").unwrap();
+        assert!(decode_audio(path.to_str().unwrap(), None).is_err());
+    }
+
+    #[test]
+    fn test_decode_truncated_mp3() {
+        let data = std::fs::read("test_data/test_audio_48kHz.mp3").expect("Failed to read MP3 file");
+        let full = symphonia_decode(Box::new(Cursor::new(data.clone())), "mp3").expect("Failed to decode MP3").samples.len();
+        let mut truncated = data[..data.len() / 2].to_vec();
+        truncated.extend_from_slice(&[0xff, 0xfb, 0x90]);
+        let decoded = symphonia_decode(Box::new(Cursor::new(truncated)), "mp3").expect("Failed to decode truncated MP3");
+        assert!(decoded.samples.len() > full / 3 && decoded.samples.len() < full, "Decoded {} of {} samples", decoded.samples.len(), full);
+        assert!(symphonia_decode(Box::new(Cursor::new(vec![0x55; 4096])), "mp3").is_err());
+    }
+
+    // Recordings of 44.1 kHz devices are windowed at their own rate: 10 s make two 5 s windows.
+    #[test]
+    fn test_window_volume_at_44_1_khz() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("audio_0.wav");
+        write_wav(&path, 44_100, 1, &vec![i16::MAX; 44_100 * 10]).expect("Failed to write WAV");
+
+        let decoded = decode_audio(path.to_str().unwrap(), None).expect("Failed to decode WAV file");
+        assert_eq!(decoded.sample_rate, 44_100);
+        let volume_db = window_volume_dbfs(decoded.samples, decoded.sample_rate, 5, AudioWeighting::Z);
+        assert_eq!(volume_db.len(), 2);
+        assert!(volume_db.iter().all(|db| db.abs() < 1e-3), "Expected 0 dBFS, got {:?}", volume_db);
+
+        // The windows stay 5 s long over minutes: 299 s hold 59 whole windows (60 if they were
+        // rounded down to a whole number of blocks, 4.97 s)
+        let volume_db = window_volume_dbfs(vec![1_000i16; 44_100 * 299], 44_100, 5, AudioWeighting::Z);
+        assert_eq!(volume_db.len(), 59);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,189 @@
+//! Respiration rate estimated from the audio recordings, as a second estimate next to the radar's
+//! (`mmwave_resp_rate_bpm`) and the piezo's (`piezo_resp_rate_bpm`).
+//!
+//! Breathing is heard as noise in the high frequency band (see
+//! [`BANDS`](crate::audio_analysis::BANDS)) rising and falling with each breath. The level of
+//! that band is measured in frames of [`FRAME_S`] seconds, and the rate of each [`RESP_WINDOW_S`]
+//! window is the periodicity of this envelope within the plausible
+//! respiration rates, found by autocorrelation as for the piezo (see [`crate::bcg`]).
+//!
+//! Only quiet windows are estimated: a window with a frame louder than [`QUIET_DB`] (snoring,
+//! speech, or any other sound masking the breaths) gets no rate, nor does a window whose envelope
+//! isn't periodic enough. The rates are stored per window in [`RESP_RATE_DATASET`], `NAN` when
+//! unavailable, with the window start times in [`RESP_TIMES_DATASET`] (see
+//! [`estimate_audio_respiration`]).
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::time::Duration;
+
+use hdf5::File as H5File;
+use tracing::{info, warn};
+
+use crate::audio_analysis::{decode_audio, BandMeter, DecodedAudio, LevelMeter};
+use crate::bcg::{dominant_rate_bpm, RESP_RATE_RANGE};
+use crate::data::{session_path, AppendableColumn, SessionReader};
+use crate::encryption::Key;
+
+/// Length of the frames the envelope is measured in, in seconds.
+pub const FRAME_S: f64 = 0.1;
+/// Length of the windows a rate is estimated for, in seconds.
+pub const RESP_WINDOW_S: u64 = 60;
+/// Level (dBFS) of the loudest frame of a window from which it is too loud to hear the breaths.
+pub const QUIET_DB: f32 = -35.0;
+/// Dataset of the start times of the respiration windows, in seconds since UNIX epoch.
+pub const RESP_TIMES_DATASET: &str = "audio_resp_t_s";
+/// Dataset of the respiration rates of the windows (breaths/min), parallel to
+/// [`RESP_TIMES_DATASET`].
+pub const RESP_RATE_DATASET: &str = "audio_resp_rate_bpm";
+
+/// Index of the high band, which holds the breathing noise, in
+/// [`BANDS`](crate::audio_analysis::BANDS).
+const BREATHING_BAND: usize = 2;
+
+/// Incremental estimator of the respiration rate of a recording's windows (see the
+/// [module documentation](self)).
+#[derive(Clone, Debug)]
+pub struct RespirationEstimator {
+    start_time_s: u64,
+    level_meter: LevelMeter,
+    band_meter: BandMeter,
+    /// Level of the breathing band of the frames of the window in progress, in dBFS.
+    envelope: Vec<f32>,
+    /// Whether a frame of the window in progress was louder than [`QUIET_DB`].
+    loud: bool,
+    /// Windows completed so far.
+    windows: u64,
+}
+
+impl RespirationEstimator {
+    /// Creates an estimator for the recording started at `start_time_s`, captured at
+    /// `sample_rate` Hz.
+    pub fn new(start_time_s: u64, sample_rate: u32) -> Self {
+        let frame = Duration::from_secs_f64(FRAME_S);
+        Self {
+            start_time_s,
+            level_meter: LevelMeter::new(sample_rate, frame),
+            band_meter: BandMeter::new(sample_rate, frame),
+            envelope: Vec::new(),
+            loud: false,
+            windows: 0,
+        }
+    }
+
+    /// Adds the next samples of the recording and returns the windows completed by them, as
+    /// (start time, breaths/min or `NAN`). The audio after the last complete window is not
+    /// estimated.
+    pub fn push(&mut self, samples: &[i16]) -> Vec<(u64, f32)> {
+        let frames_per_window = (RESP_WINDOW_S as f64 / FRAME_S).round() as usize;
+        let levels = self.level_meter.push(samples);
+        let band_levels = self.band_meter.push(samples);
+        let mut windows = Vec::new();
+        for (level_db, band_db) in levels.into_iter().zip(band_levels) {
+            self.loud |= level_db > QUIET_DB;
+            // Digital silence has no level
+            self.envelope.push(band_db[BREATHING_BAND].max(-120.0));
+            if self.envelope.len() == frames_per_window {
+                let rate_bpm = if self.loud {
+                    None
+                } else {
+                    dominant_rate_bpm(&self.envelope, (1.0 / FRAME_S) as f32, RESP_RATE_RANGE)
+                };
+                windows.push((self.start_time_s + self.windows * RESP_WINDOW_S, rate_bpm.unwrap_or(f32::NAN)));
+                self.envelope.clear();
+                self.loud = false;
+                self.windows += 1;
+            }
+        }
+        windows
+    }
+}
+
+/// Estimates the respiration rate of the recordings of `group_name` in the HDF5 file at
+/// `data_path/file_name` (see the [module documentation](self)), and appends the windows to
+/// [`RESP_TIMES_DATASET`] and [`RESP_RATE_DATASET`]. Encrypted recordings are decrypted with
+/// `key`.
+///
+/// Recordings whose windows are already stored, by an earlier run, are skipped, as are deleted
+/// recordings (with an empty path). A recording that cannot be decoded is skipped with a warning
+/// (the audio pass logs the error, see
+/// [`analyze_audio_entries`](crate::audio_analysis::analyze_audio_entries)).
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be read or written.
+#[tracing::instrument(skip(key))]
+pub fn estimate_audio_respiration(data_path: &str, file_name: &str, group_name: &str, key: Option<&Key>) -> Result<(), Box<dyn Error>> {
+    let session = SessionReader::open(data_path, file_name, group_name)?;
+    let entries = session.audio_entries()?;
+    let (times, _) = session.audio_respiration()?;
+    drop(session);
+    // The windows of a recording start at its start time
+    let mut estimated_starts: HashSet<u64> = times.into_iter().collect();
+
+    let file = H5File::append(session_path(data_path, file_name, group_name))?;
+    let group = file.group(group_name)?;
+    let time_column = AppendableColumn::<u64>::open(&group, RESP_TIMES_DATASET)?;
+    let rate_column = AppendableColumn::<f32>::open(&group, RESP_RATE_DATASET)?;
+    let mut estimated = 0;
+    for entry in entries {
+        let path = entry.path.to_string();
+        if path.is_empty() || !estimated_starts.insert(entry.start_time_s) {
+            continue;
+        }
+        let DecodedAudio { samples, sample_rate } = match decode_audio(&path, key) {
+            Ok(audio) => audio,
+            Err(e) => {
+                warn!("Skipping recording {}: {}", path, e);
+                continue;
+            }
+        };
+        let windows = RespirationEstimator::new(entry.start_time_s, sample_rate).push(&samples);
+        time_column.append(&windows.iter().map(|(t_s, _)| *t_s).collect::<Vec<u64>>())?;
+        rate_column.append(&windows.iter().map(|(_, rate_bpm)| *rate_bpm).collect::<Vec<f32>>())?;
+        estimated += windows.iter().filter(|(_, rate_bpm)| !rate_bpm.is_nan()).count();
+    }
+    file.flush()?;
+    info!("Estimated the respiration rate of {} audio windows of {}", estimated, group_name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+    use test_log::test;
+
+    const SAMPLE_RATE: u32 = 16_000;
+
+    /// Deterministic white noise, its amplitude following `envelope` of the time in seconds.
+    fn noise(seconds: f32, envelope: impl Fn(f32) -> f32) -> Vec<i16> {
+        let mut seed = 1u32;
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let t = i as f32 / SAMPLE_RATE as f32;
+                (((seed >> 16) as f32 / 32_768.0 - 1.0) * envelope(t)) as i16
+            })
+            .collect()
+    }
+
+    // Breaths at 12/min are found in a quiet window, but not under a loud sound or in steady noise.
+    #[test]
+    fn test_estimate_breathing() {
+        let breathing = |t: f32| 20.0 + 200.0 * (TAU * 0.2 * t).sin().max(0.0);
+        let mut audio = noise(120.0, breathing);
+        // A loud sound in the second minute
+        audio.extend(noise(60.0, |t| if (20.0..22.0).contains(&t) { 20_000.0 } else { breathing(t) }));
+        audio.extend(noise(60.0, |_| 100.0));
+
+        let mut estimator = RespirationEstimator::new(1_000, SAMPLE_RATE);
+        let windows: Vec<(u64, f32)> = audio.chunks(16_000).flat_map(|chunk| estimator.push(chunk)).collect();
+        let times: Vec<u64> = windows.iter().map(|(t_s, _)| *t_s).collect();
+        assert_eq!(times, [1_000, 1_060, 1_120, 1_180]);
+        for (_, rate_bpm) in &windows[..2] {
+            assert!((rate_bpm - 12.0).abs() < 1.0, "{:?}", windows);
+        }
+        assert!(windows[2].1.is_nan() && windows[3].1.is_nan(), "{:?}", windows);
+    }
+}
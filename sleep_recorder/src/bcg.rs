@@ -0,0 +1,138 @@
+//! Ballistocardiography (BCG): heart and respiration rate estimates from a piezo film under the
+//! mattress.
+//!
+//! The piezo signal is dominated by the slow chest movement of breathing, with the small, sharp
+//! recoil of each heartbeat on top. The two are separated with moving averages, and each rate is
+//! taken from the autocorrelation peaks in its plausible range.
+
+/// Rates estimated from one burst. `None` when the burst is too short or not periodic enough.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BcgEstimate {
+    /// Heart rate in beats per minute.
+    pub heart_rate_bpm: Option<f32>,
+    /// Respiration rate in breaths per minute.
+    pub resp_rate_bpm: Option<f32>,
+}
+
+/// Plausible heart rates during sleep, in bpm.
+const HEART_RATE_RANGE: (f32, f32) = (40.0, 150.0);
+/// Plausible respiration rates during sleep, in breaths per minute.
+pub(crate) const RESP_RATE_RANGE: (f32, f32) = (6.0, 30.0);
+/// Minimum normalized autocorrelation for a rate to be reported.
+const MIN_CORRELATION: f32 = 0.3;
+
+/// Estimates heart and respiration rates from a burst of samples taken at `sample_rate` Hz.
+///
+/// A rate can only be found if the burst covers at least two periods: a few seconds for the heart
+/// rate, and 10-20 s for respiration.
+pub fn estimate(samples: &[f32], sample_rate: f32) -> BcgEstimate {
+    // Breathing: smooth over 1 s
+    let breathing = moving_average(samples, (sample_rate as usize).max(1));
+    // Heartbeats: remove everything slower than ~0.4 s (twice, as breathing is much stronger),
+    // then smooth out noise over 50 ms
+    let high_pass = |signal: &[f32]| {
+        let trend = moving_average(signal, ((0.4 * sample_rate) as usize).max(1));
+        signal.iter().zip(&trend).map(|(s, t)| s - t).collect::<Vec<f32>>()
+    };
+    let beats = moving_average(&high_pass(&high_pass(samples)), ((0.05 * sample_rate) as usize).max(1));
+
+    BcgEstimate {
+        heart_rate_bpm: dominant_rate_bpm(&beats, sample_rate, HEART_RATE_RANGE),
+        resp_rate_bpm: dominant_rate_bpm(&breathing, sample_rate, RESP_RATE_RANGE),
+    }
+}
+
+/// Centered moving average over `window` samples (shrinking at the edges).
+fn moving_average(samples: &[f32], window: usize) -> Vec<f32> {
+    let mut prefix = Vec::with_capacity(samples.len() + 1);
+    prefix.push(0.0f64);
+    for &s in samples {
+        prefix.push(prefix.last().unwrap() + s as f64);
+    }
+    let half = window / 2;
+    (0..samples.len())
+        .map(|i| {
+            let start = i.saturating_sub(half);
+            let end = (i + half + 1).min(samples.len());
+            ((prefix[end] - prefix[start]) / (end - start) as f64) as f32
+        })
+        .collect()
+}
+
+/// Rate (per minute) of the fundamental periodicity of `signal` within `(min, max)` per minute.
+pub(crate) fn dominant_rate_bpm(signal: &[f32], sample_rate: f32, (min_bpm, max_bpm): (f32, f32)) -> Option<f32> {
+    let mean = signal.iter().sum::<f32>() / signal.len().max(1) as f32;
+    let centered: Vec<f32> = signal.iter().map(|s| s - mean).collect();
+    let energy: f32 = centered.iter().map(|s| s * s).sum();
+    if energy == 0.0 {
+        return None;
+    }
+
+    let min_lag = (60.0 / max_bpm * sample_rate).floor() as usize;
+    // Only trust lags covered at least twice by the burst
+    let max_lag = ((60.0 / min_bpm * sample_rate).ceil() as usize).min(centered.len() / 2);
+    if min_lag < 1 || min_lag >= max_lag {
+        return None;
+    }
+
+    let correlation = |lag: usize| {
+        let sum: f32 = centered.iter().zip(&centered[lag..]).map(|(a, b)| a * b).sum();
+        // Compensate for the shrinking overlap at long lags
+        sum / energy * centered.len() as f32 / (centered.len() - lag) as f32
+    };
+    let correlations: Vec<f32> = (min_lag..=max_lag).map(correlation).collect();
+    let peaks: Vec<usize> = (1..correlations.len() - 1)
+        .filter(|&i| correlations[i] >= correlations[i - 1] && correlations[i] >= correlations[i + 1])
+        .collect();
+    let strongest = peaks.iter().map(|&i| correlations[i]).fold(f32::NEG_INFINITY, f32::max);
+    if strongest < MIN_CORRELATION {
+        return None;
+    }
+    // Peaks at multiples of the period are about as strong as the fundamental, so take the
+    // shortest lag that is close to the strongest peak
+    let peak = peaks.into_iter().find(|&i| correlations[i] >= 0.8 * strongest)?;
+    Some(60.0 * sample_rate / (min_lag + peak) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use test_log::test;
+
+    /// Breathing at 15/min with heartbeats at 72 bpm: a short recoil pulse every 0.833 s.
+    fn synthetic_bcg(seconds: f32, sample_rate: f32) -> Vec<f32> {
+        let beat_period = 60.0 / 72.0;
+        (0..(seconds * sample_rate) as usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                let breathing = 50.0 * (2.0 * PI * 0.25 * t).sin();
+                let phase = t % beat_period;
+                let beat = if phase < 0.08 { 5.0 * (PI * phase / 0.08).sin() } else { 0.0 };
+                breathing + beat
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate_heart_and_respiration() {
+        let estimate = estimate(&synthetic_bcg(30.0, 240.0), 240.0);
+        let heart_rate = estimate.heart_rate_bpm.expect("Expected a heart rate");
+        let resp_rate = estimate.resp_rate_bpm.expect("Expected a respiration rate");
+        assert!((heart_rate - 72.0).abs() < 3.0, "Heart rate {}", heart_rate);
+        assert!((resp_rate - 15.0).abs() < 1.0, "Respiration rate {}", resp_rate);
+    }
+
+    #[test]
+    fn test_short_burst_only_estimates_heart_rate() {
+        let estimate = estimate(&synthetic_bcg(4.0, 240.0), 240.0);
+        let heart_rate = estimate.heart_rate_bpm.expect("Expected a heart rate");
+        assert!((heart_rate - 72.0).abs() < 3.0, "Heart rate {}", heart_rate);
+        assert_eq!(estimate.resp_rate_bpm, None);
+    }
+
+    #[test]
+    fn test_flat_signal_has_no_rates() {
+        assert_eq!(estimate(&[1.0; 2400], 240.0), BcgEstimate::default());
+    }
+}
@@ -0,0 +1,63 @@
+//! Minimal driver for the BH1750 ambient light sensor.
+
+use std::error::Error;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+/// BH1750 ambient light sensor in continuous high-resolution mode (1 lx resolution, 120 ms per
+/// measurement).
+pub struct Bh1750<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> Bh1750<I2C>
+where
+    I2C: I2c,
+    I2C::Error: Error + 'static,
+{
+    /// I2C address with the ADDR pin low.
+    pub const ADDRESS_LOW: u8 = 0x23;
+    /// I2C address with the ADDR pin high.
+    #[allow(dead_code)] // Driver API, not used by the recorder yet
+    pub const ADDRESS_HIGH: u8 = 0x5C;
+
+    const POWER_ON: u8 = 0x01;
+    const RESET: u8 = 0x07;
+    const CONTINUOUS_HIGH_RES_MODE: u8 = 0x10;
+
+    /// Powers the sensor on and starts continuous measurement. The first measurement is ready
+    /// after ~180 ms, which this waits for.
+    pub fn new(mut i2c: I2C, address: u8, delay: &mut impl DelayNs) -> Result<Self, Box<dyn Error>> {
+        i2c.write(address, &[Self::POWER_ON])?;
+        i2c.write(address, &[Self::RESET])?;
+        i2c.write(address, &[Self::CONTINUOUS_HIGH_RES_MODE])?;
+        delay.delay_ms(180);
+        Ok(Self { i2c, address })
+    }
+
+    /// Reads the latest illuminance in lux.
+    pub fn read_lux(&mut self) -> Result<f32, Box<dyn Error>> {
+        let mut buffer = [0u8; 2];
+        self.i2c.read(self.address, &mut buffer)?;
+        Ok(raw_to_lux(u16::from_be_bytes(buffer)))
+    }
+}
+
+/// Converts a high-resolution mode reading to lux (default measurement time, accuracy 1.2).
+fn raw_to_lux(raw: u16) -> f32 {
+    raw as f32 / 1.2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_raw_to_lux_datasheet_example() {
+        // Datasheet: 0x83 0x90 in high resolution mode is 28067 lx
+        assert_eq!(raw_to_lux(0x8390).round(), 28067.0);
+    }
+}
@@ -0,0 +1,29 @@
+use std::env;
+
+use tracing::info;
+
+use sleep_recorder::data::export::health;
+use sleep_recorder::data::SessionReader;
+
+/// Exports the sleep periods of a session for health apps: `export_health <session> [output]`,
+/// with the data in SLEEP_DATA_DIR. An output ending in `.json` is a Google Fit session, any
+/// other Apple Health XML; it defaults to `<session>.xml` in SLEEP_DATA_DIR.
+fn main() {
+    // construct a subscriber that prints formatted traces to stdout
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to set global tracing subscriber.");
+
+    let data_path = env::var("SLEEP_DATA_DIR").expect("SLEEP_DATA_DIR not set");
+    let mut args = env::args().skip(1);
+    let group_name = args.next().expect("Usage: export_health <session> [output.xml|output.json]");
+    let output = args.next().unwrap_or_else(|| format!("{}/{}.xml", data_path, group_name));
+
+    let session = SessionReader::open(&data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+    let records = match output.ends_with(".json") {
+        true => health::to_google_fit(&session, &output),
+        false => health::to_apple_health(&session, &output),
+    }
+    .expect("Failed to export session");
+    info!("Exported {records} sleep records of {group_name} to {output}");
+}
@@ -0,0 +1,24 @@
+use std::env;
+
+use tracing::info;
+
+use sleep_recorder::config::Config;
+use sleep_recorder::data::SessionReader;
+use sleep_recorder::influxdb;
+
+/// Writes a recorded session to InfluxDB: `export_influxdb <session>`, with the data file and the
+/// `[influxdb]` server taken from the config file in SLEEP_CONFIG.
+fn main() {
+    // construct a subscriber that prints formatted traces to stdout
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to set global tracing subscriber.");
+
+    let config = Config::from_file(env::var("SLEEP_CONFIG").expect("SLEEP_CONFIG not set")).expect("Failed to load config");
+    assert!(config.influxdb.is_enabled(), "influxdb.url is not set in the config");
+    let group_name = env::args().nth(1).expect("Usage: export_influxdb <session>");
+
+    let session = SessionReader::open(&config.data_path, &config.file_name, &group_name).expect("Failed to open session");
+    let points = influxdb::export_session(&config.influxdb, &session).expect("Failed to export session");
+    info!("Wrote {points} points of {group_name} to {}", config.influxdb.url);
+}
@@ -0,0 +1,33 @@
+use std::env;
+use std::fs;
+
+use tracing::info;
+
+use sleep_recorder::data::SessionReader;
+use sleep_recorder::encryption::{self, Key};
+
+/// Exports a session to a JSON document: `export_json <session> [output.json]`, with the data in
+/// SLEEP_DATA_DIR. The output defaults to `<session>.json` in SLEEP_DATA_DIR. With SLEEP_KEY_FILE
+/// set, the output is encrypted with that key into `<output>.enc`.
+fn main() {
+    // construct a subscriber that prints formatted traces to stdout
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to set global tracing subscriber.");
+
+    let data_path = env::var("SLEEP_DATA_DIR").expect("SLEEP_DATA_DIR not set");
+    let mut args = env::args().skip(1);
+    let group_name = args.next().expect("Usage: export_json <session> [output.json]");
+    let output = args.next().unwrap_or_else(|| format!("{}/{}.json", data_path, group_name));
+
+    let key = env::var("SLEEP_KEY_FILE").ok().map(|path| Key::from_file(path).expect("Failed to read key file"));
+
+    let session = SessionReader::open(&data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+    let json = session.to_json().expect("Failed to export session");
+    fs::write(&output, json).expect("Failed to write export");
+    let output = match key {
+        Some(key) => encryption::encrypt_file(&key, &output).expect("Failed to encrypt export"),
+        None => output,
+    };
+    info!("Exported {group_name} to {output}");
+}
@@ -0,0 +1,27 @@
+use std::env;
+
+use tracing::info;
+
+use sleep_recorder::data;
+
+/// Merges two sessions of the same night, e.g. split by a crash, into the one that started
+/// first: `merge_sessions <session> <session>`, with the data in SLEEP_DATA_DIR. The other
+/// session is deleted.
+fn main() {
+    // construct a subscriber that prints formatted traces to stdout
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to set global tracing subscriber.");
+
+    let data_path = env::var("SLEEP_DATA_DIR").expect("SLEEP_DATA_DIR not set");
+    let mut args = env::args().skip(1);
+    let usage = "Usage: merge_sessions <session> <session>";
+    let first = args.next().expect(usage);
+    let second = args.next().expect(usage);
+
+    let merged = data::merge(&data_path, "sleep_data.h5", &first, &second).expect("Failed to merge sessions");
+    info!(
+        "Merged {} samples into {} ({} duplicates dropped, {} media files moved)",
+        merged.merged_samples, merged.session, merged.duplicate_samples, merged.moved_files
+    );
+}
@@ -1,5 +1,5 @@
 use std::env;
-use sleep_recorder::sleep_tracker;
+use sleep_recorder::prelude::*;
 
 
 #[tokio::main]
@@ -9,9 +9,14 @@ async fn main() {
     // use that subscriber to process traces emitted after this point
     tracing::subscriber::set_global_default(subscriber).expect("Failed to set global tracing subscriber.");
 
-    let data_path = env::var("SLEEP_DATA_DIR").expect("SLEEP_DATA_DIR not set");
+    // SLEEP_CONFIG points at a TOML config file; otherwise use the defaults with SLEEP_DATA_DIR
+    let config = match env::var("SLEEP_CONFIG") {
+        Ok(config_path) => Config::from_file(config_path).expect("Failed to load config"),
+        Err(_) => Config::new(&env::var("SLEEP_DATA_DIR").expect("SLEEP_DATA_DIR not set")),
+    };
 
-    sleep_tracker(&data_path)
+    Recorder::new(config)
+        .run()
         .await
         .expect("Failed to start sleep tracker");
-}
\ No newline at end of file
+}
@@ -1,7 +1,9 @@
 use std::env;
 
 use tracing::info;
-use sleep_recorder::audio_analysis::analyze_audio_entries;
+use sleep_recorder::audio_analysis::{EventClips, SilentAudio, SilentAudioAction};
+use sleep_recorder::encryption::Key;
+use sleep_recorder::prelude::*;
 
 
 #[tokio::main]
@@ -12,8 +14,42 @@ async fn main() {
     tracing::subscriber::set_global_default(subscriber).expect("Failed to set global tracing subscriber.");
 
     let data_path = env::var("SLEEP_DATA_DIR").expect("SLEEP_DATA_DIR not set");
+    // Analyze the session given as the first argument, or the most recent one
+    let group_name = match env::args().nth(1) {
+        Some(group_name) => group_name,
+        None => {
+            let sessions = SleepDataLogger::list_sessions(&data_path, "sleep_data.h5").expect("Failed to list sessions");
+            for session in &sessions {
+                info!("Session {}: {} samples from {} to {}", session.name, session.sample_count, session.start_s, session.end_s);
+            }
+            sessions.last().expect("No sessions recorded").name.clone()
+        }
+    };
 
-    
-    info!("Starting sleep_recorder analysis");
-    analyze_audio_entries(&data_path, "sleep_data.h5", "2025-04-30_22-47-31").expect("Failed to analyze audio entries");
+    // SLEEP_KEY_FILE decrypts encrypted media
+    let key = env::var("SLEEP_KEY_FILE").ok().map(|path| Key::from_file(path).expect("Failed to read key file"));
+
+    // SLEEP_SILENCE_DB deletes the recordings quieter than it throughout, keeping clips of their
+    // sound events
+    let silent_audio = env::var("SLEEP_SILENCE_DB").ok().map(|threshold| SilentAudio {
+        threshold_db: threshold.parse().expect("SLEEP_SILENCE_DB is not a number"),
+        action: SilentAudioAction::Delete { keep_s: 10 },
+    });
+
+    // SLEEP_EVENT_CLIP_S cuts clips of this many seconds before and after each sound event
+    let event_clips = env::var("SLEEP_EVENT_CLIP_S").ok()
+        .map(|margin| EventClips::new(margin.parse().expect("SLEEP_EVENT_CLIP_S is not a number")));
+
+    // SLEEP_AUDIO_RESPIRATION=1 estimates the respiration rate from the breathing heard
+    let respiration = env::var("SLEEP_AUDIO_RESPIRATION").is_ok_and(|value| value == "1");
+
+    info!("Starting sleep_recorder analysis of {group_name}");
+    Pipeline::new()
+        .with_motion(false)
+        .with_respiration(respiration)
+        .with_event_clips(event_clips)
+        .with_silent_audio(silent_audio)
+        .with_key(key)
+        .run(&data_path, "sleep_data.h5", &group_name)
+        .expect("Failed to analyze audio entries");
 }
@@ -2,7 +2,8 @@ use std::env;
 
 use tracing::info;
 
-use sleep_recorder::image_analysis::analyze_motion;
+use sleep_recorder::encryption::Key;
+use sleep_recorder::prelude::*;
 
 #[tokio::main]
 async fn main() {
@@ -12,8 +13,25 @@ async fn main() {
     tracing::subscriber::set_global_default(subscriber).expect("Failed to set global tracing subscriber.");
 
     let data_path = env::var("SLEEP_DATA_DIR").expect("SLEEP_DATA_DIR not set");
+    // Analyze the session given as the first argument, or the most recent one
+    let group_name = match env::args().nth(1) {
+        Some(group_name) => group_name,
+        None => {
+            let sessions = SleepDataLogger::list_sessions(&data_path, "sleep_data.h5").expect("Failed to list sessions");
+            for session in &sessions {
+                info!("Session {}: {} samples from {} to {}", session.name, session.sample_count, session.start_s, session.end_s);
+            }
+            sessions.last().expect("No sessions recorded").name.clone()
+        }
+    };
 
-    
-    info!("Starting sleep_recorder analysis");
-    analyze_motion(&data_path, "sleep_data.h5", "2025-04-17_23-01-13").expect("Failed to analyze image motion");
+    // SLEEP_KEY_FILE decrypts encrypted media
+    let key = env::var("SLEEP_KEY_FILE").ok().map(|path| Key::from_file(path).expect("Failed to read key file"));
+
+    info!("Starting sleep_recorder analysis of {group_name}");
+    Pipeline::new()
+        .with_audio(false)
+        .with_key(key)
+        .run(&data_path, "sleep_data.h5", &group_name)
+        .expect("Failed to analyze image motion");
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use std::env;
+use std::process;
+
+use tracing::{info, warn};
+
+use sleep_recorder::data::{self, SleepDataLogger};
+
+/// Checks the consistency of sessions: `verify_session [--repair] [session...]`, with the data
+/// in SLEEP_DATA_DIR. Without sessions, all of them are checked. With `--repair`, references to
+/// missing files are cleared and mismatched RMS levels trimmed. Exits with status 1 if an issue
+/// is left.
+fn main() {
+    // construct a subscriber that prints formatted traces to stdout
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to set global tracing subscriber.");
+
+    let data_path = env::var("SLEEP_DATA_DIR").expect("SLEEP_DATA_DIR not set");
+    let (flags, mut sessions): (Vec<String>, Vec<String>) = env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let repair = match flags.as_slice() {
+        [] => false,
+        [flag] if flag == "--repair" => true,
+        _ => panic!("Usage: verify_session [--repair] [session...]"),
+    };
+    if sessions.is_empty() {
+        sessions = SleepDataLogger::list_sessions(&data_path, "sleep_data.h5")
+            .expect("Failed to list sessions")
+            .into_iter()
+            .map(|session| session.name)
+            .collect();
+    }
+
+    let mut left = 0;
+    for session in &sessions {
+        match data::verify_session(&data_path, "sleep_data.h5", session, repair) {
+            Ok(report) => left += report.issues.len() - report.repaired,
+            Err(e) => {
+                warn!("Failed to verify session {session}: {e}");
+                left += 1;
+            }
+        }
+    }
+    info!("Verified {} sessions; {} issues left", sessions.len(), left);
+    if left > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,258 @@
+//! Per-device sensor calibration, kept in a small TOML file so that it survives redeploys.
+//!
+//! Unlike the [`Config`](crate::config::Config), which describes the hardware build, the
+//! calibration belongs to the individual devices and is usually measured on them: a BME280 that
+//! reads warm because of the Pi's heat, a thermistor fitted against a reference thermometer, a
+//! microphone's level measured against a sound level meter. The
+//! file is loaded by `SensorReader::new` from [`Config::calibration_path`](crate::config::Config::calibration_path)
+//! and written with [`Calibration::save`]. Every value is optional; missing values leave the
+//! readings unchanged.
+//!
+//! ```toml
+//! [bme280]
+//! temperature_offset_c = -1.2
+//!
+//! [ens160]
+//! co2eq_baseline_ppm = 450
+//!
+//! [thermistor]
+//! steinhart_hart = [0.000226, 0.000375, -0.000000402]
+//!
+//! [extra_thermistors.duvet]
+//! offset_c = 0.3
+//! adc_scale = 1.004
+//!
+//! [microphone]
+//! spl_offset_db = 121.5
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Calibration of all sensors of one device.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Calibration {
+    /// BME280 calibration.
+    pub bme280: Bme280Calibration,
+    /// ENS160 calibration.
+    pub ens160: Ens160Calibration,
+    /// Calibration of the primary thermistor.
+    pub thermistor: ThermistorCalibration,
+    /// Calibration of the additional thermistors, keyed by name.
+    pub extra_thermistors: HashMap<String, ThermistorCalibration>,
+    /// Piezo BCG calibration.
+    pub piezo: PiezoCalibration,
+    /// Microphone calibration, stored in the metadata of the sessions recorded with it.
+    pub microphone: MicrophoneCalibration,
+}
+
+/// BME280 calibration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Bme280Calibration {
+    /// Added to the measured temperature, in °C (e.g. negative to correct self-heating).
+    pub temperature_offset_c: f32,
+}
+
+/// ENS160 calibration.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Ens160Calibration {
+    /// Equivalent CO2 the sensor reads in fresh (outdoor) air, in ppm. Readings are shifted so that
+    /// this baseline reads 400 ppm.
+    pub co2eq_baseline_ppm: Option<u16>,
+}
+
+impl Ens160Calibration {
+    /// Fresh-air eCO2 level the baseline is shifted to.
+    const FRESH_AIR_PPM: i32 = 400;
+
+    /// Equivalent CO2 corrected for the baseline.
+    pub fn co2eq_ppm(&self, measured_ppm: u16) -> u16 {
+        match self.co2eq_baseline_ppm {
+            Some(baseline) => (measured_ppm as i32 - baseline as i32 + Self::FRESH_AIR_PPM).clamp(0, u16::MAX as i32) as u16,
+            None => measured_ppm,
+        }
+    }
+}
+
+/// Calibration of one thermistor.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ThermistorCalibration {
+    /// Steinhart-Hart coefficients `[a, b, c]` fitted for this thermistor, replacing those in the
+    /// configuration.
+    pub steinhart_hart: Option<[f64; 3]>,
+    /// Added to the temperature, in °C.
+    pub offset_c: f32,
+    /// Factor applied to the ADC voltage, correcting the ADC's gain error.
+    pub adc_scale: f32,
+}
+
+impl Default for ThermistorCalibration {
+    fn default() -> Self {
+        Self { steinhart_hart: None, offset_c: 0.0, adc_scale: 1.0 }
+    }
+}
+
+/// Piezo BCG calibration.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct PiezoCalibration {
+    /// Factor applied to the ADC voltage, correcting the ADC's gain error.
+    pub adc_scale: f32,
+}
+
+impl Default for PiezoCalibration {
+    fn default() -> Self {
+        Self { adc_scale: 1.0 }
+    }
+}
+
+/// Microphone calibration, converting the audio levels (dBFS) to approximate sound pressure levels
+/// (dB SPL), so that nights recorded with different microphones can be compared.
+///
+/// # Example
+///
+/// ```
+/// use sleep_recorder::calibration::MicrophoneCalibration;
+/// // A 94 dB SPL sound level calibrator measured at -27.5 dBFS
+/// let microphone = MicrophoneCalibration::from_reference(94.0, -27.5);
+/// assert_eq!(microphone.spl_db(-60.0), Some(61.5));
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct MicrophoneCalibration {
+    /// Sound pressure level of a full-scale signal, in dB SPL: the SPL of a level is this plus its
+    /// dBFS. Uncalibrated if not set.
+    pub spl_offset_db: Option<f32>,
+}
+
+impl MicrophoneCalibration {
+    /// Calibration from a reference sound of `reference_spl_db` (e.g. 94 dB from a sound level
+    /// calibrator, or the reading of a sound level meter next to the microphone) whose recording
+    /// measured `measured_dbfs` (see
+    /// [`recording_level_dbfs`](crate::audio_analysis::recording_level_dbfs)).
+    pub fn from_reference(reference_spl_db: f32, measured_dbfs: f32) -> Self {
+        Self { spl_offset_db: Some(reference_spl_db - measured_dbfs) }
+    }
+
+    /// Approximate sound pressure level of `level_dbfs`, in dB SPL, or `None` if uncalibrated.
+    pub fn spl_db(&self, level_dbfs: f32) -> Option<f32> {
+        self.spl_offset_db.map(|offset_db| level_dbfs + offset_db)
+    }
+}
+
+impl Calibration {
+    /// Loads the calibration from `path`. A missing file is an empty calibration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, or holds unusable values.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read calibration file {}: {}", path.display(), e).into()),
+        };
+        let calibration: Self = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse calibration file {}: {}", path.display(), e))?;
+        calibration.validate()?;
+        Ok(calibration)
+    }
+
+    /// Saves the calibration to `path`. The file is replaced atomically, so a crash never leaves a
+    /// truncated calibration behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the calibration holds unusable values or the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        self.validate()?;
+        let path = path.as_ref();
+        let temp_path = path.with_extension("toml.tmp");
+        std::fs::write(&temp_path, toml::to_string_pretty(self)?)?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Calibration of the thermistor `name` (empty for the primary thermistor).
+    pub fn thermistor(&self, name: &str) -> ThermistorCalibration {
+        match name {
+            "" => self.thermistor.clone(),
+            name => self.extra_thermistors.get(name).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Checks that the scale factors are positive and the offsets finite.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !self.bme280.temperature_offset_c.is_finite() {
+            return Err("bme280.temperature_offset_c must be finite".into());
+        }
+        for (name, thermistor) in std::iter::once(("", &self.thermistor)).chain(self.extra_thermistors.iter().map(|(n, t)| (n.as_str(), t))) {
+            if !thermistor.offset_c.is_finite() || !is_positive(thermistor.adc_scale) {
+                return Err(format!("Thermistor {:?} calibration needs a finite offset_c and a positive adc_scale", name).into());
+            }
+        }
+        if !is_positive(self.piezo.adc_scale) {
+            return Err("piezo.adc_scale must be positive".into());
+        }
+        if self.microphone.spl_offset_db.is_some_and(|offset_db| !offset_db.is_finite()) {
+            return Err("microphone.spl_offset_db must be finite".into());
+        }
+        Ok(())
+    }
+}
+
+/// Whether `value` is a finite, positive number.
+fn is_positive(value: f32) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_missing_file_is_empty_calibration() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let calibration = Calibration::load(dir.path().join("calibration.toml")).expect("Failed to load calibration");
+        assert_eq!(calibration, Calibration::default());
+        assert_eq!(calibration.ens160.co2eq_ppm(612), 612);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("calibration.toml");
+        let mut calibration = Calibration::default();
+        calibration.bme280.temperature_offset_c = -1.2;
+        calibration.ens160.co2eq_baseline_ppm = Some(450);
+        calibration.microphone = MicrophoneCalibration::from_reference(94.0, -26.0);
+        calibration.extra_thermistors.insert("duvet".to_string(), ThermistorCalibration { offset_c: 0.3, ..Default::default() });
+        calibration.save(&path).expect("Failed to save calibration");
+
+        let loaded = Calibration::load(&path).expect("Failed to load calibration");
+        assert_eq!(loaded, calibration);
+        assert_eq!(loaded.ens160.co2eq_ppm(650), 600);
+        assert_eq!(loaded.thermistor("duvet").offset_c, 0.3);
+        assert_eq!(loaded.thermistor("unknown"), ThermistorCalibration::default());
+        assert_eq!(loaded.microphone.spl_db(-50.0), Some(70.0));
+    }
+
+    #[test]
+    fn test_invalid_calibration_is_rejected() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("calibration.toml");
+        std::fs::write(&path, "[piezo]\nadc_scale = 0.0").unwrap();
+        assert!(Calibration::load(&path).is_err());
+        std::fs::write(&path, "[bme280]\ntemperature_offset_c = \"warm\"").unwrap();
+        assert!(Calibration::load(&path).is_err());
+    }
+}
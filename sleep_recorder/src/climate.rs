@@ -0,0 +1,94 @@
+//! Climate metrics derived from the ambient temperature and relative humidity.
+//!
+//! Dashboards rarely show the raw readings: the dew point and absolute humidity tell how much
+//! water is in the air regardless of the temperature, and the comfort index sums up whether the
+//! bedroom is within the conditions recommended for sleep.
+
+/// Magnus formula coefficients over water (Sonntag 1990), valid from −45 to 60 °C.
+const MAGNUS_B: f32 = 17.62;
+const MAGNUS_C_C: f32 = 243.12;
+/// Saturation vapour pressure at 0 °C, in hPa.
+const SATURATION_0C_HPA: f32 = 6.112;
+/// Molar mass of water over the gas constant, in g·K/J (the factors of 100 of vapour pressures in
+/// hPa and relative humidities in percent cancel out).
+const WATER_VAPOUR_FACTOR: f32 = 2.1674;
+
+/// Bedroom temperatures at which sleep is most comfortable, in °C.
+pub const COMFORT_TEMPERATURE_C: (f32, f32) = (16.0, 20.0);
+/// Relative humidities at which sleep is most comfortable, in percent.
+pub const COMFORT_HUMIDITY: (f32, f32) = (40.0, 60.0);
+/// Comfort lost per °C outside [`COMFORT_TEMPERATURE_C`].
+const TEMPERATURE_PENALTY: f32 = 10.0;
+/// Comfort lost per percent outside [`COMFORT_HUMIDITY`].
+const HUMIDITY_PENALTY: f32 = 2.0;
+
+/// Metrics derived from a temperature and humidity reading. `NAN` when either is unavailable.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DerivedClimate {
+    /// Dew point in °C (see [`dew_point_c`]).
+    pub dew_point_c: f32,
+    /// Absolute humidity in g/m³ (see [`absolute_humidity_gm3`]).
+    pub absolute_humidity_gm3: f32,
+    /// Sleep comfort from 0 to 100 (see [`comfort_index`]).
+    pub comfort_index: f32,
+}
+
+impl DerivedClimate {
+    /// Derives the metrics from the temperature in °C and the relative humidity in percent.
+    pub fn new(temperature_c: f32, humidity: f32) -> Self {
+        Self {
+            dew_point_c: dew_point_c(temperature_c, humidity),
+            absolute_humidity_gm3: absolute_humidity_gm3(temperature_c, humidity),
+            comfort_index: comfort_index(temperature_c, humidity),
+        }
+    }
+}
+
+/// Dew point in °C: the temperature at which the air would be saturated (Magnus formula).
+/// `NAN` at 0 % humidity.
+pub fn dew_point_c(temperature_c: f32, humidity: f32) -> f32 {
+    let gamma = (humidity / 100.0).ln() + MAGNUS_B * temperature_c / (MAGNUS_C_C + temperature_c);
+    MAGNUS_C_C * gamma / (MAGNUS_B - gamma)
+}
+
+/// Absolute humidity in g/m³: the mass of water vapour per volume of air.
+pub fn absolute_humidity_gm3(temperature_c: f32, humidity: f32) -> f32 {
+    let saturation_hpa = SATURATION_0C_HPA * (MAGNUS_B * temperature_c / (MAGNUS_C_C + temperature_c)).exp();
+    saturation_hpa * humidity * WATER_VAPOUR_FACTOR / (273.15 + temperature_c)
+}
+
+/// Sleep comfort from 0 to 100: 100 within [`COMFORT_TEMPERATURE_C`] and [`COMFORT_HUMIDITY`],
+/// and less the further the temperature and humidity are outside them.
+pub fn comfort_index(temperature_c: f32, humidity: f32) -> f32 {
+    if temperature_c.is_nan() || humidity.is_nan() {
+        return f32::NAN;
+    }
+    let outside = |value: f32, (low, high): (f32, f32)| (low - value).max(value - high).max(0.0);
+    let penalty = TEMPERATURE_PENALTY * outside(temperature_c, COMFORT_TEMPERATURE_C)
+        + HUMIDITY_PENALTY * outside(humidity, COMFORT_HUMIDITY);
+    (100.0 - penalty).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_derived_climate() {
+        let climate = DerivedClimate::new(20.0, 50.0);
+        assert!((climate.dew_point_c - 9.3).abs() < 0.1, "{climate:?}");
+        assert!((climate.absolute_humidity_gm3 - 8.6).abs() < 0.1, "{climate:?}");
+        assert_eq!(climate.comfort_index, 100.0);
+        // Saturated air condenses at its own temperature
+        assert!((dew_point_c(15.0, 100.0) - 15.0).abs() < 1e-3);
+
+        // 2 °C too warm and 10 % too dry
+        assert_eq!(comfort_index(22.0, 30.0), 60.0);
+        assert_eq!(comfort_index(35.0, 90.0), 0.0);
+
+        let unavailable = DerivedClimate::new(21.0, f32::NAN);
+        assert!(unavailable.dew_point_c.is_nan() && unavailable.absolute_humidity_gm3.is_nan());
+        assert!(unavailable.comfort_index.is_nan());
+    }
+}
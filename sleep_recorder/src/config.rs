@@ -0,0 +1,1599 @@
+//! Runtime configuration for the sleep recorder.
+//!
+//! The configuration is normally loaded from a TOML file. Every field has a default matching
+//! the reference hardware build, so a config file only needs to list the values that differ.
+//!
+//! ```toml
+//! data_path = "/home/pi/sleep_data"
+//! max_session_s = 36000
+//!
+//! [audio]
+//! device = "plughw:1,0"
+//! segment_s = 1800
+//! backend = "native"
+//! format = "wav"
+//!
+//! [camera]
+//! resolution = [640, 480]
+//! jpeg_quality = 60
+//!
+//! [camera.night]
+//! enabled = true
+//! ir_led = { line = 17 }
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::format::{Item, StrftimeItems};
+use serde::{Deserialize, Serialize};
+
+/// Top-level recorder configuration.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// Directory where the data file, images, and audio are stored.
+    pub data_path: String,
+    /// Name of the data file within `data_path`: an HDF5 file, or an SQLite database with
+    /// `storage = "sqlite"`.
+    pub file_name: String,
+    /// Format of the data file (see [`crate::storage`]).
+    pub storage: StorageFormat,
+    /// Whether new HDF5 sessions are stored in `file_name`, or in a file per night (see
+    /// [`night_file_name`](crate::data::night_file_name)). Sessions are found in either layout.
+    pub rotation: FileRotation,
+    /// Chunking and compression of the HDF5 datasets. Not used by SQLite storage.
+    pub compression: CompressionConfig,
+    /// Context stored with each session (see [`SessionMetadata`](crate::data::SessionMetadata)).
+    pub session: SessionConfig,
+    /// Per-device calibration file (see [`crate::calibration`]). Defaults to `calibration.toml`
+    /// in `data_path`.
+    pub calibration_file: Option<String>,
+    /// Maximum session length in seconds; the recorder stops itself after this time.
+    pub max_session_s: u64,
+    /// Resume the most recent session instead of starting a new one if its last sample is at
+    /// most this many seconds old, e.g. after a crash during the night. 0 always starts a new
+    /// session.
+    pub resume_window_s: u64,
+    /// Number of writes queued for the storage writer thread (see
+    /// [`StorageWriter`](crate::storage::StorageWriter)); samples that don't fit while it is
+    /// behind are dropped.
+    pub write_queue_capacity: usize,
+    /// Number of samples the logger buffers before writing them to the data file (1 writes each
+    /// sample right away). Clamped to `1..=`[`MAX_FLUSH_EVERY`](crate::storage::MAX_FLUSH_EVERY).
+    pub flush_every: usize,
+    /// Memory the buffered samples may take, in KiB, before they are written regardless of
+    /// `flush_every`, e.g. with long piezo bursts. Clamped to
+    /// `1..=`[`MAX_BUFFER_KIB`](crate::storage::MAX_BUFFER_KIB).
+    pub max_buffer_kib: usize,
+    /// How long the images and audio of past sessions are kept (see [`crate::retention`]).
+    pub retention: RetentionConfig,
+    /// Live export of the samples to InfluxDB (see [`crate::influxdb`]).
+    pub influxdb: InfluxDbConfig,
+    /// Copy of the recorded data sent to a remote server (see [`crate::remote`]).
+    pub remote: RemoteConfig,
+    /// At-rest encryption of the images and audio (see [`crate::encryption`]).
+    pub encryption: EncryptionConfig,
+    /// Sensor polling interval in seconds.
+    pub sensor_interval_s: u64,
+    /// Longest a single sensor measurement may take, in seconds, before it is abandoned and the
+    /// sample recorded without it. Defaults to `sensor_interval_s`.
+    pub sensor_timeout_s: Option<u64>,
+    /// Sensors of the default hardware set (see [`DEFAULT_SENSORS`]) to leave out, e.g. while a
+    /// camera is unplugged. Their datasets aren't created.
+    pub disabled_sensors: Vec<String>,
+    /// Retries of sensors that fail to initialize, e.g. an ENS160 that is still warming up.
+    pub sensor_init: SensorInitConfig,
+    /// I2C bus used by the I2C sensors, e.g. /dev/i2c-1.
+    pub i2c_bus: String,
+    /// Per-sensor I2C bus overrides, keyed by sensor (see [`I2C_SENSORS`]), for sensors on
+    /// another bus such as a USB I2C adapter.
+    pub i2c_buses: HashMap<String, String>,
+    /// BME280 address and measurement settings.
+    pub bme280: Bme280Config,
+    /// Thermistor ADC channel and conversion parameters.
+    pub thermistor: ThermistorConfig,
+    /// Additional thermistors (e.g. bed surface, under the duvet), each stored in a
+    /// `thermistor_temp_<name>` dataset. They share the thermistor's I2C bus.
+    pub extra_thermistors: Vec<ThermistorConfig>,
+    /// Audio recording settings.
+    pub audio: AudioConfig,
+    /// Camera settings. This camera's images and live motion are stored in the `image_path` and
+    /// `image_motion_live` datasets.
+    pub camera: CameraConfig,
+    /// Additional cameras, each stored in `image_path_<name>` / `image_motion_live_<name>` datasets
+    /// and an `images/<name>/` directory.
+    pub extra_cameras: Vec<CameraConfig>,
+    /// DS18B20 1-Wire temperature probes, each stored in a `probe_temp_<name>` dataset.
+    pub ds18b20: Vec<Ds18b20Config>,
+    /// Read true (NDIR) CO2 from an SCD40/SCD41 on the I2C bus into `co2_ppm`.
+    pub scd4x: bool,
+    /// Read the VOC index from an SGP40 on the I2C bus into `voc_index`.
+    pub sgp40: bool,
+    /// Read ambient light from a BH1750 on the I2C bus into `light_lux`.
+    pub bh1750: bool,
+    /// Serial port of a PMS5003 particulate matter sensor, logged into `pm1_0_ugm3`,
+    /// `pm2_5_ugm3`, and `pm10_ugm3`.
+    pub pms5003: Option<String>,
+    /// HX711 load-cell amplifier under the bed, logged into `bed_weight_kg` and `bed_occupied`.
+    pub hx711: Option<Hx711Config>,
+    /// Piezo film under the mattress on the MCP3424, sampled in bursts for ballistocardiography.
+    pub piezo: Option<PiezoConfig>,
+    /// GPIO line of a PIR motion sensor, logged into `pir_motion`.
+    pub pir: Option<GpioLineConfig>,
+    /// GPIO-switched outputs (relays for a fan, humidifier, IR illuminator, ...), controlled
+    /// through [`Recorder::actuators`](crate::Recorder::actuators). State changes are logged in
+    /// the `actuator_event_*` datasets.
+    pub actuators: Vec<ActuatorConfig>,
+    /// Replace all sensors and the audio device with simulated ones producing plausible signals,
+    /// to run without hardware. Requires the `simulation` feature.
+    pub simulation: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_path: ".".to_string(),
+            file_name: "sleep_data.h5".to_string(),
+            storage: StorageFormat::default(),
+            rotation: FileRotation::default(),
+            compression: CompressionConfig::default(),
+            session: SessionConfig::default(),
+            calibration_file: None,
+            max_session_s: 60 * 60 * 10,
+            resume_window_s: 30 * 60,
+            write_queue_capacity: crate::storage::DEFAULT_QUEUE_CAPACITY,
+            flush_every: crate::storage::DEFAULT_FLUSH_EVERY,
+            max_buffer_kib: crate::storage::DEFAULT_MAX_BUFFER_KIB,
+            retention: RetentionConfig::default(),
+            influxdb: InfluxDbConfig::default(),
+            remote: RemoteConfig::default(),
+            encryption: EncryptionConfig::default(),
+            sensor_interval_s: 5,
+            sensor_timeout_s: None,
+            disabled_sensors: Vec::new(),
+            sensor_init: SensorInitConfig::default(),
+            i2c_bus: "/dev/i2c-1".to_string(),
+            i2c_buses: HashMap::new(),
+            bme280: Bme280Config::default(),
+            thermistor: ThermistorConfig::default(),
+            extra_thermistors: Vec::new(),
+            audio: AudioConfig::default(),
+            camera: CameraConfig::default(),
+            extra_cameras: Vec::new(),
+            ds18b20: Vec::new(),
+            scd4x: false,
+            sgp40: false,
+            bh1750: false,
+            pms5003: None,
+            hx711: None,
+            piezo: None,
+            pir: None,
+            actuators: Vec::new(),
+            simulation: false,
+        }
+    }
+}
+
+/// Format of the data file.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageFormat {
+    /// One HDF5 group per session, with a dataset per field.
+    #[default]
+    Hdf5,
+    /// One SQLite database with a table per kind of data, keyed by session. Requires the
+    /// `sqlite` feature.
+    Sqlite,
+}
+
+/// Layout of the HDF5 sessions in `data_path`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileRotation {
+    /// Every session is a group of `file_name`.
+    #[default]
+    None,
+    /// Every night gets its own file, e.g. `sleep_data_2025-05-01.h5`, so that a corrupted file
+    /// loses at most a night, and finished nights can be synced as they are.
+    Nightly,
+}
+
+/// Chunking and compression of the HDF5 datasets, applied when a dataset is created. The
+/// top-level values apply to every dataset, and `datasets` overrides them for some, keyed by
+/// dataset name, or by a prefix ending in `*` (e.g. `"sensor_error_*"`). An exact name takes
+/// precedence over a prefix, and a longer prefix over a shorter one; only the most specific
+/// override applies.
+///
+/// Datasets created by upgrades of older sessions and by the analyses use the defaults.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Values per chunk. Larger chunks compress better, but every flush rewrites the last chunk.
+    pub chunk: usize,
+    /// Compression filter.
+    pub codec: CompressionCodec,
+    /// Compression level, 0-9. Not used by `lzf`.
+    pub level: u8,
+    /// Shuffle the bytes of the values before compressing them, which helps numbers that change
+    /// slowly.
+    pub shuffle: bool,
+    /// Per-dataset overrides.
+    pub datasets: HashMap<String, DatasetCompression>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self { chunk: 1024, codec: CompressionCodec::Deflate, level: 6, shuffle: false, datasets: HashMap::new() }
+    }
+}
+
+impl CompressionConfig {
+    /// Checks that the chunk sizes are non-zero and the levels 0-9.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let resolved = std::iter::once(("compression".to_string(), self.for_dataset("")))
+            .chain(self.datasets.keys().map(|name| (format!("compression.datasets.{name}"), self.for_dataset(name))));
+        for (section, compression) in resolved {
+            if compression.chunk == 0 || compression.level > 9 {
+                return Err(format!("{section}.chunk must be greater than 0 and {section}.level 0-9").into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Settings of the dataset `name`.
+    pub fn for_dataset(&self, name: &str) -> Compression {
+        let default = Compression { chunk: self.chunk, codec: self.codec, level: self.level, shuffle: self.shuffle };
+        let overrides = match self.datasets.get(name) {
+            Some(overrides) => overrides,
+            None => {
+                let longest_prefix = self.datasets.iter()
+                    .filter_map(|(key, overrides)| key.strip_suffix('*').map(|prefix| (prefix, overrides)))
+                    .filter(|(prefix, _)| name.starts_with(prefix))
+                    .max_by_key(|(prefix, _)| prefix.len());
+                match longest_prefix {
+                    Some((_, overrides)) => overrides,
+                    None => return default,
+                }
+            }
+        };
+        Compression {
+            chunk: overrides.chunk.unwrap_or(default.chunk),
+            codec: overrides.codec.unwrap_or(default.codec),
+            level: overrides.level.unwrap_or(default.level),
+            shuffle: overrides.shuffle.unwrap_or(default.shuffle),
+        }
+    }
+}
+
+/// Overrides of [`CompressionConfig`] for some datasets. Unset values are the top-level ones.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct DatasetCompression {
+    /// Values per chunk.
+    pub chunk: Option<usize>,
+    /// Compression filter.
+    pub codec: Option<CompressionCodec>,
+    /// Compression level, 0-9.
+    pub level: Option<u8>,
+    /// Whether the bytes of the values are shuffled before compressing them.
+    pub shuffle: Option<bool>,
+}
+
+/// Chunking and compression of one dataset, as resolved by [`CompressionConfig::for_dataset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compression {
+    /// Values per chunk.
+    pub chunk: usize,
+    /// Compression filter.
+    pub codec: CompressionCodec,
+    /// Compression level, 0-9.
+    pub level: u8,
+    /// Whether the bytes of the values are shuffled before compressing them.
+    pub shuffle: bool,
+}
+
+/// HDF5 compression filter.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionCodec {
+    /// Uncompressed, the cheapest to flush.
+    None,
+    /// Deflate (gzip), readable everywhere.
+    #[default]
+    Deflate,
+    /// LZF, much faster than deflate but compressing less. Requires the `lzf` feature, and the
+    /// LZF filter plugin to read the file outside of this crate (h5py includes it).
+    Lzf,
+    /// Blosc with LZ4. Requires the `blosc` feature, and the Blosc filter plugin to read the file
+    /// outside of this crate (e.g. hdf5plugin in Python).
+    BloscLz4,
+    /// Blosc with Zstandard, compressing better than `blosc_lz4` but slower. Requires the
+    /// `blosc` feature and the Blosc filter plugin.
+    BloscZstd,
+}
+
+/// Context of the recordings, stored with each session. Empty values are stored as empty strings.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Identifier of the recording device, e.g. its hostname.
+    pub device_id: String,
+    /// Where the device records, e.g. "bedroom".
+    pub location: String,
+    /// Label of the person being recorded.
+    pub subject: String,
+    /// Free-form notes, e.g. "new mattress".
+    pub notes: String,
+}
+
+/// Retention of the media files of past sessions, in days since the session started. The data
+/// file is always kept. 0 keeps the files forever.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Days to keep the images, raw images, and motion clips of a session.
+    pub images_days: u64,
+    /// Days to keep the audio recordings of a session.
+    pub audio_days: u64,
+}
+
+impl RetentionConfig {
+    /// Whether any media files expire.
+    pub fn is_enabled(&self) -> bool {
+        self.images_days > 0 || self.audio_days > 0
+    }
+}
+
+/// InfluxDB v2 server the samples are written to, in line protocol, while they are recorded.
+/// Requires the `influxdb` feature. An empty `url` disables the export.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct InfluxDbConfig {
+    /// Base URL of the server, e.g. "http://influxdb.local:8086".
+    pub url: String,
+    /// Organization owning the bucket.
+    pub org: String,
+    /// Bucket the samples are written to.
+    pub bucket: String,
+    /// API token with write access to the bucket.
+    pub token: String,
+    /// Measurement of the samples. Live audio levels and actuator events are written to
+    /// `<measurement>_audio` and `<measurement>_actuator`.
+    pub measurement: String,
+    /// Timeout of a write request in seconds.
+    pub timeout_s: u64,
+}
+
+impl Default for InfluxDbConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            org: String::new(),
+            bucket: String::new(),
+            token: String::new(),
+            measurement: "sleep".to_string(),
+            timeout_s: 5,
+        }
+    }
+}
+
+impl InfluxDbConfig {
+    /// Whether samples are exported.
+    pub fn is_enabled(&self) -> bool {
+        !self.url.is_empty()
+    }
+}
+
+/// Remote server receiving a copy of the recorded data, as JSON batches posted over HTTP.
+/// Requires the `remote` feature. An empty `url` disables it.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// URL the batches are posted to, e.g. "https://backup.local/sleep/batches".
+    pub url: String,
+    /// Bearer token sent with each request; empty to send none.
+    pub token: String,
+    /// Samples per batch.
+    pub batch_size: usize,
+    /// Timeout of a request in seconds.
+    pub timeout_s: u64,
+    /// Seconds to wait after a failed request before sending the spooled batches again.
+    pub retry_s: u64,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self { url: String::new(), token: String::new(), batch_size: 60, timeout_s: 10, retry_s: 60 }
+    }
+}
+
+impl RemoteConfig {
+    /// Whether the data is sent to a remote server.
+    pub fn is_enabled(&self) -> bool {
+        !self.url.is_empty()
+    }
+}
+
+/// At-rest encryption of the recorded media.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// File holding the 256-bit key as 64 hexadecimal digits (see
+    /// [`Key::from_file`](crate::encryption::Key::from_file)). Keep it off the SD card. `None`
+    /// stores the media unencrypted.
+    pub key_file: Option<String>,
+}
+
+/// BME280 settings. Measurements are taken in forced mode (one conversion per poll, sleeping in
+/// between), which avoids self-heating skewing the temperature.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Bme280Config {
+    /// I2C address: 0x76 (SDO low) or 0x77 (SDO high).
+    pub address: u8,
+    /// Temperature oversampling: 1, 2, 4, 8, or 16.
+    pub temperature_oversampling: u8,
+    /// Pressure oversampling: 1, 2, 4, 8, or 16.
+    pub pressure_oversampling: u8,
+    /// Humidity oversampling: 1, 2, 4, 8, or 16.
+    pub humidity_oversampling: u8,
+    /// IIR filter coefficient: 0 (off), 2, 4, 8, or 16.
+    pub iir_filter: u8,
+}
+
+impl Default for Bme280Config {
+    fn default() -> Self {
+        Self {
+            address: 0x76,
+            temperature_oversampling: 1,
+            pressure_oversampling: 1,
+            humidity_oversampling: 1,
+            iir_filter: 0,
+        }
+    }
+}
+
+impl Bme280Config {
+    /// Checks that the address and filter settings are supported by the sensor.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !matches!(self.address, 0x76 | 0x77) {
+            return Err(format!("bme280.address must be 0x76 or 0x77, got {:#x}", self.address).into());
+        }
+        let oversampling = [self.temperature_oversampling, self.pressure_oversampling, self.humidity_oversampling];
+        if oversampling.iter().any(|o| ![1, 2, 4, 8, 16].contains(o)) {
+            return Err("bme280 oversampling must be 1, 2, 4, 8, or 16".into());
+        }
+        if ![0, 2, 4, 8, 16].contains(&self.iir_filter) {
+            return Err("bme280.iir_filter must be 0, 2, 4, 8, or 16".into());
+        }
+        Ok(())
+    }
+}
+
+/// A thermistor on an MCP342x channel, in a voltage divider with a fixed resistor (the ADC
+/// measures the voltage across the fixed resistor).
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ThermistorConfig {
+    /// Name of the thermistor, used in the dataset name. Required for `extra_thermistors`; leave
+    /// empty for the primary thermistor, stored in `thermistor_temp`.
+    pub name: String,
+    /// I2C address of the MCP342x, 0x68-0x6F.
+    pub address: u8,
+    /// ADC input channel, 1-4.
+    pub channel: u8,
+    /// PGA gain: 1, 2, 4, or 8.
+    pub gain: u8,
+    /// Fixed divider resistor in Ohms.
+    pub divider_ohms: f32,
+    /// Divider supply voltage in Volts.
+    pub supply_voltage: f32,
+    /// Steinhart-Hart coefficients `[a, b, c]`, with `1/T = a + b ln(R) + c ln(R)^3` (T in Kelvin).
+    pub steinhart_hart: [f64; 3],
+}
+
+impl Default for ThermistorConfig {
+    fn default() -> Self {
+        // Reference build; coefficients fitted in
+        // https://docs.google.com/spreadsheets/d/1Nf47ojSvB1wB5JmTSs-cXLMhxmIMcvHLitLAx047UdE/edit?pli=1&gid=1211676988#gid=1211676988
+        Self {
+            name: String::new(),
+            address: 0x68,
+            channel: 3,
+            gain: 1,
+            divider_ohms: 3200.0,
+            supply_voltage: 5.3,
+            steinhart_hart: [0.0002264321654, 0.0003753456578, -0.0000004022657641],
+        }
+    }
+}
+
+impl ThermistorConfig {
+    /// Checks the ADC settings, and that the coefficients give a plausible temperature when the
+    /// thermistor matches the divider resistor.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !(0x68..=0x6F).contains(&self.address) {
+            return Err(format!("thermistor.address must be 0x68-0x6F, got {:#x}", self.address).into());
+        }
+        validate_adc("thermistor", self.channel, self.gain)?;
+        if self.divider_ohms <= 0.0 || self.supply_voltage <= 0.0 {
+            return Err("thermistor.divider_ohms and thermistor.supply_voltage must be greater than 0".into());
+        }
+        let [a, b, c] = self.steinhart_hart;
+        let ln_r = (self.divider_ohms as f64).ln();
+        let temperature_c = 1.0 / (a + b * ln_r + c * ln_r.powi(3)) - 273.15;
+        if !(-55.0..=150.0).contains(&temperature_c) {
+            return Err(format!(
+                "thermistor.steinhart_hart gives {:.1}°C at {} Ohms; check the coefficients",
+                temperature_c, self.divider_ohms
+            ).into());
+        }
+        Ok(())
+    }
+}
+
+/// Checks an MCP342x channel (1-4) and gain (1, 2, 4, or 8).
+fn validate_adc(section: &str, channel: u8, gain: u8) -> Result<(), Box<dyn Error>> {
+    if !(1..=4).contains(&channel) || ![1, 2, 4, 8].contains(&gain) {
+        return Err(format!("{section}.channel must be 1-4 and {section}.gain 1, 2, 4, or 8").into());
+    }
+    Ok(())
+}
+
+/// Sensors that are always used unless listed in [`Config::disabled_sensors`]. The other sensors
+/// are only used when configured.
+pub const DEFAULT_SENSORS: [&str; 6] = ["bme280", "ens160", "thermistor", "camera", "mmwave", "system_stats"];
+
+/// Sensors that can be moved to another bus with [`Config::i2c_buses`].
+pub const I2C_SENSORS: [&str; 7] = ["bme280", "ens160", "thermistor", "scd4x", "sgp40", "bh1750", "piezo"];
+
+/// Sensor initialization retries. Sensors are opened in the background while the session runs;
+/// one that fails to initialize is retried with exponential backoff, and once its budget is spent
+/// it is marked failed and retried every `failed_retry_s`. A sensor whose measurements keep failing
+/// is reopened the same way.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct SensorInitConfig {
+    /// Wait before the first retry, in milliseconds. Doubles after every failed attempt.
+    pub initial_backoff_ms: u64,
+    /// Longest wait between attempts, in milliseconds.
+    pub max_backoff_ms: u64,
+    /// Time after which a sensor is marked failed, in seconds.
+    pub budget_s: u64,
+    /// Wait between attempts to open a failed sensor, in seconds. 0 gives up on it for the
+    /// session. Cameras use `camera.hotplug_retry_s` instead.
+    pub failed_retry_s: u64,
+    /// Reopen a sensor after this many consecutive failed measurements (e.g. after it lost power).
+    /// 0 never reopens sensors.
+    pub reinit_after_failures: u32,
+}
+
+impl Default for SensorInitConfig {
+    fn default() -> Self {
+        Self { initial_backoff_ms: 250, max_backoff_ms: 4000, budget_s: 20, failed_retry_s: 60, reinit_after_failures: 5 }
+    }
+}
+
+impl SensorInitConfig {
+    /// Checks that the backoff is non-zero and does not exceed its maximum.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.initial_backoff_ms == 0 || self.max_backoff_ms < self.initial_backoff_ms {
+            return Err("sensor_init.initial_backoff_ms must be greater than 0 and at most sensor_init.max_backoff_ms".into());
+        }
+        Ok(())
+    }
+
+    /// Wait before the first retry.
+    pub fn initial_backoff(&self) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms)
+    }
+
+    /// Longest wait between attempts.
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms)
+    }
+
+    /// Time after which a sensor is marked failed.
+    pub fn budget(&self) -> Duration {
+        Duration::from_secs(self.budget_s)
+    }
+
+    /// Wait between attempts to open a failed sensor; `None` if failed sensors are given up.
+    pub fn failed_retry(&self) -> Option<Duration> {
+        (self.failed_retry_s > 0).then(|| Duration::from_secs(self.failed_retry_s))
+    }
+}
+
+/// A DS18B20 1-Wire temperature probe, read through the kernel w1 sysfs interface.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Ds18b20Config {
+    /// 1-Wire device ID, as listed in /sys/bus/w1/devices (e.g. 28-0316a2799aff).
+    pub id: String,
+    /// Name of the probe, used in the dataset name (e.g. mattress).
+    pub name: String,
+}
+
+/// An HX711 load-cell amplifier, bit-banged over two GPIO lines.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Hx711Config {
+    /// GPIO character device, e.g. /dev/gpiochip0.
+    #[serde(default = "GpioLineConfig::default_chip")]
+    pub chip: String,
+    /// Line offset of the data (DOUT) pin.
+    pub dout_line: u32,
+    /// Line offset of the clock (PD_SCK) pin.
+    pub sck_line: u32,
+    /// Raw reading with the bed empty.
+    #[serde(default)]
+    pub offset: i32,
+    /// Raw counts per kilogram, from weighing a known load.
+    pub scale: f32,
+    /// Weight above which the bed is considered occupied, in kilograms.
+    #[serde(default = "Hx711Config::default_occupied_kg")]
+    pub occupied_kg: f32,
+    /// Readings per sample; their median is used.
+    #[serde(default = "Hx711Config::default_samples")]
+    pub samples: usize,
+}
+
+impl Hx711Config {
+    fn default_occupied_kg() -> f32 {
+        20.0
+    }
+
+    fn default_samples() -> usize {
+        5
+    }
+}
+
+/// A piezo film strip on an MCP3424 channel. It can share the chip with the thermistors (by default
+/// at 0x68, on channel 3), on another channel. Each polling cycle a burst is captured at 240 SPS and
+/// stored in `piezo_bcg_mv`, with heart and respiration rate estimates in `piezo_heart_rate_bpm`
+/// and `piezo_resp_rate_bpm`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct PiezoConfig {
+    /// I2C address of the MCP3424, 0x68-0x6F.
+    pub address: u8,
+    /// ADC input channel, 1-4.
+    pub channel: u8,
+    /// PGA gain: 1, 2, 4, or 8.
+    pub gain: u8,
+    /// Burst length in seconds. Heart rate needs a few seconds, respiration 10-20 s; the burst
+    /// must be shorter than the polling interval.
+    pub burst_s: f32,
+}
+
+impl Default for PiezoConfig {
+    fn default() -> Self {
+        Self {
+            address: 0x68,
+            channel: 1,
+            gain: 8,
+            burst_s: 4.0,
+        }
+    }
+}
+
+/// Audio recording settings.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// ALSA capture device (e.g. plughw:1,0). Ignored when `device_match` is set.
+    pub device: String,
+    /// Case-insensitive substring of the capture card's name (e.g. "Arducam"). When set, the
+    /// device is looked up at startup, so it is found however the USB devices enumerate.
+    pub device_match: Option<String>,
+    /// Length of each recording segment in seconds. Shorter segments mean more files but finer
+    /// seeking and less audio lost if a recording fails.
+    pub segment_s: u64,
+    /// Start each segment this many seconds before the previous one ends, so there is no gap at
+    /// rollover (0 records segments back to back). Needs a capture device that can be opened
+    /// twice, e.g. an ALSA `dsnoop` device.
+    pub rollover_overlap_s: u64,
+    /// How audio is captured.
+    pub backend: AudioBackend,
+    /// File format of the recordings. The native backend only records WAV.
+    pub format: AudioFormat,
+    /// Target bitrate in kbit/s for lossy formats (MP3, Opus).
+    pub bitrate_kbps: u32,
+    /// Window in seconds of the live RMS level written during recording; 0 disables live metering.
+    pub live_meter_s: u64,
+    /// Compute the RMS and band levels of the recordings while they are captured (see
+    /// [`StreamAnalyzer`](crate::audio_analysis::StreamAnalyzer)), so that the offline analysis
+    /// needn't decode them again.
+    pub live_analysis: bool,
+    /// Frequency weighting of the RMS levels, live and offline. Stored in the session metadata,
+    /// so that the offline analysis weights the recordings the same way.
+    pub weighting: AudioWeighting,
+    /// Classify the live audio with an ONNX model (see [`ClassifierConfig`]). Requires the `ml`
+    /// feature.
+    pub classifier: Option<ClassifierConfig>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            device: "plughw:/dev/snd/by-id/usb-Arducam_Technology_Co.__Ltd._USB_Camera_SN0001-02".to_string(),
+            device_match: None,
+            segment_s: 30 * 60,
+            rollover_overlap_s: 0,
+            backend: AudioBackend::default(),
+            format: AudioFormat::default(),
+            bitrate_kbps: 128,
+            live_meter_s: 5,
+            live_analysis: true,
+            weighting: AudioWeighting::default(),
+            classifier: None,
+        }
+    }
+}
+
+/// ONNX model classifying the live audio, e.g. into snoring, breathing, speech, and noise. The
+/// model gets the features of the recording's windows (see the `sound_classifier` module) and
+/// outputs a score per label; changes of the top label are logged in the `events` dataset.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ClassifierConfig {
+    /// Path of the `.onnx` model file.
+    pub model: String,
+    /// Labels of the model's outputs, in order.
+    pub labels: Vec<String>,
+    /// Lowest score (0-1) of the top label for a window to be classified; windows below it keep
+    /// the previous class.
+    pub min_score: f32,
+}
+
+impl Default for ClassifierConfig {
+    fn default() -> Self {
+        Self {
+            model: String::new(),
+            labels: ["snore", "breathing", "speech", "noise"].map(str::to_string).to_vec(),
+            min_score: 0.5,
+        }
+    }
+}
+
+/// Audio capture backend.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioBackend {
+    /// Spawn `ffmpeg` for each segment and record MP3.
+    #[default]
+    Ffmpeg,
+    /// Capture in-process through ALSA and record WAV. Requires the `native-audio` feature.
+    Native,
+}
+
+/// Camera settings.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct CameraConfig {
+    /// Name of the camera, used in dataset and directory names. Required for `extra_cameras`.
+    pub name: String,
+    /// V4L2 capture device.
+    pub device: String,
+    /// Capture resolution as `[width, height]`.
+    pub resolution: [u32; 2],
+    /// V4L2 pixel format (FourCC). MJPG and YUYV are supported.
+    pub format: String,
+    /// Frame interval as `[numerator, denominator]` seconds, e.g. `[1, 30]` for 30 fps.
+    pub frame_interval: [u32; 2],
+    /// Quality of the saved JPEG images, 1-100.
+    pub jpeg_quality: u8,
+    /// Low-light capture settings.
+    pub night: NightModeConfig,
+    /// Only measure motion within this region (e.g. the bed), so that pets or curtains elsewhere
+    /// in the frame are ignored. The whole frame is used if not set.
+    pub motion_roi: Option<MotionRoi>,
+    /// Record a short clip when motion is detected, in addition to the periodic stills.
+    pub motion_clip: Option<MotionClipConfig>,
+    /// Timestamp drawn onto the saved images.
+    pub overlay: OverlayConfig,
+    /// Also save each frame without the overlay in a `raw/` subdirectory. Offline motion analysis
+    /// uses these, as the changing timestamp otherwise counts as motion.
+    pub save_raw: bool,
+    /// Size the images are saved at as `[width, height]`, e.g. `[640, 360]`, to save storage.
+    /// Motion is still measured on the full captured frame. Unset saves the captured size.
+    pub save_resolution: Option<[u32; 2]>,
+    /// Size of the JPEG thumbnail of each frame stored in the data file as `[width, height]`, e.g.
+    /// `[160, 90]`, so that a night can be reviewed without the image directory. Unset stores none.
+    pub thumbnail_size: Option<[u32; 2]>,
+    /// If the camera cannot be opened within `sensor_init.budget_s` (USB cameras often enumerate
+    /// late after a cold boot) or is unplugged during the night, try to attach it again every this
+    /// many seconds. 0 gives up on the camera for the session.
+    pub hotplug_retry_s: u64,
+}
+
+/// Timestamp overlay drawn onto the saved images.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct OverlayConfig {
+    /// Draw the overlay.
+    pub enabled: bool,
+    /// TrueType font file. The embedded DejaVu Sans Bold is used if not set.
+    pub font: Option<String>,
+    /// Text height in pixels.
+    pub size_px: f32,
+    /// Text color as `[r, g, b]`.
+    pub color: [u8; 3],
+    /// Position of the top left corner of the text as `[x, y]` pixels.
+    pub position: [i32; 2],
+    /// `strftime`-style format of the local time, e.g. "%H:%M:%S".
+    pub format: String,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            font: None,
+            size_px: 36.0,
+            color: [255, 255, 0],
+            position: [20, 20],
+            format: "%I:%M:%S %p".to_string(),
+        }
+    }
+}
+
+/// Short video clips recorded when the camera detects motion, saved as MJPEG streams (`.mjpeg`,
+/// playable with e.g. `ffplay` or VLC).
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct MotionClipConfig {
+    /// Record a clip when the image motion (mean absolute pixel difference, 0-255) reaches this.
+    pub threshold: f32,
+    /// Clip length in seconds. Sensors are not polled while a clip is recorded, so this must be
+    /// less than `sensor_interval_s`.
+    pub duration_s: f32,
+}
+
+impl Default for MotionClipConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 10.0,
+            duration_s: 3.0,
+        }
+    }
+}
+
+/// Region of interest for motion measurement, in pixels of the capture resolution.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum MotionRoi {
+    /// Rectangle with its top left corner at (`x`, `y`).
+    Rect { x: u32, y: u32, width: u32, height: u32 },
+    /// Polygon through `points` (`[x, y]`), closed from the last point back to the first.
+    Polygon { points: Vec<[u32; 2]> },
+}
+
+/// Audio recording file format.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// MP3 (lossy).
+    #[default]
+    Mp3,
+    /// 16-bit PCM WAV (lossless, large).
+    Wav,
+    /// FLAC (lossless, compressed).
+    Flac,
+    /// Opus in an Ogg container (lossy, small).
+    Opus,
+}
+
+impl AudioFormat {
+    /// File extension used for recordings in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Opus => "opus",
+        }
+    }
+}
+
+/// Frequency weighting of audio levels.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioWeighting {
+    /// No weighting: levels in dBFS.
+    #[default]
+    Z,
+    /// A-weighting (IEC 61672), following perceived loudness: levels in dBFS(A), which differ
+    /// from dBA by the microphone's sensitivity. For comparing against nighttime dBA limits.
+    A,
+}
+
+impl AudioWeighting {
+    /// Name of the weighting, as in the configuration and the session metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioWeighting::Z => "z",
+            AudioWeighting::A => "a",
+        }
+    }
+
+    /// Parses a [`name`](Self::name). An empty name, stored by sessions recorded before the
+    /// weighting was, is [`AudioWeighting::Z`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "" | "z" => Some(AudioWeighting::Z),
+            "a" => Some(AudioWeighting::A),
+            _ => None,
+        }
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            device: "/dev/video0".to_string(),
+            resolution: [1280, 720],
+            format: "MJPG".to_string(),
+            frame_interval: [1, 30],
+            jpeg_quality: 75,
+            night: NightModeConfig::default(),
+            motion_roi: None,
+            motion_clip: None,
+            overlay: OverlayConfig::default(),
+            save_raw: false,
+            save_resolution: None,
+            thumbnail_size: None,
+            hotplug_retry_s: 30,
+        }
+    }
+}
+
+impl CameraConfig {
+    /// Checks that the camera settings are usable.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !matches!(self.format.as_str(), "MJPG" | "YUYV") {
+            return Err(format!("camera.format must be MJPG or YUYV, got {:?}", self.format).into());
+        }
+        if self.resolution.contains(&0) || self.frame_interval.contains(&0) {
+            return Err("camera.resolution and camera.frame_interval must be non-zero".into());
+        }
+        if let Some([save_width, save_height]) = self.save_resolution {
+            if save_width == 0 || save_height == 0 || save_width > self.resolution[0] || save_height > self.resolution[1] {
+                return Err("camera.save_resolution must be non-zero and at most camera.resolution".into());
+            }
+        }
+        if self.thumbnail_size.is_some_and(|size| size.contains(&0)) {
+            return Err("camera.thumbnail_size must be non-zero".into());
+        }
+        if !(1..=100).contains(&self.jpeg_quality) {
+            return Err("camera.jpeg_quality must be between 1 and 100".into());
+        }
+        if self.night.exposure_100us <= 0 {
+            return Err("camera.night.exposure_100us must be greater than 0".into());
+        }
+        if self.night.auto_lux.is_some_and(|lux| lux <= 0.0) {
+            return Err("camera.night.auto_lux must be greater than 0".into());
+        }
+        let [width, height] = self.resolution;
+        match &self.motion_roi {
+            Some(MotionRoi::Rect { x, y, width: w, height: h })
+                if *w == 0 || *h == 0 || x + w > width || y + h > height =>
+            {
+                return Err("camera.motion_roi must be a non-empty rectangle within camera.resolution".into());
+            }
+            Some(MotionRoi::Polygon { points }) => {
+                if points.len() < 3 || points.first() == points.last() {
+                    return Err("camera.motion_roi needs at least 3 points, without repeating the first point at the end".into());
+                }
+                if points.iter().any(|&[x, y]| x >= width || y >= height) {
+                    return Err("camera.motion_roi points must be within camera.resolution".into());
+                }
+            }
+            Some(MotionRoi::Rect { .. }) | None => {}
+        }
+        if self.motion_clip.as_ref().is_some_and(|clip| clip.threshold <= 0.0 || clip.duration_s <= 0.0) {
+            return Err("camera.motion_clip.threshold and camera.motion_clip.duration_s must be greater than 0".into());
+        }
+        if self.overlay.size_px <= 0.0 {
+            return Err("camera.overlay.size_px must be greater than 0".into());
+        }
+        if StrftimeItems::new(&self.overlay.format).any(|item| item == Item::Error) {
+            return Err(format!("camera.overlay.format is not a valid time format: {:?}", self.overlay.format).into());
+        }
+        Ok(())
+    }
+}
+
+/// Low-light (night) capture settings for the camera.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct NightModeConfig {
+    /// Start the session in night mode.
+    pub enabled: bool,
+    /// Manual exposure time at night, in units of 100 µs (V4L2 `exposure_absolute`).
+    pub exposure_100us: i32,
+    /// Sensor gain at night.
+    pub gain: i32,
+    /// Sensor gain restored when leaving night mode; left unchanged if not set.
+    pub day_gain: Option<i32>,
+    /// GPIO line driving an IR illuminator, switched on only while capturing at night.
+    pub ir_led: Option<GpioLineConfig>,
+    /// Time to let the IR illuminator settle before capturing, in milliseconds.
+    pub ir_warmup_ms: u64,
+    /// Switch night mode automatically from the ambient light sensor: on below this many lux,
+    /// off again above twice this. Requires `bh1750`.
+    pub auto_lux: Option<f32>,
+}
+
+impl Default for NightModeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            exposure_100us: 1000,
+            gain: 100,
+            day_gain: None,
+            ir_led: None,
+            ir_warmup_ms: 200,
+            auto_lux: None,
+        }
+    }
+}
+
+/// A GPIO output line, addressed by character device and line offset.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct GpioLineConfig {
+    /// GPIO character device, e.g. /dev/gpiochip0.
+    #[serde(default = "GpioLineConfig::default_chip")]
+    pub chip: String,
+    /// Line offset on the chip (the BCM GPIO number on a Raspberry Pi).
+    pub line: u32,
+}
+
+impl GpioLineConfig {
+    fn default_chip() -> String {
+        "/dev/gpiochip0".to_string()
+    }
+}
+
+/// A named GPIO output driving a relay or MOSFET.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ActuatorConfig {
+    /// Name of the actuator, used to switch it and in the logged events (e.g. fan).
+    pub name: String,
+    /// GPIO character device, e.g. /dev/gpiochip0.
+    #[serde(default = "GpioLineConfig::default_chip")]
+    pub chip: String,
+    /// Line offset on the chip (the BCM GPIO number on a Raspberry Pi).
+    pub line: u32,
+    /// The output is on when the line is low, as on most relay boards.
+    #[serde(default)]
+    pub active_low: bool,
+}
+
+impl Config {
+    /// Creates a default configuration storing data in `data_path`.
+    pub fn new(data_path: &str) -> Self {
+        Self { data_path: data_path.to_string(), ..Self::default() }
+    }
+
+    /// Loads and validates a configuration from a TOML file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {:?}: {}", path, e))?;
+        Self::from_toml_str(&contents)
+    }
+
+    /// Parses and validates a configuration from a TOML string.
+    pub fn from_toml_str(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let config: Self = toml::from_str(contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that the configuration values are usable.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.sensor_interval_s == 0 {
+            return Err("sensor_interval_s must be greater than 0".into());
+        }
+        if self.sensor_timeout_s == Some(0) {
+            return Err("sensor_timeout_s must be greater than 0".into());
+        }
+        if self.write_queue_capacity == 0 {
+            return Err("write_queue_capacity must be greater than 0".into());
+        }
+        if self.storage == StorageFormat::Sqlite && self.file_name.ends_with(".h5") {
+            return Err("file_name must not be an HDF5 file with storage = \"sqlite\", e.g. use sleep_data.db".into());
+        }
+        if self.storage == StorageFormat::Sqlite && self.rotation != FileRotation::None {
+            return Err("rotation is only supported with storage = \"hdf5\"".into());
+        }
+        if let Some(sensor) = self.i2c_buses.keys().find(|k| !I2C_SENSORS.contains(&k.as_str())) {
+            return Err(format!("Unknown sensor {:?} in i2c_buses; expected one of {:?}", sensor, I2C_SENSORS).into());
+        }
+        if let Some(sensor) = self.disabled_sensors.iter().find(|s| !DEFAULT_SENSORS.contains(&s.as_str())) {
+            return Err(format!("Unknown sensor {:?} in disabled_sensors; expected one of {:?}", sensor, DEFAULT_SENSORS).into());
+        }
+        self.sensor_init.validate()?;
+        self.compression.validate()?;
+        if self.influxdb.is_enabled() && (self.influxdb.bucket.is_empty() || self.influxdb.measurement.is_empty()) {
+            return Err("influxdb.bucket and influxdb.measurement must be set when influxdb.url is".into());
+        }
+        if self.influxdb.timeout_s == 0 {
+            return Err("influxdb.timeout_s must be greater than 0".into());
+        }
+        if self.remote.batch_size == 0 || self.remote.timeout_s == 0 {
+            return Err("remote.batch_size and remote.timeout_s must be greater than 0".into());
+        }
+        self.bme280.validate()?;
+        let mut adc_inputs = std::collections::HashSet::new();
+        for thermistor in std::iter::once(&self.thermistor).chain(&self.extra_thermistors) {
+            thermistor.validate()?;
+            if !adc_inputs.insert((thermistor.address, thermistor.channel)) {
+                return Err(format!(
+                    "Thermistor ADC {:#x} channel {} is configured more than once",
+                    thermistor.address, thermistor.channel
+                ).into());
+            }
+        }
+        if self.audio.segment_s == 0 {
+            return Err("audio.segment_s must be greater than 0".into());
+        }
+        if self.audio.backend == AudioBackend::Native && self.audio.format != AudioFormat::Wav {
+            return Err("The native audio backend only records WAV; set audio.format = \"wav\"".into());
+        }
+        if self.audio.rollover_overlap_s >= self.audio.segment_s {
+            return Err("audio.rollover_overlap_s must be less than audio.segment_s".into());
+        }
+        if self.audio.bitrate_kbps == 0 {
+            return Err("audio.bitrate_kbps must be greater than 0".into());
+        }
+        if let Some(classifier) = &self.audio.classifier {
+            if classifier.model.is_empty() || classifier.labels.is_empty() {
+                return Err("audio.classifier.model and audio.classifier.labels must be set".into());
+            }
+            if !(0.0..=1.0).contains(&classifier.min_score) {
+                return Err("audio.classifier.min_score must be between 0 and 1".into());
+            }
+        }
+        for camera in std::iter::once(&self.camera).chain(&self.extra_cameras) {
+            camera.validate()?;
+            if camera.night.auto_lux.is_some() && !self.bh1750 {
+                return Err("camera.night.auto_lux needs the ambient light sensor; set bh1750 = true".into());
+            }
+            if camera.motion_clip.as_ref().is_some_and(|clip| clip.duration_s >= self.sensor_interval_s as f32) {
+                return Err("camera.motion_clip.duration_s must be less than sensor_interval_s".into());
+            }
+        }
+        if let Some(hx711) = &self.hx711 {
+            if hx711.scale == 0.0 || hx711.samples == 0 {
+                return Err("hx711.scale and hx711.samples must be non-zero".into());
+            }
+        }
+        if let Some(piezo) = &self.piezo {
+            if !(0x68..=0x6F).contains(&piezo.address) {
+                return Err(format!("piezo.address must be 0x68-0x6F, got {:#x}", piezo.address).into());
+            }
+            validate_adc("piezo", piezo.channel, piezo.gain)?;
+            if self.i2c_bus_for("piezo") == self.i2c_bus_for("thermistor") && adc_inputs.contains(&(piezo.address, piezo.channel)) {
+                return Err(format!("Piezo ADC {:#x} channel {} is already used by a thermistor", piezo.address, piezo.channel).into());
+            }
+            if piezo.burst_s <= 0.0 || piezo.burst_s >= self.sensor_interval_s as f32 {
+                return Err("piezo.burst_s must be greater than 0 and less than sensor_interval_s".into());
+            }
+        }
+        let timeout_s = self.sensor_timeout().as_secs_f32();
+        let longest_measurement_s = std::iter::once(&self.camera).chain(&self.extra_cameras)
+            .filter_map(|camera| camera.motion_clip.as_ref().map(|clip| clip.duration_s))
+            .chain(self.piezo.as_ref().map(|piezo| piezo.burst_s))
+            .fold(0.0, f32::max);
+        if longest_measurement_s >= timeout_s {
+            return Err("sensor_timeout_s must be longer than piezo.burst_s and camera.motion_clip.duration_s".into());
+        }
+        validate_names("extra_cameras", self.extra_cameras.iter().map(|c| c.name.as_str()))?;
+        validate_names("extra_thermistors", self.extra_thermistors.iter().map(|t| t.name.as_str()))?;
+        validate_names("ds18b20", self.ds18b20.iter().map(|p| p.name.as_str()))?;
+        validate_names("actuators", self.actuators.iter().map(|a| a.name.as_str()))?;
+        Ok(())
+    }
+
+    /// Whether `sensor` (one of [`DEFAULT_SENSORS`]) is used, i.e. not in `disabled_sensors`.
+    pub fn is_enabled(&self, sensor: &str) -> bool {
+        !self.disabled_sensors.iter().any(|s| s == sensor)
+    }
+
+    /// I2C bus of `sensor` (one of [`I2C_SENSORS`]): its override in `i2c_buses`, or `i2c_bus`.
+    pub fn i2c_bus_for(&self, sensor: &str) -> &str {
+        self.i2c_buses.get(sensor).unwrap_or(&self.i2c_bus)
+    }
+
+    /// Path of the calibration file: `calibration_file`, or `calibration.toml` in `data_path`.
+    pub fn calibration_path(&self) -> String {
+        match &self.calibration_file {
+            Some(path) => path.clone(),
+            None => format!("{}/calibration.toml", self.data_path),
+        }
+    }
+
+    /// Maximum session length.
+    pub fn max_session(&self) -> Duration {
+        Duration::from_secs(self.max_session_s)
+    }
+
+    /// Sensor polling interval.
+    pub fn sensor_interval(&self) -> Duration {
+        Duration::from_secs(self.sensor_interval_s)
+    }
+
+    /// Timeout of a single sensor measurement.
+    pub fn sensor_timeout(&self) -> Duration {
+        Duration::from_secs(self.sensor_timeout_s.unwrap_or(self.sensor_interval_s))
+    }
+}
+
+/// Checks that names used in dataset and directory names are non-empty, safe, and unique.
+fn validate_names<'a>(section: &str, names: impl Iterator<Item = &'a str>) -> Result<(), Box<dyn Error>> {
+    let mut seen = std::collections::HashSet::new();
+    for name in names {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("{} names must be non-empty and use only [A-Za-z0-9_-], got {:?}", section, name).into());
+        }
+        if !seen.insert(name) {
+            return Err(format!("Duplicate {} name {:?}", section, name).into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config = Config::from_toml_str(r#"
+            data_path = "/data"
+            [audio]
+            device = "plughw:1,0"
+        "#).expect("Failed to parse config");
+        assert_eq!(config.data_path, "/data");
+        assert_eq!(config.audio.device, "plughw:1,0");
+        assert_eq!(config.audio.segment_s, AudioConfig::default().segment_s);
+        assert_eq!(config.file_name, "sleep_data.h5");
+        assert_eq!(config.audio.backend, AudioBackend::Ffmpeg);
+    }
+
+    #[test]
+    fn test_night_mode_ir_led_defaults_chip() {
+        let config = Config::from_toml_str(r#"
+            [camera.night]
+            enabled = true
+            ir_led = { line = 17 }
+        "#).expect("Failed to parse config");
+        assert!(config.camera.night.enabled);
+        assert_eq!(config.camera.night.gain, NightModeConfig::default().gain);
+        assert_eq!(config.camera.night.ir_led, Some(GpioLineConfig { chip: "/dev/gpiochip0".to_string(), line: 17 }));
+    }
+
+    #[test]
+    fn test_zero_interval_is_rejected() {
+        assert!(Config::from_toml_str("sensor_interval_s = 0").is_err());
+    }
+
+    #[test]
+    fn test_native_backend_requires_wav() {
+        assert!(Config::from_toml_str("[audio]\nbackend = \"native\"").is_err());
+        let config = Config::from_toml_str("[audio]\nbackend = \"native\"\nformat = \"wav\"")
+            .expect("Failed to parse config");
+        assert_eq!(config.audio.format.extension(), "wav");
+    }
+
+    #[test]
+    fn test_extra_cameras_need_unique_names() {
+        let config = Config::from_toml_str(r#"
+            [[extra_cameras]]
+            name = "bedside"
+            device = "/dev/video2"
+        "#).expect("Failed to parse config");
+        assert_eq!(config.extra_cameras[0].name, "bedside");
+        assert_eq!(config.extra_cameras[0].resolution, CameraConfig::default().resolution);
+
+        assert!(Config::from_toml_str("[[extra_cameras]]\ndevice = \"/dev/video2\"").is_err());
+        assert!(Config::from_toml_str("[[extra_cameras]]\nname = \"a\"\n[[extra_cameras]]\nname = \"a\"").is_err());
+    }
+
+    #[test]
+    fn test_ds18b20_probes() {
+        let config = Config::from_toml_str(r#"
+            [[ds18b20]]
+            id = "28-0316a2799aff"
+            name = "mattress"
+        "#).expect("Failed to parse config");
+        assert_eq!(config.ds18b20[0].id, "28-0316a2799aff");
+        assert!(Config::from_toml_str("[[ds18b20]]\nid = \"28-1\"\nname = \"under bed\"").is_err());
+    }
+
+    #[test]
+    fn test_auto_night_mode_requires_light_sensor() {
+        assert!(Config::from_toml_str("[camera.night]\nauto_lux = 5.0").is_err());
+        let config = Config::from_toml_str("bh1750 = true\n[camera.night]\nauto_lux = 5.0")
+            .expect("Failed to parse config");
+        assert_eq!(config.camera.night.auto_lux, Some(5.0));
+    }
+
+    #[test]
+    fn test_hx711_defaults() {
+        let config = Config::from_toml_str("[hx711]\ndout_line = 5\nsck_line = 6\nscale = 2000.0")
+            .expect("Failed to parse config");
+        let hx711 = config.hx711.expect("Expected an hx711 section");
+        assert_eq!(hx711.chip, "/dev/gpiochip0");
+        assert_eq!(hx711.offset, 0);
+        assert_eq!(hx711.samples, 5);
+        assert!(Config::from_toml_str("[hx711]\ndout_line = 5\nsck_line = 6\nscale = 0.0").is_err());
+    }
+
+    #[test]
+    fn test_piezo_burst_must_fit_interval() {
+        let config = Config::from_toml_str("[piezo]\nchannel = 2").expect("Failed to parse config");
+        assert_eq!(config.piezo.expect("Expected a piezo section").burst_s, 4.0);
+        assert!(Config::from_toml_str("[piezo]\nburst_s = 5.0").is_err());
+        assert!(Config::from_toml_str("sensor_interval_s = 30\n[piezo]\nburst_s = 20.0").is_ok());
+        assert!(Config::from_toml_str("[piezo]\ngain = 3").is_err());
+        assert!(Config::from_toml_str("[piezo]\naddress = 0x20").is_err());
+    }
+
+    #[test]
+    fn test_piezo_shares_adc_with_thermistors() {
+        // Same chip and channel as the primary thermistor
+        assert!(Config::from_toml_str("[piezo]\nchannel = 3").is_err());
+        assert!(Config::from_toml_str("[piezo]\nchannel = 3\naddress = 0x69").is_ok());
+        assert!(Config::from_toml_str("[[extra_thermistors]]\nname = \"duvet\"\nchannel = 1\n[piezo]\nchannel = 1").is_err());
+        // Another bus is another chip
+        assert!(Config::from_toml_str("[i2c_buses]\npiezo = \"/dev/i2c-3\"\n[piezo]\nchannel = 3").is_ok());
+    }
+
+    #[test]
+    fn test_bme280_settings() {
+        let config = Config::from_toml_str("[bme280]\naddress = 0x77\npressure_oversampling = 16\niir_filter = 4")
+            .expect("Failed to parse config");
+        assert_eq!(config.bme280.address, 0x77);
+        assert_eq!(config.bme280.humidity_oversampling, 1);
+        assert!(Config::from_toml_str("[bme280]\naddress = 0x78").is_err());
+        assert!(Config::from_toml_str("[bme280]\niir_filter = 3").is_err());
+    }
+
+    #[test]
+    fn test_i2c_bus_overrides() {
+        let config = Config::from_toml_str("i2c_bus = \"/dev/i2c-0\"\n[i2c_buses]\nscd4x = \"/dev/i2c-3\"")
+            .expect("Failed to parse config");
+        assert_eq!(config.i2c_bus_for("bme280"), "/dev/i2c-0");
+        assert_eq!(config.i2c_bus_for("scd4x"), "/dev/i2c-3");
+        assert!(Config::from_toml_str("[i2c_buses]\nbme680 = \"/dev/i2c-3\"").is_err());
+    }
+
+    #[test]
+    fn test_thermistor_settings() {
+        assert!(Config::default().thermistor.validate().is_ok());
+        let config = Config::from_toml_str("[thermistor]\nchannel = 1\ndivider_ohms = 10000.0\nsteinhart_hart = [1.129e-3, 2.341e-4, 8.775e-8]")
+            .expect("Failed to parse config");
+        assert_eq!(config.thermistor.address, 0x68);
+        assert!(Config::from_toml_str("[thermistor]\nchannel = 5").is_err());
+        assert!(Config::from_toml_str("[thermistor]\nsteinhart_hart = [0.0, 0.0, 0.0]").is_err());
+    }
+
+    #[test]
+    fn test_extra_thermistors() {
+        let config = Config::from_toml_str(r#"
+            [[extra_thermistors]]
+            name = "duvet"
+            channel = 1
+            [[extra_thermistors]]
+            name = "room"
+            address = 0x69
+            channel = 3
+        "#).expect("Failed to parse config");
+        assert_eq!(config.extra_thermistors.len(), 2);
+        assert_eq!(config.extra_thermistors[1].divider_ohms, ThermistorConfig::default().divider_ohms);
+        // Same ADC input as the primary thermistor
+        assert!(Config::from_toml_str("[[extra_thermistors]]\nname = \"duvet\"").is_err());
+        assert!(Config::from_toml_str("[[extra_thermistors]]\nchannel = 1").is_err());
+    }
+
+    #[test]
+    fn test_camera_motion_roi() {
+        let config = Config::from_toml_str(r#"
+            [camera.motion_roi]
+            shape = "polygon"
+            points = [[100, 200], [1100, 150], [1200, 700], [50, 700]]
+        "#).expect("Failed to parse config");
+        assert!(matches!(config.camera.motion_roi, Some(MotionRoi::Polygon { ref points }) if points.len() == 4));
+        let config = Config::from_toml_str("[camera.motion_roi]\nshape = \"rect\"\nx = 0\ny = 360\nwidth = 1280\nheight = 360")
+            .expect("Failed to parse config");
+        assert_eq!(config.camera.motion_roi, Some(MotionRoi::Rect { x: 0, y: 360, width: 1280, height: 360 }));
+        assert!(Config::from_toml_str("[camera.motion_roi]\nshape = \"rect\"\nx = 1000\ny = 0\nwidth = 400\nheight = 100").is_err());
+        assert!(Config::from_toml_str("[camera.motion_roi]\nshape = \"polygon\"\npoints = [[0, 0], [10, 10]]").is_err());
+    }
+
+    #[test]
+    fn test_camera_motion_clip() {
+        let config = Config::from_toml_str("[camera.motion_clip]\nthreshold = 6.5")
+            .expect("Failed to parse config");
+        assert_eq!(config.camera.motion_clip, Some(MotionClipConfig { threshold: 6.5, duration_s: 3.0 }));
+        assert!(Config::from_toml_str("sensor_interval_s = 5\n[camera.motion_clip]\nduration_s = 5.0").is_err());
+        assert!(Config::from_toml_str("[camera.motion_clip]\nthreshold = 0.0").is_err());
+    }
+
+    #[test]
+    fn test_camera_save_resolution() {
+        let config = Config::from_toml_str("[camera]\nsave_resolution = [640, 360]").expect("Failed to parse config");
+        assert_eq!(config.camera.save_resolution, Some([640, 360]));
+        assert!(Config::from_toml_str("[camera]\nsave_resolution = [1920, 1080]").is_err());
+        assert!(Config::from_toml_str("[camera]\nsave_resolution = [640, 0]").is_err());
+    }
+
+    #[test]
+    fn test_camera_thumbnail_size() {
+        assert_eq!(Config::default().camera.thumbnail_size, None);
+        let config = Config::from_toml_str("[camera]\nthumbnail_size = [160, 90]").expect("Failed to parse config");
+        assert_eq!(config.camera.thumbnail_size, Some([160, 90]));
+        assert!(Config::from_toml_str("[camera]\nthumbnail_size = [0, 90]").is_err());
+    }
+
+    #[test]
+    fn test_camera_overlay() {
+        let config = Config::from_toml_str("[camera.overlay]\ncolor = [255, 255, 255]\nformat = \"%H:%M\"")
+            .expect("Failed to parse config");
+        assert!(config.camera.overlay.enabled);
+        assert_eq!(config.camera.overlay.color, [255, 255, 255]);
+        assert_eq!(config.camera.overlay.size_px, 36.0);
+        assert!(Config::from_toml_str("[camera.overlay]\nformat = \"%H:%Q\"").is_err());
+        assert!(Config::from_toml_str("[camera.overlay]\nsize_px = 0.0").is_err());
+    }
+
+    #[test]
+    fn test_sensor_timeout() {
+        let config = Config::from_toml_str("sensor_interval_s = 10").expect("Failed to parse config");
+        assert_eq!(config.sensor_timeout(), Duration::from_secs(10));
+        let config = Config::from_toml_str("sensor_timeout_s = 2").expect("Failed to parse config");
+        assert_eq!(config.sensor_timeout(), Duration::from_secs(2));
+        assert!(Config::from_toml_str("sensor_timeout_s = 0").is_err());
+        assert!(Config::from_toml_str("sensor_timeout_s = 2\n[piezo]\nburst_s = 3.0").is_err());
+    }
+
+    #[test]
+    fn test_disabled_sensors() {
+        let config = Config::from_toml_str("disabled_sensors = [\"camera\", \"ens160\"]").expect("Failed to parse config");
+        assert!(!config.is_enabled("camera"));
+        assert!(!config.is_enabled("ens160"));
+        assert!(config.is_enabled("bme280"));
+        assert!(Config::from_toml_str("disabled_sensors = [\"scd4x\"]").is_err());
+    }
+
+    #[test]
+    fn test_sensor_init() {
+        let config = Config::from_toml_str("[sensor_init]\nbudget_s = 60").expect("Failed to parse config");
+        assert_eq!(config.sensor_init.budget(), Duration::from_secs(60));
+        assert_eq!(config.sensor_init.initial_backoff(), Duration::from_millis(250));
+        assert_eq!(config.sensor_init.failed_retry(), Some(Duration::from_secs(60)));
+        let config = Config::from_toml_str("[sensor_init]\nfailed_retry_s = 0").expect("Failed to parse config");
+        assert_eq!(config.sensor_init.failed_retry(), None);
+        assert!(Config::from_toml_str("[sensor_init]\ninitial_backoff_ms = 0").is_err());
+        assert!(Config::from_toml_str("[sensor_init]\ninitial_backoff_ms = 5000\nmax_backoff_ms = 1000").is_err());
+    }
+
+    #[test]
+    fn test_session() {
+        assert_eq!(Config::default().session, SessionConfig::default());
+        assert_eq!(Config::default().resume_window_s, 1800);
+        let config = Config::from_toml_str("[session]\nlocation = \"bedroom\"\nnotes = \"new mattress\"")
+            .expect("Failed to parse config");
+        assert_eq!(config.session.location, "bedroom");
+        assert_eq!(config.session.notes, "new mattress");
+        assert_eq!(config.session.subject, "");
+    }
+
+    #[test]
+    fn test_retention() {
+        assert!(!Config::default().retention.is_enabled());
+        let config = Config::from_toml_str("[retention]\nimages_days = 14").expect("Failed to parse config");
+        assert_eq!(config.retention, RetentionConfig { images_days: 14, audio_days: 0 });
+        assert!(config.retention.is_enabled());
+    }
+
+    #[test]
+    fn test_influxdb() {
+        assert!(!Config::default().influxdb.is_enabled());
+        let config = Config::from_toml_str("[influxdb]\nurl = \"http://localhost:8086\"\nbucket = \"sleep\"")
+            .expect("Failed to parse config");
+        assert!(config.influxdb.is_enabled());
+        assert_eq!(config.influxdb.measurement, "sleep");
+        assert!(Config::from_toml_str("[influxdb]\nurl = \"http://localhost:8086\"").is_err());
+    }
+
+    #[test]
+    fn test_remote() {
+        assert!(!Config::default().remote.is_enabled());
+        let config = Config::from_toml_str("[remote]\nurl = \"https://backup.local/sleep\"\nbatch_size = 12")
+            .expect("Failed to parse config");
+        assert!(config.remote.is_enabled());
+        assert_eq!(config.remote.batch_size, 12);
+        assert_eq!(config.remote.retry_s, 60);
+        assert!(Config::from_toml_str("[remote]\nbatch_size = 0").is_err());
+    }
+
+    #[test]
+    fn test_encryption() {
+        assert_eq!(Config::default().encryption.key_file, None);
+        let config = Config::from_toml_str("[encryption]\nkey_file = \"/media/usb/sleep.key\"")
+            .expect("Failed to parse config");
+        assert_eq!(config.encryption.key_file.as_deref(), Some("/media/usb/sleep.key"));
+    }
+
+    #[test]
+    fn test_calibration_path() {
+        let config = Config::from_toml_str("data_path = \"/data\"").expect("Failed to parse config");
+        assert_eq!(config.calibration_path(), "/data/calibration.toml");
+        let config = Config::from_toml_str("calibration_file = \"/etc/sleep/calibration.toml\"").expect("Failed to parse config");
+        assert_eq!(config.calibration_path(), "/etc/sleep/calibration.toml");
+    }
+
+    #[test]
+    fn test_actuators() {
+        let config = Config::from_toml_str("[[actuators]]\nname = \"fan\"\nline = 24\nactive_low = true")
+            .expect("Failed to parse config");
+        assert_eq!(config.actuators, vec![ActuatorConfig {
+            name: "fan".to_string(),
+            chip: "/dev/gpiochip0".to_string(),
+            line: 24,
+            active_low: true,
+        }]);
+        assert!(Config::from_toml_str("[[actuators]]\nname = \"fan\"\nline = 24\n[[actuators]]\nname = \"fan\"\nline = 25").is_err());
+    }
+
+    #[test]
+    fn test_storage() {
+        assert_eq!(Config::default().storage, StorageFormat::Hdf5);
+        let config = Config::from_toml_str("storage = \"sqlite\"\nfile_name = \"sleep_data.db\"")
+            .expect("Failed to parse config");
+        assert_eq!(config.storage, StorageFormat::Sqlite);
+        // The default file name is an HDF5 file
+        assert!(Config::from_toml_str("storage = \"sqlite\"").is_err());
+        assert!(Config::from_toml_str("storage = \"parquet\"").is_err());
+    }
+
+    #[test]
+    fn test_rotation() {
+        assert_eq!(Config::default().rotation, FileRotation::None);
+        let config = Config::from_toml_str("rotation = \"nightly\"").expect("Failed to parse config");
+        assert_eq!(config.rotation, FileRotation::Nightly);
+        assert!(Config::from_toml_str("rotation = \"nightly\"\nstorage = \"sqlite\"\nfile_name = \"sleep_data.db\"").is_err());
+    }
+
+    #[test]
+    fn test_compression() {
+        let config = Config::from_toml_str(concat!(
+            "[compression]\nchunk = 4096\nlevel = 4\n",
+            "[compression.datasets.image_path]\ncodec = \"none\"\n",
+            "[compression.datasets.\"sensor_*\"]\nchunk = 256\n",
+            "[compression.datasets.\"sensor_error_*\"]\ncodec = \"none\"\n",
+        )).expect("Failed to parse config");
+        let compression = &config.compression;
+        assert_eq!(compression.for_dataset("temperature"), Compression { chunk: 4096, codec: CompressionCodec::Deflate, level: 4, shuffle: false });
+        assert_eq!(compression.for_dataset("image_path").codec, CompressionCodec::None);
+        assert_eq!(compression.for_dataset("image_path").chunk, 4096);
+        assert_eq!(compression.for_dataset("sensor_ok_bme280").chunk, 256);
+        // The longest prefix wins
+        assert_eq!(compression.for_dataset("sensor_error_bme280"), Compression { chunk: 4096, codec: CompressionCodec::None, level: 4, shuffle: false });
+        assert!(Config::from_toml_str("[compression]\nlevel = 10").is_err());
+        assert!(Config::from_toml_str("[compression.datasets.timestamp]\nchunk = 0").is_err());
+        assert!(Config::from_toml_str("[compression]\ncodec = \"zip\"").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_camera_format_is_rejected() {
+        assert!(Config::from_toml_str("[camera]\nformat = \"H264\"").is_err());
+        assert!(Config::from_toml_str("[camera]\nformat = \"YUYV\"").is_ok());
+    }
+}
@@ -7,83 +7,276 @@
 //! This also defines the `SleepData`` and `AudioRecording`` structs, which represent
 //! the data entries for sleep and audio recordings, respectively.
 //!
+//! Each series is stored in an [`AppendableColumn`], which analysis modules also use for the
+//! datasets they derive from a session.
+//!
+//! Recorded sessions are read back with `SessionReader`, converted to other formats with the
+//! [`export`] module, and merged with [`merge`] when a crash split a night in two. Their
+//! consistency is checked with [`verify_session`]. The session being recorded is found with
+//! `SleepDataLogger::current_session`. The units of
+//! each dataset are stored in its attributes (see [`units`]). Numeric fields are also summarized
+//! per minute and per 5 minutes (see [`summary`]).
 
 #![allow(non_local_definitions)]
 
+use std::fmt;
+use std::path::Path;
 use std::time::Duration;
 use std::{collections::HashMap, str::FromStr};
 use std::error::Error;
 use std::result::Result;
 
-use chrono::Local;
+use chrono::{Local, NaiveDateTime};
 use dfrobot_c1001::C1001SleepData;
-use hdf5::types::VarLenArray;
+use hdf5::types::{FloatSize, IntSize, TypeDescriptor, VarLenArray};
 use hdf5::Dataset;
 use hdf5::{types::VarLenUnicode, File, H5Type};
+use serde::{Deserialize, Serialize};
+
+use tracing::{info, trace, warn};
+
+use crate::audio_analysis::{BANDS, BAND_TIMES_DATASET, EVENT_CLIP_CATEGORY};
+use crate::audio_respiration::{RESP_RATE_DATASET, RESP_TIMES_DATASET};
+use crate::bcg::BcgEstimate;
+use crate::calibration::MicrophoneCalibration;
+use crate::climate::DerivedClimate;
+use crate::config::CompressionConfig;
+use crate::noise_stats::NoiseStats;
+use crate::pms5003::PmMeasurement;
+use crate::retention::Media;
+use crate::sensirion::Scd4xMeasurement;
+use crate::sensor::SystemStats;
+use crate::storage::journal::Journal;
+use crate::storage::{clamp_buffering, StorageBackend, DEFAULT_FLUSH_EVERY, DEFAULT_MAX_BUFFER_KIB};
+
+pub mod export;
+mod column;
+mod integrity;
+mod merge;
+pub mod summary;
+pub mod units;
 
-use tracing::{info, warn};
+pub use column::AppendableColumn;
+pub use integrity::{verify_session, IntegrityIssue, IntegrityReport};
+pub use merge::{merge, MergeSummary};
+
+use summary::{Aggregate, Interval, Summarizer, Summary};
 
 /// Data entry for a sleep recording session. Uses a builder pattern for construction.
-#[derive(Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SleepData {
     /// Timestamp of the data entry in seconds since UNIX epoch.
     pub timestamp_s: u64,
     /// Ambient temperature in degrees Celsius.
     pub temperature_c: f32,
-    /// Ambient pressure in hPa. Currently not functional.
+    /// Ambient pressure in hPa.
     pub pressure: f32,
     /// Ambient humidity in percent RH.
     pub humidity: f32,
+    /// Dew point in degrees Celsius, derived from the temperature and humidity. NaN when either is
+    /// unavailable or implausible.
+    pub dew_point_c: f32,
+    /// Absolute humidity in g/m³, derived from the temperature and humidity. NaN when either is
+    /// unavailable or implausible.
+    pub absolute_humidity_gm3: f32,
+    /// Sleep comfort (0-100, see [`comfort_index`](crate::climate::comfort_index)), derived from
+    /// the temperature and humidity. NaN when either is unavailable or implausible.
+    pub comfort_index: f32,
     /// Equivalent CO2 concentration in ppm.
     pub co2eq_ppm: u16,
+    /// CO2 concentration in ppm measured by an NDIR sensor (SCD4x). 0 when unavailable.
+    pub co2_ppm: u16,
     /// Total volatile organic compounds in ppb.
     pub tvoc_ppb: u16,
+    /// Sensirion VOC index (1-500, 100 is the recent average) from an SGP40. 0 when unavailable.
+    pub voc_index: u16,
     /// Air quality index (AQI).
     pub air_quality_index: u16,
+    /// PM1.0 particulate matter in µg/m³ (PMS5003). 0 when unavailable.
+    pub pm1_0_ugm3: u16,
+    /// PM2.5 particulate matter in µg/m³ (PMS5003). 0 when unavailable.
+    pub pm2_5_ugm3: u16,
+    /// PM10 particulate matter in µg/m³ (PMS5003). 0 when unavailable.
+    pub pm10_ugm3: u16,
     /// Thermistor temperature in degrees Celsius.
     pub thermistor_temp_c: f32,
+    /// Ambient light in lux (BH1750). NaN when unavailable.
+    pub light_lux: f32,
     /// Path to the image file.
     pub image_path: String,
-    /// Quantification of image motion.
+    /// Image motion of the frames without overlay, computed offline by the motion pass of the
+    /// analysis [`Pipeline`](crate::analysis::Pipeline). NaN until it has run.
     pub image_motion: f32,
+    /// Image motion computed live by the camera. NaN for the first image and samples without one.
+    pub image_motion_live: f32,
+    /// Path to the motion-triggered video clip; empty when no clip was recorded.
+    pub clip_path: String,
+    /// Path to the image file without overlay; empty when not saved.
+    pub raw_image_path: String,
+    /// JPEG thumbnail of the image; empty when not enabled.
+    pub thumbnail: Thumbnail,
+    /// Weight on the bed in kilograms (HX711 load cells). NaN when unavailable.
+    pub bed_weight_kg: f32,
+    /// Whether the bed weight is above the occupancy threshold.
+    pub bed_occupied: bool,
+    /// Piezo BCG burst in millivolts, sampled at ~240 SPS. Empty when unavailable.
+    pub piezo_bcg_mv: Vec<f32>,
+    /// Heart rate [bpm] estimated from the piezo burst. NaN when unavailable.
+    pub piezo_heart_rate_bpm: f32,
+    /// Respiration rate [breaths/min] estimated from the piezo burst. NaN when unavailable.
+    pub piezo_resp_rate_bpm: f32,
+    /// Motion detected by the PIR sensor since the previous sample.
+    pub pir_motion: bool,
     /// Human presence as detected by mmWave sensor.
     pub mmwave_presence: bool,
     /// Motion as detected by mmWave sensor.
     pub mmwave_movement: bool,
-    /// Heart rate [bpm] as detected by mmWave sensor.
+    /// Heart rate [bpm] as detected by mmWave sensor. 0 when unavailable.
     pub mmwave_heart_rate_bpm: u16,
-    /// Respiration rate [bpm] as detected by mmWave sensor.
+    /// Respiration rate [bpm] as detected by mmWave sensor. 0 when unavailable.
     pub mmwave_resp_rate_bpm: u16,
+    /// CPU temperature of the host in degrees Celsius.
+    pub cpu_temp_c: f32,
+    /// 1-minute load average of the host.
+    pub load_avg_1m: f32,
+    /// Free space on the data volume in MiB. 0 when unavailable.
+    pub disk_free_mb: u64,
+    /// Host memory in use, in percent.
+    pub mem_used_percent: f32,
+    /// Readings outside their plausible range, one bit per field: bit `i` is set when the reading
+    /// of `PLAUSIBLE_RANGES[i]` is implausible (see [`PLAUSIBLE_RANGES`]). 0 when all are plausible.
+    pub quality_flags: u16,
+    /// Results of the additional (named) cameras, keyed by camera name.
+    pub extra_cameras: HashMap<String, CameraAndMotionResult>,
+    /// Temperatures of the DS18B20 probes in degrees Celsius, keyed by probe name.
+    pub probe_temps_c: HashMap<String, f32>,
+    /// Temperatures of the additional (named) thermistors in degrees Celsius, keyed by name.
+    pub extra_thermistor_temps_c: HashMap<String, f32>,
+    /// Health of each sensor in this sample, keyed by sensor name.
+    pub sensor_status: HashMap<String, SensorStatus>,
 }
 impl SleepData {
     /// Creates a new `SleepDataBuilder` instance with the given timestamp.
     pub fn builder(timestamp: u64) -> SleepDataBuilder {
         SleepDataBuilder::new(timestamp)
     }
+
+    /// Image motion of the offline analysis where it has run, and of the live camera otherwise.
+    pub fn image_motion_or_live(&self) -> f32 {
+        if self.image_motion.is_nan() { self.image_motion_live } else { self.image_motion }
+    }
+
+    /// Approximate memory taken by the sample, in bytes, for limiting the memory of buffered
+    /// samples: its size and that of the paths, thumbnails, bursts, and maps it owns.
+    pub(crate) fn buffered_size(&self) -> usize {
+        let camera_size = |camera: &CameraAndMotionResult| {
+            camera.image_path.len()
+                + camera.clip_path.as_ref().map_or(0, String::len)
+                + camera.raw_image_path.as_ref().map_or(0, String::len)
+                + camera.thumbnail.as_ref().map_or(0, |thumbnail| thumbnail.0.len())
+        };
+        std::mem::size_of::<Self>()
+            + self.image_path.len() + self.clip_path.len() + self.raw_image_path.len() + self.thumbnail.0.len()
+            + self.piezo_bcg_mv.len() * std::mem::size_of::<f32>()
+            + self.extra_cameras.iter()
+                .map(|(name, camera)| name.len() + std::mem::size_of::<CameraAndMotionResult>() + camera_size(camera))
+                .sum::<usize>()
+            + self.probe_temps_c.keys().chain(self.extra_thermistor_temps_c.keys())
+                .map(|name| name.len() + std::mem::size_of::<f32>())
+                .sum::<usize>()
+            + self.sensor_status.iter()
+                .map(|(name, status)| name.len() + std::mem::size_of::<SensorStatus>() + status.error.as_ref().map_or(0, String::len))
+                .sum::<usize>()
+    }
+
+    /// Fields whose reading in this sample is flagged as implausible in `quality_flags`.
+    pub fn implausible_fields(&self) -> Vec<&'static str> {
+        PLAUSIBLE_RANGES.iter().enumerate()
+            .filter(|(bit, _)| self.quality_flags & (1 << bit) != 0)
+            .map(|(_, range)| range.field)
+            .collect()
+    }
+}
+
+/// Whether the reading of `field` is flagged as implausible in `quality_flags` (see
+/// [`SleepData::quality_flags`]).
+pub(crate) fn is_flagged(field: &str, quality_flags: u16) -> bool {
+    PLAUSIBLE_RANGES.iter()
+        .position(|range| range.field == field)
+        .is_some_and(|bit| quality_flags & (1 << bit) != 0)
+}
+
+/// Plausible range of a sensor reading. Readings outside it, e.g. from a glitching sensor, are
+/// kept as measured, but flagged in [`SleepData::quality_flags`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlausibleRange {
+    /// Dataset (or column) name of the field.
+    pub field: &'static str,
+    /// Lowest plausible reading.
+    pub min: f32,
+    /// Highest plausible reading.
+    pub max: f32,
 }
 
+const fn plausible(field: &'static str, min: f32, max: f32) -> PlausibleRange {
+    PlausibleRange { field, min, max }
+}
+
+/// The validated fields, in the order of their bits in [`SleepData::quality_flags`]. New fields
+/// must be appended, so that the flags of recorded sessions keep their meaning.
+pub const PLAUSIBLE_RANGES: [PlausibleRange; 11] = [
+    plausible("temperature", -20.0, 60.0),
+    plausible("pressure", 300.0, 1100.0),
+    plausible("humidity", 0.0, 100.0),
+    plausible("co2eq_ppm", 400.0, 10_000.0),
+    plausible("co2_ppm", 400.0, 10_000.0),
+    plausible("voc_index", 1.0, 500.0),
+    plausible("pm1_0_ugm3", 0.0, 1000.0),
+    plausible("pm2_5_ugm3", 0.0, 1000.0),
+    plausible("pm10_ugm3", 0.0, 1000.0),
+    plausible("thermistor_temp", -20.0, 60.0),
+    plausible("light_lux", 0.0, 65_535.0),
+];
+
 /// Builder for `SleepData`. 
 /// 
 /// This struct is used to construct a `SleepData` instance using a builder pattern. 
 /// It allows for optional fields to be set, and provides a method to build the 
 /// final `SleepData` instance. Float fields default to `NAN`, and integer fields 
 /// default to `0`. The image path defaults to an empty string.
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct SleepDataBuilder {
     timestamp_s: u64,
     temperature_c: Option<f32>,
     pressure: Option<f32>,
     humidity: Option<f32>,
     co2eq_ppm: Option<u16>,
+    co2_ppm: Option<u16>,
     tvoc_ppb: Option<u16>,
+    voc_index: Option<u16>,
     air_quality_index: Option<u16>,
+    particulate_matter: Option<PmMeasurement>,
     thermistor_temp_c: Option<f32>,
+    light_lux: Option<f32>,
     image_path: Option<String>,
-    image_motion: Option<f32>,
+    image_motion_live: Option<f32>,
+    clip_path: Option<String>,
+    raw_image_path: Option<String>,
+    thumbnail: Option<Thumbnail>,
+    bed_weight_kg: Option<f32>,
+    bed_occupied: Option<bool>,
+    piezo_bcg_mv: Vec<f32>,
+    piezo_estimate: BcgEstimate,
+    pir_motion: Option<bool>,
     mmwave_presence: Option<bool>,
     mmwave_movement: Option<bool>,
     mmwave_heart_rate_bpm: Option<u16>,
     mmwave_resp_rate_bpm: Option<u16>,
+    system_stats: SystemStats,
+    extra_cameras: HashMap<String, CameraAndMotionResult>,
+    probe_temps_c: HashMap<String, f32>,
+    extra_thermistor_temps_c: HashMap<String, f32>,
+    sensor_status: HashMap<String, SensorStatus>,
 }
 
 impl SleepDataBuilder {
@@ -94,23 +287,112 @@ impl SleepDataBuilder {
         }
     }
 
-    pub fn with_bme280(mut self, measurements: bme280::Measurements<linux_embedded_hal::I2CError>) -> Self {
-        self.temperature_c = Some(measurements.temperature);
-        self.pressure = Some(measurements.pressure);
-        self.humidity = Some(measurements.humidity);
+    /// Timestamp of the sample being built, in seconds since UNIX epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp_s
+    }
+
+    pub fn with_bme280(self, measurements: bme280::Measurements<linux_embedded_hal::I2CError>) -> Self {
+        // The driver reports pascals
+        self.with_climate(measurements.temperature, measurements.pressure / 100.0, measurements.humidity)
+    }
+
+    /// Adds the ambient temperature in °C, pressure in hPa, and relative humidity in percent.
+    pub fn with_climate(mut self, temperature_c: f32, pressure_hpa: f32, humidity: f32) -> Self {
+        self.temperature_c = Some(temperature_c);
+        self.pressure = Some(pressure_hpa);
+        self.humidity = Some(humidity);
+        self
+    }
+
+    pub fn with_ens160(self, measurements: ens160_aq::data::Measurements) -> Self {
+        self.with_air_quality(measurements.co2eq_ppm.value, measurements.tvoc_ppb, measurements.air_quality_index as u16)
+    }
+
+    /// Adds the equivalent CO2 in ppm, TVOC in ppb, and air quality index (1-5) of a gas sensor.
+    pub fn with_air_quality(mut self, co2eq_ppm: u16, tvoc_ppb: u16, air_quality_index: u16) -> Self {
+        self.co2eq_ppm = Some(co2eq_ppm);
+        self.tvoc_ppb = Some(tvoc_ppb);
+        self.air_quality_index = Some(air_quality_index);
+        self
+    }
+
+    pub fn with_scd4x(mut self, measurement: Scd4xMeasurement) -> Self {
+        self.co2_ppm = Some(measurement.co2_ppm);
+        self
+    }
+
+    pub fn with_light_lux(mut self, lux: f32) -> Self {
+        self.light_lux = Some(lux);
+        self
+    }
+
+    /// Ambient light measured so far in this sample, if a light sensor has been polled.
+    pub fn light_lux(&self) -> Option<f32> {
+        self.light_lux
+    }
+
+    pub fn with_pms5003(mut self, measurement: PmMeasurement) -> Self {
+        self.particulate_matter = Some(measurement);
+        self
+    }
+
+    pub fn with_bed_weight(mut self, weight_kg: f32, occupied: bool) -> Self {
+        self.bed_weight_kg = Some(weight_kg);
+        self.bed_occupied = Some(occupied);
+        self
+    }
+
+    pub fn with_piezo_burst(mut self, samples_mv: Vec<f32>, estimate: BcgEstimate) -> Self {
+        self.piezo_bcg_mv = samples_mv;
+        self.piezo_estimate = estimate;
+        self
+    }
+
+    pub fn with_pir_motion(mut self, motion: bool) -> Self {
+        self.pir_motion = Some(motion);
         self
     }
 
-    pub fn with_ens160(mut self, measurements: ens160_aq::data::Measurements) -> Self {
-        self.co2eq_ppm = Some(measurements.co2eq_ppm.value);
-        self.tvoc_ppb = Some(measurements.tvoc_ppb);
-        self.air_quality_index = Some(measurements.air_quality_index as u16);
+    pub fn with_voc_index(mut self, voc_index: u16) -> Self {
+        self.voc_index = Some(voc_index);
         self
     }
 
     pub fn with_camera_result(mut self, camera_result: CameraAndMotionResult) -> Self {
         self.image_path = Some(camera_result.image_path);
-        self.image_motion = camera_result.motion;
+        self.image_motion_live = camera_result.motion;
+        self.clip_path = camera_result.clip_path;
+        self.raw_image_path = camera_result.raw_image_path;
+        self.thumbnail = camera_result.thumbnail;
+        self
+    }
+
+    /// Adds the result of an additional camera, stored in the `image_path_<name>`,
+    /// `image_motion_live_<name>`, `clip_path_<name>`, `raw_image_path_<name>`, and `thumbnail_<name>`
+    /// datasets.
+    pub fn with_named_camera_result(mut self, name: &str, camera_result: CameraAndMotionResult) -> Self {
+        self.extra_cameras.insert(name.to_string(), camera_result);
+        self
+    }
+
+    /// Adds the temperature of a named probe, stored in the `probe_temp_<name>` dataset.
+    pub fn with_probe_temp(mut self, name: &str, temperature_c: f32) -> Self {
+        self.probe_temps_c.insert(name.to_string(), temperature_c);
+        self
+    }
+
+    /// Adds the health of the sensor `name`, stored in the `sensor_ok_<key>`, `sensor_state_<key>`,
+    /// `sensor_error_<key>`, and `sensor_error_age_s_<key>` datasets (see [`sensor_status_key`]).
+    pub fn with_sensor_status(mut self, name: &str, status: SensorStatus) -> Self {
+        self.sensor_status.insert(name.to_string(), status);
+        self
+    }
+
+    /// Adds the temperature of an additional thermistor, stored in the `thermistor_temp_<name>`
+    /// dataset.
+    pub fn with_named_thermistor_temp(mut self, name: &str, temperature_c: f32) -> Self {
+        self.extra_thermistor_temps_c.insert(name.to_string(), temperature_c);
         self
     }
 
@@ -119,6 +401,11 @@ impl SleepDataBuilder {
         self
     }
 
+    pub fn with_system_stats(mut self, stats: SystemStats) -> Self {
+        self.system_stats = stats;
+        self
+    }
+
     pub fn with_mmwave_result(mut self, mmwave_result: C1001SleepData) -> Self {
         self.mmwave_presence = mmwave_result.presence;
         self.mmwave_movement = mmwave_result.movement;
@@ -127,33 +414,239 @@ impl SleepDataBuilder {
         self
     }
 
+    /// Adds the readings of `reading`, a builder another sensor measured into, replacing the values
+    /// it sets. The timestamp of `self` is kept.
+    ///
+    /// New fields must be added here too, or readings of concurrently polled sensors are lost.
+    pub fn merge(self, reading: SleepDataBuilder) -> Self {
+        let mut extra_cameras = self.extra_cameras;
+        extra_cameras.extend(reading.extra_cameras);
+        let mut probe_temps_c = self.probe_temps_c;
+        probe_temps_c.extend(reading.probe_temps_c);
+        let mut extra_thermistor_temps_c = self.extra_thermistor_temps_c;
+        extra_thermistor_temps_c.extend(reading.extra_thermistor_temps_c);
+        let mut sensor_status = self.sensor_status;
+        sensor_status.extend(reading.sensor_status);
+        let (piezo_bcg_mv, piezo_estimate) = match reading.piezo_bcg_mv.is_empty() {
+            true => (self.piezo_bcg_mv, self.piezo_estimate),
+            false => (reading.piezo_bcg_mv, reading.piezo_estimate),
+        };
+        Self {
+            timestamp_s: self.timestamp_s,
+            temperature_c: reading.temperature_c.or(self.temperature_c),
+            pressure: reading.pressure.or(self.pressure),
+            humidity: reading.humidity.or(self.humidity),
+            co2eq_ppm: reading.co2eq_ppm.or(self.co2eq_ppm),
+            co2_ppm: reading.co2_ppm.or(self.co2_ppm),
+            tvoc_ppb: reading.tvoc_ppb.or(self.tvoc_ppb),
+            voc_index: reading.voc_index.or(self.voc_index),
+            air_quality_index: reading.air_quality_index.or(self.air_quality_index),
+            particulate_matter: reading.particulate_matter.or(self.particulate_matter),
+            thermistor_temp_c: reading.thermistor_temp_c.or(self.thermistor_temp_c),
+            light_lux: reading.light_lux.or(self.light_lux),
+            image_path: reading.image_path.or(self.image_path),
+            image_motion_live: reading.image_motion_live.or(self.image_motion_live),
+            clip_path: reading.clip_path.or(self.clip_path),
+            raw_image_path: reading.raw_image_path.or(self.raw_image_path),
+            thumbnail: reading.thumbnail.or(self.thumbnail),
+            bed_weight_kg: reading.bed_weight_kg.or(self.bed_weight_kg),
+            bed_occupied: reading.bed_occupied.or(self.bed_occupied),
+            piezo_bcg_mv,
+            piezo_estimate,
+            pir_motion: reading.pir_motion.or(self.pir_motion),
+            mmwave_presence: reading.mmwave_presence.or(self.mmwave_presence),
+            mmwave_movement: reading.mmwave_movement.or(self.mmwave_movement),
+            mmwave_heart_rate_bpm: reading.mmwave_heart_rate_bpm.or(self.mmwave_heart_rate_bpm),
+            mmwave_resp_rate_bpm: reading.mmwave_resp_rate_bpm.or(self.mmwave_resp_rate_bpm),
+            system_stats: match reading.system_stats == SystemStats::default() {
+                true => self.system_stats,
+                false => reading.system_stats,
+            },
+            extra_cameras,
+            probe_temps_c,
+            extra_thermistor_temps_c,
+            sensor_status,
+        }
+    }
+
+    /// Reading of the field `field` of [`PLAUSIBLE_RANGES`], if one was added.
+    fn reading(&self, field: &str) -> Option<f32> {
+        let pm = self.particulate_matter;
+        match field {
+            "temperature" => self.temperature_c,
+            "pressure" => self.pressure,
+            "humidity" => self.humidity,
+            "co2eq_ppm" => self.co2eq_ppm.map(f32::from),
+            "co2_ppm" => self.co2_ppm.map(f32::from),
+            "voc_index" => self.voc_index.map(f32::from),
+            "pm1_0_ugm3" => pm.map(|pm| f32::from(pm.pm1_0)),
+            "pm2_5_ugm3" => pm.map(|pm| f32::from(pm.pm2_5)),
+            "pm10_ugm3" => pm.map(|pm| f32::from(pm.pm10)),
+            "thermistor_temp" => self.thermistor_temp_c,
+            "light_lux" => self.light_lux,
+            _ => None,
+        }
+    }
+
+    /// Flags of the readings outside their plausible range (see [`SleepData::quality_flags`]),
+    /// logging each. Unavailable (`NAN`) readings aren't flagged.
+    fn quality_flags(&self) -> u16 {
+        let mut flags = 0;
+        for (bit, range) in PLAUSIBLE_RANGES.iter().enumerate() {
+            let Some(value) = self.reading(range.field).filter(|value| !value.is_nan()) else {
+                continue;
+            };
+            if !(range.min..=range.max).contains(&value) {
+                warn!("Implausible {} reading {} (expected {} to {}), flagged", range.field, value, range.min, range.max);
+                flags |= 1 << bit;
+            }
+        }
+        flags
+    }
+
+    /// Builds the sample, flagging implausible readings in [`SleepData::quality_flags`], and
+    /// deriving the climate metrics from the temperature and humidity unless either is
+    /// implausible.
     pub fn build(self) -> SleepData {
+        let quality_flags = self.quality_flags();
+        let reading = |field| self.reading(field).filter(|_| !is_flagged(field, quality_flags)).unwrap_or(f32::NAN);
+        let climate = DerivedClimate::new(reading("temperature"), reading("humidity"));
         SleepData {
+            quality_flags,
+            dew_point_c: climate.dew_point_c,
+            absolute_humidity_gm3: climate.absolute_humidity_gm3,
+            comfort_index: climate.comfort_index,
             timestamp_s: self.timestamp_s,
             temperature_c: self.temperature_c.unwrap_or(f32::NAN),
             pressure: self.pressure.unwrap_or(f32::NAN),
             humidity: self.humidity.unwrap_or(f32::NAN),
             co2eq_ppm: self.co2eq_ppm.unwrap_or_default(),
+            co2_ppm: self.co2_ppm.unwrap_or_default(),
             tvoc_ppb: self.tvoc_ppb.unwrap_or_default(),
+            voc_index: self.voc_index.unwrap_or_default(),
             air_quality_index: self.air_quality_index.unwrap_or_default(),
+            pm1_0_ugm3: self.particulate_matter.unwrap_or_default().pm1_0,
+            pm2_5_ugm3: self.particulate_matter.unwrap_or_default().pm2_5,
+            pm10_ugm3: self.particulate_matter.unwrap_or_default().pm10,
             thermistor_temp_c: self.thermistor_temp_c.unwrap_or(f32::NAN),
+            light_lux: self.light_lux.unwrap_or(f32::NAN),
             image_path: self.image_path.unwrap_or_default(),
-            image_motion: self.image_motion.unwrap_or(f32::NAN),
+            image_motion: f32::NAN,
+            image_motion_live: self.image_motion_live.unwrap_or(f32::NAN),
+            clip_path: self.clip_path.unwrap_or_default(),
+            raw_image_path: self.raw_image_path.unwrap_or_default(),
+            thumbnail: self.thumbnail.unwrap_or_default(),
+            bed_weight_kg: self.bed_weight_kg.unwrap_or(f32::NAN),
+            bed_occupied: self.bed_occupied.unwrap_or_default(),
+            piezo_bcg_mv: self.piezo_bcg_mv,
+            piezo_heart_rate_bpm: self.piezo_estimate.heart_rate_bpm.unwrap_or(f32::NAN),
+            piezo_resp_rate_bpm: self.piezo_estimate.resp_rate_bpm.unwrap_or(f32::NAN),
+            pir_motion: self.pir_motion.unwrap_or_default(),
             mmwave_presence: self.mmwave_presence.unwrap_or_default(),
             mmwave_movement: self.mmwave_movement.unwrap_or_default(),
             mmwave_heart_rate_bpm: self.mmwave_heart_rate_bpm.unwrap_or_default(),
             mmwave_resp_rate_bpm: self.mmwave_resp_rate_bpm.unwrap_or_default(),
+            cpu_temp_c: self.system_stats.cpu_temp_c.unwrap_or(f32::NAN),
+            load_avg_1m: self.system_stats.load_avg_1m.unwrap_or(f32::NAN),
+            disk_free_mb: self.system_stats.disk_free_mb.unwrap_or_default(),
+            mem_used_percent: self.system_stats.mem_used_percent.unwrap_or(f32::NAN),
+            extra_cameras: self.extra_cameras,
+            probe_temps_c: self.probe_temps_c,
+            extra_thermistor_temps_c: self.extra_thermistor_temps_c,
+            sensor_status: self.sensor_status,
+        }
+    }
+}
+
+/// Readiness of a sensor. Sensors are opened during the session and only measured while ready.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[repr(u8)]
+pub enum SensorState {
+    /// The sensor is being opened (or reopened after repeated failures), and not measured yet.
+    Initializing = 0,
+    /// The sensor is measured.
+    #[default]
+    Ready = 1,
+    /// The sensor could not be opened within the initialization budget. It is still retried now
+    /// and then.
+    Failed = 2,
+}
+
+impl SensorState {
+    /// The state stored as `value` in a `sensor_state_<key>` dataset.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Initializing),
+            1 => Some(Self::Ready),
+            2 => Some(Self::Failed),
+            _ => None,
         }
     }
 }
 
+/// Health of a sensor in one sample.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SensorStatus {
+    /// Readiness of the sensor when the sample was taken.
+    pub state: SensorState,
+    /// Error of this sample's measurement (or, while the sensor is not ready, of opening it);
+    /// `None` if it succeeded.
+    pub error: Option<String>,
+    /// Seconds since the sensor last failed, 0 if it failed in this sample; `None` if it has not
+    /// failed this session.
+    pub last_error_age_s: Option<u64>,
+}
+
+impl SensorStatus {
+    /// Whether the sensor is ready and this sample's measurement succeeded.
+    pub fn ok(&self) -> bool {
+        self.state == SensorState::Ready && self.error.is_none()
+    }
+}
+
+/// Dataset name suffix of the sensor `name`: lowercase, with anything but letters and digits
+/// replaced by `_` (e.g. "DS18B20 mattress" becomes "ds18b20_mattress").
+pub fn sensor_status_key(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CameraAndMotionResult {
     pub image_path: String,
     pub motion: Option<f32>,
+    /// Path to the video clip recorded because of this motion, if any.
+    pub clip_path: Option<String>,
+    /// Path to the image saved without overlay, if enabled.
+    pub raw_image_path: Option<String>,
+    /// Thumbnail of the image, if enabled.
+    pub thumbnail: Option<Thumbnail>,
+}
+
+/// JPEG thumbnail of a captured image, stored in the data file (see
+/// [`CameraConfig::thumbnail_size`](crate::config::CameraConfig::thumbnail_size)). Empty when no
+/// thumbnail was stored. With at-rest encryption, it is encrypted (see
+/// [`encryption::decrypt`](crate::encryption::decrypt)).
+#[derive(Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Thumbnail(pub Vec<u8>);
+
+impl Thumbnail {
+    /// Whether no thumbnail was stored.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Thumbnail {
+    // Samples are logged; the JPEG bytes would drown the other fields
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Thumbnail({} bytes)", self.0.len())
+    }
 }
 
 /// Data entry for an audio recording session.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct AudioRecording {
     /// Path to the audio file.
     pub path: String,
@@ -162,6 +655,105 @@ pub struct AudioRecording {
     /// Timestamp of the audio recording in seconds since UNIX epoch.
     pub start_time_s: u64,
 }
+
+/// A state change of an actuator (see [`Actuators`](crate::actuator::Actuators)).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ActuatorEvent {
+    /// Time of the change in seconds since UNIX epoch.
+    pub timestamp_s: u64,
+    /// Name of the actuator.
+    pub name: String,
+    /// New state of the actuator.
+    pub on: bool,
+}
+
+/// An annotation of a session by the application or the user, e.g. "went to bed", "alarm", or
+/// "dog jumped on bed", stored in the `events` dataset.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Event {
+    /// Time of the event in seconds since UNIX epoch.
+    pub timestamp_s: u64,
+    /// Kind of event, e.g. "bedtime", "alarm", or "fall_alert".
+    pub category: String,
+    /// Free-form description of the event.
+    pub text: String,
+}
+
+/// HDF5-compatible entry of the `events` dataset. Implements `from(&Event)`.
+#[derive(H5Type, Clone, Debug)]
+#[repr(C)]
+pub struct H5Event {
+    /// Time of the event in seconds since UNIX epoch.
+    pub timestamp_s: u64,
+    /// Kind of event.
+    pub category: VarLenUnicode,
+    /// Free-form description of the event.
+    pub text: VarLenUnicode,
+}
+
+impl From<&Event> for H5Event {
+    fn from(event: &Event) -> Self {
+        Self {
+            timestamp_s: event.timestamp_s,
+            category: VarLenUnicode::from_str(&event.category).unwrap_or_default(),
+            text: VarLenUnicode::from_str(&event.text).unwrap_or_default(),
+        }
+    }
+}
+
+impl From<H5Event> for Event {
+    fn from(entry: H5Event) -> Self {
+        Self { timestamp_s: entry.timestamp_s, category: entry.category.to_string(), text: entry.text.to_string() }
+    }
+}
+
+/// Context of a session, stored as attributes of its group (or in the `session_metadata` table
+/// of an SQLite database).
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SessionMetadata {
+    /// Identifier of the recording device.
+    pub device_id: String,
+    /// Where the session was recorded.
+    pub location: String,
+    /// Label of the person recorded.
+    pub subject: String,
+    /// Free-form notes, e.g. "new mattress".
+    pub notes: String,
+    /// Version of the software that recorded the session.
+    pub software_version: String,
+    /// Frequency weighting of the RMS levels of the session's audio (see
+    /// [`AudioWeighting::name`](crate::config::AudioWeighting::name)).
+    pub audio_weighting: String,
+    /// Sound pressure level of a full-scale signal of the session's microphone, in dB SPL (see
+    /// [`MicrophoneCalibration`](crate::calibration::MicrophoneCalibration)), or empty if the
+    /// microphone wasn't calibrated. Read with [`microphone`](Self::microphone).
+    pub audio_spl_offset_db: String,
+    /// Names of the sensors used in the session.
+    pub sensors: Vec<String>,
+}
+
+impl SessionMetadata {
+    /// Names of the string attributes, with the fields storing them.
+    pub(crate) fn string_fields(&self) -> [(&'static str, &String); 7] {
+        [
+            ("device_id", &self.device_id),
+            ("location", &self.location),
+            ("subject", &self.subject),
+            ("notes", &self.notes),
+            ("software_version", &self.software_version),
+            ("audio_weighting", &self.audio_weighting),
+            ("audio_spl_offset_db", &self.audio_spl_offset_db),
+        ]
+    }
+
+    /// Calibration of the session's microphone, read from
+    /// [`audio_spl_offset_db`](Self::audio_spl_offset_db): uncalibrated if it is empty or not a
+    /// number.
+    pub fn microphone(&self) -> MicrophoneCalibration {
+        MicrophoneCalibration { spl_offset_db: self.audio_spl_offset_db.parse().ok() }
+    }
+}
+
 /// HDF5-compatible metadata for audio recordings. Implements `from(AudioRecording)`
 #[derive(H5Type, Clone, Debug)]
 #[repr(C)] // important: makes memory layout compatible
@@ -191,13 +783,158 @@ impl From<AudioRecording> for H5AudioMetadata {
     }
 }
 
-#[derive(Debug)]
-enum SleepField {
+impl H5AudioMetadata {
+    /// Whether the RMS windows of the recording were computed, live (see
+    /// [`SleepDataLogger::append_audio_rms`]) or by
+    /// [`analyze_audio_entries`](crate::audio_analysis::analyze_audio_entries).
+    pub fn is_analyzed(&self) -> bool {
+        !self.audio_rms_db.is_empty()
+    }
+}
+
+/// Per-sample field stored in its own column (an HDF5 dataset or SQLite column), with the
+/// function reading it from a sample. Sensors declare the fields they fill in with
+/// [`Sensor::fields`](crate::sensor::Sensor::fields).
+#[derive(Clone, Copy, Debug)]
+pub enum SleepField {
+    /// Stored as `bool`, `false` in samples without a reading.
     Bool(fn(&SleepData) -> bool),
+    /// Stored as `u16`, 0 in samples without a reading.
     U16(fn(&SleepData) -> u16),
+    /// Stored as `u64`, 0 in samples without a reading.
     U64(fn(&SleepData) -> u64),
+    /// Stored as `f32`, `NAN` in samples without a reading.
     F32(fn(&SleepData) -> f32),
+    /// Stored as a variable-length string, empty in samples without a value.
     String(fn(&SleepData) -> VarLenUnicode),
+    /// Stored as variable-length bytes, empty in samples without a value.
+    Bytes(fn(&SleepData) -> VarLenArray<u8>),
+}
+
+/// The fields every session has, whatever its sensors: the time of the sample, and the flags of
+/// its implausible readings.
+pub(crate) const BASE_FIELDS: [(&str, SleepField); 2] = [
+    ("timestamp", SleepField::U64(|d| d.timestamp_s)),
+    ("quality_flags", SleepField::U16(|d| d.quality_flags)),
+];
+
+/// The per-sample fields of all built-in sensors (see [`builtin_fields`](crate::sensor::builtin_fields)),
+/// and the [`BASE_FIELDS`], keyed by dataset (or column) name.
+pub(crate) fn sleep_fields() -> HashMap<&'static str, SleepField> {
+    BASE_FIELDS.into_iter()
+        .chain(crate::sensor::builtin_fields())
+        .collect()
+}
+
+/// Start time of the session `name` (e.g. "2025-04-28_22-47-31", in local time) in seconds since
+/// UNIX epoch, or `None` if the name isn't a start time.
+pub(crate) fn session_start(name: &str) -> Option<u64> {
+    let start = NaiveDateTime::parse_from_str(name, "%Y-%m-%d_%H-%M-%S").ok()?
+        .and_local_timezone(Local)
+        .earliest()?;
+    u64::try_from(start.timestamp()).ok()
+}
+
+/// Name of the file of the night of the session `session` when sessions are stored in a file
+/// per night ([`FileRotation::Nightly`](crate::config::FileRotation::Nightly)): `file_name` with
+/// the night's date appended to its stem, e.g. `sleep_data_2025-05-01.h5` for `sleep_data.h5`.
+/// Sessions starting before noon belong to the night before. `None` if the session name isn't a
+/// start time.
+pub fn night_file_name(file_name: &str, session: &str) -> Option<String> {
+    let start = NaiveDateTime::parse_from_str(session, "%Y-%m-%d_%H-%M-%S").ok()?;
+    let night = (start - chrono::Duration::hours(12)).date().format("%Y-%m-%d");
+    let path = Path::new(file_name);
+    let stem = path.file_stem()?.to_str()?;
+    Some(match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => format!("{stem}_{night}.{extension}"),
+        None => format!("{stem}_{night}"),
+    })
+}
+
+/// The HDF5 files in `data_path` holding sessions: `file_name`, if it exists, followed by the
+/// files per night (see [`night_file_name`]), oldest first.
+pub fn data_files(data_path: &str, file_name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    if Path::new(data_path).join(file_name).exists() {
+        files.push(file_name.to_string());
+    }
+    let base = Path::new(file_name);
+    let Some(stem) = base.file_stem().and_then(|stem| stem.to_str()) else {
+        return Ok(files);
+    };
+    let suffix = base.extension().and_then(|extension| extension.to_str()).map(|extension| format!(".{extension}")).unwrap_or_default();
+    let mut nights: Vec<String> = std::fs::read_dir(data_path)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            name.strip_prefix(stem)
+                .and_then(|rest| rest.strip_prefix('_'))
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .is_some_and(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok())
+        })
+        .collect();
+    // The dates are zero-padded, so the names sort chronologically
+    nights.sort();
+    files.extend(nights);
+    Ok(files)
+}
+
+/// Path of the HDF5 file holding the session `group_name`: the file of its night (see
+/// [`night_file_name`]) if that holds it, otherwise `data_path/file_name`.
+pub fn session_path(data_path: &str, file_name: &str, group_name: &str) -> String {
+    if let Some(night_file) = night_file_name(file_name, group_name) {
+        let path = format!("{data_path}/{night_file}");
+        if Path::new(&path).exists() && File::open(&path).is_ok_and(|file| file.group(group_name).is_ok()) {
+            return path;
+        }
+    }
+    data_path.to_string() + "/" + file_name
+}
+
+/// Name of the pointer to the current session: a string attribute of the root of the HDF5 file
+/// holding the session, and a symbolic link in the data directory to the session's media
+/// directory (see [`SleepDataLogger::current_session`]).
+pub const LATEST: &str = "latest";
+
+/// Points the [`LATEST`] attribute of `file` and the [`LATEST`] link in `data_path` at the
+/// session `group_name`. The link is replaced atomically, so that readers never miss it.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn write_latest(file: &File, data_path: &str, group_name: &str) -> Result<(), Box<dyn Error>> {
+    let attr = match file.attr(LATEST) {
+        Ok(attr) => attr,
+        Err(_) => file.new_attr::<VarLenUnicode>().create(LATEST)?,
+    };
+    attr.write_scalar(&VarLenUnicode::from_str(group_name)?)?;
+    #[cfg(unix)]
+    {
+        let link = Path::new(data_path).join(LATEST);
+        let staged = Path::new(data_path).join(format!(".{LATEST}"));
+        let _ = std::fs::remove_file(&staged);
+        // Relative, so that the data directory can be moved
+        std::os::unix::fs::symlink(group_name, &staged)?;
+        std::fs::rename(&staged, &link)?;
+    }
+    Ok(())
+}
+
+/// Summary of a session in an HDF5 file, as listed by [`SleepDataLogger::list_sessions`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// Name of the session group, e.g. "2025-04-28_22-47-31".
+    pub name: String,
+    /// Name of the HDF5 file in the data directory holding the session: the data file, or the
+    /// file of its night.
+    pub file_name: String,
+    /// Timestamp of the first sample in seconds since UNIX epoch, or the session's start time if
+    /// it has no samples.
+    pub start_s: u64,
+    /// Timestamp of the last sample in seconds since UNIX epoch, or `start_s` if the session has
+    /// no samples.
+    pub end_s: u64,
+    /// Number of sensor samples.
+    pub sample_count: usize,
+    /// Names of the session's datasets, sorted.
+    pub datasets: Vec<String>,
 }
 
 /// Logger for sleep data. 
@@ -215,177 +952,2246 @@ pub struct SleepDataLogger {
     buffer: Vec<SleepData>,
     /// Number of entries to buffer before flushing to HDF5 file.
     flush_every: usize,
+    /// Memory the buffered entries may take before they are flushed, in bytes.
+    max_buffer_bytes: usize,
+    /// Memory taken by the buffered entries (see [`SleepData::buffered_size`]), in bytes.
+    buffer_bytes: usize,
     /// HDF5 file handle.
     file: File,
     /// Name of the HDF5 group for this session.
     pub group_name: String,
-    /// Map of dataset names to their corresponding SleepField functions.
+    /// Map of dataset names to their corresponding SleepField functions: the `BASE_FIELDS`, and
+    /// the fields registered with `register_field`.
     data_map: HashMap<&'static str, SleepField>,
+    /// Names of the additional cameras registered with `register_camera`.
+    camera_names: Vec<String>,
+    /// Names of the temperature probes registered with `register_probe`.
+    probe_names: Vec<String>,
+    /// Names of the additional thermistors registered with `register_thermistor`.
+    thermistor_names: Vec<String>,
+    /// Names of the sensors registered with `register_sensor`.
+    sensor_names: Vec<String>,
+    /// Live audio levels (timestamp, dBFS) waiting to be flushed.
+    audio_levels: Vec<(u64, f32)>,
+    /// Live band levels (timestamp, dBFS per band) waiting to be flushed.
+    audio_bands: Vec<(u64, [f32; BANDS.len()])>,
+    /// RMS windows (timestamps, dBFS) of recordings not added yet, by recording start time.
+    pending_audio_rms: HashMap<u64, (Vec<u64>, Vec<f32>)>,
+    /// Whether this logger continues an existing session (see `resume`).
+    resumed: bool,
+    /// Write-ahead journal of the buffered samples, if enabled with `with_journal`.
+    journal: Option<Journal>,
+    /// Chunking and compression of the datasets this logger creates.
+    compression: CompressionConfig,
+    /// Summaries of the numeric fields, one per interval length (see [`summary`]).
+    summaries: Vec<Summarizer>,
 }
 
 impl Drop for SleepDataLogger {
     fn drop(&mut self) {
         info!("Data logging ended, flushing final data.");
-        if let Err(e) = self.flush() {
-            warn!("Failed to flush data on drop: {}", e);
+        match self.flush() {
+            Ok(()) => {
+                if let Some(Err(e)) = self.journal.take().map(Journal::remove) {
+                    warn!("Failed to remove journal: {}", e);
+                }
+                if let Err(e) = self.finish_summaries() {
+                    warn!("Failed to write the last summary intervals: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to flush data on drop: {}", e),
         }
     }
 }
 
 impl SleepDataLogger {
-    /// Creates a new HDF5 dataset for the given type and name.
-    /// The dataset is created with chunking and compression enabled.
-    /// The dataset is resizable and initially empty.
-    pub fn generate_dataset<T: H5Type>(group: &hdf5::Group, name: &str) -> Result<Dataset, Box<dyn Error>> {
-        group.new_dataset_builder()
-            .chunk(1024)
-            .deflate(6)
-            .empty::<T>()
-            .shape(hdf5::SimpleExtents::resizable([0]))
-            .create(name)
-            .map_err(|e| format!("Failed to create dataset {}: {}", name, e).into())
-    }
-
     /// Creates a new `SleepDataLogger` instance.
     /// The HDF5 file is created at the specified path with the given filename.
     /// A new group is created in the file with the current timestamp as its name.
-    /// The datasets for the sleep data fields are created in the group.
-    /// Defaults the `flush_every` parameter to 12.
+    /// Of the per-sample fields, only the `timestamp` and `quality_flags` datasets are created; the
+    /// fields of the session's sensors are added with [`register_field`](Self::register_field).
+    /// Buffers [`DEFAULT_FLUSH_EVERY`] samples (see [`with_buffering`](Self::with_buffering)).
     pub fn new(data_path: &str, file_name: &str) -> Result<Self, Box<dyn Error>> {
+        Self::new_with_compression(data_path, file_name, CompressionConfig::default())
+    }
+
+    /// Creates a new `SleepDataLogger` instance like [`new`](Self::new), chunking and
+    /// compressing its datasets as `compression` sets, e.g. from [`Config::compression`](crate::config::Config::compression).
+    pub fn new_with_compression(data_path: &str, file_name: &str, compression: CompressionConfig) -> Result<Self, Box<dyn Error>> {
+        let group_name = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        Self::create(data_path, file_name, &group_name, compression)
+    }
+
+    /// Creates a new `SleepDataLogger` instance like [`new_with_compression`](Self::new_with_compression),
+    /// in the file of the current night (see [`night_file_name`]) instead of `file_name`.
+    pub fn new_nightly(data_path: &str, file_name: &str, compression: CompressionConfig) -> Result<Self, Box<dyn Error>> {
+        let group_name = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let night_file = night_file_name(file_name, &group_name)
+            .ok_or_else(|| format!("Data file {} has no name to add the night to", file_name))?;
+        Self::create(data_path, &night_file, &group_name, compression)
+    }
+
+    /// Creates the session `group_name` in the HDF5 file at `data_path/file_name`.
+    pub(crate) fn create(data_path: &str, file_name: &str, group_name: &str, compression: CompressionConfig) -> Result<Self, Box<dyn Error>> {
         let file = File::append(data_path.to_string() + "/" + file_name)?;
+        let group = file.create_group(group_name)?;
 
-        let now = Local::now();
-        let group_name = now.format("%Y-%m-%d_%H-%M-%S").to_string();
-        let group = file.create_group(&group_name)?;       
-
-        let mut data_map = HashMap::new();
-
-        data_map.insert("timestamp", SleepField::U64(|d| d.timestamp_s));
-        data_map.insert("temperature", SleepField::F32(|d| d.temperature_c));
-        data_map.insert("pressure", SleepField::F32(|d| d.pressure));
-        data_map.insert("humidity", SleepField::F32(|d| d.humidity));
-        data_map.insert("co2eq_ppm", SleepField::U16(|d| d.co2eq_ppm));
-        data_map.insert("tvoc_ppb", SleepField::U16(|d| d.tvoc_ppb));
-        data_map.insert("air_quality_index", SleepField::U16(|d| d.air_quality_index));
-        data_map.insert("thermistor_temp", SleepField::F32(|d| d.thermistor_temp_c));
-        data_map.insert("image_path", SleepField::String(|d| VarLenUnicode::from_str(&d.image_path).unwrap_or_default()));
-        data_map.insert("image_motion", SleepField::F32(|d| d.image_motion));
-        data_map.insert("mmwave_presence", SleepField::Bool(|d| d.mmwave_presence));
-        data_map.insert("mmwave_movement", SleepField::Bool(|d| d.mmwave_movement));
-        data_map.insert("mmwave_heart_rate_bpm", SleepField::U16(|d| d.mmwave_heart_rate_bpm));
-        data_map.insert("mmwave_resp_rate_bpm", SleepField::U16(|d| d.mmwave_resp_rate_bpm));
-
-    
-        for (key, sleep_field) in data_map.iter() {
-            match sleep_field {
-                SleepField::Bool(_) => Self::generate_dataset::<bool>(&group, key)?,
-                SleepField::U64(_) => Self::generate_dataset::<u64>(&group, key)?,
-                SleepField::U16(_) => Self::generate_dataset::<u16>(&group, key)?,
-                SleepField::F32(_) => Self::generate_dataset::<f32>(&group, key)?,
-                SleepField::String(_) => Self::generate_dataset::<VarLenUnicode>(&group, key)?,
-            };
+        AppendableColumn::<u64>::create(&group, "timestamp", &compression)?;
+        AppendableColumn::<u16>::create(&group, "quality_flags", &compression)?;
+        AppendableColumn::<H5AudioMetadata>::create(&group, "audio", &compression)?;
+        AppendableColumn::<f32>::create(&group, "live_audio_rms_db", &compression)?;
+        AppendableColumn::<u64>::create(&group, "live_audio_rms_t_s", &compression)?;
+        AppendableColumn::<f32>::create(&group, "piezo_bcg_mv", &compression)?;
+        AppendableColumn::<u64>::create(&group, "piezo_bcg_start", &compression)?;
+        AppendableColumn::<u64>::create(&group, "actuator_event_t_s", &compression)?;
+        AppendableColumn::<VarLenUnicode>::create(&group, "actuator_event_name", &compression)?;
+        AppendableColumn::<bool>::create(&group, "actuator_event_on", &compression)?;
+        AppendableColumn::<H5Event>::create(&group, "events", &compression)?;
+        AppendableColumn::<u64>::create(&group, BAND_TIMES_DATASET, &compression)?;
+        for band in BANDS {
+            AppendableColumn::<f32>::create(&group, band.dataset, &compression)?;
         }
-        Self::generate_dataset::<H5AudioMetadata>(&group, "audio")?;
+        AppendableColumn::<u64>::create(&group, RESP_TIMES_DATASET, &compression)?;
+        AppendableColumn::<f32>::create(&group, RESP_RATE_DATASET, &compression)?;
+        for interval_s in summary::INTERVALS_S {
+            create_summary_group(&group, interval_s, &compression)?;
+        }
+        write_schema_version(&group, SCHEMA_VERSION)?;
+        write_latest(&file, data_path, group_name)?;
         info!("HDF5 file ({file_name}) and group ({group_name}) created successfully at {data_path}.");
 
         Ok(Self {
             buffer: Vec::new(),
-            flush_every: 12,
+            flush_every: DEFAULT_FLUSH_EVERY,
+            max_buffer_bytes: DEFAULT_MAX_BUFFER_KIB * 1024,
+            buffer_bytes: 0,
             file,
             group_name: group_name.to_string(),
-            data_map
+            data_map: HashMap::from(BASE_FIELDS),
+            camera_names: Vec::new(),
+            probe_names: Vec::new(),
+            thermistor_names: Vec::new(),
+            sensor_names: Vec::new(),
+            audio_levels: Vec::new(),
+            audio_bands: Vec::new(),
+            pending_audio_rms: HashMap::new(),
+            resumed: false,
+            journal: None,
+            compression,
+            summaries: summary::INTERVALS_S.into_iter().map(|interval_s| Summarizer::new(interval_s, None)).collect(),
         })
     }
 
-    /// Appends a new `SleepData` entry to the buffer.
-    /// If the buffer reaches the specified size, it flushes the data to the HDF5 file.
-    /// The `flush_every` parameter determines how many entries to buffer before flushing.
-    #[tracing::instrument(skip(self, sample))]
-    pub fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
-        info!("Pushing sample to buffer: {:?}", &sample);
-        self.buffer.push(sample);
-        if self.buffer.len() >= self.flush_every {
-            info!("Flushing data to HDF5 file...");
-            self.flush()?;
+    /// Reopens the session `group_name` in the HDF5 file at `data_path/file_name` (or the file of
+    /// its night, see [`session_path`]) to continue appending to it, e.g. after the recorder
+    /// restarted during the night. The session is first
+    /// upgraded to the current [`SCHEMA_VERSION`].
+    ///
+    /// The fields, cameras, probes, thermistors, and sensors of the session stay registered, so
+    /// that their datasets stay aligned; registering them again is a no-op.
+    pub fn resume(data_path: &str, file_name: &str, group_name: &str) -> Result<Self, Box<dyn Error>> {
+        Self::resume_with_compression(data_path, file_name, group_name, CompressionConfig::default())
+    }
+
+    /// Reopens the session `group_name` like [`resume`](Self::resume), chunking and compressing
+    /// the datasets registered from now on as `compression` sets.
+    pub fn resume_with_compression(data_path: &str, file_name: &str, group_name: &str, compression: CompressionConfig) -> Result<Self, Box<dyn Error>> {
+        upgrade_session(data_path, file_name, group_name)?;
+        let file = File::append(session_path(data_path, file_name, group_name))?;
+        let group = file.group(group_name)?;
+
+        let mut names = group.member_names()?;
+        names.sort();
+        let registered = |prefix: &str| -> Vec<String> {
+            names.iter().filter_map(|n| n.strip_prefix(prefix)).map(str::to_string).collect()
+        };
+        let data_map: HashMap<_, _> = sleep_fields().into_iter()
+            .filter(|(name, _)| names.iter().any(|n| n == name))
+            .collect();
+        // Continue the summaries after their last written interval
+        let mut summarized: Vec<&'static str> = data_map.iter()
+            .filter(|(name, field)| summary::is_summarized(name, field))
+            .map(|(name, _)| *name)
+            .collect();
+        summarized.sort();
+        let summaries = summary::INTERVALS_S.into_iter()
+            .map(|interval_s| -> Result<Summarizer, Box<dyn Error>> {
+                let starts = group.dataset(&format!("{}/t_s", summary::group_name(interval_s)))?.read_raw::<u64>()?;
+                let mut summarizer = Summarizer::new(interval_s, starts.last().copied());
+                for &name in &summarized {
+                    summarizer.add_field(name);
+                }
+                Ok(summarizer)
+            })
+            .collect::<Result<_, _>>()?;
+        let logger = Self {
+            buffer: Vec::new(),
+            flush_every: DEFAULT_FLUSH_EVERY,
+            max_buffer_bytes: DEFAULT_MAX_BUFFER_KIB * 1024,
+            buffer_bytes: 0,
+            file,
+            group_name: group_name.to_string(),
+            data_map,
+            camera_names: registered("image_path_"),
+            probe_names: registered("probe_temp_"),
+            thermistor_names: registered("thermistor_temp_"),
+            sensor_names: registered("sensor_ok_"),
+            audio_levels: Vec::new(),
+            audio_bands: Vec::new(),
+            pending_audio_rms: HashMap::new(),
+            resumed: true,
+            journal: None,
+            compression,
+            summaries,
+        };
+        write_latest(&logger.file, data_path, group_name)?;
+        info!("Resuming group ({group_name}) of HDF5 file ({file_name}) at {data_path}.");
+        Ok(logger)
+    }
+
+    /// Whether this logger continues an existing session.
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Buffers up to `flush_every` samples, and up to `max_buffer_kib` KiB of them (e.g. of piezo
+    /// bursts and thumbnails), before flushing them to the file. 1 writes every sample right
+    /// away, e.g. to a fast SSD; larger batches spare worn SD cards. The values are clamped to
+    /// `1..=`[`MAX_FLUSH_EVERY`](crate::storage::MAX_FLUSH_EVERY) and
+    /// `1..=`[`MAX_BUFFER_KIB`](crate::storage::MAX_BUFFER_KIB).
+    pub fn with_buffering(mut self, flush_every: usize, max_buffer_kib: usize) -> Self {
+        (self.flush_every, self.max_buffer_bytes) = clamp_buffering(flush_every, max_buffer_kib);
+        self
+    }
+
+    /// Journals each buffered sample to the file at `path` until it is flushed, so that a crash
+    /// doesn't lose it (see [`journal`](crate::storage::journal)). The journal is deleted when the
+    /// logger is dropped after flushing.
+    pub(crate) fn with_journal(mut self, path: impl Into<std::path::PathBuf>) -> Result<Self, Box<dyn Error>> {
+        self.journal = Some(Journal::create(path, &self.group_name)?);
+        Ok(self)
+    }
+
+    /// The most recent session in the HDF5 file at `data_path/file_name` and the files per night
+    /// (see [`data_files`]), with the time of its last sample (or, if it has none, of its start)
+    /// in seconds since UNIX epoch. `None` if there are no files or sessions.
+    pub fn last_session(data_path: &str, file_name: &str) -> Result<Option<(String, u64)>, Box<dyn Error>> {
+        if !Path::new(data_path).exists() {
+            return Ok(None);
         }
-        Ok(())
+        let mut last: Option<(String, u64, File)> = None;
+        for data_file in data_files(data_path, file_name)? {
+            let file = File::open(data_path.to_string() + "/" + &data_file)?;
+            // Session names are start times, which sort chronologically
+            let latest = file.member_names()?.into_iter()
+                .filter_map(|name| session_start(&name).map(|start| (name, start)))
+                .max();
+            if let Some((group_name, start)) = latest {
+                if last.as_ref().is_none_or(|(last_name, _, _)| group_name > *last_name) {
+                    last = Some((group_name, start, file));
+                }
+            }
+        }
+        let Some((group_name, start, file)) = last else {
+            return Ok(None);
+        };
+        let timestamps = file.group(&group_name)?.dataset("timestamp")?.read_raw::<u64>()?;
+        Ok(Some((group_name, timestamps.last().copied().unwrap_or(start))))
     }
 
-    /// Appends a new `AudioRecording` entry to the HDF5 file.
-    #[tracing::instrument(skip(self))]
-    pub fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
-        let group = self.file.group(&self.group_name)?;
-        Ok(append_to_dataset(&group, "audio", &[H5AudioMetadata::from(audio_recording)])?)
+    /// The session being recorded in `data_path`, or the last one recorded: the session the
+    /// [`LATEST`] link points at or, without it (e.g. on other platforms than Unix), the most
+    /// recent one a [`LATEST`] attribute of the HDF5 file at `data_path/file_name` or of the files
+    /// per night points at. Files written before the pointer existed fall back to
+    /// [`last_session`](Self::last_session). `None` if there are no sessions.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sleep_recorder::data::{SessionReader, SleepDataLogger};
+    /// let tonight = SleepDataLogger::current_session("/path/to/data", "sleep_data.h5")
+    ///     .expect("Failed to find the current session")
+    ///     .expect("No sessions");
+    /// let session = SessionReader::open("/path/to/data", "sleep_data.h5", &tonight).expect("Failed to open session");
+    /// ```
+    pub fn current_session(data_path: &str, file_name: &str) -> Result<Option<String>, Box<dyn Error>> {
+        if !Path::new(data_path).exists() {
+            return Ok(None);
+        }
+        let exists = |group_name: &str| {
+            File::open(session_path(data_path, file_name, group_name)).is_ok_and(|file| file.group(group_name).is_ok())
+        };
+        if let Ok(target) = std::fs::read_link(Path::new(data_path).join(LATEST)) {
+            if let Some(group_name) = target.to_str().filter(|name| exists(name)) {
+                return Ok(Some(group_name.to_string()));
+            }
+        }
+        let mut latest: Option<String> = None;
+        for data_file in data_files(data_path, file_name)? {
+            let file = File::open(data_path.to_string() + "/" + &data_file)?;
+            let Ok(attr) = file.attr(LATEST) else {
+                continue;
+            };
+            let group_name = attr.read_scalar::<VarLenUnicode>()?.to_string();
+            // Session names are start times, which sort chronologically
+            if file.group(&group_name).is_ok() && latest.as_ref().is_none_or(|latest| group_name > *latest) {
+                latest = Some(group_name);
+            }
+        }
+        match latest {
+            Some(group_name) => Ok(Some(group_name)),
+            None => Ok(Self::last_session(data_path, file_name)?.map(|(group_name, _)| group_name)),
+        }
     }
 
-    /// Flushes the buffered data to the HDF5 file.
-    #[tracing::instrument(skip(self))]
-    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
-        let buffer = std::mem::take(&mut self.buffer);
-        let file = self.file.clone();
-        let group_name = self.group_name.clone();
+    /// The sessions in the HDF5 file at `data_path/file_name` and the files per night (see
+    /// [`data_files`]), oldest first. Groups without a `timestamp` dataset aren't sessions, and
+    /// are left out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sleep_recorder::data::SleepDataLogger;
+    /// for session in SleepDataLogger::list_sessions("/path/to/data", "sleep_data.h5").expect("Failed to list sessions") {
+    ///     println!("{}: {} samples", session.name, session.sample_count);
+    /// }
+    /// ```
+    pub fn list_sessions(data_path: &str, file_name: &str) -> Result<Vec<SessionInfo>, Box<dyn Error>> {
+        let data_files = data_files(data_path, file_name)?;
+        if data_files.is_empty() {
+            return Err(format!("No data file {} in {}", file_name, data_path).into());
+        }
+        let mut sessions = Vec::new();
+        for data_file in data_files {
+            Self::list_file_sessions(data_path, &data_file, &mut sessions)?;
+        }
+        // Session names are start times, which sort chronologically
+        sessions.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(sessions)
+    }
 
-        if buffer.is_empty() {
-            return Ok(());
+    /// Adds the sessions in the HDF5 file at `data_path/file_name` to `sessions`.
+    fn list_file_sessions(data_path: &str, file_name: &str, sessions: &mut Vec<SessionInfo>) -> Result<(), Box<dyn Error>> {
+        let file = File::open(data_path.to_string() + "/" + file_name)?;
+        for name in file.member_names()? {
+            let Ok(group) = file.group(&name) else {
+                continue;
+            };
+            let Ok(timestamps) = group.dataset("timestamp").and_then(|dataset| dataset.read_raw::<u64>()) else {
+                continue;
+            };
+            let mut datasets: Vec<String> = group.member_names()?.into_iter()
+                .filter(|member| group.dataset(member).is_ok())
+                .collect();
+            datasets.sort();
+            let start_s = timestamps.first().copied().or_else(|| session_start(&name)).unwrap_or_default();
+            sessions.push(SessionInfo {
+                file_name: file_name.to_string(),
+                start_s,
+                end_s: timestamps.last().copied().unwrap_or(start_s),
+                sample_count: timestamps.len(),
+                datasets,
+                name,
+            });
         }
+        Ok(())
+    }
 
-        let group = file.group(&group_name)?;
+    /// Stores the session's metadata as attributes of its group: a string attribute per field,
+    /// and the sensor names as a `sensors` string array. Metadata can only be written once.
+    pub fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+        let group = self.file.group(&self.group_name)?;
+        if group.attr("software_version").is_ok() {
+            return Err(format!("Metadata of session {} was already written", self.group_name).into());
+        }
+        for (name, value) in metadata.string_fields() {
+            group.new_attr::<VarLenUnicode>().create(name)?.write_scalar(&VarLenUnicode::from_str(value)?)?;
+        }
+        // Zero-length attributes aren't portable, so an empty inventory is left out
+        if !metadata.sensors.is_empty() {
+            let sensors = metadata.sensors.iter()
+                .map(|s| VarLenUnicode::from_str(s))
+                .collect::<Result<Vec<_>, _>>()?;
+            group.new_attr::<VarLenUnicode>().shape([sensors.len()]).create("sensors")?.write_raw(&sensors)?;
+        }
+        Ok(())
+    }
 
-        // Collect data from buffer
-        for (name, sleep_field) in self.data_map.iter() {
-            match sleep_field {
-                SleepField::Bool(f) => {
-                    let data: Vec<bool> = buffer.iter().map(f).collect();
-                    append_to_dataset(&group, name, &data)?;
-                }
-                SleepField::U16(f) => {
-                    let data: Vec<u16> = buffer.iter().map(f).collect();
-                    append_to_dataset(&group, name, &data)?;
-                }
-                SleepField::U64(f) => {
-                    let data: Vec<u64> = buffer.iter().map(f).collect();
-                    append_to_dataset(&group, name, &data)?;
-                }
-                SleepField::F32(f) => {
-                    let data: Vec<f32> = buffer.iter().map(f).collect();
-                    append_to_dataset(&group, name, &data)?;
-                }
-                SleepField::String(f) => {
-                    let data: Vec<VarLenUnicode> = buffer.iter().map(f).collect();
-                    append_to_dataset(&group, name, &data)?;
-                }
+    /// Registers a per-sample field of a sensor (see [`Sensor::fields`](crate::sensor::Sensor::fields)),
+    /// creating its dataset. Samples written before, in a resumed session, get the placeholder of
+    /// the field's type (see [`SleepField`]).
+    ///
+    /// Numeric fields are also summarized (see [`summary`]), with `NAN` aggregates for the
+    /// intervals written before.
+    ///
+    /// Like cameras, fields must be registered before the first sample is flushed.
+    pub fn register_field(&mut self, name: &'static str, field: SleepField) -> Result<(), Box<dyn Error>> {
+        if self.data_map.contains_key(name) {
+            return self.already_registered("Field", name);
+        }
+        let group = self.registration_group(name)?;
+        field.create_column(&group, name, &self.compression, group.dataset("timestamp")?.shape()[0])?;
+        if summary::is_summarized(name, &field) {
+            for summarizer in &mut self.summaries {
+                create_summary_datasets(&group, &summary::group_name(summarizer.interval_s()), name, &self.compression)?;
+                summarizer.add_field(name);
             }
         }
+        self.data_map.insert(name, field);
+        Ok(())
+    }
 
-        info!("Successfully flushed to hdf5");
+    /// Registers an additional camera, creating its `image_path_<name>`, `image_motion_live_<name>`,
+    /// `clip_path_<name>`, `raw_image_path_<name>`, and `thumbnail_<name>` datasets. Samples without
+    /// a result for this camera are stored as empty paths and thumbnails, and `NAN`.
+    ///
+    /// Cameras must be registered before the first sample is flushed, so that their datasets
+    /// stay aligned with `timestamp`. In a resumed session, cameras of the session are already
+    /// registered, and new ones get placeholders for the samples written before.
+    pub fn register_camera(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if self.camera_names.iter().any(|n| n == name) {
+            return self.already_registered("Camera", name);
+        }
+        let group = self.registration_group(name)?;
+        self.sample_dataset(&group, &format!("image_path_{name}"), VarLenUnicode::default())?;
+        self.sample_dataset(&group, &format!("image_motion_live_{name}"), f32::NAN)?;
+        self.sample_dataset(&group, &format!("clip_path_{name}"), VarLenUnicode::default())?;
+        self.sample_dataset(&group, &format!("raw_image_path_{name}"), VarLenUnicode::default())?;
+        self.sample_dataset(&group, &format!("thumbnail_{name}"), VarLenArray::<u8>::from_slice(&[]))?;
+        self.camera_names.push(name.to_string());
         Ok(())
-    }    
-}
+    }
 
-/// Appends new values to an existing dataset in the HDF5 file. 
-/// Uses `resize` and `write_slice` to add new data.
-/// # Arguments
-/// * `group` - The HDF5 group containing the dataset.
-/// * `dataset_name` - The name of the dataset to append to.
-/// * `new_vals` - The new values to append to the dataset.
-///
-/// # Errors
-/// If the dataset does not exist or if there is an error during resizing or writing.
-fn append_to_dataset<T: H5Type>(group: &hdf5::Group, dataset_name: &str, new_vals: &[T]) -> hdf5::Result<()> {
-    let dataset = group.dataset(dataset_name)?;
+    /// Registers a named temperature probe, creating its `probe_temp_<name>` dataset. Samples
+    /// without a reading for this probe are stored as `NAN`.
+    ///
+    /// Like cameras, probes must be registered before the first sample is flushed.
+    pub fn register_probe(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if self.probe_names.iter().any(|n| n == name) {
+            return self.already_registered("Probe", name);
+        }
+        let group = self.registration_group(name)?;
+        self.sample_dataset(&group, &format!("probe_temp_{name}"), f32::NAN)?;
+        self.probe_names.push(name.to_string());
+        Ok(())
+    }
+
+    /// Registers an additional thermistor, creating its `thermistor_temp_<name>` dataset. Samples
+    /// without a reading for this thermistor are stored as `NAN`.
+    ///
+    /// Like cameras, thermistors must be registered before the first sample is flushed.
+    pub fn register_thermistor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if self.thermistor_names.iter().any(|n| n == name) {
+            return self.already_registered("Thermistor", name);
+        }
+        let group = self.registration_group(name)?;
+        self.sample_dataset(&group, &format!("thermistor_temp_{name}"), f32::NAN)?;
+        self.thermistor_names.push(name.to_string());
+        Ok(())
+    }
 
-    // 1) find current length
-    let old_len = dataset.shape()[0];
-    let add_len = new_vals.len();
-    let new_len = old_len + add_len;
+    /// Registers a sensor whose health is recorded with every sample, creating its
+    /// `sensor_ok_<key>`, `sensor_state_<key>`, `sensor_error_<key>`, and `sensor_error_age_s_<key>`
+    /// datasets, with the key from [`sensor_status_key`]. The state is a [`SensorState`] as `u8`,
+    /// the error is empty when the measurement succeeded, and the error age is `NAN` until the
+    /// sensor first fails.
+    ///
+    /// Like cameras, sensors must be registered before the first sample is flushed.
+    pub fn register_sensor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let key = sensor_status_key(name);
+        if let Some(registered) = self.sensor_names.iter_mut().find(|n| sensor_status_key(n) == key) {
+            if self.resumed {
+                // Sensors of a resumed session are only known by their key until registered again
+                *registered = name.to_string();
+            }
+            return self.already_registered("Sensor", name);
+        }
+        let group = self.registration_group(name)?;
+        self.sample_dataset(&group, &format!("sensor_ok_{key}"), false)?;
+        self.sample_dataset(&group, &format!("sensor_state_{key}"), SensorState::Initializing as u8)?;
+        self.sample_dataset(&group, &format!("sensor_error_{key}"), VarLenUnicode::default())?;
+        self.sample_dataset(&group, &format!("sensor_error_age_s_{key}"), f32::NAN)?;
+        self.sensor_names.push(name.to_string());
+        Ok(())
+    }
 
-    // 2) resize the dataset
-    //    for 1‑D you can just pass the new length
-    dataset.resize(new_len)?;
+    /// Result of registering a name that is already registered: fine in a resumed session, where
+    /// the session's names are registered again.
+    fn already_registered(&self, kind: &str, name: &str) -> Result<(), Box<dyn Error>> {
+        match self.resumed {
+            true => Ok(()),
+            false => Err(format!("{} {} is already registered", kind, name).into()),
+        }
+    }
 
-    // 3) write into the hyperslab at the end
-    //    note the comma to make it a 1‑tuple
-    dataset.write_slice(new_vals, (old_len..new_len,))?;
+    /// The session group, checking that no samples have been written yet unless the session was
+    /// resumed.
+    fn registration_group(&self, name: &str) -> Result<hdf5::Group, Box<dyn Error>> {
+        let group = self.file.group(&self.group_name)?;
+        if !self.resumed && group.dataset("timestamp")?.shape()[0] > 0 {
+            return Err(format!("{} registered after data was written", name).into());
+        }
+        Ok(group)
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    /// Creates a per-sample dataset, with `placeholder` for the samples already written.
+    fn sample_dataset<T: H5Type + Clone>(&self, group: &hdf5::Group, name: &str, placeholder: T) -> Result<(), Box<dyn Error>> {
+        let sample_count = group.dataset("timestamp")?.shape()[0];
+        AppendableColumn::create(group, name, &self.compression)?.pad_to(sample_count, placeholder)?;
+        Ok(())
+    }
+
+    /// Appends a new `SleepData` entry to the buffer.
+    /// If the buffer reaches the specified size, it flushes the data to the HDF5 file.
+    /// The buffer is flushed once it holds `flush_every` entries or takes `max_buffer_kib` KiB
+    /// (see [`with_buffering`](Self::with_buffering)).
+    /// With a journal, the sample is journaled before it is buffered.
+    #[tracing::instrument(skip(self, sample))]
+    pub fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+        // The sample holds the whole piezo burst, so it is only dumped at trace level
+        trace!("Pushing sample to buffer: {:?}", &sample);
+        if let Some(Err(e)) = self.journal.as_mut().map(|journal| journal.append(&sample)) {
+            warn!("Failed to journal sample: {}", e);
+        }
+        self.buffer_bytes += sample.buffered_size();
+        self.buffer.push(sample);
+        if self.buffer.len() >= self.flush_every || self.buffer_bytes >= self.max_buffer_bytes {
+            info!("Flushing data to HDF5 file...");
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Appends a new `AudioRecording` entry to the HDF5 file, with the RMS windows appended for
+    /// it so far (see [`append_audio_rms`](Self::append_audio_rms)).
+    #[tracing::instrument(skip(self))]
+    pub fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        let group = self.file.group(&self.group_name)?;
+        let mut entry = H5AudioMetadata::from(audio_recording);
+        if let Some((times, levels)) = self.pending_audio_rms.remove(&entry.start_time_s) {
+            entry.audio_rms_t_s = VarLenArray::from_slice(&times);
+            entry.audio_rms_db = VarLenArray::from_slice(&levels);
+        }
+        Ok(append_to_dataset(&group, "audio", &[entry])?)
+    }
+
+    /// Appends RMS windows (dBFS of the windows starting at `rms_t_s`) to the `audio` entry of the
+    /// recording started at `start_time_s`, e.g. as a live analyzer computes them. The windows of
+    /// a recording that isn't added yet are kept until [`add_audio_entry`](Self::add_audio_entry)
+    /// adds it, and lost if it never is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `rms_t_s` and `rms_db` differ in length, or if the entry cannot be
+    /// updated.
+    pub fn append_audio_rms(&mut self, start_time_s: u64, rms_t_s: &[u64], rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+        if rms_t_s.len() != rms_db.len() {
+            return Err(format!("{} RMS window timestamps for {} levels", rms_t_s.len(), rms_db.len()).into());
+        }
+        let dataset = self.file.group(&self.group_name)?.dataset("audio")?;
+        let mut entries = dataset.read_raw::<H5AudioMetadata>()?;
+        let Some(index) = entries.iter().rposition(|entry| entry.start_time_s == start_time_s) else {
+            let (times, levels) = self.pending_audio_rms.entry(start_time_s).or_default();
+            times.extend_from_slice(rms_t_s);
+            levels.extend_from_slice(rms_db);
+            return Ok(());
+        };
+        let entry = &mut entries[index];
+        entry.audio_rms_t_s = VarLenArray::from_slice(&[entry.audio_rms_t_s.as_slice(), rms_t_s].concat());
+        entry.audio_rms_db = VarLenArray::from_slice(&[entry.audio_rms_db.as_slice(), rms_db].concat());
+        dataset.write_slice(&entries[index..index + 1], (index..index + 1,))?;
+        Ok(())
+    }
+
+    /// Appends an actuator state change to the `actuator_event_*` datasets. Events are rare, so
+    /// they are written immediately rather than buffered.
+    pub fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        let group = self.file.group(&self.group_name)?;
+        append_to_dataset(&group, "actuator_event_t_s", &[event.timestamp_s])?;
+        append_to_dataset(&group, "actuator_event_name", &[VarLenUnicode::from_str(&event.name)?])?;
+        append_to_dataset(&group, "actuator_event_on", &[event.on])?;
+        Ok(())
+    }
+
+    /// Appends an annotation to the `events` dataset. Events are rare, so they are written
+    /// immediately rather than buffered.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sleep_recorder::data::{Event, SleepDataLogger};
+    /// let mut logger = SleepDataLogger::new("/path/to/data", "sleep_data.h5").expect("Failed to create logger");
+    /// logger.add_event(&Event {
+    ///     timestamp_s: 1745873251,
+    ///     category: "bedtime".to_string(),
+    ///     text: "went to bed".to_string(),
+    /// }).expect("Failed to add event");
+    /// ```
+    pub fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let group = self.file.group(&self.group_name)?;
+        append_to_dataset(&group, "events", &[H5Event::from(event)])?;
+        Ok(())
+    }
+
+    /// Buffers a live audio level (RMS dBFS of the window starting at `timestamp_s`), written to the
+    /// `live_audio_rms_db` and `live_audio_rms_t_s` datasets on the next flush.
+    pub fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32) {
+        self.audio_levels.push((timestamp_s, rms_db));
+    }
+
+    /// Buffers the band levels of a live-analyzed audio window (dBFS in the order of [`BANDS`],
+    /// the window starting at `timestamp_s`), written to the band datasets on the next flush.
+    pub fn append_audio_bands(&mut self, timestamp_s: u64, levels_db: [f32; BANDS.len()]) {
+        self.audio_bands.push((timestamp_s, levels_db));
+    }
+
+    /// Flushes the buffered data to the HDF5 file.
+    #[tracing::instrument(skip(self))]
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let file = self.file.clone();
+        let group_name = self.group_name.clone();
+
+        let audio_levels = std::mem::take(&mut self.audio_levels);
+        if !audio_levels.is_empty() {
+            let group = file.group(&group_name)?;
+            let (times, levels): (Vec<u64>, Vec<f32>) = audio_levels.into_iter().unzip();
+            append_to_dataset(&group, "live_audio_rms_t_s", &times)?;
+            append_to_dataset(&group, "live_audio_rms_db", &levels)?;
+        }
+
+        let audio_bands = std::mem::take(&mut self.audio_bands);
+        if !audio_bands.is_empty() {
+            let group = file.group(&group_name)?;
+            let times: Vec<u64> = audio_bands.iter().map(|(t, _)| *t).collect();
+            append_to_dataset(&group, BAND_TIMES_DATASET, &times)?;
+            for (index, band) in BANDS.iter().enumerate() {
+                let levels: Vec<f32> = audio_bands.iter().map(|(_, levels)| levels[index]).collect();
+                append_to_dataset(&group, band.dataset, &levels)?;
+            }
+        }
+
+        let buffer = std::mem::take(&mut self.buffer);
+        self.buffer_bytes = 0;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+
+        let group = file.group(&group_name)?;
+
+        // Collect data from buffer
+        for (name, sleep_field) in self.data_map.iter() {
+            sleep_field.append_column(&group, name, &buffer)?;
+        }
+
+        // Write the summary intervals the buffered samples complete
+        for summarizer in &mut self.summaries {
+            let mut intervals = Vec::new();
+            for sample in &buffer {
+                let readings: Vec<f32> = summarizer.fields().iter()
+                    .map(|name| summary::reading(sample, name, &self.data_map[name]))
+                    .collect();
+                intervals.extend(summarizer.add(sample.timestamp_s, &readings));
+            }
+            append_intervals(&group, summarizer.interval_s(), summarizer.fields(), &intervals)?;
+        }
+
+        for name in &self.camera_names {
+            let paths: Vec<VarLenUnicode> = buffer.iter()
+                .map(|d| d.extra_cameras.get(name)
+                    .and_then(|r| VarLenUnicode::from_str(&r.image_path).ok())
+                    .unwrap_or_default())
+                .collect();
+            let motion: Vec<f32> = buffer.iter()
+                .map(|d| d.extra_cameras.get(name).and_then(|r| r.motion).unwrap_or(f32::NAN))
+                .collect();
+            append_to_dataset(&group, &format!("image_path_{name}"), &paths)?;
+            let optional_paths = |path: fn(&CameraAndMotionResult) -> Option<&str>| -> Vec<VarLenUnicode> {
+                buffer.iter()
+                    .map(|d| d.extra_cameras.get(name)
+                        .and_then(path)
+                        .and_then(|p| VarLenUnicode::from_str(p).ok())
+                        .unwrap_or_default())
+                    .collect()
+            };
+            append_to_dataset(&group, &format!("image_motion_live_{name}"), &motion)?;
+            append_to_dataset(&group, &format!("clip_path_{name}"), &optional_paths(|r| r.clip_path.as_deref()))?;
+            append_to_dataset(&group, &format!("raw_image_path_{name}"), &optional_paths(|r| r.raw_image_path.as_deref()))?;
+            let thumbnails: Vec<VarLenArray<u8>> = buffer.iter()
+                .map(|d| d.extra_cameras.get(name)
+                    .and_then(|r| r.thumbnail.as_ref())
+                    .map_or_else(|| VarLenArray::from_slice(&[]), |t| VarLenArray::from_slice(&t.0)))
+                .collect();
+            append_to_dataset(&group, &format!("thumbnail_{name}"), &thumbnails)?;
+        }
+        // Bursts are concatenated; each sample stores where its burst starts (it ends where the
+        // next one starts)
+        let mut burst_start = group.dataset("piezo_bcg_mv")?.shape()[0] as u64;
+        let starts: Vec<u64> = buffer.iter()
+            .map(|d| {
+                let start = burst_start;
+                burst_start += d.piezo_bcg_mv.len() as u64;
+                start
+            })
+            .collect();
+        let bursts: Vec<f32> = buffer.iter().flat_map(|d| d.piezo_bcg_mv.iter().copied()).collect();
+        append_to_dataset(&group, "piezo_bcg_mv", &bursts)?;
+        append_to_dataset(&group, "piezo_bcg_start", &starts)?;
+
+        for name in &self.probe_names {
+            let temps: Vec<f32> = buffer.iter()
+                .map(|d| d.probe_temps_c.get(name).copied().unwrap_or(f32::NAN))
+                .collect();
+            append_to_dataset(&group, &format!("probe_temp_{name}"), &temps)?;
+        }
+        for name in &self.thermistor_names {
+            let temps: Vec<f32> = buffer.iter()
+                .map(|d| d.extra_thermistor_temps_c.get(name).copied().unwrap_or(f32::NAN))
+                .collect();
+            append_to_dataset(&group, &format!("thermistor_temp_{name}"), &temps)?;
+        }
+        for name in &self.sensor_names {
+            let key = sensor_status_key(name);
+            let statuses: Vec<Option<&SensorStatus>> = buffer.iter().map(|d| d.sensor_status.get(name)).collect();
+            let ok: Vec<bool> = statuses.iter().map(|s| s.is_some_and(|s| s.ok())).collect();
+            let states: Vec<u8> = statuses.iter()
+                .map(|s| s.map_or(SensorState::Initializing, |s| s.state) as u8)
+                .collect();
+            let errors: Vec<VarLenUnicode> = statuses.iter()
+                .map(|s| s.and_then(|s| s.error.as_deref())
+                    .and_then(|e| VarLenUnicode::from_str(e).ok())
+                    .unwrap_or_default())
+                .collect();
+            let ages: Vec<f32> = statuses.iter()
+                .map(|s| s.and_then(|s| s.last_error_age_s).map_or(f32::NAN, |age| age as f32))
+                .collect();
+            append_to_dataset(&group, &format!("sensor_ok_{key}"), &ok)?;
+            append_to_dataset(&group, &format!("sensor_state_{key}"), &states)?;
+            append_to_dataset(&group, &format!("sensor_error_{key}"), &errors)?;
+            append_to_dataset(&group, &format!("sensor_error_age_s_{key}"), &ages)?;
+        }
+
+        if let Some(journal) = &mut self.journal {
+            journal.clear()?;
+        }
+        info!("Successfully flushed to hdf5");
+        Ok(())
+    }
+
+    /// Writes the summary intervals still being aggregated, once the last sample is flushed.
+    fn finish_summaries(&mut self) -> Result<(), Box<dyn Error>> {
+        let group = self.file.group(&self.group_name)?;
+        for summarizer in &mut self.summaries {
+            let intervals: Vec<Interval> = summarizer.finish().into_iter().collect();
+            append_intervals(&group, summarizer.interval_s(), summarizer.fields(), &intervals)?;
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for SleepDataLogger {
+    fn session_name(&self) -> &str {
+        &self.group_name
+    }
+
+    fn is_resumed(&self) -> bool {
+        SleepDataLogger::is_resumed(self)
+    }
+
+    fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::write_metadata(self, metadata)
+    }
+
+    fn register_field(&mut self, name: &'static str, field: SleepField) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::register_field(self, name, field)
+    }
+
+    fn register_camera(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::register_camera(self, name)
+    }
+
+    fn register_probe(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::register_probe(self, name)
+    }
+
+    fn register_thermistor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::register_thermistor(self, name)
+    }
+
+    fn register_sensor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::register_sensor(self, name)
+    }
+
+    fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::append(self, sample)
+    }
+
+    fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::add_audio_entry(self, audio_recording)
+    }
+
+    fn append_audio_rms(&mut self, start_time_s: u64, rms_t_s: &[u64], rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::append_audio_rms(self, start_time_s, rms_t_s, rms_db)
+    }
+
+    fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::add_actuator_event(self, event)
+    }
+
+    fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::add_event(self, event)
+    }
+
+    fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32) {
+        SleepDataLogger::append_audio_level(self, timestamp_s, rms_db)
+    }
+
+    fn append_audio_bands(&mut self, timestamp_s: u64, levels_db: [f32; BANDS.len()]) {
+        SleepDataLogger::append_audio_bands(self, timestamp_s, levels_db)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        SleepDataLogger::flush(self)
+    }
+}
+
+/// Window start timestamps and per-band dBFS levels, as read by [`SessionReader::audio_band_levels`].
+type BandLevels = (Vec<u64>, Vec<[f32; BANDS.len()]>);
+
+/// Read-only access to a recorded session group.
+///
+/// Hides the dataset names and HDF5 types used by `SleepDataLogger`, so that analysis code and
+/// downstream users don't need to reimplement raw dataset reads.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::data::SessionReader;
+/// let session = SessionReader::open("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31")
+///     .expect("Failed to open session");
+/// let timestamps = session.timestamps().expect("Failed to read timestamps");
+/// ```
+#[derive(Debug)]
+pub struct SessionReader {
+    /// HDF5 file handle.
+    file: File,
+    /// Name of the HDF5 group for this session.
+    group_name: String,
+}
+
+impl SessionReader {
+    /// Opens the session `group_name` in the HDF5 file at `data_path/file_name`, or the file of
+    /// its night (see [`session_path`]), read-only.
+    ///
+    /// Sessions of an older [`SCHEMA_VERSION`] can be opened, but may lack datasets until they
+    /// are upgraded with [`upgrade_session`]. Sessions written by newer code are rejected.
+    pub fn open(data_path: &str, file_name: &str, group_name: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(session_path(data_path, file_name, group_name))?;
+        let group = file.group(group_name)
+            .map_err(|e| format!("Session {} not found in {}: {}", group_name, file_name, e))?;
+        let version = schema_version(&group)?;
+        if version > SCHEMA_VERSION {
+            return Err(format!("Session {} has schema version {}, newer than the supported {}", group_name, version, SCHEMA_VERSION).into());
+        }
+        if version < SCHEMA_VERSION {
+            warn!("Session {} has schema version {}; upgrade it to {} with upgrade_session", group_name, version, SCHEMA_VERSION);
+        }
+        Ok(Self { file, group_name: group_name.to_string() })
+    }
+
+    /// Chunks of the session's datasets that cannot be read (see [`verify`]).
+    pub fn verify(&self) -> Result<Vec<CorruptChunk>, Box<dyn Error>> {
+        verify(&self.group()?)
+    }
+
+    /// Schema version of the session (see [`SCHEMA_VERSION`]).
+    pub fn schema_version(&self) -> Result<u32, Box<dyn Error>> {
+        schema_version(&self.group()?)
+    }
+
+    /// Name of the session group.
+    pub fn group_name(&self) -> &str {
+        &self.group_name
+    }
+
+    /// Metadata of the session. Fields that weren't stored (e.g. in sessions recorded before
+    /// metadata was) are empty.
+    pub fn metadata(&self) -> Result<SessionMetadata, Box<dyn Error>> {
+        let group = self.group()?;
+        let string_attr = |name: &str| -> Result<String, Box<dyn Error>> {
+            match group.attr(name) {
+                Ok(attr) => Ok(attr.read_scalar::<VarLenUnicode>()?.to_string()),
+                Err(_) => Ok(String::new()),
+            }
+        };
+        let sensors = match group.attr("sensors") {
+            Ok(attr) => attr.read_raw::<VarLenUnicode>()?.iter().map(|s| s.to_string()).collect(),
+            Err(_) => Vec::new(),
+        };
+        Ok(SessionMetadata {
+            device_id: string_attr("device_id")?,
+            location: string_attr("location")?,
+            subject: string_attr("subject")?,
+            notes: string_attr("notes")?,
+            software_version: string_attr("software_version")?,
+            audio_weighting: string_attr("audio_weighting")?,
+            audio_spl_offset_db: string_attr("audio_spl_offset_db")?,
+            sensors,
+        })
+    }
+
+    /// When the `media` files of the session were deleted by [`retention::prune`](crate::retention::prune),
+    /// in seconds since UNIX epoch, or `None` if they are kept.
+    pub fn purged_s(&self, media: Media) -> Result<Option<u64>, Box<dyn Error>> {
+        let dataset = match media {
+            Media::Images => "image_path",
+            Media::Audio => "audio",
+        };
+        match self.group()?.dataset(dataset)?.attr("purged_s") {
+            Ok(attr) => Ok(Some(attr.read_scalar::<u64>()?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Noise statistics of the session (see [`noise_stats`](crate::noise_stats)), or `None` if
+    /// they weren't computed.
+    pub fn noise_stats(&self) -> Result<Option<NoiseStats>, Box<dyn Error>> {
+        Ok(NoiseStats::read_attrs(&self.group()?)?)
+    }
+
+    /// Number of sensor samples recorded in the session.
+    pub fn sample_count(&self) -> Result<usize, Box<dyn Error>> {
+        Ok(self.group()?.dataset("timestamp")?.shape()[0])
+    }
+
+    /// Summaries of the session's fields per interval of `interval_s` seconds, one of
+    /// [`summary::INTERVALS_S`]. Empty if the session has no such summary.
+    pub fn summary(&self, interval_s: u64) -> Result<Summary, Box<dyn Error>> {
+        let Ok(group) = self.group()?.group(&summary::group_name(interval_s)) else {
+            return Ok(Summary::default());
+        };
+        let start_s = group.dataset("t_s")?.read_raw::<u64>()?;
+        let mut fields = HashMap::new();
+        for name in group.member_names()? {
+            let Some(field) = name.strip_suffix("_mean") else {
+                continue;
+            };
+            let read = |aggregate: &str| -> hdf5::Result<Vec<f32>> {
+                group.dataset(&format!("{field}_{aggregate}"))?.read_raw::<f32>()
+            };
+            let aggregates = read("mean")?.into_iter().zip(read("min")?).zip(read("max")?)
+                .map(|((mean, min), max)| Aggregate { mean, min, max })
+                .collect();
+            fields.insert(field.to_string(), aggregates);
+        }
+        Ok(Summary { start_s, fields })
+    }
+
+    /// Sample timestamps in seconds since UNIX epoch.
+    pub fn timestamps(&self) -> Result<Vec<u64>, Box<dyn Error>> {
+        Ok(self.group()?.dataset("timestamp")?.read_raw::<u64>()?)
+    }
+
+    /// The samples of the session, in the order they were recorded, including the results of the
+    /// additional cameras, probes, thermistors, and sensors.
+    ///
+    /// Fields of datasets missing from sessions of an older schema version are unavailable (`NAN`,
+    /// 0, `false`, or empty). Additional cameras, probes, and thermistors are only included in the
+    /// samples that have a result for them. Sensors are keyed by the names stored in the
+    /// [`metadata`](Self::metadata), or by their dataset key if the session has none.
+    pub fn samples(&self) -> Result<Vec<SleepData>, Box<dyn Error>> {
+        let group = self.group()?;
+        let count = self.sample_count()?;
+        let (mut bools, mut u16s, mut u64s, mut f32s, mut strings, mut bytes) =
+            (HashMap::new(), HashMap::new(), HashMap::new(), HashMap::new(), HashMap::new(), HashMap::new());
+        for (name, sleep_field) in sleep_fields() {
+            match sleep_field {
+                SleepField::Bool(_) => { bools.insert(name, read_column(&group, name, count, false)?); }
+                SleepField::U16(_) => { u16s.insert(name, read_column(&group, name, count, 0u16)?); }
+                SleepField::U64(_) => { u64s.insert(name, read_column(&group, name, count, 0u64)?); }
+                SleepField::F32(_) => { f32s.insert(name, read_column(&group, name, count, f32::NAN)?); }
+                SleepField::String(_) => {
+                    let values = read_column(&group, name, count, VarLenUnicode::default())?;
+                    strings.insert(name, values.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+                }
+                SleepField::Bytes(_) => {
+                    let values = read_column(&group, name, count, VarLenArray::<u8>::from_slice(&[]))?;
+                    bytes.insert(name, values.iter().map(|v| Thumbnail(v.to_vec())).collect::<Vec<_>>());
+                }
+            }
+        }
+        let image_motion = self.image_motion()?;
+        let mut piezo_bursts = match group.dataset("piezo_bcg_start") {
+            Ok(_) => self.piezo_bursts()?,
+            Err(_) => vec![Vec::new(); count],
+        };
+
+        let mut names = group.member_names()?;
+        names.sort();
+        let registered = |prefix: &str| -> Vec<String> {
+            names.iter().filter_map(|n| n.strip_prefix(prefix)).map(str::to_string).collect()
+        };
+        let mut cameras = Vec::new();
+        for name in registered("image_path_") {
+            let optional = |paths: Vec<String>| paths.into_iter().map(|p| Some(p).filter(|p| !p.is_empty())).collect::<Vec<_>>();
+            let dataset = |prefix: &str| -> Result<Vec<String>, Box<dyn Error>> {
+                let values = read_column(&group, &format!("{prefix}_{name}"), count, VarLenUnicode::default())?;
+                Ok(values.iter().map(|v| v.to_string()).collect())
+            };
+            let images = self.camera_image_paths(&name)?;
+            let motion = read_column(&group, &format!("image_motion_live_{name}"), count, f32::NAN)?;
+            let clips = optional(dataset("clip_path")?);
+            let raw_images = optional(dataset("raw_image_path")?);
+            let thumbnails = self.camera_thumbnails(&name)?;
+            cameras.push((name, images, motion, clips, raw_images, thumbnails));
+        }
+        let mut probes = Vec::new();
+        for name in registered("probe_temp_") {
+            let temps = self.probe_temps(&name)?;
+            probes.push((name, temps));
+        }
+        let mut thermistors = Vec::new();
+        for name in registered("thermistor_temp_") {
+            let temps = self.thermistor_temps(&name)?;
+            thermistors.push((name, temps));
+        }
+        let sensor_names = self.metadata()?.sensors;
+        let mut sensors = Vec::new();
+        for key in registered("sensor_ok_") {
+            let name = sensor_names.iter().find(|name| sensor_status_key(name) == key).cloned().unwrap_or(key);
+            let status = self.sensor_status(&name)?;
+            sensors.push((name, status));
+        }
+
+        let mut samples = Vec::with_capacity(count);
+        for i in 0..count {
+            samples.push(SleepData {
+                timestamp_s: u64s["timestamp"][i],
+                temperature_c: f32s["temperature"][i],
+                pressure: f32s["pressure"][i],
+                humidity: f32s["humidity"][i],
+                dew_point_c: f32s["dew_point"][i],
+                absolute_humidity_gm3: f32s["absolute_humidity"][i],
+                comfort_index: f32s["comfort_index"][i],
+                co2eq_ppm: u16s["co2eq_ppm"][i],
+                co2_ppm: u16s["co2_ppm"][i],
+                tvoc_ppb: u16s["tvoc_ppb"][i],
+                voc_index: u16s["voc_index"][i],
+                air_quality_index: u16s["air_quality_index"][i],
+                pm1_0_ugm3: u16s["pm1_0_ugm3"][i],
+                pm2_5_ugm3: u16s["pm2_5_ugm3"][i],
+                pm10_ugm3: u16s["pm10_ugm3"][i],
+                thermistor_temp_c: f32s["thermistor_temp"][i],
+                light_lux: f32s["light_lux"][i],
+                image_path: strings["image_path"][i].clone(),
+                image_motion: image_motion[i],
+                image_motion_live: f32s["image_motion_live"][i],
+                clip_path: strings["clip_path"][i].clone(),
+                raw_image_path: strings["raw_image_path"][i].clone(),
+                thumbnail: bytes["thumbnail"][i].clone(),
+                bed_weight_kg: f32s["bed_weight_kg"][i],
+                bed_occupied: bools["bed_occupied"][i],
+                piezo_bcg_mv: piezo_bursts.get_mut(i).map(std::mem::take).unwrap_or_default(),
+                piezo_heart_rate_bpm: f32s["piezo_heart_rate_bpm"][i],
+                piezo_resp_rate_bpm: f32s["piezo_resp_rate_bpm"][i],
+                pir_motion: bools["pir_motion"][i],
+                mmwave_presence: bools["mmwave_presence"][i],
+                mmwave_movement: bools["mmwave_movement"][i],
+                mmwave_heart_rate_bpm: u16s["mmwave_heart_rate_bpm"][i],
+                mmwave_resp_rate_bpm: u16s["mmwave_resp_rate_bpm"][i],
+                cpu_temp_c: f32s["cpu_temp"][i],
+                load_avg_1m: f32s["load_avg_1m"][i],
+                disk_free_mb: u64s["disk_free_mb"][i],
+                mem_used_percent: f32s["mem_used_percent"][i],
+                quality_flags: u16s["quality_flags"][i],
+                extra_cameras: cameras.iter()
+                    .filter(|(_, images, ..)| !images[i].is_empty())
+                    .map(|(name, images, motion, clips, raw_images, thumbnails)| (name.clone(), CameraAndMotionResult {
+                        image_path: images[i].clone(),
+                        motion: Some(motion[i]).filter(|m| !m.is_nan()),
+                        clip_path: clips[i].clone(),
+                        raw_image_path: raw_images[i].clone(),
+                        thumbnail: Some(thumbnails[i].clone()).filter(|t| !t.is_empty()),
+                    }))
+                    .collect(),
+                probe_temps_c: available(&probes, i),
+                extra_thermistor_temps_c: available(&thermistors, i),
+                sensor_status: sensors.iter().map(|(name, status)| (name.clone(), status[i].clone())).collect(),
+            });
+        }
+        Ok(samples)
+    }
+
+    /// Paths of the captured images, one per sample (empty when no image was captured, or the
+    /// session has no camera).
+    pub fn image_paths(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.read_paths("image_path")
+    }
+
+    /// Paths of the captured images without overlay, one per sample (empty when not saved).
+    pub fn raw_image_paths(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.read_paths("raw_image_path")
+    }
+
+    fn read_paths(&self, dataset: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let paths = read_column(&self.group()?, dataset, self.sample_count()?, VarLenUnicode::default())?;
+        Ok(paths.iter().map(|p| p.to_string()).collect())
+    }
+
+    /// Image motion of the frames without overlay, one per sample, as computed by the motion pass
+    /// of the analysis [`Pipeline`](crate::analysis::Pipeline) (`NAN` until it has run, and for the
+    /// first image).
+    pub fn image_motion(&self) -> Result<Vec<f32>, Box<dyn Error>> {
+        read_column(&self.group()?, "image_motion", self.sample_count()?, f32::NAN)
+    }
+
+    /// Image motion, one per sample, as computed live by the camera while recording (`NAN` for the
+    /// first image and samples without one).
+    pub fn image_motion_live(&self) -> Result<Vec<f32>, Box<dyn Error>> {
+        read_column(&self.group()?, "image_motion_live", self.sample_count()?, f32::NAN)
+    }
+
+    /// JPEG thumbnails of the captured images, one per sample (empty when none was stored, or the
+    /// session was recorded before thumbnails were).
+    pub fn thumbnails(&self) -> Result<Vec<Thumbnail>, Box<dyn Error>> {
+        self.read_thumbnails("thumbnail")
+    }
+
+    /// JPEG thumbnails of the images captured by the additional camera `name`, one per sample.
+    pub fn camera_thumbnails(&self, name: &str) -> Result<Vec<Thumbnail>, Box<dyn Error>> {
+        self.read_thumbnails(&format!("thumbnail_{name}"))
+    }
+
+    fn read_thumbnails(&self, dataset: &str) -> Result<Vec<Thumbnail>, Box<dyn Error>> {
+        let thumbnails = read_column(&self.group()?, dataset, self.sample_count()?, VarLenArray::<u8>::from_slice(&[]))?;
+        Ok(thumbnails.iter().map(|t| Thumbnail(t.to_vec())).collect())
+    }
+
+    /// Paths of the motion-triggered clips, one per sample (empty when no clip was recorded).
+    pub fn clip_paths(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.read_paths("clip_path")
+    }
+
+    /// Paths of the motion-triggered clips of the additional camera `name`, one per sample.
+    pub fn camera_clip_paths(&self, name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let paths = self.group()?.dataset(&format!("clip_path_{name}"))?.read_raw::<VarLenUnicode>()?;
+        Ok(paths.iter().map(|p| p.to_string()).collect())
+    }
+
+    /// Paths of the images captured by the additional camera `name`, one per sample.
+    pub fn camera_image_paths(&self, name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let paths = self.group()?.dataset(&format!("image_path_{name}"))?.read_raw::<VarLenUnicode>()?;
+        Ok(paths.iter().map(|p| p.to_string()).collect())
+    }
+
+    /// Image motion of the additional camera `name` as computed live, one per sample (`NAN` when
+    /// unavailable).
+    pub fn camera_motion(&self, name: &str) -> Result<Vec<f32>, Box<dyn Error>> {
+        Ok(self.group()?.dataset(&format!("image_motion_live_{name}"))?.read_raw::<f32>()?)
+    }
+
+    /// Temperatures of the probe `name` in degrees Celsius, one per sample (`NAN` when unavailable).
+    pub fn probe_temps(&self, name: &str) -> Result<Vec<f32>, Box<dyn Error>> {
+        Ok(self.group()?.dataset(&format!("probe_temp_{name}"))?.read_raw::<f32>()?)
+    }
+
+    /// Health of the sensor `name`, one per sample.
+    pub fn sensor_status(&self, name: &str) -> Result<Vec<SensorStatus>, Box<dyn Error>> {
+        let group = self.group()?;
+        let key = sensor_status_key(name);
+        let errors = group.dataset(&format!("sensor_error_{key}"))?.read_raw::<VarLenUnicode>()?;
+        let ages = group.dataset(&format!("sensor_error_age_s_{key}"))?.read_raw::<f32>()?;
+        // Sessions recorded before sensor states were logged only have ready sensors
+        let states = match group.dataset(&format!("sensor_state_{key}")) {
+            Ok(dataset) => dataset.read_raw::<u8>()?.into_iter().map(|s| SensorState::from_u8(s).unwrap_or_default()).collect(),
+            Err(_) => vec![SensorState::Ready; errors.len()],
+        };
+        Ok(errors.iter()
+            .zip(ages)
+            .zip(states)
+            .map(|((error, age), state)| SensorStatus {
+                state,
+                error: Some(error.to_string()).filter(|e| !e.is_empty()),
+                last_error_age_s: (!age.is_nan()).then_some(age as u64),
+            })
+            .collect())
+    }
+
+    /// Temperatures of the additional thermistor `name` in degrees Celsius, one per sample (`NAN`
+    /// when unavailable).
+    pub fn thermistor_temps(&self, name: &str) -> Result<Vec<f32>, Box<dyn Error>> {
+        Ok(self.group()?.dataset(&format!("thermistor_temp_{name}"))?.read_raw::<f32>()?)
+    }
+
+    /// Audio levels measured live during recording, as (window start timestamps, RMS dBFS).
+    pub fn live_audio_levels(&self) -> Result<(Vec<u64>, Vec<f32>), Box<dyn Error>> {
+        let group = self.group()?;
+        Ok((
+            group.dataset("live_audio_rms_t_s")?.read_raw::<u64>()?,
+            group.dataset("live_audio_rms_db")?.read_raw::<f32>()?,
+        ))
+    }
+
+    /// Levels of the frequency [`BANDS`] per analysis window (see
+    /// [`BandMeter`](crate::audio_analysis::BandMeter)), as (window start timestamps, dBFS levels
+    /// in the order of [`BANDS`]).
+    pub fn audio_band_levels(&self) -> Result<BandLevels, Box<dyn Error>> {
+        let group = self.group()?;
+        let times = group.dataset(BAND_TIMES_DATASET)?.read_raw::<u64>()?;
+        let mut levels = vec![[f32::NAN; BANDS.len()]; times.len()];
+        for (index, band) in BANDS.iter().enumerate() {
+            for (level, value) in levels.iter_mut().zip(group.dataset(band.dataset)?.read_raw::<f32>()?) {
+                level[index] = value;
+            }
+        }
+        Ok((times, levels))
+    }
+
+    /// Respiration rates estimated from the audio per window (see
+    /// [`crate::audio_respiration`]), as (window start timestamps, breaths/min or `NAN`).
+    pub fn audio_respiration(&self) -> Result<(Vec<u64>, Vec<f32>), Box<dyn Error>> {
+        let group = self.group()?;
+        Ok((
+            group.dataset(RESP_TIMES_DATASET)?.read_raw::<u64>()?,
+            group.dataset(RESP_RATE_DATASET)?.read_raw::<f32>()?,
+        ))
+    }
+
+    /// Piezo BCG bursts in millivolts, one per sample (empty when no burst was captured).
+    pub fn piezo_bursts(&self) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let group = self.group()?;
+        let samples = group.dataset("piezo_bcg_mv")?.read_raw::<f32>()?;
+        let mut starts = group.dataset("piezo_bcg_start")?.read_raw::<u64>()?;
+        starts.push(samples.len() as u64);
+        Ok(starts.windows(2).map(|w| samples[w[0] as usize..w[1] as usize].to_vec()).collect())
+    }
+
+    /// Metadata of the audio recordings in the session.
+    pub fn audio_entries(&self) -> Result<Vec<H5AudioMetadata>, Box<dyn Error>> {
+        Ok(self.group()?.dataset("audio")?.read_raw::<H5AudioMetadata>()?)
+    }
+
+    /// Actuator state changes in the session, in the order they happened.
+    pub fn actuator_events(&self) -> Result<Vec<ActuatorEvent>, Box<dyn Error>> {
+        let group = self.group()?;
+        let times = group.dataset("actuator_event_t_s")?.read_raw::<u64>()?;
+        let names = group.dataset("actuator_event_name")?.read_raw::<VarLenUnicode>()?;
+        let states = group.dataset("actuator_event_on")?.read_raw::<bool>()?;
+        Ok(times.into_iter().zip(names).zip(states)
+            .map(|((timestamp_s, name), on)| ActuatorEvent { timestamp_s, name: name.to_string(), on })
+            .collect())
+    }
+
+    /// Annotations of the session (see [`SleepDataLogger::add_event`]), in the order they were
+    /// added.
+    pub fn events(&self) -> Result<Vec<Event>, Box<dyn Error>> {
+        Ok(self.group()?.dataset("events")?.read_raw::<H5Event>()?.into_iter().map(Event::from).collect())
+    }
+
+    fn group(&self) -> hdf5::Result<hdf5::Group> {
+        self.file.group(&self.group_name)
+    }
+}
+
+/// Reads the per-sample dataset `name`, or `count` placeholders if the group doesn't have it.
+fn read_column<T: H5Type + Clone>(group: &hdf5::Group, name: &str, count: usize, placeholder: T) -> Result<Vec<T>, Box<dyn Error>> {
+    let Ok(dataset) = group.dataset(name) else {
+        return Ok(vec![placeholder; count]);
+    };
+    let values = dataset.read_raw::<T>()?;
+    if values.len() != count {
+        return Err(format!("Dataset {} has {} values; expected {} samples", name, values.len(), count).into());
+    }
+    Ok(values)
+}
+
+/// The available (non-`NAN`) values of sample `index` of named per-sample values.
+fn available(named_values: &[(String, Vec<f32>)], index: usize) -> HashMap<String, f32> {
+    named_values.iter()
+        .filter(|(_, values)| !values[index].is_nan())
+        .map(|(name, values)| (name.clone(), values[index]))
+        .collect()
+}
+
+/// Version of the layout of session groups, stored in the `schema_version` attribute of each
+/// group. Groups written before the layout was versioned have no attribute and are version 0.
+///
+/// Bump it whenever datasets are added or changed, and add a step to [`MIGRATIONS`] that brings
+/// groups of the previous version up to date.
+pub const SCHEMA_VERSION: u32 = 10;
+
+/// A step upgrading a group to the given version from the version before.
+type Migration = (u32, fn(&hdf5::Group) -> Result<(), Box<dyn Error>>);
+
+/// Upgrade steps, in order of version.
+const MIGRATIONS: [Migration; 10] = [
+    (1, migrate_to_v1),
+    (2, migrate_to_v2),
+    (3, migrate_to_v3),
+    (4, migrate_to_v4),
+    (5, migrate_to_v5),
+    (6, migrate_to_v6),
+    (7, migrate_to_v7),
+    (8, migrate_to_v8),
+    (9, migrate_to_v9),
+    (10, migrate_to_v10),
+];
+
+/// Schema version of a session group (see [`SCHEMA_VERSION`]).
+pub fn schema_version(group: &hdf5::Group) -> Result<u32, Box<dyn Error>> {
+    match group.attr("schema_version") {
+        Ok(attr) => Ok(attr.read_scalar::<u32>()?),
+        Err(_) => Ok(0),
+    }
+}
+
+/// A chunk of a dataset that cannot be read, found by [`verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorruptChunk {
+    /// Name of the dataset.
+    pub dataset: String,
+    /// Indices of the values stored in the chunk.
+    pub values: std::ops::Range<usize>,
+    /// The read error, e.g. a checksum mismatch.
+    pub error: String,
+}
+
+/// Reads every dataset of `group` and its summary groups chunk by chunk, and returns the chunks
+/// that fail to read.
+///
+/// Datasets created with Fletcher32 checksums (all datasets created since checksums were enabled)
+/// fail to read when a chunk was corrupted, e.g. by the SD card. Older datasets are only reported
+/// if the corruption breaks their compression. Datasets of a type the recorder doesn't write are
+/// skipped.
+///
+/// # Errors
+///
+/// Returns an error if the members of `group` or the type of a dataset cannot be read.
+pub fn verify(group: &hdf5::Group) -> Result<Vec<CorruptChunk>, Box<dyn Error>> {
+    let mut names = group.member_names()?;
+    for subgroup in names.clone().iter().filter(|name| group.group(name).is_ok()) {
+        names.extend(group.group(subgroup)?.member_names()?.into_iter().map(|name| format!("{subgroup}/{name}")));
+    }
+    names.sort();
+    let mut corrupt = Vec::new();
+    for name in names {
+        // Skip anything that isn't a dataset
+        let Ok(dataset) = group.dataset(&name) else {
+            continue;
+        };
+        let descriptor = dataset.dtype()?.to_descriptor()?;
+        let len = dataset.shape().first().copied().unwrap_or_default();
+        let chunk_len = dataset.chunk().and_then(|chunk| chunk.first().copied()).unwrap_or(len).max(1);
+        for start in (0..len).step_by(chunk_len) {
+            let values = start..(start + chunk_len).min(len);
+            match read_values(&dataset, &descriptor, values.clone()) {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!("Skipping verification of dataset {} of unsupported type {:?}", name, descriptor);
+                    break;
+                }
+                Err(e) => corrupt.push(CorruptChunk { dataset: name.clone(), values, error: e.to_string() }),
+            }
+        }
+    }
+    Ok(corrupt)
+}
+
+/// Reads the `values` of `dataset`, whose type is `descriptor`. Returns `false` if the type is
+/// not one the recorder writes.
+fn read_values(dataset: &Dataset, descriptor: &TypeDescriptor, values: std::ops::Range<usize>) -> hdf5::Result<bool> {
+    let selection = (values,);
+    match descriptor {
+        TypeDescriptor::Boolean => dataset.read_slice_1d::<bool, _>(selection).map(drop)?,
+        TypeDescriptor::Unsigned(IntSize::U1) => dataset.read_slice_1d::<u8, _>(selection).map(drop)?,
+        TypeDescriptor::Unsigned(IntSize::U2) => dataset.read_slice_1d::<u16, _>(selection).map(drop)?,
+        TypeDescriptor::Unsigned(IntSize::U8) => dataset.read_slice_1d::<u64, _>(selection).map(drop)?,
+        TypeDescriptor::Float(FloatSize::U4) => dataset.read_slice_1d::<f32, _>(selection).map(drop)?,
+        TypeDescriptor::VarLenUnicode => dataset.read_slice_1d::<VarLenUnicode, _>(selection).map(drop)?,
+        TypeDescriptor::VarLenArray(element) if **element == TypeDescriptor::Unsigned(IntSize::U1) => {
+            dataset.read_slice_1d::<VarLenArray<u8>, _>(selection).map(drop)?
+        }
+        TypeDescriptor::Compound(_) if dataset.name().ends_with("/audio") => {
+            dataset.read_slice_1d::<H5AudioMetadata, _>(selection).map(drop)?
+        }
+        TypeDescriptor::Compound(_) if dataset.name().ends_with("/events") => {
+            dataset.read_slice_1d::<H5Event, _>(selection).map(drop)?
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn write_schema_version(group: &hdf5::Group, version: u32) -> Result<(), Box<dyn Error>> {
+    let attr = match group.attr("schema_version") {
+        Ok(attr) => attr,
+        Err(_) => group.new_attr::<u32>().create("schema_version")?,
+    };
+    attr.write_scalar(&version)?;
+    Ok(())
+}
+
+/// Upgrades the session `group_name` in the HDF5 file at `data_path/file_name` (or the file of its
+/// night, see [`session_path`]) to the current
+/// [`SCHEMA_VERSION`], so that it has every dataset current code reads. Returns the version the
+/// session had; sessions that are up to date are left untouched.
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be opened, was written by newer code, or an upgrade
+/// step fails. Steps completed before the failure are kept, and the session is left at the
+/// version of the last one.
+pub fn upgrade_session(data_path: &str, file_name: &str, group_name: &str) -> Result<u32, Box<dyn Error>> {
+    let file = File::append(session_path(data_path, file_name, group_name))?;
+    let group = file.group(group_name)
+        .map_err(|e| format!("Session {} not found in {}: {}", group_name, file_name, e))?;
+    let version = schema_version(&group)?;
+    if version > SCHEMA_VERSION {
+        return Err(format!("Session {} has schema version {}, newer than the supported {}", group_name, version, SCHEMA_VERSION).into());
+    }
+    for (target, migrate) in MIGRATIONS.iter().filter(|(target, _)| *target > version) {
+        migrate(&group).map_err(|e| format!("Failed to upgrade session {} to schema version {}: {}", group_name, target, e))?;
+        write_schema_version(&group, *target)?;
+        info!("Upgraded session {} to schema version {}", group_name, target);
+    }
+    Ok(version)
+}
+
+/// Marks the `media` files of the session `group_name` in the HDF5 file at `data_path/file_name`
+/// as deleted at `purged_s` (seconds since UNIX epoch), by setting a `purged_s` attribute on each
+/// dataset holding their paths. The paths are kept, except the paths of the event clips cut from
+/// the audio (see [`EVENT_CLIP_CATEGORY`]), which are cleared from the events.
+pub fn mark_purged(data_path: &str, file_name: &str, group_name: &str, media: Media, purged_s: u64) -> Result<(), Box<dyn Error>> {
+    let file = File::append(session_path(data_path, file_name, group_name))?;
+    let group = file.group(group_name)
+        .map_err(|e| format!("Session {} not found in {}: {}", group_name, file_name, e))?;
+    for name in group.member_names()? {
+        if !media.references(&name) {
+            continue;
+        }
+        let dataset = group.dataset(&name)?;
+        let attr = match dataset.attr("purged_s") {
+            Ok(attr) => attr,
+            Err(_) => dataset.new_attr::<u64>().create("purged_s")?,
+        };
+        attr.write_scalar(&purged_s)?;
+    }
+    if media == Media::Audio {
+        clear_event_clips(&group)?;
+    }
+    Ok(())
+}
+
+/// Empties the text (the clip path) of the [`EVENT_CLIP_CATEGORY`] events of `group`, keeping the
+/// events so that the events they covered aren't clipped again.
+fn clear_event_clips(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    let Ok(dataset) = group.dataset("events") else {
+        return Ok(());
+    };
+    let mut events = dataset.read_raw::<H5Event>()?;
+    let mut cleared = false;
+    for event in events.iter_mut().filter(|event| event.category.as_str() == EVENT_CLIP_CATEGORY && !event.text.is_empty()) {
+        event.text = VarLenUnicode::default();
+        cleared = true;
+    }
+    if cleared {
+        dataset.write_raw(&events)?;
+    }
+    Ok(())
+}
+
+/// Version 1: adds the datasets missing from groups written before versioning, such as the
+/// mmWave columns of sessions recorded before the radar, or `image_motion_live` of sessions
+/// recorded before motion was computed live. Per-sample datasets are filled with the value of an
+/// unavailable reading (`NAN`, 0, `false`, or empty); sensor states with `Ready`.
+fn migrate_to_v1(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    let sample_count = group.dataset("timestamp")?.shape()[0];
+    for (name, sleep_field) in sleep_fields() {
+        if group.dataset(name).is_ok() {
+            continue;
+        }
+        sleep_field.create_column(group, name, &CompressionConfig::default(), sample_count)?;
+    }
+    if group.dataset("piezo_bcg_start").is_err() {
+        // No bursts: every (empty) burst starts at 0
+        fill_dataset(group, "piezo_bcg_start", &vec![0u64; sample_count])?;
+    }
+    for name in group.member_names()? {
+        if let Some(key) = name.strip_prefix("sensor_ok_") {
+            let state = format!("sensor_state_{key}");
+            if group.dataset(&state).is_err() {
+                fill_dataset(group, &state, &vec![SensorState::Ready as u8; sample_count])?;
+            }
+        }
+    }
+    if group.dataset("audio").is_err() {
+        AppendableColumn::<H5AudioMetadata>::create(group, "audio", &CompressionConfig::default())?;
+    }
+    for name in ["live_audio_rms_db", "piezo_bcg_mv"] {
+        if group.dataset(name).is_err() {
+            AppendableColumn::<f32>::create(group, name, &CompressionConfig::default())?;
+        }
+    }
+    for name in ["live_audio_rms_t_s", "actuator_event_t_s"] {
+        if group.dataset(name).is_err() {
+            AppendableColumn::<u64>::create(group, name, &CompressionConfig::default())?;
+        }
+    }
+    if group.dataset("actuator_event_name").is_err() {
+        AppendableColumn::<VarLenUnicode>::create(group, "actuator_event_name", &CompressionConfig::default())?;
+    }
+    if group.dataset("actuator_event_on").is_err() {
+        AppendableColumn::<bool>::create(group, "actuator_event_on", &CompressionConfig::default())?;
+    }
+    Ok(())
+}
+
+/// Version 2: adds the `units`, `description`, and `sensor` attributes (see [`units`]) to the
+/// datasets created without them.
+fn migrate_to_v2(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    for name in group.member_names()? {
+        if let Ok(dataset) = group.dataset(&name) {
+            units::write_dataset_info(&dataset, &name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Version 3: adds the `thumbnail` and per-camera `thumbnail_<name>` datasets, with no thumbnails
+/// for the samples recorded before.
+fn migrate_to_v3(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    let sample_count = group.dataset("timestamp")?.shape()[0];
+    let cameras: Vec<String> = group.member_names()?.iter()
+        .filter_map(|name| name.strip_prefix("image_path_"))
+        .map(|camera| format!("thumbnail_{camera}"))
+        .collect();
+    for name in std::iter::once("thumbnail".to_string()).chain(cameras) {
+        if group.dataset(&name).is_err() {
+            fill_dataset(group, &name, &vec![VarLenArray::<u8>::from_slice(&[]); sample_count])?;
+        }
+    }
+    Ok(())
+}
+
+/// Version 4: adds the `quality_flags` dataset, with no readings flagged in the samples recorded
+/// before.
+fn migrate_to_v4(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    let sample_count = group.dataset("timestamp")?.shape()[0];
+    if group.dataset("quality_flags").is_err() {
+        fill_dataset(group, "quality_flags", &vec![0u16; sample_count])?;
+    }
+    Ok(())
+}
+
+/// Version 5: adds the summaries (see [`summary`]), computed from the samples recorded before.
+fn migrate_to_v5(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    write_summaries(group)
+}
+
+/// Computes the summary groups (see [`summary`]) that `group` doesn't have from its samples.
+fn write_summaries(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    let timestamps = group.dataset("timestamp")?.read_raw::<u64>()?;
+    let flags = group.dataset("quality_flags")?.read_raw::<u16>()?;
+    let mut fields: Vec<(&'static str, SleepField)> = sleep_fields().into_iter()
+        .filter(|(name, field)| summary::is_summarized(name, field) && group.dataset(name).is_ok())
+        .collect();
+    fields.sort_by_key(|(name, _)| *name);
+    let mut columns: Vec<Vec<f32>> = Vec::new();
+    for (name, field) in &fields {
+        let dataset = group.dataset(name)?;
+        columns.push(match field {
+            SleepField::U16(_) => dataset.read_raw::<u16>()?.into_iter().map(f32::from).collect(),
+            _ => dataset.read_raw::<f32>()?,
+        });
+    }
+    let names: Vec<&'static str> = fields.iter().map(|(name, _)| *name).collect();
+
+    let compression = CompressionConfig::default();
+    for interval_s in summary::INTERVALS_S {
+        if group.group(&summary::group_name(interval_s)).is_ok() {
+            continue;
+        }
+        create_summary_group(group, interval_s, &compression)?;
+        for name in &names {
+            create_summary_datasets(group, &summary::group_name(interval_s), name, &compression)?;
+        }
+        let intervals = summarize_columns(interval_s, &names, &columns, &timestamps, &flags);
+        append_intervals(group, interval_s, &names, &intervals)?;
+    }
+    Ok(())
+}
+
+/// Version 6: adds the `dew_point`, `absolute_humidity`, and `comfort_index` datasets and their
+/// summaries, derived from the temperature and humidity recorded before. Unavailable values
+/// filled in by earlier steps are replaced.
+fn migrate_to_v6(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    let (Ok(temperatures), Ok(humidities)) = (group.dataset("temperature"), group.dataset("humidity")) else {
+        // Recorded without a BME280
+        return Ok(());
+    };
+    let (temperatures, humidities) = (temperatures.read_raw::<f32>()?, humidities.read_raw::<f32>()?);
+    let timestamps = group.dataset("timestamp")?.read_raw::<u64>()?;
+    let flags = group.dataset("quality_flags")?.read_raw::<u16>()?;
+    let climate: Vec<DerivedClimate> = (0..timestamps.len())
+        .map(|index| {
+            let quality_flags = flags.get(index).copied().unwrap_or_default();
+            let reading = |field, values: &[f32]| {
+                values.get(index).copied().filter(|_| !is_flagged(field, quality_flags)).unwrap_or(f32::NAN)
+            };
+            DerivedClimate::new(reading("temperature", &temperatures), reading("humidity", &humidities))
+        })
+        .collect();
+    let names = ["dew_point", "absolute_humidity", "comfort_index"];
+    let columns: Vec<Vec<f32>> = vec![
+        climate.iter().map(|c| c.dew_point_c).collect(),
+        climate.iter().map(|c| c.absolute_humidity_gm3).collect(),
+        climate.iter().map(|c| c.comfort_index).collect(),
+    ];
+    for (name, values) in names.iter().zip(&columns) {
+        clear_dataset::<f32>(group, name)?;
+        append_to_dataset(group, name, values)?;
+    }
+
+    for interval_s in summary::INTERVALS_S {
+        let summary_group = summary::group_name(interval_s);
+        for name in names {
+            for aggregate in ["mean", "min", "max"] {
+                clear_dataset::<f32>(group, &format!("{summary_group}/{name}_{aggregate}"))?;
+            }
+        }
+        let intervals = summarize_columns(interval_s, &names, &columns, &timestamps, &flags);
+        append_aggregates(group, &summary_group, &names, &intervals)?;
+    }
+    Ok(())
+}
+
+/// Version 7: adds the empty `events` dataset.
+fn migrate_to_v7(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    if group.dataset("events").is_err() {
+        AppendableColumn::<H5Event>::create(group, "events", &CompressionConfig::default())?;
+    }
+    Ok(())
+}
+
+/// Version 8: adds the empty audio band level datasets (see [`BANDS`]), unless an analysis of
+/// the session already created them.
+fn migrate_to_v8(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    AppendableColumn::<u64>::open_or_create(group, BAND_TIMES_DATASET, &CompressionConfig::default())?;
+    for band in BANDS {
+        AppendableColumn::<f32>::open_or_create(group, band.dataset, &CompressionConfig::default())?;
+    }
+    Ok(())
+}
+
+/// Version 9: adds the empty audio respiration datasets (see [`crate::audio_respiration`]).
+fn migrate_to_v9(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    AppendableColumn::<u64>::open_or_create(group, RESP_TIMES_DATASET, &CompressionConfig::default())?;
+    AppendableColumn::<f32>::open_or_create(group, RESP_RATE_DATASET, &CompressionConfig::default())?;
+    Ok(())
+}
+
+/// Version 10: moves the live image motion, which earlier versions wrote to `image_motion` and
+/// `image_motion_<name>`, to `image_motion_live` and `image_motion_live_<name>`, so that
+/// `image_motion` only holds the motion of [`analyze_motion`](crate::image_analysis::analyze_motion).
+/// Sessions analyzed before have the analyzed motion moved as well, until they are analyzed again.
+fn migrate_to_v10(group: &hdf5::Group) -> Result<(), Box<dyn Error>> {
+    for name in group.member_names()? {
+        let Some(camera) = name.strip_prefix("image_motion") else {
+            continue;
+        };
+        let live = format!("image_motion_live{camera}");
+        if camera.starts_with("_live") || group.dataset(&live).is_ok() {
+            continue;
+        }
+        group.relink(&name, &live)?;
+    }
+    Ok(())
+}
+
+/// Summarizes the `columns` of the fields `names`, recorded at `timestamps` with the quality
+/// `flags`, per interval of `interval_s` seconds.
+fn summarize_columns(interval_s: u64, names: &[&'static str], columns: &[Vec<f32>], timestamps: &[u64], flags: &[u16]) -> Vec<Interval> {
+    let mut summarizer = Summarizer::new(interval_s, None);
+    for &name in names {
+        summarizer.add_field(name);
+    }
+    let mut intervals = Vec::new();
+    for (index, &timestamp_s) in timestamps.iter().enumerate() {
+        let quality_flags = flags.get(index).copied().unwrap_or_default();
+        let readings: Vec<f32> = names.iter().zip(columns)
+            .map(|(name, column)| summary::available(name, column.get(index).copied().unwrap_or(f32::NAN), quality_flags))
+            .collect();
+        intervals.extend(summarizer.add(timestamp_s, &readings));
+    }
+    intervals.extend(summarizer.finish());
+    intervals
+}
+
+/// Empties the dataset `name`, creating it if it doesn't exist.
+fn clear_dataset<T: H5Type>(group: &hdf5::Group, name: &str) -> Result<(), Box<dyn Error>> {
+    AppendableColumn::<T>::open_or_create(group, name, &CompressionConfig::default())?.clear()?;
+    Ok(())
+}
+
+/// Creates the summary group of the intervals of `interval_s` seconds (see [`summary`]), with its
+/// `t_s` dataset.
+fn create_summary_group(group: &hdf5::Group, interval_s: u64, compression: &CompressionConfig) -> Result<(), Box<dyn Error>> {
+    let summary_group = summary::group_name(interval_s);
+    group.create_group(&summary_group)?;
+    AppendableColumn::<u64>::create(group, &format!("{summary_group}/t_s"), compression)?;
+    Ok(())
+}
+
+/// Creates the `<field>_mean`, `<field>_min`, and `<field>_max` datasets of the summary group
+/// `summary_group`, with `NAN` aggregates for the intervals written before.
+fn create_summary_datasets(group: &hdf5::Group, summary_group: &str, field: &str, compression: &CompressionConfig) -> Result<(), Box<dyn Error>> {
+    let interval_count = group.dataset(&format!("{summary_group}/t_s"))?.shape()[0];
+    for aggregate in ["mean", "min", "max"] {
+        let name = format!("{summary_group}/{field}_{aggregate}");
+        AppendableColumn::create(group, &name, compression)?.pad_to(interval_count, f32::NAN)?;
+    }
+    Ok(())
+}
+
+/// Appends the `intervals` of `interval_s` seconds, with the aggregates of `fields`, to their
+/// summary group.
+fn append_intervals(group: &hdf5::Group, interval_s: u64, fields: &[&str], intervals: &[Interval]) -> Result<(), Box<dyn Error>> {
+    if intervals.is_empty() {
+        return Ok(());
+    }
+    let summary_group = summary::group_name(interval_s);
+    let starts: Vec<u64> = intervals.iter().map(|interval| interval.start_s).collect();
+    append_to_dataset(group, &format!("{summary_group}/t_s"), &starts)?;
+    append_aggregates(group, &summary_group, fields, intervals)
+}
+
+/// Appends the aggregates of `fields` in `intervals` to the summary group `summary_group`.
+fn append_aggregates(group: &hdf5::Group, summary_group: &str, fields: &[&str], intervals: &[Interval]) -> Result<(), Box<dyn Error>> {
+    for (index, field) in fields.iter().enumerate() {
+        let values = |aggregate: fn(&Aggregate) -> f32| -> Vec<f32> {
+            intervals.iter().map(|interval| aggregate(&interval.aggregates[index])).collect()
+        };
+        append_to_dataset(group, &format!("{summary_group}/{field}_mean"), &values(|a| a.mean))?;
+        append_to_dataset(group, &format!("{summary_group}/{field}_min"), &values(|a| a.min))?;
+        append_to_dataset(group, &format!("{summary_group}/{field}_max"), &values(|a| a.max))?;
+    }
+    Ok(())
+}
+
+/// Creates the dataset `name` holding `values`.
+fn fill_dataset<T: H5Type>(group: &hdf5::Group, name: &str, values: &[T]) -> Result<(), Box<dyn Error>> {
+    AppendableColumn::create(group, name, &CompressionConfig::default())?.append(values)?;
+    Ok(())
+}
+
+/// Appends `values` to the existing dataset `name` of `group` (see [`AppendableColumn::append`]).
+fn append_to_dataset<T: H5Type>(group: &hdf5::Group, name: &str, values: &[T]) -> hdf5::Result<()> {
+    AppendableColumn::open(group, name)?.append(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CompressionCodec;
+    use crate::sensor::{BME280_FIELDS, CAMERA_FIELDS, ENS160_FIELDS, PIR_FIELDS};
+    use test_log::test;
+
+    /// Registers the fields of sensors, as the recorder does for its sensors at startup.
+    fn register_fields(logger: &mut SleepDataLogger, sensors: &[&[(&'static str, SleepField)]]) {
+        for &(name, field) in sensors.concat().iter() {
+            logger.register_field(name, field).expect("Failed to register field");
+        }
+    }
+
+    #[test]
+    fn test_upgrade_moves_live_motion() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        {
+            // Version 9 wrote the live motion to `image_motion` and `image_motion_<name>`
+            let file = File::create(dir.path().join("sleep_data.h5")).expect("Failed to create file");
+            let group = file.create_group("2025-04-28_22-00-00").unwrap();
+            fill_dataset(&group, "timestamp", &[10u64, 15]).unwrap();
+            fill_dataset(&group, "image_motion", &[f32::NAN, 3.0]).unwrap();
+            fill_dataset(&group, "image_motion_crib", &[1.0f32, 2.0]).unwrap();
+            write_schema_version(&group, 9).unwrap();
+        }
+        assert_eq!(upgrade_session(data_path, "sleep_data.h5", "2025-04-28_22-00-00").expect("Failed to upgrade"), 9);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", "2025-04-28_22-00-00").expect("Failed to open session");
+        let group = session.group().unwrap();
+        assert!(group.dataset("image_motion").is_err() && group.dataset("image_motion_crib").is_err());
+        assert_eq!(session.image_motion_live().unwrap()[1], 3.0);
+        assert_eq!(session.camera_motion("crib").unwrap(), vec![1.0, 2.0]);
+        assert!(session.image_motion().unwrap().iter().all(|m| m.is_nan()));
+    }
+
+    #[test]
+    fn test_upgrade_unversioned_session() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        {
+            // A session from before the radar and versioning: only a few datasets
+            let file = File::create(dir.path().join("sleep_data.h5")).expect("Failed to create file");
+            let group = file.create_group("2024-01-01_22-00-00").unwrap();
+            fill_dataset(&group, "timestamp", &[10u64, 15, 20]).unwrap();
+            fill_dataset(&group, "temperature", &[21.0f32, 21.1, 21.2]).unwrap();
+            fill_dataset(&group, "humidity", &[40.0f32, 40.0, 40.0]).unwrap();
+            fill_dataset(&group, "sensor_ok_bme280", &[true, true, false]).unwrap();
+        }
+        let session = SessionReader::open(data_path, "sleep_data.h5", "2024-01-01_22-00-00").expect("Failed to open session");
+        assert_eq!(session.schema_version().unwrap(), 0);
+        drop(session);
+
+        assert_eq!(upgrade_session(data_path, "sleep_data.h5", "2024-01-01_22-00-00").expect("Failed to upgrade"), 0);
+        // Upgrading again is a no-op
+        assert_eq!(upgrade_session(data_path, "sleep_data.h5", "2024-01-01_22-00-00").unwrap(), SCHEMA_VERSION);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", "2024-01-01_22-00-00").expect("Failed to open session");
+        assert_eq!(session.schema_version().unwrap(), SCHEMA_VERSION);
+        let group = session.group().unwrap();
+        assert_eq!(group.dataset("mmwave_heart_rate_bpm").unwrap().read_raw::<u16>().unwrap(), vec![0, 0, 0]);
+        assert!(group.dataset("image_motion_live").unwrap().read_raw::<f32>().unwrap().iter().all(|m| m.is_nan()));
+        assert_eq!(group.dataset("temperature").unwrap().read_raw::<f32>().unwrap(), vec![21.0, 21.1, 21.2]);
+        assert_eq!(session.piezo_bursts().unwrap(), vec![Vec::<f32>::new(); 3]);
+        assert_eq!(session.thumbnails().unwrap(), vec![Thumbnail::default(); 3]);
+        assert_eq!(session.audio_respiration().unwrap(), (vec![], vec![]));
+        assert_eq!(group.dataset("quality_flags").unwrap().read_raw::<u16>().unwrap(), vec![0, 0, 0]);
+        // Summaries are computed from the samples recorded before
+        let summary = session.summary(60).unwrap();
+        assert_eq!(summary.start_s, vec![0]);
+        assert!((summary.fields["temperature"][0].mean - 21.1).abs() < 1e-4);
+        // Climate metrics are derived from the temperature and humidity recorded before
+        let dew_points = group.dataset("dew_point").unwrap().read_raw::<f32>().unwrap();
+        assert_eq!(dew_points[0], crate::climate::dew_point_c(21.0, 40.0));
+        assert_eq!(summary.fields["comfort_index"][0].max, 90.0);
+        assert_eq!(group.dataset("sensor_state_bme280").unwrap().read_raw::<u8>().unwrap(), vec![SensorState::Ready as u8; 3]);
+        assert!(session.actuator_events().unwrap().is_empty());
+        assert!(session.events().unwrap().is_empty());
+        // Sessions recorded without metadata have empty metadata
+        assert_eq!(session.metadata().unwrap(), SessionMetadata::default());
+        let units = group.dataset("temperature").unwrap().attr("units").unwrap().read_scalar::<VarLenUnicode>().unwrap();
+        assert_eq!(units.as_str(), "degC");
+    }
+
+    #[test]
+    fn test_quality_flags() {
+        let sample = SleepData::builder(10)
+            .with_climate(85.0, 1013.0, 120.0)
+            .with_air_quality(450, 30, 1)
+            .with_light_lux(f32::NAN)
+            .build();
+        assert_eq!(sample.quality_flags, 0b101);
+        assert_eq!(sample.implausible_fields(), vec!["temperature", "humidity"]);
+        // Flagged readings are kept as measured
+        assert_eq!((sample.temperature_c, sample.humidity), (85.0, 120.0));
+
+        let sample = SleepData::builder(15)
+            .with_climate(21.5, 1013.0, 40.0)
+            .merge(SleepData::builder(15).with_scd4x(Scd4xMeasurement { co2_ppm: 0, temperature_c: f32::NAN, humidity: f32::NAN }))
+            .build();
+        assert_eq!(sample.implausible_fields(), vec!["co2_ppm"]);
+        assert_eq!(SleepData::builder(20).build().quality_flags, 0);
+    }
+
+    #[test]
+    fn test_session_metadata() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let metadata = SessionMetadata {
+            device_id: "pi-bedroom".to_string(),
+            notes: "new mattress".to_string(),
+            software_version: "0.1.0".to_string(),
+            audio_weighting: "a".to_string(),
+            audio_spl_offset_db: "121.5".to_string(),
+            sensors: vec!["BME280".to_string(), "DS18B20 mattress".to_string()],
+            ..Default::default()
+        };
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        logger.write_metadata(&metadata).expect("Failed to write metadata");
+        assert!(logger.write_metadata(&metadata).is_err());
+        let group_name = logger.group_name.clone();
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let read = session.metadata().expect("Failed to read metadata");
+        assert_eq!(read, metadata);
+        assert_eq!(read.microphone().spl_db(-60.0), Some(61.5));
+    }
+
+    #[test]
+    fn test_mark_purged() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        logger.register_camera("crib").unwrap();
+        let group_name = logger.group_name.clone();
+        drop(logger);
+
+        mark_purged(data_path, "sleep_data.h5", &group_name, Media::Images, 1_700_000_000).expect("Failed to mark images purged");
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        assert_eq!(session.purged_s(Media::Images).unwrap(), Some(1_700_000_000));
+        assert_eq!(session.purged_s(Media::Audio).unwrap(), None);
+        let group = session.group().unwrap();
+        assert!(group.dataset("image_path_crib").unwrap().attr("purged_s").is_ok());
+        assert!(group.dataset("timestamp").unwrap().attr("purged_s").is_err());
+    }
+
+    #[test]
+    fn test_verify_session() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        register_fields(&mut logger, &[BME280_FIELDS]);
+        let group_name = logger.group_name.clone();
+        for timestamp in 0..2000 {
+            logger.append(SleepData::builder(timestamp).with_climate(21.5, 1013.0, 40.0).build()).unwrap();
+        }
+        logger.add_audio_entry(AudioRecording { path: "audio.mp3".to_string(), duration: Duration::from_secs(60), start_time_s: 0 }).unwrap();
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let temperature = session.group().unwrap().dataset("temperature").unwrap();
+        assert!(temperature.filters().iter().any(|filter| matches!(filter, hdf5::filters::Filter::Fletcher32)));
+        assert_eq!(session.verify().expect("Failed to verify"), Vec::new());
+    }
+
+    #[test]
+    fn test_dataset_compression() {
+        use crate::config::DatasetCompression;
+        use hdf5::filters::Filter;
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut compression = CompressionConfig { chunk: 256, ..Default::default() };
+        compression.datasets.insert("image_path*".to_string(), DatasetCompression {
+            codec: Some(CompressionCodec::None),
+            ..Default::default()
+        });
+        compression.datasets.insert("tvoc_ppb".to_string(), DatasetCompression {
+            level: Some(1),
+            shuffle: Some(true),
+            ..Default::default()
+        });
+        let mut logger = SleepDataLogger::new_with_compression(data_path, "sleep_data.h5", compression)
+            .expect("Failed to create logger");
+        register_fields(&mut logger, &[BME280_FIELDS, ENS160_FIELDS, CAMERA_FIELDS]);
+        logger.register_camera("crib").unwrap();
+        let group_name = logger.group_name.clone();
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let group = session.group().unwrap();
+        let temperature = group.dataset("temperature").unwrap();
+        assert_eq!(temperature.chunk(), Some(vec![256]));
+        assert_eq!(temperature.filters(), vec![Filter::Deflate(6), Filter::Fletcher32]);
+        assert_eq!(group.dataset("tvoc_ppb").unwrap().filters(), vec![Filter::Shuffle, Filter::Deflate(1), Filter::Fletcher32]);
+        // Registered datasets are compressed as configured too
+        assert_eq!(group.dataset("image_path").unwrap().filters(), vec![Filter::Fletcher32]);
+        assert_eq!(group.dataset("image_path_crib").unwrap().filters(), vec![Filter::Fletcher32]);
+    }
+
+    #[test]
+    fn test_night_file_name() {
+        assert_eq!(night_file_name("sleep_data.h5", "2025-05-01_22-47-31").as_deref(), Some("sleep_data_2025-05-01.h5"));
+        // Sessions after midnight belong to the night before
+        assert_eq!(night_file_name("sleep_data.h5", "2025-05-02_03-10-00").as_deref(), Some("sleep_data_2025-05-01.h5"));
+        assert_eq!(night_file_name("sleep", "2025-05-02_13-00-00").as_deref(), Some("sleep_2025-05-02"));
+        assert_eq!(night_file_name("sleep_data.h5", "calibration"), None);
+    }
+
+    #[test]
+    fn test_sessions_in_nightly_files() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        // A session from before the rotation, and two nights in their own files
+        for (file_name, group_name, timestamp) in [
+            ("sleep_data.h5", "2025-04-30_22-00-00", 10),
+            ("sleep_data_2025-05-01.h5", "2025-05-01_22-47-31", 20),
+            ("sleep_data_2025-05-01.h5", "2025-05-02_03-10-00", 30),
+            ("sleep_data_2025-05-02.h5", "2025-05-02_23-00-00", 40),
+        ] {
+            let mut logger = SleepDataLogger::create(data_path, file_name, group_name, CompressionConfig::default())
+                .expect("Failed to create logger");
+            logger.append(SleepData::builder(timestamp).build()).unwrap();
+        }
+        std::fs::write(dir.path().join("sleep_data_backup.h5"), b"").unwrap();
+
+        assert_eq!(data_files(data_path, "sleep_data.h5").unwrap(), vec!["sleep_data.h5", "sleep_data_2025-05-01.h5", "sleep_data_2025-05-02.h5"]);
+        let sessions = SleepDataLogger::list_sessions(data_path, "sleep_data.h5").expect("Failed to list sessions");
+        let names: Vec<_> = sessions.iter().map(|s| (s.name.as_str(), s.file_name.as_str(), s.start_s)).collect();
+        assert_eq!(names, vec![
+            ("2025-04-30_22-00-00", "sleep_data.h5", 10),
+            ("2025-05-01_22-47-31", "sleep_data_2025-05-01.h5", 20),
+            ("2025-05-02_03-10-00", "sleep_data_2025-05-01.h5", 30),
+            ("2025-05-02_23-00-00", "sleep_data_2025-05-02.h5", 40),
+        ]);
+        assert_eq!(SleepDataLogger::last_session(data_path, "sleep_data.h5").unwrap(), Some(("2025-05-02_23-00-00".to_string(), 40)));
+
+        // Sessions are found through the data file in either layout
+        for (group_name, timestamp) in [("2025-04-30_22-00-00", 10), ("2025-05-02_03-10-00", 30)] {
+            let session = SessionReader::open(data_path, "sleep_data.h5", group_name).expect("Failed to open session");
+            assert_eq!(session.timestamps().unwrap(), vec![timestamp]);
+        }
+        let mut logger = SleepDataLogger::resume(data_path, "sleep_data.h5", "2025-05-02_03-10-00").expect("Failed to resume");
+        logger.append(SleepData::builder(35).build()).unwrap();
+        drop(logger);
+        let session = SessionReader::open(data_path, "sleep_data_2025-05-01.h5", "2025-05-02_03-10-00").expect("Failed to open session");
+        assert_eq!(session.timestamps().unwrap(), vec![30, 35]);
+    }
+
+    #[test]
+    fn test_current_session() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        assert!(SleepDataLogger::current_session(data_path, "sleep_data.h5").unwrap().is_none());
+
+        for (file_name, group_name) in [("sleep_data.h5", "2025-04-30_22-00-00"), ("sleep_data_2025-05-01.h5", "2025-05-01_22-47-31")] {
+            SleepDataLogger::create(data_path, file_name, group_name, CompressionConfig::default()).expect("Failed to create logger");
+        }
+        let current = || SleepDataLogger::current_session(data_path, "sleep_data.h5").unwrap();
+        assert_eq!(current().as_deref(), Some("2025-05-01_22-47-31"));
+        assert_eq!(std::fs::read_link(dir.path().join(LATEST)).unwrap(), Path::new("2025-05-01_22-47-31"));
+
+        // Resuming an older session points at it again
+        drop(SleepDataLogger::resume(data_path, "sleep_data.h5", "2025-04-30_22-00-00").expect("Failed to resume"));
+        assert_eq!(current().as_deref(), Some("2025-04-30_22-00-00"));
+
+        // Without the link, the most recent session pointed at by a file is current
+        std::fs::remove_file(dir.path().join(LATEST)).unwrap();
+        assert_eq!(current().as_deref(), Some("2025-05-01_22-47-31"));
+        let file = File::open(dir.path().join("sleep_data.h5")).unwrap();
+        assert_eq!(file.attr(LATEST).unwrap().read_scalar::<VarLenUnicode>().unwrap().as_str(), "2025-04-30_22-00-00");
+    }
+
+    #[test]
+    fn test_resume_session() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        assert!(SleepDataLogger::last_session(data_path, "sleep_data.h5").unwrap().is_none());
+
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        logger.register_probe("mattress").unwrap();
+        logger.register_sensor("BME280").unwrap();
+        let group_name = logger.group_name.clone();
+        for timestamp in [10, 15] {
+            logger.append(SleepData::builder(timestamp).with_probe_temp("mattress", 30.0).build()).unwrap();
+        }
+        drop(logger);
+
+        let (last_session, last_s) = SleepDataLogger::last_session(data_path, "sleep_data.h5")
+            .expect("Failed to find last session")
+            .expect("Expected a session");
+        assert_eq!((last_session.as_str(), last_s), (group_name.as_str(), 15));
+        let sessions = SleepDataLogger::list_sessions(data_path, "sleep_data.h5").expect("Failed to list sessions");
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].name.as_str(), sessions[0].start_s, sessions[0].end_s, sessions[0].sample_count), (group_name.as_str(), 10, 15, 2));
+        assert!(sessions[0].datasets.iter().any(|d| d == "probe_temp_mattress"));
+
+        let mut logger = SleepDataLogger::resume(data_path, "sleep_data.h5", &group_name).expect("Failed to resume");
+        assert!(logger.is_resumed());
+        // The session's names are registered already; new ones are backfilled
+        logger.register_probe("mattress").expect("Failed to register probe again");
+        logger.register_sensor("BME280").expect("Failed to register sensor again");
+        logger.register_probe("pillow").expect("Failed to register new probe");
+        logger.append(SleepData::builder(600).with_probe_temp("pillow", 25.0).build()).unwrap();
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        assert_eq!(session.timestamps().unwrap(), vec![10, 15, 600]);
+        let mattress = session.probe_temps("mattress").unwrap();
+        assert_eq!(mattress[..2], [30.0, 30.0]);
+        assert!(mattress[2].is_nan());
+        let pillow = session.probe_temps("pillow").unwrap();
+        assert!(pillow[0].is_nan() && pillow[1].is_nan());
+        assert_eq!(pillow[2], 25.0);
+        assert_eq!(session.sensor_status("BME280").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_register_fields() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        register_fields(&mut logger, &[BME280_FIELDS]);
+        assert!(logger.register_field("temperature", BME280_FIELDS[0].1).is_err());
+        let group_name = logger.group_name.clone();
+        logger.append(SleepData::builder(10).with_climate(21.5, 1013.0, 40.0).build()).unwrap();
+        logger.flush().unwrap();
+        assert!(logger.register_field("pir_motion", PIR_FIELDS[0].1).is_err());
+        drop(logger);
+
+        // Only the datasets of the registered sensors are created
+        let sessions = SleepDataLogger::list_sessions(data_path, "sleep_data.h5").expect("Failed to list sessions");
+        let datasets = &sessions[0].datasets;
+        assert!(["timestamp", "temperature", "pressure", "humidity"].iter().all(|d| datasets.iter().any(|n| n == d)));
+        assert!(!datasets.iter().any(|d| d == "co2eq_ppm" || d == "image_path"));
+
+        // A resumed session keeps its fields; new ones are backfilled
+        let mut logger = SleepDataLogger::resume(data_path, "sleep_data.h5", &group_name).expect("Failed to resume");
+        register_fields(&mut logger, &[BME280_FIELDS, PIR_FIELDS]);
+        logger.append(SleepData::builder(15).with_climate(21.0, 1013.0, 41.0).with_pir_motion(true).build()).unwrap();
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let samples = session.samples().expect("Failed to read samples");
+        assert_eq!(samples.iter().map(|s| s.temperature_c).collect::<Vec<_>>(), vec![21.5, 21.0]);
+        assert_eq!(samples.iter().map(|s| s.pir_motion).collect::<Vec<_>>(), vec![false, true]);
+        // Fields of sensors the session didn't have are unavailable
+        assert!(samples.iter().all(|s| s.co2eq_ppm == 0 && s.image_path.is_empty()));
+        assert_eq!(session.image_paths().unwrap(), vec![String::new(); 2]);
+    }
+
+    #[test]
+    fn test_summaries() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        register_fields(&mut logger, &[BME280_FIELDS, PIR_FIELDS]);
+        let group_name = logger.group_name.clone();
+        // Aligned to 5 minutes
+        let start_s = 1_800_000_000;
+        for (offset_s, temperature_c, humidity) in [(0, 20.0, 40.0), (30, 22.0, 40.0), (45, 24.0, 40.0), (60, 30.0, 150.0)] {
+            logger.append(SleepData::builder(start_s + offset_s).with_climate(temperature_c, 1013.0, humidity).build()).unwrap();
+        }
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let minutes = session.summary(60).expect("Failed to read summary");
+        assert_eq!(minutes.start_s, vec![start_s, start_s + 60]);
+        assert_eq!(minutes.fields["temperature"][0], Aggregate { mean: 22.0, min: 20.0, max: 24.0 });
+        // The implausible humidity is left out
+        assert!(minutes.fields["humidity"][1].mean.is_nan());
+        // Only numeric fields are summarized
+        assert!(!minutes.fields.contains_key("pir_motion"));
+        let five_minutes = session.summary(300).unwrap();
+        assert_eq!(five_minutes.start_s, vec![start_s]);
+        assert_eq!(five_minutes.fields["temperature"][0], Aggregate { mean: 24.0, min: 20.0, max: 30.0 });
+        assert_eq!(five_minutes.fields["humidity"][0].max, 40.0);
+        assert!(session.verify().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_samples() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        register_fields(&mut logger, &[BME280_FIELDS, CAMERA_FIELDS, PIR_FIELDS]);
+        logger.register_camera("crib").unwrap();
+        logger.register_probe("mattress").unwrap();
+        logger.register_sensor("BME280").unwrap();
+        logger.write_metadata(&SessionMetadata { sensors: vec!["BME280".to_string()], ..Default::default() }).unwrap();
+        let group_name = logger.group_name.clone();
+        logger.append(SleepData::builder(10)
+            .with_climate(21.5, 1013.0, 40.0)
+            .with_camera_result(CameraAndMotionResult {
+                image_path: "image_10.jpg".to_string(),
+                motion: Some(0.25),
+                clip_path: None,
+                raw_image_path: None,
+                thumbnail: Some(Thumbnail(vec![0xff, 0xd8, 0xff, 0xd9])),
+            })
+            .with_probe_temp("mattress", 30.0)
+            .with_named_camera_result("crib", CameraAndMotionResult {
+                image_path: "crib_10.jpg".to_string(),
+                motion: Some(0.5),
+                clip_path: None,
+                raw_image_path: None,
+                thumbnail: None,
+            })
+            .with_sensor_status("BME280", SensorStatus { error: Some("timeout".to_string()), ..Default::default() })
+            .build()).unwrap();
+        logger.append(SleepData::builder(15).with_pir_motion(true).build()).unwrap();
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let samples = session.samples().expect("Failed to read samples");
+        assert_eq!(samples.len(), 2);
+        assert_eq!((samples[0].timestamp_s, samples[0].temperature_c), (10, 21.5));
+        assert!(!samples[0].pir_motion && samples[1].pir_motion);
+        assert!(samples[1].temperature_c.is_nan());
+        // Live motion is stored with each sample, apart from the motion of the offline analysis
+        assert_eq!(samples[0].image_motion_live, 0.25);
+        let motion = session.image_motion_live().unwrap();
+        assert!(motion[0] == 0.25 && motion[1].is_nan());
+        assert!(samples[0].image_motion.is_nan() && session.image_motion().unwrap().iter().all(|m| m.is_nan()));
+        assert!(session.camera_motion("crib").unwrap()[0] == 0.5);
+        assert_eq!(samples[0].thumbnail, Thumbnail(vec![0xff, 0xd8, 0xff, 0xd9]));
+        assert!(samples[1].thumbnail.is_empty());
+        assert_eq!(session.camera_thumbnails("crib").unwrap(), vec![Thumbnail::default(); 2]);
+        assert_eq!(samples[0].probe_temps_c.get("mattress"), Some(&30.0));
+        assert!(samples[1].probe_temps_c.is_empty());
+        let crib = &samples[0].extra_cameras["crib"];
+        assert_eq!((crib.image_path.as_str(), crib.motion, crib.clip_path.as_ref()), ("crib_10.jpg", Some(0.5), None));
+        assert!(samples[1].extra_cameras.is_empty());
+        assert_eq!(samples[0].sensor_status["BME280"].error.as_deref(), Some("timeout"));
+        assert_eq!(samples[1].sensor_status["BME280"].state, SensorState::Initializing);
+    }
+
+    #[test]
+    fn test_append_audio_rms() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        let group_name = logger.group_name.clone();
+        let recording = |start_time_s| AudioRecording { path: format!("audio_{start_time_s}.wav"), duration: Duration::from_secs(10), start_time_s };
+        // Windows computed during the recording are stored with its entry
+        logger.append_audio_rms(100, &[100], &[-50.0]).unwrap();
+        logger.add_audio_entry(recording(100)).unwrap();
+        logger.append_audio_rms(100, &[105], &[-45.0]).unwrap();
+        logger.add_audio_entry(recording(110)).unwrap();
+        assert!(logger.append_audio_rms(110, &[110, 115], &[-40.0]).is_err());
+        drop(logger);
+
+        let entries = SessionReader::open(data_path, "sleep_data.h5", &group_name).unwrap().audio_entries().unwrap();
+        assert_eq!(entries[0].audio_rms_t_s.as_slice(), [100, 105]);
+        assert_eq!(entries[0].audio_rms_db.as_slice(), [-50.0, -45.0]);
+        assert!(entries[0].is_analyzed() && !entries[1].is_analyzed());
+    }
+
+    #[test]
+    fn test_add_event() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        let group_name = logger.group_name.clone();
+        let events = vec![
+            Event { timestamp_s: 10, category: "bedtime".to_string(), text: "went to bed".to_string() },
+            Event { timestamp_s: 600, category: "disturbance".to_string(), text: "dog jumped on bed".to_string() },
+        ];
+        for event in &events {
+            logger.add_event(event).expect("Failed to add event");
+        }
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).unwrap();
+        assert_eq!(session.events().unwrap(), events);
+        assert!(verify(&session.group().unwrap()).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_session_json() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        register_fields(&mut logger, &[BME280_FIELDS]);
+        logger.write_metadata(&SessionMetadata { device_id: "pi".to_string(), ..Default::default() }).unwrap();
+        let group_name = logger.group_name.clone();
+        logger.append(SleepData::builder(10).with_climate(21.5, 1013.0, 40.0).build()).unwrap();
+        logger.append(SleepData::builder(15).build()).unwrap();
+        logger.add_audio_entry(AudioRecording { path: "audio_10.wav".to_string(), duration: Duration::from_secs(600), start_time_s: 10 }).unwrap();
+        drop(logger);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let json: serde_json::Value = serde_json::from_str(&session.to_json().expect("Failed to export")).unwrap();
+        assert_eq!(json["session"], group_name.as_str());
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["metadata"]["device_id"], "pi");
+        assert_eq!(json["samples"][0]["timestamp_s"], 10);
+        assert_eq!(json["samples"][0]["temperature_c"], 21.5);
+        // NaN is written as null
+        assert!(json["samples"][1]["temperature_c"].is_null());
+        assert_eq!(json["audio_recordings"][0]["path"], "audio_10.wav");
+        assert_eq!(json["audio_recordings"][0]["duration_s"], 600);
+    }
+}
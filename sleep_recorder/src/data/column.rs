@@ -0,0 +1,256 @@
+//! Appendable columns: the resizable, one-dimensional datasets every series of a session is
+//! stored in.
+//!
+//! The logger writes the per-sample fields (see [`SleepField`]), the events, and the summaries as
+//! [`AppendableColumn`]s, and analysis modules use them to create and extend the datasets they
+//! derive from a session, e.g. `image_motion` (see
+//! [`analyze_motion`](crate::image_analysis::analyze_motion)). Columns are chunked, compressed as
+//! the [`CompressionConfig`] sets for their name, checksummed so that corrupted chunks fail to
+//! read (see [`verify`](super::verify)), and carry the attributes describing them (see
+//! [`units`](super::units)).
+
+use std::error::Error;
+use std::marker::PhantomData;
+
+use hdf5::types::{VarLenArray, VarLenUnicode};
+use hdf5::{Dataset, H5Type};
+
+use super::{units, SleepData, SleepField};
+use crate::config::{CompressionCodec, CompressionConfig};
+
+/// A resizable one-dimensional dataset of `T` values, extended at its end.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sleep_recorder::config::CompressionConfig;
+/// use sleep_recorder::data::AppendableColumn;
+///
+/// let file = hdf5::File::append("/data/2025-04-28_22-47-31/sleep_data.h5").unwrap();
+/// let group = file.group("2025-04-28_22-47-31").unwrap();
+/// let column = AppendableColumn::<f32>::open_or_create(&group, "radar_waveform", &CompressionConfig::default()).unwrap();
+/// column.append(&[0.1, 0.2, 0.3]).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct AppendableColumn<T> {
+    dataset: Dataset,
+    values: PhantomData<T>,
+}
+
+impl<T: H5Type> AppendableColumn<T> {
+    /// Creates the empty column `name` in `group`, chunked and compressed as `compression` sets
+    /// for `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dataset cannot be created (e.g. it already exists), or if LZF or
+    /// Blosc compression is selected but the crate was built without the `lzf` or `blosc` feature.
+    pub fn create(group: &hdf5::Group, name: &str, compression: &CompressionConfig) -> Result<Self, Box<dyn Error>> {
+        let compression = compression.for_dataset(name);
+        let mut builder = group.new_dataset_builder().chunk(compression.chunk);
+        // Blosc shuffles by itself; the shuffle filter has to come before the compression
+        if compression.shuffle && !matches!(compression.codec, CompressionCodec::BloscLz4 | CompressionCodec::BloscZstd) {
+            builder = builder.shuffle();
+        }
+        builder = match compression.codec {
+            CompressionCodec::None => builder,
+            CompressionCodec::Deflate => builder.deflate(compression.level),
+            #[cfg(feature = "lzf")]
+            CompressionCodec::Lzf => builder.lzf(),
+            #[cfg(not(feature = "lzf"))]
+            CompressionCodec::Lzf => return Err("LZF compression requires the lzf feature".into()),
+            #[cfg(feature = "blosc")]
+            CompressionCodec::BloscLz4 => builder.blosc_lz4(compression.level, compression.shuffle),
+            #[cfg(feature = "blosc")]
+            CompressionCodec::BloscZstd => builder.blosc_zstd(compression.level, compression.shuffle),
+            #[cfg(not(feature = "blosc"))]
+            CompressionCodec::BloscLz4 | CompressionCodec::BloscZstd => {
+                return Err("Blosc compression requires the blosc feature".into())
+            }
+        };
+        let dataset = builder
+            .fletcher32()
+            .empty::<T>()
+            .shape(hdf5::SimpleExtents::resizable([0]))
+            .create(name)
+            .map_err(|e| format!("Failed to create dataset {}: {}", name, e))?;
+        units::write_dataset_info(&dataset, name)?;
+        Ok(Self::from_dataset(dataset))
+    }
+
+    /// Opens the existing column `name` of `group`.
+    pub fn open(group: &hdf5::Group, name: &str) -> hdf5::Result<Self> {
+        Ok(Self::from_dataset(group.dataset(name)?))
+    }
+
+    /// Opens the column `name` of `group`, creating it as [`create`](Self::create) does if it
+    /// doesn't exist.
+    pub fn open_or_create(group: &hdf5::Group, name: &str, compression: &CompressionConfig) -> Result<Self, Box<dyn Error>> {
+        match group.dataset(name) {
+            Ok(dataset) => Ok(Self::from_dataset(dataset)),
+            Err(_) => Self::create(group, name, compression),
+        }
+    }
+
+    fn from_dataset(dataset: Dataset) -> Self {
+        Self { dataset, values: PhantomData }
+    }
+
+    /// The underlying dataset, e.g. to write attributes.
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    /// Number of values in the column.
+    pub fn len(&self) -> usize {
+        self.dataset.shape().first().copied().unwrap_or(0)
+    }
+
+    /// Whether the column has no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends `values` at the end of the column.
+    ///
+    /// # Errors
+    ///
+    /// If the dataset cannot be resized, or the values cannot be written (e.g. `T` isn't the type
+    /// of the dataset).
+    pub fn append(&self, values: &[T]) -> hdf5::Result<()> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        let old_len = self.len();
+        let new_len = old_len + values.len();
+        self.dataset.resize(new_len)?;
+        // Note the comma making the range a 1-tuple: the hyperslab at the end
+        self.dataset.write_slice(values, (old_len..new_len,))
+    }
+
+    /// Overwrites the values from index `start` with `values`, extending the column if they go
+    /// past its end.
+    pub fn write_at(&self, start: usize, values: &[T]) -> hdf5::Result<()> {
+        let end = start + values.len();
+        if end > self.len() {
+            self.dataset.resize(end)?;
+        }
+        self.dataset.write_slice(values, (start..end,))
+    }
+
+    /// Replaces all values of the column with `values`, e.g. when a derived series is computed
+    /// again.
+    pub fn replace(&self, values: &[T]) -> hdf5::Result<()> {
+        self.dataset.resize(values.len())?;
+        self.dataset.write(values)
+    }
+
+    /// Removes all values of the column.
+    pub fn clear(&self) -> hdf5::Result<()> {
+        self.dataset.resize(0)
+    }
+
+    /// Reads all values of the column.
+    pub fn read(&self) -> hdf5::Result<Vec<T>> {
+        self.dataset.read_raw()
+    }
+}
+
+impl<T: H5Type + Clone> AppendableColumn<T> {
+    /// Appends `placeholder` until the column has `len` values, e.g. for the samples written
+    /// before a column was added.
+    pub fn pad_to(&self, len: usize, placeholder: T) -> hdf5::Result<()> {
+        self.append(&vec![placeholder; len.saturating_sub(self.len())])
+    }
+}
+
+impl SleepField {
+    /// Creates the column of the field `name` in `group`, with the value of an unavailable
+    /// reading (`false`, 0, `NAN`, or empty) for the `sample_count` samples already written.
+    pub(crate) fn create_column(&self, group: &hdf5::Group, name: &str, compression: &CompressionConfig, sample_count: usize) -> Result<(), Box<dyn Error>> {
+        match self {
+            SleepField::Bool(_) => create_padded(group, name, compression, sample_count, false),
+            SleepField::U16(_) => create_padded(group, name, compression, sample_count, 0u16),
+            SleepField::U64(_) => create_padded(group, name, compression, sample_count, 0u64),
+            SleepField::F32(_) => create_padded(group, name, compression, sample_count, f32::NAN),
+            SleepField::String(_) => create_padded(group, name, compression, sample_count, VarLenUnicode::default()),
+            SleepField::Bytes(_) => create_padded(group, name, compression, sample_count, VarLenArray::<u8>::from_slice(&[])),
+        }
+    }
+
+    /// Appends the field of `samples` to its column `name` in `group`.
+    pub(crate) fn append_column(&self, group: &hdf5::Group, name: &str, samples: &[SleepData]) -> Result<(), Box<dyn Error>> {
+        match *self {
+            SleepField::Bool(f) => append_field(group, name, samples, f),
+            SleepField::U16(f) => append_field(group, name, samples, f),
+            SleepField::U64(f) => append_field(group, name, samples, f),
+            SleepField::F32(f) => append_field(group, name, samples, f),
+            SleepField::String(f) => append_field(group, name, samples, f),
+            SleepField::Bytes(f) => append_field(group, name, samples, f),
+        }
+    }
+}
+
+/// Creates the column `name` of `group` with `len` `placeholder` values.
+fn create_padded<T: H5Type + Clone>(group: &hdf5::Group, name: &str, compression: &CompressionConfig, len: usize, placeholder: T) -> Result<(), Box<dyn Error>> {
+    AppendableColumn::<T>::create(group, name, compression)?.pad_to(len, placeholder)?;
+    Ok(())
+}
+
+/// Appends the values `field` reads from `samples` to the column `name` of `group`.
+fn append_field<T: H5Type>(group: &hdf5::Group, name: &str, samples: &[SleepData], field: fn(&SleepData) -> T) -> Result<(), Box<dyn Error>> {
+    let values: Vec<T> = samples.iter().map(field).collect();
+    AppendableColumn::<T>::open(group, name)?.append(&values)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_appendable_column() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let file = hdf5::File::create(dir.path().join("columns.h5")).expect("Failed to create file");
+        let group = file.create_group("session").expect("Failed to create group");
+        let compression = CompressionConfig::default();
+
+        let column = AppendableColumn::<f32>::create(&group, "radar_waveform", &compression).expect("Failed to create column");
+        assert!(column.is_empty());
+        column.append(&[1.0, 2.0]).expect("Failed to append");
+        column.append(&[]).expect("Failed to append nothing");
+        column.pad_to(4, f32::NAN).expect("Failed to pad");
+        column.write_at(3, &[4.0, 5.0]).expect("Failed to write");
+        let values = AppendableColumn::<f32>::open_or_create(&group, "radar_waveform", &compression)
+            .expect("Failed to open column")
+            .read()
+            .expect("Failed to read");
+        assert_eq!(values[..2], [1.0, 2.0]);
+        assert!(values[2].is_nan());
+        assert_eq!(values[3..], [4.0, 5.0]);
+
+        column.replace(&[7.0]).expect("Failed to replace");
+        assert_eq!(column.read().unwrap(), [7.0]);
+        column.clear().expect("Failed to clear");
+        assert_eq!(column.len(), 0);
+        assert!(AppendableColumn::<f32>::create(&group, "radar_waveform", &compression).is_err());
+        assert!(AppendableColumn::<u64>::open(&group, "missing").is_err());
+    }
+
+    #[test]
+    fn test_field_columns() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let file = hdf5::File::create(dir.path().join("columns.h5")).expect("Failed to create file");
+        let group = file.create_group("session").expect("Failed to create group");
+        let field = SleepField::F32(|d| d.temperature_c);
+        field.create_column(&group, "temperature", &CompressionConfig::default(), 2).expect("Failed to create column");
+        let samples = [SleepData::builder(10).with_climate(21.5, 1013.0, 40.0).build()];
+        field.append_column(&group, "temperature", &samples).expect("Failed to append column");
+
+        let values = AppendableColumn::<f32>::open(&group, "temperature").unwrap().read().unwrap();
+        assert_eq!(values.len(), 3);
+        assert!(values[0].is_nan() && values[1].is_nan());
+        assert_eq!(values[2], 21.5);
+    }
+}
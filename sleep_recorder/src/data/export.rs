@@ -0,0 +1,313 @@
+//! Export of recorded sessions for analysis tools that expect one table per session, such as
+//! polars or pandas.
+//!
+//! [`sample_columns`] collects the per-sample datasets of a session (`timestamp`, the sensor
+//! fields, and the per-camera, per-probe, and per-sensor datasets) as named columns, one row per
+//! sample. With the `parquet` feature, [`to_parquet`] writes them to a Parquet file.
+//!
+//! With the `json` feature, [`SessionReader::to_json`] instead writes a whole session as a single
+//! JSON document, for web frontends and scripts without an HDF5 stack.
+//!
+//! [`edf::to_edf`] writes the heart and respiration rates, motion, and audio level of a session,
+//! with its events, to an EDF+ file for polysomnography tools, and [`health`] its sleep periods
+//! to Apple Health XML or Google Fit JSON.
+
+use std::error::Error;
+
+use hdf5::types::{FloatSize, IntSize, TypeDescriptor, VarLenUnicode};
+use tracing::warn;
+
+use super::SessionReader;
+#[cfg(feature = "json")]
+use super::{Event, SessionMetadata, SleepData};
+
+pub mod edf;
+pub mod health;
+
+/// Datasets of a session that are not stored per sample, and are left out of the export.
+pub(crate) const NON_SAMPLE_DATASETS: [&str; 15] = [
+    "audio",
+    "live_audio_rms_db",
+    "live_audio_rms_t_s",
+    "piezo_bcg_mv",
+    "piezo_bcg_start",
+    "actuator_event_t_s",
+    "actuator_event_name",
+    "actuator_event_on",
+    "events",
+    "audio_band_t_s",
+    "audio_band_low_db",
+    "audio_band_speech_db",
+    "audio_band_high_db",
+    "audio_resp_t_s",
+    "audio_resp_rate_bpm",
+];
+
+/// Values of one per-sample dataset.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Bool(Vec<bool>),
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U64(Vec<u64>),
+    F32(Vec<f32>),
+    String(Vec<String>),
+}
+
+impl Column {
+    /// Number of values in the column.
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Bool(values) => values.len(),
+            Column::U8(values) => values.len(),
+            Column::U16(values) => values.len(),
+            Column::U64(values) => values.len(),
+            Column::F32(values) => values.len(),
+            Column::String(values) => values.len(),
+        }
+    }
+
+    /// Whether the column has no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Reads the per-sample datasets of `session` as columns named like the datasets, `timestamp`
+/// first and the others in alphabetical order.
+///
+/// Datasets that were not written with every sample (audio, live audio levels, piezo bursts, and
+/// actuator events) are left out, as are datasets of a type other than those of [`Column`].
+pub fn sample_columns(session: &SessionReader) -> Result<Vec<(String, Column)>, Box<dyn Error>> {
+    let group = session.group()?;
+    let sample_count = session.sample_count()?;
+    let mut names = group.member_names()?;
+    names.sort();
+    if let Some(index) = names.iter().position(|name| name == "timestamp") {
+        let timestamp = names.remove(index);
+        names.insert(0, timestamp);
+    }
+
+    let mut columns = Vec::new();
+    for name in names {
+        if NON_SAMPLE_DATASETS.contains(&name.as_str()) {
+            continue;
+        }
+        // Skip anything that isn't a dataset
+        let Ok(dataset) = group.dataset(&name) else {
+            continue;
+        };
+        if dataset.shape() != [sample_count] {
+            warn!("Skipping dataset {} with shape {:?}; expected {} samples", name, dataset.shape(), sample_count);
+            continue;
+        }
+        let column = match dataset.dtype()?.to_descriptor()? {
+            TypeDescriptor::Boolean => Column::Bool(dataset.read_raw()?),
+            TypeDescriptor::Unsigned(IntSize::U1) => Column::U8(dataset.read_raw()?),
+            TypeDescriptor::Unsigned(IntSize::U2) => Column::U16(dataset.read_raw()?),
+            TypeDescriptor::Unsigned(IntSize::U8) => Column::U64(dataset.read_raw()?),
+            TypeDescriptor::Float(FloatSize::U4) => Column::F32(dataset.read_raw()?),
+            TypeDescriptor::VarLenUnicode => Column::String(
+                dataset.read_raw::<VarLenUnicode>()?.iter().map(|s| s.to_string()).collect()
+            ),
+            other => {
+                warn!("Skipping dataset {} of unsupported type {:?}", name, other);
+                continue;
+            }
+        };
+        columns.push((name, column));
+    }
+    Ok(columns)
+}
+
+/// An audio recording in the JSON export.
+#[cfg(feature = "json")]
+#[derive(Debug, serde::Serialize)]
+struct JsonAudioRecording {
+    path: String,
+    start_time_s: u64,
+    duration_s: u64,
+    rms_db: Vec<f32>,
+    rms_t_s: Vec<u64>,
+}
+
+/// A session in the JSON export.
+#[cfg(feature = "json")]
+#[derive(Debug, serde::Serialize)]
+struct JsonSession {
+    session: String,
+    schema_version: u32,
+    metadata: SessionMetadata,
+    samples: Vec<SleepData>,
+    audio_recordings: Vec<JsonAudioRecording>,
+    events: Vec<Event>,
+}
+
+#[cfg(feature = "json")]
+impl SessionReader {
+    /// The session as a single JSON document:
+    ///
+    /// ```json
+    /// {
+    ///   "session": "2025-04-28_22-47-31",
+    ///   "schema_version": 8,
+    ///   "metadata": { "device_id": "pi", ... },
+    ///   "samples": [{ "timestamp_s": 1745873251, "temperature_c": 21.5, ... }],
+    ///   "audio_recordings": [{ "path": "...", "start_time_s": 1745873251, "duration_s": 600, "rms_db": [...], "rms_t_s": [...] }],
+    ///   "events": [{ "timestamp_s": 1745873251, "category": "bedtime", "text": "went to bed" }]
+    /// }
+    /// ```
+    ///
+    /// The samples have the fields of [`SleepData`], and NaN values are `null`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sleep_recorder::data::SessionReader;
+    /// let session = SessionReader::open("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31")
+    ///     .expect("Failed to open session");
+    /// std::fs::write("/path/to/2025-04-28_22-47-31.json", session.to_json().expect("Failed to export"))
+    ///     .expect("Failed to write export");
+    /// ```
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        let audio_recordings = self.audio_entries()?.into_iter()
+            .map(|entry| JsonAudioRecording {
+                path: entry.path.to_string(),
+                start_time_s: entry.start_time_s,
+                duration_s: entry.duration_s,
+                rms_db: entry.audio_rms_db.to_vec(),
+                rms_t_s: entry.audio_rms_t_s.to_vec(),
+            })
+            .collect();
+        let session = JsonSession {
+            session: self.group_name().to_string(),
+            schema_version: self.schema_version()?,
+            metadata: self.metadata()?,
+            samples: self.samples()?,
+            audio_recordings,
+            events: self.events()?,
+        };
+        Ok(serde_json::to_string(&session)?)
+    }
+}
+
+/// Writes the per-sample datasets of `session` (see [`sample_columns`]) to a Parquet file at
+/// `path`, one row per sample, and returns the number of rows.
+///
+/// Unsigned integers keep their width as Parquet logical types, and float columns are nullable
+/// with `NAN` (unavailable) values stored as null.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::data::{export, SessionReader};
+/// let session = SessionReader::open("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31")
+///     .expect("Failed to open session");
+/// export::to_parquet(&session, "/path/to/2025-04-28_22-47-31.parquet").expect("Failed to export");
+/// ```
+#[cfg(feature = "parquet")]
+pub fn to_parquet(session: &SessionReader, path: impl AsRef<std::path::Path>) -> Result<usize, Box<dyn Error>> {
+    let columns = sample_columns(session)?;
+    let file = std::fs::File::create(path)?;
+    write_parquet(&columns, file)?;
+    Ok(columns.first().map_or(0, |(_, column)| column.len()))
+}
+
+/// Writes `columns` as a single row group of a Parquet file.
+#[cfg(feature = "parquet")]
+fn write_parquet<W: std::io::Write + Send>(columns: &[(String, Column)], writer: W) -> Result<(), Box<dyn Error>> {
+    use std::sync::Arc;
+
+    use parquet::basic::{Compression, LogicalType, Repetition, Type as PhysicalType};
+    use parquet::data_type::{BoolType, ByteArray, ByteArrayType, FloatType, Int32Type, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::types::Type;
+
+    let unsigned = |bit_width| Some(LogicalType::Integer { bit_width, is_signed: false });
+    let mut fields = Vec::new();
+    for (name, column) in columns {
+        let (physical_type, logical_type, repetition) = match column {
+            Column::Bool(_) => (PhysicalType::BOOLEAN, None, Repetition::REQUIRED),
+            Column::U8(_) => (PhysicalType::INT32, unsigned(8), Repetition::REQUIRED),
+            Column::U16(_) => (PhysicalType::INT32, unsigned(16), Repetition::REQUIRED),
+            Column::U64(_) => (PhysicalType::INT64, unsigned(64), Repetition::REQUIRED),
+            Column::F32(_) => (PhysicalType::FLOAT, None, Repetition::OPTIONAL),
+            Column::String(_) => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String), Repetition::REQUIRED),
+        };
+        let field = Type::primitive_type_builder(name, physical_type)
+            .with_logical_type(logical_type)
+            .with_repetition(repetition)
+            .build()?;
+        fields.push(Arc::new(field));
+    }
+    let schema = Type::group_type_builder("session").with_fields(fields).build()?;
+    let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+
+    let mut file_writer = SerializedFileWriter::new(writer, Arc::new(schema), Arc::new(properties))?;
+    let mut row_group = file_writer.next_row_group()?;
+    for (_, column) in columns {
+        let mut column_writer = row_group.next_column()?.ok_or("Fewer Parquet columns than datasets")?;
+        match column {
+            Column::Bool(values) => {
+                column_writer.typed::<BoolType>().write_batch(values, None, None)?;
+            }
+            Column::U8(values) => {
+                let values: Vec<i32> = values.iter().map(|&v| v as i32).collect();
+                column_writer.typed::<Int32Type>().write_batch(&values, None, None)?;
+            }
+            Column::U16(values) => {
+                let values: Vec<i32> = values.iter().map(|&v| v as i32).collect();
+                column_writer.typed::<Int32Type>().write_batch(&values, None, None)?;
+            }
+            Column::U64(values) => {
+                // Stored as the same 64 bits; readers reinterpret them as unsigned
+                let values: Vec<i64> = values.iter().map(|&v| v as i64).collect();
+                column_writer.typed::<Int64Type>().write_batch(&values, None, None)?;
+            }
+            Column::F32(values) => {
+                let present: Vec<f32> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+                let definition_levels: Vec<i16> = values.iter().map(|v| (!v.is_nan()) as i16).collect();
+                column_writer.typed::<FloatType>().write_batch(&present, Some(&definition_levels), None)?;
+            }
+            Column::String(values) => {
+                let values: Vec<ByteArray> = values.iter().map(|v| ByteArray::from(v.as_str())).collect();
+                column_writer.typed::<ByteArrayType>().write_batch(&values, None, None)?;
+            }
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+    file_writer.close()?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "parquet"))]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+    use test_log::test;
+
+    #[test]
+    fn test_parquet_rows_and_nulls() {
+        let columns = vec![
+            ("timestamp".to_string(), Column::U64(vec![10, 15, 20])),
+            ("temperature".to_string(), Column::F32(vec![21.5, f32::NAN, 21.0])),
+            ("pir_motion".to_string(), Column::Bool(vec![false, true, false])),
+            ("image_path".to_string(), Column::String(vec!["a.jpg".to_string(), String::new(), "c.jpg".to_string()])),
+        ];
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("session.parquet");
+        write_parquet(&columns, std::fs::File::create(&path).unwrap()).expect("Failed to write Parquet");
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).expect("Failed to read Parquet");
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|row| row.unwrap()).collect();
+        let fields: Vec<_> = rows[1].get_column_iter().map(|(name, field)| (name.clone(), field.clone())).collect();
+        assert_eq!(fields[0], ("timestamp".to_string(), Field::ULong(15)));
+        // NAN is stored as null
+        assert_eq!(fields[1], ("temperature".to_string(), Field::Null));
+        assert_eq!(fields[2], ("pir_motion".to_string(), Field::Bool(true)));
+    }
+}
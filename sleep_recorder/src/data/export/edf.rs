@@ -0,0 +1,394 @@
+//! European Data Format (EDF+) export of sessions, for polysomnography viewers such as
+//! EDFbrowser and sleep-research pipelines.
+//!
+//! EDF stores signals sampled at a fixed rate, in data records of a fixed duration. [`to_edf`]
+//! resamples the heart and respiration rates, motion, and live audio level at 1 Hz, each second
+//! holding the latest reading for up to [`MAX_HOLD_S`] seconds, in records of [`RECORD_S`]
+//! seconds (a sleep-scoring epoch). Seconds without a reading hold the signal's physical minimum
+//! (e.g. 0 bpm). Actuator events and audio recordings are written as EDF+ annotations.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+
+use crate::data::{summary, SessionReader, SleepData};
+
+/// Duration of a data record, in seconds.
+pub const RECORD_S: u64 = 30;
+/// Longest time a reading is held for, in seconds; later seconds without a reading are
+/// unavailable.
+pub const MAX_HOLD_S: u64 = 60;
+
+/// Label of the EDF+ annotation signal.
+const ANNOTATIONS_LABEL: &str = "EDF Annotations";
+
+/// A signal sampled at 1 Hz from the start of the file.
+#[derive(Clone, Debug, PartialEq)]
+struct Signal {
+    label: &'static str,
+    transducer: &'static str,
+    dimension: &'static str,
+    physical_min: f32,
+    physical_max: f32,
+    /// Values per second; `NAN` when unavailable.
+    samples: Vec<f32>,
+}
+
+/// An EDF+ annotation.
+#[derive(Clone, Debug, PartialEq)]
+struct Annotation {
+    /// Time from the start of the file, in seconds.
+    onset_s: i64,
+    duration_s: Option<u64>,
+    text: String,
+}
+
+/// Writes the session to an EDF+ file at `path` (see the [module documentation](self)), and
+/// returns the number of data records.
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be read or has no samples, or if the file cannot be
+/// written.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::data::{export, SessionReader};
+/// let session = SessionReader::open("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31")
+///     .expect("Failed to open session");
+/// export::edf::to_edf(&session, "/path/to/2025-04-28_22-47-31.edf").expect("Failed to export");
+/// ```
+pub fn to_edf(session: &SessionReader, path: impl AsRef<Path>) -> Result<usize, Box<dyn Error>> {
+    let samples = session.samples()?;
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return Err(format!("Session {} has no samples", session.group_name()).into());
+    };
+    let start_s = first.timestamp_s;
+    let seconds = (last.timestamp_s - start_s + 1) as usize;
+    let times: Vec<u64> = samples.iter().map(|sample| sample.timestamp_s).collect();
+    let column = |name: &str, value: fn(&SleepData) -> f32| -> Vec<f32> {
+        let values: Vec<f32> = samples.iter()
+            .map(|sample| summary::available(name, value(sample), sample.quality_flags))
+            .collect();
+        hold(&times, &values, start_s, seconds)
+    };
+    let (audio_times, audio_levels) = session.live_audio_levels()?;
+
+    let signals = [
+        Signal {
+            label: "HR mmWave",
+            transducer: "C1001 mmWave radar",
+            dimension: "bpm",
+            physical_min: 0.0,
+            physical_max: 250.0,
+            samples: column("mmwave_heart_rate_bpm", |sample| f32::from(sample.mmwave_heart_rate_bpm)),
+        },
+        Signal {
+            label: "HR piezo",
+            transducer: "Piezo BCG",
+            dimension: "bpm",
+            physical_min: 0.0,
+            physical_max: 250.0,
+            samples: column("piezo_heart_rate_bpm", |sample| sample.piezo_heart_rate_bpm),
+        },
+        Signal {
+            label: "RR mmWave",
+            transducer: "C1001 mmWave radar",
+            dimension: "/min",
+            physical_min: 0.0,
+            physical_max: 60.0,
+            samples: column("mmwave_resp_rate_bpm", |sample| f32::from(sample.mmwave_resp_rate_bpm)),
+        },
+        Signal {
+            label: "RR piezo",
+            transducer: "Piezo BCG",
+            dimension: "/min",
+            physical_min: 0.0,
+            physical_max: 60.0,
+            samples: column("piezo_resp_rate_bpm", |sample| sample.piezo_resp_rate_bpm),
+        },
+        Signal {
+            label: "Motion image",
+            transducer: "Camera",
+            dimension: "",
+            physical_min: 0.0,
+            physical_max: 255.0,
+            samples: column("image_motion", SleepData::image_motion_or_live),
+        },
+        Signal {
+            label: "Motion PIR",
+            transducer: "PIR",
+            dimension: "",
+            physical_min: 0.0,
+            physical_max: 1.0,
+            samples: column("pir_motion", |sample| f32::from(u8::from(sample.pir_motion))),
+        },
+        Signal {
+            label: "Motion mmWave",
+            transducer: "C1001 mmWave radar",
+            dimension: "",
+            physical_min: 0.0,
+            physical_max: 1.0,
+            samples: column("mmwave_movement", |sample| f32::from(u8::from(sample.mmwave_movement))),
+        },
+        Signal {
+            label: "Audio RMS",
+            transducer: "Microphone",
+            dimension: "dBFS",
+            physical_min: -120.0,
+            physical_max: 0.0,
+            samples: hold(&audio_times, &audio_levels, start_s, seconds),
+        },
+    ];
+
+    let onset = |time_s: u64| time_s as i64 - start_s as i64;
+    let mut annotations: Vec<Annotation> = session.actuator_events()?.into_iter()
+        .map(|event| Annotation {
+            onset_s: onset(event.timestamp_s),
+            duration_s: None,
+            text: format!("{} {}", event.name, if event.on { "on" } else { "off" }),
+        })
+        .collect();
+    annotations.extend(session.events()?.into_iter().map(|event| Annotation {
+        onset_s: onset(event.timestamp_s),
+        duration_s: None,
+        text: format!("{}: {}", event.category, event.text),
+    }));
+    for entry in session.audio_entries()? {
+        let file_name = Path::new(entry.path.as_str()).file_name().map_or_else(
+            || entry.path.to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        annotations.push(Annotation {
+            onset_s: onset(entry.start_time_s),
+            duration_s: Some(entry.duration_s),
+            text: format!("Audio recording {file_name}"),
+        });
+    }
+    annotations.sort_by_key(|annotation| annotation.onset_s);
+
+    let start = DateTime::from_timestamp(start_s as i64, 0)
+        .ok_or("Invalid session start")?
+        .with_timezone(&Local)
+        .naive_local();
+    let metadata = session.metadata()?;
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_edf(file, start, &metadata.subject, &metadata.device_id, &signals, &annotations)
+}
+
+/// Resamples the readings `values` at `times` (sorted, in seconds since UNIX epoch) at 1 Hz
+/// over `count` seconds from `start_s`, holding each reading for up to [`MAX_HOLD_S`] seconds.
+fn hold(times: &[u64], values: &[f32], start_s: u64, count: usize) -> Vec<f32> {
+    let mut held = vec![f32::NAN; count];
+    let mut readings = times.iter().zip(values).peekable();
+    let mut latest = None;
+    for (second, slot) in held.iter_mut().enumerate() {
+        let time_s = start_s + second as u64;
+        while let Some((&reading_s, &value)) = readings.next_if(|(&reading_s, _)| reading_s <= time_s) {
+            latest = Some((reading_s, value));
+        }
+        if let Some((reading_s, value)) = latest {
+            if time_s - reading_s <= MAX_HOLD_S {
+                *slot = value;
+            }
+        }
+    }
+    held
+}
+
+/// Writes an EDF+ file of `signals` and `annotations` starting at `start` (local time) to
+/// `writer`, and returns the number of data records.
+fn write_edf<W: Write>(
+    mut writer: W,
+    start: NaiveDateTime,
+    subject: &str,
+    equipment: &str,
+    signals: &[Signal],
+    annotations: &[Annotation],
+) -> Result<usize, Box<dyn Error>> {
+    let seconds = signals.iter().map(|signal| signal.samples.len()).max().unwrap_or(0).max(1);
+    let records = seconds.div_ceil(RECORD_S as usize);
+    let mut tals = vec![Vec::new(); records];
+    for (record, tal) in tals.iter_mut().enumerate() {
+        // Each record starts with the time-keeping annotation of its start
+        tal.extend(format!("+{}\x14\x14\0", record as u64 * RECORD_S).as_bytes());
+    }
+    for annotation in annotations {
+        let record = (annotation.onset_s.max(0) as u64 / RECORD_S).min(records as u64 - 1) as usize;
+        let duration = annotation.duration_s.map(|duration_s| format!("\x15{duration_s}")).unwrap_or_default();
+        let onset = format!("{:+}", annotation.onset_s);
+        tals[record].extend(format!("{onset}{duration}\x14{}\x14\0", annotation.text).as_bytes());
+    }
+    let annotation_samples = tals.iter().map(|tal| tal.len().div_ceil(2)).max().unwrap_or(1);
+
+    let signal_count = signals.len() + 1;
+    let mut header = Vec::with_capacity(256 * (signal_count + 1));
+    field(&mut header, "0", 8);
+    field(&mut header, &format!("X X X {}", subfield(subject)), 80);
+    let startdate = start.format("%d-%b-%Y").to_string().to_uppercase();
+    field(&mut header, &format!("Startdate {startdate} X X {}", subfield(equipment)), 80);
+    field(&mut header, &start.format("%d.%m.%y").to_string(), 8);
+    field(&mut header, &start.format("%H.%M.%S").to_string(), 8);
+    field(&mut header, &(256 * (signal_count + 1)).to_string(), 8);
+    field(&mut header, "EDF+C", 44);
+    field(&mut header, &records.to_string(), 8);
+    field(&mut header, &RECORD_S.to_string(), 8);
+    field(&mut header, &signal_count.to_string(), 4);
+
+    let annotation_signal = (ANNOTATIONS_LABEL, "", "", "-1".to_string(), "1".to_string(), annotation_samples);
+    let signal_headers: Vec<_> = signals.iter()
+        .map(|signal| (
+            signal.label,
+            signal.transducer,
+            signal.dimension,
+            signal.physical_min.to_string(),
+            signal.physical_max.to_string(),
+            RECORD_S as usize,
+        ))
+        .chain([annotation_signal])
+        .collect();
+    for (label, ..) in &signal_headers {
+        field(&mut header, label, 16);
+    }
+    for (_, transducer, ..) in &signal_headers {
+        field(&mut header, transducer, 80);
+    }
+    for (_, _, dimension, ..) in &signal_headers {
+        field(&mut header, dimension, 8);
+    }
+    for (_, _, _, physical_min, ..) in &signal_headers {
+        field(&mut header, physical_min, 8);
+    }
+    for (_, _, _, _, physical_max, _) in &signal_headers {
+        field(&mut header, physical_max, 8);
+    }
+    for _ in &signal_headers {
+        field(&mut header, &i16::MIN.to_string(), 8);
+    }
+    for _ in &signal_headers {
+        field(&mut header, &i16::MAX.to_string(), 8);
+    }
+    for _ in &signal_headers {
+        field(&mut header, "", 80);
+    }
+    for (.., samples) in &signal_headers {
+        field(&mut header, &samples.to_string(), 8);
+    }
+    for _ in &signal_headers {
+        field(&mut header, "", 32);
+    }
+    writer.write_all(&header)?;
+
+    for (record, tal) in tals.iter_mut().enumerate() {
+        let mut data = Vec::new();
+        for signal in signals {
+            for second in record * RECORD_S as usize..(record + 1) * RECORD_S as usize {
+                let value = signal.samples.get(second).copied().unwrap_or(f32::NAN);
+                data.extend(digital(value, signal.physical_min, signal.physical_max).to_le_bytes());
+            }
+        }
+        tal.resize(2 * annotation_samples, 0);
+        data.extend(tal.iter());
+        writer.write_all(&data)?;
+    }
+    writer.flush()?;
+    Ok(records)
+}
+
+/// Digital value of `value` in the physical range from `min` to `max`, mapped onto the whole
+/// range of `i16`. Unavailable values are the digital minimum.
+fn digital(value: f32, min: f32, max: f32) -> i16 {
+    if value.is_nan() {
+        return i16::MIN;
+    }
+    let scaled = (value.clamp(min, max) - min) / (max - min) * 65535.0 + f32::from(i16::MIN);
+    scaled.round() as i16
+}
+
+/// Appends `value` to `header` as printable ASCII, cut or padded with spaces to `width` bytes.
+fn field(header: &mut Vec<u8>, value: &str, width: usize) {
+    let mut bytes: Vec<u8> = value.chars()
+        .map(|c| if c.is_ascii_graphic() || c == ' ' { c as u8 } else { b'_' })
+        .take(width)
+        .collect();
+    bytes.resize(width, b' ');
+    header.extend(bytes);
+}
+
+/// `value` as an EDF+ header subfield: `X` when unknown, with spaces replaced by underscores.
+fn subfield(value: &str) -> String {
+    match value.trim() {
+        "" => "X".to_string(),
+        value => value.replace(' ', "_"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    /// The ASCII header field of `width` bytes at `offset`, without padding.
+    fn header_field(edf: &[u8], offset: usize, width: usize) -> &str {
+        std::str::from_utf8(&edf[offset..offset + width]).unwrap().trim_end()
+    }
+
+    #[test]
+    fn test_write_edf() {
+        let heart_rate = Signal {
+            label: "HR mmWave",
+            transducer: "C1001 mmWave radar",
+            dimension: "bpm",
+            physical_min: 0.0,
+            physical_max: 250.0,
+            samples: vec![0.0, 250.0, f32::NAN, 125.0],
+        };
+        let annotations = vec![
+            Annotation { onset_s: 2, duration_s: None, text: "fan on".to_string() },
+            Annotation { onset_s: 35, duration_s: Some(600), text: "Audio recording audio_35.wav".to_string() },
+        ];
+        let start = NaiveDateTime::parse_from_str("2025-04-28_22-47-31", "%Y-%m-%d_%H-%M-%S").unwrap();
+        let mut edf = Vec::new();
+        // Two records, for an annotation after the signal's end
+        let signal = Signal { samples: [heart_rate.samples.clone(), vec![f32::NAN; 32]].concat(), ..heart_rate };
+        let records = write_edf(&mut edf, start, "Jane Doe", "", &[signal], &annotations).expect("Failed to write EDF");
+        assert_eq!(records, 2);
+
+        assert_eq!(header_field(&edf, 0, 8), "0");
+        assert_eq!(header_field(&edf, 8, 80), "X X X Jane_Doe");
+        assert_eq!(header_field(&edf, 88, 80), "Startdate 28-APR-2025 X X X");
+        assert_eq!((header_field(&edf, 168, 8), header_field(&edf, 176, 8)), ("28.04.25", "22.47.31"));
+        assert_eq!(header_field(&edf, 184, 8), "768");
+        assert_eq!(header_field(&edf, 192, 44), "EDF+C");
+        assert_eq!((header_field(&edf, 236, 8), header_field(&edf, 244, 8), header_field(&edf, 252, 4)), ("2", "30", "2"));
+        assert_eq!((header_field(&edf, 256, 16), header_field(&edf, 272, 16)), ("HR mmWave", ANNOTATIONS_LABEL));
+        // Samples per record, after the labels, transducers, dimensions, ranges, and prefiltering
+        let samples_offset = 256 + 2 * (16 + 80 + 8 + 4 * 8 + 80);
+        assert_eq!(header_field(&edf, samples_offset, 8), "30");
+        let annotation_samples: usize = header_field(&edf, samples_offset + 8, 8).parse().unwrap();
+        assert_eq!(edf.len(), 768 + 2 * (2 * 30 + 2 * annotation_samples));
+
+        let sample = |index: usize| i16::from_le_bytes([edf[768 + 2 * index], edf[768 + 2 * index + 1]]);
+        assert_eq!((sample(0), sample(1), sample(2)), (i16::MIN, i16::MAX, i16::MIN));
+        // Mid-range
+        assert!(sample(3).abs() <= 1);
+        let first_tals = &edf[768 + 60..768 + 60 + 2 * annotation_samples];
+        assert!(first_tals.starts_with(b"+0\x14\x14\0+2\x14fan on\x14\0"));
+        let second_tals = &edf[768 + 60 + 2 * annotation_samples + 60..];
+        assert!(second_tals.starts_with(b"+30\x14\x14\0+35\x15600\x14Audio recording audio_35.wav\x14\0"));
+    }
+
+    #[test]
+    fn test_hold() {
+        let held = hold(&[100, 102, 200], &[1.0, 2.0, 3.0], 99, 170);
+        assert!(held[0].is_nan());
+        assert_eq!(held[1..4], [1.0, 1.0, 2.0]);
+        // Readings are held for at most MAX_HOLD_S
+        assert_eq!(held[(102 + MAX_HOLD_S - 99) as usize], 2.0);
+        assert!(held[(103 + MAX_HOLD_S - 99) as usize].is_nan());
+        assert_eq!(held[101], 3.0);
+    }
+}
@@ -0,0 +1,178 @@
+//! Export of the sleep periods of a session (see [`sleep_periods`](crate::sleep_periods)) in the
+//! formats of health apps, to compare nights recorded by this device with wearable data.
+//!
+//! [`to_apple_health`] writes Apple Health `HKCategoryTypeIdentifierSleepAnalysis` records, in
+//! the XML of an Apple Health export: an `InBed` record per time in bed, and `Awake` and
+//! `AsleepUnspecified` records within it. With the `json` feature, [`to_google_fit`] writes a
+//! Google Fit sleep session with its `com.google.sleep.segment` data points, as JSON for the
+//! Fit REST API. Sleep stages are not detected, so all sleep is unspecified (Google Fit's
+//! generic "sleep" segment).
+
+use std::error::Error;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+
+use super::SessionReader;
+use crate::sleep_periods::{self, SleepPeriod, SleepState};
+
+/// Source (application) name of the exported records.
+const SOURCE_NAME: &str = "sleep_recorder";
+
+/// Writes the sleep periods of `session` to an Apple Health XML file at `path`, and returns the
+/// number of records.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::data::{export, SessionReader};
+/// let session = SessionReader::open("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31")
+///     .expect("Failed to open session");
+/// export::health::to_apple_health(&session, "/path/to/2025-04-28_22-47-31.xml").expect("Failed to export");
+/// ```
+pub fn to_apple_health(session: &SessionReader, path: impl AsRef<Path>) -> Result<usize, Box<dyn Error>> {
+    let periods = sleep_periods::detect(&session.samples()?);
+    let (xml, records) = apple_health_xml(&periods);
+    std::fs::write(path, xml)?;
+    Ok(records)
+}
+
+/// Apple Health XML of `periods`, and its number of records.
+fn apple_health_xml(periods: &[SleepPeriod]) -> (String, usize) {
+    let mut records = Vec::new();
+    for in_bed in periods.chunk_by(|a, b| (a.state == SleepState::OutOfBed) == (b.state == SleepState::OutOfBed)) {
+        let (Some(first), Some(last)) = (in_bed.first(), in_bed.last()) else {
+            continue;
+        };
+        if first.state == SleepState::OutOfBed {
+            continue;
+        }
+        records.push(("InBed", first.start_s, last.end_s));
+        for period in in_bed {
+            let value = match period.state {
+                SleepState::Asleep => "AsleepUnspecified",
+                _ => "Awake",
+            };
+            records.push((value, period.start_s, period.end_s));
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<HealthData locale=\"en_US\">\n");
+    for (value, start_s, end_s) in &records {
+        xml.push_str(&format!(
+            " <Record type=\"HKCategoryTypeIdentifierSleepAnalysis\" sourceName=\"{SOURCE_NAME}\" \
+             value=\"HKCategoryValueSleepAnalysis{value}\" startDate=\"{}\" endDate=\"{}\"/>\n",
+            apple_date(*start_s),
+            apple_date(*end_s),
+        ));
+    }
+    xml.push_str("</HealthData>\n");
+    (xml, records.len())
+}
+
+/// `time_s` (seconds since UNIX epoch) as a date of Apple Health, in local time.
+fn apple_date(time_s: u64) -> String {
+    DateTime::from_timestamp(time_s as i64, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S %z")
+        .to_string()
+}
+
+/// Writes the sleep periods of `session` to a Google Fit session JSON file at `path`, and
+/// returns the number of sleep segments.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::data::{export, SessionReader};
+/// let session = SessionReader::open("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31")
+///     .expect("Failed to open session");
+/// export::health::to_google_fit(&session, "/path/to/2025-04-28_22-47-31.json").expect("Failed to export");
+/// ```
+#[cfg(feature = "json")]
+pub fn to_google_fit(session: &SessionReader, path: impl AsRef<Path>) -> Result<usize, Box<dyn Error>> {
+    let periods = sleep_periods::detect(&session.samples()?);
+    std::fs::write(path, google_fit_json(session.group_name(), &periods).to_string())?;
+    Ok(periods.len())
+}
+
+/// Google Fit sleep session of `periods`, with a sleep segment per period. Fit's int64 values
+/// are strings.
+#[cfg(feature = "json")]
+fn google_fit_json(session: &str, periods: &[SleepPeriod]) -> serde_json::Value {
+    let start_ms = periods.first().map_or(0, |period| period.start_s * 1000);
+    let end_ms = periods.last().map_or(0, |period| period.end_s * 1000);
+    let points: Vec<serde_json::Value> = periods.iter()
+        .map(|period| {
+            // Sleep segment types of Google Fit
+            let segment = match period.state {
+                SleepState::Awake => 1,
+                SleepState::Asleep => 2,
+                SleepState::OutOfBed => 3,
+            };
+            serde_json::json!({
+                "dataTypeName": "com.google.sleep.segment",
+                "startTimeNanos": (period.start_s * 1_000_000_000).to_string(),
+                "endTimeNanos": (period.end_s * 1_000_000_000).to_string(),
+                "value": [{ "intVal": segment }],
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "session": {
+            "id": format!("{SOURCE_NAME}-{session}"),
+            "name": "Sleep",
+            "startTimeMillis": start_ms.to_string(),
+            "endTimeMillis": end_ms.to_string(),
+            // Activity type of sleep
+            "activityType": 72,
+            "application": { "name": SOURCE_NAME },
+        },
+        "sleepSegments": {
+            "dataSourceId": format!("raw:com.google.sleep.segment:{SOURCE_NAME}"),
+            "minStartTimeNs": (start_ms * 1_000_000).to_string(),
+            "maxEndTimeNs": (end_ms * 1_000_000).to_string(),
+            "point": points,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn periods() -> Vec<SleepPeriod> {
+        vec![
+            SleepPeriod { start_s: 0, end_s: 60, state: SleepState::OutOfBed },
+            SleepPeriod { start_s: 60, end_s: 120, state: SleepState::Awake },
+            SleepPeriod { start_s: 120, end_s: 1020, state: SleepState::Asleep },
+        ]
+    }
+
+    #[test]
+    fn test_apple_health_xml() {
+        let (xml, records) = apple_health_xml(&periods());
+        assert_eq!(records, 3);
+        let lines: Vec<&str> = xml.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[2].contains("value=\"HKCategoryValueSleepAnalysisInBed\""));
+        assert!(lines[2].contains(&format!("startDate=\"{}\" endDate=\"{}\"", apple_date(60), apple_date(1020))));
+        assert!(lines[3].contains("HKCategoryValueSleepAnalysisAwake"));
+        assert!(lines[4].contains("HKCategoryValueSleepAnalysisAsleepUnspecified"));
+        assert_eq!(lines[5], "</HealthData>");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_google_fit_json() {
+        let json = google_fit_json("2025-04-28_22-47-31", &periods());
+        assert_eq!(json["session"]["id"], "sleep_recorder-2025-04-28_22-47-31");
+        assert_eq!(json["session"]["endTimeMillis"], "1020000");
+        let points = json["sleepSegments"]["point"].as_array().unwrap();
+        let segments: Vec<_> = points.iter().map(|point| point["value"][0]["intVal"].as_i64().unwrap()).collect();
+        assert_eq!(segments, vec![3, 1, 2]);
+        assert_eq!(points[2]["startTimeNanos"], "120000000000");
+    }
+}
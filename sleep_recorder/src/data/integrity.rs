@@ -0,0 +1,303 @@
+//! Consistency checks of recorded sessions, and repair of the references they find dangling.
+//!
+//! A crash, a full disk, or files deleted by hand can leave a session subtly inconsistent, e.g.
+//! with a per-sample dataset shorter than `timestamp`, or paths of images that no longer exist.
+//! [`verify_session`] checks that:
+//! - the per-sample datasets have a value per timestamp, and the datasets stored together (live
+//!   audio levels and their times, the audio band levels and their times, the `actuator_event_*`
+//!   datasets, the datasets of a summary) have the same length;
+//! - the timestamps increase;
+//! - the image and audio files the session references exist, unless retention purged them (see
+//!   [`retention::prune`](crate::retention::prune));
+//! - each audio recording has as many RMS levels as RMS window times;
+//! - every chunk can be read (see [`verify`]).
+//!
+//! With `repair`, references to missing files are cleared (as if there had been no image or
+//! recording file) and the RMS levels and times of a recording are trimmed to the shorter of the
+//! two. The other issues are only reported.
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use hdf5::types::{VarLenArray, VarLenUnicode};
+use hdf5::File;
+use tracing::{info, warn};
+
+use super::export::NON_SAMPLE_DATASETS;
+use super::{session_path, summary, verify, CorruptChunk, H5AudioMetadata};
+use crate::image_analysis::ARCHIVE_MEMBER_SEPARATOR;
+use crate::retention::Media;
+
+/// Datasets written together, which have the same length.
+const PARALLEL_DATASETS: [&[&str]; 3] = [
+    &["live_audio_rms_t_s", "live_audio_rms_db"],
+    &["audio_band_t_s", "audio_band_low_db", "audio_band_speech_db", "audio_band_high_db"],
+    &["actuator_event_t_s", "actuator_event_name", "actuator_event_on"],
+];
+
+/// An inconsistency found by [`verify_session`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// A dataset with another length than the datasets stored with it.
+    LengthMismatch { dataset: String, len: usize, expected: usize },
+    /// A timestamp that isn't after the one before it.
+    UnorderedTimestamp { index: usize, timestamp_s: u64, previous_s: u64 },
+    /// A path, at `index` of `dataset`, of a file that doesn't exist.
+    MissingFile { dataset: String, index: usize, path: String },
+    /// An audio recording with a different number of RMS levels and window times.
+    RmsMismatch { start_time_s: u64, levels: usize, times: usize },
+    /// A chunk that cannot be read.
+    Corrupt(CorruptChunk),
+}
+
+impl fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityIssue::LengthMismatch { dataset, len, expected } => {
+                write!(f, "dataset {dataset} has {len} values instead of {expected}")
+            }
+            IntegrityIssue::UnorderedTimestamp { index, timestamp_s, previous_s } => {
+                write!(f, "timestamp {index} ({timestamp_s}) is not after the previous one ({previous_s})")
+            }
+            IntegrityIssue::MissingFile { dataset, index, path } => {
+                write!(f, "value {index} of dataset {dataset} references missing file {path}")
+            }
+            IntegrityIssue::RmsMismatch { start_time_s, levels, times } => {
+                write!(f, "audio recording at {start_time_s} has {levels} RMS levels but {times} window times")
+            }
+            IntegrityIssue::Corrupt(chunk) => {
+                write!(f, "values {:?} of dataset {} cannot be read: {}", chunk.values, chunk.dataset, chunk.error)
+            }
+        }
+    }
+}
+
+/// What [`verify_session`] found, and repaired.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Name of the session.
+    pub session: String,
+    /// The issues found, including those repaired.
+    pub issues: Vec<IntegrityIssue>,
+    /// Number of the issues that were repaired.
+    pub repaired: usize,
+}
+
+impl IntegrityReport {
+    /// Whether no issue was found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks the consistency of the session `group_name` in the HDF5 file at `data_path/file_name`
+/// (or the file of its night, see [`session_path`]), as described in the
+/// [module documentation](self). With `repair`, references to missing files are cleared and
+/// mismatched RMS levels trimmed.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::data;
+/// let report = data::verify_session("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31", false)
+///     .expect("Failed to verify session");
+/// for issue in &report.issues {
+///     println!("{issue}");
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be opened or its datasets listed, or if a repair
+/// cannot be written.
+pub fn verify_session(data_path: &str, file_name: &str, group_name: &str, repair: bool) -> Result<IntegrityReport, Box<dyn Error>> {
+    let path = session_path(data_path, file_name, group_name);
+    let file = if repair { File::append(&path)? } else { File::open(&path)? };
+    let group = file.group(group_name)
+        .map_err(|e| format!("Session {} not found in {}: {}", group_name, file_name, e))?;
+    let mut report = IntegrityReport { session: group_name.to_string(), ..IntegrityReport::default() };
+
+    // Lengths
+    let timestamps = group.dataset("timestamp")?.read_raw::<u64>()?;
+    let mut names = group.member_names()?;
+    names.sort();
+    let per_sample: Vec<&str> = names.iter()
+        .map(String::as_str)
+        .filter(|name| *name != "timestamp" && group.dataset(name).is_ok())
+        .filter(|name| !NON_SAMPLE_DATASETS.contains(name) || *name == "piezo_bcg_start")
+        .collect();
+    check_lengths(&group, &per_sample, timestamps.len(), &mut report)?;
+    for datasets in PARALLEL_DATASETS {
+        let present: Vec<&str> = datasets.iter().copied().filter(|name| group.dataset(name).is_ok()).collect();
+        if let Some(first) = present.first() {
+            check_lengths(&group, &present[1..], group.dataset(first)?.shape()[0], &mut report)?;
+        }
+    }
+    for interval_s in summary::INTERVALS_S {
+        let Ok(summary_group) = group.group(&summary::group_name(interval_s)) else {
+            continue;
+        };
+        let expected = summary_group.dataset("t_s")?.shape()[0];
+        let mut fields: Vec<String> = summary_group.member_names()?.into_iter().filter(|name| name != "t_s").collect();
+        fields.sort();
+        let fields: Vec<String> = fields.iter().map(|name| format!("{}/{}", summary::group_name(interval_s), name)).collect();
+        check_lengths(&group, &fields.iter().map(String::as_str).collect::<Vec<_>>(), expected, &mut report)?;
+    }
+
+    // Order
+    for (index, pair) in timestamps.windows(2).enumerate() {
+        if pair[1] <= pair[0] {
+            report.issues.push(IntegrityIssue::UnorderedTimestamp { index: index + 1, timestamp_s: pair[1], previous_s: pair[0] });
+        }
+    }
+
+    // References
+    for name in names.iter().filter(|name| Media::Images.references(name)) {
+        let dataset = group.dataset(name)?;
+        if dataset.attr("purged_s").is_ok() {
+            continue;
+        }
+        let mut paths = dataset.read_raw::<VarLenUnicode>()?;
+        let mut cleared = 0;
+        for (index, path) in paths.iter_mut().enumerate() {
+            if path.as_str().is_empty() || file_exists(path.as_str()) {
+                continue;
+            }
+            report.issues.push(IntegrityIssue::MissingFile { dataset: name.clone(), index, path: path.to_string() });
+            *path = VarLenUnicode::default();
+            cleared += 1;
+        }
+        if repair && cleared > 0 {
+            dataset.write_raw(&paths)?;
+            report.repaired += cleared;
+        }
+    }
+    if let Ok(dataset) = group.dataset("audio") {
+        let mut entries = dataset.read_raw::<H5AudioMetadata>()?;
+        let purged = dataset.attr("purged_s").is_ok();
+        let mut repaired = 0;
+        for (index, entry) in entries.iter_mut().enumerate() {
+            if !purged && !entry.path.as_str().is_empty() && !file_exists(entry.path.as_str()) {
+                report.issues.push(IntegrityIssue::MissingFile { dataset: "audio".to_string(), index, path: entry.path.to_string() });
+                entry.path = VarLenUnicode::default();
+                repaired += 1;
+            }
+            let (levels, times) = (entry.audio_rms_db.len(), entry.audio_rms_t_s.len());
+            if levels != times {
+                report.issues.push(IntegrityIssue::RmsMismatch { start_time_s: entry.start_time_s, levels, times });
+                let len = levels.min(times);
+                entry.audio_rms_db = VarLenArray::from_slice(&entry.audio_rms_db.as_slice()[..len]);
+                entry.audio_rms_t_s = VarLenArray::from_slice(&entry.audio_rms_t_s.as_slice()[..len]);
+                repaired += 1;
+            }
+        }
+        if repair && repaired > 0 {
+            dataset.write_raw(&entries)?;
+            report.repaired += repaired;
+        }
+    }
+
+    // Chunks
+    report.issues.extend(verify(&group)?.into_iter().map(IntegrityIssue::Corrupt));
+
+    for issue in &report.issues {
+        warn!("Session {}: {}", group_name, issue);
+    }
+    info!("Verified session {}: {} issues, {} repaired", group_name, report.issues.len(), report.repaired);
+    Ok(report)
+}
+
+/// Adds an issue for each of the datasets `names` of `group` that doesn't have `expected` values.
+fn check_lengths(group: &hdf5::Group, names: &[&str], expected: usize, report: &mut IntegrityReport) -> Result<(), Box<dyn Error>> {
+    for name in names {
+        let len = group.dataset(name)?.shape().first().copied().unwrap_or_default();
+        if len != expected {
+            report.issues.push(IntegrityIssue::LengthMismatch { dataset: name.to_string(), len, expected });
+        }
+    }
+    Ok(())
+}
+
+/// Whether the media file at `path` exists. Archived images (see
+/// [`ImageArchive::Tar`](crate::image_analysis::ImageArchive::Tar)) exist if their archive does.
+fn file_exists(path: &str) -> bool {
+    match path.split_once(ARCHIVE_MEMBER_SEPARATOR) {
+        Some((archive, _)) => Path::new(archive).exists(),
+        None => Path::new(path).exists(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    use crate::config::CompressionConfig;
+    use crate::data::{append_to_dataset, AudioRecording, CameraAndMotionResult, SleepData, SleepDataLogger};
+    use crate::sensor::CAMERA_FIELDS;
+    use test_log::test;
+
+    #[test]
+    fn test_verify_session() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let group_name = "2025-04-28_22-00-00";
+        let mut logger = SleepDataLogger::create(data_path, "sleep_data.h5", group_name, CompressionConfig::default())
+            .expect("Failed to create logger");
+        for &(name, field) in CAMERA_FIELDS {
+            logger.register_field(name, field).unwrap();
+        }
+        let image_path = |timestamp_s: u64| format!("{data_path}/image_{timestamp_s}.jpg");
+        fs::write(image_path(10), b"JPEG").unwrap();
+        // The second image is missing, and the third sample goes back in time
+        for timestamp_s in [10, 15, 12] {
+            logger.append(SleepData::builder(timestamp_s)
+                .with_camera_result(CameraAndMotionResult {
+                    image_path: image_path(timestamp_s),
+                    motion: None,
+                    clip_path: None,
+                    raw_image_path: None,
+                    thumbnail: None,
+                })
+                .build()).unwrap();
+        }
+        logger.add_audio_entry(AudioRecording {
+            path: format!("{data_path}/audio_10.wav"),
+            duration: Duration::from_secs(5),
+            start_time_s: 10,
+        }).unwrap();
+        drop(logger);
+        {
+            let file = File::append(dir.path().join("sleep_data.h5")).unwrap();
+            let group = file.group(group_name).unwrap();
+            append_to_dataset(&group, "image_motion_live", &[0.5f32]).unwrap();
+            let dataset = group.dataset("audio").unwrap();
+            let mut entries = dataset.read_raw::<H5AudioMetadata>().unwrap();
+            entries[0].audio_rms_db = VarLenArray::from_slice(&[-50.0, -45.0]);
+            entries[0].audio_rms_t_s = VarLenArray::from_slice(&[10]);
+            dataset.write_raw(&entries).unwrap();
+        }
+
+        let report = verify_session(data_path, "sleep_data.h5", group_name, false).expect("Failed to verify session");
+        assert_eq!(report.issues, vec![
+            IntegrityIssue::LengthMismatch { dataset: "image_motion_live".to_string(), len: 4, expected: 3 },
+            IntegrityIssue::UnorderedTimestamp { index: 2, timestamp_s: 12, previous_s: 15 },
+            IntegrityIssue::MissingFile { dataset: "image_path".to_string(), index: 1, path: image_path(15) },
+            IntegrityIssue::MissingFile { dataset: "image_path".to_string(), index: 2, path: image_path(12) },
+            IntegrityIssue::MissingFile { dataset: "audio".to_string(), index: 0, path: format!("{data_path}/audio_10.wav") },
+            IntegrityIssue::RmsMismatch { start_time_s: 10, levels: 2, times: 1 },
+        ]);
+        assert_eq!(report.repaired, 0);
+
+        let report = verify_session(data_path, "sleep_data.h5", group_name, true).expect("Failed to repair session");
+        assert_eq!((report.issues.len(), report.repaired), (6, 4));
+        // Only what cannot be repaired is left
+        let report = verify_session(data_path, "sleep_data.h5", group_name, false).unwrap();
+        assert_eq!(report.issues.len(), 2);
+        let file = File::open(dir.path().join("sleep_data.h5")).unwrap();
+        let paths = file.group(group_name).unwrap().dataset("image_path").unwrap().read_raw::<VarLenUnicode>().unwrap();
+        assert_eq!(paths.iter().map(|p| p.as_str()).collect::<Vec<_>>(), vec![image_path(10).as_str(), "", ""]);
+    }
+}
@@ -0,0 +1,322 @@
+//! Merging of sessions split in two, e.g. by a crash during the night.
+//!
+//! [`merge`] appends the samples of the later session to the earlier one, and deletes the later
+//! one. Its samples up to the last sample of the earlier session are dropped as duplicates.
+//! Per-sample datasets of only one of the sessions (e.g. of a camera plugged in after the
+//! restart) get the value of an unavailable reading for the samples of the other.
+//!
+//! Audio recordings, live audio levels, audio band levels, piezo bursts, actuator events, and
+//! annotations are appended as well.
+//! The media files of the later session are moved into the directory of the earlier one, and
+//! their paths rewritten to match. The summaries are recomputed from the merged samples.
+
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+use hdf5::types::{FloatSize, IntSize, TypeDescriptor, VarLenUnicode};
+use hdf5::{File, H5Type};
+use tracing::{info, warn};
+
+use super::export::NON_SAMPLE_DATASETS;
+use crate::audio_analysis::{BANDS, BAND_TIMES_DATASET};
+use crate::audio_respiration::{RESP_RATE_DATASET, RESP_TIMES_DATASET};
+use super::{
+    append_to_dataset, read_column, session_path, session_start, summary, upgrade_session, write_summaries,
+    AppendableColumn, H5AudioMetadata, H5Event,
+};
+use crate::config::CompressionConfig;
+
+/// What [`merge`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    /// The remaining session, which started first.
+    pub session: String,
+    /// Number of samples appended from the later session.
+    pub merged_samples: usize,
+    /// Number of samples of the later session dropped as duplicates.
+    pub duplicate_samples: usize,
+    /// Number of media files moved into the directory of the remaining session.
+    pub moved_files: usize,
+}
+
+/// Merges the sessions `first` and `second` in the HDF5 file at `data_path/file_name` (or the
+/// files of their nights, see [`session_path`]) into the one that started first, and deletes the
+/// other (see the [module documentation](self)). Both are first upgraded to the current
+/// [`SCHEMA_VERSION`](super::SCHEMA_VERSION).
+///
+/// # Errors
+///
+/// Returns an error if either session cannot be found or upgraded, if they are the same
+/// session, or if a per-sample dataset doesn't have a value per sample.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::data;
+/// let merged = data::merge("/path/to/data", "sleep_data.h5", "2025-04-28_22-47-31", "2025-04-29_02-13-05")
+///     .expect("Failed to merge sessions");
+/// println!("Merged {} samples into {}", merged.merged_samples, merged.session);
+/// ```
+pub fn merge(data_path: &str, file_name: &str, first: &str, second: &str) -> Result<MergeSummary, Box<dyn Error>> {
+    if first == second {
+        return Err(format!("Cannot merge session {} into itself", first).into());
+    }
+    for session in [first, second] {
+        upgrade_session(data_path, file_name, session)?;
+    }
+    let (target, source) = match session_start(second) < session_start(first) {
+        true => (second, first),
+        false => (first, second),
+    };
+    let target_path = session_path(data_path, file_name, target);
+    let source_path = session_path(data_path, file_name, source);
+    let target_file = File::append(&target_path)?;
+    let source_file = match source_path == target_path {
+        true => target_file.clone(),
+        false => File::append(&source_path)?,
+    };
+    let target_group = target_file.group(target)
+        .map_err(|e| format!("Session {} not found in {}: {}", target, target_path, e))?;
+    let source_group = source_file.group(source)
+        .map_err(|e| format!("Session {} not found in {}: {}", source, source_path, e))?;
+
+    let target_times = target_group.dataset("timestamp")?.read_raw::<u64>()?;
+    let source_times = source_group.dataset("timestamp")?.read_raw::<u64>()?;
+    let first_kept = match target_times.last() {
+        Some(&last_s) => source_times.iter().position(|&t| t > last_s).unwrap_or(source_times.len()),
+        None => 0,
+    };
+    let keep = first_kept..source_times.len();
+    let counts = (target_times.len(), source_times.len());
+    let rewrite = |path: &str| path.replacen(&format!("/{source}/"), &format!("/{target}/"), 1);
+
+    let mut names = target_group.member_names()?;
+    names.extend(source_group.member_names()?);
+    names.sort();
+    names.dedup();
+    for name in names.iter().filter(|name| !NON_SAMPLE_DATASETS.contains(&name.as_str())) {
+        // Skip anything that isn't a dataset, such as the summary groups
+        let Ok(dataset) = target_group.dataset(name).or_else(|_| source_group.dataset(name)) else {
+            continue;
+        };
+        let (into, from) = (&target_group, &source_group);
+        match dataset.dtype()?.to_descriptor()? {
+            TypeDescriptor::Boolean => merge_column(into, from, name, counts, &keep, false, |v| v)?,
+            TypeDescriptor::Unsigned(IntSize::U1) => merge_column(into, from, name, counts, &keep, 0u8, |v| v)?,
+            TypeDescriptor::Unsigned(IntSize::U2) => merge_column(into, from, name, counts, &keep, 0u16, |v| v)?,
+            TypeDescriptor::Unsigned(IntSize::U8) => merge_column(into, from, name, counts, &keep, 0u64, |v| v)?,
+            TypeDescriptor::Float(FloatSize::U4) => merge_column(into, from, name, counts, &keep, f32::NAN, |v| v)?,
+            // Paths of media files, e.g. camera images
+            TypeDescriptor::VarLenUnicode => {
+                merge_column(into, from, name, counts, &keep, VarLenUnicode::default(), |path| {
+                    VarLenUnicode::from_str(&rewrite(path.as_str())).unwrap_or(path)
+                })?
+            }
+            other => warn!("Skipping dataset {} of unsupported type {:?}", name, other),
+        }
+    }
+
+    // Bursts are concatenated; each sample stores where its burst starts
+    let source_bursts = source_group.dataset("piezo_bcg_mv")?.read_raw::<f32>()?;
+    let source_starts = read_column(&source_group, "piezo_bcg_start", source_times.len(), 0u64)?;
+    let first_burst = source_starts.get(keep.start).map_or(source_bursts.len(), |&start| start as usize);
+    let offset = target_group.dataset("piezo_bcg_mv")?.shape()[0] as u64;
+    let starts: Vec<u64> = source_starts[keep.clone()].iter().map(|start| start - first_burst as u64 + offset).collect();
+    append_to_dataset(&target_group, "piezo_bcg_mv", &source_bursts[first_burst..])?;
+    append_to_dataset(&target_group, "piezo_bcg_start", &starts)?;
+
+    // Recordings, levels, and events after the last ones of the earlier session
+    let last_audio_s = target_group.dataset("audio")?.read_raw::<H5AudioMetadata>()?.last().map(|entry| entry.start_time_s);
+    let mut audio = Vec::new();
+    for mut entry in source_group.dataset("audio")?.read_raw::<H5AudioMetadata>()? {
+        if last_audio_s.is_none_or(|last_s| entry.start_time_s > last_s) {
+            entry.path = VarLenUnicode::from_str(&rewrite(entry.path.as_str()))?;
+            audio.push(entry);
+        }
+    }
+    append_to_dataset(&target_group, "audio", &audio)?;
+    let kept = after_last(&target_group, &source_group, "live_audio_rms_t_s")?;
+    append_to_dataset(&target_group, "live_audio_rms_t_s", &select::<u64>(&source_group, "live_audio_rms_t_s", &kept)?)?;
+    append_to_dataset(&target_group, "live_audio_rms_db", &select::<f32>(&source_group, "live_audio_rms_db", &kept)?)?;
+    let kept = after_last(&target_group, &source_group, BAND_TIMES_DATASET)?;
+    append_to_dataset(&target_group, BAND_TIMES_DATASET, &select::<u64>(&source_group, BAND_TIMES_DATASET, &kept)?)?;
+    for band in BANDS {
+        append_to_dataset(&target_group, band.dataset, &select::<f32>(&source_group, band.dataset, &kept)?)?;
+    }
+    let kept = after_last(&target_group, &source_group, RESP_TIMES_DATASET)?;
+    append_to_dataset(&target_group, RESP_TIMES_DATASET, &select::<u64>(&source_group, RESP_TIMES_DATASET, &kept)?)?;
+    append_to_dataset(&target_group, RESP_RATE_DATASET, &select::<f32>(&source_group, RESP_RATE_DATASET, &kept)?)?;
+    let kept = after_last(&target_group, &source_group, "actuator_event_t_s")?;
+    append_to_dataset(&target_group, "actuator_event_t_s", &select::<u64>(&source_group, "actuator_event_t_s", &kept)?)?;
+    append_to_dataset(&target_group, "actuator_event_name", &select::<VarLenUnicode>(&source_group, "actuator_event_name", &kept)?)?;
+    append_to_dataset(&target_group, "actuator_event_on", &select::<bool>(&source_group, "actuator_event_on", &kept)?)?;
+    let last_event_s = target_group.dataset("events")?.read_raw::<H5Event>()?.last().map(|event| event.timestamp_s);
+    let events: Vec<H5Event> = source_group.dataset("events")?.read_raw::<H5Event>()?.into_iter()
+        .filter(|event| last_event_s.is_none_or(|last_s| event.timestamp_s > last_s))
+        .collect();
+    append_to_dataset(&target_group, "events", &events)?;
+
+    let moved_files = move_media(&Path::new(data_path).join(source), &Path::new(data_path).join(target))?;
+    for interval_s in summary::INTERVALS_S {
+        if target_group.group(&summary::group_name(interval_s)).is_ok() {
+            target_group.unlink(&summary::group_name(interval_s))?;
+        }
+    }
+    write_summaries(&target_group)?;
+    source_file.unlink(source)?;
+
+    let merged = MergeSummary {
+        session: target.to_string(),
+        merged_samples: keep.len(),
+        duplicate_samples: keep.start,
+        moved_files,
+    };
+    info!("Merged session {} into {}: {:?}", source, target, merged);
+    Ok(merged)
+}
+
+/// Appends the `keep` values of the per-sample dataset `name` of `source`, mapped with `map`, to
+/// `target`. `counts` are the numbers of samples of `target` and `source`. Sessions without the
+/// dataset get `placeholder` values.
+fn merge_column<T: H5Type + Clone>(
+    target: &hdf5::Group,
+    source: &hdf5::Group,
+    name: &str,
+    counts: (usize, usize),
+    keep: &Range<usize>,
+    placeholder: T,
+    map: impl Fn(T) -> T,
+) -> Result<(), Box<dyn Error>> {
+    let (target_count, source_count) = counts;
+    let values: Vec<T> = read_column(source, name, source_count, placeholder.clone())?
+        .drain(keep.clone())
+        .map(map)
+        .collect();
+    match target.dataset(name) {
+        Ok(dataset) if dataset.shape()[0] != target_count => {
+            return Err(format!("Dataset {} has {} values; expected {} samples", name, dataset.shape()[0], target_count).into());
+        }
+        Ok(_) => {}
+        Err(_) => {
+            AppendableColumn::create(target, name, &CompressionConfig::default())?.pad_to(target_count, placeholder)?;
+        }
+    }
+    Ok(append_to_dataset(target, name, &values)?)
+}
+
+/// Indices of the times of the dataset `name` of `source` after the last time in `target`.
+fn after_last(target: &hdf5::Group, source: &hdf5::Group, name: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+    let last_s = target.dataset(name)?.read_raw::<u64>()?.last().copied();
+    Ok(source.dataset(name)?.read_raw::<u64>()?.iter()
+        .enumerate()
+        .filter(|(_, &t)| last_s.is_none_or(|last_s| t > last_s))
+        .map(|(index, _)| index)
+        .collect())
+}
+
+/// The values at `indices` of the dataset `name` of `group`.
+fn select<T: H5Type + Clone>(group: &hdf5::Group, name: &str, indices: &[usize]) -> Result<Vec<T>, Box<dyn Error>> {
+    let values = group.dataset(name)?.read_raw::<T>()?;
+    Ok(indices.iter().filter_map(|&index| values.get(index).cloned()).collect())
+}
+
+/// Moves the files under `from` to the same relative paths under `to`, except those that would
+/// replace a file, and removes the directories left empty. Returns the number of files moved.
+fn move_media(from: &Path, to: &Path) -> Result<usize, Box<dyn Error>> {
+    if !from.is_dir() {
+        return Ok(0);
+    }
+    fs::create_dir_all(to)?;
+    let mut moved = 0;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let destination = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            moved += move_media(&entry.path(), &destination)?;
+        } else if destination.exists() {
+            warn!("Not moving {}: {} already exists", entry.path().display(), destination.display());
+        } else {
+            fs::rename(entry.path(), &destination)?;
+            moved += 1;
+        }
+    }
+    if fs::read_dir(from)?.next().is_none() {
+        fs::remove_dir(from)?;
+    }
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::bcg::BcgEstimate;
+    use crate::data::{AudioRecording, Event, SessionReader, SleepData, SleepDataLogger};
+    use crate::sensor::BME280_FIELDS;
+    use test_log::test;
+
+    /// Records the samples at `timestamps`, with the temperature `temperature_c` and a burst of
+    /// `burst`, into the session `group_name`.
+    fn record(data_path: &str, group_name: &str, timestamps: &[u64], temperature_c: f32, burst: &[f32]) -> SleepDataLogger {
+        let mut logger = SleepDataLogger::create(data_path, "sleep_data.h5", group_name, CompressionConfig::default())
+            .expect("Failed to create logger");
+        for &(name, field) in BME280_FIELDS {
+            logger.register_field(name, field).unwrap();
+        }
+        for &timestamp_s in timestamps {
+            logger.append(SleepData::builder(timestamp_s)
+                .with_climate(temperature_c, 1013.0, 40.0)
+                .with_piezo_burst(burst.to_vec(), BcgEstimate::default())
+                .build()).unwrap();
+        }
+        logger
+    }
+
+    #[test]
+    fn test_merge_sessions() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let (first, second) = ("2025-04-28_22-00-00", "2025-04-28_23-30-00");
+        drop(record(data_path, first, &[10, 15, 20], 20.0, &[1.0, 2.0]));
+
+        let audio_path = format!("{data_path}/{second}/audio/audio_20.wav");
+        fs::create_dir_all(format!("{data_path}/{second}/audio")).unwrap();
+        fs::write(&audio_path, b"RIFF").unwrap();
+        let mut logger = record(data_path, second, &[], 22.0, &[]);
+        logger.register_probe("mattress").unwrap();
+        // The first sample overlaps the last one of the first session
+        for timestamp_s in [20, 25] {
+            logger.append(SleepData::builder(timestamp_s)
+                .with_climate(22.0, 1013.0, 40.0)
+                .with_probe_temp("mattress", 30.0)
+                .with_piezo_burst(vec![3.0], BcgEstimate::default())
+                .build()).unwrap();
+        }
+        logger.add_audio_entry(AudioRecording { path: audio_path, duration: Duration::from_secs(5), start_time_s: 20 }).unwrap();
+        logger.add_event(&Event { timestamp_s: 21, category: "restart".to_string(), text: "power cut".to_string() }).unwrap();
+        drop(logger);
+
+        // Merged into the session that started first, whatever the order
+        let merged = merge(data_path, "sleep_data.h5", second, first).expect("Failed to merge sessions");
+        assert_eq!(merged, MergeSummary { session: first.to_string(), merged_samples: 1, duplicate_samples: 1, moved_files: 1 });
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", first).expect("Failed to open session");
+        assert_eq!(session.timestamps().unwrap(), vec![10, 15, 20, 25]);
+        assert_eq!(session.samples().unwrap()[3].temperature_c, 22.0);
+        // The probe of the second session has no readings in the first
+        let probe_temps = session.probe_temps("mattress").unwrap();
+        assert!(probe_temps[0].is_nan() && probe_temps[3] == 30.0);
+        assert_eq!(session.piezo_bursts().unwrap()[2..], [vec![1.0, 2.0], vec![3.0]]);
+        let moved = format!("{data_path}/{first}/audio/audio_20.wav");
+        assert_eq!(session.audio_entries().unwrap()[0].path.as_str(), moved);
+        assert!(Path::new(&moved).exists());
+        assert_eq!(session.events().unwrap()[0].text, "power cut");
+        assert_eq!(session.summary(60).unwrap().fields["temperature"][0].max, 22.0);
+        drop(session);
+        assert!(SessionReader::open(data_path, "sleep_data.h5", second).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Downsampled summaries of the per-sample fields.
+//!
+//! Plotting a whole night from the raw samples, one every few seconds, means reading and reducing
+//! thousands of points per field. Each session therefore also keeps the mean, minimum, and
+//! maximum of its numeric fields per minute and per 5 minutes (see [`INTERVALS_S`]), in the
+//! `summary_<interval>s` groups: `t_s` holds the start of each interval, and `<field>_mean`,
+//! `<field>_min`, and `<field>_max` the aggregates of the field's readings in it.
+//!
+//! Intervals are aligned to multiples of their length since UNIX epoch. An interval is written by
+//! the first flush after a sample of a later interval, and the last one when the logger is
+//! dropped. Unavailable (`NAN`, or 0 for the fields where 0 marks an unavailable reading) and
+//! implausible (see [`quality_flags`](super::SleepData::quality_flags)) readings are left out; an
+//! interval without any reading of a field has `NAN` aggregates.
+
+use std::collections::HashMap;
+
+use super::{is_flagged, SleepData, SleepField};
+
+/// Lengths of the summary intervals, in seconds.
+pub const INTERVALS_S: [u64; 2] = [60, 300];
+
+/// Integer fields whose 0 marks an unavailable reading (see [`units`](super::units)).
+const ZERO_UNAVAILABLE: [&str; 7] = [
+    "co2_ppm",
+    "voc_index",
+    "pm1_0_ugm3",
+    "pm2_5_ugm3",
+    "pm10_ugm3",
+    "mmwave_heart_rate_bpm",
+    "mmwave_resp_rate_bpm",
+];
+
+/// Name of the group holding the summaries per interval of `interval_s` seconds.
+pub fn group_name(interval_s: u64) -> String {
+    format!("summary_{interval_s}s")
+}
+
+/// Whether the field `name` is summarized: the numeric fields, except the quality flags.
+pub(crate) fn is_summarized(name: &str, field: &SleepField) -> bool {
+    matches!(field, SleepField::U16(_) | SleepField::F32(_)) && name != "quality_flags"
+}
+
+/// Reading of the summarized field `field`, named `name`, in `sample` (see [`available`]).
+pub(crate) fn reading(sample: &SleepData, name: &str, field: &SleepField) -> f32 {
+    let value = match field {
+        SleepField::U16(f) => f32::from(f(sample)),
+        SleepField::F32(f) => f(sample),
+        _ => f32::NAN,
+    };
+    available(name, value, sample.quality_flags)
+}
+
+/// `value` of the field `name`, or `NAN` if it is unavailable or flagged as implausible in
+/// `quality_flags`.
+pub(crate) fn available(name: &str, value: f32, quality_flags: u16) -> f32 {
+    if is_flagged(name, quality_flags) || (value == 0.0 && ZERO_UNAVAILABLE.contains(&name)) {
+        f32::NAN
+    } else {
+        value
+    }
+}
+
+/// Mean, minimum, and maximum of a field's readings in an interval; `NAN` if there are none.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aggregate {
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// Running aggregate of a field's readings.
+#[derive(Clone, Copy, Debug)]
+struct Accumulator {
+    count: u32,
+    sum: f64,
+    min: f32,
+    max: f32,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self { count: 0, sum: 0.0, min: f32::INFINITY, max: f32::NEG_INFINITY }
+    }
+}
+
+impl Accumulator {
+    fn add(&mut self, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+        self.count += 1;
+        self.sum += f64::from(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn aggregate(&self) -> Aggregate {
+        match self.count {
+            0 => Aggregate { mean: f32::NAN, min: f32::NAN, max: f32::NAN },
+            count => Aggregate { mean: (self.sum / f64::from(count)) as f32, min: self.min, max: self.max },
+        }
+    }
+}
+
+/// Aggregates of the summarized fields in one interval.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Interval {
+    /// Start of the interval, in seconds since UNIX epoch.
+    pub start_s: u64,
+    /// Aggregate of each field, in the order of [`Summarizer::fields`].
+    pub aggregates: Vec<Aggregate>,
+}
+
+/// Summarizes the readings of a session's fields per interval of a given length, sample by
+/// sample.
+#[derive(Debug)]
+pub(crate) struct Summarizer {
+    interval_s: u64,
+    /// Names of the summarized fields, in the order of their readings.
+    fields: Vec<&'static str>,
+    /// Start of the interval being aggregated, and the accumulators of its fields.
+    current: Option<(u64, Vec<Accumulator>)>,
+    /// Start of the last interval written; samples up to its end are skipped, e.g. when resuming
+    /// a session.
+    written_s: Option<u64>,
+}
+
+impl Summarizer {
+    /// Creates a summarizer of intervals of `interval_s` seconds, skipping the samples of the
+    /// intervals up to the one starting at `written_s`.
+    pub(crate) fn new(interval_s: u64, written_s: Option<u64>) -> Self {
+        Self { interval_s, fields: Vec::new(), current: None, written_s }
+    }
+
+    /// Length of the intervals, in seconds.
+    pub(crate) fn interval_s(&self) -> u64 {
+        self.interval_s
+    }
+
+    /// Names of the summarized fields, in the order of their readings and aggregates.
+    pub(crate) fn fields(&self) -> &[&'static str] {
+        &self.fields
+    }
+
+    /// Adds the field `name`, without readings in the interval being aggregated.
+    pub(crate) fn add_field(&mut self, name: &'static str) {
+        self.fields.push(name);
+        if let Some((_, accumulators)) = &mut self.current {
+            accumulators.push(Accumulator::default());
+        }
+    }
+
+    /// Adds the `readings` of the fields in the sample at `timestamp_s`, and returns the interval
+    /// it completes, if any. Samples older than the interval being aggregated are skipped.
+    pub(crate) fn add(&mut self, timestamp_s: u64, readings: &[f32]) -> Option<Interval> {
+        let start_s = timestamp_s - timestamp_s % self.interval_s;
+        if self.written_s.is_some_and(|written_s| start_s <= written_s)
+            || self.current.as_ref().is_some_and(|(current_s, _)| start_s < *current_s)
+        {
+            return None;
+        }
+        let completed = match &self.current {
+            Some((current_s, _)) if *current_s != start_s => self.finish(),
+            _ => None,
+        };
+        let fields = self.fields.len();
+        let (_, accumulators) = self.current.get_or_insert_with(|| (start_s, vec![Accumulator::default(); fields]));
+        for (accumulator, &value) in accumulators.iter_mut().zip(readings) {
+            accumulator.add(value);
+        }
+        completed
+    }
+
+    /// Ends the interval being aggregated, and returns it if there is one.
+    pub(crate) fn finish(&mut self) -> Option<Interval> {
+        let (start_s, accumulators) = self.current.take()?;
+        self.written_s = Some(start_s);
+        Some(Interval { start_s, aggregates: accumulators.iter().map(Accumulator::aggregate).collect() })
+    }
+}
+
+/// Summaries of a session's fields per interval, read with
+/// [`SessionReader::summary`](super::SessionReader::summary).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    /// Start of each interval, in seconds since UNIX epoch.
+    pub start_s: Vec<u64>,
+    /// Aggregates of each summarized field per interval, by field name.
+    pub fields: HashMap<String, Vec<Aggregate>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_summarizer() {
+        let mut summarizer = Summarizer::new(60, None);
+        summarizer.add_field("temperature");
+        assert_eq!(summarizer.add(120, &[20.0]), None);
+        assert_eq!(summarizer.add(150, &[f32::NAN]), None);
+        assert_eq!(summarizer.add(179, &[24.0]), None);
+        // Older samples are skipped
+        assert_eq!(summarizer.add(100, &[50.0]), None);
+        summarizer.add_field("humidity");
+
+        let completed = summarizer.add(180, &[30.0, 40.0]).expect("Expected a completed interval");
+        assert_eq!(completed.start_s, 120);
+        assert_eq!(completed.aggregates[0], Aggregate { mean: 22.0, min: 20.0, max: 24.0 });
+        assert!(completed.aggregates[1].mean.is_nan());
+
+        let last = summarizer.finish().expect("Expected the last interval");
+        assert_eq!(last.aggregates, vec![Aggregate { mean: 30.0, min: 30.0, max: 30.0 }, Aggregate { mean: 40.0, min: 40.0, max: 40.0 }]);
+        assert_eq!(summarizer.finish(), None);
+        // Samples of written intervals are skipped, e.g. after resuming
+        assert_eq!(summarizer.add(200, &[0.0, 0.0]), None);
+        assert_eq!(summarizer.finish(), None);
+    }
+
+    #[test]
+    fn test_available() {
+        assert_eq!(available("humidity", 40.0, 0), 40.0);
+        // Humidity is bit 2 of the quality flags
+        assert!(available("humidity", 150.0, 0b100).is_nan());
+        assert!(available("co2_ppm", 0.0, 0).is_nan());
+        assert_eq!(available("tvoc_ppb", 0.0, 0), 0.0);
+    }
+}
@@ -0,0 +1,160 @@
+//! Units and descriptions of the datasets of a session.
+//!
+//! Every dataset is created with `units`, `description`, and `sensor` string attributes, so that
+//! downstream tools don't have to guess what e.g. `pressure` or `thermistor_temp` mean.
+//! Dimensionless datasets (flags, indices, paths) have empty units.
+
+use std::error::Error;
+use std::str::FromStr;
+
+use hdf5::types::VarLenUnicode;
+use hdf5::Dataset;
+
+/// What a dataset holds, stored in its attributes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DatasetInfo {
+    /// Units of the values, e.g. "degC" or "ppm". Empty for dimensionless values.
+    pub units: &'static str,
+    /// What the values are.
+    pub description: &'static str,
+    /// The sensor measuring the values, e.g. "BME280". Empty if there is none, or it is named
+    /// in the dataset name (e.g. `probe_temp_<name>`).
+    pub sensor: &'static str,
+}
+
+const fn info(units: &'static str, description: &'static str, sensor: &'static str) -> Option<DatasetInfo> {
+    Some(DatasetInfo { units, description, sensor })
+}
+
+/// Units, description, and sensor of the dataset `name`, or `None` for datasets this version
+/// doesn't know.
+pub fn dataset_info(name: &str) -> Option<DatasetInfo> {
+    match name {
+        "timestamp" => info("s", "Time of the sample since UNIX epoch", ""),
+        "quality_flags" => info("", "Readings outside their plausible range, one bit per field of PLAUSIBLE_RANGES", ""),
+        "temperature" => info("degC", "Ambient temperature", "BME280"),
+        "pressure" => info("hPa", "Ambient air pressure", "BME280"),
+        "humidity" => info("%RH", "Ambient relative humidity", "BME280"),
+        "dew_point" => info("degC", "Dew point derived from the temperature and humidity", "BME280"),
+        "absolute_humidity" => info("g/m3", "Absolute humidity derived from the temperature and humidity", "BME280"),
+        "comfort_index" => info("", "Sleep comfort (0-100, 100 at 16-20 degC and 40-60 %RH) derived from the temperature and humidity", "BME280"),
+        "co2eq_ppm" => info("ppm", "Equivalent CO2 (eCO2) estimated from VOCs", "ENS160"),
+        "co2_ppm" => info("ppm", "CO2 concentration measured by NDIR; 0 when unavailable", "SCD4x"),
+        "tvoc_ppb" => info("ppb", "Total volatile organic compounds", "ENS160"),
+        "voc_index" => info("", "VOC index (1-500, 100 is the recent average); 0 when unavailable", "SGP40"),
+        "air_quality_index" => info("", "Air quality index (UBA, 1-5)", "ENS160"),
+        "pm1_0_ugm3" => info("ug/m3", "PM1.0 particulate matter; 0 when unavailable", "PMS5003"),
+        "pm2_5_ugm3" => info("ug/m3", "PM2.5 particulate matter; 0 when unavailable", "PMS5003"),
+        "pm10_ugm3" => info("ug/m3", "PM10 particulate matter; 0 when unavailable", "PMS5003"),
+        "thermistor_temp" => info("degC", "Thermistor temperature", "Thermistor (MCP3424)"),
+        "light_lux" => info("lux", "Ambient light", "BH1750"),
+        "image_path" => info("", "Path of the captured image; empty when none was captured", "Camera"),
+        "image_motion" => info("", "Mean absolute pixel difference to the previous image without overlay (0-255), computed offline", "Camera"),
+        "image_motion_live" => info("", "Mean absolute pixel difference to the previous image (0-255), computed live", "Camera"),
+        "clip_path" => info("", "Path of the motion-triggered video clip; empty when none was recorded", "Camera"),
+        "raw_image_path" => info("", "Path of the image saved without overlay; empty when not saved", "Camera"),
+        "thumbnail" => info("", "JPEG thumbnail of the image; empty when none was stored", "Camera"),
+        "bed_weight_kg" => info("kg", "Weight on the bed", "HX711"),
+        "bed_occupied" => info("", "Whether the bed weight is above the occupancy threshold", "HX711"),
+        "piezo_heart_rate_bpm" => info("bpm", "Heart rate estimated from the piezo burst", "Piezo (MCP3424)"),
+        "piezo_resp_rate_bpm" => info("breaths/min", "Respiration rate estimated from the piezo burst", "Piezo (MCP3424)"),
+        "piezo_bcg_mv" => info("mV", "Piezo BCG bursts of all samples, concatenated", "Piezo (MCP3424)"),
+        "piezo_bcg_start" => info("", "Index of the first value of each sample's burst in piezo_bcg_mv", "Piezo (MCP3424)"),
+        "pir_motion" => info("", "Motion detected since the previous sample", "PIR"),
+        "mmwave_presence" => info("", "Human presence", "C1001 mmWave"),
+        "mmwave_movement" => info("", "Movement", "C1001 mmWave"),
+        "mmwave_heart_rate_bpm" => info("bpm", "Heart rate; 0 when unavailable", "C1001 mmWave"),
+        "mmwave_resp_rate_bpm" => info("breaths/min", "Respiration rate; 0 when unavailable", "C1001 mmWave"),
+        "cpu_temp" => info("degC", "CPU temperature of the host", ""),
+        "load_avg_1m" => info("", "1-minute load average of the host", ""),
+        "disk_free_mb" => info("MiB", "Free space on the data volume; 0 when unavailable", ""),
+        "mem_used_percent" => info("%", "Host memory in use", ""),
+        "audio" => info("", "Audio recordings: start time and duration (s), path, and RMS levels (dBFS, weighted as the session's audio_weighting) with their times (s)", "Microphone"),
+        "live_audio_rms_db" => info("dBFS", "Audio RMS level measured live during recording, weighted as the session's audio_weighting", "Microphone"),
+        "live_audio_rms_t_s" => info("s", "Start of each live audio level window since UNIX epoch", "Microphone"),
+        "audio_band_t_s" => info("s", "Start of each audio band level window since UNIX epoch", "Microphone"),
+        "audio_band_low_db" => info("dBFS", "Audio level in the 20-250 Hz band (rumble, e.g. HVAC hum)", "Microphone"),
+        "audio_band_speech_db" => info("dBFS", "Audio level in the 250-4000 Hz band (voices, snoring)", "Microphone"),
+        "audio_band_high_db" => info("dBFS", "Audio level in the 4-16 kHz band (breathing, rustling)", "Microphone"),
+        "audio_resp_t_s" => info("s", "Start of each audio respiration window since UNIX epoch", "Microphone"),
+        "audio_resp_rate_bpm" => info("breaths/min", "Respiration rate estimated from the breathing heard in quiet audio; NaN when unavailable", "Microphone"),
+        "actuator_event_t_s" => info("s", "Time of the actuator state change since UNIX epoch", ""),
+        "actuator_event_name" => info("", "Name of the switched actuator", ""),
+        "actuator_event_on" => info("", "Whether the actuator was switched on", ""),
+        "events" => info("", "Annotations of the session: time (s), category, and text", ""),
+        _ if name.starts_with("summary_") => summary_dataset_info(name),
+        _ => named_dataset_info(name),
+    }
+}
+
+/// Info of the datasets of the summary groups (see [`summary`](super::summary)), named by their
+/// path in the session group, e.g. `summary_60s/temperature_mean`. The aggregates keep the units
+/// and sensor of their field.
+fn summary_dataset_info(name: &str) -> Option<DatasetInfo> {
+    let (_, dataset) = name.split_once('/')?;
+    if dataset == "t_s" {
+        return info("s", "Start of each summary interval since UNIX epoch", "");
+    }
+    let aggregates = [
+        ("_mean", "Mean of the field's readings in each summary interval"),
+        ("_min", "Minimum of the field's readings in each summary interval"),
+        ("_max", "Maximum of the field's readings in each summary interval"),
+    ];
+    aggregates.into_iter().find_map(|(suffix, description)| {
+        let field = dataset_info(dataset.strip_suffix(suffix)?)?;
+        Some(DatasetInfo { description, ..field })
+    })
+}
+
+/// Info of the per-camera, per-probe, per-thermistor, and per-sensor datasets.
+fn named_dataset_info(name: &str) -> Option<DatasetInfo> {
+    let prefixes = [
+        ("image_path_", info("", "Path of the image captured by the named camera; empty when none was captured", "Camera")),
+        ("image_motion_live_", info("", "Mean absolute pixel difference to the previous image of the named camera (0-255), computed live", "Camera")),
+        ("clip_path_", info("", "Path of the named camera's motion-triggered video clip; empty when none was recorded", "Camera")),
+        ("raw_image_path_", info("", "Path of the named camera's image saved without overlay; empty when not saved", "Camera")),
+        ("thumbnail_", info("", "JPEG thumbnail of the named camera's image; empty when none was stored", "Camera")),
+        ("probe_temp_", info("degC", "Temperature of the named probe", "DS18B20")),
+        ("thermistor_temp_", info("degC", "Temperature of the named thermistor", "Thermistor (MCP3424)")),
+        ("sensor_ok_", info("", "Whether the named sensor was ready and measured successfully", "")),
+        ("sensor_state_", info("", "Readiness of the named sensor (0: initializing, 1: ready, 2: failed)", "")),
+        ("sensor_error_age_s_", info("s", "Time since the named sensor last failed; NaN if it hasn't failed", "")),
+        ("sensor_error_", info("", "Error of the named sensor's measurement; empty if it succeeded", "")),
+    ];
+    prefixes.into_iter().find(|(prefix, _)| name.starts_with(prefix)).and_then(|(_, info)| info)
+}
+
+/// Writes the `units`, `description`, and `sensor` attributes of `dataset`, named `name`, if it
+/// is known (see [`dataset_info`]) and doesn't have them yet.
+pub(crate) fn write_dataset_info(dataset: &Dataset, name: &str) -> Result<(), Box<dyn Error>> {
+    let Some(info) = dataset_info(name) else {
+        return Ok(());
+    };
+    if dataset.attr("units").is_ok() {
+        return Ok(());
+    }
+    for (attribute, value) in [("units", info.units), ("description", info.description), ("sensor", info.sensor)] {
+        dataset.new_attr::<VarLenUnicode>().create(attribute)?.write_scalar(&VarLenUnicode::from_str(value)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_named_dataset_info() {
+        assert_eq!(dataset_info("temperature").unwrap().units, "degC");
+        assert_eq!(dataset_info("probe_temp_mattress").unwrap().sensor, "DS18B20");
+        // Checked before the shorter sensor_error_ prefix
+        assert_eq!(dataset_info("sensor_error_age_s_bme280").unwrap().units, "s");
+        assert_eq!(dataset_info("sensor_error_bme280").unwrap().units, "");
+        assert_eq!(dataset_info("raw_image_path_crib").unwrap().sensor, "Camera");
+        assert!(dataset_info("image_motion_live_crib").unwrap().description.contains("named camera"));
+        assert!(dataset_info("unknown").is_none());
+        let summary = dataset_info("summary_60s/image_motion_max").unwrap();
+        assert_eq!((summary.sensor, summary.description), ("Camera", "Maximum of the field's readings in each summary interval"));
+    }
+}
@@ -0,0 +1,538 @@
+//! At-rest encryption of the recorded media, configured with [`Config::encryption`].
+//!
+//! With `encryption.key_file` set, [`storage::open`](crate::storage::open) wraps the storage
+//! backend in an [`EncryptingBackend`], which encrypts the images, raw images, motion clips, and
+//! audio recordings of each sample as it is stored, and deletes the plaintext files. Encrypted
+//! files get an additional `.enc` extension (e.g. `image_1745873251.jpg.enc`), and the data file
+//! references them under that name. Thumbnails are stored encrypted in the data file. The sensor
+//! values are not encrypted.
+//!
+//! Files are plaintext while they are written: an image for an instant, an audio recording until
+//! it is finished and encrypted. Audio recordings are encrypted on a thread of their own, and
+//! stored once they are. Keep the key off the SD card (e.g. on a USB stick, or copied to a tmpfs
+//! at boot), or it protects nothing.
+//!
+//! Data is encrypted with AES-256-GCM. Thumbnails (see [`encrypt`]) are [`MAGIC`], a fresh random
+//! 12-byte nonce, and the ciphertext with its 16-byte tag. Files are encrypted in chunks of 64 KiB
+//! with the STREAM construction, so that they are never held in memory whole: an encrypted file is
+//! [`STREAM_MAGIC`], a random 7-byte nonce prefix, and the ciphertext of each chunk with its tag.
+//! The nonce of each chunk holds its index and whether it is the last, so that chunks cannot be
+//! reordered, and a truncated file fails to decrypt. Files encrypted whole, as they were before,
+//! are still read. The analysis passes decrypt files in memory (see
+//! [`Pipeline::with_key`](crate::analysis::Pipeline::with_key)), and [`read`] and
+//! [`decrypt_file`] give access to them otherwise.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32};
+use aes_gcm::aead::{Aead, AeadCore, OsRng};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use tracing::warn;
+
+use crate::audio_analysis::BANDS;
+use crate::data::{ActuatorEvent, AudioRecording, Event, SessionMetadata, SleepData, SleepField, Thumbnail};
+use crate::storage::StorageBackend;
+
+/// Start of encrypted data, followed by the nonce and the ciphertext.
+pub const MAGIC: &[u8; 8] = b"SLPENC01";
+
+/// Start of an encrypted file, followed by the nonce prefix and the encrypted chunks.
+pub const STREAM_MAGIC: &[u8; 8] = b"SLPENC02";
+
+/// Extension added to the name of encrypted files.
+pub const EXTENSION: &str = "enc";
+
+const NONCE_LEN: usize = 12;
+
+/// Length of the nonce prefix of an encrypted file: the nonce without the 32-bit chunk counter
+/// and the last chunk flag.
+const NONCE_PREFIX_LEN: usize = 7;
+
+const TAG_LEN: usize = 16;
+
+/// Plaintext bytes per chunk of an encrypted file.
+const CHUNK_LEN: usize = 64 * 1024;
+
+const DECRYPTION_FAILED: &str = "Failed to decrypt data: wrong key, or the data is corrupt";
+
+/// 256-bit key encrypting the media.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key([u8; 32]);
+
+impl Key {
+    /// Creates a key from its bytes.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parses a key written as 64 hexadecimal digits, e.g. by
+    /// `head -c 32 /dev/urandom | xxd -p -c 32`. Surrounding whitespace is ignored.
+    pub fn from_hex(hex: &str) -> Result<Self, Box<dyn Error>> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err("An encryption key must be 64 hexadecimal digits".into());
+        }
+        let mut bytes = [0u8; 32];
+        for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(digits)?, 16)
+                .map_err(|_| "An encryption key must be 64 hexadecimal digits")?;
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Reads a key file holding the key as hexadecimal digits (see [`Key::from_hex`]).
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let hex = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read key file {}: {}", path.display(), e))?;
+        Self::from_hex(&hex).map_err(|e| format!("Invalid key file {}: {}", path.display(), e).into())
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.0.into())
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// Whether `data` is encrypted, i.e. starts with [`MAGIC`] or [`STREAM_MAGIC`].
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC) || data.starts_with(STREAM_MAGIC)
+}
+
+/// Whether the file at `path` is encrypted, judging by its extension.
+pub fn is_encrypted_path(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| extension == EXTENSION)
+}
+
+/// Encrypts `plaintext` with `key`.
+pub fn encrypt(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = key.cipher().encrypt(&nonce, plaintext).map_err(|_| "Failed to encrypt data")?;
+    let mut data = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// Decrypts `data` encrypted by [`encrypt`].
+///
+/// # Errors
+///
+/// Returns an error if `data` isn't encrypted, or was encrypted with another key or modified.
+pub fn decrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let payload = data.strip_prefix(MAGIC).ok_or("Data is not encrypted")?;
+    if payload.len() < NONCE_LEN {
+        return Err("Encrypted data is truncated".into());
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    key.cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| DECRYPTION_FAILED.into())
+}
+
+/// Encrypts what `reader` yields into `writer`, chunk by chunk (see the
+/// [module documentation](self)).
+fn encrypt_stream(key: &Key, reader: &mut impl Read, writer: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+    OsRng.fill_bytes(&mut nonce_prefix);
+    writer.write_all(STREAM_MAGIC)?;
+    writer.write_all(&nonce_prefix)?;
+    let mut encryptor = EncryptorBE32::from_aead(key.cipher(), (&nonce_prefix).into());
+    // Read a chunk ahead to know which chunk is the last
+    let mut chunk = read_chunk(reader, CHUNK_LEN)?;
+    loop {
+        let next = read_chunk(reader, CHUNK_LEN)?;
+        if next.is_empty() {
+            writer.write_all(&encryptor.encrypt_last(chunk.as_slice()).map_err(|_| "Failed to encrypt data")?)?;
+            return Ok(());
+        }
+        writer.write_all(&encryptor.encrypt_next(chunk.as_slice()).map_err(|_| "Failed to encrypt data")?)?;
+        chunk = next;
+    }
+}
+
+/// Decrypts what `reader` yields, encrypted by [`encrypt_stream`] or [`encrypt`], into `writer`.
+/// Chunks are only written once they are authenticated.
+fn decrypt_stream(key: &Key, reader: &mut impl Read, writer: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let mut magic = [0u8; STREAM_MAGIC.len()];
+    reader.read_exact(&mut magic).map_err(|_| "Data is not encrypted")?;
+    if &magic == MAGIC {
+        // Encrypted whole
+        let mut data = magic.to_vec();
+        reader.read_to_end(&mut data)?;
+        writer.write_all(&decrypt(key, &data)?)?;
+        return Ok(());
+    }
+    if &magic != STREAM_MAGIC {
+        return Err("Data is not encrypted".into());
+    }
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+    reader.read_exact(&mut nonce_prefix).map_err(|_| "Encrypted data is truncated")?;
+    let mut decryptor = DecryptorBE32::from_aead(key.cipher(), (&nonce_prefix).into());
+    let mut chunk = read_chunk(reader, CHUNK_LEN + TAG_LEN)?;
+    loop {
+        let next = read_chunk(reader, CHUNK_LEN + TAG_LEN)?;
+        if next.is_empty() {
+            writer.write_all(&decryptor.decrypt_last(chunk.as_slice()).map_err(|_| DECRYPTION_FAILED)?)?;
+            return Ok(());
+        }
+        writer.write_all(&decryptor.decrypt_next(chunk.as_slice()).map_err(|_| DECRYPTION_FAILED)?)?;
+        chunk = next;
+    }
+}
+
+/// Reads up to `len` bytes, fewer only at the end of `reader`.
+fn read_chunk(reader: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(len);
+    reader.by_ref().take(len as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+/// Encrypts the file at `path` into `<path>.enc` chunk by chunk, and deletes the plaintext file.
+/// Returns the path of the encrypted file.
+pub fn encrypt_file(key: &Key, path: &str) -> Result<String, Box<dyn Error>> {
+    let mut plaintext = File::open(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let encrypted_path = format!("{}.{}", path, EXTENSION);
+    // Write next to the target and rename, so an interruption can't leave a truncated file
+    let temporary = format!("{}.tmp", encrypted_path);
+    let mut output = BufWriter::new(File::create(&temporary)?);
+    encrypt_stream(key, &mut plaintext, &mut output)?;
+    output.flush()?;
+    drop(output);
+    fs::rename(&temporary, &encrypted_path)?;
+    fs::remove_file(path)?;
+    Ok(encrypted_path)
+}
+
+/// Reads the file at `path`, decrypting it with `key` if it is encrypted (see
+/// [`is_encrypted_path`]).
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, or is encrypted and `key` is `None` or cannot
+/// decrypt it.
+pub fn read(path: &str, key: Option<&Key>) -> Result<Vec<u8>, Box<dyn Error>> {
+    if !is_encrypted_path(path) {
+        return Ok(fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?);
+    }
+    let key = key.ok_or_else(|| format!("{} is encrypted; an encryption key is needed to read it", path))?;
+    let mut file = File::open(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut data = Vec::new();
+    decrypt_stream(key, &mut file, &mut data).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(data)
+}
+
+/// Decrypts the encrypted file at `path` chunk by chunk into a file without the `.enc`
+/// extension, which is returned. The encrypted file is kept; the decrypted one is deleted again
+/// if decryption fails.
+pub fn decrypt_file(key: &Key, path: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if !is_encrypted_path(path) {
+        return Err(format!("{} is not an encrypted file", path).into());
+    }
+    let output = Path::new(path).with_extension("");
+    let mut file = File::open(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut writer = BufWriter::new(File::create(&output)?);
+    let result = decrypt_stream(key, &mut file, &mut writer).and_then(|()| Ok(writer.flush()?));
+    if let Err(e) = result {
+        drop(writer);
+        fs::remove_file(&output)?;
+        return Err(format!("{}: {}", path, e).into());
+    }
+    Ok(output)
+}
+
+/// Encrypts the file at `path`, if there is one, and updates `path` to the encrypted file.
+/// Files that cannot be encrypted are logged, and left as they are.
+fn encrypt_path(key: &Key, path: &mut String) {
+    if path.is_empty() || is_encrypted_path(path) {
+        return;
+    }
+    match encrypt_file(key, path) {
+        Ok(encrypted_path) => *path = encrypted_path,
+        Err(e) => warn!("Failed to encrypt {}; it is stored unencrypted: {}", path, e),
+    }
+}
+
+/// Thread encrypting audio recordings, which takes seconds on a Raspberry Pi, so that the storage
+/// writer thread keeps storing samples meanwhile.
+struct AudioEncryption {
+    recordings: mpsc::Sender<AudioRecording>,
+    encrypted: mpsc::Receiver<AudioRecording>,
+    thread: JoinHandle<()>,
+}
+
+impl AudioEncryption {
+    fn spawn(key: Key) -> Result<Self, Box<dyn Error>> {
+        let (recordings, queued) = mpsc::channel::<AudioRecording>();
+        let (done, encrypted) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("audio-encryption".to_string())
+            .spawn(move || {
+                for mut recording in queued {
+                    encrypt_path(&key, &mut recording.path);
+                    if done.send(recording).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        Ok(Self { recordings, encrypted, thread })
+    }
+}
+
+/// [`StorageBackend`] encrypting the media of what is written to it, before passing it on to the
+/// inner backend (see the [module documentation](self)).
+///
+/// Files that cannot be encrypted are logged, and stored unencrypted under their original path.
+/// Audio recordings are encrypted in the background, and passed on to the inner backend with the
+/// next sample, audio recording, or flush once they are, and when the backend is dropped.
+pub struct EncryptingBackend {
+    inner: Box<dyn StorageBackend>,
+    key: Key,
+    /// Started with the first audio recording.
+    audio: Option<AudioEncryption>,
+}
+
+impl EncryptingBackend {
+    /// Wraps `inner`, encrypting media with `key`.
+    pub fn new(inner: Box<dyn StorageBackend>, key: Key) -> Self {
+        Self { inner, key, audio: None }
+    }
+
+    fn encrypt_path(&self, path: &mut String) {
+        encrypt_path(&self.key, path);
+    }
+
+    /// Passes the audio recordings encrypted so far on to the inner backend.
+    fn store_encrypted_audio(&mut self) {
+        let Some(audio) = &self.audio else {
+            return;
+        };
+        for recording in audio.encrypted.try_iter() {
+            if let Err(e) = self.inner.add_audio_entry(recording) {
+                warn!("Failed to store encrypted audio recording: {}", e);
+            }
+        }
+    }
+
+    fn encrypt_thumbnail(&self, thumbnail: &mut Thumbnail) {
+        if thumbnail.is_empty() || is_encrypted(&thumbnail.0) {
+            return;
+        }
+        match encrypt(&self.key, &thumbnail.0) {
+            Ok(encrypted) => thumbnail.0 = encrypted,
+            Err(e) => warn!("Failed to encrypt thumbnail; it is stored unencrypted: {}", e),
+        }
+    }
+}
+
+impl StorageBackend for EncryptingBackend {
+    fn session_name(&self) -> &str {
+        self.inner.session_name()
+    }
+
+    fn is_resumed(&self) -> bool {
+        self.inner.is_resumed()
+    }
+
+    fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+        self.inner.write_metadata(metadata)
+    }
+
+    fn register_field(&mut self, name: &'static str, field: SleepField) -> Result<(), Box<dyn Error>> {
+        self.inner.register_field(name, field)
+    }
+
+    fn register_camera(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.inner.register_camera(name)
+    }
+
+    fn register_probe(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.inner.register_probe(name)
+    }
+
+    fn register_thermistor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.inner.register_thermistor(name)
+    }
+
+    fn register_sensor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.inner.register_sensor(name)
+    }
+
+    fn append(&mut self, mut sample: SleepData) -> Result<(), Box<dyn Error>> {
+        self.store_encrypted_audio();
+        for path in [&mut sample.image_path, &mut sample.raw_image_path, &mut sample.clip_path] {
+            self.encrypt_path(path);
+        }
+        self.encrypt_thumbnail(&mut sample.thumbnail);
+        for result in sample.extra_cameras.values_mut() {
+            self.encrypt_path(&mut result.image_path);
+            for path in [&mut result.raw_image_path, &mut result.clip_path].into_iter().flatten() {
+                self.encrypt_path(path);
+            }
+            if let Some(thumbnail) = &mut result.thumbnail {
+                self.encrypt_thumbnail(thumbnail);
+            }
+        }
+        self.inner.append(sample)
+    }
+
+    fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        self.store_encrypted_audio();
+        if self.audio.is_none() {
+            self.audio = Some(AudioEncryption::spawn(self.key.clone())?);
+        }
+        if let Some(audio) = &self.audio {
+            audio.recordings.send(audio_recording).map_err(|_| "The audio encryption thread has stopped")?;
+        }
+        Ok(())
+    }
+
+    fn append_audio_rms(&mut self, start_time_s: u64, rms_t_s: &[u64], rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+        self.inner.append_audio_rms(start_time_s, rms_t_s, rms_db)
+    }
+
+    fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        self.inner.add_actuator_event(event)
+    }
+
+    fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.inner.add_event(event)
+    }
+
+    fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32) {
+        self.inner.append_audio_level(timestamp_s, rms_db)
+    }
+
+    fn append_audio_bands(&mut self, timestamp_s: u64, levels_db: [f32; BANDS.len()]) {
+        self.inner.append_audio_bands(timestamp_s, levels_db)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.store_encrypted_audio();
+        self.inner.flush()
+    }
+}
+
+impl Drop for EncryptingBackend {
+    fn drop(&mut self) {
+        // Wait for the recordings still being encrypted, and store them before the inner backend
+        // is closed
+        let Some(AudioEncryption { recordings, encrypted, thread }) = self.audio.take() else {
+            return;
+        };
+        drop(recordings);
+        if thread.join().is_err() {
+            warn!("The audio encryption thread panicked");
+        }
+        for recording in encrypted.try_iter() {
+            if let Err(e) = self.inner.add_audio_entry(recording) {
+                warn!("Failed to store encrypted audio recording: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::data::{SessionReader, SleepDataLogger};
+    use test_log::test;
+
+    #[test]
+    fn test_encrypt_file_round_trip() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let key = Key::from_hex(&"0f".repeat(32)).expect("Failed to parse key");
+        assert!(Key::from_hex("0f0f").is_err());
+
+        let path = dir.path().join("image_1.jpg").to_str().unwrap().to_string();
+        fs::write(&path, b"not really a jpeg").unwrap();
+        let encrypted_path = encrypt_file(&key, &path).expect("Failed to encrypt file");
+        assert_eq!(encrypted_path, format!("{}.enc", path));
+        assert!(!Path::new(&path).exists());
+        let data = fs::read(&encrypted_path).unwrap();
+        assert!(is_encrypted(&data));
+        assert!(!data.windows(6).any(|w| w == b"really"));
+
+        assert_eq!(read(&encrypted_path, Some(&key)).unwrap(), b"not really a jpeg");
+        assert!(read(&encrypted_path, None).is_err());
+        assert!(read(&encrypted_path, Some(&Key::new([1; 32]))).is_err());
+        assert_eq!(decrypt_file(&key, &encrypted_path).unwrap(), Path::new(&path));
+        assert_eq!(fs::read(&path).unwrap(), b"not really a jpeg");
+
+        // Tampered data is rejected
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        fs::write(&encrypted_path, &tampered).unwrap();
+        assert!(read(&encrypted_path, Some(&key)).is_err());
+        let thumbnail = encrypt(&key, b"thumbnail").unwrap();
+        assert_eq!(decrypt(&key, &thumbnail).unwrap(), b"thumbnail");
+        assert!(decrypt(&key, &thumbnail[..thumbnail.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_file_in_chunks() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let key = Key::new([7; 32]);
+        // Two full chunks and a partial one
+        let plaintext: Vec<u8> = (0..2 * CHUNK_LEN + 1000).map(|i| (i % 251) as u8).collect();
+        let path = dir.path().join("audio_1.wav").to_str().unwrap().to_string();
+        fs::write(&path, &plaintext).unwrap();
+        let encrypted_path = encrypt_file(&key, &path).expect("Failed to encrypt file");
+        let data = fs::read(&encrypted_path).unwrap();
+        assert!(data.starts_with(STREAM_MAGIC));
+        assert_eq!(data.len(), STREAM_MAGIC.len() + NONCE_PREFIX_LEN + plaintext.len() + 3 * TAG_LEN);
+        assert_eq!(read(&encrypted_path, Some(&key)).unwrap(), plaintext);
+
+        // Dropping the last chunk is detected, and leaves no partially decrypted file behind
+        fs::write(&encrypted_path, &data[..data.len() - 1000 - TAG_LEN]).unwrap();
+        assert!(read(&encrypted_path, Some(&key)).is_err());
+        assert!(decrypt_file(&key, &encrypted_path).is_err());
+        assert!(!Path::new(&path).exists());
+
+        // Empty files, and files encrypted whole before, are read
+        fs::write(&path, b"").unwrap();
+        let encrypted_path = encrypt_file(&key, &path).unwrap();
+        assert_eq!(read(&encrypted_path, Some(&key)).unwrap(), b"");
+        fs::write(&encrypted_path, encrypt(&key, &plaintext).unwrap()).unwrap();
+        assert_eq!(read(&encrypted_path, Some(&key)).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_audio_encrypted_in_background() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let key = Key::new([7; 32]);
+        let logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        let group_name = logger.session_name().to_string();
+        let mut backend = EncryptingBackend::new(Box::new(logger), key.clone());
+        let path = format!("{data_path}/audio_10.wav");
+        let plaintext = vec![1u8; 3 * CHUNK_LEN];
+        fs::write(&path, &plaintext).unwrap();
+        backend.add_audio_entry(AudioRecording { path: path.clone(), duration: Duration::from_secs(5), start_time_s: 10 })
+            .expect("Failed to add audio entry");
+        // The recording is stored once it is encrypted, at the latest when the backend is dropped
+        drop(backend);
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let entries = session.audio_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path.as_str(), format!("{path}.enc"));
+        assert!(!Path::new(&path).exists());
+        assert_eq!(read(&format!("{path}.enc"), Some(&key)).unwrap(), plaintext);
+    }
+}
@@ -0,0 +1,92 @@
+//! Minimal bit-banged driver for the HX711 load-cell ADC, using two GPIO lines.
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+
+/// HX711 reading channel A at gain 128, the usual setup for load cells.
+pub struct Hx711 {
+    dout: LineHandle,
+    pd_sck: LineHandle,
+}
+
+impl Hx711 {
+    /// Requests the data (`DOUT`) and clock (`PD_SCK`) lines on `chip`.
+    pub fn new(chip: &str, dout_line: u32, pd_sck_line: u32) -> Result<Self, Box<dyn Error>> {
+        let mut chip = Chip::new(chip).map_err(|e| format!("Failed to open HX711 GPIO chip {}: {}", chip, e))?;
+        let dout = chip.get_line(dout_line)?.request(LineRequestFlags::INPUT, 0, "sleep-recorder-hx711")?;
+        let pd_sck = chip.get_line(pd_sck_line)?.request(LineRequestFlags::OUTPUT, 0, "sleep-recorder-hx711")?;
+        Ok(Self { dout, pd_sck })
+    }
+
+    /// Waits for a conversion (10 per second) and reads it as a signed raw value.
+    pub fn read_raw(&mut self) -> Result<i32, Box<dyn Error>> {
+        let start = Instant::now();
+        while self.dout.get_value()? == 1 {
+            if start.elapsed() > Duration::from_millis(500) {
+                return Err("HX711 not ready".into());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let mut raw = 0u32;
+        for _ in 0..24 {
+            self.pd_sck.set_value(1)?;
+            self.pd_sck.set_value(0)?;
+            raw = (raw << 1) | self.dout.get_value()? as u32;
+        }
+        // A 25th pulse selects channel A, gain 128 for the next conversion
+        self.pd_sck.set_value(1)?;
+        self.pd_sck.set_value(0)?;
+        Ok(sign_extend_24(raw))
+    }
+
+    /// Reads `samples` conversions and returns their median, which rejects the occasional
+    /// corrupted read (e.g. when the process was descheduled mid-transfer).
+    pub fn read_median(&mut self, samples: usize) -> Result<i32, Box<dyn Error>> {
+        let mut values = (0..samples.max(1)).map(|_| self.read_raw()).collect::<Result<Vec<_>, _>>()?;
+        values.sort_unstable();
+        Ok(values[values.len() / 2])
+    }
+}
+
+/// Converts a 24-bit two's complement value to `i32`.
+fn sign_extend_24(raw: u32) -> i32 {
+    ((raw << 8) as i32) >> 8
+}
+
+/// Converts raw readings to weight: `(raw - offset) / scale`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoadCellCalibration {
+    /// Raw reading with the bed empty (tare).
+    pub offset: i32,
+    /// Raw counts per kilogram.
+    pub scale: f32,
+}
+
+impl LoadCellCalibration {
+    /// Weight in kilograms for a raw reading.
+    pub fn weight_kg(&self, raw: i32) -> f32 {
+        (raw - self.offset) as f32 / self.scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_sign_extend_24() {
+        assert_eq!(sign_extend_24(0x7FFFFF), 8_388_607);
+        assert_eq!(sign_extend_24(0x800000), -8_388_608);
+        assert_eq!(sign_extend_24(0xFFFFFF), -1);
+    }
+
+    #[test]
+    fn test_load_cell_calibration() {
+        let calibration = LoadCellCalibration { offset: -1200, scale: 2000.0 };
+        assert_eq!(calibration.weight_kg(-1200), 0.0);
+        assert_eq!(calibration.weight_kg(148_800), 75.0);
+    }
+}
@@ -1,17 +1,27 @@
  //! This module contains functions for image analysis for the sleep tracker application.
 
 use hdf5::{types::VarLenUnicode, File as H5File};
-use image::GrayImage;
+use image::codecs::jpeg::JpegEncoder;
+use image::{GrayImage, Luma};
+use imageproc::drawing::{draw_filled_rect_mut, draw_polygon_mut};
+use imageproc::point::Point;
+use imageproc::rect::Rect;
+use std::collections::HashSet;
 use std::error::Error;
-use tracing::{error, info};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use tracing::{error, info, warn};
 
-use crate::data::SleepDataLogger;
+use crate::config::{CompressionConfig, MotionRoi};
+use crate::data::{session_path, AppendableColumn, SessionReader};
+use crate::encryption::{self, Key};
 
 /// Analyzes motion by computing differences between consecutive images stored in an HDF5 file for offline analysis.
 ///
-/// This function opens an HDF5 file located at the given `data_path` combined with `file_name`,
-/// and accesses a specific group defined by `group_name`. It then reads a dataset named "image_path"
-/// to obtain the list of image file paths. For each consecutive pair of images, it computes the
+/// This function reads the image paths of the session `group_name` in the HDF5 file located at the
+/// given `data_path` combined with `file_name` with a [`SessionReader`], using the images saved
+/// without overlay where available. For each consecutive pair of images, it computes the
 /// average absolute difference in pixel intensities using the `frame_difference` function. The
 /// result for each pair is stored in a vector, which is eventually written to (or used to generate)
 /// the "image_motion" dataset in the same group.
@@ -23,6 +33,7 @@ use crate::data::SleepDataLogger;
 /// * `data_path` - A string slice representing the directory path where the HDF5 file is located.
 /// * `file_name` - A string slice that specifies the name of the HDF5 file.
 /// * `group_name` - A string slice identifying the group within the HDF5 file containing relevant datasets.
+/// * `key` - Key decrypting encrypted images (see [`crate::encryption`]) in memory.
 ///
 /// # Returns
 ///
@@ -35,36 +46,26 @@ use crate::data::SleepDataLogger;
 /// - The HDF5 file or the specified group cannot be opened.
 /// - The required datasets ("image_path" or "image_motion") cannot be read or generated.
 /// - An image file cannot be opened or processed.
-///
-/// # Examples
-///
-/// ```no_run
-/// use sleep_recorder::image_analysis::analyze_motion;
-/// let result = analyze_motion("/data", "record.h5", "session1").expect("Failed to analyze motion");
-/// ```
+/// - An image is encrypted and `key` is `None` or cannot decrypt it.
 #[tracing::instrument()]
-pub fn analyze_motion(data_path: &str, file_name: &str, group_name: &str) -> Result<(), Box<dyn Error>> {
+pub fn analyze_motion(data_path: &str, file_name: &str, group_name: &str, key: Option<&Key>) -> Result<(), Box<dyn Error>> {
     const PROGRESS_PERCENT: f32 = 0.01;
     info!("Analyzing image motion...");
-    let file = H5File::append(data_path.to_string() + "/" + file_name)?;
-
-    let group = file.group(group_name)?;
-
-    let image_dataset = group.dataset("image_path")?;
-    let image_paths = image_dataset.read_1d::<VarLenUnicode>()?;
-
-    let motion_dataset = match group.dataset("image_motion") {
-        Ok(dataset) => dataset,
-        Err(_) => SleepDataLogger::generate_dataset::<f32>(&group, "image_motion")?,    
+    let (image_paths, raw_paths) = {
+        let session = SessionReader::open(data_path, file_name, group_name)?;
+        // Frames without the overlay, whose changing timestamp would otherwise count as motion
+        (session.image_paths()?, session.raw_image_paths().ok())
     };
-
-    info!("Image dataset shape: {:?}, size: {:?}", image_dataset.shape(), image_dataset.size());
+    info!("Analyzing {} images", image_paths.len());
 
     let mut last_image = None;
     let mut motions: Vec<f32> = vec![f32::NAN; image_paths.len()];
     for (index, entry) in image_paths.iter().enumerate() {
-        let path = entry.to_string();
-        let current_image: image::ImageBuffer<image::Luma<u8>, Vec<u8>> = image::open(&path).map_err(|e| format!("Failed to open image at {} with error {}", path, e))?.into_luma8();
+        let path = raw_paths.as_ref()
+            .and_then(|raw| raw.get(index))
+            .filter(|raw| !raw.is_empty())
+            .unwrap_or(entry);
+        let current_image: image::ImageBuffer<image::Luma<u8>, Vec<u8>> = open_image(path, key)?.into_luma8();
         if let Some(last_image) = last_image {
             let diff = frame_difference(&current_image, &last_image);
             motions[index] = diff.unwrap_or(-1.0);
@@ -74,11 +75,154 @@ pub fn analyze_motion(data_path: &str, file_name: &str, group_name: &str) -> Res
             info!("Progress: {:.2}%", (index as f32 / image_paths.len() as f32) * 100.0);
         }
     }
-    motion_dataset.resize(image_paths.len())?;
-    motion_dataset.write(&motions)?;
+
+    let file = H5File::append(session_path(data_path, file_name, group_name))?;
+    let group = file.group(group_name)?;
+    AppendableColumn::open_or_create(&group, "image_motion", &CompressionConfig::default())?.replace(&motions)?;
     Ok(())
 }
 
+/// Opens the image at `path`, decrypting it in memory with `key` if it is encrypted.
+fn open_image(path: &str, key: Option<&Key>) -> Result<image::DynamicImage, Box<dyn Error>> {
+    let image = if encryption::is_encrypted_path(path) {
+        image::load_from_memory(&encryption::read(path, key)?)
+    } else {
+        image::open(path)
+    };
+    Ok(image.map_err(|e| format!("Failed to open image at {} with error {}", path, e))?)
+}
+
+/// How the images of a session are stored once their motion has been analyzed (see
+/// [`archive_images`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageArchive {
+    /// Re-encode each image in place as a JPEG of this quality (1-100). Images the re-encoding
+    /// wouldn't shrink, and encrypted images, are left as they are. The paths don't change.
+    Reencode { quality: u8 },
+    /// Move the images into an uncompressed `images.tar` in the session directory. Their paths
+    /// become `<archive path>#<member name>`, e.g. `/data/2025-04-28_22-47-31/images.tar#raw/image_1745873251.jpg`.
+    Tar,
+}
+
+/// Separates the archive path from the member name in the paths of archived images.
+pub const ARCHIVE_MEMBER_SEPARATOR: char = '#';
+
+/// Shrinks the stored images of `group_name` in the HDF5 file at `data_path/file_name`, those
+/// referenced by the `image_path`, `raw_image_path`, and per-camera `image_path_<name>` and
+/// `raw_image_path_<name>` datasets, as selected by `archive`. Motion clips are left as they are.
+///
+/// The full-resolution stills are rarely needed once [`analyze_motion`] has run, but dominate the
+/// disk usage of a night.
+///
+/// # Errors
+///
+/// Returns an error if the motion of the session hasn't been analyzed yet, if the session is
+/// already archived as a tar, or if an image cannot be read, encoded, or archived. When
+/// archiving as a tar, the images are only deleted once the archive and all paths are written.
+#[tracing::instrument()]
+pub fn archive_images(data_path: &str, file_name: &str, group_name: &str, archive: ImageArchive) -> Result<(), Box<dyn Error>> {
+    let file = H5File::append(session_path(data_path, file_name, group_name))?;
+    let group = file.group(group_name)?;
+    let image_count = group.dataset("image_path").map(|dataset| dataset.shape()[0]).unwrap_or(0);
+    let motion_count = group.dataset("image_motion").map(|dataset| dataset.shape()[0]).unwrap_or(0);
+    if motion_count < image_count {
+        return Err(format!("Motion of session {} hasn't been analyzed; run analyze_motion first", group_name).into());
+    }
+
+    let mut datasets = Vec::new();
+    for name in group.member_names()? {
+        if name.starts_with("image_path") || name.starts_with("raw_image_path") {
+            let paths = group.dataset(&name)?.read_raw::<VarLenUnicode>()?;
+            datasets.push((name, paths.iter().map(|p| p.to_string()).collect::<Vec<_>>()));
+        }
+    }
+
+    match archive {
+        ImageArchive::Reencode { quality } => {
+            let mut saved_bytes = 0;
+            for path in datasets.iter().flat_map(|(_, paths)| paths).filter(|p| !p.is_empty()) {
+                if path.contains(ARCHIVE_MEMBER_SEPARATOR) {
+                    warn!("Skipping archived image {}", path);
+                    continue;
+                }
+                if encryption::is_encrypted_path(path) {
+                    warn!("Skipping encrypted image {}", path);
+                    continue;
+                }
+                saved_bytes += reencode_jpeg(path, quality)?;
+            }
+            info!("Re-encoded images of {} at quality {}, saving {} bytes", group_name, quality, saved_bytes);
+        }
+        ImageArchive::Tar => {
+            let session_directory = format!("{}/{}", data_path, group_name);
+            let archive_path = format!("{}/images.tar", session_directory);
+            if Path::new(&archive_path).exists() {
+                return Err(format!("Images of session {} are already archived in {}", group_name, archive_path).into());
+            }
+            let images_directory = Path::new(&session_directory).join("images");
+            let mut builder = tar::Builder::new(fs::File::create(&archive_path)?);
+            let mut archived = HashSet::new();
+            let mut updated_datasets = Vec::new();
+            for (name, paths) in &datasets {
+                let mut updated = Vec::with_capacity(paths.len());
+                for path in paths {
+                    if path.is_empty() {
+                        updated.push(VarLenUnicode::default());
+                        continue;
+                    }
+                    let member = archive_member_name(&images_directory, path)?;
+                    if archived.insert(path.clone()) {
+                        builder.append_path_with_name(path, &member)
+                            .map_err(|e| format!("Failed to archive image {}: {}", path, e))?;
+                    }
+                    updated.push(VarLenUnicode::from_str(&format!("{}{}{}", archive_path, ARCHIVE_MEMBER_SEPARATOR, member))?);
+                }
+                updated_datasets.push((name, updated));
+            }
+            builder.into_inner()?.sync_all()?;
+            for (name, updated) in updated_datasets {
+                group.dataset(name)?.write_raw(&updated)?;
+            }
+            file.flush()?;
+            for path in &archived {
+                if let Err(e) = fs::remove_file(path) {
+                    warn!("Failed to remove archived image {}: {}", path, e);
+                }
+            }
+            info!("Archived {} images of {} in {}", archived.len(), group_name, archive_path);
+        }
+    }
+    Ok(())
+}
+
+/// Re-encodes the JPEG at `path` with `quality`, if that makes it smaller. Returns the number of
+/// bytes saved.
+fn reencode_jpeg(path: &str, quality: u8) -> Result<u64, Box<dyn Error>> {
+    let original_size = fs::metadata(path)?.len();
+    let image = image::open(path).map_err(|e| format!("Failed to open image at {} with error {}", path, e))?;
+    let mut encoded = Vec::new();
+    image.write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, quality))?;
+    if encoded.len() as u64 >= original_size {
+        return Ok(0);
+    }
+    // Write next to the original and rename, so an interruption can't leave a truncated image
+    let temporary = format!("{}.tmp", path);
+    fs::write(&temporary, &encoded)?;
+    fs::rename(&temporary, path)?;
+    Ok(original_size - encoded.len() as u64)
+}
+
+/// Name of the image at `path` in the tar archive: its path within `images_directory` (e.g.
+/// `raw/image_1745873251.jpg`), or its file name if it is stored elsewhere.
+fn archive_member_name(images_directory: &Path, path: &str) -> Result<String, Box<dyn Error>> {
+    let path = Path::new(path);
+    let member = match path.strip_prefix(images_directory) {
+        Ok(relative) => relative,
+        Err(_) => Path::new(path.file_name().ok_or_else(|| format!("Image path {} has no file name", path.display()))?),
+    };
+    Ok(member.to_string_lossy().into_owned())
+}
+
 /// Computes the average absolute difference of pixel intensities between two grayscale images.
 ///
 /// The function performs a per-pixel comparison between `new_frame` and `old_frame` (both assumed to have
@@ -94,14 +238,6 @@ pub fn analyze_motion(data_path: &str, file_name: &str, group_name: &str) -> Res
 ///
 /// A result with a floating point number (`f32`) representing the average absolute difference per pixel,
 /// or an error message if the dimensions of the images do not match.
-///
-/// # Examples
-///
-/// ```ignore
-/// use sleep_recorder::image_analysis::frame_difference;
-/// let diff = frame_difference(&new_gray_image, &old_gray_image);
-/// println!("Frame difference: {}", diff.expect("Failed to compute frame difference"));
-/// ```
 pub fn frame_difference(new_frame: &GrayImage, old_frame: &GrayImage) -> Result<f32, String> {
     if new_frame.dimensions() != old_frame.dimensions() {
         let err_message: String = format!(
@@ -116,4 +252,82 @@ pub fn frame_difference(new_frame: &GrayImage, old_frame: &GrayImage) -> Result<
         .zip(old_frame.pixels())
         .map(|(p1, p2)| (p1[0] as f32 - p2[0] as f32).abs())
         .sum::<f32>() / (new_frame.width() * new_frame.height()) as f32)
-}
\ No newline at end of file
+}
+
+/// Builds a mask of `roi` for `width` x `height` frames: 255 inside the region, 0 outside. Any part
+/// of the region outside the frame is ignored.
+pub fn roi_mask(roi: &MotionRoi, width: u32, height: u32) -> GrayImage {
+    let mut mask = GrayImage::new(width, height);
+    match roi {
+        MotionRoi::Rect { x, y, width, height } => {
+            draw_filled_rect_mut(&mut mask, Rect::at(*x as i32, *y as i32).of_size(*width, *height), Luma([255]));
+        }
+        MotionRoi::Polygon { points } => {
+            let points: Vec<Point<i32>> = points.iter().map(|&[x, y]| Point::new(x as i32, y as i32)).collect();
+            draw_polygon_mut(&mut mask, &points, Luma([255]));
+        }
+    }
+    mask
+}
+
+/// Like [`frame_difference`], averaged only over the pixels where `mask` is non-zero (see
+/// [`roi_mask`]).
+///
+/// # Errors
+///
+/// Returns an error message if the dimensions of the images and mask do not match, or if the mask
+/// is empty.
+pub fn masked_frame_difference(new_frame: &GrayImage, old_frame: &GrayImage, mask: &GrayImage) -> Result<f32, String> {
+    if new_frame.dimensions() != old_frame.dimensions() || new_frame.dimensions() != mask.dimensions() {
+        let err_message: String = format!(
+            "Image dimensions do not match: new {:?} vs old {:?} vs mask {:?}",
+            new_frame.dimensions(),
+            old_frame.dimensions(),
+            mask.dimensions()
+        );
+        error!(err_message);
+        return Err(err_message);
+    }
+    let (sum, count) = new_frame.pixels()
+        .zip(old_frame.pixels())
+        .zip(mask.pixels())
+        .filter(|(_, m)| m[0] != 0)
+        .fold((0.0f32, 0usize), |(sum, count), ((p1, p2), _)| {
+            (sum + (p1[0] as f32 - p2[0] as f32).abs(), count + 1)
+        });
+    if count == 0 {
+        return Err("Motion mask does not cover any pixels".to_string());
+    }
+    Ok(sum / count as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_motion_outside_roi_is_ignored() {
+        let old_frame = GrayImage::new(8, 8);
+        let mut new_frame = old_frame.clone();
+        // Movement in the left half only
+        for y in 0..8 {
+            for x in 0..4 {
+                new_frame.put_pixel(x, y, Luma([100]));
+            }
+        }
+        let right_half = roi_mask(&MotionRoi::Rect { x: 4, y: 0, width: 4, height: 8 }, 8, 8);
+        assert_eq!(masked_frame_difference(&new_frame, &old_frame, &right_half), Ok(0.0));
+        let left_half = roi_mask(&MotionRoi::Polygon { points: vec![[0, 0], [3, 0], [3, 7], [0, 7]] }, 8, 8);
+        assert_eq!(masked_frame_difference(&new_frame, &old_frame, &left_half), Ok(100.0));
+        assert_eq!(frame_difference(&new_frame, &old_frame), Ok(50.0));
+    }
+
+    #[test]
+    fn test_archive_member_name() {
+        let images = Path::new("/data/2025-04-28_22-47-31/images");
+        assert_eq!(archive_member_name(images, "/data/2025-04-28_22-47-31/images//image_1.jpg").unwrap(), "image_1.jpg");
+        assert_eq!(archive_member_name(images, "/data/2025-04-28_22-47-31/images/crib/raw/image_1.jpg").unwrap(), "crib/raw/image_1.jpg");
+        assert_eq!(archive_member_name(images, "/elsewhere/image_2.jpg").unwrap(), "image_2.jpg");
+    }
+}
@@ -0,0 +1,415 @@
+//! Export of the recorded data to InfluxDB, for dashboards such as Grafana.
+//!
+//! With [`Config::influxdb`] set, [`storage::open`](crate::storage::open) pairs the data file's
+//! backend with an [`InfluxSink`] (see [`Tee`](crate::storage::Tee)), which writes each sample
+//! while the session is recorded. [`export_session`] writes a recorded session, e.g. one recorded
+//! before the export was set up.
+//!
+//! Points are written in line protocol with second precision:
+//! - each sample is a point of the configured measurement, with the per-sample datasets as fields
+//!   named like the datasets. NaN values, empty strings, and thumbnails are left out.
+//! - live audio levels are `rms_db` fields of `<measurement>_audio`.
+//! - actuator events are `on` fields of `<measurement>_actuator`, tagged with the `actuator`.
+//! - annotations of the session are `text` fields of `<measurement>_event`, tagged with their
+//!   `category`, e.g. for Grafana annotations.
+//!
+//! All points are tagged with the session name and the session's `device_id`, `location`, and
+//! `subject`, if set.
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::audio_analysis::BANDS;
+use crate::config::{Config, InfluxDbConfig};
+use crate::data::{
+    sensor_status_key, sleep_fields, ActuatorEvent, AudioRecording, Event, SessionMetadata, SessionReader, SleepData,
+    SleepField,
+};
+use crate::storage::StorageBackend;
+
+/// Lines written per request by [`export_session`].
+const EXPORT_BATCH_LINES: usize = 5000;
+
+/// Lines kept by an [`InfluxSink`] while the server is unreachable; older ones are dropped.
+const MAX_PENDING_LINES: usize = 2000;
+
+/// Time an [`InfluxSink`] waits after a failed write before trying again, so that an unreachable
+/// server doesn't stall the storage writer on every sample.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Client of the write API of an InfluxDB v2 server.
+#[derive(Debug)]
+pub struct InfluxClient {
+    agent: ureq::Agent,
+    write_url: String,
+    org: String,
+    bucket: String,
+    token: String,
+}
+
+impl InfluxClient {
+    /// Creates a client of the server in `config`. No request is made until [`InfluxClient::write`].
+    pub fn new(config: &InfluxDbConfig) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(config.timeout_s)))
+            .build()
+            .into();
+        Self {
+            agent,
+            write_url: format!("{}/api/v2/write", config.url.trim_end_matches('/')),
+            org: config.org.clone(),
+            bucket: config.bucket.clone(),
+            token: config.token.clone(),
+        }
+    }
+
+    /// Writes `lines` of line protocol, separated by newlines.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot be reached or rejects the points.
+    pub fn write(&self, lines: &str) -> Result<(), Box<dyn Error>> {
+        self.agent
+            .post(&self.write_url)
+            .query("org", &self.org)
+            .query("bucket", &self.bucket)
+            .query("precision", "s")
+            .header("Authorization", &format!("Token {}", self.token))
+            .content_type("text/plain; charset=utf-8")
+            .send(lines)
+            .map_err(|e| format!("Failed to write to InfluxDB at {}: {}", self.write_url, e))?;
+        Ok(())
+    }
+}
+
+/// Tag set of the points of `session`, starting with the comma after the measurement: the session
+/// name and the non-empty `tags`, sorted by key as InfluxDB recommends.
+fn tag_set(session: &str, tags: [(&str, &str); 3]) -> String {
+    let mut tags: Vec<(&str, &str)> = tags.into_iter().filter(|(_, value)| !value.is_empty()).collect();
+    tags.push(("session", session));
+    tags.sort();
+    tags.into_iter().map(|(key, value)| format!(",{}={}", escape_key(key), escape_key(value))).collect()
+}
+
+/// Tag set of the points of a session with `metadata`.
+fn metadata_tags(session: &str, metadata: &SessionMetadata) -> String {
+    tag_set(session, [
+        ("device_id", &metadata.device_id),
+        ("location", &metadata.location),
+        ("subject", &metadata.subject),
+    ])
+}
+
+/// Escapes a measurement, tag key, tag value, or field key.
+fn escape_key(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for c in key.chars() {
+        match c {
+            ',' | '=' | ' ' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Quotes and escapes a string field value.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// Formats a float field value, or `None` for NaN and infinite values, which InfluxDB rejects.
+fn float(value: f32) -> Option<String> {
+    value.is_finite().then(|| value.to_string())
+}
+
+/// Line of `sample`: a point of `measurement` with `tags` (see [`tag_set`]).
+pub(crate) fn sample_line(measurement: &str, tags: &str, sample: &SleepData) -> String {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for (name, field) in sleep_fields() {
+        let value = match field {
+            _ if name == "timestamp" => None,
+            SleepField::Bool(f) => Some(f(sample).to_string()),
+            SleepField::U16(f) => Some(format!("{}i", f(sample))),
+            SleepField::U64(f) => Some(format!("{}i", f(sample))),
+            SleepField::F32(f) => float(f(sample)),
+            SleepField::String(f) => Some(f(sample).as_str().to_string()).filter(|s| !s.is_empty()).map(|s| quote(&s)),
+            SleepField::Bytes(_) => None,
+        };
+        if let Some(value) = value {
+            fields.push((name.to_string(), value));
+        }
+    }
+    for (name, result) in &sample.extra_cameras {
+        if !result.image_path.is_empty() {
+            fields.push((format!("image_path_{name}"), quote(&result.image_path)));
+        }
+        if let Some(motion) = result.motion.and_then(float) {
+            fields.push((format!("image_motion_live_{name}"), motion));
+        }
+    }
+    for (prefix, temps) in [("probe_temp_", &sample.probe_temps_c), ("thermistor_temp_", &sample.extra_thermistor_temps_c)] {
+        for (name, temp) in temps {
+            if let Some(temp) = float(*temp) {
+                fields.push((format!("{prefix}{name}"), temp));
+            }
+        }
+    }
+    for (name, status) in &sample.sensor_status {
+        let key = sensor_status_key(name);
+        fields.push((format!("sensor_ok_{key}"), status.ok().to_string()));
+        fields.push((format!("sensor_state_{key}"), format!("{}i", status.state as u8)));
+    }
+    fields.sort();
+    let fields: Vec<String> = fields.into_iter().map(|(name, value)| format!("{}={}", escape_key(&name), value)).collect();
+    format!("{}{} {} {}", escape_key(measurement), tags, fields.join(","), sample.timestamp_s)
+}
+
+/// Line of a live audio level, a point of `<measurement>_audio`.
+fn audio_level_line(measurement: &str, tags: &str, timestamp_s: u64, rms_db: f32) -> Option<String> {
+    let rms_db = float(rms_db)?;
+    Some(format!("{}{} rms_db={} {}", escape_key(&format!("{measurement}_audio")), tags, rms_db, timestamp_s))
+}
+
+/// Line of an actuator event, a point of `<measurement>_actuator`.
+fn actuator_line(measurement: &str, tags: &str, event: &ActuatorEvent) -> String {
+    format!(
+        "{}{},actuator={} on={} {}",
+        escape_key(&format!("{measurement}_actuator")), tags, escape_key(&event.name), event.on, event.timestamp_s
+    )
+}
+
+/// Line of an annotation of the session, a point of `<measurement>_event`.
+fn event_line(measurement: &str, tags: &str, event: &Event) -> String {
+    let category = if event.category.is_empty() { String::new() } else { format!(",category={}", escape_key(&event.category)) };
+    format!(
+        "{}{}{} text={} {}",
+        escape_key(&format!("{measurement}_event")), tags, category, quote(&event.text), event.timestamp_s
+    )
+}
+
+/// Writes the samples, live audio levels, actuator events, and annotations of a recorded session to the
+/// InfluxDB server in `config`, tagged with the session's metadata. Returns the number of points
+/// written.
+///
+/// Points are written in batches; writing a session again overwrites its points.
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be read, or a batch cannot be written.
+pub fn export_session(config: &InfluxDbConfig, session: &SessionReader) -> Result<usize, Box<dyn Error>> {
+    let tags = metadata_tags(session.group_name(), &session.metadata()?);
+    let measurement = config.measurement.as_str();
+    let (level_times, levels) = session.live_audio_levels()?;
+    let lines: Vec<String> = session.samples()?.iter()
+        .map(|sample| sample_line(measurement, &tags, sample))
+        .chain(level_times.into_iter().zip(levels).filter_map(|(t, rms_db)| audio_level_line(measurement, &tags, t, rms_db)))
+        .chain(session.actuator_events()?.iter().map(|event| actuator_line(measurement, &tags, event)))
+        .chain(session.events()?.iter().map(|event| event_line(measurement, &tags, event)))
+        .collect();
+
+    let client = InfluxClient::new(config);
+    for batch in lines.chunks(EXPORT_BATCH_LINES) {
+        client.write(&batch.join("\n"))?;
+    }
+    Ok(lines.len())
+}
+
+/// Secondary [`StorageBackend`] writing the session to InfluxDB while it is recorded.
+///
+/// Each sample is written as soon as it is appended, together with the audio levels buffered since
+/// the previous one. If a write fails, the points are kept (up to a limit) and written with a later
+/// sample, once a retry interval has passed. Names and metadata aren't stored: the points are
+/// tagged with the session metadata from the config.
+#[derive(Debug)]
+pub struct InfluxSink {
+    client: InfluxClient,
+    measurement: String,
+    session: String,
+    tags: String,
+    pending: Vec<String>,
+    retry_at: Option<Instant>,
+}
+
+impl InfluxSink {
+    /// Creates a sink for `session` writing to the server in `config.influxdb`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the export is disabled (`influxdb.url` is empty).
+    pub fn new(config: &Config, session: &str) -> Result<Self, Box<dyn Error>> {
+        if !config.influxdb.is_enabled() {
+            return Err("influxdb.url is not set".into());
+        }
+        let tags = tag_set(session, [
+            ("device_id", &config.session.device_id),
+            ("location", &config.session.location),
+            ("subject", &config.session.subject),
+        ]);
+        Ok(Self {
+            client: InfluxClient::new(&config.influxdb),
+            measurement: config.influxdb.measurement.clone(),
+            session: session.to_string(),
+            tags,
+            pending: Vec::new(),
+            retry_at: None,
+        })
+    }
+
+    fn push(&mut self, line: String) {
+        if self.pending.len() == MAX_PENDING_LINES {
+            self.pending.remove(0);
+        }
+        self.pending.push(line);
+    }
+
+    /// Writes the pending points, unless a write failed less than [`RETRY_INTERVAL`] ago.
+    fn write_pending(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.pending.is_empty() || self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return Ok(());
+        }
+        match self.client.write(&self.pending.join("\n")) {
+            Ok(()) => {
+                if self.retry_at.take().is_some() {
+                    info!("Wrote {} pending points to InfluxDB", self.pending.len());
+                }
+                self.pending.clear();
+                Ok(())
+            }
+            Err(e) => {
+                self.retry_at = Some(Instant::now() + RETRY_INTERVAL);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl StorageBackend for InfluxSink {
+    fn session_name(&self) -> &str {
+        &self.session
+    }
+
+    fn is_resumed(&self) -> bool {
+        false
+    }
+
+    fn write_metadata(&mut self, _metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_field(&mut self, _name: &'static str, _field: SleepField) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_camera(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_probe(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_thermistor(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_sensor(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+        let line = sample_line(&self.measurement, &self.tags, &sample);
+        self.push(line);
+        self.write_pending()
+    }
+
+    fn add_audio_entry(&mut self, _audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn append_audio_rms(&mut self, _start_time_s: u64, _rms_t_s: &[u64], _rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        let line = actuator_line(&self.measurement, &self.tags, event);
+        self.push(line);
+        self.write_pending()
+    }
+
+    fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let line = event_line(&self.measurement, &self.tags, event);
+        self.push(line);
+        self.write_pending()
+    }
+
+    fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32) {
+        if let Some(line) = audio_level_line(&self.measurement, &self.tags, timestamp_s, rms_db) {
+            self.push(line);
+        }
+    }
+
+    fn append_audio_bands(&mut self, _timestamp_s: u64, _levels_db: [f32; BANDS.len()]) {}
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        // Try once more at the end of the session
+        self.retry_at = None;
+        self.write_pending()
+    }
+}
+
+impl Drop for InfluxSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Dropping {} points not written to InfluxDB: {}", self.pending.len(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{SensorState, SensorStatus};
+    use test_log::test;
+
+    #[test]
+    fn test_sample_line() {
+        let tags = tag_set("2025-04-28_22-47-31", [("device_id", "pi"), ("location", "bed room"), ("subject", "")]);
+        assert_eq!(tags, ",device_id=pi,location=bed\\ room,session=2025-04-28_22-47-31");
+
+        let status = SensorStatus { state: SensorState::Ready, error: Some("I2C \"timeout\"".to_string()), last_error_age_s: Some(0) };
+        let sample = SleepData::builder(1745873251)
+            .with_climate(21.5, f32::NAN, 40.0)
+            .with_probe_temp("mattress", 30.0)
+            .with_sensor_status("BME280", status)
+            .build();
+        let line = sample_line("sleep", ",session=s", &sample);
+        let (series, rest) = line.split_once(' ').unwrap();
+        assert_eq!(series, "sleep,session=s");
+        assert!(rest.ends_with(" 1745873251"));
+        let fields: Vec<&str> = rest.trim_end_matches(" 1745873251").split(',').collect();
+        assert!(fields.contains(&"temperature=21.5"));
+        assert!(fields.contains(&"humidity=40"));
+        assert!(fields.contains(&"probe_temp_mattress=30"));
+        assert!(fields.contains(&"sensor_ok_bme280=false"));
+        assert!(fields.contains(&"sensor_state_bme280=1i"));
+        assert!(fields.contains(&"pir_motion=false"));
+        assert!(fields.contains(&"co2_ppm=0i"));
+        // NaN values, empty strings, thumbnails, and the timestamp are left out
+        for name in ["pressure", "image_path", "thumbnail", "timestamp"] {
+            assert!(!fields.iter().any(|f| f.starts_with(&format!("{name}="))), "{name} in {line}");
+        }
+
+        assert_eq!(quote("I2C \"timeout\"\n"), "\"I2C \\\"timeout\\\"\\n\"");
+        assert_eq!(audio_level_line("sleep", &tags, 10, f32::NEG_INFINITY), None);
+        let event = ActuatorEvent { timestamp_s: 10, name: "fan".to_string(), on: true };
+        assert_eq!(actuator_line("sleep", ",session=s", &event), "sleep_actuator,session=s,actuator=fan on=true 10");
+        let event = Event { timestamp_s: 20, category: "fall alert".to_string(), text: "got up".to_string() };
+        assert_eq!(event_line("sleep", ",session=s", &event), "sleep_event,session=s,category=fall\\ alert text=\"got up\" 20");
+    }
+}
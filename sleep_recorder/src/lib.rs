@@ -1,27 +1,327 @@
 //! Module for a Raspberry Pi sleep recording device.
-//! Users call the `sleep_tracker` function to start the application.
+//! Users create a [`Recorder`] from a [`Config`] (or call the `sleep_tracker` function) to start the application.
+//!
+//! # Public API and semver policy
+//!
+//! The supported public API is everything exported from the crate root and [`prelude`], plus the
+//! `pub` items of the [`config`], [`sensor`], [`actuator`], [`data`], [`storage`], [`retention`],
+//! [`encryption`], [`analysis`], [`audio_analysis`], [`calibration`], and [`sleep_periods`] modules,
+//! and of the feature-gated `sqlite`, `influxdb`, `remote`, `simulation`, and `sound_classifier` modules.
+//! Breaking changes to these items are only made with a minor version bump while the crate is at
+//! 0.x (and a major bump after 1.0). The device drivers and analysis internals are `pub(crate)`
+//! modules, and may change at any time; the types of theirs used by the public API are
+//! re-exported from the crate root.
+//! `tests/public_api.rs` names every supported item and pins the signatures of the prelude, so
+//! removing or renaming one fails the tests.
 
+use std::collections::HashSet;
 use std::error::Error;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
-use data::SleepDataLogger;
-use sensor::{AudioRecorder, SensorReader};
-// use audio_analysis::decode_mp3;
+use actuator::{ActuatorCommand, ActuatorHandle, Actuators};
+use calibration::Calibration;
+use config::AudioWeighting;
+use data::{ActuatorEvent, AudioRecording, Event, SessionMetadata};
+use audio_analysis::{LevelMeter, StreamAnalyzer};
+use sensor::{AudioChunk, AudioRecorder, SensorReader};
+use sound_events::SoundEventDetector;
+#[cfg(feature = "ml")]
+use sound_classifier::{RecordingClassifier, SoundClassifier};
+use storage::StorageWriter;
 
 pub mod sensor;
+pub mod actuator;
 pub mod data;
+pub mod config;
+pub mod calibration;
+pub(crate) mod climate;
+pub mod sleep_periods;
+pub mod analysis;
 pub mod audio_analysis;
-pub mod image_analysis;
+pub(crate) mod sound_events;
+pub(crate) mod audio_respiration;
+pub(crate) mod noise_stats;
+pub(crate) mod image_analysis;
+pub(crate) mod sensirion;
+pub(crate) mod bh1750;
+pub(crate) mod pms5003;
+pub(crate) mod hx711;
+pub(crate) mod bcg;
+pub mod storage;
+pub mod retention;
+pub mod encryption;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "influxdb")]
+pub mod influxdb;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+#[cfg(feature = "ml")]
+pub mod sound_classifier;
 
-/// Starts the sleep tracker application. 
+pub use config::Config;
+// Types of the internal modules that appear in the public API
+pub use bcg::BcgEstimate;
+pub use image_analysis::ImageArchive;
+pub use noise_stats::NoiseStats;
+pub use pms5003::PmMeasurement;
+pub use sensirion::Scd4xMeasurement;
+pub use sound_events::{SoundClass, SoundEvent};
+
+/// Commonly used types, re-exported for `use sleep_recorder::prelude::*`.
+pub mod prelude {
+    pub use crate::Recorder;
+    pub use crate::config::{AudioConfig, Config};
+    pub use crate::data::{AudioRecording, SessionReader, SleepData, SleepDataBuilder, SleepDataLogger};
+    pub use crate::analysis::Pipeline;
+}
+
+/// Handle to a sleep recording session.
+///
+/// Creates a DataLogger, SensorReader, and AudioRecorder from the [`Config`] when run, and spawns two
+/// separate tasks for reading sensor data and recording audio. The session ends when the user
+/// interrupts the program, when [`Recorder::stop`] is called, or after `max_session_s`.
+///
+/// # Example
+///
+/// ```no_run
+/// use sleep_recorder::prelude::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let recorder = Recorder::new(Config::new("/path/to/data"));
+///     if let Err(e) = recorder.run().await {
+///         eprintln!("Error: {}", e);
+///     }
+/// }
+/// ```
+pub struct Recorder {
+    config: Config,
+    cancel: CancellationToken,
+    actuators: ActuatorHandle,
+    actuator_commands: Arc<Mutex<mpsc::UnboundedReceiver<ActuatorCommand>>>,
+    events: mpsc::UnboundedSender<Event>,
+    event_queue: Arc<Mutex<mpsc::UnboundedReceiver<Event>>>,
+}
+
+impl Recorder {
+    /// Creates a new recorder. No hardware is touched until [`Recorder::run`] is called.
+    pub fn new(config: Config) -> Self {
+        let (actuators, actuator_commands) = ActuatorHandle::channel();
+        let (events, event_queue) = mpsc::unbounded_channel();
+        Self {
+            config,
+            cancel: CancellationToken::new(),
+            actuators,
+            actuator_commands: Arc::new(Mutex::new(actuator_commands)),
+            events,
+            event_queue: Arc::new(Mutex::new(event_queue)),
+        }
+    }
+
+    /// The configuration this recorder was created with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Token that stops the session when cancelled. Equivalent to calling [`Recorder::stop`].
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Handle for switching the configured actuators while the session runs.
+    pub fn actuators(&self) -> ActuatorHandle {
+        self.actuators.clone()
+    }
+
+    /// Records an annotation of the session at the current time, e.g. `add_event("bedtime", "went
+    /// to bed")` (see [`SleepDataLogger::add_event`](data::SleepDataLogger::add_event)). Events
+    /// added before the session starts are recorded once it has.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recorder has been dropped.
+    pub fn add_event(&self, category: &str, text: &str) -> Result<(), Box<dyn Error>> {
+        let timestamp_s = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.events.send(Event { timestamp_s, category: category.to_string(), text: text.to_string() })
+            .map_err(|_| "The recorder is no longer running".into())
+    }
+
+    /// Requests a clean shutdown of a running session.
+    pub fn stop(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Runs the recording session until it is stopped, interrupted, or times out.
+    ///
+    /// # Errors
+    ///
+    /// If any of the initialization steps fail, an error is returned.
+    /// Individual failures of sensor or audio recording tasks are logged but do not cause the entire application to fail.
+    pub async fn run(&self) -> Result<(), Box<dyn Error>> {
+        let config = &self.config;
+        let data_path = config.data_path.as_str();
+
+        // 1) Setup
+        let cancel = self.cancel.clone();
+        let sensor_cancel = cancel.clone();
+        let audio_cancel  = cancel.clone();
+
+        if config.retention.is_enabled() {
+            let retention_config = config.clone();
+            let now_s = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            let pruned = tokio::task::spawn_blocking(move || {
+                retention::prune(&retention_config, now_s).map_err(|e| e.to_string())
+            }).await?;
+            match pruned {
+                Ok(report) => info!("Pruned {} expired media files ({} bytes)", report.files, report.bytes),
+                Err(e) => warn!("Failed to prune expired media files: {e}"),
+            }
+        }
+
+        let mut logger = storage::open(config)?;
+        for camera in &config.extra_cameras {
+            logger.register_camera(&camera.name)?;
+        }
+        for probe in &config.ds18b20 {
+            logger.register_probe(&probe.name)?;
+        }
+        for thermistor in &config.extra_thermistors {
+            logger.register_thermistor(&thermistor.name)?;
+        }
+        let sensor_reader = SensorReader::from_config(config, logger.session_name())?;
+        for (name, field) in sensor_reader.fields() {
+            logger.register_field(name, field)?;
+        }
+        for name in sensor_reader.sensor_names() {
+            logger.register_sensor(name)?;
+        }
+        if !logger.is_resumed() {
+            let calibration = Calibration::load(config.calibration_path())?;
+            logger.write_metadata(&SessionMetadata {
+                device_id: config.session.device_id.clone(),
+                location: config.session.location.clone(),
+                subject: config.session.subject.clone(),
+                notes: config.session.notes.clone(),
+                software_version: env!("CARGO_PKG_VERSION").to_string(),
+                audio_weighting: config.audio.weighting.name().to_string(),
+                audio_spl_offset_db: calibration.microphone.spl_offset_db.map(|db| db.to_string()).unwrap_or_default(),
+                sensors: sensor_reader.sensor_names().into_iter().map(str::to_string).collect(),
+            })?;
+        }
+        let actuators = Actuators::from_config(config)?;
+        let audio_directory = format!("{}/{}/audio/", data_path, logger.session_name());
+        let (data_logger, writer_thread) = StorageWriter::spawn_with_capacity(logger, config.write_queue_capacity)?;
+        let sensor_reader = Arc::new(Mutex::new(sensor_reader));
+        #[cfg(feature = "simulation")]
+        let audio_recorder = if config.simulation {
+            AudioRecorder::simulated(&audio_directory, Duration::from_secs(config.audio.segment_s))?
+        } else {
+            AudioRecorder::from_config(&audio_directory, &config.audio)?
+        };
+        // Without the feature, SensorReader::from_config has already rejected `simulation`
+        #[cfg(not(feature = "simulation"))]
+        let audio_recorder = AudioRecorder::from_config(&audio_directory, &config.audio)?;
+        let audio_recorder = Arc::new(audio_recorder.with_cancel(cancel.clone()));
+
+        // Live level meter, fed by the samples published while recording
+        let meter_handle = (config.audio.live_meter_s > 0).then(|| tokio::spawn(meter_loop(
+            cancel.clone(),
+            Duration::from_secs(config.audio.live_meter_s),
+            data_logger.clone(),
+            audio_recorder.subscribe_samples(),
+            config.audio.weighting,
+        )));
+        // Live analysis of the recordings, done once the last one is analyzed to its end
+        let analysis_done = CancellationToken::new();
+        let analysis_handle = config.audio.live_analysis.then(|| tokio::spawn(analysis_loop(
+            analysis_done.clone(),
+            data_logger.clone(),
+            audio_recorder.subscribe_samples(),
+            config.audio.weighting,
+        )));
+        let classifier_handle = spawn_classifier(config, analysis_done.clone(), data_logger.clone(), audio_recorder.subscribe_samples())?;
+
+        let actuator_handle = tokio::spawn(actuator_loop(cancel.clone(), data_logger.clone(), actuators, self.actuator_commands.clone()));
+        let event_handle = tokio::spawn(event_loop(cancel.clone(), data_logger.clone(), self.event_queue.clone()));
+
+        // 2) Spawn the sensor‐polling task
+        let mut sensor_handle = tokio::spawn(sensor_loop(sensor_cancel, config.sensor_interval(), data_logger.clone(), sensor_reader.clone()));
+        let overlap = (config.audio.rollover_overlap_s > 0).then(|| Duration::from_secs(config.audio.rollover_overlap_s));
+        let mut audio_handle  = tokio::spawn(audio_loop(audio_cancel, data_logger.clone(), audio_recorder.clone(), overlap));
+
+        // 4) Top‐level select: Ctrl‑C, timeout, stop request, or task failures
+        let timeout = tokio::time::sleep(config.max_session());
+        tokio::pin!(timeout);
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("Ctrl‑C received; cancelling...");
+                cancel.cancel();
+            }
+
+            _ = &mut timeout => {
+                info!("Timeout reached; cancelling...");
+                cancel.cancel();
+            }
+
+            _ = cancel.cancelled() => {
+                info!("Stop requested; cancelling...");
+            }
+
+            // If either background task panics or returns:
+            res = &mut sensor_handle => {
+                if let Err(e) = res {
+                    error!("Sensor task aborted: {:?}", e);
+                    cancel.cancel();
+                }
+            }
+            res = &mut audio_handle => {
+                if let Err(e) = res {
+                    error!("Audio task aborted: {:?}", e);
+                    cancel.cancel();
+                }
+            }
+        }
+
+        // 5) Wait for both loops to finish cleanly
+        let _ = sensor_handle.await;
+        let _ = audio_handle.await;
+        analysis_done.cancel();
+        if let Some(analysis_handle) = analysis_handle {
+            let _ = analysis_handle.await;
+        }
+        if let Some(classifier_handle) = classifier_handle {
+            let _ = classifier_handle.await;
+        }
+        if let Some(meter_handle) = meter_handle {
+            let _ = meter_handle.await;
+        }
+        let _ = actuator_handle.await;
+        let _ = event_handle.await;
+
+        // Wait for the writer to flush the last samples
+        drop(data_logger);
+        if tokio::task::spawn_blocking(move || writer_thread.join()).await.is_err() {
+            error!("Storage writer panicked; the last samples may be lost");
+        }
+
+        info!("All loops exited; sleep_tracker done.");
+        Ok(())
+    }
+}
+
+/// Starts the sleep tracker application with the default [`Config`] for `data_path`.
 /// 
-/// Creates a DataLogger, SensorReader, and AudioRecorder, and spawns two separate tasks
-/// for reading sensor data and recording audio.
-/// The tasks run concurrently and are cancelled when either the user interrupts the program.
+/// Equivalent to `Recorder::new(Config::new(data_path)).run().await`.
 /// Times out after 10 hours if the user does not interrupt.
 /// 
 /// # Arguments
@@ -31,7 +331,7 @@ pub mod image_analysis;
 /// 
 /// # Example
 /// 
-/// ```
+/// ```no_run
 /// use sleep_recorder::sleep_tracker;
 /// 
 /// #[tokio::main]
@@ -48,70 +348,16 @@ pub mod image_analysis;
 /// Individual failures of sensor or audio recording tasks are logged but do not cause the entire application to fail.
 /// 
 pub async fn sleep_tracker(data_path: &str) -> Result<(), Box<dyn Error>> {
-    // 1) Setup
-    let cancel = CancellationToken::new();
-    let sensor_cancel = cancel.clone();
-    let audio_cancel  = cancel.clone();
-
-    let data_logger   = Arc::new(Mutex::new(
-        SleepDataLogger::new(data_path, "sleep_data.h5")?));
-    let sensor_reader = Arc::new(Mutex::new(
-        SensorReader::new(data_path, &data_logger.lock().await.group_name)?));
-    let audio_recorder = Arc::new(
-        AudioRecorder::new(
-            &format!("{}/{}/audio/", data_path, &data_logger.lock().await.group_name),
-            Duration::from_secs(30*60),
-            "plughw:/dev/snd/by-id/usb-Arducam_Technology_Co.__Ltd._USB_Camera_SN0001-02".to_string(),
-        )?);
-
-    // 2) Spawn the sensor‐polling task
-    let mut sensor_handle = tokio::spawn(sensor_loop(sensor_cancel, data_logger.clone(), sensor_reader.clone()));
-    let mut audio_handle  = tokio::spawn(audio_loop(audio_cancel, data_logger.clone(), audio_recorder.clone()));
-
-    // 4) Top‐level select: Ctrl‑C, timeout, or task failures
-    let timeout = tokio::time::sleep(Duration::from_secs(60 * 60 * 10)); // 10 h
-    tokio::pin!(timeout);
-
-    tokio::select! {
-        _ = tokio::signal::ctrl_c() => {
-            info!("Ctrl‑C received; cancelling...");
-            cancel.cancel();
-        }
-
-        _ = &mut timeout => {
-            info!("Timeout reached; cancelling...");
-            cancel.cancel();
-        }
-
-        // If either background task panics or returns:
-        res = &mut sensor_handle => {
-            if let Err(e) = res {
-                error!("Sensor task aborted: {:?}", e);
-                cancel.cancel();
-            }
-        }
-        res = &mut audio_handle => {
-            if let Err(e) = res {
-                error!("Audio task aborted: {:?}", e);
-                cancel.cancel();
-            }
-        }
-    }
-
-    // 5) Wait for both loops to finish cleanly
-    let _ = sensor_handle.await;
-    let _ = audio_handle.await;
-
-    info!("All loops exited; sleep_tracker done.");
-    Ok(())
+    Recorder::new(Config::new(data_path)).run().await
 }
 
 async fn sensor_loop(
     cancel: CancellationToken,
-    data_logger: Arc<Mutex<SleepDataLogger>>,
+    period: Duration,
+    data_logger: StorageWriter,
     sensor_reader: Arc<Mutex<SensorReader>>,
 ) {
-    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    let mut interval = tokio::time::interval(period);
     loop {
         tokio::select! {
             _ = cancel.cancelled() => {
@@ -119,11 +365,15 @@ async fn sensor_loop(
                 break;
             }
             _ = interval.tick() => {
-                let sample = match sensor_reader.lock().await.measure() {
-                    Ok(s)  => s,
-                    Err(e) => { warn!("sensor read error: {}", e); continue; }
+                // Sensors are read with blocking I/O (and waited on up to their timeouts), so the
+                // measurement runs on the blocking pool instead of stalling the async workers.
+                let mut reader = sensor_reader.clone().lock_owned().await;
+                let sample = match tokio::task::spawn_blocking(move || reader.measure()).await {
+                    Ok(Ok(s))  => s,
+                    Ok(Err(e)) => { warn!("sensor read error: {}", e); continue; }
+                    Err(e) => { warn!("sensor read task failed: {}", e); continue; }
                 };
-                if let Err(e) = data_logger.lock().await.append(sample) {
+                if let Err(e) = data_logger.append(sample) {
                     warn!("log append error: {}", e);
                 }
             }
@@ -133,28 +383,550 @@ async fn sensor_loop(
 
 async fn audio_loop(
     cancel: CancellationToken,
-    data_logger: Arc<Mutex<SleepDataLogger>>,
+    data_logger: StorageWriter,
     recorder: Arc<AudioRecorder>,
+    overlap: Option<Duration>,
 ) {
+    if let Some(overlap) = overlap {
+        return overlapping_audio_loop(cancel, data_logger, recorder, overlap).await;
+    }
     while !cancel.is_cancelled() {
         // Start a cancellable recording
-        match recorder.async_audio_recording().await {
-            Ok(rec) => {
-                let path = rec.path.clone();
-                if data_logger.lock().await.add_audio_entry(rec).is_ok() {
-                    info!("audio saved to {:?}", path);
+        let result = recorder.async_audio_recording().await;
+        if !log_recording(&cancel, &data_logger, result).await {
+            break;
+        }
+    }
+
+    info!("audio_loop: shutdown complete");
+}
+
+/// Gapless variant of `audio_loop`: each segment is started `overlap` before the previous one
+/// ends, so there is no gap while the next recording opens the device.
+async fn overlapping_audio_loop(
+    cancel: CancellationToken,
+    data_logger: StorageWriter,
+    recorder: Arc<AudioRecorder>,
+    overlap: Duration,
+) {
+    let mut in_flight = None;
+    while !cancel.is_cancelled() {
+        let started = tokio::time::Instant::now();
+        let next = recorder.clone();
+        let handle = tokio::spawn(async move { next.async_audio_recording().await });
+        if let Some(previous) = in_flight.replace(handle) {
+            if !log_recording(&cancel, &data_logger, flatten_join(previous.await)).await {
+                break;
+            }
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep_until(started + recorder.recording_time.saturating_sub(overlap)) => {}
+        }
+    }
+    if let Some(last) = in_flight {
+        log_recording(&cancel, &data_logger, flatten_join(last.await)).await;
+    }
+
+    info!("audio_loop: shutdown complete");
+}
+
+type RecordingResult = Result<AudioRecording, Box<dyn Error + Send + Sync>>;
+
+fn flatten_join(result: Result<RecordingResult, tokio::task::JoinError>) -> RecordingResult {
+    result.unwrap_or_else(|e| Err(e.into()))
+}
+
+/// Logs a finished recording. Returns false if the loop should stop.
+async fn log_recording(
+    cancel: &CancellationToken,
+    data_logger: &StorageWriter,
+    result: RecordingResult,
+) -> bool {
+    match result {
+        Ok(rec) => {
+            let path = rec.path.clone();
+            if data_logger.add_audio_entry(rec).is_ok() {
+                info!("audio saved to {:?}", path);
+            }
+        }
+        Err(e) => {
+            if cancel.is_cancelled() {
+                info!("audio_loop: recording cancelled early: {e}");
+                return false;
+            } else {
+                warn!("audio error: {e}");
+            }
+        }
+    }
+    true
+}
+
+async fn meter_loop(
+    cancel: CancellationToken,
+    window: Duration,
+    data_logger: StorageWriter,
+    mut samples: broadcast::Receiver<AudioChunk>,
+    weighting: AudioWeighting,
+) {
+    // Built for the sample rate of the chunks, which is whatever the capture device negotiated
+    let mut meter: Option<LevelMeter> = None;
+    // Recording the meter follows. With overlapping rollover two recordings publish the same
+    // audio, so the meter moves on to the newer one as soon as it starts.
+    let mut recording_start_s = 0;
+    loop {
+        let chunk = tokio::select! {
+            _ = cancel.cancelled() => break,
+            chunk = samples.recv() => chunk,
+        };
+        match chunk {
+            Ok(chunk) => {
+                if chunk.recording_start_s < recording_start_s {
+                    continue;
+                }
+                recording_start_s = chunk.recording_start_s;
+                let meter = match meter.take() {
+                    Some(current) if current.sample_rate() == chunk.sample_rate => meter.insert(current),
+                    _ => meter.insert(LevelMeter::new(chunk.sample_rate, window).with_weighting(weighting)),
+                };
+                for level in meter.push(&chunk.samples) {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                    let start = now.saturating_sub(window).as_secs();
+                    if let Err(e) = data_logger.append_audio_level(start, level) {
+                        warn!("meter_loop: {e}");
+                    }
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("meter_loop: dropped {n} audio chunks");
+                meter = None;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+    info!("meter_loop: shutdown complete");
+}
+
+/// Analyzes the samples published while recording (see [`StreamAnalyzer`]), appending the RMS
+/// windows to the audio entries of their recordings and the band levels to the session, and
+/// logging the sound events detected in them (see [`SoundEventDetector`]). The RMS levels are
+/// weighted by `weighting`. Runs until `done` is cancelled and the samples published before are
+/// analyzed.
+async fn analysis_loop(
+    done: CancellationToken,
+    data_logger: StorageWriter,
+    mut samples: broadcast::Receiver<AudioChunk>,
+    weighting: AudioWeighting,
+) {
+    // Recordings being captured, at most two with overlapping rollover
+    let mut analyzers: Vec<(StreamAnalyzer, SoundEventDetector)> = Vec::new();
+    // Recordings that lost chunks, whose later windows would be misplaced
+    let mut abandoned = HashSet::new();
+    loop {
+        let chunk = tokio::select! {
+            biased;
+            chunk = samples.recv() => chunk,
+            _ = done.cancelled() => break,
+        };
+        match chunk {
+            Ok(chunk) => {
+                if abandoned.contains(&chunk.recording_start_s) {
+                    continue;
+                }
+                let index = match analyzers.iter().position(|(a, _)| a.start_time_s() == chunk.recording_start_s) {
+                    Some(index) => index,
+                    None => {
+                        // The oldest recording has ended when a third one starts
+                        if analyzers.len() == 2 {
+                            let (_, detector) = analyzers.remove(0);
+                            detector.finish().iter().for_each(|event| log_sound_event(&data_logger, event));
+                        }
+                        analyzers.push((
+                            StreamAnalyzer::new(chunk.recording_start_s, chunk.sample_rate).with_weighting(weighting),
+                            SoundEventDetector::new(chunk.recording_start_s, chunk.sample_rate),
+                        ));
+                        analyzers.len() - 1
+                    }
+                };
+                let (analyzer, detector) = &mut analyzers[index];
+                for event in detector.push(&chunk.samples) {
+                    log_sound_event(&data_logger, &event);
+                }
+                for window in analyzer.push(&chunk.samples) {
+                    let result = data_logger
+                        .append_audio_rms(chunk.recording_start_s, vec![window.t_s], vec![window.rms_db])
+                        .and_then(|()| data_logger.append_audio_bands(window.t_s, window.band_db));
+                    if let Err(e) = result {
+                        warn!("analysis_loop: {e}");
+                    }
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("analysis_loop: dropped {n} audio chunks; the rest of the recordings being captured is not analyzed");
+                for (analyzer, detector) in analyzers.drain(..) {
+                    abandoned.insert(analyzer.start_time_s());
+                    detector.finish().iter().for_each(|event| log_sound_event(&data_logger, event));
                 }
             }
-            Err(e) => {
-                if cancel.is_cancelled() {
-                    info!("audio_loop: recording cancelled early: {e}");
-                    break;
-                } else {
-                    warn!("audio error: {e}");
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+    for (_, detector) in analyzers {
+        detector.finish().iter().for_each(|event| log_sound_event(&data_logger, event));
+    }
+    info!("analysis_loop: shutdown complete");
+}
+
+fn log_sound_event(data_logger: &StorageWriter, event: &SoundEvent) {
+    info!("Sound event at {}: {} ({:.1} s, peak {:.0} dBFS)", event.timestamp_s, event.class.label(), event.duration_s, event.peak_db);
+    if let Err(e) = data_logger.add_event(&event.to_event()) {
+        warn!("sound event log error: {e}");
+    }
+}
+
+/// Spawns [`classifier_loop`] with the model of `config.audio.classifier`, if set.
+#[cfg(feature = "ml")]
+fn spawn_classifier(
+    config: &Config,
+    done: CancellationToken,
+    data_logger: StorageWriter,
+    samples: broadcast::Receiver<AudioChunk>,
+) -> Result<Option<JoinHandle<()>>, Box<dyn Error>> {
+    let Some(classifier_config) = &config.audio.classifier else { return Ok(None) };
+    let classifier = SoundClassifier::load(classifier_config)?;
+    info!("Classifying the audio into {:?} with {}", classifier.labels(), classifier_config.model);
+    Ok(Some(tokio::spawn(classifier_loop(done, data_logger, samples, Arc::new(classifier)))))
+}
+
+#[cfg(not(feature = "ml"))]
+fn spawn_classifier(
+    config: &Config,
+    _done: CancellationToken,
+    _data_logger: StorageWriter,
+    _samples: broadcast::Receiver<AudioChunk>,
+) -> Result<Option<JoinHandle<()>>, Box<dyn Error>> {
+    match config.audio.classifier {
+        Some(_) => Err("Sound classification requires the ml feature".into()),
+        None => Ok(None),
+    }
+}
+
+/// Classifies the samples published while recording with `classifier` (see
+/// [`RecordingClassifier`]), logging the changes of class as events. Runs until `done` is
+/// cancelled and the samples published before are classified.
+#[cfg(feature = "ml")]
+async fn classifier_loop(
+    done: CancellationToken,
+    data_logger: StorageWriter,
+    mut samples: broadcast::Receiver<AudioChunk>,
+    classifier: Arc<SoundClassifier>,
+) {
+    // Recordings being captured, at most two with overlapping rollover
+    let mut recordings: Vec<RecordingClassifier> = Vec::new();
+    // Recordings that lost chunks, whose later windows would be misplaced
+    let mut abandoned = HashSet::new();
+    loop {
+        let chunk = tokio::select! {
+            biased;
+            chunk = samples.recv() => chunk,
+            _ = done.cancelled() => break,
+        };
+        match chunk {
+            Ok(chunk) => {
+                if abandoned.contains(&chunk.recording_start_s) {
+                    continue;
                 }
+                let index = match recordings.iter().position(|r| r.start_time_s() == chunk.recording_start_s) {
+                    Some(index) => index,
+                    None => {
+                        // The oldest recording has ended when a third one starts
+                        if recordings.len() == 2 {
+                            recordings.remove(0);
+                        }
+                        recordings.push(RecordingClassifier::new(classifier.clone(), chunk.recording_start_s, chunk.sample_rate));
+                        recordings.len() - 1
+                    }
+                };
+                match recordings[index].push(&chunk.samples) {
+                    Ok(changes) => {
+                        for change in changes {
+                            info!("Sound class at {}: {} ({:.2})", change.timestamp_s, change.label, change.score);
+                            log_event(&data_logger, &change.to_event());
+                        }
+                    }
+                    Err(e) => warn!("classifier_loop: {e}"),
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("classifier_loop: dropped {n} audio chunks; the rest of the recordings being captured is not classified");
+                abandoned.extend(recordings.drain(..).map(|r| r.start_time_s()));
             }
+            Err(broadcast::error::RecvError::Closed) => break,
         }
     }
+    info!("classifier_loop: shutdown complete");
+}
 
-    info!("audio_loop: shutdown complete");
+/// Applies actuator commands until the session ends, then switches all actuators off.
+async fn actuator_loop(
+    cancel: CancellationToken,
+    data_logger: StorageWriter,
+    mut actuators: Actuators,
+    commands: Arc<Mutex<mpsc::UnboundedReceiver<ActuatorCommand>>>,
+) {
+    let mut commands = commands.lock().await;
+    loop {
+        let command = tokio::select! {
+            _ = cancel.cancelled() => break,
+            command = commands.recv() => command,
+        };
+        let Some(command) = command else { break };
+        // The error isn't Send, so it must not be held across the await
+        let event = actuators.set(&command.name, command.on).unwrap_or_else(|e| {
+            warn!("actuator error: {e}");
+            None
+        });
+        if let Some(event) = event {
+            log_actuator_event(&data_logger, &event).await;
+        }
+    }
+    for event in actuators.all_off() {
+        log_actuator_event(&data_logger, &event).await;
+    }
+    info!("actuator_loop: shutdown complete");
+}
+
+async fn log_actuator_event(data_logger: &StorageWriter, event: &ActuatorEvent) {
+    if let Err(e) = data_logger.add_actuator_event(event) {
+        warn!("actuator event log error: {e}");
+    }
+}
+
+async fn event_loop(cancel: CancellationToken, data_logger: StorageWriter, events: Arc<Mutex<mpsc::UnboundedReceiver<Event>>>) {
+    let mut events = events.lock().await;
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.recv() => event,
+        };
+        let Some(event) = event else { break };
+        log_event(&data_logger, &event);
+    }
+    // Keep the events added while stopping
+    while let Ok(event) = events.try_recv() {
+        log_event(&data_logger, &event);
+    }
+    info!("event_loop: shutdown complete");
+}
+
+fn log_event(data_logger: &StorageWriter, event: &Event) {
+    if let Err(e) = data_logger.add_event(event) {
+        warn!("event log error: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hdf5::File as H5File;
+    use test_log::test;
+
+    use crate::audio_analysis::analyze_audio_entries;
+    use crate::data::{H5AudioMetadata, SessionReader, SleepDataLogger};
+
+    const AUDIO_FIXTURE: &str = "test_data/test_audio_48kHz.mp3";
+
+    // Run the audio loop against the loopback source, cancel mid-recording, and check that
+    // every recording (including the one in flight at cancellation, cut short) is logged and
+    // analyzable.
+    #[test(tokio::test)]
+    async fn test_audio_loop_with_loopback_source() {
+        tokio::time::pause();
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+
+        let logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        let group_name = logger.group_name.clone();
+        let (data_logger, writer_thread) = StorageWriter::spawn(Box::new(logger)).expect("Failed to spawn writer");
+        let cancel = CancellationToken::new();
+        let recorder = Arc::new(AudioRecorder::loopback(
+            &format!("{}/{}/audio/", data_path, group_name),
+            Duration::from_secs(10),
+            AUDIO_FIXTURE,
+        ).expect("Failed to create loopback recorder").with_cancel(cancel.clone()));
+
+        let handle = tokio::spawn(audio_loop(cancel.clone(), data_logger.clone(), recorder, None));
+        tokio::time::sleep(Duration::from_millis(25_500)).await;
+        cancel.cancel();
+        handle.await.expect("Audio loop panicked");
+
+        // Close the HDF5 file before re-opening it for analysis
+        drop(data_logger);
+        writer_thread.join().expect("Storage writer panicked");
+
+        analyze_audio_entries(data_path, "sleep_data.h5", &group_name).expect("Failed to analyze audio entries");
+
+        let file = H5File::open(format!("{}/sleep_data.h5", data_path)).unwrap();
+        let entries = file.group(&group_name).unwrap()
+            .dataset("audio").unwrap()
+            .read_1d::<H5AudioMetadata>().unwrap();
+        assert_eq!(entries.len(), 3, "Expected 3 recordings, got {}", entries.len());
+        // Cancelled 5.5 s into the third recording (durations are whole seconds)
+        for (entry, duration_s) in entries.iter().zip([10, 10, 5]) {
+            assert_eq!(entry.duration_s, duration_s);
+            let recorded = hound::WavReader::open(entry.path.as_str()).expect("Failed to open recording");
+            assert_eq!(recorded.duration() as u64 / recorded.spec().sample_rate as u64, duration_s);
+            // Analyzed in 5 s windows
+            assert_eq!(entry.audio_rms_db.len() as u64, duration_s / 5);
+            assert_eq!(entry.audio_rms_t_s.len(), entry.audio_rms_db.len());
+        }
+        drop(file);
+        let band_levels = || {
+            let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+            session.audio_band_levels().expect("Failed to read band levels")
+        };
+        let (band_times, levels) = band_levels();
+        assert!(!band_times.is_empty());
+        assert_eq!(levels.len(), band_times.len());
+
+        // Analyzing again computes nothing twice
+        analyze_audio_entries(data_path, "sleep_data.h5", &group_name).expect("Failed to analyze audio entries");
+        assert_eq!(band_levels().0, band_times);
+    }
+
+    // Recordings analyzed live get their RMS windows and band levels while they are captured,
+    // including those of overlapping recordings, and the offline analysis leaves them alone.
+    #[test(tokio::test)]
+    async fn test_analysis_loop() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+
+        let logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        let group_name = logger.group_name.clone();
+        let (data_logger, writer_thread) = StorageWriter::spawn(Box::new(logger)).expect("Failed to spawn writer");
+        let (samples, receiver) = broadcast::channel(64);
+        let done = CancellationToken::new();
+        let handle = tokio::spawn(analysis_loop(done.clone(), data_logger.clone(), receiver, AudioWeighting::Z));
+
+        // 12 s of the first recording, overlapped by 6 s of the second, in 100 ms chunks
+        let chunk = |recording_start_s| AudioChunk { recording_start_s, sample_rate: 48_000, samples: vec![1_000i16; 4_800].into() };
+        for i in 0..120 {
+            samples.send(chunk(100)).unwrap();
+            if i >= 60 {
+                samples.send(chunk(106)).unwrap();
+            }
+            // Don't outrun the analysis loop
+            tokio::task::yield_now().await;
+        }
+        done.cancel();
+        handle.await.expect("Analysis loop panicked");
+        for start_time_s in [100, 106] {
+            data_logger.add_audio_entry(AudioRecording {
+                path: format!("{data_path}/audio_{start_time_s}.mp3"),
+                duration: Duration::from_secs(12),
+                start_time_s,
+            }).expect("Failed to add audio entry");
+        }
+        drop(data_logger);
+        writer_thread.join().expect("Storage writer panicked");
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let entries = session.audio_entries().expect("Failed to read audio entries");
+        assert_eq!(entries[0].audio_rms_t_s.as_slice(), [100, 105]);
+        assert_eq!(entries[1].audio_rms_t_s.as_slice(), [106]);
+        let (band_times, levels) = session.audio_band_levels().expect("Failed to read band levels");
+        let mut sorted_times = band_times.clone();
+        sorted_times.sort();
+        assert_eq!(sorted_times, [100, 105, 106]);
+        assert_eq!(levels.len(), 3);
+        drop(session);
+
+        // Nothing is left to decode (the recordings don't exist)
+        analyze_audio_entries(data_path, "sleep_data.h5", &group_name).expect("Failed to analyze audio entries");
+    }
+
+    // The live levels are measured in windows of the chunks' sample rate, which can change, and
+    // in only one of two overlapping recordings.
+    #[test(tokio::test)]
+    async fn test_meter_loop() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+
+        let logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        let group_name = logger.group_name.clone();
+        let (data_logger, writer_thread) = StorageWriter::spawn(Box::new(logger)).expect("Failed to spawn writer");
+        let (samples, receiver) = broadcast::channel(64);
+        let handle = tokio::spawn(meter_loop(CancellationToken::new(), Duration::from_secs(1), data_logger.clone(), receiver, AudioWeighting::Z));
+
+        // 2 s at 44.1 kHz, then 1 s at 48 kHz, in 100 ms chunks
+        let chunk = |recording_start_s, sample_rate: u32| AudioChunk {
+            recording_start_s,
+            sample_rate,
+            samples: vec![1_000i16; sample_rate as usize / 10].into(),
+        };
+        for i in 0..30 {
+            samples.send(chunk(100, if i < 20 { 44_100 } else { 48_000 })).unwrap();
+            // Don't outrun the meter loop
+            tokio::task::yield_now().await;
+        }
+        // 3 s of a recording overlapped by the next one for its last second, which goes on for 2 s
+        for i in 0..40 {
+            if i < 30 {
+                samples.send(chunk(103, 48_000)).unwrap();
+            }
+            if i >= 20 {
+                samples.send(chunk(105, 48_000)).unwrap();
+            }
+            tokio::task::yield_now().await;
+        }
+        drop(samples);
+        handle.await.expect("Meter loop panicked");
+        drop(data_logger);
+        writer_thread.join().expect("Storage writer panicked");
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let (times, levels) = session.live_audio_levels().expect("Failed to read live levels");
+        // 7 s of audio, the overlap measured once
+        assert_eq!(times.len(), 7);
+        // 1000 counts is -30.3 dBFS
+        assert!(levels.iter().all(|level| (level + 30.3).abs() < 0.1), "{:?}", levels);
+    }
+
+    // With overlapping rollover each segment starts before the previous one ends, and the
+    // segments in flight at cancellation are still logged, cut short.
+    #[test(tokio::test)]
+    async fn test_overlapping_audio_loop() {
+        tokio::time::pause();
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+
+        let logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        let group_name = logger.group_name.clone();
+        let (data_logger, writer_thread) = StorageWriter::spawn(Box::new(logger)).expect("Failed to spawn writer");
+        let cancel = CancellationToken::new();
+        let recorder = Arc::new(AudioRecorder::loopback(
+            &format!("{}/{}/audio/", data_path, group_name),
+            Duration::from_secs(2),
+            AUDIO_FIXTURE,
+        ).expect("Failed to create loopback recorder").with_cancel(cancel.clone()));
+
+        let overlap = Some(Duration::from_secs(1));
+        let handle = tokio::spawn(audio_loop(cancel.clone(), data_logger.clone(), recorder, overlap));
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        cancel.cancel();
+        handle.await.expect("Audio loop panicked");
+        drop(data_logger);
+        writer_thread.join().expect("Storage writer panicked");
+
+        let session = SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        let entries = session.audio_entries().expect("Failed to read audio entries");
+        assert_eq!(entries.len(), 3, "Expected 3 recordings, got {}", entries.len());
+        for pair in entries.windows(2) {
+            // Next segment starts 1 s into the 2 s segment before it
+            assert_eq!(pair[1].start_time_s - pair[0].start_time_s, 1);
+        }
+        // Cancelled 2.5 s in: the second segment 1.5 s in, the third 0.5 s in (whole seconds)
+        let durations: Vec<u64> = entries.iter().map(|entry| entry.duration_s).collect();
+        assert_eq!(durations, [2, 1, 0]);
+    }
 }
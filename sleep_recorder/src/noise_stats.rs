@@ -0,0 +1,256 @@
+//! Noise statistics of a night, from the RMS levels of its recordings.
+//!
+//! The percentile levels L10, L50, and L90 are the levels exceeded in 10, 50, and 90 % of the
+//! analyzed windows: L90 is the background noise, L50 the median, and L10 the recurring loud
+//! noises. A noise event is a run of consecutive windows above a threshold, and the quietest hour
+//! is the clock hour with the lowest equivalent (energy-averaged) level, among those with levels
+//! for at least half of it.
+//!
+//! The levels are in dBFS, weighted as the session's audio (see
+//! [`SessionMetadata::audio_weighting`](crate::data::SessionMetadata::audio_weighting)). The
+//! statistics are stored as attributes of the session group (see [`write_noise_stats`]) and read
+//! with [`SessionReader::noise_stats`](crate::data::SessionReader::noise_stats).
+
+use std::error::Error;
+
+use hdf5::{File as H5File, H5Type};
+use tracing::info;
+
+use crate::audio_analysis::ANALYSIS_WINDOW_S;
+use crate::data::{session_path, SessionReader};
+
+/// Level from which a window is part of a noise event by default, in dBFS.
+pub const DEFAULT_NOISE_EVENT_DB: f32 = -35.0;
+
+/// Length of the span the quietest hour is chosen among, in seconds.
+const HOUR_S: u64 = 3600;
+
+/// Noise statistics of a session (see the [module documentation](self)).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseStats {
+    /// Level exceeded in 10 % of the windows, in dBFS.
+    pub l10_db: f32,
+    /// Level exceeded in half of the windows, in dBFS.
+    pub l50_db: f32,
+    /// Level exceeded in 90 % of the windows, in dBFS.
+    pub l90_db: f32,
+    /// Level from which a window is part of a noise event, in dBFS.
+    pub event_threshold_db: f32,
+    /// Number of noise events.
+    pub noise_events: u32,
+    /// Start of the quietest hour, in seconds since UNIX epoch.
+    pub quietest_hour_s: u64,
+    /// Equivalent level of the quietest hour, in dBFS.
+    pub quietest_hour_db: f32,
+}
+
+impl NoiseStats {
+    /// Computes the statistics of the windows starting at `times` (s since UNIX epoch) with the
+    /// RMS `levels` (dBFS), counting the noise events from `event_threshold_db`. Windows without a
+    /// level (`NAN`) are left out, and windows repeated by overlapping recordings counted once.
+    /// `None` if there is no level.
+    pub fn compute(times: &[u64], levels: &[f32], event_threshold_db: f32) -> Option<Self> {
+        let mut windows: Vec<(u64, f32)> = times.iter().copied()
+            .zip(levels.iter().copied())
+            .filter(|(_, level)| !level.is_nan())
+            .collect();
+        windows.sort_by_key(|(t_s, _)| *t_s);
+        windows.dedup_by_key(|(t_s, _)| *t_s);
+        if windows.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<f32> = windows.iter().map(|(_, level)| *level).collect();
+        sorted.sort_by(f32::total_cmp);
+        // Nearest rank: the level exceeded in `percent` % of the windows
+        let exceeded = |percent: usize| sorted[(sorted.len() * (100 - percent)).div_ceil(100).max(1) - 1];
+
+        let mut noise_events = 0;
+        let mut previous: Option<(u64, bool)> = None;
+        for &(t_s, level) in &windows {
+            let loud = level >= event_threshold_db;
+            let continues = matches!(previous, Some((previous_s, true)) if t_s - previous_s <= ANALYSIS_WINDOW_S);
+            if loud && !continues {
+                noise_events += 1;
+            }
+            previous = Some((t_s, loud));
+        }
+
+        // Energy sum and number of windows of each clock hour, in time order
+        let mut hours: Vec<(u64, f64, u64)> = Vec::new();
+        for &(t_s, level) in &windows {
+            let hour_s = t_s - t_s % HOUR_S;
+            let energy = 10f64.powf(level as f64 / 10.0);
+            match hours.last_mut() {
+                Some((last_s, sum, count)) if *last_s == hour_s => {
+                    *sum += energy;
+                    *count += 1;
+                }
+                _ => hours.push((hour_s, energy, 1)),
+            }
+        }
+        // Sessions shorter than half an hour still have a quietest hour
+        let covered = |count: u64| count * ANALYSIS_WINDOW_S * 2 >= HOUR_S;
+        if hours.iter().any(|(_, _, count)| covered(*count)) {
+            hours.retain(|(_, _, count)| covered(*count));
+        }
+        let (quietest_hour_s, quietest_hour_db) = hours.iter()
+            .map(|(hour_s, sum, count)| (*hour_s, (10.0 * (sum / *count as f64).log10()) as f32))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+        Some(Self {
+            l10_db: exceeded(10),
+            l50_db: exceeded(50),
+            l90_db: exceeded(90),
+            event_threshold_db,
+            noise_events,
+            quietest_hour_s,
+            quietest_hour_db,
+        })
+    }
+
+    /// Writes the statistics as attributes of `group`, replacing earlier ones.
+    pub(crate) fn write_attrs(&self, group: &hdf5::Group) -> hdf5::Result<()> {
+        write_attr(group, "noise_l10_db", self.l10_db)?;
+        write_attr(group, "noise_l50_db", self.l50_db)?;
+        write_attr(group, "noise_l90_db", self.l90_db)?;
+        write_attr(group, "noise_event_threshold_db", self.event_threshold_db)?;
+        write_attr(group, "noise_events", self.noise_events)?;
+        write_attr(group, "quietest_hour_s", self.quietest_hour_s)?;
+        write_attr(group, "quietest_hour_db", self.quietest_hour_db)
+    }
+
+    /// Reads the statistics written by [`write_attrs`](Self::write_attrs) from `group`, or `None`
+    /// if they weren't.
+    pub(crate) fn read_attrs(group: &hdf5::Group) -> hdf5::Result<Option<Self>> {
+        if group.attr("noise_l50_db").is_err() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            l10_db: group.attr("noise_l10_db")?.read_scalar()?,
+            l50_db: group.attr("noise_l50_db")?.read_scalar()?,
+            l90_db: group.attr("noise_l90_db")?.read_scalar()?,
+            event_threshold_db: group.attr("noise_event_threshold_db")?.read_scalar()?,
+            noise_events: group.attr("noise_events")?.read_scalar()?,
+            quietest_hour_s: group.attr("quietest_hour_s")?.read_scalar()?,
+            quietest_hour_db: group.attr("quietest_hour_db")?.read_scalar()?,
+        }))
+    }
+}
+
+fn write_attr<T: H5Type>(group: &hdf5::Group, name: &str, value: T) -> hdf5::Result<()> {
+    let attr = match group.attr(name) {
+        Ok(attr) => attr,
+        Err(_) => group.new_attr::<T>().create(name)?,
+    };
+    attr.write_scalar(&value)
+}
+
+/// Computes the noise statistics of `group_name` in the HDF5 file at `data_path/file_name` from
+/// the RMS levels of its `audio` entries (see
+/// [`analyze_audio_entries`](crate::audio_analysis::analyze_audio_entries)), and stores them as
+/// attributes of the session group. Returns `None`, storing nothing, if no recording has levels.
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be read, or the attributes written.
+#[tracing::instrument()]
+pub fn write_noise_stats(data_path: &str, file_name: &str, group_name: &str, event_threshold_db: f32) -> Result<Option<NoiseStats>, Box<dyn Error>> {
+    let entries = SessionReader::open(data_path, file_name, group_name)?.audio_entries()?;
+    let times: Vec<u64> = entries.iter().flat_map(|entry| entry.audio_rms_t_s.iter().copied()).collect();
+    let levels: Vec<f32> = entries.iter().flat_map(|entry| entry.audio_rms_db.iter().copied()).collect();
+    let Some(stats) = NoiseStats::compute(&times, &levels, event_threshold_db) else {
+        info!("No audio levels in {}; skipping noise statistics", group_name);
+        return Ok(None);
+    };
+    let file = H5File::append(session_path(data_path, file_name, group_name))?;
+    stats.write_attrs(&file.group(group_name)?)?;
+    file.flush()?;
+    info!("Noise of {}: L10 {:.1}, L50 {:.1}, L90 {:.1} dBFS, {} events", group_name, stats.l10_db, stats.l50_db, stats.l90_db, stats.noise_events);
+    Ok(Some(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    use crate::data::{AudioRecording, SleepDataLogger};
+    use crate::storage::StorageBackend;
+    use std::time::Duration;
+
+    // Two hours and ten minutes of windows: a quiet first hour at -60 dBFS, then -50 dBFS with
+    // two 15 s noises and a 5 s noise.
+    fn night() -> (Vec<u64>, Vec<f32>) {
+        let start_s = 1_745_877_600;
+        let times: Vec<u64> = (0..(2 * HOUR_S + 600) / ANALYSIS_WINDOW_S).map(|i| start_s + i * ANALYSIS_WINDOW_S).collect();
+        let levels = times.iter()
+            .map(|t_s| match t_s - start_s {
+                0..3600 => -60.0,
+                4000..4015 | 5000..5005 | 6000..6015 => -20.0,
+                _ => -50.0,
+            })
+            .collect();
+        (times, levels)
+    }
+
+    #[test]
+    fn test_compute() {
+        let (times, levels) = night();
+        let stats = NoiseStats::compute(&times, &levels, DEFAULT_NOISE_EVENT_DB).unwrap();
+        assert_eq!((stats.l10_db, stats.l50_db, stats.l90_db), (-50.0, -50.0, -60.0));
+        assert_eq!(stats.noise_events, 3);
+        assert_eq!((stats.quietest_hour_s, stats.quietest_hour_db), (1_745_877_600, -60.0));
+
+        // A gap splits a noise, and levels without a value or repeated by an overlap are left out
+        let (mut times, mut levels) = night();
+        let gap = times.iter().position(|t_s| *t_s == 1_745_877_600 + 6005).unwrap();
+        times.remove(gap);
+        levels.remove(gap);
+        times.extend([1_745_877_600 + 4000, 1_745_877_600 + 100]);
+        levels.extend([-20.0, f32::NAN]);
+        assert_eq!(NoiseStats::compute(&times, &levels, DEFAULT_NOISE_EVENT_DB).unwrap().noise_events, 4);
+
+        assert_eq!(NoiseStats::compute(&[], &[], DEFAULT_NOISE_EVENT_DB), None);
+        assert_eq!(NoiseStats::compute(&[0], &[f32::NAN], DEFAULT_NOISE_EVENT_DB), None);
+    }
+
+    // A partly covered hour isn't the quietest, unless no hour is half covered.
+    #[test]
+    fn test_quietest_hour_coverage() {
+        let times = [0, 5, 3600, 3605, 3610, 3615];
+        let levels = [-70.0, -70.0, -40.0, -40.0, -40.0, -40.0];
+        assert_eq!(NoiseStats::compute(&times, &levels, 0.0).unwrap().quietest_hour_s, 0);
+
+        let times: Vec<u64> = (0..HOUR_S / ANALYSIS_WINDOW_S).map(|i| 3600 + i * ANALYSIS_WINDOW_S).chain([0, 5]).collect();
+        let levels: Vec<f32> = times.iter().map(|t_s| if *t_s < 3600 { -70.0 } else { -40.0 }).collect();
+        assert_eq!(NoiseStats::compute(&times, &levels, 0.0).unwrap().quietest_hour_s, 3600);
+    }
+
+    #[test]
+    fn test_write_noise_stats() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SleepDataLogger::new(data_path, "sleep_data.h5").expect("Failed to create logger");
+        let group_name = logger.session_name().to_string();
+        let (times, levels) = night();
+        logger.add_audio_entry(AudioRecording {
+            path: "audio_1745877600.wav".to_string(),
+            duration: Duration::from_secs(2 * HOUR_S + 600),
+            start_time_s: times[0],
+        }).expect("Failed to add audio entry");
+        logger.append_audio_rms(times[0], &times, &levels).expect("Failed to append RMS");
+        drop(logger);
+
+        let session = || SessionReader::open(data_path, "sleep_data.h5", &group_name).expect("Failed to open session");
+        assert_eq!(session().noise_stats().expect("Failed to read noise stats"), None);
+        let stats = write_noise_stats(data_path, "sleep_data.h5", &group_name, DEFAULT_NOISE_EVENT_DB)
+            .expect("Failed to write noise stats")
+            .expect("No noise stats");
+        assert_eq!(session().noise_stats().expect("Failed to read noise stats"), Some(stats));
+        // Computed again, the statistics replace the earlier ones
+        let stats = write_noise_stats(data_path, "sleep_data.h5", &group_name, -55.0).unwrap().unwrap();
+        assert_eq!(stats.noise_events, 1);
+        assert_eq!(session().noise_stats().unwrap(), Some(stats));
+    }
+}
@@ -0,0 +1,129 @@
+//! Minimal driver for the Plantower PMS5003 particulate matter sensor (UART, 9600 baud).
+//!
+//! The sensor is switched to passive mode so that a fresh reading is requested on every poll,
+//! instead of parsing the stream of frames it sends by default.
+
+use std::error::Error;
+use std::io::Read;
+use std::time::Duration;
+
+use serialport::{ClearBuffer, SerialPort};
+
+/// Start bytes of every frame, in both directions.
+const START: [u8; 2] = [0x42, 0x4D];
+/// Length of a data frame, including start bytes, length, and checksum.
+const FRAME_LEN: usize = 32;
+
+const CMD_CHANGE_MODE: u8 = 0xE1;
+const CMD_READ_PASSIVE: u8 = 0xE2;
+const MODE_PASSIVE: u16 = 0x0000;
+
+/// Particulate matter concentrations in µg/m³, using the atmospheric-environment values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PmMeasurement {
+    /// PM1.0 concentration.
+    pub pm1_0: u16,
+    /// PM2.5 concentration.
+    pub pm2_5: u16,
+    /// PM10 concentration.
+    pub pm10: u16,
+}
+
+/// PMS5003 in passive mode.
+pub struct Pms5003 {
+    port: Box<dyn SerialPort>,
+}
+
+impl Pms5003 {
+    /// Opens the sensor on the given serial port and switches it to passive mode.
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let port = serialport::new(path, 9600)
+            .timeout(Duration::from_millis(1500))
+            .open()
+            .map_err(|e| format!("Failed to open PMS5003 serial port {}: {}", path, e))?;
+        let mut pms = Self { port };
+        pms.command(CMD_CHANGE_MODE, MODE_PASSIVE)?;
+        // Let the sensor acknowledge, then drop the acknowledgement and any active-mode frames
+        std::thread::sleep(Duration::from_millis(100));
+        pms.port.clear(ClearBuffer::Input)?;
+        Ok(pms)
+    }
+
+    /// Requests and reads a measurement.
+    pub fn read(&mut self) -> Result<PmMeasurement, Box<dyn Error>> {
+        self.port.clear(ClearBuffer::Input)?;
+        self.command(CMD_READ_PASSIVE, 0)?;
+        read_frame(&mut self.port)
+    }
+
+    fn command(&mut self, command: u8, data: u16) -> Result<(), Box<dyn Error>> {
+        let [data_h, data_l] = data.to_be_bytes();
+        let mut frame = vec![START[0], START[1], command, data_h, data_l];
+        frame.extend_from_slice(&checksum(&frame).to_be_bytes());
+        self.port.write_all(&frame)?;
+        self.port.flush()?;
+        Ok(())
+    }
+}
+
+/// Sum of all bytes, as used by both command and data frames.
+fn checksum(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |acc, &b| acc.wrapping_add(b as u16))
+}
+
+/// Reads bytes until a complete data frame with a valid checksum has been read.
+fn read_frame(reader: &mut impl Read) -> Result<PmMeasurement, Box<dyn Error>> {
+    let mut frame = [0u8; FRAME_LEN];
+    // Synchronize on the start bytes
+    let mut previous = 0u8;
+    loop {
+        reader.read_exact(&mut frame[..1])?;
+        if previous == START[0] && frame[0] == START[1] {
+            break;
+        }
+        previous = frame[0];
+    }
+    frame[..2].copy_from_slice(&START);
+    reader.read_exact(&mut frame[2..])?;
+
+    let word = |i: usize| u16::from_be_bytes([frame[2 * i], frame[2 * i + 1]]);
+    if word(1) as usize != FRAME_LEN - 4 {
+        return Err(format!("Unexpected PMS5003 frame length {}", word(1)).into());
+    }
+    if checksum(&frame[..FRAME_LEN - 2]) != word(FRAME_LEN / 2 - 1) {
+        return Err("PMS5003 checksum mismatch".into());
+    }
+    // Words 2-4 are the factory (CF=1) values, 5-7 the atmospheric-environment values
+    Ok(PmMeasurement { pm1_0: word(5), pm2_5: word(6), pm10: word(7) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn frame(words: [u16; 13]) -> Vec<u8> {
+        let mut frame = START.to_vec();
+        frame.extend_from_slice(&28u16.to_be_bytes());
+        for word in words {
+            frame.extend_from_slice(&word.to_be_bytes());
+        }
+        frame.extend_from_slice(&checksum(&frame).to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_read_frame_after_garbage() {
+        let mut bytes = vec![0x00, 0x42, 0x13];
+        bytes.extend(frame([5, 9, 12, 4, 8, 11, 900, 260, 40, 3, 1, 0, 0]));
+        let measurement = read_frame(&mut bytes.as_slice()).expect("Failed to read frame");
+        assert_eq!(measurement, PmMeasurement { pm1_0: 4, pm2_5: 8, pm10: 11 });
+    }
+
+    #[test]
+    fn test_read_frame_checksum_mismatch() {
+        let mut bytes = frame([5, 9, 12, 4, 8, 11, 900, 260, 40, 3, 1, 0, 0]);
+        bytes[10] ^= 0x01;
+        assert!(read_frame(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,359 @@
+//! Copy of the recorded data on a remote server, configured with [`Config::remote`].
+//!
+//! With `remote.url` set, [`storage::open`](crate::storage::open) pairs the data file's backend
+//! with a [`RemoteSink`] (see [`Tee`](crate::storage::Tee)). The sink collects what is written
+//! into batches of `remote.batch_size` samples, and posts each batch as a JSON object:
+//!
+//! ```json
+//! {
+//!   "session": "2025-04-28_22-47-31",
+//!   "metadata": { "device_id": "pi", ... },
+//!   "samples": [{ "timestamp_s": 1745873251, "temperature_c": 21.5, ... }],
+//!   "audio_levels": [[1745873251, -48.5]],
+//!   "audio_recordings": [{ "path": "...", "start_time_s": 1745873251, "duration_s": 600.0 }],
+//!   "actuator_events": [{ "timestamp_s": 1745873251, "name": "fan", "on": true }],
+//!   "events": [{ "timestamp_s": 1745873251, "category": "bedtime", "text": "went to bed" }]
+//! }
+//! ```
+//!
+//! `metadata` is only set in the first batch of a new session, and NaN values are `null`. A
+//! request succeeds if the server answers with a 2xx status.
+//!
+//! Each batch is first written to the `remote_spool/` directory in `data_path`, and deleted once
+//! the server accepted it. While the server is unreachable the batches pile up there, and are
+//! sent in order once it is back, also by the next session if the recorder is restarted.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::audio_analysis::BANDS;
+use crate::config::Config;
+use crate::data::{ActuatorEvent, AudioRecording, Event, SessionMetadata, SleepData, SleepField};
+use crate::storage::StorageBackend;
+
+/// Directory in `data_path` holding the batches not sent yet.
+pub const SPOOL_DIRECTORY: &str = "remote_spool";
+
+/// An audio recording as sent to the server.
+#[derive(Debug, Serialize)]
+struct RemoteAudioRecording {
+    path: String,
+    start_time_s: u64,
+    duration_s: f64,
+}
+
+/// The data written since the previous batch.
+#[derive(Debug, Default, Serialize)]
+struct Batch {
+    session: String,
+    metadata: Option<SessionMetadata>,
+    samples: Vec<SleepData>,
+    audio_levels: Vec<(u64, f32)>,
+    audio_recordings: Vec<RemoteAudioRecording>,
+    actuator_events: Vec<ActuatorEvent>,
+    events: Vec<Event>,
+}
+
+impl Batch {
+    fn new(session: &str) -> Self {
+        Self { session: session.to_string(), ..Self::default() }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.metadata.is_none()
+            && self.samples.is_empty()
+            && self.audio_levels.is_empty()
+            && self.audio_recordings.is_empty()
+            && self.actuator_events.is_empty()
+            && self.events.is_empty()
+    }
+}
+
+/// Secondary [`StorageBackend`] posting the session to a remote server in batches.
+///
+/// Batches are spooled to disk before they are sent, so that none is lost while the server is
+/// unreachable or the recorder restarts. After a failed request, the spooled batches are retried
+/// with the next batch once `remote.retry_s` has passed.
+#[derive(Debug)]
+pub struct RemoteSink {
+    agent: ureq::Agent,
+    url: String,
+    token: String,
+    batch_size: usize,
+    retry_interval: Duration,
+    spool: PathBuf,
+    batch: Batch,
+    /// Sequence number of the next spooled batch, to keep the file names unique and in order when
+    /// batches are spooled within the same millisecond.
+    spooled: u64,
+    retry_at: Option<Instant>,
+}
+
+impl RemoteSink {
+    /// Creates a sink for `session` posting to the server in `config.remote`, spooling batches in
+    /// the [`SPOOL_DIRECTORY`] of `config.data_path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the remote copy is disabled (`remote.url` is empty), or the spool
+    /// directory cannot be created.
+    pub fn new(config: &Config, session: &str) -> Result<Self, Box<dyn Error>> {
+        let remote = &config.remote;
+        if !remote.is_enabled() {
+            return Err("remote.url is not set".into());
+        }
+        let spool = Path::new(&config.data_path).join(SPOOL_DIRECTORY);
+        fs::create_dir_all(&spool)
+            .map_err(|e| format!("Failed to create spool directory {}: {}", spool.display(), e))?;
+        // Batches left by an earlier sink may have been spooled in the same millisecond
+        let spooled = fs::read_dir(&spool)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                name.strip_suffix(".json")?.split_once('-')?.1.parse::<u64>().ok()
+            })
+            .max()
+            .map_or(0, |last| last + 1);
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(remote.timeout_s)))
+            .build()
+            .into();
+        Ok(Self {
+            agent,
+            url: remote.url.clone(),
+            token: remote.token.clone(),
+            batch_size: remote.batch_size,
+            retry_interval: Duration::from_secs(remote.retry_s),
+            spool,
+            batch: Batch::new(session),
+            spooled,
+            retry_at: None,
+        })
+    }
+
+    /// Spools the current batch, if it has anything, and sends the spooled batches.
+    fn send_batch(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.batch.is_empty() {
+            let next = Batch::new(&self.batch.session);
+            let batch = std::mem::replace(&mut self.batch, next);
+            let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            // Zero-padded, so that the names sort in the order the batches were spooled
+            let path = self.spool.join(format!("{:015}-{:06}.json", now_ms, self.spooled));
+            fs::write(&path, serde_json::to_vec(&batch)?)
+                .map_err(|e| format!("Failed to spool batch {}: {}", path.display(), e))?;
+            self.spooled += 1;
+        }
+        self.send_spooled()
+    }
+
+    /// Posts the spooled batches in order, deleting each once the server accepted it. Stops at
+    /// the first failure, and waits until the retry interval has passed before trying again.
+    fn send_spooled(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return Ok(());
+        }
+        let mut paths: Vec<PathBuf> = fs::read_dir(&self.spool)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .collect();
+        paths.sort();
+        for (sent, path) in paths.iter().enumerate() {
+            if let Err(e) = self.post(&fs::read(path)?) {
+                self.retry_at = Some(Instant::now() + self.retry_interval);
+                return Err(format!("{} ({} batches spooled)", e, paths.len() - sent).into());
+            }
+            fs::remove_file(path)?;
+        }
+        if self.retry_at.take().is_some() {
+            info!("Sent {} spooled batches to {}", paths.len(), self.url);
+        }
+        Ok(())
+    }
+
+    fn post(&self, body: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut request = self.agent.post(&self.url).content_type("application/json");
+        if !self.token.is_empty() {
+            request = request.header("Authorization", &format!("Bearer {}", self.token));
+        }
+        request.send(body).map_err(|e| format!("Failed to post batch to {}: {}", self.url, e))?;
+        Ok(())
+    }
+}
+
+impl StorageBackend for RemoteSink {
+    fn session_name(&self) -> &str {
+        &self.batch.session
+    }
+
+    fn is_resumed(&self) -> bool {
+        false
+    }
+
+    fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+        self.batch.metadata = Some(metadata.clone());
+        Ok(())
+    }
+
+    fn register_field(&mut self, _name: &'static str, _field: SleepField) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_camera(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_probe(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_thermistor(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn register_sensor(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+        self.batch.samples.push(sample);
+        if self.batch.samples.len() >= self.batch_size {
+            self.send_batch()?;
+        }
+        Ok(())
+    }
+
+    fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        self.batch.audio_recordings.push(RemoteAudioRecording {
+            path: audio_recording.path,
+            start_time_s: audio_recording.start_time_s,
+            duration_s: audio_recording.duration.as_secs_f64(),
+        });
+        Ok(())
+    }
+
+    fn append_audio_rms(&mut self, _start_time_s: u64, _rms_t_s: &[u64], _rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        self.batch.actuator_events.push(event.clone());
+        Ok(())
+    }
+
+    fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.batch.events.push(event.clone());
+        Ok(())
+    }
+
+    fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32) {
+        self.batch.audio_levels.push((timestamp_s, rms_db));
+    }
+
+    fn append_audio_bands(&mut self, _timestamp_s: u64, _levels_db: [f32; BANDS.len()]) {}
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        // Try once more at the end of the session; what fails stays spooled for the next one
+        self.retry_at = None;
+        self.send_batch()
+    }
+}
+
+impl Drop for RemoteSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Failed to send the last batches to {}; they stay spooled: {}", self.url, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use test_log::test;
+
+    /// Serves `requests` HTTP requests on a local port, answering 204 and sending each body to the
+    /// returned receiver.
+    fn serve(requests: usize) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/batches", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                sender.send(String::from_utf8(body).unwrap()).unwrap();
+                reader.get_mut().write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn test_batches_are_spooled_until_sent() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let mut config = Config::new(dir.path().to_str().unwrap());
+        // Nothing listens on the discard port
+        config.remote.url = "http://127.0.0.1:9/batches".to_string();
+        config.remote.batch_size = 2;
+        config.remote.retry_s = 0;
+        let spool = dir.path().join(SPOOL_DIRECTORY);
+        let spooled = || fs::read_dir(&spool).unwrap().count();
+
+        let mut sink = RemoteSink::new(&config, "2025-04-28_22-47-31").expect("Failed to create sink");
+        sink.write_metadata(&SessionMetadata { device_id: "pi".to_string(), ..SessionMetadata::default() }).unwrap();
+        sink.append(SleepData::builder(10).with_climate(21.5, f32::NAN, 40.0).build()).unwrap();
+        sink.append_audio_level(10, -48.5);
+        assert_eq!(spooled(), 0);
+        assert!(sink.append(SleepData::builder(15).build()).is_err());
+        assert_eq!(spooled(), 1);
+        sink.add_actuator_event(&ActuatorEvent { timestamp_s: 17, name: "fan".to_string(), on: true }).unwrap();
+        sink.add_event(&Event { timestamp_s: 18, category: "alarm".to_string(), text: String::new() }).unwrap();
+        assert!(sink.flush().is_err());
+        assert_eq!(spooled(), 2);
+        drop(sink);
+
+        // The next sink sends the spooled batches first, in order
+        let (url, bodies) = serve(3);
+        config.remote.url = url;
+        let mut sink = RemoteSink::new(&config, "2025-04-29_22-00-00").expect("Failed to create sink");
+        sink.append(SleepData::builder(20).build()).unwrap();
+        sink.flush().expect("Failed to send batches");
+        assert_eq!(spooled(), 0);
+
+        let batches: Vec<serde_json::Value> = bodies.iter().take(3).map(|body| serde_json::from_str(&body).unwrap()).collect();
+        assert_eq!(batches[0]["session"], "2025-04-28_22-47-31");
+        assert_eq!(batches[0]["metadata"]["device_id"], "pi");
+        assert_eq!(batches[0]["samples"][0]["temperature_c"], 21.5);
+        assert!(batches[0]["samples"][0]["pressure"].is_null());
+        assert_eq!(batches[0]["audio_levels"][0][1], -48.5);
+        assert_eq!(batches[1]["actuator_events"][0]["name"], "fan");
+        assert_eq!(batches[1]["events"][0]["category"], "alarm");
+        assert!(batches[1]["metadata"].is_null());
+        assert_eq!(batches[2]["session"], "2025-04-29_22-00-00");
+        assert_eq!(batches[2]["samples"][0]["timestamp_s"], 20);
+    }
+}
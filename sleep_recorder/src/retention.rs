@@ -0,0 +1,232 @@
+//! Pruning of the media files of past sessions, configured with [`Config::retention`].
+//!
+//! Images and audio are stored in the `images/` and `audio/` directories of each session's
+//! directory in `data_path` (images may also be archived in `images.tar`, see
+//! [`archive_images`](crate::image_analysis::archive_images), and clips of the audio cut around
+//! events in `events/`, see [`extract_event_clips`](crate::audio_analysis::extract_event_clips)),
+//! and dominate the disk usage. Once they are older than the retention period they are deleted,
+//! and the datasets (or, with SQLite storage, the `purged_media` table) referencing them are
+//! marked as purged. The data file itself is never pruned.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::data::session_start;
+use crate::storage;
+
+/// Kind of media files stored with a session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Media {
+    /// Images, raw images, and motion clips of all cameras.
+    Images,
+    /// Audio recordings, and the clips cut from them around events.
+    Audio,
+}
+
+impl Media {
+    /// Name of the directory of the session storing these files, also used to name them in
+    /// the data file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Media::Images => "images",
+            Media::Audio => "audio",
+        }
+    }
+
+    /// Other directories of the session storing copies of these files.
+    fn copies(self) -> &'static [&'static str] {
+        match self {
+            Media::Images => &[],
+            Media::Audio => &["events"],
+        }
+    }
+
+    /// Name of the archive of these files in the session directory, if they can be archived.
+    fn archive(self) -> Option<&'static str> {
+        match self {
+            Media::Images => Some("images.tar"),
+            Media::Audio => None,
+        }
+    }
+
+    /// Whether the HDF5 dataset `dataset` holds paths of these files.
+    pub fn references(self, dataset: &str) -> bool {
+        match self {
+            Media::Images => ["image_path", "raw_image_path", "clip_path"].iter().any(|prefix| dataset.starts_with(prefix)),
+            Media::Audio => dataset == "audio",
+        }
+    }
+
+    fn retention_days(self, config: &Config) -> u64 {
+        match self {
+            Media::Images => config.retention.images_days,
+            Media::Audio => config.retention.audio_days,
+        }
+    }
+}
+
+/// Files removed by [`prune`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Number of files deleted.
+    pub files: usize,
+    /// Total size of the deleted files in bytes.
+    pub bytes: u64,
+}
+
+/// Deletes the media directories of sessions in `config.data_path` that started more than the
+/// configured retention period before `now_s` (seconds since UNIX epoch), and marks them as
+/// purged in the data file.
+///
+/// Sessions whose files cannot be deleted or marked are logged and skipped.
+///
+/// # Errors
+///
+/// Returns an error if `config.data_path` cannot be read.
+pub fn prune(config: &Config, now_s: u64) -> Result<PruneReport, Box<dyn Error>> {
+    let mut report = PruneReport::default();
+    let mut sessions: Vec<(String, u64)> = fs::read_dir(&config.data_path)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            session_start(&name).map(|start| (name, start))
+        })
+        .collect();
+    sessions.sort();
+
+    for (session, start_s) in sessions {
+        for media in [Media::Images, Media::Audio] {
+            let days = media.retention_days(config);
+            if days == 0 || now_s.saturating_sub(start_s) < days * 24 * 60 * 60 {
+                continue;
+            }
+            let session_directory = Path::new(&config.data_path).join(&session);
+            let directories: Vec<_> = std::iter::once(media.name())
+                .chain(media.copies().iter().copied())
+                .map(|name| session_directory.join(name))
+                .filter(|path| path.exists())
+                .collect();
+            let archive = media.archive().map(|name| session_directory.join(name)).filter(|path| path.exists());
+            if directories.is_empty() && archive.is_none() {
+                continue;
+            }
+            let removed = match remove_media(&directories, archive.as_deref()) {
+                Ok(removed) => removed,
+                Err(e) => {
+                    warn!("Failed to prune the {} of session {}: {}", media.name(), session, e);
+                    continue;
+                }
+            };
+            info!("Pruned {} {} files ({} bytes) of session {}", removed.files, media.name(), removed.bytes, session);
+            report.files += removed.files;
+            report.bytes += removed.bytes;
+            if let Err(e) = storage::mark_purged(config, &session, media, now_s) {
+                warn!("Failed to mark the {} of session {} as purged: {}", media.name(), session, e);
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Deletes the media `directories` and the `archive` file.
+fn remove_media(directories: &[PathBuf], archive: Option<&Path>) -> io::Result<PruneReport> {
+    let mut report = PruneReport::default();
+    for directory in directories {
+        let removed = remove_directory(directory)?;
+        report.files += removed.files;
+        report.bytes += removed.bytes;
+    }
+    if let Some(archive) = archive {
+        report.bytes += fs::metadata(archive)?.len();
+        report.files += 1;
+        fs::remove_file(archive)?;
+    }
+    Ok(report)
+}
+
+/// Deletes `directory` and everything in it, counting the files.
+fn remove_directory(directory: &Path) -> io::Result<PruneReport> {
+    let mut report = PruneReport::default();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            let removed = remove_directory(&entry.path())?;
+            report.files += removed.files;
+            report.bytes += removed.bytes;
+        } else {
+            report.bytes += entry.metadata()?.len();
+            report.files += 1;
+            fs::remove_file(entry.path())?;
+        }
+    }
+    fs::remove_dir(directory)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_analysis::EVENT_CLIP_CATEGORY;
+    use crate::config::{CompressionConfig, RetentionConfig};
+    use crate::data::{Event, SessionReader, SleepDataLogger};
+    use test_log::test;
+
+    #[test]
+    fn test_prune_expired_media() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        for session in ["2025-04-01_22-00-00", "2025-04-27_22-00-00"] {
+            fs::create_dir_all(format!("{}/{}/images/raw", data_path, session)).unwrap();
+            fs::create_dir_all(format!("{}/{}/audio", data_path, session)).unwrap();
+            fs::write(format!("{}/{}/images/image_1.jpg", data_path, session), [0u8; 10]).unwrap();
+            fs::write(format!("{}/{}/images/raw/image_1.jpg", data_path, session), [0u8; 20]).unwrap();
+            fs::write(format!("{}/{}/audio/audio_1.mp3", data_path, session), [0u8; 40]).unwrap();
+        }
+        fs::write(format!("{}/2025-04-01_22-00-00/images.tar", data_path), [0u8; 5]).unwrap();
+        let config = Config {
+            data_path: data_path.to_string(),
+            retention: RetentionConfig { images_days: 14, audio_days: 0 },
+            ..Config::default()
+        };
+        let now_s = session_start("2025-04-28_22-00-00").unwrap();
+
+        // The sessions aren't in the data file, so marking them fails and is only logged
+        let report = prune(&config, now_s).expect("Failed to prune");
+        assert_eq!(report, PruneReport { files: 3, bytes: 35 });
+        assert!(!Path::new(&format!("{}/2025-04-01_22-00-00/images", data_path)).exists());
+        assert!(!Path::new(&format!("{}/2025-04-01_22-00-00/images.tar", data_path)).exists());
+        assert!(Path::new(&format!("{}/2025-04-01_22-00-00/audio/audio_1.mp3", data_path)).exists());
+        assert!(Path::new(&format!("{}/2025-04-27_22-00-00/images/image_1.jpg", data_path)).exists());
+
+        // Pruning again finds nothing left to delete
+        assert_eq!(prune(&config, now_s).expect("Failed to prune"), PruneReport::default());
+
+        // Pruning the audio also deletes the clips cut from it, and clears their events
+        let session = "2025-04-01_22-00-00";
+        fs::create_dir_all(format!("{}/{}/events", data_path, session)).unwrap();
+        let clip_path = format!("{}/{}/events/audio_1_100.mp3", data_path, session);
+        fs::write(&clip_path, [0u8; 8]).unwrap();
+        let mut logger = SleepDataLogger::create(data_path, &config.file_name, session, CompressionConfig::default())
+            .expect("Failed to create logger");
+        let snore = Event { timestamp_s: now_s, category: "snore".to_string(), text: "loud".to_string() };
+        let clip = Event { timestamp_s: now_s, category: EVENT_CLIP_CATEGORY.to_string(), text: clip_path };
+        logger.add_event(&snore).unwrap();
+        logger.add_event(&clip).unwrap();
+        drop(logger);
+        let config = Config { retention: RetentionConfig { images_days: 14, audio_days: 14 }, ..config };
+        assert_eq!(prune(&config, now_s).expect("Failed to prune"), PruneReport { files: 2, bytes: 48 });
+        assert!(!Path::new(&format!("{}/{}/events", data_path, session)).exists());
+        assert!(!Path::new(&format!("{}/{}/audio", data_path, session)).exists());
+        assert!(Path::new(&format!("{}/2025-04-27_22-00-00/audio/audio_1.mp3", data_path)).exists());
+        let reader = SessionReader::open(data_path, &config.file_name, session).expect("Failed to open session");
+        assert_eq!(reader.purged_s(Media::Audio).unwrap(), Some(now_s));
+        let cleared = Event { text: String::new(), ..clip };
+        assert_eq!(reader.events().unwrap(), vec![snore, cleared]);
+    }
+}
@@ -0,0 +1,454 @@
+//! Minimal drivers for Sensirion I2C gas sensors.
+//!
+//! Sensirion sensors share a command protocol: 16-bit commands, and 16-bit data words each
+//! followed by a CRC-8 checksum.
+
+use std::error::Error;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+/// CRC-8 used by Sensirion sensors (polynomial 0x31, initial value 0xFF).
+pub(crate) fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x31 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Sends a command, optionally followed by data words.
+fn write_command<I2C: I2c>(i2c: &mut I2C, address: u8, command: u16, words: &[u16]) -> Result<(), Box<dyn Error>>
+where
+    I2C::Error: Error + 'static,
+{
+    let mut buffer = command.to_be_bytes().to_vec();
+    for word in words {
+        let bytes = word.to_be_bytes();
+        buffer.extend_from_slice(&bytes);
+        buffer.push(crc8(&bytes));
+    }
+    Ok(i2c.write(address, &buffer)?)
+}
+
+/// Reads `N` data words, checking their CRCs.
+fn read_words<I2C: I2c, const N: usize>(i2c: &mut I2C, address: u8) -> Result<[u16; N], Box<dyn Error>>
+where
+    I2C::Error: Error + 'static,
+{
+    let mut buffer = vec![0u8; N * 3];
+    i2c.read(address, &mut buffer)?;
+    let mut words = [0u16; N];
+    for (word, chunk) in words.iter_mut().zip(buffer.chunks_exact(3)) {
+        if crc8(&chunk[..2]) != chunk[2] {
+            return Err("Sensirion CRC mismatch".into());
+        }
+        *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+    }
+    Ok(words)
+}
+
+/// A CO2 measurement from an SCD4x.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scd4xMeasurement {
+    /// CO2 concentration in ppm.
+    pub co2_ppm: u16,
+    /// Temperature in degrees Celsius.
+    pub temperature_c: f32,
+    /// Relative humidity in percent.
+    pub humidity: f32,
+}
+
+impl Scd4xMeasurement {
+    fn from_words([co2, temperature, humidity]: [u16; 3]) -> Self {
+        Self {
+            co2_ppm: co2,
+            temperature_c: -45.0 + 175.0 * temperature as f32 / 65535.0,
+            humidity: 100.0 * humidity as f32 / 65535.0,
+        }
+    }
+}
+
+/// SCD40/SCD41 NDIR CO2 sensor in periodic measurement mode (one measurement every 5 s).
+pub struct Scd4x<I2C, D> {
+    i2c: I2C,
+    delay: D,
+}
+
+impl<I2C, D> Scd4x<I2C, D>
+where
+    I2C: I2c,
+    I2C::Error: Error + 'static,
+    D: DelayNs,
+{
+    /// I2C address of the SCD4x.
+    pub const ADDRESS: u8 = 0x62;
+
+    const START_PERIODIC_MEASUREMENT: u16 = 0x21B1;
+    const STOP_PERIODIC_MEASUREMENT: u16 = 0x3F86;
+    const GET_DATA_READY_STATUS: u16 = 0xE4B8;
+    const READ_MEASUREMENT: u16 = 0xEC05;
+    const SET_AMBIENT_PRESSURE: u16 = 0xE000;
+
+    /// Creates the driver. Call [`Scd4x::start`] to begin measuring.
+    pub fn new(i2c: I2C, delay: D) -> Self {
+        Self { i2c, delay }
+    }
+
+    /// Restarts periodic measurement. The first measurement is ready after ~5 s.
+    pub fn start(&mut self) -> Result<(), Box<dyn Error>> {
+        // The sensor only accepts a few commands while measuring, so stop any previous session first
+        write_command(&mut self.i2c, Self::ADDRESS, Self::STOP_PERIODIC_MEASUREMENT, &[])?;
+        self.delay.delay_ms(500);
+        write_command(&mut self.i2c, Self::ADDRESS, Self::START_PERIODIC_MEASUREMENT, &[])
+    }
+
+    /// Whether a new measurement is ready to be read.
+    pub fn data_ready(&mut self) -> Result<bool, Box<dyn Error>> {
+        write_command(&mut self.i2c, Self::ADDRESS, Self::GET_DATA_READY_STATUS, &[])?;
+        self.delay.delay_ms(1);
+        let [status] = read_words::<_, 1>(&mut self.i2c, Self::ADDRESS)?;
+        Ok(status & 0x07FF != 0)
+    }
+
+    /// Reads the latest measurement, or `None` if no new measurement is ready.
+    pub fn read(&mut self) -> Result<Option<Scd4xMeasurement>, Box<dyn Error>> {
+        if !self.data_ready()? {
+            return Ok(None);
+        }
+        write_command(&mut self.i2c, Self::ADDRESS, Self::READ_MEASUREMENT, &[])?;
+        self.delay.delay_ms(1);
+        let words = read_words::<_, 3>(&mut self.i2c, Self::ADDRESS)?;
+        Ok(Some(Scd4xMeasurement::from_words(words)))
+    }
+
+    /// Sets the ambient pressure used for CO2 compensation, in hPa.
+    #[allow(dead_code)] // Driver API, not used by the recorder yet
+    pub fn set_ambient_pressure(&mut self, pressure_hpa: f32) -> Result<(), Box<dyn Error>> {
+        write_command(&mut self.i2c, Self::ADDRESS, Self::SET_AMBIENT_PRESSURE, &[pressure_hpa.round() as u16])
+    }
+}
+
+/// SGP40 metal-oxide VOC sensor. Produces a raw signal; use [`VocIndex`] to turn it into the
+/// Sensirion VOC index.
+pub struct Sgp40<I2C, D> {
+    i2c: I2C,
+    delay: D,
+}
+
+impl<I2C, D> Sgp40<I2C, D>
+where
+    I2C: I2c,
+    I2C::Error: Error + 'static,
+    D: DelayNs,
+{
+    /// I2C address of the SGP40.
+    pub const ADDRESS: u8 = 0x59;
+
+    const MEASURE_RAW_SIGNAL: u16 = 0x260F;
+    const TURN_HEATER_OFF: u16 = 0x3615;
+
+    /// Creates the driver.
+    pub fn new(i2c: I2C, delay: D) -> Self {
+        Self { i2c, delay }
+    }
+
+    /// Measures the raw VOC signal, compensated for the given temperature (°C) and humidity (%RH).
+    pub fn measure_raw(&mut self, temperature_c: f32, humidity: f32) -> Result<u16, Box<dyn Error>> {
+        let rh_ticks = (humidity.clamp(0.0, 100.0) * 65535.0 / 100.0) as u16;
+        let t_ticks = ((temperature_c.clamp(-45.0, 130.0) + 45.0) * 65535.0 / 175.0) as u16;
+        write_command(&mut self.i2c, Self::ADDRESS, Self::MEASURE_RAW_SIGNAL, &[rh_ticks, t_ticks])?;
+        self.delay.delay_ms(30);
+        let [sraw] = read_words::<_, 1>(&mut self.i2c, Self::ADDRESS)?;
+        Ok(sraw)
+    }
+
+    /// Turns the hotplate off until the next measurement.
+    #[allow(dead_code)] // Driver API, not used by the recorder yet
+    pub fn heater_off(&mut self) -> Result<(), Box<dyn Error>> {
+        write_command(&mut self.i2c, Self::ADDRESS, Self::TURN_HEATER_OFF, &[])
+    }
+}
+
+/// Sensirion's VOC index algorithm (gas index algorithm, VOC variant), converting SGP40 raw
+/// signals into a VOC index from 1 to 500, where 100 is the average over the past hours.
+///
+/// The index adapts to the environment: the first 45 samples return 0, and it takes a few hours
+/// of continuous operation to settle.
+#[derive(Clone, Debug)]
+pub struct VocIndex {
+    sampling_interval: f32,
+    uptime: f32,
+    sraw: f32,
+    gas_index: f32,
+    mve: MeanVarianceEstimator,
+    lowpass: AdaptiveLowpass,
+}
+
+// Tuning constants of the VOC index algorithm, as published by Sensirion.
+const INITIAL_BLACKOUT: f32 = 45.0;
+const INDEX_GAIN: f32 = 230.0;
+const SRAW_STD_INITIAL: f32 = 50.0;
+const SRAW_STD_BONUS: f32 = 220.0;
+const TAU_MEAN_HOURS: f32 = 12.0;
+const TAU_VARIANCE_HOURS: f32 = 12.0;
+const TAU_INITIAL_MEAN: f32 = 20.0;
+const INIT_DURATION_MEAN: f32 = 3600.0 * 0.75;
+const INIT_TRANSITION_MEAN: f32 = 0.01;
+const TAU_INITIAL_VARIANCE: f32 = 2500.0;
+const INIT_DURATION_VARIANCE: f32 = 3600.0 * 1.45;
+const INIT_TRANSITION_VARIANCE: f32 = 0.01;
+const GATING_THRESHOLD: f32 = 340.0;
+const GATING_THRESHOLD_INITIAL: f32 = 510.0;
+const GATING_THRESHOLD_TRANSITION: f32 = 0.09;
+const GATING_MAX_DURATION_MINUTES: f32 = 60.0 * 3.0;
+const GATING_MAX_RATIO: f32 = 0.3;
+const SIGMOID_L: f32 = 500.0;
+const SIGMOID_K: f32 = -0.0065;
+const SIGMOID_X0: f32 = 213.0;
+const INDEX_OFFSET: f32 = 100.0;
+const LP_TAU_FAST: f32 = 20.0;
+const LP_TAU_SLOW: f32 = 500.0;
+const LP_ALPHA: f32 = -0.2;
+const SRAW_MINIMUM: i32 = 20000;
+const GAMMA_SCALING: f32 = 64.0;
+const ADDITIONAL_GAMMA_MEAN_SCALING: f32 = 8.0;
+const FIX16_MAX: f32 = 32767.0;
+
+impl VocIndex {
+    /// Creates the algorithm for raw samples taken every `sampling_interval_s` seconds
+    /// (Sensirion recommends 1 s; up to 10 s is supported).
+    pub fn new(sampling_interval_s: f32) -> Self {
+        Self {
+            sampling_interval: sampling_interval_s,
+            uptime: 0.0,
+            sraw: 0.0,
+            gas_index: 0.0,
+            mve: MeanVarianceEstimator::new(sampling_interval_s),
+            lowpass: AdaptiveLowpass::new(sampling_interval_s),
+        }
+    }
+
+    /// Processes a raw signal and returns the VOC index (0 during the initial blackout).
+    pub fn process(&mut self, sraw: u16) -> u16 {
+        if self.uptime <= INITIAL_BLACKOUT {
+            self.uptime += self.sampling_interval;
+            return 0;
+        }
+        let sraw = sraw as i32;
+        if sraw > 0 && sraw < 65000 {
+            let sraw = sraw.clamp(SRAW_MINIMUM + 1, SRAW_MINIMUM + 32767);
+            self.sraw = (sraw - SRAW_MINIMUM) as f32;
+        }
+        let mox = (self.sraw - self.mve.mean()) / (-(self.mve.std + SRAW_STD_BONUS)) * INDEX_GAIN;
+        self.gas_index = self.lowpass.process(sigmoid_scaled(mox)).max(0.5);
+        if self.sraw > 0.0 {
+            self.mve.process(self.sraw, self.gas_index);
+        }
+        (self.gas_index + 0.5) as u16
+    }
+}
+
+/// Sigmoid mapping the MOX model output onto the 1..500 index scale, with 0 mapped to 100.
+fn sigmoid_scaled(sample: f32) -> f32 {
+    let x = SIGMOID_K * (sample - SIGMOID_X0);
+    if x < -50.0 {
+        SIGMOID_L
+    } else if x > 50.0 {
+        0.0
+    } else if sample >= 0.0 {
+        let shift = (SIGMOID_L - 5.0 * INDEX_OFFSET) / 4.0;
+        (SIGMOID_L + shift) / (1.0 + x.exp()) - shift
+    } else {
+        SIGMOID_L / (1.0 + x.exp())
+    }
+}
+
+/// Logistic function `1 / (1 + exp(k (sample - x0)))`, saturating outside |x| > 50.
+fn sigmoid(sample: f32, x0: f32, k: f32) -> f32 {
+    let x = k * (sample - x0);
+    if x < -50.0 {
+        1.0
+    } else if x > 50.0 {
+        0.0
+    } else {
+        1.0 / (1.0 + x.exp())
+    }
+}
+
+/// Tracks the long-term mean and standard deviation of the raw signal, with faster adaptation
+/// during start-up and gating while the index is high (so VOC events don't become the baseline).
+#[derive(Clone, Debug)]
+struct MeanVarianceEstimator {
+    sampling_interval: f32,
+    initialized: bool,
+    mean: f32,
+    sraw_offset: f32,
+    std: f32,
+    gamma_mean: f32,
+    gamma_variance: f32,
+    gamma_initial_mean: f32,
+    gamma_initial_variance: f32,
+    current_gamma_mean: f32,
+    current_gamma_variance: f32,
+    uptime_gamma: f32,
+    uptime_gating: f32,
+    gating_duration_minutes: f32,
+}
+
+impl MeanVarianceEstimator {
+    fn new(sampling_interval: f32) -> Self {
+        let hours = sampling_interval / 3600.0;
+        Self {
+            sampling_interval,
+            initialized: false,
+            mean: 0.0,
+            sraw_offset: 0.0,
+            std: SRAW_STD_INITIAL,
+            gamma_mean: ADDITIONAL_GAMMA_MEAN_SCALING * GAMMA_SCALING * hours / (TAU_MEAN_HOURS + hours),
+            gamma_variance: GAMMA_SCALING * hours / (TAU_VARIANCE_HOURS + hours),
+            gamma_initial_mean: ADDITIONAL_GAMMA_MEAN_SCALING * GAMMA_SCALING * sampling_interval
+                / (TAU_INITIAL_MEAN + sampling_interval),
+            gamma_initial_variance: GAMMA_SCALING * sampling_interval / (TAU_INITIAL_VARIANCE + sampling_interval),
+            current_gamma_mean: 0.0,
+            current_gamma_variance: 0.0,
+            uptime_gamma: 0.0,
+            uptime_gating: 0.0,
+            gating_duration_minutes: 0.0,
+        }
+    }
+
+    fn mean(&self) -> f32 {
+        self.mean + self.sraw_offset
+    }
+
+    fn calculate_gamma(&mut self, gas_index: f32) {
+        let uptime_limit = FIX16_MAX - self.sampling_interval;
+        if self.uptime_gamma < uptime_limit {
+            self.uptime_gamma += self.sampling_interval;
+        }
+        if self.uptime_gating < uptime_limit {
+            self.uptime_gating += self.sampling_interval;
+        }
+
+        let sigmoid_gamma_mean = sigmoid(self.uptime_gamma, INIT_DURATION_MEAN, INIT_TRANSITION_MEAN);
+        let gamma_mean = self.gamma_mean + (self.gamma_initial_mean - self.gamma_mean) * sigmoid_gamma_mean;
+        let gating_threshold_mean = GATING_THRESHOLD + (GATING_THRESHOLD_INITIAL - GATING_THRESHOLD)
+            * sigmoid(self.uptime_gating, INIT_DURATION_MEAN, INIT_TRANSITION_MEAN);
+        let sigmoid_gating_mean = sigmoid(gas_index, gating_threshold_mean, GATING_THRESHOLD_TRANSITION);
+        self.current_gamma_mean = sigmoid_gating_mean * gamma_mean;
+
+        let sigmoid_gamma_variance = sigmoid(self.uptime_gamma, INIT_DURATION_VARIANCE, INIT_TRANSITION_VARIANCE);
+        let gamma_variance = self.gamma_variance
+            + (self.gamma_initial_variance - self.gamma_variance) * (sigmoid_gamma_variance - sigmoid_gamma_mean);
+        let gating_threshold_variance = GATING_THRESHOLD + (GATING_THRESHOLD_INITIAL - GATING_THRESHOLD)
+            * sigmoid(self.uptime_gating, INIT_DURATION_VARIANCE, INIT_TRANSITION_VARIANCE);
+        let sigmoid_gating_variance = sigmoid(gas_index, gating_threshold_variance, GATING_THRESHOLD_TRANSITION);
+        self.current_gamma_variance = sigmoid_gating_variance * gamma_variance;
+
+        self.gating_duration_minutes += (self.sampling_interval / 60.0)
+            * ((1.0 - sigmoid_gating_mean) * (1.0 + GATING_MAX_RATIO) - GATING_MAX_RATIO);
+        self.gating_duration_minutes = self.gating_duration_minutes.max(0.0);
+        if self.gating_duration_minutes > GATING_MAX_DURATION_MINUTES {
+            self.uptime_gating = 0.0;
+        }
+    }
+
+    fn process(&mut self, sraw: f32, gas_index: f32) {
+        if !self.initialized {
+            self.initialized = true;
+            self.sraw_offset = sraw;
+            self.mean = 0.0;
+            return;
+        }
+        if self.mean >= 100.0 || self.mean <= -100.0 {
+            self.sraw_offset += self.mean;
+            self.mean = 0.0;
+        }
+        let sraw = sraw - self.sraw_offset;
+        self.calculate_gamma(gas_index);
+
+        let delta = (sraw - self.mean) / GAMMA_SCALING;
+        let c = self.std + delta.abs();
+        let additional_scaling = if c > 1440.0 { (c / 1440.0) * (c / 1440.0) } else { 1.0 };
+        self.std = (additional_scaling * (GAMMA_SCALING - self.current_gamma_variance)).sqrt()
+            * (self.std * (self.std / (GAMMA_SCALING * additional_scaling))
+                + self.current_gamma_variance * delta / additional_scaling * delta)
+                .sqrt();
+        self.mean += self.current_gamma_mean * delta / ADDITIONAL_GAMMA_MEAN_SCALING;
+    }
+}
+
+/// Low-pass filter that follows fast changes quickly and smooths slow drift.
+#[derive(Clone, Debug)]
+struct AdaptiveLowpass {
+    sampling_interval: f32,
+    a1: f32,
+    a2: f32,
+    state: Option<(f32, f32, f32)>,
+}
+
+impl AdaptiveLowpass {
+    fn new(sampling_interval: f32) -> Self {
+        Self {
+            sampling_interval,
+            a1: sampling_interval / (LP_TAU_FAST + sampling_interval),
+            a2: sampling_interval / (LP_TAU_SLOW + sampling_interval),
+            state: None,
+        }
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        let (x1, x2, x3) = self.state.unwrap_or((sample, sample, sample));
+        let x1 = (1.0 - self.a1) * x1 + self.a1 * sample;
+        let x2 = (1.0 - self.a2) * x2 + self.a2 * sample;
+        let f1 = (LP_ALPHA * (x1 - x2).abs()).exp();
+        let tau = (LP_TAU_SLOW - LP_TAU_FAST) * f1 + LP_TAU_FAST;
+        let a3 = self.sampling_interval / (self.sampling_interval + tau);
+        let x3 = (1.0 - a3) * x3 + a3 * sample;
+        self.state = Some((x1, x2, x3));
+        x3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc8_datasheet_example() {
+        assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+    }
+
+    #[test]
+    fn test_voc_index_settles_at_baseline() {
+        let mut voc = VocIndex::new(1.0);
+        let indices: Vec<u16> = (0..3600).map(|_| voc.process(30000)).collect();
+        assert_eq!(indices[0], 0, "Expected blackout at start");
+        let last = *indices.last().unwrap();
+        assert!((90..=110).contains(&last), "Expected ~100 for a constant signal, got {}", last);
+    }
+
+    #[test]
+    fn test_voc_index_rises_when_signal_drops() {
+        // More VOCs lower the raw signal of the MOX sensor
+        let mut voc = VocIndex::new(1.0);
+        for _ in 0..3600 {
+            voc.process(30000);
+        }
+        let index = (0..60).map(|_| voc.process(29000)).last().unwrap();
+        assert!(index > 150, "Expected a raised index, got {}", index);
+    }
+
+    #[test]
+    fn test_scd4x_conversion_datasheet_example() {
+        // Example response from the SCD4x datasheet: 500 ppm, 25 °C, 37 %RH
+        let m = Scd4xMeasurement::from_words([0x01F4, 0x6667, 0x5EB9]);
+        assert_eq!(m.co2_ppm, 500);
+        assert!((m.temperature_c - 25.0).abs() < 0.01, "Expected 25 °C, got {}", m.temperature_c);
+        assert!((m.humidity - 37.0).abs() < 0.01, "Expected 37 %RH, got {}", m.humidity);
+    }
+}
@@ -9,64 +9,271 @@ use ab_glyph::{FontArc, PxScale};
 use chrono::{Local, TimeZone};
 use dfrobot_c1001::{Led, C1001};
 use ens160_aq::Ens160;
-use image::{GrayImage, ImageFormat, RgbImage};
-use mcp342x::{Channel, Gain, MCP342x, Resolution};
+use hdf5::types::{VarLenArray, VarLenUnicode};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayImage, RgbImage};
+use mcp342x::{Capture, Channel, Gain, MCP342x, Resolution, Volts};
+use mcp342x::thermistor::{Divider, SteinhartHart};
 use nix::sys::signal::Signal;
 use tokio::process::Command;
-use tracing::{info, warn};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
+use embedded_hal::i2c::I2c;
+use embedded_hal_bus::i2c::MutexDevice;
 use linux_embedded_hal::{Delay, I2cdev};
 use bme280::i2c::BME280;
+use bme280::{Configuration as Bme280Configuration, IIRFilter, Oversampling};
 use rscam::{Camera, Config};
+use gpio_cdev::{Chip, EventRequestFlags, EventType, LineHandle, LineRequestFlags};
 
 use imageproc::drawing::draw_text_mut;
 
-use std::{error::Error, fs::File, io::BufWriter, path::Path, time::{Duration, SystemTime, SystemTimeError, UNIX_EPOCH}};
+use std::{collections::HashMap, error::Error, fs::File, io::{BufWriter, Write}, path::Path, str::FromStr, sync::{atomic::{AtomicBool, Ordering}, mpsc::{RecvTimeoutError, TryRecvError}, Arc, Mutex, MutexGuard, OnceLock}, time::{Duration, Instant, SystemTime, SystemTimeError, UNIX_EPOCH}};
 
-use crate::data::{AudioRecording, CameraAndMotionResult, SleepData};
+use crate::config::{AudioBackend, AudioConfig, AudioFormat, Bme280Config, CameraConfig, GpioLineConfig, Hx711Config, MotionClipConfig, MotionRoi, NightModeConfig, OverlayConfig, PiezoConfig, SensorInitConfig, ThermistorConfig};
+#[cfg(any(test, feature = "audio-loopback"))]
+use crate::audio_analysis::{decode_audio, write_wav, DecodedAudio};
+use crate::image_analysis::{frame_difference, masked_frame_difference, roi_mask};
+use crate::bh1750::Bh1750;
+use crate::bcg::{self, BcgEstimate};
+use crate::hx711::{Hx711, LoadCellCalibration};
+use crate::pms5003::{PmMeasurement, Pms5003};
+use crate::sensirion::{Scd4x, Scd4xMeasurement, Sgp40, VocIndex};
+use crate::calibration::{Bme280Calibration, Calibration, Ens160Calibration, PiezoCalibration, ThermistorCalibration};
+use crate::data::{AudioRecording, CameraAndMotionResult, SensorState, SensorStatus, SleepData, SleepDataBuilder, SleepField, Thumbnail};
+
+/// Handle to a shared I2C bus, as used by all I2C sensor wrappers.
+pub type SharedI2c = MutexDevice<'static, I2cdev>;
+
+/// Returns a handle to the I2C bus at `path`, opening the bus on first use.
+///
+/// Every I2C sensor on the same bus gets its own handle to a single `I2cdev`, instead of each
+/// driver opening the device separately. Buses stay open for the lifetime of the process.
+///
+/// # Examples
+///
+/// ```no_run
+/// use sleep_recorder::sensor::shared_i2c;
+/// let bus = shared_i2c("/dev/i2c-1").expect("Failed to open I2C bus");
+/// ```
+pub fn shared_i2c(path: &str) -> Result<SharedI2c, Box<dyn Error>> {
+    static BUSES: OnceLock<Mutex<HashMap<String, &'static Mutex<I2cdev>>>> = OnceLock::new();
+
+    let mut buses = BUSES.get_or_init(Default::default).lock().map_err(|_| "I2C bus registry poisoned")?;
+    let bus = match buses.get(path) {
+        Some(bus) => *bus,
+        None => {
+            let bus: &'static Mutex<I2cdev> = Box::leak(Box::new(Mutex::new(I2cdev::new(path)?)));
+            buses.insert(path.to_string(), bus);
+            bus
+        }
+    };
+    Ok(MutexDevice::new(bus))
+}
+
+/// Handle to an MCP342x ADC shared by the sensors wired to its inputs.
+pub type SharedAdc = Arc<Mutex<MCP342x<SharedI2c>>>;
+
+/// Returns a handle to the MCP342x at `address` on the I2C bus at `bus`, creating it on first use.
+///
+/// The thermistors and the piezo can be wired to inputs of the same chip. Every conversion rewrites
+/// the chip's channel, gain, resolution and mode, so all of them must go through one handle (see
+/// [`AdcInput`]). ADCs stay in use for the lifetime of the process.
+pub fn shared_adc(bus: &str, address: u8) -> Result<SharedAdc, Box<dyn Error>> {
+    static ADCS: OnceLock<Mutex<HashMap<(String, u8), SharedAdc>>> = OnceLock::new();
+
+    let mut adcs = ADCS.get_or_init(Default::default).lock().map_err(|_| "ADC registry poisoned")?;
+    let key = (bus.to_string(), address);
+    let adc = match adcs.get(&key) {
+        Some(adc) => adc.clone(),
+        None => {
+            let adc = Arc::new(Mutex::new(MCP342x::new(shared_i2c(bus)?, address)));
+            adcs.insert(key, adc.clone());
+            adc
+        }
+    };
+    Ok(adc)
+}
+
+/// One input of an MCP342x that may be shared with other sensors (see [`shared_adc`]).
+///
+/// The ADC stays locked for a whole conversion or burst, with this input's channel and gain written
+/// first, so a sensor on another input can't switch the channel, resolution or mode mid-measurement.
+pub struct AdcInput<I2C> {
+    adc: Arc<Mutex<MCP342x<I2C>>>,
+    channel: Channel,
+    gain: Gain,
+}
+
+impl<I2C, E> AdcInput<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: std::error::Error + 'static,
+{
+    /// Creates the input `channel` of `adc`, read with PGA gain `gain`.
+    pub fn new(adc: Arc<Mutex<MCP342x<I2C>>>, channel: Channel, gain: Gain) -> Self {
+        Self { adc, channel, gain }
+    }
+
+    /// Locks the ADC and selects this input, in one-shot mode.
+    fn select(&self) -> Result<MutexGuard<'_, MCP342x<I2C>>, Box<dyn Error>> {
+        let mut adc = self.adc.lock().map_err(|_| "ADC lock poisoned")?;
+        adc.set_channel(self.channel);
+        adc.set_gain(self.gain);
+        adc.set_continuous_mode(false);
+        Ok(adc)
+    }
+
+    /// Runs a one-shot conversion at `resolution` and returns the input voltage.
+    pub fn read(&self, resolution: Resolution) -> Result<Volts, Box<dyn Error>> {
+        let mut adc = self.select()?;
+        adc.set_resolution(resolution);
+        Ok(adc.convert_and_read(true)?)
+    }
+
+    /// Captures a burst of `n_samples` readings at 12 bits (see [`MCP342x::capture`]), leaving the
+    /// ADC in standby afterwards.
+    pub fn capture(&self, n_samples: usize) -> Result<Capture, Box<dyn Error>> {
+        Ok(self.select()?.capture(n_samples)?)
+    }
+}
+
+/// I2C bus used by the sensor constructors that don't take a bus path.
+pub const DEFAULT_I2C_BUS: &str = "/dev/i2c-1";
 
 /// Wrapper for the BME280 sensor, providing temperature, humidity, and pressure measurements.
 pub struct BME280Wrapper {
-    bme280: BME280<I2cdev>,
+    bme280: BME280<SharedI2c>,
+    /// Added to the measured temperature (see [`Bme280Calibration`]).
+    temperature_offset_c: f32,
 }
 impl BME280Wrapper {
-    /// Creates a new instance of `BME280Wrapper`.
+    /// Creates a new instance of `BME280Wrapper` with the default settings (primary address, 1x
+    /// oversampling, no IIR filter).
     /// 
     /// # Returns
     /// 
     /// * `Result<Self, Box<dyn Error>>` - A result containing the initialized `BME280Wrapper` instance or an error.
     pub fn new() -> Result<Self, Box<dyn Error>> {
-        let i2c_bus = I2cdev::new("/dev/i2c-1")?;
+        Self::from_config(DEFAULT_I2C_BUS, &Bme280Config::default())
+    }
+
+    /// Creates a new instance of `BME280Wrapper` on the I2C bus at `bus`, with the given address,
+    /// oversampling, and IIR filter settings.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the I2C bus cannot be opened, the settings are invalid, or the sensor
+    ///   does not respond.
+    pub fn from_config(bus: &str, config: &Bme280Config) -> Result<Self, Box<dyn Error>> {
+        config.validate()?;
+        let i2c_bus = shared_i2c(bus)?;
         let mut delay = Delay;
-        let mut bme280 = BME280::new_primary(i2c_bus);
-        bme280.init(&mut delay)?;
-        Ok(Self { bme280 })
+        let mut bme280 = match config.address {
+            0x77 => BME280::new_secondary(i2c_bus),
+            _ => BME280::new_primary(i2c_bus),
+        };
+        let configuration = Bme280Configuration::default()
+            .with_temperature_oversampling(oversampling(config.temperature_oversampling))
+            .with_pressure_oversampling(oversampling(config.pressure_oversampling))
+            .with_humidity_oversampling(oversampling(config.humidity_oversampling))
+            .with_iir_filter(match config.iir_filter {
+                2 => IIRFilter::Coefficient2,
+                4 => IIRFilter::Coefficient4,
+                8 => IIRFilter::Coefficient8,
+                16 => IIRFilter::Coefficient16,
+                _ => IIRFilter::Off,
+            });
+        bme280.init_with_config(&mut delay, configuration)?;
+        Ok(Self { bme280, temperature_offset_c: 0.0 })
+    }
+
+    /// Applies the temperature offset in `calibration` to the measurements.
+    pub fn with_calibration(mut self, calibration: &Bme280Calibration) -> Self {
+        self.temperature_offset_c = calibration.temperature_offset_c;
+        self
     }
+
     /// Measures and returns the current temperature, humidity, and pressure from the BME280 sensor.
+    ///
+    /// Each call triggers a single forced-mode conversion; the sensor sleeps in between.
     /// 
     /// # Returns
     /// 
     /// * `Option<bme280::Measurements<linux_embedded_hal::I2CError>>` - A result containing the measurements or None if an error occurs.
     pub fn measure(&mut self) -> Option<bme280::Measurements<linux_embedded_hal::I2CError>> {
         let mut delay = Delay;
-        self.bme280
+        let mut measurements = self.bme280
             .measure(&mut delay)
             .map_err(|e| warn!("BME280 measurement error: {e}"))
-            .ok()
+            .ok()?;
+        measurements.temperature += self.temperature_offset_c;
+        Some(measurements)
+    }
+}
+
+/// BME280 oversampling setting for a validated factor (1, 2, 4, 8, or 16).
+fn oversampling(factor: u8) -> Oversampling {
+    match factor {
+        2 => Oversampling::Oversampling2X,
+        4 => Oversampling::Oversampling4X,
+        8 => Oversampling::Oversampling8X,
+        16 => Oversampling::Oversampling16X,
+        _ => Oversampling::Oversampling1X,
     }
 }
 
+/// V4L2 control IDs used for night mode (from linux/v4l2-controls.h).
+const V4L2_CID_EXPOSURE_AUTO: u32 = 0x009a_0901;
+const V4L2_CID_EXPOSURE_ABSOLUTE: u32 = 0x009a_0902;
+const V4L2_CID_GAIN: u32 = 0x0098_0913;
+const V4L2_EXPOSURE_MANUAL: i32 = 1;
+const V4L2_EXPOSURE_APERTURE_PRIORITY: i32 = 3;
+/// Number of buffers the camera streams into; this many frames are dropped after a settings change.
+const CAMERA_BUFFERS: u32 = 2;
+
 /// Wrapper for the camera, providing image capture functionality.
 pub struct CameraWrapper {
+    /// Name of the camera; empty for the primary camera.
+    name: String,
+    /// Name reported through the `Sensor` trait.
+    label: String,
     /// The camera instance used for capturing images.
     camera: Camera,
     /// The directory where captured images will be stored.
     image_directory: String,
     /// The last image captured, used for motion analysis.
-    last_image: Option<GrayImage>
+    last_image: Option<GrayImage>,
+    /// Quality of the saved JPEG images, 1-100.
+    jpeg_quality: u8,
+    /// Low-light capture settings.
+    night: NightModeConfig,
+    /// Whether night mode is currently active.
+    night_mode: bool,
+    /// IR illuminator output, if configured.
+    ir_led: Option<LineHandle>,
+    /// Region of the frame used for motion measurement, if configured.
+    motion_roi: Option<MotionRoi>,
+    /// Mask of `motion_roi`, built for the size of the captured frames.
+    motion_mask: Option<GrayImage>,
+    /// Motion-triggered clip settings, if enabled.
+    motion_clip: Option<MotionClipConfig>,
+    /// Timestamp overlay settings and font, if enabled.
+    overlay: Option<(OverlayConfig, FontArc)>,
+    /// Latest ambient light level, for switching night mode automatically.
+    light_level: Option<LightLevel>,
+    /// Whether to also save frames without the overlay, in `image_directory/raw/`.
+    save_raw: bool,
+    /// Size the images are saved at, if smaller than the capture resolution.
+    save_resolution: Option<[u32; 2]>,
+    /// Size of the thumbnails stored in the data file, if enabled.
+    thumbnail_size: Option<[u32; 2]>,
 }
 impl CameraWrapper {
-    /// Creates a new instance of `CameraWrapper`.
+    /// Creates a new instance of `CameraWrapper` with the default camera settings.
     ///
     /// # Arguments
     /// 
@@ -89,16 +296,154 @@ impl CameraWrapper {
     /// ```
     /// 
     pub fn new(image_directory: &str) -> Result<Self, Box<dyn Error>> {
-        let mut camera = Camera::new("/dev/video0")?;
+        Self::from_config(image_directory, &CameraConfig::default())
+    }
+
+    /// Creates a new instance of `CameraWrapper` using the given camera settings (device,
+    /// resolution, pixel format, frame interval, and JPEG quality).
+    ///
+    /// If night mode is enabled in `config`, the low-light exposure and gain are applied
+    /// immediately. If an IR illuminator is configured, its GPIO line is requested and held low.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the camera cannot be opened or configured, or the IR GPIO line cannot be requested.
+    pub fn from_config(image_directory: &str, config: &CameraConfig) -> Result<Self, Box<dyn Error>> {
+        let mut camera = Camera::new(&config.device)
+            .map_err(|e| format!("Failed to open camera {}: {}", config.device, e))?;
         camera.start(&Config {
-            interval: (1, 30),          
-            resolution: (1280, 720),
-            format: b"MJPG",             // MJPEG is widely supported
+            interval: (config.frame_interval[0], config.frame_interval[1]),
+            resolution: (config.resolution[0], config.resolution[1]),
+            format: config.format.as_bytes(),
+            nbuffers: CAMERA_BUFFERS,
             ..Default::default()
         })?;
         std::fs::create_dir_all(image_directory)?;
-        Ok(Self { camera, image_directory: image_directory.to_string(), last_image: None})
+        if config.save_raw {
+            std::fs::create_dir_all(format!("{}/raw", image_directory))?;
+        }
+
+        let ir_led = match &config.night.ir_led {
+            Some(gpio) => {
+                let mut chip = Chip::new(&gpio.chip)
+                    .map_err(|e| format!("Failed to open IR GPIO chip {}: {}", gpio.chip, e))?;
+                let handle = chip.get_line(gpio.line)?
+                    .request(LineRequestFlags::OUTPUT, 0, "sleep-recorder-ir")?;
+                info!("IR illuminator on {} line {}", gpio.chip, gpio.line);
+                Some(handle)
+            }
+            None => None,
+        };
+
+        let overlay = match &config.overlay {
+            overlay if !overlay.enabled => None,
+            overlay => Some((overlay.clone(), overlay_font(overlay.font.as_deref())?)),
+        };
+
+        let label = match config.name.as_str() {
+            "" => "Camera".to_string(),
+            name => format!("Camera {name}"),
+        };
+        let mut wrapper = Self {
+            name: config.name.clone(),
+            label,
+            camera,
+            image_directory: image_directory.to_string(),
+            last_image: None,
+            jpeg_quality: config.jpeg_quality,
+            night: config.night.clone(),
+            night_mode: false,
+            ir_led,
+            motion_roi: config.motion_roi.clone(),
+            motion_mask: None,
+            motion_clip: config.motion_clip.clone(),
+            overlay,
+            light_level: None,
+            save_raw: config.save_raw,
+            save_resolution: config.save_resolution,
+            thumbnail_size: config.thumbnail_size,
+        };
+        if config.night.enabled {
+            wrapper.set_night_mode(true)?;
+        }
+        Ok(wrapper)
+    }
+
+    /// Switches night mode from the latest reading in `light_level` before each capture (see
+    /// [`CameraWrapper::apply_light_level`]).
+    pub fn with_light_level(mut self, light_level: LightLevel) -> Self {
+        self.light_level = Some(light_level);
+        self
+    }
+
+    /// Name of the camera from its configuration; empty for the primary camera.
+    pub fn camera_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether night mode is currently active.
+    pub fn night_mode(&self) -> bool {
+        self.night_mode
+    }
+
+    /// Switches between night (low-light) and day capture settings.
+    ///
+    /// Night mode uses manual exposure with the configured exposure time and gain boost, and
+    /// switches on the IR illuminator (if configured) around each capture. Day mode restores
+    /// automatic exposure and, if `day_gain` is configured, the day gain.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the camera rejects one of the controls.
+    pub fn set_night_mode(&mut self, on: bool) -> Result<(), Box<dyn Error>> {
+        if on {
+            self.camera.set_control(V4L2_CID_EXPOSURE_AUTO, &V4L2_EXPOSURE_MANUAL)?;
+            self.camera.set_control(V4L2_CID_EXPOSURE_ABSOLUTE, &self.night.exposure_100us)?;
+            self.camera.set_control(V4L2_CID_GAIN, &self.night.gain)?;
+        } else {
+            self.camera.set_control(V4L2_CID_EXPOSURE_AUTO, &V4L2_EXPOSURE_APERTURE_PRIORITY)?;
+            if let Some(gain) = self.night.day_gain {
+                self.camera.set_control(V4L2_CID_GAIN, &gain)?;
+            }
+        }
+        info!("Camera night mode {}", if on { "enabled" } else { "disabled" });
+        self.night_mode = on;
+        Ok(())
+    }
+
+    /// Switches night mode according to the ambient light level, if `auto_lux` is configured.
+    ///
+    /// Night mode is switched on below `auto_lux` and off above twice that, so the camera doesn't
+    /// toggle back and forth around the threshold.
+    pub fn apply_light_level(&mut self, lux: f32) -> Result<(), Box<dyn Error>> {
+        let Some(threshold) = self.night.auto_lux else {
+            return Ok(());
+        };
+        if !self.night_mode && lux < threshold {
+            self.set_night_mode(true)?;
+        } else if self.night_mode && lux > 2.0 * threshold {
+            self.set_night_mode(false)?;
+        }
+        Ok(())
+    }
+
+    /// Captures a frame, switching on the IR illuminator first when in night mode.
+    fn capture_frame(&mut self) -> Result<rscam::Frame, Box<dyn Error>> {
+        let Some(ir_led) = self.ir_led.as_ref().filter(|_| self.night_mode) else {
+            return Ok(self.camera.capture()?);
+        };
+        ir_led.set_value(1)?;
+        std::thread::sleep(Duration::from_millis(self.night.ir_warmup_ms));
+        // Drop frames exposed before the illuminator was on
+        let result = (0..CAMERA_BUFFERS)
+            .try_for_each(|_| self.camera.capture().map(|_| ()))
+            .and_then(|_| self.camera.capture());
+        if let Err(e) = ir_led.set_value(0) {
+            warn!("Failed to switch off IR illuminator: {e}");
+        }
+        Ok(result?)
     }
+
     /// Captures an image from the camera and saves it to the specified directory.
     /// 
     /// # Arguments
@@ -110,54 +455,443 @@ impl CameraWrapper {
     /// * `Result<CameraAndMotionResult>` - A result containing the path to the saved image and difference from the last image
     /// 
     pub fn measure(&mut self, timestamp: u64) -> Result<CameraAndMotionResult, Box<dyn Error>> {
-        let frame = self.camera.capture()?;
+        let frame = self.capture_frame()?;
     
         let image_path = format!("{}/image_{}.jpg", self.image_directory, timestamp);
-        let image = image::load_from_memory(&frame)?;
+        let image = Self::decode_frame(&frame)?;
         let mut rgb_img = image.to_rgb8();
+        if let Some([width, height]) = self.save_resolution {
+            rgb_img = imageops::resize(&rgb_img, width, height, FilterType::Triangle);
+        }
+
+        let mut raw_image_path = None;
+        if self.save_raw {
+            let path = format!("{}/raw/image_{}.jpg", self.image_directory, timestamp);
+            self.save_jpeg(&rgb_img, &path)?;
+            raw_image_path = Some(path);
+        }
+        let thumbnail = self.thumbnail_size
+            .map(|size| Self::thumbnail(&rgb_img, size, self.jpeg_quality))
+            .transpose()?;
 
         // Add a timestamp to the image
-        Self::timestamp_image_mut(&mut rgb_img, timestamp)?;
+        if let Some((overlay, font)) = &self.overlay {
+            Self::timestamp_image_mut(&mut rgb_img, timestamp, overlay, font)?;
+        }
     
         // Save image
-        let path = Path::new(&image_path);
-        let mut file = BufWriter::new(File::create(path)?);
-        rgb_img
-            .write_to(&mut file, ImageFormat::Jpeg)?;
+        self.save_jpeg(&rgb_img, &image_path)?;
 
-        // Measure motion since last frame
+        // Measure motion since last frame, on the frame without overlay
         let gray_image = image.to_luma8();
-        let mut motion = None;
-        if let Some(last_image) = &self.last_image {
-            motion = crate::image_analysis::frame_difference(&gray_image, last_image).ok();
-        }
+        let motion = match self.last_image.take() {
+            Some(last_image) => self.motion(&gray_image, &last_image),
+            None => None,
+        };
         self.last_image = Some(gray_image);
 
-        Ok(CameraAndMotionResult { image_path, motion })
+        let mut clip_path = None;
+        if let Some(clip) = self.motion_clip.clone().filter(|clip| motion.is_some_and(|m| m >= clip.threshold)) {
+            let path = format!("{}/clip_{}.mjpeg", self.image_directory, timestamp);
+            match self.record_clip(&path, Duration::from_secs_f32(clip.duration_s)) {
+                Ok(()) => clip_path = Some(path),
+                Err(e) => warn!("{}: failed to record motion clip: {}", self.label, e),
+            }
+        }
+
+        Ok(CameraAndMotionResult { image_path, motion, clip_path, raw_image_path, thumbnail })
     }
 
-    fn timestamp_image_mut(image: &mut RgbImage, timestamp: u64) -> Result<(), Box<dyn Error>> {
-        // Load font
-        let font_data = std::fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf")?;
-        let font = FontArc::try_from_vec(font_data)?;
-    
-        // Format timestamp into 12-hour format
+    /// Saves `image` to `path` as a JPEG with the configured quality.
+    fn save_jpeg(&self, image: &RgbImage, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = BufWriter::new(File::create(Path::new(path))?);
+        image.write_with_encoder(JpegEncoder::new_with_quality(&mut file, self.jpeg_quality))?;
+        Ok(())
+    }
+
+    /// Records `duration` of frames to `path` as an MJPEG stream (concatenated JPEG frames),
+    /// with the IR illuminator on at night.
+    fn record_clip(&mut self, path: &str, duration: Duration) -> Result<(), Box<dyn Error>> {
+        let ir_led = self.ir_led.as_ref().filter(|_| self.night_mode);
+        if let Some(ir_led) = ir_led {
+            ir_led.set_value(1)?;
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        let start = Instant::now();
+        let mut record = || -> Result<(), Box<dyn Error>> {
+            while start.elapsed() < duration {
+                let frame = self.camera.capture()?;
+                match &frame.format {
+                    b"YUYV" => {
+                        let (width, height) = frame.resolution;
+                        yuyv_to_rgb(&frame, width, height)
+                            .ok_or("YUYV frame is smaller than its resolution")?
+                            .write_with_encoder(JpegEncoder::new_with_quality(&mut file, self.jpeg_quality))?;
+                    }
+                    _ => file.write_all(&frame)?,
+                }
+            }
+            Ok(file.flush()?)
+        };
+        let result = record();
+        if let Some(ir_led) = ir_led {
+            if let Err(e) = ir_led.set_value(0) {
+                warn!("Failed to switch off IR illuminator: {e}");
+            }
+        }
+        result
+    }
+
+    /// Motion between two frames, within the region of interest if one is configured.
+    fn motion(&mut self, frame: &GrayImage, last_frame: &GrayImage) -> Option<f32> {
+        let Some(roi) = &self.motion_roi else {
+            return frame_difference(frame, last_frame).ok();
+        };
+        // The camera may deliver another size than configured, so build the mask from the frame
+        if self.motion_mask.as_ref().map(|mask| mask.dimensions()) != Some(frame.dimensions()) {
+            self.motion_mask = Some(roi_mask(roi, frame.width(), frame.height()));
+        }
+        masked_frame_difference(frame, last_frame, self.motion_mask.as_ref()?).ok()
+    }
+
+    /// Decodes a captured frame, either JPEG-compressed (MJPG) or packed YUV 4:2:2 (YUYV).
+    fn decode_frame(frame: &rscam::Frame) -> Result<DynamicImage, Box<dyn Error>> {
+        match &frame.format {
+            b"YUYV" => {
+                let (width, height) = frame.resolution;
+                Ok(DynamicImage::ImageRgb8(yuyv_to_rgb(frame, width, height)
+                    .ok_or("YUYV frame is smaller than its resolution")?))
+            }
+            _ => Ok(image::load_from_memory(frame)?),
+        }
+    }
+
+    pub(crate) fn timestamp_image_mut(image: &mut RgbImage, timestamp: u64, overlay: &OverlayConfig, font: &FontArc) -> Result<(), Box<dyn Error>> {
+        // Format timestamp in local time
         let local_time = Local
             .timestamp_opt(timestamp as i64, 0)
             .single()
             .ok_or("Could not generate local timestamp".to_string())?;
-        let formatted = local_time.format("%I:%M:%S %p").to_string();
+        let formatted = local_time.format(&overlay.format).to_string();
         
         // Draw timestamp onto image
-        let scale = PxScale::from(36.0);
-        draw_text_mut(image, image::Rgb([255, 255, 0]), 20, 20, scale, &font, &formatted);
+        let scale = PxScale::from(overlay.size_px);
+        let [x, y] = overlay.position;
+        draw_text_mut(image, image::Rgb(overlay.color), x, y, scale, font, &formatted);
         Ok(())
     }
+
+    /// Encodes `image`, downscaled to `[width, height]`, as a JPEG thumbnail.
+    pub(crate) fn thumbnail(image: &RgbImage, [width, height]: [u32; 2], jpeg_quality: u8) -> Result<Thumbnail, Box<dyn Error>> {
+        let mut jpeg = Vec::new();
+        imageops::resize(image, width, height, FilterType::Triangle)
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, jpeg_quality))?;
+        Ok(Thumbnail(jpeg))
+    }
+}
+
+/// Font for the timestamp overlay: the TrueType file at `path`, or the embedded DejaVu Sans Bold.
+pub(crate) fn overlay_font(path: Option<&str>) -> Result<FontArc, Box<dyn Error>> {
+    static EMBEDDED: OnceLock<FontArc> = OnceLock::new();
+    match path {
+        Some(path) => {
+            let font_data = std::fs::read(path).map_err(|e| format!("Failed to read overlay font {}: {}", path, e))?;
+            Ok(FontArc::try_from_vec(font_data)?)
+        }
+        None => Ok(EMBEDDED
+            .get_or_init(|| {
+                FontArc::try_from_slice(include_bytes!("../assets/fonts/DejaVuSans-Bold.ttf"))
+                    .expect("Embedded font is valid")
+            })
+            .clone()),
+    }
+}
+
+/// Converts a packed YUV 4:2:2 (YUYV) buffer to RGB using the BT.601 coefficients.
+fn yuyv_to_rgb(data: &[u8], width: u32, height: u32) -> Option<RgbImage> {
+    let len = width as usize * height as usize * 2;
+    let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+    for pixels in data.get(..len)?.chunks_exact(4) {
+        let (u, v) = (pixels[1] as f32 - 128.0, pixels[3] as f32 - 128.0);
+        for y in [pixels[0], pixels[2]] {
+            let y = y as f32;
+            rgb.push((y + 1.402 * v).clamp(0.0, 255.0) as u8);
+            rgb.push((y - 0.344 * u - 0.714 * v).clamp(0.0, 255.0) as u8);
+            rgb.push((y + 1.772 * u).clamp(0.0, 255.0) as u8);
+        }
+    }
+    RgbImage::from_raw(width, height, rgb)
+}
+
+/// Wrapper for the SCD4x sensor, providing true (NDIR) CO2 measurements.
+pub struct SCD4xWrapper {
+    scd4x: Scd4x<SharedI2c, Delay>,
+}
+
+impl SCD4xWrapper {
+    /// Creates a new instance of `SCD4xWrapper` and starts periodic measurement.
+    ///
+    /// The sensor produces a new measurement every 5 s; the first is ready ~5 s after startup.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the I2C bus cannot be opened or the sensor does not respond.
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        Self::on_bus(DEFAULT_I2C_BUS)
+    }
+
+    /// Like [`SCD4xWrapper::new`], on the I2C bus at `bus`.
+    pub fn on_bus(bus: &str) -> Result<Self, Box<dyn Error>> {
+        let i2c_bus = shared_i2c(bus)?;
+        let mut scd4x = Scd4x::new(i2c_bus, Delay);
+        scd4x.start()?;
+        Ok(Self { scd4x })
+    }
+
+    /// Reads the latest measurement, or `None` if there is no new measurement since the last read.
+    pub fn measure(&mut self) -> Result<Option<Scd4xMeasurement>, Box<dyn Error>> {
+        self.scd4x.read()
+    }
+}
+
+/// Wrapper for the BH1750 sensor, providing ambient light measurements.
+pub struct BH1750Wrapper {
+    bh1750: Bh1750<SharedI2c>,
+    /// Where the latest reading is shared with the cameras, if set.
+    light_level: Option<LightLevel>,
+}
+
+/// Latest ambient light level in lux, shared by the BH1750 with the cameras.
+pub type LightLevel = Arc<Mutex<Option<f32>>>;
+
+impl BH1750Wrapper {
+    /// Creates a new instance of `BH1750Wrapper` (ADDR pin low) and starts continuous measurement.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the I2C bus cannot be opened or the sensor does not respond.
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        Self::on_bus(DEFAULT_I2C_BUS)
+    }
+
+    /// Like [`BH1750Wrapper::new`], on the I2C bus at `bus`.
+    pub fn on_bus(bus: &str) -> Result<Self, Box<dyn Error>> {
+        let i2c_bus = shared_i2c(bus)?;
+        let bh1750 = Bh1750::new(i2c_bus, Bh1750::<SharedI2c>::ADDRESS_LOW, &mut Delay)?;
+        Ok(Self { bh1750, light_level: None })
+    }
+
+    /// Publishes every reading to `light_level`, e.g. for [`CameraWrapper::with_light_level`].
+    pub fn with_light_level(mut self, light_level: LightLevel) -> Self {
+        self.light_level = Some(light_level);
+        self
+    }
+
+    /// Reads the current illuminance in lux.
+    pub fn measure(&mut self) -> Result<f32, Box<dyn Error>> {
+        self.bh1750.read_lux()
+    }
+}
+
+/// Wrapper for an HX711 with load cells under the bed legs, providing bed weight and occupancy.
+pub struct HX711Wrapper {
+    hx711: Hx711,
+    calibration: LoadCellCalibration,
+    occupied_kg: f32,
+    samples: usize,
+}
+
+impl HX711Wrapper {
+    /// Creates a new instance of `HX711Wrapper` from its configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the GPIO lines cannot be requested.
+    pub fn from_config(config: &Hx711Config) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            hx711: Hx711::new(&config.chip, config.dout_line, config.sck_line)?,
+            calibration: LoadCellCalibration { offset: config.offset, scale: config.scale },
+            occupied_kg: config.occupied_kg,
+            samples: config.samples,
+        })
+    }
+
+    /// Measures the weight on the bed in kilograms.
+    pub fn measure(&mut self) -> Result<f32, Box<dyn Error>> {
+        let raw = self.hx711.read_median(self.samples)?;
+        debug!("HX711 raw reading {}", raw);
+        Ok(self.calibration.weight_kg(raw))
+    }
+}
+
+/// Wrapper for a piezo film on an MCP3424 channel, capturing ballistocardiography (BCG) bursts.
+pub struct PiezoWrapper {
+    input: AdcInput<SharedI2c>,
+    burst_samples: usize,
+    /// Factor applied to the ADC voltage (see [`PiezoCalibration`]).
+    adc_scale: f32,
+}
+
+impl PiezoWrapper {
+    /// Nominal sample rate of a burst (the MCP342x at 12 bits).
+    const SAMPLE_RATE: f32 = 240.0;
+
+    /// Creates a new instance of `PiezoWrapper` on the MCP3424 at `config.address` on the I2C bus
+    /// at `bus`, shared with the thermistors on the same chip (see [`shared_adc`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the I2C bus cannot be opened or the channel or gain is invalid.
+    pub fn from_config(bus: &str, config: &PiezoConfig) -> Result<Self, Box<dyn Error>> {
+        let input = AdcInput::new(shared_adc(bus, config.address)?, adc_channel(config.channel)?, adc_gain(config.gain)?);
+        Ok(Self { input, burst_samples: (config.burst_s * Self::SAMPLE_RATE) as usize, adc_scale: 1.0 })
+    }
+
+    /// Applies the ADC scale factor in `calibration` to the samples.
+    pub fn with_calibration(mut self, calibration: &PiezoCalibration) -> Self {
+        self.adc_scale = calibration.adc_scale;
+        self
+    }
+
+    /// Captures a burst and estimates heart and respiration rates from it.
+    ///
+    /// Returns the burst in millivolts. Blocks for the length of the burst.
+    pub fn measure(&mut self) -> Result<(Vec<f32>, BcgEstimate), Box<dyn Error>> {
+        let capture = self.input.capture(self.burst_samples)?;
+        let samples_mv: Vec<f32> = capture.samples.iter().map(|s| s.voltage.millivolts() * self.adc_scale).collect();
+        // Use the measured rate, as I2C latency can make it fall short of 240 SPS
+        let sample_rate = match (capture.samples.first(), capture.samples.last()) {
+            (Some(first), Some(last)) if last.offset > first.offset => {
+                (capture.samples.len() - 1) as f32 / (last.offset - first.offset).as_secs_f32()
+            }
+            _ => Self::SAMPLE_RATE,
+        };
+        let estimate = bcg::estimate(&samples_mv, sample_rate);
+        Ok((samples_mv, estimate))
+    }
+}
+
+/// Wrapper for a PIR motion sensor on a GPIO line (high while motion is detected).
+///
+/// Edges are watched on a background thread, so motion between two polls is not missed.
+pub struct PirWrapper {
+    /// Set on every rising edge, cleared when polled.
+    triggered: Arc<AtomicBool>,
+    /// Current level of the line.
+    active: Arc<AtomicBool>,
+}
+
+impl PirWrapper {
+    /// Creates a new instance of `PirWrapper`, requesting edge events on the configured line.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the GPIO chip cannot be opened or the line cannot be requested.
+    pub fn new(config: &GpioLineConfig) -> Result<Self, Box<dyn Error>> {
+        let mut chip = Chip::new(&config.chip)
+            .map_err(|e| format!("Failed to open PIR GPIO chip {}: {}", config.chip, e))?;
+        let events = chip.get_line(config.line)?
+            .events(LineRequestFlags::INPUT, EventRequestFlags::BOTH_EDGES, "sleep-recorder-pir")?;
+        let triggered = Arc::new(AtomicBool::new(false));
+        let active = Arc::new(AtomicBool::new(events.get_value()? == 1));
+
+        let (thread_triggered, thread_active) = (triggered.clone(), active.clone());
+        // Blocks on the line's event file for the lifetime of the process
+        std::thread::spawn(move || {
+            for event in events {
+                match event.map(|e| e.event_type()) {
+                    Ok(EventType::RisingEdge) => {
+                        thread_active.store(true, Ordering::Relaxed);
+                        thread_triggered.store(true, Ordering::Relaxed);
+                    }
+                    Ok(EventType::FallingEdge) => thread_active.store(false, Ordering::Relaxed),
+                    Err(e) => {
+                        warn!("PIR event error, stopping: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self { triggered, active })
+    }
+
+    /// Whether motion was detected since the last call (or is being detected now).
+    pub fn measure(&mut self) -> bool {
+        self.triggered.swap(false, Ordering::Relaxed) || self.active.load(Ordering::Relaxed)
+    }
+}
+
+/// Wrapper for the PMS5003 sensor, providing particulate matter measurements.
+pub struct PMS5003Wrapper {
+    pms5003: Pms5003,
+}
+
+impl PMS5003Wrapper {
+    /// Creates a new instance of `PMS5003Wrapper` on the given serial port.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the serial port cannot be opened or configured.
+    pub fn new(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Self { pms5003: Pms5003::open(path)? })
+    }
+
+    /// Requests and reads a measurement.
+    pub fn measure(&mut self) -> Result<PmMeasurement, Box<dyn Error>> {
+        self.pms5003.read()
+    }
+}
+
+/// Wrapper for the SGP40 sensor, providing the Sensirion VOC index.
+pub struct SGP40Wrapper {
+    sgp40: Sgp40<SharedI2c, Delay>,
+    voc_index: VocIndex,
+    cal_temp: f32,
+    cal_humidity: f32,
+}
+
+impl SGP40Wrapper {
+    /// Creates a new instance of `SGP40Wrapper`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cal_temp` - Compensation temperature in Celsius.
+    /// * `cal_humidity` - Compensation relative humidity in percent.
+    /// * `sampling_interval_s` - Seconds between measurements, used by the VOC index algorithm.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the I2C bus cannot be opened or the sensor does not respond.
+    pub fn new(cal_temp: f32, cal_humidity: f32, sampling_interval_s: u64) -> Result<Self, Box<dyn Error>> {
+        Self::on_bus(DEFAULT_I2C_BUS, cal_temp, cal_humidity, sampling_interval_s)
+    }
+
+    /// Like [`SGP40Wrapper::new`], on the I2C bus at `bus`.
+    pub fn on_bus(bus: &str, cal_temp: f32, cal_humidity: f32, sampling_interval_s: u64) -> Result<Self, Box<dyn Error>> {
+        let i2c_bus = shared_i2c(bus)?;
+        let mut sgp40 = Sgp40::new(i2c_bus, Delay);
+        // The first raw signal is discarded by the algorithm anyway, but it checks the sensor is there
+        sgp40.measure_raw(cal_temp, cal_humidity)?;
+        Ok(Self {
+            sgp40,
+            voc_index: VocIndex::new(sampling_interval_s as f32),
+            cal_temp,
+            cal_humidity,
+        })
+    }
+
+    /// Measures the raw signal and returns the updated VOC index (0 during the first 45 samples).
+    pub fn measure(&mut self) -> Result<u16, Box<dyn Error>> {
+        let sraw = self.sgp40.measure_raw(self.cal_temp, self.cal_humidity)?;
+        Ok(self.voc_index.process(sraw))
+    }
 }
 
 /// Wrapper for the ENS160 sensor, providing air quality measurements.
 pub struct ENS160Wrapper {
-    ens160: Ens160<I2cdev, Delay>,
+    ens160: Ens160<SharedI2c, Delay>,
+    /// eCO2 baseline correction.
+    calibration: Ens160Calibration,
 }
 impl ENS160Wrapper {
     /// Creates a new instance of `ENS160Wrapper`.
@@ -184,13 +918,24 @@ impl ENS160Wrapper {
     /// ```
     /// 
     pub fn new(cal_temp: f32, cal_humidity: f32) -> Result<Self, String> {
-        let i2c_bus = I2cdev::new("/dev/i2c-1").map_err(|e| format!("I2C Initialization error: {:?}", e))?;
+        Self::on_bus(DEFAULT_I2C_BUS, cal_temp, cal_humidity)
+    }
+
+    /// Like [`ENS160Wrapper::new`], on the I2C bus at `bus`.
+    pub fn on_bus(bus: &str, cal_temp: f32, cal_humidity: f32) -> Result<Self, String> {
+        let i2c_bus = shared_i2c(bus).map_err(|e| format!("I2C Initialization error: {:?}", e))?;
         let delay = Delay;
         let mut ens160 = Ens160::new_secondary_address(i2c_bus, delay);
         ens160.initialize().map_err(|e| format!("ENS160 Initialization error: {:?}", e))?;
         ens160.set_temp_rh_comp(cal_temp, (cal_humidity * 100.) as u16).map_err(|e| format!("ENS160 Initialization error: {:?}", e))?;
         std::thread::sleep(Duration::from_millis(500));  // wait for the sensor to stabilize
-        Ok(Self { ens160 })
+        Ok(Self { ens160, calibration: Ens160Calibration::default() })
+    }
+
+    /// Corrects the eCO2 readings for the baseline in `calibration`.
+    pub fn with_calibration(mut self, calibration: &Ens160Calibration) -> Self {
+        self.calibration = calibration.clone();
+        self
     }
     /// Measures and returns the current air quality measurements from the ENS160 sensor.
     /// 
@@ -214,22 +959,169 @@ impl ENS160Wrapper {
     }
 }
 
+/// Health metrics of the host running the recorder.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SystemStats {
+    /// CPU temperature in degrees Celsius.
+    pub cpu_temp_c: Option<f32>,
+    /// 1-minute load average.
+    pub load_avg_1m: Option<f32>,
+    /// Free space on the data volume in MiB.
+    pub disk_free_mb: Option<u64>,
+    /// Memory in use, in percent of total.
+    pub mem_used_percent: Option<f32>,
+}
+
+/// Sensor for the host's own health: CPU temperature, load, free disk space on the data volume,
+/// and memory usage. Thermal throttling and full disks would otherwise go unnoticed.
+pub struct SystemStatsWrapper {
+    /// Directory on the volume whose free space is reported.
+    data_path: String,
+}
+
+impl SystemStatsWrapper {
+    /// Creates a host metrics sensor reporting free space on the volume containing `data_path`.
+    pub fn new(data_path: &str) -> Self {
+        Self { data_path: data_path.to_string() }
+    }
+
+    /// Samples the host metrics. Metrics that can't be read are `None`.
+    // The statvfs block counts are only u64 on 64-bit targets.
+    #[allow(clippy::useless_conversion)]
+    pub fn measure(&self) -> SystemStats {
+        let read = |path: &str| std::fs::read_to_string(path)
+            .map_err(|e| warn!("Failed to read {path}: {e}"))
+            .ok();
+        SystemStats {
+            cpu_temp_c: read("/sys/class/thermal/thermal_zone0/temp")
+                .and_then(|t| t.trim().parse::<f32>().ok())
+                .map(|millidegrees| millidegrees / 1000.0),
+            load_avg_1m: read("/proc/loadavg").as_deref().and_then(parse_loadavg),
+            disk_free_mb: nix::sys::statvfs::statvfs(self.data_path.as_str())
+                .map_err(|e| warn!("Failed to stat {}: {e}", self.data_path))
+                .ok()
+                .map(|s| u64::from(s.blocks_available()) * u64::from(s.fragment_size()) / (1024 * 1024)),
+            mem_used_percent: read("/proc/meminfo").as_deref().and_then(parse_meminfo_used_percent),
+        }
+    }
+}
+
+/// 1-minute load average from the contents of /proc/loadavg.
+fn parse_loadavg(contents: &str) -> Option<f32> {
+    contents.split_whitespace().next()?.parse().ok()
+}
+
+/// Memory in use (total minus available) in percent, from the contents of /proc/meminfo.
+fn parse_meminfo_used_percent(contents: &str) -> Option<f32> {
+    let field = |name: &str| contents.lines()
+        .find_map(|l| l.strip_prefix(name))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse::<f64>().ok());
+    let total = field("MemTotal:")?;
+    let available = field("MemAvailable:")?;
+    (total > 0.0).then(|| (100.0 * (total - available) / total) as f32)
+}
+
+/// DS18B20 1-Wire temperature probe, read through the kernel w1 sysfs interface
+/// (the `w1-gpio` and `w1-therm` drivers must be loaded).
+pub struct Ds18b20Wrapper {
+    /// Name of the probe, used in the dataset name.
+    name: String,
+    /// Name reported through the `Sensor` trait.
+    label: String,
+    /// Path of the probe's `w1_slave` file.
+    path: std::path::PathBuf,
+}
+
+impl Ds18b20Wrapper {
+    /// Creates a wrapper for the probe with 1-Wire ID `id` (e.g. 28-0316a2799aff).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error listing the connected 1-Wire devices if the probe is not present.
+    pub fn new(id: &str, name: &str) -> Result<Self, Box<dyn Error>> {
+        let devices = Path::new("/sys/bus/w1/devices");
+        let path = devices.join(id).join("w1_slave");
+        if !path.exists() {
+            let available = std::fs::read_dir(devices)
+                .map(|entries| entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.file_name().to_string_lossy().into_owned())
+                    .filter(|id| id.starts_with("28-"))
+                    .collect::<Vec<_>>()
+                    .join(", "))
+                .unwrap_or_default();
+            return Err(format!("DS18B20 probe {} not found. Connected probes: [{}]", id, available).into());
+        }
+        Ok(Self { name: name.to_string(), label: format!("DS18B20 {name}"), path })
+    }
+
+    /// Name of the probe.
+    pub fn probe_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Reads the probe temperature in degrees Celsius. A conversion takes up to 750 ms.
+    pub fn measure(&self) -> Result<f32, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(&self.path)?;
+        parse_w1_slave(&contents)
+            .ok_or_else(|| format!("Invalid or CRC-failed DS18B20 reading: {:?}", contents.trim()).into())
+    }
+}
+
+/// Temperature in degrees Celsius from the contents of a w1_slave file:
+/// ```text
+/// 72 01 4b 46 7f ff 0e 10 57 : crc=57 YES
+/// 72 01 4b 46 7f ff 0e 10 57 t=23125
+/// ```
+fn parse_w1_slave(contents: &str) -> Option<f32> {
+    let mut lines = contents.lines();
+    if !lines.next()?.trim_end().ends_with("YES") {
+        return None;
+    }
+    let (_, millidegrees) = lines.next()?.split_once("t=")?;
+    Some(millidegrees.trim().parse::<f32>().ok()? / 1000.0)
+}
+
+/// MCP342x input channel for a validated channel number (1-4).
+fn adc_channel(channel: u8) -> Result<Channel, Box<dyn Error>> {
+    match channel {
+        1 => Ok(Channel::Ch1),
+        2 => Ok(Channel::Ch2),
+        3 => Ok(Channel::Ch3),
+        4 => Ok(Channel::Ch4),
+        channel => Err(format!("Invalid ADC channel {}", channel).into()),
+    }
+}
+
+/// MCP342x PGA gain for a validated gain factor (1, 2, 4, or 8).
+fn adc_gain(gain: u8) -> Result<Gain, Box<dyn Error>> {
+    match gain {
+        1 => Ok(Gain::G1),
+        2 => Ok(Gain::G2),
+        4 => Ok(Gain::G4),
+        8 => Ok(Gain::G8),
+        gain => Err(format!("Invalid ADC gain {}", gain).into()),
+    }
+}
+
 /// Thermistor wrapper for MCP342x ADC, with internal voltage-temperature conversion.
 pub struct ThermistorWrapper {
-    /// MCP342x ADC instance for reading thermistor voltage.
-    adc: MCP342x<I2cdev>,
+    /// Name of the thermistor; empty for the primary thermistor.
+    name: String,
+    /// Name reported through the `Sensor` trait.
+    label: String,
+    /// MCP342x ADC input with the thermistor divider attached.
+    input: AdcInput<SharedI2c>,
+    /// Divider the thermistor is wired into.
+    divider: Divider,
+    /// Steinhart-Hart coefficients of the thermistor.
+    coefficients: SteinhartHart,
+    /// Offset and ADC scale applied to each reading.
+    calibration: ThermistorCalibration,
 }
 impl ThermistorWrapper {
-    /// Constants for thermistor voltage divider and Steinhart-Hart coefficients.
-    const R_I: f32 = 3200.0; // Voltage divider resistor value in Ohms
-    const V_SS: f32 = 5.3; // Supply voltage in Volts
-    // Steinhart-Hart coefficients for the thermistor
-    // https://docs.google.com/spreadsheets/d/1Nf47ojSvB1wB5JmTSs-cXLMhxmIMcvHLitLAx047UdE/edit?pli=1&gid=1211676988#gid=1211676988
-    const A : f64 = 0.0002264321654;
-    const B : f64 = 0.0003753456578;
-    const C : f64 = -0.0000004022657641;
-
-    /// Creates a new instance of `ThermistorWrapper`.
+    /// Creates a new instance of `ThermistorWrapper` for the reference build (see
+    /// [`ThermistorConfig::default`]).
     /// 
     /// # Returns
     /// 
@@ -249,49 +1141,201 @@ impl ThermistorWrapper {
     /// 
     /// # Note
     /// 
-    /// * The channel, voltage divider, and S-H coefficients are hardcoded for the current setup.
-    /// * The ADC is set to one-shot mode, and a delay is introduced to allow for measurement stabilization.
+    /// * The ADC is shared with the other inputs on its address (see [`shared_adc`]); a one-shot
+    ///   read through it checks that the ADC responds.
     pub fn new() -> Result<Self, Box<dyn Error>> {
-        let i2c_bus = I2cdev::new("/dev/i2c-1")?;
-        let mut adc = MCP342x::new(i2c_bus, 0x68);
-        adc.set_channel(Channel::Ch3);
-        adc.set_gain(Gain::G1);
-        adc.set_resolution(Resolution::Bits16);
-        adc.convert()?; // Force one shot mode and write the configuration
-        std::thread::sleep(Duration::from_millis(10));
-        Ok(Self { adc })
+        Self::from_config(DEFAULT_I2C_BUS, &ThermistorConfig::default())
+    }
+
+    /// Creates a new instance of `ThermistorWrapper` with the ADC on the I2C bus at `bus`, using
+    /// the ADC address, channel, gain, divider, and Steinhart-Hart coefficients from `config`.
+    /// The ADC is shared with the other sensors on the same chip (see [`shared_adc`]).
+    /// Named thermistors are stored in the `thermistor_temp_<name>` dataset.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the configuration is invalid, the I2C bus cannot be opened, or the
+    ///   ADC does not respond.
+    pub fn from_config(bus: &str, config: &ThermistorConfig) -> Result<Self, Box<dyn Error>> {
+        config.validate()?;
+        let input = AdcInput::new(shared_adc(bus, config.address)?, adc_channel(config.channel)?, adc_gain(config.gain)?);
+        input.read(Resolution::Bits16)?; // Check that the ADC responds
+        let divider = Divider { resistance_ohms: config.divider_ohms, supply_voltage: config.supply_voltage };
+        let [a, b, c] = config.steinhart_hart;
+        let label = match config.name.as_str() {
+            "" => "Thermistor".to_string(),
+            name => format!("Thermistor {name}"),
+        };
+        Ok(Self {
+            name: config.name.clone(),
+            label,
+            input,
+            divider,
+            coefficients: SteinhartHart { a, b, c },
+            calibration: ThermistorCalibration::default(),
+        })
+    }
+
+    /// Applies `calibration`: its Steinhart-Hart coefficients (if set) replace the configured
+    /// ones, and the ADC scale and temperature offset are applied to each reading.
+    pub fn with_calibration(mut self, calibration: &ThermistorCalibration) -> Self {
+        if let Some([a, b, c]) = calibration.steinhart_hart {
+            self.coefficients = SteinhartHart { a, b, c };
+        }
+        self.calibration = calibration.clone();
+        self
     }
     pub fn measure(&mut self) -> Option<f32> {
-        let voltage = self.adc.convert_and_read(true, false).map_err(|e| {
-            warn!("Thermistor measurement error: {:?}", e);
+        let voltage = self.input.read(Resolution::Bits16).map_err(|e| {
+            warn!("{} measurement error: {:?}", self.label, e);
         }).ok()?;
 
-        info!("Thermistor voltage: {}", voltage);
+        info!("{} voltage: {}", self.label, voltage);
 
-        // R = (voltage divider resistor [Ohms]) * (Vss [V] / voltage [V] - 1)
-        let resistance: f64 = (Self::R_I * (Self::V_SS / voltage - 1.0)).into();
-        // Calculate temperature in Celsius using the Steinhart-Hart equation
-        let temp = 1.0 / (Self::A + Self::B*resistance.ln() + Self::C*resistance.ln().powi(3)) - 273.15;
-        Some(temp as f32)
+        let voltage = Volts(voltage.volts() * self.calibration.adc_scale);
+        let resistance_ohms = self.divider.thermistor_resistance(voltage);
+        Some(self.coefficients.temperature_c(resistance_ohms) as f32 + self.calibration.offset_c)
     }
 }
 
-/// Provides functionality to record audio using `ffmpeg`.
-pub struct AudioRecorder {
-    /// The directory where the recorded audio files will be stored.
-    pub audio_directory: String,
-    /// The duration for which the audio will be recorded.
-    pub recording_time: Duration,
-    /// The identifier of the audio capture device (e.g. plughw:1,0)
-    pub device_id: String
+/// An ALSA capture device, as listed in /proc/asound.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureDevice {
+    /// Card index.
+    pub card: u32,
+    /// Device index on the card.
+    pub device: u32,
+    /// Card ID, stable across reboots (e.g. "Camera").
+    pub card_id: String,
+    /// Human-readable card name, including the long name (e.g. "Arducam Technology Co., Ltd. USB Camera at ...").
+    pub name: String,
 }
- 
-impl AudioRecorder {
-    /// Creates a new instance of `AudioRecorder`.
-    ///
-    /// # Arguments
-    ///
-    /// * `audio_directory` - A string representing the directory where audio files will be stored.
+
+impl CaptureDevice {
+    /// ALSA device name for the device, addressed by card ID rather than index.
+    pub fn alsa_name(&self) -> String {
+        format!("plughw:CARD={},DEV={}", self.card_id, self.device)
+    }
+}
+
+/// Lists the ALSA capture devices.
+pub fn list_capture_devices() -> Result<Vec<CaptureDevice>, Box<dyn Error>> {
+    let cards = std::fs::read_to_string("/proc/asound/cards")
+        .map_err(|e| format!("Failed to read /proc/asound/cards: {}", e))?;
+    let pcm = std::fs::read_to_string("/proc/asound/pcm").unwrap_or_default();
+    Ok(parse_capture_devices(&cards, &pcm))
+}
+
+/// Finds the first capture device whose card ID or name contains `pattern` (case-insensitive).
+///
+/// # Errors
+///
+/// Returns an error listing the available capture devices if none match.
+pub fn find_capture_device(pattern: &str) -> Result<CaptureDevice, Box<dyn Error>> {
+    let devices = list_capture_devices()?;
+    let needle = pattern.to_lowercase();
+    devices.iter()
+        .find(|d| d.card_id.to_lowercase().contains(&needle) || d.name.to_lowercase().contains(&needle))
+        .cloned()
+        .ok_or_else(|| {
+            let available = devices.iter()
+                .map(|d| format!("{} ({})", d.alsa_name(), d.name))
+                .collect::<Vec<_>>();
+            let available = if available.is_empty() { "none".to_string() } else { available.join(", ") };
+            format!("No capture device matching {:?}. Available capture devices: {}", pattern, available).into()
+        })
+}
+
+/// Parses the contents of /proc/asound/cards and /proc/asound/pcm into capture devices.
+fn parse_capture_devices(cards: &str, pcm: &str) -> Vec<CaptureDevice> {
+    // Cards: " 2 [Camera         ]: USB-Audio - USB Camera" followed by an indented long name line
+    let mut card_info: HashMap<u32, (String, String)> = HashMap::new();
+    let mut lines = cards.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some((index, rest)) = line.trim_start().split_once(" [") else { continue };
+        let (Ok(index), Some((id, name))) = (index.parse::<u32>(), rest.split_once("]:")) else { continue };
+        let mut name = name.split_once(" - ").map_or(name, |(_, n)| n).trim().to_string();
+        if let Some(long_name) = lines.next_if(|l| l.starts_with("  ") && !l.contains(" [")) {
+            name = format!("{} - {}", name, long_name.trim());
+        }
+        card_info.insert(index, (id.trim().to_string(), name));
+    }
+
+    // PCM devices: "02-00: USB Audio : USB Audio : capture 1"
+    pcm.lines()
+        .filter(|line| line.contains("capture"))
+        .filter_map(|line| {
+            let (address, _) = line.split_once(':')?;
+            let (card, device) = address.split_once('-')?;
+            let (card, device) = (card.trim().parse().ok()?, device.trim().parse().ok()?);
+            let (card_id, name) = card_info.get(&card)?.clone();
+            Some(CaptureDevice { card, device, card_id, name })
+        })
+        .collect()
+}
+
+/// Sample rate requested from the device by the native capture backend.
+#[cfg(feature = "native-audio")]
+const NATIVE_SAMPLE_RATE: u32 = 48_000;
+
+/// How long `ffmpeg` gets to finish its file after being interrupted before it is killed.
+const FFMPEG_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A block of mono 16-bit samples published by `AudioRecorder` while capturing.
+#[derive(Clone, Debug)]
+pub struct AudioChunk {
+    /// Start of the recording the samples belong to (its `start_time_s`), in seconds since the
+    /// epoch.
+    pub recording_start_s: u64,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// The samples, following those of the previous chunk of the recording.
+    pub samples: Arc<[i16]>,
+}
+
+/// Where `AudioRecorder` gets its audio from.
+#[derive(Clone, Debug)]
+pub enum AudioSource {
+    /// ALSA capture device recorded with `ffmpeg` (e.g. plughw:1,0).
+    Alsa(String),
+    /// ALSA capture device read in-process and written as WAV (e.g. plughw:1,0).
+    #[cfg(feature = "native-audio")]
+    Native(String),
+    /// Test-only source that "records" the audio of a fixture file (WAV or MP3) as WAV, repeated or
+    /// cut to the length of the recording, so the audio pipeline can be exercised without audio
+    /// hardware. Recordings are timestamped by tokio's clock, from `started` (the same instant on
+    /// the system clock and tokio's), so tests can run them with the clock paused.
+    #[cfg(any(test, feature = "audio-loopback"))]
+    Loopback { fixture: std::path::PathBuf, started: (SystemTime, tokio::time::Instant) },
+    /// Synthesized bedroom audio written as WAV in real time (see [`crate::simulation::record_audio`]).
+    #[cfg(feature = "simulation")]
+    Simulated,
+}
+
+/// Provides functionality to record audio, either with `ffmpeg` or natively through ALSA.
+pub struct AudioRecorder {
+    /// The directory where the recorded audio files will be stored.
+    pub audio_directory: String,
+    /// The duration for which the audio will be recorded.
+    pub recording_time: Duration,
+    /// The audio source, normally an ALSA capture device.
+    pub source: AudioSource,
+    /// File format written by `ffmpeg`.
+    format: AudioFormat,
+    /// Bitrate in kbit/s for lossy formats.
+    bitrate_kbps: u32,
+    /// Stops native capture as soon as it is cancelled.
+    cancel: CancellationToken,
+    /// Publishes captured samples to subscribers (native capture only).
+    samples: broadcast::Sender<AudioChunk>,
+}
+ 
+impl AudioRecorder {
+    /// Creates a new instance of `AudioRecorder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `audio_directory` - A string representing the directory where audio files will be stored.
     /// * `recording_time` - A `Duration` representing how long the recording should last.
     /// * `device_id` - A string representing the identifier of the audio capture device.
     ///
@@ -299,14 +1343,106 @@ impl AudioRecorder {
     ///
     /// An instance of `AudioRecorder` initialized with the specified parameters.
     pub fn new(audio_directory: &str, recording_time: Duration, device_id: String) -> Result<Self, Box<dyn Error>> {
+        Self::with_source(audio_directory, recording_time, AudioSource::Alsa(device_id))
+    }
+
+    /// Creates an `AudioRecorder` for the device, segment length, and backend in `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the audio directory cannot be created, if `device_match` matches no
+    /// capture device, or if the native backend is selected but the crate was built without the
+    /// `native-audio` feature.
+    pub fn from_config(audio_directory: &str, config: &AudioConfig) -> Result<Self, Box<dyn Error>> {
+        let device = match &config.device_match {
+            Some(pattern) => {
+                let device = find_capture_device(pattern)?;
+                info!("Using capture device {} ({})", device.alsa_name(), device.name);
+                device.alsa_name()
+            }
+            None => config.device.clone(),
+        };
+        let source = match config.backend {
+            AudioBackend::Ffmpeg => AudioSource::Alsa(device),
+            #[cfg(feature = "native-audio")]
+            AudioBackend::Native => AudioSource::Native(device),
+            #[cfg(not(feature = "native-audio"))]
+            AudioBackend::Native => return Err("The native audio backend requires the native-audio feature".into()),
+        };
+        Ok(Self::with_source(audio_directory, Duration::from_secs(config.segment_s), source)?
+            .with_format(config.format, config.bitrate_kbps))
+    }
+
+    /// Creates an `AudioRecorder` recording from `source`.
+    pub fn with_source(audio_directory: &str, recording_time: Duration, source: AudioSource) -> Result<Self, Box<dyn Error>> {
         std::fs::create_dir_all(audio_directory)?;
-        Ok(Self { audio_directory: audio_directory.to_string(), recording_time, device_id })
+        let (samples, _) = broadcast::channel(64);
+        Ok(Self {
+            audio_directory: audio_directory.to_string(),
+            recording_time,
+            source,
+            format: AudioFormat::Mp3,
+            bitrate_kbps: 128,
+            cancel: CancellationToken::new(),
+            samples,
+        })
+    }
+
+    /// Sets the file format and bitrate (for lossy formats) that `ffmpeg` records. Defaults to
+    /// 128 kbit/s MP3. The native backend always records WAV.
+    pub fn with_format(mut self, format: AudioFormat, bitrate_kbps: u32) -> Self {
+        self.format = format;
+        self.bitrate_kbps = bitrate_kbps;
+        self
+    }
+
+    /// Uses `cancel` to end the current recording early, keeping the partial recording. `ffmpeg`
+    /// is interrupted and finishes the file it is writing; native capture stops within one period
+    /// (~100 ms).
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Subscribes to the samples being captured (mono, 48 kHz unless the device doesn't support
+    /// it), e.g. for live analysis.
+    ///
+    /// Subscribe before a recording starts: the `ffmpeg` backend only sets up its sample output
+    /// when there are subscribers. Chunks are dropped for subscribers that fall behind (the
+    /// receiver reports `Lagged`). The loopback source doesn't publish samples.
+    pub fn subscribe_samples(&self) -> broadcast::Receiver<AudioChunk> {
+        self.samples.subscribe()
+    }
+
+    /// Creates an `AudioRecorder` that records the audio of `fixture` instead of capturing from a
+    /// device.
+    ///
+    /// Each "recording" waits for `recording_time`, so the timing of the recording loop matches a
+    /// real device, and then writes the fixture's audio for that long into `audio_directory`. A
+    /// cancelled recording is cut to the time recorded.
+    #[cfg(any(test, feature = "audio-loopback"))]
+    pub fn loopback(audio_directory: &str, recording_time: Duration, fixture: impl Into<std::path::PathBuf>) -> Result<Self, Box<dyn Error>> {
+        // Starts on a whole second, so the timestamps of the recordings don't depend on when the
+        // test runs
+        let now_s = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let started = (UNIX_EPOCH + Duration::from_secs(now_s), tokio::time::Instant::now());
+        Self::with_source(audio_directory, recording_time, AudioSource::Loopback { fixture: fixture.into(), started })
+    }
+
+    /// Creates an `AudioRecorder` that records synthesized audio instead of capturing from a device.
+    #[cfg(feature = "simulation")]
+    pub fn simulated(audio_directory: &str, recording_time: Duration) -> Result<Self, Box<dyn Error>> {
+        Self::with_source(audio_directory, recording_time, AudioSource::Simulated)
     }
-    /// Asynchronously records audio by spawning a `ffmpeg` process.
+
+    /// Asynchronously records audio from the configured `AudioSource`.
+    ///
+    /// This method constructs a file path using the current Unix timestamp. For an ALSA source it spawns
+    /// an `ffmpeg` command that captures audio from the device and saves it in the configured format
+    /// (MP3 by default) in the specified `audio_directory`. A native source reads the device directly and saves a WAV file.
     ///
-    /// This method constructs a file path using the current Unix timestamp and spawns an `ffmpeg`
-    /// command that captures audio from the device specified by `device_id`. The recording is saved
-    /// as an MP3 file in the specified `audio_directory`.
+    /// The recording ends early when the recorder's cancellation token (see [`AudioRecorder::with_cancel`])
+    /// is cancelled; the partial file is kept.
     ///
     /// # Returns
     ///
@@ -321,38 +1457,82 @@ impl AudioRecorder {
     /// * There's an error spawning the `ffmpeg` process.
     /// * The `ffmpeg` process exits with a non-success status.
     pub async fn async_audio_recording(&self) -> Result<AudioRecording, Box<dyn Error + Send + Sync>> {
-        let timestamp = SystemTime::now()
+        let timestamp = self.now()
             .duration_since(UNIX_EPOCH)?
             .as_secs();
-        
-        let filepath = format!("{}audio_{}.mp3", &self.audio_directory, timestamp);
+
+        match &self.source {
+            AudioSource::Alsa(device_id) => self.ffmpeg_recording(device_id, timestamp).await,
+            #[cfg(feature = "native-audio")]
+            AudioSource::Native(device_id) => self.native_recording(device_id, timestamp).await,
+            #[cfg(any(test, feature = "audio-loopback"))]
+            AudioSource::Loopback { fixture, .. } => self.loopback_recording(fixture, timestamp).await,
+            #[cfg(feature = "simulation")]
+            AudioSource::Simulated => self.simulated_recording(timestamp).await,
+        }
+    }
+
+    /// Current time of the source: the system clock, except for the loopback source (see
+    /// [`AudioSource::Loopback`]).
+    fn now(&self) -> SystemTime {
+        #[cfg(any(test, feature = "audio-loopback"))]
+        if let AudioSource::Loopback { started: (system_time, instant), .. } = &self.source {
+            return *system_time + instant.elapsed();
+        }
+        SystemTime::now()
+    }
+
+    /// Records from an ALSA device with `ffmpeg`, salvaging the partial file if interrupted.
+    async fn ffmpeg_recording(&self, device_id: &str, timestamp: u64) -> Result<AudioRecording, Box<dyn Error + Send + Sync>> {
+        let filepath = format!("{}audio_{}.{}", &self.audio_directory, timestamp, self.format.extension());
+        let bitrate = format!("{}k", self.bitrate_kbps);
+        let codec_args: &[&str] = match self.format {
+            AudioFormat::Mp3 => &["-acodec", "libmp3lame", "-b:a", &bitrate],
+            AudioFormat::Wav => &["-acodec", "pcm_s16le"],
+            AudioFormat::Flac => &["-acodec", "flac"],
+            AudioFormat::Opus => &["-acodec", "libopus", "-b:a", &bitrate],
+        };
 
         let mut duration = self.recording_time;
 
+        let recording_s = self.recording_time.as_secs().to_string();
+        let output_args = ["-t", &recording_s, "-ac", "1", "-af", "afftdn=nr=12:nf=-50:tn=1"];
+        // With live subscribers, also write raw 48 kHz samples to stdout as a second output
+        let publish = self.samples.receiver_count() > 0;
+
         // spawn ffmpeg and wait asynchronously
-        let mut child = Command::new("ffmpeg")
-            .args([
-                "-f", "alsa",
-                "-ac", "1",
-                "-i", &self.device_id,
-                "-t", &self.recording_time.as_secs().to_string(),
-                "-ac", "1",
-                "-af", "afftdn=nr=12:nf=-50:tn=1",
-                "-acodec", "libmp3lame",
-                "-b:a", "128k",
-                "-y",
-                &filepath,
-            ])
-            .spawn()?;
-
-        let status = child.wait().await?;
-           
-        // The program is stopped with CTRL-C - this is automatically passed down to the child process
-        // Could use nix crate to independently terminate the recording, but for now don't double up.       
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-f", "alsa", "-ac", "1", "-i", device_id])
+            .args(output_args)
+            .args(codec_args)
+            .args(["-y", &filepath]);
+        if publish {
+            command
+                .args(output_args)
+                .args(["-ar", "48000", "-f", "s16le", "pipe:1"])
+                .stdout(std::process::Stdio::piped());
+        }
+        // Never leave ffmpeg running if this future is dropped
+        command.kill_on_drop(true);
+        let mut child = command.spawn()?;
+
+        let publisher = child.stdout.take()
+            .map(|stdout| tokio::spawn(publish_pcm(stdout, timestamp, self.samples.clone())));
+        let (status, cancelled) = tokio::select! {
+            status = child.wait() => (status?, false),
+            _ = self.cancel.cancelled() => (stop_ffmpeg(&mut child).await?, true),
+        };
+        if let Some(publisher) = publisher {
+            let _ = publisher.await;
+        }
+
         // status.signal() only returns Some(sig) when the kernel terminated the process directly.
-        // FFmpeg catches the SIGINT/SIGTERM, finishes writing the file, then calls exit(255)
-        let early_exit = status.signal() == Some(Signal::SIGINT as i32) 
-            || status.signal() == Some(Signal::SIGTERM as i32) 
+        // FFmpeg catches the SIGINT/SIGTERM (sent by stop_ffmpeg, or by the terminal on CTRL-C),
+        // finishes writing the file, then calls exit(255)
+        let early_exit = cancelled
+            || status.signal() == Some(Signal::SIGINT as i32)
+            || status.signal() == Some(Signal::SIGTERM as i32)
             || status.code() == Some(255);
 
         if !status.success() && !early_exit {
@@ -374,20 +1554,868 @@ impl AudioRecorder {
             start_time_s: timestamp,
         })
     }
+
+    /// Records from an ALSA device in-process on a blocking thread, writing a mono 16-bit WAV.
+    #[cfg(feature = "native-audio")]
+    async fn native_recording(&self, device_id: &str, timestamp: u64) -> Result<AudioRecording, Box<dyn Error + Send + Sync>> {
+        let filepath = format!("{}audio_{}.wav", &self.audio_directory, timestamp);
+        let (device_id, path) = (device_id.to_string(), filepath.clone());
+        let (recording_time, cancel, samples) = (self.recording_time, self.cancel.clone(), self.samples.clone());
+
+        let duration = tokio::task::spawn_blocking(move || {
+            native_capture(&device_id, &path, timestamp, recording_time, &cancel, &samples)
+        }).await??;
+        if duration < self.recording_time {
+            info!("Received cancel signal, final audio segment is {:?} s", duration);
+        }
+
+        Ok(AudioRecording {
+            path: filepath,
+            duration,
+            start_time_s: timestamp,
+        })
+    }
+
+    /// Records synthesized audio on a blocking thread, writing a mono 16-bit WAV.
+    #[cfg(feature = "simulation")]
+    async fn simulated_recording(&self, timestamp: u64) -> Result<AudioRecording, Box<dyn Error + Send + Sync>> {
+        let filepath = format!("{}audio_{}.wav", &self.audio_directory, timestamp);
+        let path = filepath.clone();
+        let (recording_time, cancel, samples) = (self.recording_time, self.cancel.clone(), self.samples.clone());
+
+        let duration = tokio::task::spawn_blocking(move || {
+            crate::simulation::record_audio(&path, timestamp, recording_time, &cancel, &samples)
+        }).await??;
+
+        Ok(AudioRecording {
+            path: filepath,
+            duration,
+            start_time_s: timestamp,
+        })
+    }
+
+    /// "Records" by waiting out the recording time, or until cancelled, then writing that long of
+    /// the fixture's audio as a mono 16-bit WAV.
+    ///
+    /// The fixture is decoded and written on the runtime's thread: with tokio's clock paused, as in
+    /// tests, waiting for a blocking task would let the clock skip ahead.
+    #[cfg(any(test, feature = "audio-loopback"))]
+    async fn loopback_recording(&self, fixture: &Path, timestamp: u64) -> Result<AudioRecording, Box<dyn Error + Send + Sync>> {
+        let filepath = format!("{}audio_{}.wav", &self.audio_directory, timestamp);
+        let DecodedAudio { samples, sample_rate } = decode_audio(&fixture.to_string_lossy(), None)
+            .map_err(|e| format!("Failed to decode audio fixture {:?}: {}", fixture, e))?;
+        if samples.is_empty() {
+            return Err(format!("Audio fixture {:?} has no audio", fixture).into());
+        }
+
+        let start = tokio::time::Instant::now();
+        let cancelled = tokio::select! {
+            _ = tokio::time::sleep(self.recording_time) => false,
+            _ = self.cancel.cancelled() => true,
+        };
+        let duration = start.elapsed().min(self.recording_time);
+        if cancelled {
+            info!("Received cancel signal, final audio segment is {:?} s", duration);
+        }
+
+        let length = (sample_rate as f64 * duration.as_secs_f64()) as usize;
+        let recorded: Vec<i16> = samples.iter().cycle().take(length).copied().collect();
+        write_wav(&filepath, sample_rate, 1, &recorded)?;
+
+        Ok(AudioRecording {
+            path: filepath,
+            duration,
+            start_time_s: timestamp,
+        })
+    }
+}
+
+/// Interrupts `ffmpeg` so that it finalizes its output file, killing it if it has not exited
+/// within [`FFMPEG_STOP_TIMEOUT`]. Returns the exit status of the reaped process.
+async fn stop_ffmpeg(child: &mut tokio::process::Child) -> std::io::Result<std::process::ExitStatus> {
+    if let Some(pid) = child.id() {
+        let pid = nix::unistd::Pid::from_raw(pid as i32);
+        if let Err(e) = nix::sys::signal::kill(pid, Signal::SIGINT) {
+            warn!("Failed to interrupt ffmpeg: {e}");
+        }
+    }
+    match tokio::time::timeout(FFMPEG_STOP_TIMEOUT, child.wait()).await {
+        Ok(status) => status,
+        Err(_) => {
+            warn!("ffmpeg did not exit within {:?} of being interrupted; killing it", FFMPEG_STOP_TIMEOUT);
+            child.kill().await?;
+            child.wait().await
+        }
+    }
+}
+
+/// Reads raw little-endian 16-bit samples from `ffmpeg`'s stdout and publishes them in ~100 ms
+/// chunks of the recording started at `recording_start_s`.
+async fn publish_pcm(mut stdout: tokio::process::ChildStdout, recording_start_s: u64, samples: broadcast::Sender<AudioChunk>) {
+    use tokio::io::AsyncReadExt;
+
+    let mut buffer = vec![0u8; 9600];
+    let mut filled = 0;
+    loop {
+        match stdout.read(&mut buffer[filled..]).await {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) => {
+                warn!("Failed to read samples from ffmpeg: {e}");
+                break;
+            }
+        }
+        let whole = filled - filled % 2;
+        let chunk: Vec<i16> = buffer[..whole]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        buffer.copy_within(whole..filled, 0);
+        filled -= whole;
+        // No subscribers is not an error
+        let _ = samples.send(AudioChunk { recording_start_s, sample_rate: 48_000, samples: chunk.into() });
+    }
+}
+
+/// Captures mono 16-bit audio from `device_id` into a WAV file at `path` for `recording_time`, or
+/// until `cancel` is cancelled, publishing the samples as chunks of the recording started at
+/// `recording_start_s`. Returns the captured duration.
+#[cfg(feature = "native-audio")]
+fn native_capture(
+    device_id: &str,
+    path: &str,
+    recording_start_s: u64,
+    recording_time: Duration,
+    cancel: &CancellationToken,
+    samples: &broadcast::Sender<AudioChunk>,
+) -> Result<Duration, Box<dyn Error + Send + Sync>> {
+    use alsa::pcm::{Access, Format, HwParams, PCM};
+    use alsa::{Direction, ValueOr};
+    use crate::audio_analysis::wav_spec;
+
+    let pcm = PCM::new(device_id, Direction::Capture, false)
+        .map_err(|e| format!("Failed to open audio device {}: {}", device_id, e))?;
+    {
+        let hwp = HwParams::any(&pcm)?;
+        hwp.set_channels(1)?;
+        hwp.set_rate(NATIVE_SAMPLE_RATE, ValueOr::Nearest)?;
+        hwp.set_format(Format::s16())?;
+        hwp.set_access(Access::RWInterleaved)?;
+        pcm.hw_params(&hwp)?;
+    }
+    let rate = pcm.hw_params_current()?.get_rate()?;
+    let io = pcm.io_i16()?;
+
+    let mut writer = hound::WavWriter::create(path, wav_spec(rate, 1))?;
+
+    let total = (recording_time.as_secs_f64() * rate as f64) as usize;
+    let mut buffer = vec![0i16; rate as usize / 10];
+    let mut captured = 0;
+    while captured < total && !cancel.is_cancelled() {
+        let wanted = buffer.len().min(total - captured);
+        let n = match io.readi(&mut buffer[..wanted]) {
+            Ok(n) => n,
+            Err(e) => {
+                // Recover from overruns (e.g. when the system was briefly too busy to read)
+                warn!("Audio capture error, recovering: {e}");
+                pcm.try_recover(e, true)?;
+                continue;
+            }
+        };
+        for sample in &buffer[..n] {
+            writer.write_sample(*sample)?;
+        }
+        // No subscribers is not an error
+        let _ = samples.send(AudioChunk { recording_start_s, sample_rate: rate, samples: buffer[..n].into() });
+        captured += n;
+    }
+    pcm.drop()?;
+    writer.finalize()?;
+
+    Ok(Duration::from_secs_f64(captured as f64 / rate as f64))
+}
+
+/// A sensor polled by `SensorReader` on every measurement cycle.
+///
+/// Implement this trait to add new hardware without changing `SensorReader`: each sensor adds its
+/// readings to the `SleepDataBuilder` for the current sample, and declares the fields it fills in
+/// so that the storage creates their datasets.
+pub trait Sensor: Send {
+    /// Short, human-readable name of the sensor, used in logs.
+    fn name(&self) -> &str;
+
+    /// The per-sample fields this sensor fills in, by dataset (or column) name, registered with
+    /// the storage at startup (see [`SensorReader::fields`]). Named cameras, probes, and
+    /// thermistors are registered by name instead, and have no fields.
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        &[]
+    }
+
+    /// Takes a measurement and adds it to `builder`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no measurement could be taken; the builder is left unchanged for this
+    /// sensor and the sample is still recorded with the remaining sensors' data.
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Readiness of the sensor; only `Ready` sensors are measured. Sensors that are opened before
+    /// they are added are always ready.
+    fn state(&self) -> SensorState {
+        SensorState::Ready
+    }
+
+    /// Advances the initialization of a sensor that is not ready, e.g. by trying to open its
+    /// device. Called instead of `measure` on every poll until the sensor is ready.
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason the sensor failed to initialize once it is [`SensorState::Failed`].
+    fn initialize(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+/// Fields of the BME280, and the climate metrics derived from its readings (see
+/// [`climate`](crate::climate)).
+pub(crate) const BME280_FIELDS: &[(&str, SleepField)] = &[
+    ("temperature", SleepField::F32(|d| d.temperature_c)),
+    ("pressure", SleepField::F32(|d| d.pressure)),
+    ("humidity", SleepField::F32(|d| d.humidity)),
+    ("dew_point", SleepField::F32(|d| d.dew_point_c)),
+    ("absolute_humidity", SleepField::F32(|d| d.absolute_humidity_gm3)),
+    ("comfort_index", SleepField::F32(|d| d.comfort_index)),
+];
+
+/// Fields of the ENS160.
+pub(crate) const ENS160_FIELDS: &[(&str, SleepField)] = &[
+    ("co2eq_ppm", SleepField::U16(|d| d.co2eq_ppm)),
+    ("tvoc_ppb", SleepField::U16(|d| d.tvoc_ppb)),
+    ("air_quality_index", SleepField::U16(|d| d.air_quality_index)),
+];
+
+/// Fields of the primary thermistor.
+pub(crate) const THERMISTOR_FIELDS: &[(&str, SleepField)] = &[
+    ("thermistor_temp", SleepField::F32(|d| d.thermistor_temp_c)),
+];
+
+/// Fields of the primary camera.
+pub(crate) const CAMERA_FIELDS: &[(&str, SleepField)] = &[
+    ("image_path", SleepField::String(|d| VarLenUnicode::from_str(&d.image_path).unwrap_or_default())),
+    ("image_motion_live", SleepField::F32(|d| d.image_motion_live)),
+    ("clip_path", SleepField::String(|d| VarLenUnicode::from_str(&d.clip_path).unwrap_or_default())),
+    ("raw_image_path", SleepField::String(|d| VarLenUnicode::from_str(&d.raw_image_path).unwrap_or_default())),
+    ("thumbnail", SleepField::Bytes(|d| VarLenArray::from_slice(&d.thumbnail.0))),
+];
+
+/// Fields of the SCD4x.
+pub(crate) const SCD4X_FIELDS: &[(&str, SleepField)] = &[
+    ("co2_ppm", SleepField::U16(|d| d.co2_ppm)),
+];
+
+/// Fields of the BH1750.
+pub(crate) const BH1750_FIELDS: &[(&str, SleepField)] = &[
+    ("light_lux", SleepField::F32(|d| d.light_lux)),
+];
+
+/// Fields of the HX711.
+pub(crate) const HX711_FIELDS: &[(&str, SleepField)] = &[
+    ("bed_weight_kg", SleepField::F32(|d| d.bed_weight_kg)),
+    ("bed_occupied", SleepField::Bool(|d| d.bed_occupied)),
+];
+
+/// Fields of the piezo BCG. Its bursts are stored in `piezo_bcg_mv` and `piezo_bcg_start`, which
+/// every session has.
+pub(crate) const PIEZO_FIELDS: &[(&str, SleepField)] = &[
+    ("piezo_heart_rate_bpm", SleepField::F32(|d| d.piezo_heart_rate_bpm)),
+    ("piezo_resp_rate_bpm", SleepField::F32(|d| d.piezo_resp_rate_bpm)),
+];
+
+/// Fields of the PIR.
+pub(crate) const PIR_FIELDS: &[(&str, SleepField)] = &[
+    ("pir_motion", SleepField::Bool(|d| d.pir_motion)),
+];
+
+/// Fields of the PMS5003.
+pub(crate) const PMS5003_FIELDS: &[(&str, SleepField)] = &[
+    ("pm1_0_ugm3", SleepField::U16(|d| d.pm1_0_ugm3)),
+    ("pm2_5_ugm3", SleepField::U16(|d| d.pm2_5_ugm3)),
+    ("pm10_ugm3", SleepField::U16(|d| d.pm10_ugm3)),
+];
+
+/// Fields of the SGP40.
+pub(crate) const SGP40_FIELDS: &[(&str, SleepField)] = &[
+    ("voc_index", SleepField::U16(|d| d.voc_index)),
+];
+
+/// Fields of the host's system stats.
+pub(crate) const SYSTEM_STATS_FIELDS: &[(&str, SleepField)] = &[
+    ("cpu_temp", SleepField::F32(|d| d.cpu_temp_c)),
+    ("load_avg_1m", SleepField::F32(|d| d.load_avg_1m)),
+    ("disk_free_mb", SleepField::U64(|d| d.disk_free_mb)),
+    ("mem_used_percent", SleepField::F32(|d| d.mem_used_percent)),
+];
+
+/// Fields of the C1001 mmWave radar.
+pub(crate) const MMWAVE_FIELDS: &[(&str, SleepField)] = &[
+    ("mmwave_presence", SleepField::Bool(|d| d.mmwave_presence)),
+    ("mmwave_movement", SleepField::Bool(|d| d.mmwave_movement)),
+    ("mmwave_heart_rate_bpm", SleepField::U16(|d| d.mmwave_heart_rate_bpm)),
+    ("mmwave_resp_rate_bpm", SleepField::U16(|d| d.mmwave_resp_rate_bpm)),
+];
+
+/// The fields of all built-in sensors, which sessions recorded with any of them may have. Used to
+/// read sessions back and for the SQLite columns.
+pub(crate) fn builtin_fields() -> Vec<(&'static str, SleepField)> {
+    [
+        BME280_FIELDS, ENS160_FIELDS, THERMISTOR_FIELDS, CAMERA_FIELDS, SCD4X_FIELDS, BH1750_FIELDS,
+        HX711_FIELDS, PIEZO_FIELDS, PIR_FIELDS, PMS5003_FIELDS, SGP40_FIELDS, SYSTEM_STATS_FIELDS,
+        MMWAVE_FIELDS,
+    ].concat()
+}
+
+impl Sensor for BME280Wrapper {
+    fn name(&self) -> &str {
+        "BME280"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        BME280_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let measurements = BME280Wrapper::measure(self).ok_or("no BME280 measurement")?;
+        *builder = std::mem::take(builder).with_bme280(measurements);
+        Ok(())
+    }
+}
+
+impl Sensor for ENS160Wrapper {
+    fn name(&self) -> &str {
+        "ENS160"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        ENS160_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let measurements = ENS160Wrapper::measure(self).ok_or("no ENS160 measurement")?;
+        let co2eq_ppm = self.calibration.co2eq_ppm(measurements.co2eq_ppm.value);
+        *builder = std::mem::take(builder).with_air_quality(co2eq_ppm, measurements.tvoc_ppb, measurements.air_quality_index as u16);
+        Ok(())
+    }
+}
+
+impl Sensor for ThermistorWrapper {
+    // `name` is the dataset suffix; the sensor is logged by its label.
+    #[allow(clippy::misnamed_getters)]
+    fn name(&self) -> &str {
+        &self.label
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        match self.name.as_str() {
+            "" => THERMISTOR_FIELDS,
+            _ => &[],
+        }
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let temperature = ThermistorWrapper::measure(self).ok_or("no thermistor measurement")?;
+        *builder = match self.name.as_str() {
+            "" => std::mem::take(builder).with_thermistor_temp(temperature),
+            name => std::mem::take(builder).with_named_thermistor_temp(name, temperature),
+        };
+        Ok(())
+    }
+}
+
+impl Sensor for CameraWrapper {
+    // The sensor name is the label; `name` only suffixes the datasets.
+    #[allow(clippy::misnamed_getters)]
+    fn name(&self) -> &str {
+        &self.label
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        match self.name.as_str() {
+            "" => CAMERA_FIELDS,
+            _ => &[],
+        }
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let lux = self.light_level.as_ref().and_then(|l| l.lock().ok().and_then(|lux| *lux));
+        if let Some(lux) = lux {
+            if let Err(e) = self.apply_light_level(lux) {
+                warn!("{}: failed to switch night mode: {}", self.label, e);
+            }
+        }
+        let result = CameraWrapper::measure(self, builder.timestamp()).map_err(|e| e.to_string())?;
+        *builder = match self.name.as_str() {
+            "" => std::mem::take(builder).with_camera_result(result),
+            name => std::mem::take(builder).with_named_camera_result(name, result),
+        };
+        Ok(())
+    }
+}
+
+impl Sensor for Ds18b20Wrapper {
+    // Logged by label, while `name` keys the probe's dataset.
+    #[allow(clippy::misnamed_getters)]
+    fn name(&self) -> &str {
+        &self.label
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let temperature = Ds18b20Wrapper::measure(self).map_err(|e| e.to_string())?;
+        *builder = std::mem::take(builder).with_probe_temp(&self.name, temperature);
+        Ok(())
+    }
+}
+
+impl Sensor for SCD4xWrapper {
+    fn name(&self) -> &str {
+        "SCD4x"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        SCD4X_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let measurement = SCD4xWrapper::measure(self)
+            .map_err(|e| e.to_string())?
+            .ok_or("no new SCD4x measurement")?;
+        *builder = std::mem::take(builder).with_scd4x(measurement);
+        Ok(())
+    }
+}
+
+impl Sensor for BH1750Wrapper {
+    fn name(&self) -> &str {
+        "BH1750"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        BH1750_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let lux = BH1750Wrapper::measure(self).map_err(|e| e.to_string())?;
+        if let Some(Ok(mut light_level)) = self.light_level.as_ref().map(|l| l.lock()) {
+            *light_level = Some(lux);
+        }
+        *builder = std::mem::take(builder).with_light_lux(lux);
+        Ok(())
+    }
+}
+
+impl Sensor for HX711Wrapper {
+    fn name(&self) -> &str {
+        "HX711"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        HX711_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let weight_kg = HX711Wrapper::measure(self).map_err(|e| e.to_string())?;
+        *builder = std::mem::take(builder).with_bed_weight(weight_kg, weight_kg > self.occupied_kg);
+        Ok(())
+    }
+}
+
+impl Sensor for PiezoWrapper {
+    fn name(&self) -> &str {
+        "Piezo BCG"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        PIEZO_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (samples_mv, estimate) = PiezoWrapper::measure(self).map_err(|e| e.to_string())?;
+        *builder = std::mem::take(builder).with_piezo_burst(samples_mv, estimate);
+        Ok(())
+    }
+}
+
+impl Sensor for PirWrapper {
+    fn name(&self) -> &str {
+        "PIR"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        PIR_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        *builder = std::mem::take(builder).with_pir_motion(PirWrapper::measure(self));
+        Ok(())
+    }
+}
+
+impl Sensor for PMS5003Wrapper {
+    fn name(&self) -> &str {
+        "PMS5003"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        PMS5003_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let measurement = PMS5003Wrapper::measure(self).map_err(|e| e.to_string())?;
+        *builder = std::mem::take(builder).with_pms5003(measurement);
+        Ok(())
+    }
+}
+
+impl Sensor for SGP40Wrapper {
+    fn name(&self) -> &str {
+        "SGP40"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        SGP40_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let voc_index = SGP40Wrapper::measure(self).map_err(|e| e.to_string())?;
+        *builder = std::mem::take(builder).with_voc_index(voc_index);
+        Ok(())
+    }
 }
 
-/// Represents a collection of sensor wrappers for sleep data measurement. Only supports simultaneous polling of sensors.
+impl Sensor for SystemStatsWrapper {
+    fn name(&self) -> &str {
+        "System stats"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        SYSTEM_STATS_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        *builder = std::mem::take(builder).with_system_stats(SystemStatsWrapper::measure(self));
+        Ok(())
+    }
+}
+
+impl Sensor for C1001 {
+    fn name(&self) -> &str {
+        "C1001 mmWave"
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        MMWAVE_FIELDS
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        *builder = std::mem::take(builder).with_mmwave_result(self.poll_sleep_data());
+        Ok(())
+    }
+}
+
+/// Represents a collection of sensors for sleep data measurement. Only supports simultaneous polling of sensors.
+///
+/// The default hardware set is created by `SensorReader::new`:
+/// - BME280: Used for collecting environmental measurements such as temperature and humidity.
+/// - ENS160: Initialized using temperature and humidity from BME280 for gas measurements (CO2 and TVOC)
+/// - Thermistor: Utilized for ADC-based temperature measurements.
+/// - Camera: Configured with a directory path derived from the provided data_path to store images.
+///   Additional cameras from `Config::extra_cameras` store images in a subdirectory named after the camera.
+/// - C1001 mWave: radar sensor for presence, motion, heart rate, and respiration measurement
+/// - System stats: CPU temperature, load, free disk space, and memory usage of the host
+/// - DS18B20 probes listed in `Config::ds18b20`, e.g. under-mattress temperature
+/// - Additional thermistors listed in `Config::extra_thermistors`, e.g. bed surface or room
+/// - SCD4x, if enabled in `Config::scd4x`: true CO2, alongside the ENS160's eCO2 estimate
+/// - BH1750, if enabled in `Config::bh1750`: ambient light, shared with the cameras so that they can
+///   switch night mode from it (`NightModeConfig::auto_lux`)
+/// - PMS5003, if a serial port is set in `Config::pms5003`: PM1.0/PM2.5/PM10 particulate matter
+/// - HX711, if configured in `Config::hx711`: bed weight and occupancy from load cells
+/// - Piezo BCG, if configured in `Config::piezo`: a burst from a piezo film on the MCP3424, with heart
+///   and respiration rate estimates
+/// - PIR, if a GPIO line is set in `Config::pir`: motion since the previous poll
+/// - SGP40, if enabled in `Config::sgp40`: VOC index, compensated with the BME280's temperature and humidity
+///
+/// Any of the first six can be left out with `Config::disabled_sensors`, e.g. while a camera is
+/// unplugged; their datasets are then not created (see [`SensorReader::fields`]). Except for the BME280, the sensors are
+/// opened on their polling threads once polling starts, and their datasets stay empty while they
+/// are not [`SensorState::Ready`]. Without the BME280, the ENS160 and SGP40 are calibrated for
+/// 25 °C and 50 % RH.
+///
+/// Other sets of sensors can be used with `SensorReader::with_sensors` and `SensorReader::add_sensor`.
+///
+/// Each sensor runs on its own thread. On every measurement, all sensors measure at the same time
+/// and post their readings back through a channel, so a slow camera capture does not delay the
+/// other sensors. A measurement blocked on faulty hardware is abandoned after a timeout
+/// (`SensorReader::with_timeout`), and the sensor is skipped until it returns.
 pub struct SensorReader {
-    /// - BME280: Used for collecting environmental measurements such as temperature and humidity.
-    bme280: BME280Wrapper,
-    /// - ENS160: Initialized using temperature and humidity from BME280 for gas measurements (CO2 and TVOC)
-    ens160: ENS160Wrapper,
-    /// - Thermistor: Utilized for ADC-based temperature measurements.
-    thermistor: ThermistorWrapper,
-    /// - C1001 mWave: radar sensor for presence, motion, heart rate, and respiration measurement
-    mm_wave: C1001,
-    /// - Camera: Configured with a directory path derived from the provided data_path to store images.=
-    camera: CameraWrapper,
+    /// Sensors polled on every measurement, in order.
+    sensors: Vec<SensorWorker>,
+    /// Longest a single measurement may take.
+    timeout: Duration,
+}
+
+/// Default timeout of a single sensor measurement.
+pub const DEFAULT_SENSOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Gas sensor calibration temperature (°C) when the BME280 is disabled.
+const DEFAULT_CALIBRATION_TEMPERATURE: f32 = 25.0;
+/// Gas sensor calibration relative humidity (%) when the BME280 is disabled.
+const DEFAULT_CALIBRATION_HUMIDITY: f32 = 50.0;
+
+/// Runs `init` until it succeeds, waiting with exponential backoff between attempts. Gives up
+/// once the next attempt would start after `retry.budget()`, and returns `None` so that the
+/// recorder starts without the sensor. Used for sensors that must be open before the others.
+fn init_with_retry<T>(label: &str, retry: &SensorInitConfig, mut init: impl FnMut() -> Result<T, Box<dyn Error>>) -> Option<T> {
+    let start = Instant::now();
+    let mut backoff = retry.initial_backoff();
+    let mut attempt = 1;
+    loop {
+        match init() {
+            Ok(sensor) => return Some(sensor),
+            Err(e) if start.elapsed() + backoff > retry.budget() => {
+                error!("{label} failed to initialize after {attempt} attempts, continuing without it: {e}");
+                return None;
+            }
+            Err(e) => {
+                warn!("{label} failed to initialize (attempt {attempt}), retrying in {backoff:?}: {e}");
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(retry.max_backoff());
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Opens a sensor's device.
+type OpenSensor = Box<dyn FnMut() -> Result<Box<dyn Sensor>, Box<dyn Error>> + Send>;
+
+/// A sensor opened on its polling thread while the session runs, so that a missing, slow, or
+/// warming-up device neither delays the start nor is lost for the night.
+///
+/// It starts [`SensorState::Initializing`], retrying with backoff as configured in
+/// [`SensorInitConfig`]; is `Ready` once open; and `Failed` once the budget is spent, after which
+/// it is still retried every `retry_failed`. After `reinit_after_failures` failed measurements in
+/// a row it is dropped and reopened, which re-attaches replugged cameras and sensors that lost
+/// power.
+struct LazySensor {
+    /// Name reported through the `Sensor` trait.
+    label: String,
+    /// Fields of the sensor, known before it is open.
+    fields: &'static [(&'static str, SleepField)],
+    /// Opens the device.
+    open: OpenSensor,
+    /// Backoff and budget of the initialization.
+    retry: SensorInitConfig,
+    /// Wait between attempts while failed; `None` gives up on the sensor.
+    retry_failed: Option<Duration>,
+    /// The sensor, while open.
+    sensor: Option<Box<dyn Sensor>>,
+    /// Current readiness.
+    state: SensorState,
+    /// Start of the current initialization.
+    init_started: Instant,
+    /// Wait after the next failed attempt.
+    backoff: Duration,
+    /// Earliest time of the next attempt.
+    next_attempt: Instant,
+    /// Error of the last failed attempt.
+    last_error: Option<String>,
+    /// Number of consecutive failed measurements.
+    failures: u32,
+}
+
+impl LazySensor {
+    fn new(
+        label: &str,
+        fields: &'static [(&'static str, SleepField)],
+        retry: &SensorInitConfig,
+        retry_failed: Option<Duration>,
+        open: OpenSensor,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            label: label.to_string(),
+            fields,
+            open,
+            retry: retry.clone(),
+            retry_failed,
+            sensor: None,
+            state: SensorState::Initializing,
+            init_started: now,
+            backoff: retry.initial_backoff(),
+            next_attempt: now,
+            last_error: None,
+            failures: 0,
+        }
+    }
+
+    /// Drops the sensor and starts initializing it again.
+    fn reinitialize(&mut self) {
+        let now = Instant::now();
+        self.sensor = None;
+        self.state = SensorState::Initializing;
+        self.init_started = now;
+        self.backoff = self.retry.initial_backoff();
+        self.next_attempt = now;
+        self.failures = 0;
+    }
+
+    /// Outcome of a poll in which the sensor was not opened: the last error once it has failed.
+    fn not_ready(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match (self.state, &self.last_error) {
+            (SensorState::Failed, Some(e)) => Err(e.clone().into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Sensor for LazySensor {
+    fn name(&self) -> &str {
+        &self.label
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        self.fields
+    }
+
+    fn state(&self) -> SensorState {
+        self.state
+    }
+
+    fn initialize(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let now = Instant::now();
+        let given_up = self.state == SensorState::Failed && self.retry_failed.is_none();
+        if given_up || now < self.next_attempt {
+            return self.not_ready();
+        }
+        match (self.open)() {
+            Ok(sensor) => {
+                if self.state == SensorState::Failed {
+                    info!("{} recovered.", self.label);
+                }
+                self.sensor = Some(sensor);
+                self.state = SensorState::Ready;
+                self.last_error = None;
+            }
+            Err(e) => {
+                match self.state {
+                    SensorState::Initializing if now.duration_since(self.init_started) + self.backoff > self.retry.budget() => {
+                        error!("{} failed to initialize, continuing without it: {e}", self.label);
+                        self.state = SensorState::Failed;
+                        self.next_attempt = now + self.retry_failed.unwrap_or_default();
+                    }
+                    SensorState::Initializing => {
+                        warn!("{} failed to initialize, retrying in {:?}: {e}", self.label, self.backoff);
+                        self.next_attempt = now + self.backoff;
+                        self.backoff = (self.backoff * 2).min(self.retry.max_backoff());
+                    }
+                    _ => {
+                        debug!("{} still failing to initialize: {e}", self.label);
+                        self.next_attempt = now + self.retry_failed.unwrap_or_default();
+                    }
+                }
+                self.last_error = Some(e.to_string());
+            }
+        }
+        self.not_ready()
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let sensor = self.sensor.as_mut().ok_or("not initialized")?;
+        let result = sensor.measure(builder);
+        if result.is_err() {
+            self.failures += 1;
+            let limit = self.retry.reinit_after_failures;
+            if limit > 0 && self.failures >= limit {
+                warn!("{}: {} measurements failed in a row, reopening it", self.label, self.failures);
+                self.reinitialize();
+            }
+        } else {
+            self.failures = 0;
+        }
+        result
+    }
+}
+
+/// A sensor running on its own thread, measuring into the builders it is sent.
+struct SensorWorker {
+    /// Name of the sensor.
+    name: String,
+    /// Fields of the sensor.
+    fields: &'static [(&'static str, SleepField)],
+    /// Builders to add a measurement to.
+    requests: std::sync::mpsc::Sender<SleepDataBuilder>,
+    /// Builders with the measurement added, or the error, and the sensor's state afterwards.
+    results: std::sync::mpsc::Receiver<(SleepDataBuilder, Result<(), String>, SensorState)>,
+    /// State of the sensor after its last measurement.
+    state: SensorState,
+    /// Whether a timed out measurement has not returned yet.
+    busy: bool,
+    /// Time of the last failed measurement, in seconds since UNIX epoch.
+    last_error: Option<u64>,
+}
+
+impl SensorWorker {
+    fn spawn(mut sensor: Box<dyn Sensor>) -> Self {
+        let (name, fields, state) = (sensor.name().to_string(), sensor.fields(), sensor.state());
+        let (requests, pending) = std::sync::mpsc::channel::<SleepDataBuilder>();
+        let (done, results) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for mut builder in pending {
+                let result = match sensor.state() {
+                    SensorState::Ready => sensor.measure(&mut builder),
+                    // A sensor that has just become ready is measured right away
+                    _ => sensor.initialize().and_then(|()| match sensor.state() {
+                        SensorState::Ready => sensor.measure(&mut builder),
+                        _ => Ok(()),
+                    }),
+                };
+                if done.send((builder, result.map_err(|e| e.to_string()), sensor.state())).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { name, fields, requests, results, state, busy: false, last_error: None }
+    }
+
+    /// Starts a measurement of the sample at `timestamp`.
+    fn start(&mut self, timestamp: u64) -> Result<(), String> {
+        if self.busy {
+            match self.results.try_recv() {
+                // Discard the late result, which belongs to an earlier sample
+                Ok(_) => self.busy = false,
+                Err(TryRecvError::Empty) => return Err("still blocked in a timed out measurement".to_string()),
+                Err(TryRecvError::Disconnected) => return Err(Self::STOPPED.to_string()),
+            }
+        }
+        self.requests.send(SleepData::builder(timestamp)).map_err(|_| Self::STOPPED.to_string())?;
+        Ok(())
+    }
+
+    /// Waits until `deadline` for the reading of the started measurement.
+    fn finish(&mut self, deadline: Instant, timeout: Duration) -> Result<SleepDataBuilder, String> {
+        match self.results.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok((reading, result, state)) => {
+                self.state = state;
+                result.map(|()| reading)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                self.busy = true;
+                Err(format!("timed out after {:?}", timeout))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(Self::STOPPED.to_string()),
+        }
+    }
+
+    const STOPPED: &str = "sensor thread stopped";
 }
 
 impl SensorReader {
@@ -398,12 +2426,16 @@ impl SensorReader {
     /// # Arguments
     ///
     /// * `data_path` - A string slice representing the base directory where camera images will be stored.
-    ///                This path is concatenated with "/images/" for the actual camera data storage.
+    ///   This path is concatenated with "/images/" for the actual camera data storage.
     ///
     /// # Errors
     ///
-    /// Returns an error if the initialization of any sensor (BME280, ENS160, Thermistor, or Camera) fails,
-    /// or if a measurement cannot be successfully obtained during the setup process.
+    /// Returns an error if the calibration file (`Config::calibration_path`) cannot be read, or if
+    /// `Config::simulation` is set but the crate was built without the `simulation` feature. Sensors
+    /// that fail to initialize are retried with backoff as configured in `Config::sensor_init` and
+    /// reported as initializing or failed in their [`SensorStatus`]. The BME280 (which must also
+    /// take a first measurement) is retried before the reader is returned, and left out if it
+    /// still fails.
     ///
     /// # Examples
     ///
@@ -412,39 +2444,231 @@ impl SensorReader {
     /// let mut sensor_reader = SensorReader::new("/path/to/data", "2025-04-28_22-47-31")
     ///     .expect("Failed to initialize sensor reader");
     /// ```    
-    #[tracing::instrument]
     pub fn new(data_path: &str, group_name: &str) -> Result<Self, Box<dyn Error>> {
-        let mut bme280 = BME280Wrapper::new()?;
-        info!("BME280 initialized successfully.");
+        Self::from_config(&crate::config::Config::new(data_path), group_name)
+    }
 
-        let bme280_measurements = bme280.measure().ok_or("Failed to read BME280 measurements.")?;
-        let ens160 = ENS160Wrapper::new(bme280_measurements.temperature, bme280_measurements.humidity)?;
-        info!("ENS160 initialized successfully with cal temp of {}°C and {} RH.", bme280_measurements.temperature, bme280_measurements.humidity);
+    /// Creates a new instance of SensorReader with all sensors initialized from `config`.
+    ///
+    /// Images are stored under `config.data_path/group_name/images/`. The sensors are calibrated
+    /// from the calibration file (see [`Calibration`]), if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`SensorReader::new`].
+    #[tracing::instrument(skip(config))]
+    pub fn from_config(config: &crate::config::Config, group_name: &str) -> Result<Self, Box<dyn Error>> {
+        if config.simulation {
+            #[cfg(feature = "simulation")]
+            return crate::simulation::sensor_reader(config, group_name);
+            #[cfg(not(feature = "simulation"))]
+            return Err("Simulated sensors require the simulation feature".into());
+        }
+        let data_path = config.data_path.as_str();
+        let retry = &config.sensor_init;
+        let calibration = Calibration::load(config.calibration_path())?;
+        let mut reader = Self::with_sensors(Vec::new()).with_timeout(config.sensor_timeout());
+
+        // The BME280 is opened up front: its first reading calibrates the ENS160 and SGP40 gas
+        // sensors, which otherwise assume room conditions
+        let mut climate = (DEFAULT_CALIBRATION_TEMPERATURE, DEFAULT_CALIBRATION_HUMIDITY);
+        if config.is_enabled("bme280") {
+            let bme280 = init_with_retry("BME280", retry, || {
+                let mut bme280 = BME280Wrapper::from_config(config.i2c_bus_for("bme280"), &config.bme280)?
+                    .with_calibration(&calibration.bme280);
+                let measurements = bme280.measure().ok_or("Failed to read BME280 measurements.")?;
+                Ok((bme280, measurements))
+            });
+            if let Some((bme280, measurements)) = bme280 {
+                climate = (measurements.temperature, measurements.humidity);
+                reader.add_sensor(Box::new(bme280));
+                info!("BME280 initialized successfully.");
+            }
+        }
+        let (cal_temperature, cal_humidity) = climate;
+
+        if config.is_enabled("ens160") {
+            let (bus, ens160_calibration) = (config.i2c_bus_for("ens160").to_string(), calibration.ens160.clone());
+            reader.add_lazy_sensor("ENS160", ENS160_FIELDS, retry, move || {
+                let ens160 = ENS160Wrapper::on_bus(&bus, cal_temperature, cal_humidity)?.with_calibration(&ens160_calibration);
+                info!("ENS160 initialized successfully with cal temp of {}°C and {} RH.", cal_temperature, cal_humidity);
+                Ok(Box::new(ens160))
+            });
+        }
 
-        let thermistor = ThermistorWrapper::new()?;
-        info!("Thermistor ADC initialized successfully.");
+        if config.is_enabled("thermistor") {
+            let (bus, thermistor, thermistor_calibration) = (config.i2c_bus_for("thermistor").to_string(), config.thermistor.clone(), calibration.thermistor.clone());
+            reader.add_lazy_sensor("Thermistor", THERMISTOR_FIELDS, retry, move || {
+                let wrapper = ThermistorWrapper::from_config(&bus, &thermistor)?.with_calibration(&thermistor_calibration);
+                info!("Thermistor ADC initialized successfully.");
+                Ok(Box::new(wrapper))
+            });
+        }
 
-        let camera = CameraWrapper::new(&format!("{}/{}/images/", data_path, group_name))?;            
-        info!("Camera initialized successfully.");
+        // Shared by the BH1750 with the cameras, for switching night mode
+        let light_level = LightLevel::default();
+        if config.bh1750 {
+            let (bus, light_level) = (config.i2c_bus_for("bh1750").to_string(), light_level.clone());
+            reader.add_lazy_sensor("BH1750", BH1750_FIELDS, retry, move || {
+                let bh1750 = BH1750Wrapper::on_bus(&bus)?.with_light_level(light_level.clone());
+                info!("BH1750 initialized successfully.");
+                Ok(Box::new(bh1750))
+            });
+        }
 
-        let mut mm_wave = C1001::open("/dev/serial0", 115_200, Duration::from_millis(1000))?;
-        mm_wave.begin()?;
-        mm_wave.config_work_mode(dfrobot_c1001::Mode::Sleep)?;
-        mm_wave.set_led(Led::Sleep, false)?;
-        info!("mmWave sensor intialized successfully.");
-        
-        Ok(Self { bme280, ens160, thermistor, mm_wave, camera })
+        if config.is_enabled("camera") {
+            let image_directory = format!("{}/{}/images/", data_path, group_name);
+            reader.add_camera("Camera", retry, image_directory, &config.camera, &light_level);
+        }
+
+        if config.is_enabled("mmwave") {
+            reader.add_lazy_sensor("C1001 mmWave", MMWAVE_FIELDS, retry, || {
+                let mut mm_wave = C1001::open("/dev/serial0", 115_200, Duration::from_millis(1000))?;
+                mm_wave.begin()?;
+                mm_wave.config_work_mode(dfrobot_c1001::Mode::Sleep)?;
+                mm_wave.set_led(Led::Sleep, false)?;
+                info!("mmWave sensor intialized successfully.");
+                Ok(Box::new(mm_wave))
+            });
+        }
+
+        if config.is_enabled("system_stats") {
+            reader.add_sensor(Box::new(SystemStatsWrapper::new(data_path)));
+        }
+
+        for camera_config in &config.extra_cameras {
+            let image_directory = format!("{}/{}/images/{}/", data_path, group_name, camera_config.name);
+            reader.add_camera(&format!("Camera {}", camera_config.name), retry, image_directory, camera_config, &light_level);
+        }
+        if config.scd4x {
+            let bus = config.i2c_bus_for("scd4x").to_string();
+            reader.add_lazy_sensor("SCD4x", SCD4X_FIELDS, retry, move || {
+                let scd4x = SCD4xWrapper::on_bus(&bus)?;
+                info!("SCD4x initialized successfully.");
+                Ok(Box::new(scd4x))
+            });
+        }
+        if config.sgp40 {
+            let (bus, sampling_interval_s) = (config.i2c_bus_for("sgp40").to_string(), config.sensor_interval_s);
+            reader.add_lazy_sensor("SGP40", SGP40_FIELDS, retry, move || {
+                let sgp40 = SGP40Wrapper::on_bus(&bus, cal_temperature, cal_humidity, sampling_interval_s)?;
+                info!("SGP40 initialized successfully with cal temp of {}°C and {} RH.", cal_temperature, cal_humidity);
+                Ok(Box::new(sgp40))
+            });
+        }
+        if let Some(path) = config.pms5003.clone() {
+            reader.add_lazy_sensor("PMS5003", PMS5003_FIELDS, retry, move || {
+                let pms5003 = PMS5003Wrapper::new(&path)?;
+                info!("PMS5003 initialized successfully on {}.", path);
+                Ok(Box::new(pms5003))
+            });
+        }
+        if let Some(hx711) = config.hx711.clone() {
+            reader.add_lazy_sensor("HX711", HX711_FIELDS, retry, move || {
+                let wrapper = HX711Wrapper::from_config(&hx711)?;
+                info!("HX711 initialized successfully on {} lines {}/{}.", hx711.chip, hx711.dout_line, hx711.sck_line);
+                Ok(Box::new(wrapper))
+            });
+        }
+        if let Some(piezo) = config.piezo.clone() {
+            let (bus, piezo_calibration) = (config.i2c_bus_for("piezo").to_string(), calibration.piezo.clone());
+            reader.add_lazy_sensor("Piezo BCG", PIEZO_FIELDS, retry, move || {
+                let wrapper = PiezoWrapper::from_config(&bus, &piezo)?.with_calibration(&piezo_calibration);
+                info!("Piezo BCG initialized successfully on ADC {:#x} channel {}.", piezo.address, piezo.channel);
+                Ok(Box::new(wrapper))
+            });
+        }
+        if let Some(pir) = config.pir.clone() {
+            reader.add_lazy_sensor("PIR", PIR_FIELDS, retry, move || {
+                let wrapper = PirWrapper::new(&pir)?;
+                info!("PIR initialized successfully on {} line {}.", pir.chip, pir.line);
+                Ok(Box::new(wrapper))
+            });
+        }
+        for probe in config.ds18b20.iter().cloned() {
+            reader.add_lazy_sensor(&format!("DS18B20 {}", probe.name), &[], retry, move || {
+                let wrapper = Ds18b20Wrapper::new(&probe.id, &probe.name)?;
+                info!("DS18B20 probe {} ({}) initialized successfully.", probe.name, probe.id);
+                Ok(Box::new(wrapper))
+            });
+        }
+        for thermistor in config.extra_thermistors.iter().cloned() {
+            let (bus, thermistor_calibration) = (config.i2c_bus_for("thermistor").to_string(), calibration.thermistor(&thermistor.name));
+            reader.add_lazy_sensor(&format!("Thermistor {}", thermistor.name), &[], retry, move || {
+                let wrapper = ThermistorWrapper::from_config(&bus, &thermistor)?.with_calibration(&thermistor_calibration);
+                info!("Thermistor {} initialized successfully on ADC {:#x} channel {}.", thermistor.name, thermistor.address, thermistor.channel);
+                Ok(Box::new(wrapper))
+            });
+        }
+        Ok(reader)
+    }
+
+    /// Creates a SensorReader polling the given, already initialized, sensors in order.
+    pub fn with_sensors(sensors: Vec<Box<dyn Sensor>>) -> Self {
+        Self {
+            sensors: sensors.into_iter().map(SensorWorker::spawn).collect(),
+            timeout: DEFAULT_SENSOR_TIMEOUT,
+        }
+    }
+
+    /// Sets the timeout of a single sensor measurement (default [`DEFAULT_SENSOR_TIMEOUT`]).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds a sensor, polled after the existing ones.
+    pub fn add_sensor(&mut self, sensor: Box<dyn Sensor>) {
+        self.sensors.push(SensorWorker::spawn(sensor));
+    }
+
+    /// Adds a sensor that is opened by `open` on its polling thread once polling starts, retried
+    /// as configured in `retry`. Until it is open, its samples have no readings and its
+    /// [`SensorStatus`] shows it initializing or failed. `fields` are the fields of the sensor
+    /// `open` returns (see [`Sensor::fields`]), registered before it is open.
+    pub fn add_lazy_sensor(
+        &mut self,
+        label: &str,
+        fields: &'static [(&'static str, SleepField)],
+        retry: &SensorInitConfig,
+        open: impl FnMut() -> Result<Box<dyn Sensor>, Box<dyn Error>> + Send + 'static,
+    ) {
+        self.add_sensor(Box::new(LazySensor::new(label, fields, retry, retry.failed_retry(), Box::new(open))));
+    }
+
+    /// Adds the camera described by `config`, opened like [`SensorReader::add_lazy_sensor`] but
+    /// retried every `hotplug_retry_s` once failed, so that it is attached whenever it is plugged in.
+    fn add_camera(&mut self, label: &str, retry: &SensorInitConfig, image_directory: String, config: &CameraConfig, light_level: &LightLevel) {
+        let hotplug_retry = (config.hotplug_retry_s > 0).then(|| Duration::from_secs(config.hotplug_retry_s));
+        // Additional cameras are registered by name
+        let fields = if config.name.is_empty() { CAMERA_FIELDS } else { &[] };
+        let (config, light_level, name) = (config.clone(), light_level.clone(), label.to_string());
+        let open: OpenSensor = Box::new(move || {
+            let camera = CameraWrapper::from_config(&image_directory, &config)?.with_light_level(light_level.clone());
+            info!("{} initialized successfully.", name);
+            Ok(Box::new(camera))
+        });
+        self.add_sensor(Box::new(LazySensor::new(label, fields, retry, hotplug_retry, open)));
+    }
+
+    /// Names of the sensors, in polling order.
+    pub fn sensor_names(&self) -> Vec<&str> {
+        self.sensors.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    /// The fields of the sensors (see [`Sensor::fields`]), in polling order, for the storage to
+    /// create exactly the datasets of this set of sensors.
+    pub fn fields(&self) -> Vec<(&'static str, SleepField)> {
+        self.sensors.iter().flat_map(|s| s.fields.iter().copied()).collect()
     }
 
     /// Measures and returns SensorData.
     ///
-    /// This function fetches the current timestamp and attempts to gather sensor readings from the initialized sensors:
-    /// - BME280: Provides environmental measurements, added to SleepData if available.
-    /// - ENS160: Provides environmental data based on calibrated readings, added if available.
-    /// - Thermistor: Provides the temperature reading, added if available.
-    /// - Camera: Captures an image and includes the image path in SleepData if the measurement is successful.
+    /// This function fetches the current timestamp and polls every sensor in order, each adding
+    /// its readings to the SleepData for this timestamp.
     ///
-    /// Sensor measurements that return None are simply skipped, allowing partial data to be collected.
+    /// Sensor measurements that fail or time out are logged and skipped, allowing partial data to be
+    /// collected. The outcome for every sensor is added to the sample as its [`SensorStatus`].
     /// The constructed SleepData encapsulates the timestamp along with all successful sensor measurements.
     ///
     /// # Returns
@@ -466,20 +2690,253 @@ impl SensorReader {
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
         let mut builder = SleepData::builder(timestamp);
 
-        if let Some(bme280_measurements) = self.bme280.measure() {
-            builder = builder.with_bme280(bme280_measurements);
-        } 
-        if let Some(ens160_measurements) = self.ens160.measure() {
-            builder = builder.with_ens160(ens160_measurements);
-        } 
-        if let Some(thermistor_measurement) = self.thermistor.measure() {
-            builder = builder.with_thermistor_temp(thermistor_measurement);
-        }
-        if let Ok(camera_result) = self.camera.measure(timestamp) {
-            builder = builder.with_camera_result(camera_result);
+        let started: Vec<Result<(), String>> = self.sensors.iter_mut().map(|s| s.start(timestamp)).collect();
+        let deadline = Instant::now() + self.timeout;
+        for (sensor, started) in self.sensors.iter_mut().zip(started) {
+            let error = match started.and_then(|()| sensor.finish(deadline, self.timeout)) {
+                Ok(reading) => {
+                    builder = builder.merge(reading);
+                    None
+                }
+                Err(e) => {
+                    // Sensors that are not ready have logged their initialization errors already
+                    if sensor.state == SensorState::Ready {
+                        warn!("{} measurement skipped: {}", sensor.name, e);
+                    }
+                    sensor.last_error = Some(timestamp);
+                    Some(e)
+                }
+            };
+            let status = SensorStatus {
+                state: sensor.state,
+                error,
+                last_error_age_s: sensor.last_error.map(|t| timestamp.saturating_sub(t)),
+            };
+            builder = builder.with_sensor_status(&sensor.name, status);
         }
-        builder = builder.with_mmwave_result(self.mm_wave.poll_sleep_data());
 
         Ok(builder.build())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::{ErrorType, Operation};
+
+    /// Fails every other measurement.
+    struct FlakySensor {
+        calls: u32,
+    }
+
+    impl Sensor for FlakySensor {
+        fn name(&self) -> &str {
+            "Flaky"
+        }
+
+        fn measure(&mut self, _builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(2) {
+                return Err("bus error".into());
+            }
+            Ok(())
+        }
+    }
+
+    /// Takes `delay` for every measurement, e.g. like a device hanging the bus, and reports `lux`.
+    struct SlowSensor {
+        name: &'static str,
+        delay: Duration,
+        lux: f32,
+    }
+
+    impl Sensor for SlowSensor {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+            std::thread::sleep(self.delay);
+            *builder = std::mem::take(builder).with_light_lux(self.lux);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_measure_polls_sensors_concurrently() {
+        let slow = |name, lux| Box::new(SlowSensor { name, delay: Duration::from_millis(200), lux }) as Box<dyn Sensor>;
+        let mut reader = SensorReader::with_sensors(vec![slow("A", 1.0), slow("B", 2.0), slow("C", 3.0)]);
+        let start = Instant::now();
+        let sample = reader.measure().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500), "Took {:?}", start.elapsed());
+        // Readings are merged in sensor order
+        assert_eq!(sample.light_lux, 3.0);
+        assert_eq!(sample.sensor_status.len(), 3);
+    }
+
+    #[test]
+    fn test_measure_abandons_stuck_sensor() {
+        let stuck = SlowSensor { name: "Stuck", delay: Duration::from_millis(300), lux: 1.0 };
+        let mut reader = SensorReader::with_sensors(vec![Box::new(stuck), Box::new(FlakySensor { calls: 0 })])
+            .with_timeout(Duration::from_millis(50));
+        let sample = reader.measure().unwrap();
+        assert!(sample.sensor_status["Stuck"].error.as_deref().unwrap().contains("timed out"));
+        assert!(sample.sensor_status["Flaky"].ok());
+        // Skipped without waiting while the measurement is still blocked
+        let start = Instant::now();
+        let sample = reader.measure().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert!(!sample.sensor_status["Stuck"].ok());
+        // Polled again once it has returned
+        std::thread::sleep(Duration::from_millis(400));
+        assert!(reader.with_timeout(Duration::from_secs(1)).measure().unwrap().sensor_status["Stuck"].ok());
+    }
+
+    #[test]
+    fn test_measure_records_sensor_status() {
+        let mut reader = SensorReader::with_sensors(vec![Box::new(FlakySensor { calls: 0 })]);
+        let first = reader.measure().unwrap().sensor_status["Flaky"].clone();
+        assert_eq!(first, SensorStatus { state: SensorState::Ready, error: None, last_error_age_s: None });
+        let second = reader.measure().unwrap().sensor_status["Flaky"].clone();
+        assert_eq!(second, SensorStatus { state: SensorState::Ready, error: Some("bus error".to_string()), last_error_age_s: Some(0) });
+        let third = reader.measure().unwrap().sensor_status["Flaky"].clone();
+        assert!(third.ok());
+        assert!(third.last_error_age_s.is_some());
+    }
+
+    #[test]
+    fn test_init_with_retry() {
+        let retry = SensorInitConfig { initial_backoff_ms: 10, max_backoff_ms: 20, budget_s: 1, ..Default::default() };
+        let mut attempts = 0;
+        let sensor = init_with_retry("Warming up", &retry, || {
+            attempts += 1;
+            match attempts {
+                1..=2 => Err("not ready".into()),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(sensor, Some(3));
+
+        let start = Instant::now();
+        let sensor: Option<()> = init_with_retry("Missing", &retry, || Err("no such device".into()));
+        assert_eq!(sensor, None);
+        assert!(start.elapsed() < Duration::from_millis(1500), "Took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_lazy_sensor_states() {
+        let retry = SensorInitConfig { budget_s: 0, reinit_after_failures: 1, ..Default::default() };
+        // Missing on the first attempt, then a sensor failing every other measurement
+        let mut attempts = 0;
+        let lazy = LazySensor::new("Lazy", &[], &retry, Some(Duration::ZERO), Box::new(move || {
+            attempts += 1;
+            match attempts {
+                1 => Err("no such device".into()),
+                _ => Ok(Box::new(FlakySensor { calls: 0 })),
+            }
+        }));
+        let mut reader = SensorReader::with_sensors(vec![Box::new(lazy)]);
+        let mut status = || reader.measure().unwrap().sensor_status["Lazy"].clone();
+
+        let failed = status();
+        assert_eq!(failed.state, SensorState::Failed);
+        assert_eq!(failed.error.as_deref(), Some("no such device"));
+        // Opened on the next poll and measured right away
+        assert!(status().ok());
+        // The failed measurement reopens the sensor
+        let reopening = status();
+        assert_eq!(reopening.state, SensorState::Initializing);
+        assert_eq!(reopening.error.as_deref(), Some("bus error"));
+        assert!(status().ok());
+    }
+
+    #[test]
+    fn test_parse_capture_devices() {
+        let cards = " 0 [vc4hdmi0       ]: vc4-hdmi - vc4-hdmi-0
+                      vc4-hdmi-0
+ 2 [Camera         ]: USB-Audio - USB Camera
+                      Arducam Technology Co., Ltd. USB Camera at usb-3f980000.usb-1.3, high speed
+";
+        let pcm = "00-00: MAI PCM i2s-hifi-0 : MAI PCM i2s-hifi-0 : playback 1
+02-00: USB Audio : USB Audio : capture 1
+";
+        let devices = parse_capture_devices(cards, pcm);
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].card, 2);
+        assert_eq!(devices[0].card_id, "Camera");
+        assert!(devices[0].name.contains("Arducam"));
+        assert_eq!(devices[0].alsa_name(), "plughw:CARD=Camera,DEV=0");
+    }
+
+    /// MCP3424 answering every read with a count of 100 times the selected channel number, and
+    /// the configuration last written to it (with the conversion ready).
+    #[derive(Default)]
+    struct FakeMcp3424 {
+        config: u8,
+    }
+
+    impl ErrorType for FakeMcp3424 {
+        type Error = std::convert::Infallible;
+    }
+
+    impl I2c for FakeMcp3424 {
+        fn transaction(&mut self, _address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => self.config = bytes[0] & 0x7F,
+                    Operation::Read(buf) => {
+                        let count = (((self.config >> 5) & 0b11) as u16 + 1) * 100;
+                        buf[..2].copy_from_slice(&count.to_be_bytes());
+                        buf[2] = self.config;
+                    }
+                }
+            }
+            // Leave the other sensor's thread time to get in between
+            std::thread::sleep(Duration::from_micros(50));
+            Ok(())
+        }
+    }
+
+    // Piezo bursts and thermistor conversions on the same chip, interleaved, each see only their
+    // own channel and resolution.
+    #[test]
+    fn test_shared_adc_inputs() {
+        let adc = Arc::new(Mutex::new(MCP342x::new(FakeMcp3424::default(), 0x68)));
+        let piezo = AdcInput::new(adc.clone(), Channel::Ch1, Gain::G8);
+        let thermistor = AdcInput::new(adc, Channel::Ch3, Gain::G1);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..10 {
+                    let capture = piezo.capture(50).expect("Failed to capture burst");
+                    // 100 counts at 1 mV (12 bits) and gain 8
+                    assert!(capture.samples.iter().all(|s| (s.voltage.millivolts() - 12.5).abs() < 1e-3));
+                }
+            });
+            for _ in 0..5 {
+                // 300 counts at 62.5 µV (16 bits)
+                let voltage = thermistor.read(Resolution::Bits16).expect("Failed to read thermistor");
+                assert!((voltage.millivolts() - 18.75).abs() < 1e-3, "{}", voltage.millivolts());
+            }
+        });
+    }
+
+    #[test]
+    fn test_parse_w1_slave() {
+        let ok = "72 01 4b 46 7f ff 0e 10 57 : crc=57 YES\n72 01 4b 46 7f ff 0e 10 57 t=23125\n";
+        assert_eq!(parse_w1_slave(ok), Some(23.125));
+        let bad_crc = "72 01 4b 46 7f ff 0e 10 57 : crc=12 NO\n72 01 4b 46 7f ff 0e 10 57 t=23125\n";
+        assert_eq!(parse_w1_slave(bad_crc), None);
+    }
+
+    #[test]
+    fn test_parse_host_stats() {
+        assert_eq!(parse_loadavg("0.52 0.58 0.59 1/189 12345\n"), Some(0.52));
+        let meminfo = "MemTotal:         949448 kB
+MemFree:          120000 kB
+MemAvailable:     474724 kB
+";
+        let used = parse_meminfo_used_percent(meminfo).unwrap();
+        assert!((used - 50.0).abs() < 0.01, "Expected 50 %, got {}", used);
+        assert_eq!(parse_meminfo_used_percent("MemTotal: 1000 kB\n"), None);
+    }
+}
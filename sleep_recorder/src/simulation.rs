@@ -0,0 +1,565 @@
+//! Simulated sensors and audio, for running the whole recorder on a machine without the hardware
+//! (a laptop, CI). Built with the `simulation` feature and selected with [`Config::simulation`].
+//!
+//! Every wrapper in [`crate::sensor`] has a stand-in here that adds plausible readings to the same
+//! datasets: a slowly drifting room temperature, random-walk CO2, a sleeper who occasionally turns
+//! over in the camera images, and breathing noise in the audio. The simulated sensors follow the
+//! configuration like the real ones: disabled sensors are left out, and optional sensors are only
+//! simulated when configured.
+
+use std::error::Error;
+use std::f32::consts::PI;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ab_glyph::FontArc;
+use chrono::{Local, TimeZone, Timelike};
+use dfrobot_c1001::C1001SleepData;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{GrayImage, Rgb, RgbImage};
+use imageproc::drawing::{draw_filled_ellipse_mut, draw_filled_rect_mut};
+use imageproc::rect::Rect;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::actuator::{Actuator, Actuators};
+use crate::audio_analysis::wav_spec;
+use crate::bcg;
+use crate::config::{CameraConfig, Config, OverlayConfig};
+use crate::data::{CameraAndMotionResult, SleepDataBuilder, SleepField};
+use crate::image_analysis::{frame_difference, masked_frame_difference, roi_mask};
+use crate::pms5003::PmMeasurement;
+use crate::sensirion::Scd4xMeasurement;
+use crate::sensor::{
+    overlay_font, AudioChunk, CameraWrapper, LightLevel, Sensor, SensorReader, SystemStatsWrapper, BH1750_FIELDS, BME280_FIELDS,
+    CAMERA_FIELDS, ENS160_FIELDS, HX711_FIELDS, MMWAVE_FIELDS, PIEZO_FIELDS, PIR_FIELDS, PMS5003_FIELDS, SCD4X_FIELDS,
+    SGP40_FIELDS, THERMISTOR_FIELDS,
+};
+
+/// Sample rate of the simulated audio.
+pub const AUDIO_SAMPLE_RATE: u32 = 48_000;
+
+/// Sample rate of the simulated piezo bursts, as captured from the MCP3424.
+const PIEZO_SAMPLE_RATE: f32 = 240.0;
+
+/// Creates a `SensorReader` with a simulated stand-in for every sensor `config` enables.
+///
+/// Images are stored under `config.data_path/group_name/images/`, like those of real cameras.
+///
+/// # Errors
+///
+/// Returns an error if an image directory cannot be created or the overlay font cannot be loaded.
+pub fn sensor_reader(config: &Config, group_name: &str) -> Result<SensorReader, Box<dyn Error>> {
+    let data_path = config.data_path.as_str();
+    let mut reader = SensorReader::with_sensors(Vec::new()).with_timeout(config.sensor_timeout());
+
+    if config.is_enabled("bme280") {
+        let (mut pressure, mut humidity) = (RandomWalk::new(1013.0, 0.1, 990.0, 1030.0), RandomWalk::new(45.0, 0.2, 30.0, 60.0));
+        reader.add_sensor(Simulated::boxed("BME280", BME280_FIELDS, move |rng, builder| {
+            let temperature = 20.0 + 1.5 * daily_cycle(builder.timestamp()) + 0.05 * rng.normal();
+            builder.with_climate(temperature, pressure.step(rng), humidity.step(rng))
+        }));
+    }
+    if config.is_enabled("ens160") {
+        let mut co2eq = RandomWalk::new(600.0, 15.0, 400.0, 2500.0);
+        reader.add_sensor(Simulated::boxed("ENS160", ENS160_FIELDS, move |rng, builder| {
+            let co2eq_ppm = co2eq.step(rng);
+            // The ENS160 derives its eCO2 from the TVOC, so the two move together
+            let tvoc_ppb = (co2eq_ppm - 400.0) * 0.5 + 20.0 * rng.next_f32();
+            let air_quality_index = match co2eq_ppm {
+                ppm if ppm < 600.0 => 1,
+                ppm if ppm < 800.0 => 2,
+                ppm if ppm < 1000.0 => 3,
+                ppm if ppm < 1500.0 => 4,
+                _ => 5,
+            };
+            builder.with_air_quality(co2eq_ppm as u16, tvoc_ppb as u16, air_quality_index)
+        }));
+    }
+    if config.is_enabled("thermistor") {
+        reader.add_sensor(thermistor(""));
+    }
+
+    let light_level = LightLevel::default();
+    if config.bh1750 {
+        let light_level = light_level.clone();
+        reader.add_sensor(Simulated::boxed("BH1750", BH1750_FIELDS, move |rng, builder| {
+            let lux = daylight_lux(builder.timestamp()) * (1.0 + 0.02 * rng.normal()).max(0.0);
+            if let Ok(mut light_level) = light_level.lock() {
+                *light_level = Some(lux);
+            }
+            builder.with_light_lux(lux)
+        }));
+    }
+
+    if config.is_enabled("camera") {
+        let image_directory = format!("{}/{}/images/", data_path, group_name);
+        reader.add_sensor(Box::new(SimulatedCamera::from_config(&image_directory, &config.camera, light_level.clone())?));
+    }
+    if config.is_enabled("mmwave") {
+        let (mut heart_rate, mut resp_rate) = (RandomWalk::new(60.0, 1.0, 45.0, 80.0), RandomWalk::new(14.0, 0.3, 10.0, 20.0));
+        reader.add_sensor(Simulated::boxed("C1001 mmWave", MMWAVE_FIELDS, move |rng, builder| {
+            builder.with_mmwave_result(C1001SleepData {
+                presence: Some(true),
+                movement: Some(rng.next_f32() < 0.1),
+                heart_rate_bpm: Some(heart_rate.step(rng) as u16),
+                resp_rate_bpm: Some(resp_rate.step(rng) as u16),
+            })
+        }));
+    }
+    if config.is_enabled("system_stats") {
+        // The host's own metrics are real on any Linux machine
+        reader.add_sensor(Box::new(SystemStatsWrapper::new(data_path)));
+    }
+
+    for camera_config in &config.extra_cameras {
+        let image_directory = format!("{}/{}/images/{}/", data_path, group_name, camera_config.name);
+        reader.add_sensor(Box::new(SimulatedCamera::from_config(&image_directory, camera_config, light_level.clone())?));
+    }
+    if config.scd4x {
+        let mut co2 = RandomWalk::new(550.0, 10.0, 400.0, 2500.0);
+        reader.add_sensor(Simulated::boxed("SCD4x", SCD4X_FIELDS, move |rng, builder| {
+            builder.with_scd4x(Scd4xMeasurement { co2_ppm: co2.step(rng) as u16, temperature_c: f32::NAN, humidity: f32::NAN })
+        }));
+    }
+    if config.sgp40 {
+        let mut voc_index = RandomWalk::new(100.0, 5.0, 1.0, 500.0);
+        reader.add_sensor(Simulated::boxed("SGP40", SGP40_FIELDS, move |rng, builder| builder.with_voc_index(voc_index.step(rng) as u16)));
+    }
+    if config.pms5003.is_some() {
+        let mut pm2_5 = RandomWalk::new(8.0, 0.5, 0.0, 100.0);
+        reader.add_sensor(Simulated::boxed("PMS5003", PMS5003_FIELDS, move |rng, builder| {
+            let pm2_5 = pm2_5.step(rng);
+            builder.with_pms5003(PmMeasurement { pm1_0: (0.7 * pm2_5) as u16, pm2_5: pm2_5 as u16, pm10: (1.3 * pm2_5) as u16 })
+        }));
+    }
+    if let Some(hx711) = &config.hx711 {
+        let occupied_kg = hx711.occupied_kg;
+        reader.add_sensor(Simulated::boxed("HX711", HX711_FIELDS, move |rng, builder| {
+            let weight_kg = 72.0 + 0.3 * rng.normal();
+            builder.with_bed_weight(weight_kg, weight_kg > occupied_kg)
+        }));
+    }
+    if let Some(piezo) = &config.piezo {
+        let burst_samples = (piezo.burst_s * PIEZO_SAMPLE_RATE) as usize;
+        reader.add_sensor(Simulated::boxed("Piezo BCG", PIEZO_FIELDS, move |rng, builder| {
+            let samples_mv = bcg_burst(rng, burst_samples);
+            let estimate = bcg::estimate(&samples_mv, PIEZO_SAMPLE_RATE);
+            builder.with_piezo_burst(samples_mv, estimate)
+        }));
+    }
+    if config.pir.is_some() {
+        reader.add_sensor(Simulated::boxed("PIR", PIR_FIELDS, |rng, builder| builder.with_pir_motion(rng.next_f32() < 0.05)));
+    }
+    for probe in &config.ds18b20 {
+        let name = probe.name.clone();
+        reader.add_sensor(Simulated::boxed(&format!("DS18B20 {}", probe.name), &[], move |rng, builder| {
+            let temperature = 24.0 + daily_cycle(builder.timestamp()) + 0.06 * rng.normal();
+            builder.with_probe_temp(&name, temperature)
+        }));
+    }
+    for thermistor_config in &config.extra_thermistors {
+        reader.add_sensor(thermistor(&thermistor_config.name));
+    }
+    info!("Simulated sensors initialized: {:?}", reader.sensor_names());
+    Ok(reader)
+}
+
+/// Simulated thermistor under the sleeper, warmed to body temperature.
+fn thermistor(name: &str) -> Box<dyn Sensor> {
+    let label = match name {
+        "" => "Thermistor".to_string(),
+        name => format!("Thermistor {name}"),
+    };
+    let fields = if name.is_empty() { THERMISTOR_FIELDS } else { &[] };
+    let name = name.to_string();
+    Simulated::boxed(&label, fields, move |rng, builder| {
+        let temperature = 31.0 + 0.5 * daily_cycle(builder.timestamp()) + 0.1 * rng.normal();
+        match name.as_str() {
+            "" => builder.with_thermistor_temp(temperature),
+            name => builder.with_named_thermistor_temp(name, temperature),
+        }
+    })
+}
+
+/// A simulated sensor whose readings are generated by a closure.
+struct Simulated<F> {
+    label: String,
+    fields: &'static [(&'static str, SleepField)],
+    rng: Rng,
+    reading: F,
+}
+
+impl<F> Simulated<F>
+where
+    F: FnMut(&mut Rng, SleepDataBuilder) -> SleepDataBuilder + Send + 'static,
+{
+    fn boxed(label: &str, fields: &'static [(&'static str, SleepField)], reading: F) -> Box<dyn Sensor> {
+        Box::new(Self { label: label.to_string(), fields, rng: Rng::seeded(), reading })
+    }
+}
+
+impl<F> Sensor for Simulated<F>
+where
+    F: FnMut(&mut Rng, SleepDataBuilder) -> SleepDataBuilder + Send,
+{
+    fn name(&self) -> &str {
+        &self.label
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        self.fields
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        *builder = (self.reading)(&mut self.rng, std::mem::take(builder));
+        Ok(())
+    }
+}
+
+/// Simulated camera looking at a bed, on which the sleeper turns over now and then. The images are
+/// darker at night and saved like those of a real camera, with the timestamp overlay and
+/// optionally a raw copy. Motion clips are not simulated.
+struct SimulatedCamera {
+    camera_name: String,
+    label: String,
+    image_directory: String,
+    resolution: (u32, u32),
+    jpeg_quality: u8,
+    save_raw: bool,
+    save_resolution: Option<[u32; 2]>,
+    thumbnail_size: Option<[u32; 2]>,
+    overlay: Option<(OverlayConfig, FontArc)>,
+    motion_mask: Option<GrayImage>,
+    light_level: LightLevel,
+    /// Center of the sleeper, as a fraction of the frame size.
+    sleeper: (f32, f32),
+    last_image: Option<GrayImage>,
+    rng: Rng,
+}
+
+impl SimulatedCamera {
+    fn from_config(image_directory: &str, config: &CameraConfig, light_level: LightLevel) -> Result<Self, Box<dyn Error>> {
+        std::fs::create_dir_all(image_directory)?;
+        if config.save_raw {
+            std::fs::create_dir_all(format!("{}/raw", image_directory))?;
+        }
+        let overlay = match &config.overlay {
+            overlay if !overlay.enabled => None,
+            overlay => Some((overlay.clone(), overlay_font(overlay.font.as_deref())?)),
+        };
+        let (width, height) = (config.resolution[0], config.resolution[1]);
+        let label = match config.name.as_str() {
+            "" => "Camera".to_string(),
+            name => format!("Camera {name}"),
+        };
+        Ok(Self {
+            camera_name: config.name.clone(),
+            label,
+            image_directory: image_directory.to_string(),
+            resolution: (width, height),
+            jpeg_quality: config.jpeg_quality,
+            save_raw: config.save_raw,
+            save_resolution: config.save_resolution,
+            thumbnail_size: config.thumbnail_size,
+            overlay,
+            motion_mask: config.motion_roi.as_ref().map(|roi| roi_mask(roi, width, height)),
+            light_level,
+            sleeper: (0.5, 0.55),
+            last_image: None,
+            rng: Rng::seeded(),
+        })
+    }
+
+    /// Renders the next frame: the room, the bed, and the sleeper, with sensor noise.
+    fn render(&mut self) -> RgbImage {
+        if self.rng.next_f32() < 0.05 {
+            // Turn over
+            self.sleeper = (0.4 + 0.2 * self.rng.next_f32(), 0.5 + 0.1 * self.rng.next_f32());
+        }
+        let lux = self.light_level.lock().ok().and_then(|lux| *lux).unwrap_or(100.0);
+        // Night frames look like gray IR images
+        let brightness = (0.3 + lux / 100.0).min(1.0);
+        let (width, height) = self.resolution;
+        let shade = |value: f32| (value * brightness).clamp(0.0, 255.0) as u8;
+
+        let mut image = RgbImage::from_fn(width, height, |_, y| {
+            let wall = 90.0 + 60.0 * y as f32 / height as f32;
+            Rgb([shade(wall), shade(wall * 0.95), shade(wall * 0.9)])
+        });
+        let bed = Rect::at((0.2 * width as f32) as i32, (0.35 * height as f32) as i32)
+            .of_size(((0.6 * width as f32) as u32).max(1), ((0.55 * height as f32) as u32).max(1));
+        draw_filled_rect_mut(&mut image, bed, Rgb([shade(200.0), shade(200.0), shade(210.0)]));
+        let center = ((self.sleeper.0 * width as f32) as i32, (self.sleeper.1 * height as f32) as i32);
+        draw_filled_ellipse_mut(&mut image, center, (width / 6) as i32, (height / 10) as i32, Rgb([shade(120.0), shade(100.0), shade(90.0)]));
+        for pixel in image.pixels_mut() {
+            let noise = 4.0 * self.rng.normal();
+            pixel.0 = pixel.0.map(|channel| (channel as f32 + noise).clamp(0.0, 255.0) as u8);
+        }
+        image
+    }
+
+    fn save_jpeg(&self, image: &RgbImage, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        image.write_with_encoder(JpegEncoder::new_with_quality(&mut file, self.jpeg_quality))?;
+        Ok(())
+    }
+
+    fn capture(&mut self, timestamp: u64) -> Result<CameraAndMotionResult, Box<dyn Error>> {
+        let mut image = self.render();
+        let gray_image = image::DynamicImage::ImageRgb8(image.clone()).to_luma8();
+        if let Some([width, height]) = self.save_resolution {
+            image = imageops::resize(&image, width, height, FilterType::Triangle);
+        }
+
+        let mut raw_image_path = None;
+        if self.save_raw {
+            let path = format!("{}/raw/image_{}.jpg", self.image_directory, timestamp);
+            self.save_jpeg(&image, &path)?;
+            raw_image_path = Some(path);
+        }
+        let thumbnail = self.thumbnail_size
+            .map(|size| CameraWrapper::thumbnail(&image, size, self.jpeg_quality))
+            .transpose()?;
+        if let Some((overlay, font)) = &self.overlay {
+            CameraWrapper::timestamp_image_mut(&mut image, timestamp, overlay, font)?;
+        }
+        let image_path = format!("{}/image_{}.jpg", self.image_directory, timestamp);
+        self.save_jpeg(&image, &image_path)?;
+
+        let motion = self.last_image.take().and_then(|last_image| match &self.motion_mask {
+            Some(mask) => masked_frame_difference(&gray_image, &last_image, mask).ok(),
+            None => frame_difference(&gray_image, &last_image).ok(),
+        });
+        self.last_image = Some(gray_image);
+        Ok(CameraAndMotionResult { image_path, motion, clip_path: None, raw_image_path, thumbnail })
+    }
+}
+
+impl Sensor for SimulatedCamera {
+    fn name(&self) -> &str {
+        &self.label
+    }
+
+    fn fields(&self) -> &'static [(&'static str, SleepField)] {
+        match self.camera_name.as_str() {
+            "" => CAMERA_FIELDS,
+            _ => &[],
+        }
+    }
+
+    fn measure(&mut self, builder: &mut SleepDataBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let result = self.capture(builder.timestamp()).map_err(|e| e.to_string())?;
+        *builder = match self.camera_name.as_str() {
+            "" => std::mem::take(builder).with_camera_result(result),
+            name => std::mem::take(builder).with_named_camera_result(name, result),
+        };
+        Ok(())
+    }
+}
+
+/// Creates a simulated stand-in for every actuator in `config.actuators`. Switching them only
+/// logs the state change.
+pub fn actuators(config: &Config) -> Actuators {
+    Actuators::new(config.actuators.iter()
+        .map(|a| Box::new(SimulatedRelay { relay_name: a.name.clone() }) as Box<dyn Actuator>)
+        .collect())
+}
+
+/// Relay without hardware.
+struct SimulatedRelay {
+    relay_name: String,
+}
+
+impl Actuator for SimulatedRelay {
+    fn name(&self) -> &str {
+        &self.relay_name
+    }
+
+    fn set(&mut self, _on: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+/// Writes `recording_time` of simulated bedroom audio to a mono 16-bit WAV file at `path`, in real
+/// time, or until `cancel` is cancelled. Returns the recorded duration.
+///
+/// The audio is quiet background noise, breathing at 15 breaths per minute, and now and then a
+/// minute of snoring. Samples are published to `samples` in 100 ms chunks of the recording
+/// started at `recording_start_s`, like a capture device.
+pub fn record_audio(
+    path: &str,
+    recording_start_s: u64,
+    recording_time: Duration,
+    cancel: &CancellationToken,
+    samples: &broadcast::Sender<AudioChunk>,
+) -> Result<Duration, Box<dyn Error + Send + Sync>> {
+    let mut writer = hound::WavWriter::create(path, wav_spec(AUDIO_SAMPLE_RATE, 1))?;
+    let mut rng = Rng::seeded();
+
+    let total = (recording_time.as_secs_f64() * AUDIO_SAMPLE_RATE as f64) as usize;
+    let chunk_len = AUDIO_SAMPLE_RATE as usize / 10;
+    let start = Instant::now();
+    let mut recorded = 0;
+    while recorded < total && !cancel.is_cancelled() {
+        let chunk: Vec<i16> = (recorded..(recorded + chunk_len).min(total))
+            .map(|i| {
+                let t = i as f32 / AUDIO_SAMPLE_RATE as f32;
+                // Snoring during every fifth minute of the session
+                let snoring = ((recording_start_s + t as u64) / 60).is_multiple_of(5);
+                let inhale = (2.0 * PI * 0.25 * t).sin().max(0.0);
+                let breath = 200.0 * inhale * rng.normal();
+                let snore = if snoring { 1500.0 * inhale * (2.0 * PI * 90.0 * t).sin() } else { 0.0 };
+                (30.0 * rng.normal() + breath + snore) as i16
+            })
+            .collect();
+        for sample in &chunk {
+            writer.write_sample(*sample)?;
+        }
+        recorded += chunk.len();
+        // No subscribers is not an error
+        let _ = samples.send(AudioChunk { recording_start_s, sample_rate: AUDIO_SAMPLE_RATE, samples: chunk.into() });
+        // Pace like a real device
+        let elapsed = Duration::from_secs_f64(recorded as f64 / AUDIO_SAMPLE_RATE as f64);
+        std::thread::sleep(elapsed.saturating_sub(start.elapsed()));
+    }
+    writer.finalize()?;
+
+    Ok(Duration::from_secs_f64(recorded as f64 / AUDIO_SAMPLE_RATE as f64))
+}
+
+/// A burst of piezo samples (mV): breathing at ~15/min with heartbeats at ~60 bpm.
+fn bcg_burst(rng: &mut Rng, samples: usize) -> Vec<f32> {
+    let (breath_rate, heart_rate) = (0.25 + 0.02 * rng.normal(), 1.0 + 0.05 * rng.normal());
+    let phase = rng.next_f32();
+    (0..samples)
+        .map(|i| {
+            let t = i as f32 / PIEZO_SAMPLE_RATE + phase;
+            let breathing = 50.0 * (2.0 * PI * breath_rate * t).sin();
+            let beat_phase = (t * heart_rate).fract() / heart_rate;
+            let beat = if beat_phase < 0.08 { 5.0 * (PI * beat_phase / 0.08).sin() } else { 0.0 };
+            breathing + beat + 0.2 * rng.normal()
+        })
+        .collect()
+}
+
+/// Daily temperature cycle in [-1, 1]: warmest in the afternoon, coolest before dawn.
+fn daily_cycle(timestamp: u64) -> f32 {
+    let hour = local_hour(timestamp);
+    (2.0 * PI * (hour - 15.0) / 24.0).cos()
+}
+
+/// Ambient light in lux: dark from 22:00 to 07:00, daylight in between.
+fn daylight_lux(timestamp: u64) -> f32 {
+    match local_hour(timestamp) {
+        hour if !(7.0..22.0).contains(&hour) => 0.5,
+        _ => 250.0,
+    }
+}
+
+/// Local time of day in hours.
+fn local_hour(timestamp: u64) -> f32 {
+    Local.timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|time| time.hour() as f32 + time.minute() as f32 / 60.0)
+        .unwrap_or(0.0)
+}
+
+/// A value drifting randomly by up to `step` per reading, kept within `[min, max]`.
+struct RandomWalk {
+    value: f32,
+    step: f32,
+    min: f32,
+    max: f32,
+}
+
+impl RandomWalk {
+    fn new(value: f32, step: f32, min: f32, max: f32) -> Self {
+        Self { value, step, min, max }
+    }
+
+    fn step(&mut self, rng: &mut Rng) -> f32 {
+        self.value = (self.value + self.step * (2.0 * rng.next_f32() - 1.0)).clamp(self.min, self.max);
+        self.value
+    }
+}
+
+/// Small xorshift generator; the simulation needs variety, not statistical quality.
+struct Rng(u64);
+
+impl Rng {
+    /// A generator seeded from the clock.
+    fn seeded() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        Self(nanos | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Approximately standard normal (Irwin-Hall).
+    fn normal(&mut self) -> f32 {
+        (0..12).map(|_| self.next_f32()).sum::<f32>() - 6.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_simulated_sensor_reader() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let config = Config {
+            data_path: dir.path().to_str().unwrap().to_string(),
+            scd4x: true,
+            disabled_sensors: vec!["mmwave".to_string()],
+            camera: CameraConfig { resolution: [160, 120], thumbnail_size: Some([40, 30]), ..CameraConfig::default() },
+            ..Config::default()
+        };
+        let mut reader = sensor_reader(&config, "group").expect("Failed to create simulated sensors");
+        assert!(reader.sensor_names().contains(&"SCD4x"));
+        assert!(!reader.sensor_names().contains(&"C1001 mmWave"));
+
+        reader.measure().expect("Failed to measure");
+        let data = reader.measure().expect("Failed to measure");
+        assert!((15.0..25.0).contains(&data.temperature_c), "Temperature {}", data.temperature_c);
+        assert!(data.co2_ppm >= 400);
+        assert!(std::path::Path::new(&data.image_path).exists());
+        assert!(data.image_motion_live.is_finite());
+        let thumbnail = image::load_from_memory(&data.thumbnail.0).expect("Failed to decode thumbnail");
+        assert_eq!((thumbnail.width(), thumbnail.height()), (40, 30));
+    }
+
+    #[test]
+    fn test_simulated_piezo_burst_has_heart_rate() {
+        let samples = bcg_burst(&mut Rng::seeded(), (8.0 * PIEZO_SAMPLE_RATE) as usize);
+        let heart_rate = bcg::estimate(&samples, PIEZO_SAMPLE_RATE).heart_rate_bpm.expect("Expected a heart rate");
+        assert!((50.0..70.0).contains(&heart_rate), "Heart rate {}", heart_rate);
+    }
+
+    #[test]
+    fn test_record_audio() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("audio.wav");
+        let (samples, mut receiver) = broadcast::channel(64);
+        let duration = record_audio(path.to_str().unwrap(), 1_745_873_251, Duration::from_millis(300), &CancellationToken::new(), &samples)
+            .expect("Failed to record simulated audio");
+        assert_eq!(duration, Duration::from_millis(300));
+        let chunk = receiver.try_recv().expect("Expected a published chunk");
+        assert_eq!((chunk.recording_start_s, chunk.sample_rate, chunk.samples.len()), (1_745_873_251, 48_000, 4800));
+        let reader = hound::WavReader::open(&path).expect("Failed to open recording");
+        assert_eq!(reader.duration(), 14_400);
+    }
+}
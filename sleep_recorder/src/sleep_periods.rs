@@ -0,0 +1,108 @@
+//! Sleep periods detected from a session's samples, in the way of actigraphy.
+//!
+//! The session is split into epochs of [`EPOCH_S`] seconds. An epoch is in bed when the bed is
+//! occupied or the mmWave radar detects a presence in any of its samples; when neither the load
+//! cells nor the radar report anything in the whole session, every epoch is assumed to be in bed.
+//! An epoch is still when no sample reports PIR or mmWave movement, or image motion of at least
+//! [`MOTION_THRESHOLD`]. A run of still in-bed epochs lasting at least [`SLEEP_ONSET_S`] is
+//! asleep; the other in-bed epochs are awake.
+//!
+//! Sleep stages (light, deep, REM) are not distinguished.
+
+use crate::data::SleepData;
+
+/// Length of an epoch, in seconds.
+pub const EPOCH_S: u64 = 30;
+/// Image motion (mean absolute pixel difference, 0-255) from which an epoch is not still.
+pub const MOTION_THRESHOLD: f32 = 5.0;
+/// Shortest time in bed without movement that counts as sleep, in seconds.
+pub const SLEEP_ONSET_S: u64 = 600;
+
+/// State of the sleeper during a [`SleepPeriod`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepState {
+    OutOfBed,
+    Awake,
+    Asleep,
+}
+
+/// A period of a single [`SleepState`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SleepPeriod {
+    /// Start of the period, in seconds since UNIX epoch.
+    pub start_s: u64,
+    /// End of the period (exclusive), in seconds since UNIX epoch.
+    pub end_s: u64,
+    pub state: SleepState,
+}
+
+/// Detects the sleep periods of a session from its `samples`, sorted by time (see the
+/// [module documentation](self)). Consecutive periods have different states, and together cover
+/// the epochs from the first sample to the last.
+pub fn detect(samples: &[SleepData]) -> Vec<SleepPeriod> {
+    let Some(start_s) = samples.first().map(|sample| sample.timestamp_s) else {
+        return Vec::new();
+    };
+    let epochs = ((samples[samples.len() - 1].timestamp_s - start_s) / EPOCH_S + 1) as usize;
+    let presence_sensed = samples.iter().any(|sample| sample.bed_occupied || sample.mmwave_presence);
+    let mut in_bed = vec![!presence_sensed; epochs];
+    let mut still = vec![true; epochs];
+    for sample in samples {
+        let epoch = ((sample.timestamp_s - start_s) / EPOCH_S) as usize;
+        in_bed[epoch] |= sample.bed_occupied || sample.mmwave_presence;
+        still[epoch] &= !(sample.pir_motion || sample.mmwave_movement || sample.image_motion_or_live() >= MOTION_THRESHOLD);
+    }
+
+    let onset_epochs = SLEEP_ONSET_S.div_ceil(EPOCH_S) as usize;
+    let mut states: Vec<SleepState> = in_bed.iter()
+        .map(|&in_bed| if in_bed { SleepState::Awake } else { SleepState::OutOfBed })
+        .collect();
+    let mut epoch = 0;
+    while epoch < epochs {
+        let run = (epoch..epochs).take_while(|&e| in_bed[e] && still[e]).count();
+        if run >= onset_epochs {
+            states[epoch..epoch + run].fill(SleepState::Asleep);
+        }
+        epoch += run.max(1);
+    }
+
+    let mut periods: Vec<SleepPeriod> = Vec::new();
+    for (epoch, state) in states.into_iter().enumerate() {
+        let epoch_start_s = start_s + epoch as u64 * EPOCH_S;
+        match periods.last_mut() {
+            Some(period) if period.state == state => period.end_s = epoch_start_s + EPOCH_S,
+            _ => periods.push(SleepPeriod { start_s: epoch_start_s, end_s: epoch_start_s + EPOCH_S, state }),
+        }
+    }
+    periods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_detect() {
+        // Out of bed for a minute, restless in bed for a minute, then still for 15 minutes
+        let mut samples = Vec::new();
+        for timestamp_s in (0..1020).step_by(10) {
+            let in_bed = timestamp_s >= 60;
+            samples.push(SleepData::builder(timestamp_s)
+                .with_bed_weight(if in_bed { 70.0 } else { 0.0 }, in_bed)
+                .with_pir_motion(timestamp_s < 120)
+                .build());
+        }
+        let periods = detect(&samples);
+        assert_eq!(periods, vec![
+            SleepPeriod { start_s: 0, end_s: 60, state: SleepState::OutOfBed },
+            SleepPeriod { start_s: 60, end_s: 120, state: SleepState::Awake },
+            SleepPeriod { start_s: 120, end_s: 1020, state: SleepState::Asleep },
+        ]);
+
+        // Stillness shorter than the onset is awake; without presence sensors, all is in bed
+        let short: Vec<SleepData> = (0..300).step_by(10).map(|t| SleepData::builder(t).build()).collect();
+        assert_eq!(detect(&short), vec![SleepPeriod { start_s: 0, end_s: 300, state: SleepState::Awake }]);
+        assert!(detect(&[]).is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+//! Sound classification of the live audio by an ONNX model, e.g. into snoring, breathing, speech,
+//! and noise. Requires the `ml` feature.
+//!
+//! The audio is measured in frames of [`FRAME_S`] seconds, by level and by frequency band (see
+//! [`BANDS`]), unweighted and in dBFS. [`WINDOW_FRAMES`] consecutive frames make a window, the
+//! model's input: a tensor of shape `[1, WINDOW_FRAMES, FEATURES]` holding the level and the band
+//! levels of each frame, in that order. The model outputs a score per label, of shape
+//! `[1, labels]` (e.g. softmax probabilities); a window's class is the top label, if its score
+//! reaches the configured `min_score`.
+//!
+//! When the class of a recording changes, an event is stored in the `events` dataset with the
+//! category [`SOUND_CLASS_CATEGORY`] and the label as text, at the start of the window.
+
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tract_onnx::prelude::*;
+
+use crate::audio_analysis::{BandMeter, LevelMeter, BANDS};
+use crate::config::ClassifierConfig;
+use crate::data::Event;
+
+/// Length of the frames the audio is measured in, in seconds.
+pub const FRAME_S: f64 = 0.1;
+/// Frames per classified window.
+pub const WINDOW_FRAMES: usize = 50;
+/// Features of a frame: its level, then the levels of the [`BANDS`].
+pub const FEATURES: usize = 1 + BANDS.len();
+/// Level (dBFS) the features are clamped to, as digital silence has none.
+pub const FLOOR_DB: f32 = -120.0;
+/// Category of the classification events in the `events` dataset.
+pub const SOUND_CLASS_CATEGORY: &str = "sound_class";
+
+/// An ONNX model classifying windows of audio features (see the [module documentation](self)).
+#[derive(Debug)]
+pub struct SoundClassifier {
+    model: TypedRunnableModel<TypedModel>,
+    labels: Vec<String>,
+    min_score: f32,
+}
+
+impl SoundClassifier {
+    /// Loads the model of `config`, checking that it classifies a window into its labels.
+    pub fn load(config: &ClassifierConfig) -> Result<Self, Box<dyn Error>> {
+        let model = tract_onnx::onnx()
+            .model_for_path(&config.model)
+            .map_err(|e| format!("Failed to load the sound classifier {}: {e}", config.model))?;
+        Self::from_model(model, config.labels.clone(), config.min_score)
+    }
+
+    fn from_model(model: InferenceModel, labels: Vec<String>, min_score: f32) -> Result<Self, Box<dyn Error>> {
+        let model = model
+            .with_input_fact(0, f32::fact([1, WINDOW_FRAMES, FEATURES]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+        let classifier = Self { model, labels, min_score };
+        let scores = classifier.scores(&[[FLOOR_DB; FEATURES]; WINDOW_FRAMES])?;
+        if scores.len() != classifier.labels.len() {
+            return Err(format!(
+                "The sound classifier outputs {} scores for {} labels",
+                scores.len(),
+                classifier.labels.len()
+            ).into());
+        }
+        Ok(classifier)
+    }
+
+    /// Labels of the model's outputs.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Scores of the labels for a window of [`WINDOW_FRAMES`] frames.
+    pub fn scores(&self, window: &[[f32; FEATURES]]) -> Result<Vec<f32>, Box<dyn Error>> {
+        let features: Vec<f32> = window.iter().flatten().copied().collect();
+        let input = Tensor::from_shape(&[1, window.len(), FEATURES], &features)?;
+        let outputs = self.model.run(tvec!(input.into()))?;
+        Ok(outputs[0].as_slice::<f32>()?.to_vec())
+    }
+
+    /// Top label of a window of [`WINDOW_FRAMES`] frames and its score, or `None` if the score is
+    /// below `min_score`.
+    pub fn classify(&self, window: &[[f32; FEATURES]]) -> Result<Option<(&str, f32)>, Box<dyn Error>> {
+        let scores = self.scores(window)?;
+        let top = scores.into_iter().enumerate().max_by(|(_, a), (_, b)| a.total_cmp(b));
+        Ok(top.filter(|&(_, score)| score >= self.min_score).map(|(index, score)| (self.labels[index].as_str(), score)))
+    }
+}
+
+/// A change of the class of a recording, found by [`RecordingClassifier`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoundClassification {
+    /// Start of the first window of the class, in seconds since UNIX epoch.
+    pub timestamp_s: u64,
+    pub label: String,
+    /// Score of the label for the window.
+    pub score: f32,
+}
+
+impl SoundClassification {
+    /// The classification as stored in the `events` dataset.
+    pub fn to_event(&self) -> Event {
+        Event {
+            timestamp_s: self.timestamp_s,
+            category: SOUND_CLASS_CATEGORY.to_string(),
+            text: self.label.clone(),
+        }
+    }
+}
+
+/// Incremental classifier of the windows of a recording, reporting the changes of its class.
+#[derive(Clone, Debug)]
+pub struct RecordingClassifier {
+    classifier: Arc<SoundClassifier>,
+    start_time_s: u64,
+    level_meter: LevelMeter,
+    band_meter: BandMeter,
+    /// Frames of the window in progress.
+    window: Vec<[f32; FEATURES]>,
+    /// Windows completed so far.
+    windows: u64,
+    /// Class of the last classified window.
+    label: Option<String>,
+}
+
+impl RecordingClassifier {
+    /// Creates a classifier for the recording started at `start_time_s`, captured at
+    /// `sample_rate` Hz.
+    pub fn new(classifier: Arc<SoundClassifier>, start_time_s: u64, sample_rate: u32) -> Self {
+        let frame = Duration::from_secs_f64(FRAME_S);
+        Self {
+            classifier,
+            start_time_s,
+            level_meter: LevelMeter::new(sample_rate, frame),
+            band_meter: BandMeter::new(sample_rate, frame),
+            window: Vec::with_capacity(WINDOW_FRAMES),
+            windows: 0,
+            label: None,
+        }
+    }
+
+    /// Start of the recording, in seconds since UNIX epoch.
+    pub fn start_time_s(&self) -> u64 {
+        self.start_time_s
+    }
+
+    /// Adds the next samples of the recording and returns the changes of class in the windows
+    /// completed by them.
+    pub fn push(&mut self, samples: &[i16]) -> Result<Vec<SoundClassification>, Box<dyn Error>> {
+        let levels = self.level_meter.push(samples);
+        let band_levels = self.band_meter.push(samples);
+        // Windows are counted before classifying them, so that a failure doesn't shift the next
+        let mut completed = Vec::new();
+        for (level_db, band_db) in levels.into_iter().zip(band_levels) {
+            let mut frame = [FLOOR_DB; FEATURES];
+            for (feature, db) in frame.iter_mut().zip(std::iter::once(level_db).chain(band_db)) {
+                *feature = db.max(FLOOR_DB);
+            }
+            self.window.push(frame);
+            if self.window.len() == WINDOW_FRAMES {
+                let start_s = self.start_time_s + (self.windows as f64 * WINDOW_FRAMES as f64 * FRAME_S) as u64;
+                completed.push((start_s, std::mem::replace(&mut self.window, Vec::with_capacity(WINDOW_FRAMES))));
+                self.windows += 1;
+            }
+        }
+        let mut changes = Vec::new();
+        for (timestamp_s, window) in completed {
+            let Some((label, score)) = self.classifier.classify(&window)? else { continue };
+            if self.label.as_deref() != Some(label) {
+                self.label = Some(label.to_string());
+                changes.push(SoundClassification { timestamp_s, label: label.to_string(), score });
+            }
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use tract_onnx::pb::{
+        attribute_proto::AttributeType, tensor_proto::DataType, tensor_shape_proto, type_proto, AttributeProto,
+        GraphProto, ModelProto, NodeProto, OperatorSetIdProto, TensorProto, TensorShapeProto, TypeProto,
+        ValueInfoProto,
+    };
+
+    fn value_info(name: &str, shape: &[i64]) -> ValueInfoProto {
+        let dims = shape.iter()
+            .map(|&dim| tensor_shape_proto::Dimension {
+                value: Some(tensor_shape_proto::dimension::Value::DimValue(dim)),
+                ..Default::default()
+            })
+            .collect();
+        ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(TypeProto {
+                value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                    elem_type: DataType::Float as i32,
+                    shape: Some(TensorShapeProto { dim: dims }),
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn node(op_type: &str, inputs: &[&str], output: &str, attribute: Vec<AttributeProto>) -> NodeProto {
+        NodeProto {
+            op_type: op_type.to_string(),
+            input: inputs.iter().map(|input| input.to_string()).collect(),
+            output: vec![output.to_string()],
+            attribute,
+            ..Default::default()
+        }
+    }
+
+    /// Model scoring two labels by the mean level of the window: "quiet" below -40 dBFS, "loud"
+    /// above.
+    fn loudness_model() -> InferenceModel {
+        // Mean of the features over the frames, then scores of (-40 - level, level + 40) per label
+        let mut weights = vec![0.0f32; FEATURES * 2];
+        weights[0] = -1.0;
+        weights[1] = 1.0;
+        let axes = AttributeProto {
+            name: "axes".to_string(),
+            r#type: AttributeType::Ints as i32,
+            ints: vec![1],
+            ..Default::default()
+        };
+        let keepdims = AttributeProto {
+            name: "keepdims".to_string(),
+            r#type: AttributeType::Int as i32,
+            i: 0,
+            ..Default::default()
+        };
+        let graph = GraphProto {
+            node: vec![
+                node("ReduceMean", &["features"], "mean", vec![axes, keepdims]),
+                node("MatMul", &["mean", "weights"], "product", vec![]),
+                node("Add", &["product", "bias"], "logits", vec![]),
+                node("Softmax", &["logits"], "scores", vec![]),
+            ],
+            initializer: vec![
+                TensorProto {
+                    name: "weights".to_string(),
+                    dims: vec![FEATURES as i64, 2],
+                    data_type: DataType::Float as i32,
+                    float_data: weights,
+                    ..Default::default()
+                },
+                TensorProto {
+                    name: "bias".to_string(),
+                    dims: vec![2],
+                    data_type: DataType::Float as i32,
+                    float_data: vec![-40.0, 40.0],
+                    ..Default::default()
+                },
+            ],
+            input: vec![value_info("features", &[1, WINDOW_FRAMES as i64, FEATURES as i64])],
+            output: vec![value_info("scores", &[1, 2])],
+            ..Default::default()
+        };
+        let proto = ModelProto {
+            ir_version: 7,
+            opset_import: vec![OperatorSetIdProto { domain: String::new(), version: 13 }],
+            graph: Some(graph),
+            ..Default::default()
+        };
+        tract_onnx::onnx().model_for_proto_model(&proto).expect("Failed to build model")
+    }
+
+    fn classifier(labels: &[&str]) -> Result<SoundClassifier, Box<dyn Error>> {
+        SoundClassifier::from_model(loudness_model(), labels.iter().map(|label| label.to_string()).collect(), 0.9)
+    }
+
+    // A recording changing from quiet to loud to quiet is reported at each change, once.
+    #[test]
+    fn test_classify_recording() {
+        let classifier = Arc::new(classifier(&["quiet", "loud"]).expect("Failed to load classifier"));
+        let mut recording = RecordingClassifier::new(classifier, 1_000, 16_000);
+        let window_len = (16_000.0 * FRAME_S) as usize * WINDOW_FRAMES;
+        let mut audio = vec![30i16; window_len * 2];
+        audio.extend((0..window_len).map(|i| if i % 2 == 0 { 10_000i16 } else { -10_000 }));
+        audio.extend(vec![0i16; window_len]);
+
+        let changes: Vec<SoundClassification> = audio.chunks(4_000)
+            .flat_map(|chunk| recording.push(chunk).expect("Failed to classify"))
+            .collect();
+        let found: Vec<(u64, &str)> = changes.iter().map(|change| (change.timestamp_s, change.label.as_str())).collect();
+        assert_eq!(found, [(1_000, "quiet"), (1_010, "loud"), (1_015, "quiet")], "{:?}", changes);
+        assert!(changes[1].score > 0.99);
+        assert_eq!(changes[1].to_event().category, SOUND_CLASS_CATEGORY);
+    }
+
+    // A model whose outputs don't match the labels is rejected when loaded.
+    #[test]
+    fn test_labels_must_match_outputs() {
+        assert!(classifier(&["snore", "breathing", "speech", "noise"]).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Sound events detected in the audio recordings: coughs, speech, alarms, and dog barks.
+//!
+//! The audio is measured in frames of [`FRAME_S`] seconds, by level and by frequency band (see
+//! [`BANDS`]). A slowly adapting background level follows the room's noise; an event starts when
+//! a frame is [`ONSET_DB`] louder than the background, and ends once the frames have been back
+//! near the background for [`GAP_S`], so that syllables or beeps close together make one event.
+//!
+//! Events are classified by their length, how steady their level is, and which band holds their
+//! energy (see [`SoundClass`]). The classes are coarse guesses from these few features, meant to
+//! tell what woke the sleeper rather than to recognize sounds reliably; loud sounds that fit none
+//! of them are [`SoundClass::Noise`].
+//!
+//! Events are stored in the `events` dataset with the category [`SOUND_EVENT_CATEGORY`] and the
+//! class label as text, by the live analysis while recording and otherwise by
+//! [`analyze_audio_entries`](crate::audio_analysis::analyze_audio_entries).
+
+use std::time::Duration;
+
+use crate::audio_analysis::{BandMeter, LevelMeter, BANDS};
+use crate::data::Event;
+
+/// Length of the frames the audio is measured in, in seconds.
+pub const FRAME_S: f64 = 0.05;
+/// How much louder than the background (dB) a frame starts an event.
+pub const ONSET_DB: f32 = 12.0;
+/// Level (dBFS) below which no event starts, however quiet the background.
+pub const MIN_LEVEL_DB: f32 = -60.0;
+/// How long the frames must be back near the background to end an event, in seconds.
+pub const GAP_S: f64 = 0.3;
+/// Longest event, in seconds. A sound lasting longer becomes the new background.
+pub const MAX_EVENT_S: f64 = 30.0;
+/// Category of the sound events in the `events` dataset.
+pub const SOUND_EVENT_CATEGORY: &str = "sound";
+
+/// How far below the onset level (dB) a frame still continues an event.
+const HYSTERESIS_DB: f32 = 3.0;
+/// Time constant of the background level, in seconds.
+const BACKGROUND_S: f64 = 30.0;
+
+/// Coarse class of a [`SoundEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundClass {
+    /// Short broadband burst (under a second, with high-frequency energy).
+    Cough,
+    /// A second or more of fluctuating sound, mostly in the speech band.
+    Speech,
+    /// Two seconds or more of steady, tonal sound (one band well above the others) in the speech
+    /// or high band.
+    Alarm,
+    /// Short voiced burst (under a second, in the speech band with little high-frequency energy).
+    DogBark,
+    /// Any other loud sound.
+    Noise,
+}
+
+impl SoundClass {
+    /// Label of the class, the text of its events in the `events` dataset.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SoundClass::Cough => "cough",
+            SoundClass::Speech => "speech",
+            SoundClass::Alarm => "alarm",
+            SoundClass::DogBark => "dog_bark",
+            SoundClass::Noise => "noise",
+        }
+    }
+
+    /// Classifies an event lasting `duration_s`, whose frame levels have the standard deviation
+    /// `level_std_db`, and with the mean levels `band_db` in the [`BANDS`].
+    fn classify(duration_s: f64, level_std_db: f32, band_db: [f32; BANDS.len()]) -> Self {
+        let [low, speech, high] = band_db;
+        // Tones put their energy in one band, noise spreads it over the wide speech and high bands
+        let tonal = speech.max(high) > speech.min(high) + 6.0 && speech.max(high) > low + 6.0;
+        if duration_s >= 2.0 && level_std_db < 3.0 && tonal {
+            SoundClass::Alarm
+        } else if duration_s < 1.0 && high > speech - 10.0 && high.max(speech) > low {
+            SoundClass::Cough
+        } else if duration_s < 1.0 && speech > high + 10.0 && speech > low {
+            SoundClass::DogBark
+        } else if duration_s >= 1.0 && speech > low && speech > high {
+            SoundClass::Speech
+        } else {
+            SoundClass::Noise
+        }
+    }
+}
+
+/// A sound detected by [`SoundEventDetector`].
+///
+/// # Example
+///
+/// ```
+/// use sleep_recorder::{SoundClass, SoundEvent};
+/// let event = SoundEvent { timestamp_s: 1_745_873_251, duration_s: 0.4, peak_db: -12.0, class: SoundClass::Cough };
+/// let stored = event.to_event();
+/// assert_eq!((stored.timestamp_s, stored.text.as_str()), (1_745_873_251, "cough"));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundEvent {
+    /// Start of the event, in seconds since UNIX epoch.
+    pub timestamp_s: u64,
+    /// Length of the event, in seconds.
+    pub duration_s: f32,
+    /// Level of the event's loudest frame, in dBFS.
+    pub peak_db: f32,
+    pub class: SoundClass,
+}
+
+impl SoundEvent {
+    /// The event as stored in the `events` dataset.
+    pub fn to_event(self) -> Event {
+        Event {
+            timestamp_s: self.timestamp_s,
+            category: SOUND_EVENT_CATEGORY.to_string(),
+            text: self.class.label().to_string(),
+        }
+    }
+}
+
+/// Incremental detector of the [`SoundEvent`]s of a recording (see the
+/// [module documentation](self)).
+#[derive(Clone, Debug)]
+pub struct SoundEventDetector {
+    start_time_s: u64,
+    level_meter: LevelMeter,
+    band_meter: BandMeter,
+    /// Frames measured so far.
+    frames: u64,
+    /// Level of the background in dBFS, from the first frame on.
+    background_db: Option<f32>,
+    /// Event in progress.
+    current: Option<EventStats>,
+}
+
+impl SoundEventDetector {
+    /// Creates a detector for the recording started at `start_time_s`, captured at
+    /// `sample_rate` Hz.
+    pub fn new(start_time_s: u64, sample_rate: u32) -> Self {
+        let frame = Duration::from_secs_f64(FRAME_S);
+        Self {
+            start_time_s,
+            level_meter: LevelMeter::new(sample_rate, frame),
+            band_meter: BandMeter::new(sample_rate, frame),
+            frames: 0,
+            background_db: None,
+            current: None,
+        }
+    }
+
+    /// Adds the next samples of the recording and returns the events ended by them.
+    pub fn push(&mut self, samples: &[i16]) -> Vec<SoundEvent> {
+        let levels = self.level_meter.push(samples);
+        let band_levels = self.band_meter.push(samples);
+        levels.into_iter()
+            .zip(band_levels)
+            .filter_map(|(level_db, band_db)| self.add_frame(level_db, band_db))
+            .collect()
+    }
+
+    /// Ends the recording, returning the event in progress, if any.
+    pub fn finish(mut self) -> Option<SoundEvent> {
+        self.current.take().map(|stats| stats.event(self.start_time_s))
+    }
+
+    fn add_frame(&mut self, level_db: f32, band_db: [f32; BANDS.len()]) -> Option<SoundEvent> {
+        let frame = self.frames;
+        self.frames += 1;
+        // Digital silence has no level to compare with
+        let level_db = level_db.max(-120.0);
+        let background_db = *self.background_db.get_or_insert(level_db);
+        let Some(stats) = &mut self.current else {
+            if level_db > (background_db + ONSET_DB).max(MIN_LEVEL_DB) {
+                self.current = Some(EventStats::new(frame, level_db, band_db));
+            } else {
+                let alpha = (FRAME_S / BACKGROUND_S) as f32;
+                self.background_db = Some(background_db + alpha * (level_db - background_db));
+            }
+            return None;
+        };
+        if level_db > background_db + ONSET_DB - HYSTERESIS_DB {
+            stats.add(frame, level_db, band_db);
+        }
+        let quiet_s = (frame - stats.last_frame) as f64 * FRAME_S;
+        let duration_s = (frame - stats.first_frame + 1) as f64 * FRAME_S;
+        if quiet_s >= GAP_S {
+            self.current.take().map(|stats| stats.event(self.start_time_s))
+        } else if duration_s >= MAX_EVENT_S {
+            // A lasting sound, e.g. a fan switched on, is the room's noise from now on
+            self.background_db = Some(stats.mean_level_db());
+            self.current.take().map(|stats| stats.event(self.start_time_s))
+        } else {
+            None
+        }
+    }
+}
+
+/// Statistics of the loud frames of an event in progress.
+#[derive(Clone, Debug)]
+struct EventStats {
+    first_frame: u64,
+    last_frame: u64,
+    frames: usize,
+    level_sum: f64,
+    level_sum_squares: f64,
+    peak_db: f32,
+    /// Sum of the band powers (relative to full scale).
+    band_power: [f64; BANDS.len()],
+}
+
+impl EventStats {
+    fn new(frame: u64, level_db: f32, band_db: [f32; BANDS.len()]) -> Self {
+        let mut stats = Self {
+            first_frame: frame,
+            last_frame: frame,
+            frames: 0,
+            level_sum: 0.0,
+            level_sum_squares: 0.0,
+            peak_db: level_db,
+            band_power: [0.0; BANDS.len()],
+        };
+        stats.add(frame, level_db, band_db);
+        stats
+    }
+
+    fn add(&mut self, frame: u64, level_db: f32, band_db: [f32; BANDS.len()]) {
+        self.last_frame = frame;
+        self.frames += 1;
+        self.level_sum += level_db as f64;
+        self.level_sum_squares += (level_db as f64).powi(2);
+        self.peak_db = self.peak_db.max(level_db);
+        for (power, db) in self.band_power.iter_mut().zip(band_db) {
+            *power += 10f64.powf(db as f64 / 10.0);
+        }
+    }
+
+    fn mean_level_db(&self) -> f32 {
+        (self.level_sum / self.frames as f64) as f32
+    }
+
+    fn event(&self, start_time_s: u64) -> SoundEvent {
+        let duration_s = (self.last_frame - self.first_frame + 1) as f64 * FRAME_S;
+        let mean = self.level_sum / self.frames as f64;
+        let level_std_db = (self.level_sum_squares / self.frames as f64 - mean * mean).max(0.0).sqrt() as f32;
+        let band_db = self.band_power.map(|power| (10.0 * (power / self.frames as f64).log10()) as f32);
+        SoundEvent {
+            timestamp_s: start_time_s + (self.first_frame as f64 * FRAME_S) as u64,
+            duration_s: duration_s as f32,
+            peak_db: self.peak_db,
+            class: SoundClass::classify(duration_s, level_std_db, band_db),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+    use test_log::test;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// Deterministic white noise with amplitude `amplitude`.
+    fn noise(seconds: f32, amplitude: f32, seed: &mut u32) -> Vec<i16> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|_| {
+                *seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                ((*seed >> 16) as f32 / 32_768.0 - 1.0) * amplitude
+            })
+            .map(|s| s as i16)
+            .collect()
+    }
+
+    /// Sum of the `harmonics` of `fundamental_hz`, with its level modulated by `envelope`.
+    fn harmonics(seconds: f32, fundamental_hz: f32, harmonics: usize, envelope: impl Fn(f32) -> f32) -> Vec<i16> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let sum: f32 = (1..=harmonics).map(|h| (TAU * fundamental_hz * h as f32 * t).sin()).sum();
+                (sum / harmonics as f32 * 12_000.0 * envelope(t)) as i16
+            })
+            .collect()
+    }
+
+    fn mix(background: &mut [i16], sound: &[i16], at_s: f32) {
+        let start = (at_s * SAMPLE_RATE as f32) as usize;
+        for (sample, s) in background[start..].iter_mut().zip(sound) {
+            *sample = sample.saturating_add(*s);
+        }
+    }
+
+    // Each kind of sound, well apart in a quiet room, is detected once with its class.
+    #[test]
+    fn test_detect_sound_classes() {
+        let mut seed = 1;
+        let mut audio = noise(30.0, 30.0, &mut seed);
+        mix(&mut audio, &noise(0.4, 20_000.0, &mut seed), 5.0);
+        mix(&mut audio, &harmonics(0.3, 600.0, 3, |_| 1.0), 9.0);
+        // Syllables at 4 Hz
+        mix(&mut audio, &harmonics(2.0, 300.0, 10, |t| (TAU * 2.0 * t).sin().abs()), 13.0);
+        mix(&mut audio, &harmonics(3.0, 3_000.0, 1, |_| 1.0), 20.0);
+
+        let mut detector = SoundEventDetector::new(1_000, SAMPLE_RATE);
+        let mut events: Vec<SoundEvent> = audio.chunks(4_800).flat_map(|chunk| detector.push(chunk)).collect();
+        events.extend(detector.finish());
+
+        let found: Vec<(u64, SoundClass)> = events.iter().map(|event| (event.timestamp_s, event.class)).collect();
+        assert_eq!(found, [
+            (1_005, SoundClass::Cough),
+            (1_009, SoundClass::DogBark),
+            (1_013, SoundClass::Speech),
+            (1_020, SoundClass::Alarm),
+        ], "{:?}", events);
+        assert!((events[3].duration_s - 3.0).abs() < 0.2, "{:?}", events[3]);
+        assert_eq!(events[0].to_event().text, "cough");
+    }
+
+    // A sound that doesn't stop becomes the background instead of one endless event.
+    #[test]
+    fn test_lasting_sound_becomes_background() {
+        let mut seed = 1;
+        let mut audio = noise(5.0, 30.0, &mut seed);
+        audio.extend(noise(70.0, 3_000.0, &mut seed));
+        let mut detector = SoundEventDetector::new(0, SAMPLE_RATE);
+        let mut events = detector.push(&audio);
+        events.extend(detector.finish());
+        assert_eq!(events.len(), 1, "{:?}", events);
+        assert_eq!((events[0].timestamp_s, events[0].class), (5, SoundClass::Noise));
+    }
+}
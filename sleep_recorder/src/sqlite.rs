@@ -0,0 +1,852 @@
+//! SQLite storage backend ([`StorageFormat::Sqlite`](crate::config::StorageFormat::Sqlite)).
+//!
+//! All sessions share one database, which is easier to query ad-hoc than the HDF5 file and needs
+//! no native library (SQLite is compiled in). Every table has a `session` column naming the
+//! session, which is listed in the `sessions` table:
+//!
+//! - `session_metadata`: the [`SessionMetadata`] as `key`/`value` rows, named like the fields,
+//!   with a `sensor` row per sensor.
+//! - `samples`: one row per sample, with a column per field of [`SleepData`] named like its HDF5
+//!   dataset (`timestamp`, `temperature`, ...). `NAN` values are stored as `NULL`.
+//! - `camera_samples`, `probe_temps`, `thermistor_temps`, `sensor_status`: one row per sample and
+//!   registered camera, probe, thermistor, or sensor, keyed by `timestamp` and name.
+//! - `piezo_bursts`: the piezo BCG bursts as little-endian `f32` blobs, keyed by `timestamp`.
+//! - `audio`, `live_audio_levels`, `actuator_events`, `events`: audio recordings, live audio
+//!   levels, actuator state changes, and annotations of the session.
+//! - `audio_rms`: the RMS windows of the audio recordings, keyed by the recording's
+//!   `start_time_s` and the window's `timestamp`.
+//! - `audio_bands`: the live-analyzed levels of the audio frequency bands, one row per window
+//!   `timestamp` and `band` (see [`BANDS`]).
+//! - `purged_media`: when the images or audio of a session were deleted by
+//!   [`retention::prune`](crate::retention::prune), keyed by `media` ("images" or "audio").
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::Local;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection};
+use tracing::{info, warn};
+
+use crate::audio_analysis::BANDS;
+use crate::data::{
+    sensor_status_key, session_start, sleep_fields, ActuatorEvent, AudioRecording, Event, SensorState, SessionMetadata,
+    SleepData, SleepField,
+};
+use crate::retention::Media;
+use crate::storage::journal::Journal;
+use crate::storage::{clamp_buffering, StorageBackend, DEFAULT_FLUSH_EVERY, DEFAULT_MAX_BUFFER_KIB};
+
+/// The tables. The columns of `samples` other than `session` are added from [`sleep_fields`].
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (name TEXT PRIMARY KEY);
+    CREATE TABLE IF NOT EXISTS session_metadata (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        key TEXT NOT NULL,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS samples (session TEXT NOT NULL REFERENCES sessions(name));
+    CREATE TABLE IF NOT EXISTS camera_samples (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        camera TEXT NOT NULL,
+        image_path TEXT NOT NULL,
+        motion REAL,
+        clip_path TEXT NOT NULL,
+        raw_image_path TEXT NOT NULL,
+        thumbnail BLOB NOT NULL DEFAULT x''
+    );
+    CREATE TABLE IF NOT EXISTS probe_temps (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        probe TEXT NOT NULL,
+        temperature_c REAL
+    );
+    CREATE TABLE IF NOT EXISTS thermistor_temps (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        thermistor TEXT NOT NULL,
+        temperature_c REAL
+    );
+    CREATE TABLE IF NOT EXISTS sensor_status (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        sensor TEXT NOT NULL,
+        ok INTEGER NOT NULL,
+        state INTEGER NOT NULL,
+        error TEXT NOT NULL,
+        last_error_age_s REAL
+    );
+    CREATE TABLE IF NOT EXISTS piezo_bursts (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        samples_mv BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS audio (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        start_time_s INTEGER NOT NULL,
+        duration_s INTEGER NOT NULL,
+        path TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS audio_rms (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        start_time_s INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        rms_db REAL
+    );
+    CREATE TABLE IF NOT EXISTS audio_bands (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        band TEXT NOT NULL,
+        level_db REAL
+    );
+    CREATE TABLE IF NOT EXISTS live_audio_levels (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        rms_db REAL
+    );
+    CREATE TABLE IF NOT EXISTS actuator_events (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        \"on\" INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        timestamp INTEGER NOT NULL,
+        category TEXT NOT NULL,
+        text TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS purged_media (
+        session TEXT NOT NULL REFERENCES sessions(name),
+        media TEXT NOT NULL,
+        purged_s INTEGER NOT NULL,
+        PRIMARY KEY (session, media)
+    );
+";
+
+/// Logger writing sleep data to an SQLite database, with the same buffering as
+/// [`SleepDataLogger`](crate::data::SleepDataLogger): samples are written in one transaction every
+/// `flush_every` samples (or `max_buffer_kib` KiB of them), and the rest when the logger is dropped.
+#[derive(Debug)]
+pub struct SqliteLogger {
+    /// Buffer for storing sleep data entries before flushing to the database.
+    buffer: Vec<SleepData>,
+    /// Number of entries to buffer before flushing to the database.
+    flush_every: usize,
+    /// Memory the buffered entries may take before they are flushed, in bytes.
+    max_buffer_bytes: usize,
+    /// Memory taken by the buffered entries, in bytes.
+    buffer_bytes: usize,
+    /// Database connection.
+    connection: Connection,
+    /// Name of this session in the `session` columns.
+    pub session_name: String,
+    /// Map of column names to their corresponding SleepField functions.
+    data_map: HashMap<&'static str, SleepField>,
+    /// Names of the additional cameras registered with `register_camera`.
+    camera_names: Vec<String>,
+    /// Names of the temperature probes registered with `register_probe`.
+    probe_names: Vec<String>,
+    /// Names of the additional thermistors registered with `register_thermistor`.
+    thermistor_names: Vec<String>,
+    /// Names of the sensors registered with `register_sensor`.
+    sensor_names: Vec<String>,
+    /// Live audio levels (timestamp, dBFS) waiting to be flushed.
+    audio_levels: Vec<(u64, f32)>,
+    /// Live band levels (timestamp, dBFS per band) waiting to be flushed.
+    audio_bands: Vec<(u64, [f32; BANDS.len()])>,
+    /// Whether samples have been written, after which no more names can be registered.
+    has_samples: bool,
+    /// Whether this logger continues an existing session (see `resume`).
+    resumed: bool,
+    /// Write-ahead journal of the buffered samples, if enabled with `with_journal`.
+    journal: Option<Journal>,
+}
+
+impl Drop for SqliteLogger {
+    fn drop(&mut self) {
+        info!("Data logging ended, flushing final data.");
+        match self.flush() {
+            Ok(()) => {
+                if let Some(Err(e)) = self.journal.take().map(Journal::remove) {
+                    warn!("Failed to remove journal: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to flush data on drop: {}", e),
+        }
+    }
+}
+
+impl SqliteLogger {
+    /// Opens (or creates) the database at `data_path/file_name` and starts a new session named
+    /// after the current time. Buffers [`DEFAULT_FLUSH_EVERY`] samples.
+    pub fn new(data_path: &str, file_name: &str) -> Result<Self, Box<dyn Error>> {
+        let connection = Self::open_database(data_path, file_name)?;
+        let session_name = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        connection.execute("INSERT INTO sessions (name) VALUES (?1)", [&session_name])
+            .map_err(|e| format!("Failed to create session {}: {}", session_name, e))?;
+        info!("SQLite database ({file_name}) and session ({session_name}) created successfully at {data_path}.");
+        Ok(Self::with_session(connection, session_name, false))
+    }
+
+    /// Reopens the session `session_name` in the database at `data_path/file_name` to continue
+    /// appending to it, e.g. after the recorder restarted during the night.
+    pub fn resume(data_path: &str, file_name: &str, session_name: &str) -> Result<Self, Box<dyn Error>> {
+        let connection = Self::open_database(data_path, file_name)?;
+        let exists: bool = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sessions WHERE name = ?1)", [session_name], |row| row.get(0))?;
+        if !exists {
+            return Err(format!("Session {} not found in {}", session_name, file_name).into());
+        }
+        info!("Resuming session ({session_name}) of SQLite database ({file_name}) at {data_path}.");
+        Ok(Self::with_session(connection, session_name.to_string(), true))
+    }
+
+    /// The most recent session in the database at `data_path/file_name`, with the time of its
+    /// last sample (or, if it has none, of its start) in seconds since UNIX epoch. `None` if the
+    /// database doesn't exist or has no sessions.
+    pub fn last_session(data_path: &str, file_name: &str) -> Result<Option<(String, u64)>, Box<dyn Error>> {
+        if !std::path::Path::new(&format!("{}/{}", data_path, file_name)).exists() {
+            return Ok(None);
+        }
+        let connection = Self::open_database(data_path, file_name)?;
+        // Session names are start times, which sort chronologically
+        let session_name: Option<String> = connection.query_row(
+            "SELECT MAX(name) FROM sessions", [], |row| row.get(0))?;
+        let Some(session_name) = session_name else {
+            return Ok(None);
+        };
+        let last_sample: Option<i64> = connection.query_row(
+            "SELECT MAX(timestamp) FROM samples WHERE session = ?1", [&session_name], |row| row.get(0))?;
+        let last = match last_sample {
+            Some(timestamp) => timestamp as u64,
+            None => session_start(&session_name).unwrap_or_default(),
+        };
+        Ok(Some((session_name, last)))
+    }
+
+    /// Records in the `purged_media` table that the `media` files of the session `session_name`
+    /// in the database at `data_path/file_name` were deleted at `purged_s`.
+    pub fn mark_purged(data_path: &str, file_name: &str, session_name: &str, media: Media, purged_s: u64) -> Result<(), Box<dyn Error>> {
+        let connection = Self::open_database(data_path, file_name)?;
+        let exists: bool = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sessions WHERE name = ?1)", [session_name], |row| row.get(0))?;
+        if !exists {
+            return Err(format!("Session {} not found in {}", session_name, file_name).into());
+        }
+        connection.execute(
+            "INSERT OR REPLACE INTO purged_media (session, media, purged_s) VALUES (?1, ?2, ?3)",
+            params![session_name, media.name(), purged_s as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Whether this logger continues an existing session.
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Buffers up to `flush_every` samples and `max_buffer_kib` KiB of them, like
+    /// [`SleepDataLogger::with_buffering`](crate::data::SleepDataLogger::with_buffering).
+    pub fn with_buffering(mut self, flush_every: usize, max_buffer_kib: usize) -> Self {
+        (self.flush_every, self.max_buffer_bytes) = clamp_buffering(flush_every, max_buffer_kib);
+        self
+    }
+
+    /// Journals each buffered sample to the file at `path` until it is flushed, like
+    /// [`SleepDataLogger::with_journal`](crate::data::SleepDataLogger::with_journal).
+    pub(crate) fn with_journal(mut self, path: impl Into<std::path::PathBuf>) -> Result<Self, Box<dyn Error>> {
+        self.journal = Some(Journal::create(path, &self.session_name)?);
+        Ok(self)
+    }
+
+    /// Opens (or creates) the database, adding columns for fields added since the database was
+    /// created to the `samples` and `camera_samples` tables.
+    fn open_database(data_path: &str, file_name: &str) -> Result<Connection, Box<dyn Error>> {
+        let connection = Connection::open(data_path.to_string() + "/" + file_name)?;
+        connection.execute_batch(SCHEMA)?;
+
+        let mut existing_columns = Vec::new();
+        {
+            let mut statement = connection.prepare("SELECT name FROM pragma_table_info('samples')")?;
+            for column in statement.query_map([], |row| row.get::<_, String>(0))? {
+                existing_columns.push(column?);
+            }
+        }
+        for (key, sleep_field) in sleep_fields().iter() {
+            if existing_columns.iter().any(|c| c == key) {
+                continue;
+            }
+            connection.execute(&format!("ALTER TABLE samples ADD COLUMN \"{key}\" {}", column_type(sleep_field)), [])?;
+        }
+        let has_thumbnails: bool = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info('camera_samples') WHERE name = 'thumbnail')", [], |row| row.get(0))?;
+        if !has_thumbnails {
+            connection.execute("ALTER TABLE camera_samples ADD COLUMN thumbnail BLOB NOT NULL DEFAULT x''", [])?;
+        }
+        connection.execute("CREATE INDEX IF NOT EXISTS samples_session_timestamp ON samples (session, timestamp)", [])?;
+        Ok(connection)
+    }
+
+    fn with_session(connection: Connection, session_name: String, resumed: bool) -> Self {
+        Self {
+            buffer: Vec::new(),
+            flush_every: DEFAULT_FLUSH_EVERY,
+            max_buffer_bytes: DEFAULT_MAX_BUFFER_KIB * 1024,
+            buffer_bytes: 0,
+            connection,
+            session_name,
+            data_map: sleep_fields(),
+            camera_names: Vec::new(),
+            probe_names: Vec::new(),
+            thermistor_names: Vec::new(),
+            sensor_names: Vec::new(),
+            audio_levels: Vec::new(),
+            audio_bands: Vec::new(),
+            has_samples: false,
+            resumed,
+            journal: None,
+        }
+    }
+
+    /// Stores the session's metadata in the `session_metadata` table. Metadata can only be
+    /// written once.
+    pub fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+        let written: i64 = self.connection.query_row(
+            "SELECT COUNT(*) FROM session_metadata WHERE session = ?1", [&self.session_name], |row| row.get(0))?;
+        if written > 0 {
+            return Err(format!("Metadata of session {} was already written", self.session_name).into());
+        }
+        let transaction = self.connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO session_metadata (session, key, value) VALUES (?1, ?2, ?3)")?;
+            for (key, value) in metadata.string_fields() {
+                insert.execute(params![self.session_name, key, value])?;
+            }
+            for sensor in &metadata.sensors {
+                insert.execute(params![self.session_name, "sensor", sensor])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Registers a per-sample field of a sensor, adding its column to `samples` if the table has
+    /// none yet. The table has a column for every built-in field (see [`sleep_fields`]), which are
+    /// written in every session, `NULL` (or 0) when no sensor fills them in.
+    ///
+    /// Like cameras, fields must be registered before the first sample is flushed.
+    pub fn register_field(&mut self, name: &'static str, field: SleepField) -> Result<(), Box<dyn Error>> {
+        if self.data_map.contains_key(name) {
+            return Ok(());
+        }
+        if self.has_samples {
+            return Err(format!("{} registered after data was written", name).into());
+        }
+        let exists: bool = self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info('samples') WHERE name = ?1)", [name], |row| row.get(0))?;
+        if !exists {
+            self.connection.execute(&format!("ALTER TABLE samples ADD COLUMN \"{name}\" {}", column_type(&field)), [])?;
+        }
+        self.data_map.insert(name, field);
+        Ok(())
+    }
+
+    /// Registers an additional camera, stored in `camera_samples`. Samples without a result for
+    /// this camera are stored as empty paths and `NULL` motion.
+    ///
+    /// Cameras must be registered before the first sample is flushed.
+    pub fn register_camera(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        Self::register(&mut self.camera_names, "Camera", name, self.has_samples)
+    }
+
+    /// Registers a named temperature probe, stored in `probe_temps`. Samples without a reading
+    /// for this probe are stored as `NULL`.
+    ///
+    /// Like cameras, probes must be registered before the first sample is flushed.
+    pub fn register_probe(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        Self::register(&mut self.probe_names, "Probe", name, self.has_samples)
+    }
+
+    /// Registers an additional thermistor, stored in `thermistor_temps`. Samples without a
+    /// reading for this thermistor are stored as `NULL`.
+    ///
+    /// Like cameras, thermistors must be registered before the first sample is flushed.
+    pub fn register_thermistor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        Self::register(&mut self.thermistor_names, "Thermistor", name, self.has_samples)
+    }
+
+    /// Registers a sensor whose health is recorded with every sample in `sensor_status`, under
+    /// the key from [`sensor_status_key`]. The state is a [`SensorState`] as integer, the error
+    /// is empty when the measurement succeeded, and the error age is `NULL` until the sensor
+    /// first fails.
+    ///
+    /// Like cameras, sensors must be registered before the first sample is flushed.
+    pub fn register_sensor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let key = sensor_status_key(name);
+        if self.sensor_names.iter().any(|n| sensor_status_key(n) == key) {
+            return Err(format!("Sensor {} is already registered", name).into());
+        }
+        Self::register(&mut self.sensor_names, "Sensor", name, self.has_samples)
+    }
+
+    /// Adds `name` to `names`, checking that it is new and that no samples have been written yet.
+    fn register(names: &mut Vec<String>, kind: &str, name: &str, has_samples: bool) -> Result<(), Box<dyn Error>> {
+        if names.iter().any(|n| n == name) {
+            return Err(format!("{} {} is already registered", kind, name).into());
+        }
+        if has_samples {
+            return Err(format!("{} registered after data was written", name).into());
+        }
+        names.push(name.to_string());
+        Ok(())
+    }
+
+    /// Appends a new `SleepData` entry to the buffer.
+    /// If the buffer reaches the specified size, it flushes the data to the database.
+    #[tracing::instrument(skip(self, sample))]
+    pub fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+        info!("Pushing sample to buffer: {:?}", &sample);
+        if let Some(Err(e)) = self.journal.as_mut().map(|journal| journal.append(&sample)) {
+            warn!("Failed to journal sample: {}", e);
+        }
+        self.buffer_bytes += sample.buffered_size();
+        self.buffer.push(sample);
+        if self.buffer.len() >= self.flush_every || self.buffer_bytes >= self.max_buffer_bytes {
+            info!("Flushing data to SQLite database...");
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Inserts a new `AudioRecording` entry into the `audio` table.
+    #[tracing::instrument(skip(self))]
+    pub fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        self.connection.execute(
+            "INSERT INTO audio (session, start_time_s, duration_s, path) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.session_name,
+                audio_recording.start_time_s as i64,
+                audio_recording.duration.as_secs() as i64,
+                audio_recording.path,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Inserts RMS windows (dBFS of the windows starting at `rms_t_s`) of the recording started at
+    /// `start_time_s` into the `audio_rms` table.
+    pub fn append_audio_rms(&mut self, start_time_s: u64, rms_t_s: &[u64], rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+        if rms_t_s.len() != rms_db.len() {
+            return Err(format!("{} RMS window timestamps for {} levels", rms_t_s.len(), rms_db.len()).into());
+        }
+        let transaction = self.connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO audio_rms (session, start_time_s, timestamp, rms_db) VALUES (?1, ?2, ?3, ?4)")?;
+            for (timestamp_s, rms_db) in rms_t_s.iter().zip(rms_db) {
+                insert.execute(params![self.session_name, start_time_s as i64, *timestamp_s as i64, real(*rms_db)])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Inserts an actuator state change into the `actuator_events` table. Events are rare, so they
+    /// are written immediately rather than buffered.
+    pub fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        self.connection.execute(
+            "INSERT INTO actuator_events (session, timestamp, name, \"on\") VALUES (?1, ?2, ?3, ?4)",
+            params![self.session_name, event.timestamp_s as i64, event.name, event.on],
+        )?;
+        Ok(())
+    }
+
+    /// Inserts an annotation of the session into the `events` table, immediately like actuator
+    /// events.
+    pub fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.connection.execute(
+            "INSERT INTO events (session, timestamp, category, text) VALUES (?1, ?2, ?3, ?4)",
+            params![self.session_name, event.timestamp_s as i64, event.category, event.text],
+        )?;
+        Ok(())
+    }
+
+    /// Buffers a live audio level (RMS dBFS of the window starting at `timestamp_s`), written to the
+    /// `live_audio_levels` table on the next flush.
+    pub fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32) {
+        self.audio_levels.push((timestamp_s, rms_db));
+    }
+
+    /// Buffers the band levels of a live-analyzed audio window (dBFS in the order of [`BANDS`],
+    /// the window starting at `timestamp_s`), written to the `audio_bands` table on the next flush.
+    pub fn append_audio_bands(&mut self, timestamp_s: u64, levels_db: [f32; BANDS.len()]) {
+        self.audio_bands.push((timestamp_s, levels_db));
+    }
+
+    /// Writes the buffered data to the database in one transaction.
+    #[tracing::instrument(skip(self))]
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let audio_levels = std::mem::take(&mut self.audio_levels);
+        let audio_bands = std::mem::take(&mut self.audio_bands);
+        let buffer = std::mem::take(&mut self.buffer);
+        self.buffer_bytes = 0;
+        if audio_levels.is_empty() && audio_bands.is_empty() && buffer.is_empty() {
+            return Ok(());
+        }
+        let session = self.session_name.as_str();
+        let transaction = self.connection.transaction()?;
+
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO live_audio_levels (session, timestamp, rms_db) VALUES (?1, ?2, ?3)")?;
+            for (timestamp_s, rms_db) in &audio_levels {
+                insert.execute(params![session, *timestamp_s as i64, real(*rms_db)])?;
+            }
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO audio_bands (session, timestamp, band, level_db) VALUES (?1, ?2, ?3, ?4)")?;
+            for (timestamp_s, levels_db) in &audio_bands {
+                for (band, level_db) in BANDS.iter().zip(levels_db) {
+                    insert.execute(params![session, *timestamp_s as i64, band.name, real(*level_db)])?;
+                }
+            }
+        }
+
+        if !buffer.is_empty() {
+            let fields: Vec<(&&str, &SleepField)> = self.data_map.iter().collect();
+            let columns: Vec<String> = fields.iter().map(|(name, _)| format!("\"{name}\"")).collect();
+            let placeholders: Vec<String> = (1..=fields.len() + 1).map(|i| format!("?{i}")).collect();
+            let mut insert = transaction.prepare_cached(&format!(
+                "INSERT INTO samples (session, {}) VALUES ({})",
+                columns.join(", "),
+                placeholders.join(", "),
+            ))?;
+            for sample in &buffer {
+                let values = std::iter::once(Value::Text(session.to_string()))
+                    .chain(fields.iter().map(|(_, sleep_field)| match sleep_field {
+                        SleepField::Bool(f) => Value::Integer(f(sample) as i64),
+                        SleepField::U16(f) => Value::Integer(f(sample) as i64),
+                        SleepField::U64(f) => Value::Integer(f(sample) as i64),
+                        SleepField::F32(f) => real(f(sample)),
+                        SleepField::String(f) => Value::Text(f(sample).as_str().to_string()),
+                        SleepField::Bytes(f) => Value::Blob(f(sample).to_vec()),
+                    }));
+                insert.execute(params_from_iter(values))?;
+            }
+        }
+
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO camera_samples (session, timestamp, camera, image_path, motion, clip_path, raw_image_path, thumbnail) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+            for sample in &buffer {
+                for name in &self.camera_names {
+                    let result = sample.extra_cameras.get(name);
+                    insert.execute(params![
+                        session,
+                        sample.timestamp_s as i64,
+                        name,
+                        result.map_or("", |r| r.image_path.as_str()),
+                        real(result.and_then(|r| r.motion).unwrap_or(f32::NAN)),
+                        result.and_then(|r| r.clip_path.as_deref()).unwrap_or_default(),
+                        result.and_then(|r| r.raw_image_path.as_deref()).unwrap_or_default(),
+                        result.and_then(|r| r.thumbnail.as_ref()).map_or(&[][..], |t| t.0.as_slice()),
+                    ])?;
+                }
+            }
+        }
+
+        for (table, column, names, temps) in [
+            ("probe_temps", "probe", &self.probe_names, (|d| &d.probe_temps_c) as fn(&SleepData) -> &HashMap<String, f32>),
+            ("thermistor_temps", "thermistor", &self.thermistor_names, |d| &d.extra_thermistor_temps_c),
+        ] {
+            let mut insert = transaction.prepare_cached(&format!(
+                "INSERT INTO {table} (session, timestamp, {column}, temperature_c) VALUES (?1, ?2, ?3, ?4)"))?;
+            for sample in &buffer {
+                for name in names {
+                    let temperature_c = temps(sample).get(name).copied().unwrap_or(f32::NAN);
+                    insert.execute(params![session, sample.timestamp_s as i64, name, real(temperature_c)])?;
+                }
+            }
+        }
+
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO sensor_status (session, timestamp, sensor, ok, state, error, last_error_age_s) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+            for sample in &buffer {
+                for name in &self.sensor_names {
+                    let status = sample.sensor_status.get(name);
+                    insert.execute(params![
+                        session,
+                        sample.timestamp_s as i64,
+                        sensor_status_key(name),
+                        status.is_some_and(|s| s.ok()),
+                        status.map_or(SensorState::Initializing, |s| s.state) as u8,
+                        status.and_then(|s| s.error.as_deref()).unwrap_or_default(),
+                        status.and_then(|s| s.last_error_age_s).map(|age| age as f64),
+                    ])?;
+                }
+            }
+        }
+
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO piezo_bursts (session, timestamp, samples_mv) VALUES (?1, ?2, ?3)")?;
+            for sample in buffer.iter().filter(|d| !d.piezo_bcg_mv.is_empty()) {
+                let blob: Vec<u8> = sample.piezo_bcg_mv.iter().flat_map(|v| v.to_le_bytes()).collect();
+                insert.execute(params![session, sample.timestamp_s as i64, blob])?;
+            }
+        }
+
+        transaction.commit()?;
+        if let Some(journal) = &mut self.journal {
+            journal.clear()?;
+        }
+        self.has_samples |= !buffer.is_empty();
+        info!("Successfully flushed to SQLite");
+        Ok(())
+    }
+}
+
+/// SQL type of the column of `field`.
+fn column_type(field: &SleepField) -> &'static str {
+    match field {
+        SleepField::Bool(_) | SleepField::U16(_) | SleepField::U64(_) => "INTEGER",
+        SleepField::F32(_) => "REAL",
+        SleepField::String(_) => "TEXT",
+        SleepField::Bytes(_) => "BLOB",
+    }
+}
+
+/// A float as SQL value, with `NAN` stored as `NULL`.
+fn real(value: f32) -> Value {
+    if value.is_nan() {
+        Value::Null
+    } else {
+        Value::Real(value as f64)
+    }
+}
+
+impl StorageBackend for SqliteLogger {
+    fn session_name(&self) -> &str {
+        &self.session_name
+    }
+
+    fn is_resumed(&self) -> bool {
+        SqliteLogger::is_resumed(self)
+    }
+
+    fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::write_metadata(self, metadata)
+    }
+
+    fn register_field(&mut self, name: &'static str, field: SleepField) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::register_field(self, name, field)
+    }
+
+    fn register_camera(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::register_camera(self, name)
+    }
+
+    fn register_probe(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::register_probe(self, name)
+    }
+
+    fn register_thermistor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::register_thermistor(self, name)
+    }
+
+    fn register_sensor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::register_sensor(self, name)
+    }
+
+    fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::append(self, sample)
+    }
+
+    fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::add_audio_entry(self, audio_recording)
+    }
+
+    fn append_audio_rms(&mut self, start_time_s: u64, rms_t_s: &[u64], rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::append_audio_rms(self, start_time_s, rms_t_s, rms_db)
+    }
+
+    fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::add_actuator_event(self, event)
+    }
+
+    fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::add_event(self, event)
+    }
+
+    fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32) {
+        SqliteLogger::append_audio_level(self, timestamp_s, rms_db)
+    }
+
+    fn append_audio_bands(&mut self, timestamp_s: u64, levels_db: [f32; BANDS.len()]) {
+        SqliteLogger::append_audio_bands(self, timestamp_s, levels_db)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        SqliteLogger::flush(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use test_log::test;
+
+    use crate::config::{Config, StorageFormat};
+    use crate::data::{CameraAndMotionResult, SensorStatus, Thumbnail};
+    use crate::storage::{self, journal};
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let mut logger = SqliteLogger::new(data_path, "sleep_data.db").expect("Failed to create logger");
+        logger.register_camera("crib").expect("Failed to register camera");
+        logger.register_probe("mattress").expect("Failed to register probe");
+        logger.register_sensor("BME280").expect("Failed to register sensor");
+        let session = logger.session_name.clone();
+        let metadata = SessionMetadata {
+            notes: "new mattress".to_string(),
+            sensors: vec!["BME280".to_string(), "crib".to_string()],
+            ..Default::default()
+        };
+        logger.write_metadata(&metadata).expect("Failed to write metadata");
+        assert!(logger.write_metadata(&metadata).is_err());
+
+        for timestamp in 0..15 {
+            let mut sample = SleepData::builder(timestamp).with_climate(21.5, 1013.0, 40.0);
+            if timestamp % 2 == 0 {
+                sample = sample.with_named_camera_result("crib", CameraAndMotionResult {
+                    image_path: format!("crib_{timestamp}.jpg"),
+                    motion: Some(0.5),
+                    clip_path: None,
+                    raw_image_path: None,
+                    thumbnail: Some(Thumbnail(vec![0xff, 0xd8, 0xff, 0xd9])),
+                });
+            }
+            let status = SensorStatus { error: Some("timeout".to_string()), ..Default::default() };
+            let sample = sample.with_probe_temp("mattress", 30.0).with_sensor_status("BME280", status);
+            logger.append(sample.build()).expect("Failed to append sample");
+        }
+        // 12 samples were flushed, so the layout is fixed
+        assert!(logger.register_probe("pillow").is_err());
+        logger.append_audio_level(3, -40.0);
+        logger.append_audio_bands(3, [-45.0, -50.0, f32::NAN]);
+        logger.add_audio_entry(AudioRecording {
+            path: "audio.mp3".to_string(),
+            duration: Duration::from_secs(1800),
+            start_time_s: 0,
+        }).expect("Failed to add audio entry");
+        logger.append_audio_rms(0, &[0, 5], &[-50.0, f32::NAN]).expect("Failed to append audio RMS");
+        assert!(logger.append_audio_rms(0, &[10], &[]).is_err());
+        logger.add_actuator_event(&ActuatorEvent { timestamp_s: 4, name: "fan".to_string(), on: true })
+            .expect("Failed to add actuator event");
+        logger.add_event(&Event { timestamp_s: 6, category: "bedtime".to_string(), text: "went to bed".to_string() })
+            .expect("Failed to add event");
+        drop(logger);
+
+        // Resume the session and continue appending to it
+        let (last_session, last_s) = SqliteLogger::last_session(data_path, "sleep_data.db")
+            .expect("Failed to find last session")
+            .expect("Expected a session");
+        assert_eq!((last_session.as_str(), last_s), (session.as_str(), 14));
+        let mut logger = SqliteLogger::resume(data_path, "sleep_data.db", &session).expect("Failed to resume session");
+        assert!(logger.is_resumed());
+        logger.append(SleepData::builder(15).build()).expect("Failed to append sample");
+        drop(logger);
+        SqliteLogger::mark_purged(data_path, "sleep_data.db", &session, Media::Audio, 100).expect("Failed to mark audio purged");
+        assert!(SqliteLogger::mark_purged(data_path, "sleep_data.db", "2020-01-01_00-00-00", Media::Audio, 100).is_err());
+
+        let connection = Connection::open(dir.path().join("sleep_data.db")).expect("Failed to open database");
+        let count = |query: &str| -> i64 {
+            connection.query_row(query, [&session], |row| row.get(0)).expect("Failed to query")
+        };
+        assert_eq!(count("SELECT COUNT(*) FROM samples WHERE session = ?1"), 16);
+        assert_eq!(count("SELECT COUNT(*) FROM samples WHERE session = ?1 AND temperature = 21.5"), 15);
+        // Unavailable readings are NULL
+        assert_eq!(count("SELECT COUNT(*) FROM samples WHERE session = ?1 AND light_lux IS NULL"), 16);
+        assert_eq!(count("SELECT COUNT(*) FROM camera_samples WHERE session = ?1 AND motion IS NOT NULL"), 8);
+        assert_eq!(count("SELECT COUNT(*) FROM camera_samples WHERE session = ?1 AND length(thumbnail) = 4"), 8);
+        assert_eq!(count("SELECT COUNT(*) FROM probe_temps WHERE session = ?1 AND probe = 'mattress'"), 15);
+        assert_eq!(count("SELECT COUNT(*) FROM sensor_status WHERE session = ?1 AND sensor = 'bme280' AND NOT ok"), 15);
+        assert_eq!(count("SELECT COUNT(*) FROM live_audio_levels WHERE session = ?1"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM audio_bands WHERE session = ?1 AND level_db IS NOT NULL"), 2);
+        assert_eq!(count("SELECT duration_s FROM audio WHERE session = ?1"), 1800);
+        assert_eq!(count("SELECT COUNT(*) FROM audio_rms WHERE session = ?1 AND start_time_s = 0 AND rms_db IS NULL"), 1);
+        assert_eq!(count("SELECT \"on\" FROM actuator_events WHERE session = ?1"), 1);
+        assert_eq!(count("SELECT timestamp FROM events WHERE session = ?1 AND category = 'bedtime'"), 6);
+        assert_eq!(count("SELECT COUNT(*) FROM session_metadata WHERE session = ?1 AND key = 'sensor'"), 2);
+        assert_eq!(count("SELECT purged_s FROM purged_media WHERE session = ?1 AND media = 'audio'"), 100);
+    }
+
+    #[test]
+    fn test_buffering() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+
+        // 0 is clamped to 1: every sample is written right away
+        let mut logger = SqliteLogger::new(data_path, "sleep_data.db")
+            .expect("Failed to create logger")
+            .with_buffering(0, 1024);
+        let connection = Connection::open(dir.path().join("sleep_data.db")).expect("Failed to open database");
+        let count = || -> i64 {
+            connection.query_row("SELECT COUNT(*) FROM samples", [], |row| row.get(0)).expect("Failed to query")
+        };
+        logger.append(SleepData::builder(0).build()).expect("Failed to append sample");
+        assert_eq!(count(), 1);
+        let session = logger.session_name.clone();
+        drop(logger);
+
+        // A large piezo burst exceeds the 1 KiB limit before 12 samples are buffered
+        let mut logger = SqliteLogger::resume(data_path, "sleep_data.db", &session)
+            .expect("Failed to resume session")
+            .with_buffering(12, 1);
+        logger.append(SleepData::builder(1).build()).expect("Failed to append sample");
+        assert_eq!(count(), 1);
+        let mut sample = SleepData::builder(5).build();
+        sample.piezo_bcg_mv = vec![0.0; 500];
+        logger.append(sample).expect("Failed to append sample");
+        assert_eq!(count(), 3);
+    }
+
+    #[test]
+    fn test_replay_journal() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let data_path = dir.path().to_str().unwrap();
+        let config = Config {
+            data_path: data_path.to_string(),
+            file_name: "sleep_data.db".to_string(),
+            storage: StorageFormat::Sqlite,
+            ..Config::default()
+        };
+        let mut backend = storage::open(&config).expect("Failed to open storage");
+        let session = backend.session_name().to_string();
+        let now_s = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        for timestamp in now_s - 15..now_s {
+            backend.append(SleepData::builder(timestamp).build()).expect("Failed to append sample");
+        }
+        // Crash: 12 samples were flushed, and the other 3 only journaled
+        std::mem::forget(backend);
+        assert!(journal::path(data_path, "sleep_data.db").exists());
+
+        // Reopening resumes the session, after replaying the journal into it
+        let backend = storage::open(&config).expect("Failed to reopen storage");
+        assert_eq!(backend.session_name(), session);
+        drop(backend);
+        let connection = Connection::open(dir.path().join("sleep_data.db")).expect("Failed to open database");
+        let count: i64 = connection.query_row("SELECT COUNT(*) FROM samples WHERE session = ?1", [&session], |row| row.get(0))
+            .expect("Failed to query");
+        assert_eq!(count, 15);
+        assert!(!journal::path(data_path, "sleep_data.db").exists());
+    }
+}
@@ -0,0 +1,711 @@
+//! Storage backends for the recorded data, selected with [`Config::storage`].
+//!
+//! The recorder writes through the [`StorageBackend`] trait, implemented by the HDF5
+//! [`SleepDataLogger`] and, with the `sqlite` feature, the
+//! [`SqliteLogger`](crate::sqlite::SqliteLogger). Both buffer samples and write them in batches, and
+//! flush what is left when dropped. Buffered samples are also written to a [`journal`], so that
+//! a crash between flushes doesn't lose them. During a session the backend is owned by a
+//! [`StorageWriter`] thread, so that file I/O doesn't block the async tasks. Its queue is
+//! bounded: when the thread falls behind, new samples are dropped and counted (see
+//! [`WriterMetrics`]) rather than piling up in memory or stalling the sensors.
+//!
+//! Export sinks enabled in the config, the InfluxDB export and the remote copy, are secondary
+//! backends receiving a copy of everything written (see [`Tee`]).
+
+use std::error::Error;
+use std::fs;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn};
+
+use crate::audio_analysis::BANDS;
+use crate::config::{Config, FileRotation, StorageFormat};
+use crate::data::{self, ActuatorEvent, AudioRecording, Event, SessionMetadata, SleepData, SleepDataLogger, SleepField};
+use crate::encryption::{EncryptingBackend, Key};
+use crate::retention::Media;
+
+pub(crate) mod journal;
+
+/// Destination of a recording session's data.
+///
+/// The fields of the session's sensors, and named cameras, probes, thermistors, and sensors must
+/// be registered before the first sample is flushed, so that every sample has a value (or a
+/// placeholder) for each of them. In a resumed session, registering them again is a no-op.
+pub trait StorageBackend: Send {
+    /// Name of the session, e.g. "2025-04-28_22-47-31". Images and audio are stored in a
+    /// directory of this name.
+    fn session_name(&self) -> &str;
+
+    /// Whether this continues an existing session, e.g. after the recorder restarted during the
+    /// night. The session's names are registered already, and its metadata is written.
+    fn is_resumed(&self) -> bool;
+
+    /// Stores the context of the session. Metadata can only be written once.
+    fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<(), Box<dyn Error>>;
+
+    /// Registers a per-sample field of a sensor (see [`Sensor::fields`](crate::sensor::Sensor::fields)),
+    /// stored in its own dataset or column.
+    fn register_field(&mut self, name: &'static str, field: SleepField) -> Result<(), Box<dyn Error>>;
+
+    /// Registers an additional camera, stored like the primary one under its name.
+    fn register_camera(&mut self, name: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Registers a named temperature probe.
+    fn register_probe(&mut self, name: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Registers an additional thermistor.
+    fn register_thermistor(&mut self, name: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Registers a sensor whose health is recorded with every sample.
+    fn register_sensor(&mut self, name: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Buffers a sample, flushing the buffer once it is full.
+    fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>>;
+
+    /// Stores a finished audio recording.
+    fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>>;
+
+    /// Appends RMS windows (dBFS of the windows starting at `rms_t_s`) to the audio recording
+    /// started at `start_time_s`, which may not be stored yet.
+    fn append_audio_rms(&mut self, start_time_s: u64, rms_t_s: &[u64], rms_db: &[f32]) -> Result<(), Box<dyn Error>>;
+
+    /// Stores an actuator state change.
+    fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>>;
+
+    /// Stores an annotation of the session, e.g. "went to bed".
+    fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>>;
+
+    /// Buffers a live audio level (RMS dBFS of the window starting at `timestamp_s`).
+    fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32);
+
+    /// Buffers the levels (dBFS, in the order of [`BANDS`]) of the frequency bands in the audio
+    /// window starting at `timestamp_s`, as computed live by a
+    /// [`StreamAnalyzer`](crate::audio_analysis::StreamAnalyzer).
+    fn append_audio_bands(&mut self, timestamp_s: u64, levels_db: [f32; BANDS.len()]);
+
+    /// Writes the buffered samples and audio levels.
+    fn flush(&mut self) -> Result<(), Box<dyn Error>>;
+}
+
+/// Opens the data file `config.file_name` in `config.data_path`, stored in the format selected
+/// by `config.storage`, and resumes its most recent session if its last sample is at most
+/// `config.resume_window_s` old. Otherwise a new session is started. With `config.rotation` set
+/// to [`FileRotation::Nightly`], new HDF5 sessions are started in the file of the night instead
+/// (see [`night_file_name`](crate::data::night_file_name)), and the last session is looked up in
+/// both layouts.
+///
+/// With `config.encryption.key_file` set, the backend encrypts the media of what it stores (see
+/// [`crate::encryption`]).
+///
+/// Samples left in the [`journal`] by a crash are first written to the session they belong to.
+/// If that fails, the journal is kept as `<file_name>.journal.failed`.
+///
+/// # Errors
+///
+/// Returns an error if the encryption key or the file cannot be read, the session cannot be created
+/// or resumed, or if SQLite storage, LZF or Blosc compression, the InfluxDB export, or the remote
+/// copy is selected but the crate was built without the `sqlite`, `lzf`, `blosc`, `influxdb`, or
+/// `remote` feature.
+pub fn open(config: &Config) -> Result<Box<dyn StorageBackend>, Box<dyn Error>> {
+    let key = config.encryption.key_file.as_ref().map(Key::from_file).transpose()?;
+    let (data_path, file_name) = (config.data_path.as_str(), config.file_name.as_str());
+    let journal_path = journal::path(data_path, file_name);
+    if let Err(e) = replay_journal(config) {
+        warn!("Failed to replay journal {}: {}", journal_path.display(), e);
+        fs::rename(&journal_path, journal_path.with_extension("journal.failed"))?;
+    }
+    let backend: Box<dyn StorageBackend> = match config.storage {
+        StorageFormat::Hdf5 => {
+            let logger = match resumable(config, SleepDataLogger::last_session(data_path, file_name)?) {
+                Some(session) => SleepDataLogger::resume_with_compression(data_path, file_name, &session, config.compression.clone())?,
+                None => match config.rotation {
+                    FileRotation::None => SleepDataLogger::new_with_compression(data_path, file_name, config.compression.clone())?,
+                    FileRotation::Nightly => SleepDataLogger::new_nightly(data_path, file_name, config.compression.clone())?,
+                },
+            };
+            Box::new(logger.with_buffering(config.flush_every, config.max_buffer_kib).with_journal(journal_path)?)
+        }
+        #[cfg(feature = "sqlite")]
+        StorageFormat::Sqlite => {
+            use crate::sqlite::SqliteLogger;
+            let logger = match resumable(config, SqliteLogger::last_session(data_path, file_name)?) {
+                Some(session) => SqliteLogger::resume(data_path, file_name, &session)?,
+                None => SqliteLogger::new(data_path, file_name)?,
+            };
+            Box::new(logger.with_buffering(config.flush_every, config.max_buffer_kib).with_journal(journal_path)?)
+        }
+        #[cfg(not(feature = "sqlite"))]
+        StorageFormat::Sqlite => return Err("SQLite storage requires the sqlite feature".into()),
+    };
+    let backend = with_sinks(config, backend)?;
+    match key {
+        Some(key) => Ok(Box::new(EncryptingBackend::new(backend, key))),
+        None => Ok(backend),
+    }
+}
+
+/// Pairs `backend` with the export sinks enabled in `config`, if any.
+fn with_sinks(config: &Config, backend: Box<dyn StorageBackend>) -> Result<Box<dyn StorageBackend>, Box<dyn Error>> {
+    let mut sinks = Vec::new();
+    if config.influxdb.is_enabled() {
+        sinks.push(influxdb_sink(config, backend.session_name())?);
+    }
+    if config.remote.is_enabled() {
+        sinks.push(remote_sink(config, backend.session_name())?);
+    }
+    if sinks.is_empty() {
+        return Ok(backend);
+    }
+    Ok(Box::new(sinks.into_iter().fold(Tee::new(backend), Tee::with_secondary)))
+}
+
+#[cfg(feature = "influxdb")]
+fn influxdb_sink(config: &Config, session: &str) -> Result<Box<dyn StorageBackend>, Box<dyn Error>> {
+    Ok(Box::new(crate::influxdb::InfluxSink::new(config, session)?))
+}
+
+#[cfg(not(feature = "influxdb"))]
+fn influxdb_sink(_config: &Config, _session: &str) -> Result<Box<dyn StorageBackend>, Box<dyn Error>> {
+    Err("InfluxDB export requires the influxdb feature".into())
+}
+
+#[cfg(feature = "remote")]
+fn remote_sink(config: &Config, session: &str) -> Result<Box<dyn StorageBackend>, Box<dyn Error>> {
+    Ok(Box::new(crate::remote::RemoteSink::new(config, session)?))
+}
+
+#[cfg(not(feature = "remote"))]
+fn remote_sink(_config: &Config, _session: &str) -> Result<Box<dyn StorageBackend>, Box<dyn Error>> {
+    Err("The remote copy requires the remote feature".into())
+}
+
+/// Writes the samples left in the journal of the data file to the session they belong to, and
+/// deletes the journal. Samples already in the session, flushed right before the crash, are
+/// skipped.
+fn replay_journal(config: &Config) -> Result<(), Box<dyn Error>> {
+    let (data_path, file_name) = (config.data_path.as_str(), config.file_name.as_str());
+    let path = journal::path(data_path, file_name);
+    if let Some(journal::Journaled { session, samples }) = journal::read(&path)? {
+        // The last sample is looked up before the session is opened for writing
+        let (last_session, mut backend): (_, Box<dyn StorageBackend>) = match config.storage {
+            StorageFormat::Hdf5 => {
+                let last_session = SleepDataLogger::last_session(data_path, file_name)?;
+                let logger = SleepDataLogger::resume_with_compression(data_path, file_name, &session, config.compression.clone())?;
+                (last_session, Box::new(logger))
+            }
+            #[cfg(feature = "sqlite")]
+            StorageFormat::Sqlite => {
+                use crate::sqlite::SqliteLogger;
+                let last_session = SqliteLogger::last_session(data_path, file_name)?;
+                (last_session, Box::new(SqliteLogger::resume(data_path, file_name, &session)?))
+            }
+            #[cfg(not(feature = "sqlite"))]
+            StorageFormat::Sqlite => return Err("SQLite storage requires the sqlite feature".into()),
+        };
+        let last_s = match last_session {
+            Some((last_session, last_s)) if last_session == session => last_s,
+            _ => 0,
+        };
+        let mut replayed = 0;
+        for sample in samples.into_iter().filter(|sample| sample.timestamp_s > last_s) {
+            backend.append(sample)?;
+            replayed += 1;
+        }
+        backend.flush()?;
+        info!("Replayed {} journaled samples into session {}", replayed, session);
+    }
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Marks the `media` files of `session` in the data file as deleted at `purged_s` (see
+/// [`crate::retention`]).
+///
+/// # Errors
+///
+/// Returns an error if the session isn't in the data file, or if SQLite storage is selected but
+/// the crate was built without the `sqlite` feature.
+pub fn mark_purged(config: &Config, session: &str, media: Media, purged_s: u64) -> Result<(), Box<dyn Error>> {
+    let (data_path, file_name) = (config.data_path.as_str(), config.file_name.as_str());
+    match config.storage {
+        StorageFormat::Hdf5 => data::mark_purged(data_path, file_name, session, media, purged_s),
+        #[cfg(feature = "sqlite")]
+        StorageFormat::Sqlite => crate::sqlite::SqliteLogger::mark_purged(data_path, file_name, session, media, purged_s),
+        #[cfg(not(feature = "sqlite"))]
+        StorageFormat::Sqlite => Err("SQLite storage requires the sqlite feature".into()),
+    }
+}
+
+/// The session to resume: the last session, if its last activity is within the resume window.
+fn resumable(config: &Config, last_session: Option<(String, u64)>) -> Option<String> {
+    let (session, last_s) = last_session?;
+    let now_s = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let age_s = now_s.saturating_sub(last_s);
+    if age_s > config.resume_window_s {
+        return None;
+    }
+    info!("Resuming session {} (last sample {} s ago)", session, age_s);
+    Some(session)
+}
+
+/// Backend writing to a primary backend, the data file, and copying everything to secondary
+/// backends, such as an [`InfluxSink`](crate::influxdb::InfluxSink) or a
+/// [`RemoteSink`](crate::remote::RemoteSink).
+///
+/// The session name and whether it is resumed are the primary's. Failures of the secondary
+/// backends are logged, and don't fail the write.
+pub struct Tee {
+    primary: Box<dyn StorageBackend>,
+    secondaries: Vec<Box<dyn StorageBackend>>,
+}
+
+impl Tee {
+    /// Creates a tee of `primary` without secondary backends.
+    pub fn new(primary: Box<dyn StorageBackend>) -> Self {
+        Self { primary, secondaries: Vec::new() }
+    }
+
+    /// Adds a secondary backend.
+    pub fn with_secondary(mut self, secondary: Box<dyn StorageBackend>) -> Self {
+        self.secondaries.push(secondary);
+        self
+    }
+
+    /// Applies `write` to the primary backend, then to the secondary ones, logging their errors.
+    fn write_all(
+        &mut self,
+        mut write: impl FnMut(&mut dyn StorageBackend) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let result = write(self.primary.as_mut());
+        for secondary in &mut self.secondaries {
+            if let Err(e) = write(secondary.as_mut()) {
+                warn!("Secondary storage write error: {}", e);
+            }
+        }
+        result
+    }
+}
+
+impl StorageBackend for Tee {
+    fn session_name(&self) -> &str {
+        self.primary.session_name()
+    }
+
+    fn is_resumed(&self) -> bool {
+        self.primary.is_resumed()
+    }
+
+    fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.write_metadata(metadata))
+    }
+
+    fn register_field(&mut self, name: &'static str, field: SleepField) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.register_field(name, field))
+    }
+
+    fn register_camera(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.register_camera(name))
+    }
+
+    fn register_probe(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.register_probe(name))
+    }
+
+    fn register_thermistor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.register_thermistor(name))
+    }
+
+    fn register_sensor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.register_sensor(name))
+    }
+
+    fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+        for secondary in &mut self.secondaries {
+            if let Err(e) = secondary.append(sample.clone()) {
+                warn!("Secondary storage write error: {}", e);
+            }
+        }
+        self.primary.append(sample)
+    }
+
+    fn add_audio_entry(&mut self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.add_audio_entry(audio_recording.clone()))
+    }
+
+    fn append_audio_rms(&mut self, start_time_s: u64, rms_t_s: &[u64], rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.append_audio_rms(start_time_s, rms_t_s, rms_db))
+    }
+
+    fn add_actuator_event(&mut self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.add_actuator_event(event))
+    }
+
+    fn add_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.add_event(event))
+    }
+
+    fn append_audio_level(&mut self, timestamp_s: u64, rms_db: f32) {
+        self.primary.append_audio_level(timestamp_s, rms_db);
+        for secondary in &mut self.secondaries {
+            secondary.append_audio_level(timestamp_s, rms_db);
+        }
+    }
+
+    fn append_audio_bands(&mut self, timestamp_s: u64, levels_db: [f32; BANDS.len()]) {
+        self.primary.append_audio_bands(timestamp_s, levels_db);
+        for secondary in &mut self.secondaries {
+            secondary.append_audio_bands(timestamp_s, levels_db);
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.write_all(|backend| backend.flush())
+    }
+}
+
+/// Default number of writes a [`StorageWriter`] queues.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// Default number of samples a logger buffers before writing them.
+pub const DEFAULT_FLUSH_EVERY: usize = 12;
+/// Most samples a logger buffers; larger `flush_every` values are clamped to it. The journal
+/// keeps the buffered samples through a crash, but they are lost if the SD card fails.
+pub const MAX_FLUSH_EVERY: usize = 720;
+/// Default memory the samples buffered by a logger may take before they are written, in KiB.
+pub const DEFAULT_MAX_BUFFER_KIB: usize = 16 * 1024;
+/// Most memory the buffered samples may take, in KiB; larger limits are clamped to it.
+pub const MAX_BUFFER_KIB: usize = 256 * 1024;
+
+/// `flush_every` and `max_buffer_kib` clamped to `1..=`[`MAX_FLUSH_EVERY`] and
+/// `1..=`[`MAX_BUFFER_KIB`], with a warning for each value out of range. The limit is returned in
+/// bytes.
+pub(crate) fn clamp_buffering(flush_every: usize, max_buffer_kib: usize) -> (usize, usize) {
+    let clamped = (flush_every.clamp(1, MAX_FLUSH_EVERY), max_buffer_kib.clamp(1, MAX_BUFFER_KIB));
+    if clamped.0 != flush_every {
+        warn!("flush_every {} is out of range; buffering {} samples", flush_every, clamped.0);
+    }
+    if clamped.1 != max_buffer_kib {
+        warn!("max_buffer_kib {} is out of range; buffering up to {} KiB", max_buffer_kib, clamped.1);
+    }
+    (clamped.0, clamped.1 * 1024)
+}
+
+/// A write queued for the writer thread.
+enum WriteCommand {
+    Sample(Box<SleepData>),
+    AudioEntry(AudioRecording),
+    AudioRms(u64, Vec<u64>, Vec<f32>),
+    ActuatorEvent(ActuatorEvent),
+    Event(Event),
+    AudioLevel(u64, f32),
+    AudioBands(u64, [f32; BANDS.len()]),
+}
+
+/// Counters of a [`StorageWriter`]'s queue, read with [`StorageWriter::metrics`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriterMetrics {
+    /// Number of writes the queue holds at most.
+    pub capacity: usize,
+    /// Writes queued and not applied yet.
+    pub queue_depth: usize,
+    /// Most writes queued at once.
+    pub max_queue_depth: usize,
+    /// Samples dropped because the queue was full.
+    pub dropped_samples: u64,
+    /// Live audio levels dropped because the queue was full.
+    pub dropped_audio_levels: u64,
+}
+
+/// Counters shared by the handles of a writer and its thread.
+#[derive(Debug, Default)]
+struct Counters {
+    queue_depth: AtomicUsize,
+    max_queue_depth: AtomicUsize,
+    dropped_samples: AtomicU64,
+    dropped_audio_levels: AtomicU64,
+}
+
+/// Cloneable handle queuing writes for a [`StorageBackend`] owned by a dedicated writer thread.
+///
+/// Flushes perform blocking file I/O, which can take long on a slow SD card; doing it on the
+/// writer thread keeps it from stalling the async tasks and the sensor polling cadence. Writes are
+/// applied in the order they are queued, and failures are logged.
+///
+/// The queue is bounded. Samples and live audio levels that don't fit are dropped, so that the
+/// sensors keep their cadence while the thread catches up; audio recordings and actuator events
+/// wait for room instead, as they are rare and refer to files and states that would otherwise be
+/// lost.
+#[derive(Clone, Debug)]
+pub struct StorageWriter {
+    commands: mpsc::SyncSender<WriteCommand>,
+    capacity: usize,
+    counters: Arc<Counters>,
+}
+
+impl StorageWriter {
+    /// Moves `backend` to a new writer thread with a queue of [`DEFAULT_QUEUE_CAPACITY`] writes
+    /// (see [`spawn_with_capacity`](Self::spawn_with_capacity)).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thread cannot be spawned.
+    pub fn spawn(backend: Box<dyn StorageBackend>) -> Result<(Self, JoinHandle<()>), Box<dyn Error>> {
+        Self::spawn_with_capacity(backend, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Moves `backend` to a new writer thread queuing up to `capacity` writes. Once every handle
+    /// is dropped, the thread drops the backend, flushing what is left, and exits; join the
+    /// returned handle to wait for that.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `capacity` is 0, or if the thread cannot be spawned.
+    pub fn spawn_with_capacity(mut backend: Box<dyn StorageBackend>, capacity: usize) -> Result<(Self, JoinHandle<()>), Box<dyn Error>> {
+        if capacity == 0 {
+            return Err("The storage writer queue needs a capacity of at least 1".into());
+        }
+        let (commands, receiver) = mpsc::sync_channel(capacity);
+        let counters = Arc::new(Counters::default());
+        let thread_counters = counters.clone();
+        let thread = thread::Builder::new()
+            .name("storage-writer".to_string())
+            .spawn(move || {
+                for command in receiver {
+                    thread_counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                    let result = match command {
+                        WriteCommand::Sample(sample) => backend.append(*sample),
+                        WriteCommand::AudioEntry(recording) => backend.add_audio_entry(recording),
+                        WriteCommand::AudioRms(start_time_s, rms_t_s, rms_db) => {
+                            backend.append_audio_rms(start_time_s, &rms_t_s, &rms_db)
+                        }
+                        WriteCommand::ActuatorEvent(event) => backend.add_actuator_event(&event),
+                        WriteCommand::Event(event) => backend.add_event(&event),
+                        WriteCommand::AudioLevel(timestamp_s, rms_db) => {
+                            backend.append_audio_level(timestamp_s, rms_db);
+                            Ok(())
+                        }
+                        WriteCommand::AudioBands(timestamp_s, levels_db) => {
+                            backend.append_audio_bands(timestamp_s, levels_db);
+                            Ok(())
+                        }
+                    };
+                    if let Err(e) = result {
+                        warn!("storage write error: {}", e);
+                    }
+                }
+                info!(
+                    "Storage writer stopped; closing {} (max queue depth {}, {} samples and {} audio levels dropped)",
+                    backend.session_name(),
+                    thread_counters.max_queue_depth.load(Ordering::Relaxed),
+                    thread_counters.dropped_samples.load(Ordering::Relaxed),
+                    thread_counters.dropped_audio_levels.load(Ordering::Relaxed),
+                );
+            })?;
+        Ok((Self { commands, capacity, counters }, thread))
+    }
+
+    /// Current counters of the queue.
+    pub fn metrics(&self) -> WriterMetrics {
+        WriterMetrics {
+            capacity: self.capacity,
+            queue_depth: self.counters.queue_depth.load(Ordering::Relaxed),
+            max_queue_depth: self.counters.max_queue_depth.load(Ordering::Relaxed),
+            dropped_samples: self.counters.dropped_samples.load(Ordering::Relaxed),
+            dropped_audio_levels: self.counters.dropped_audio_levels.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Queues a sample.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the queue is full, dropping the sample, or if the writer has stopped.
+    pub fn append(&self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+        self.try_send(WriteCommand::Sample(Box::new(sample)), &self.counters.dropped_samples)
+    }
+
+    /// Queues a finished audio recording.
+    pub fn add_audio_entry(&self, audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+        self.send(WriteCommand::AudioEntry(audio_recording))
+    }
+
+    /// Queues RMS windows of an audio recording (see [`StorageBackend::append_audio_rms`]).
+    pub fn append_audio_rms(&self, start_time_s: u64, rms_t_s: Vec<u64>, rms_db: Vec<f32>) -> Result<(), Box<dyn Error>> {
+        self.send(WriteCommand::AudioRms(start_time_s, rms_t_s, rms_db))
+    }
+
+    /// Queues an actuator state change.
+    pub fn add_actuator_event(&self, event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+        self.send(WriteCommand::ActuatorEvent(event.clone()))
+    }
+
+    /// Queues an annotation of the session.
+    pub fn add_event(&self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.send(WriteCommand::Event(event.clone()))
+    }
+
+    /// Queues a live audio level.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the queue is full, dropping the level, or if the writer has stopped.
+    pub fn append_audio_level(&self, timestamp_s: u64, rms_db: f32) -> Result<(), Box<dyn Error>> {
+        self.try_send(WriteCommand::AudioLevel(timestamp_s, rms_db), &self.counters.dropped_audio_levels)
+    }
+
+    /// Queues the band levels of a live-analyzed audio window.
+    pub fn append_audio_bands(&self, timestamp_s: u64, levels_db: [f32; BANDS.len()]) -> Result<(), Box<dyn Error>> {
+        self.send(WriteCommand::AudioBands(timestamp_s, levels_db))
+    }
+
+    /// Queues `command`, waiting for room in the queue.
+    fn send(&self, command: WriteCommand) -> Result<(), Box<dyn Error>> {
+        self.queued();
+        self.commands.send(command).map_err(|_| {
+            self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            "The storage writer has stopped".into()
+        })
+    }
+
+    /// Queues `command` if there is room, and otherwise drops it and counts it in `dropped`.
+    fn try_send(&self, command: WriteCommand, dropped: &AtomicU64) -> Result<(), Box<dyn Error>> {
+        self.queued();
+        self.commands.try_send(command).map_err(|e| {
+            self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            match e {
+                mpsc::TrySendError::Full(_) => {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                    format!("The storage writer queue is full ({} writes); dropped", self.capacity).into()
+                }
+                mpsc::TrySendError::Disconnected(_) => "The storage writer has stopped".into(),
+            }
+        })
+    }
+
+    /// Counts a write about to be queued (before it is, so that the writer thread never takes it
+    /// off the queue depth first).
+    fn queued(&self) {
+        let depth = self.counters.queue_depth.fetch_add(1, Ordering::Relaxed) + 1;
+        self.counters.max_queue_depth.fetch_max(depth.min(self.capacity), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use test_log::test;
+
+    /// Backend recording the timestamps of its samples, whose first append waits for `gate`.
+    struct GatedBackend {
+        entered: mpsc::Sender<()>,
+        gate: Option<mpsc::Receiver<()>>,
+        timestamps: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl StorageBackend for GatedBackend {
+        fn session_name(&self) -> &str {
+            "gated"
+        }
+
+        fn is_resumed(&self) -> bool {
+            false
+        }
+
+        fn write_metadata(&mut self, _metadata: &SessionMetadata) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn register_field(&mut self, _name: &'static str, _field: SleepField) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn register_camera(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn register_probe(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn register_thermistor(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn register_sensor(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn append(&mut self, sample: SleepData) -> Result<(), Box<dyn Error>> {
+            if let Some(gate) = self.gate.take() {
+                self.entered.send(())?;
+                gate.recv()?;
+            }
+            self.timestamps.lock().unwrap().push(sample.timestamp_s);
+            Ok(())
+        }
+
+        fn add_audio_entry(&mut self, _audio_recording: AudioRecording) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn append_audio_rms(&mut self, _start_time_s: u64, _rms_t_s: &[u64], _rms_db: &[f32]) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn add_actuator_event(&mut self, _event: &ActuatorEvent) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn add_event(&mut self, _event: &Event) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn append_audio_level(&mut self, _timestamp_s: u64, _rms_db: f32) {}
+
+        fn append_audio_bands(&mut self, _timestamp_s: u64, _levels_db: [f32; BANDS.len()]) {}
+
+        fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_bounded_writer_drops_samples() {
+        let (entered, entered_rx) = mpsc::channel();
+        let (open_gate, gate) = mpsc::channel();
+        let timestamps = Arc::new(Mutex::new(Vec::new()));
+        let backend = GatedBackend { entered, gate: Some(gate), timestamps: timestamps.clone() };
+        let (writer, thread) = StorageWriter::spawn_with_capacity(Box::new(backend), 2).expect("Failed to spawn writer");
+
+        // The first sample blocks the writer thread, the next two fill the queue
+        writer.append(SleepData::builder(1).build()).unwrap();
+        entered_rx.recv().unwrap();
+        writer.append(SleepData::builder(2).build()).unwrap();
+        writer.append(SleepData::builder(3).build()).unwrap();
+        assert!(writer.append(SleepData::builder(4).build()).is_err());
+        assert!(writer.append_audio_level(4, -40.0).is_err());
+        assert_eq!(writer.metrics(), WriterMetrics {
+            capacity: 2,
+            queue_depth: 2,
+            max_queue_depth: 2,
+            dropped_samples: 1,
+            dropped_audio_levels: 1,
+        });
+
+        open_gate.send(()).unwrap();
+        drop(writer);
+        thread.join().expect("Storage writer panicked");
+        assert_eq!(*timestamps.lock().unwrap(), vec![1, 2, 3]);
+        assert!(StorageWriter::spawn_with_capacity(Box::new(GatedBackend {
+            entered: mpsc::channel().0,
+            gate: None,
+            timestamps,
+        }), 0).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Write-ahead journal of the samples buffered by a storage backend.
+//!
+//! Backends buffer samples and write them in batches, so a panic or power cut between flushes
+//! would lose the buffered samples. Each buffered sample is therefore first appended to a small
+//! journal file next to the data file (`<file_name>.journal`), which is truncated once the batch
+//! is flushed. On the next start, [`storage::open`](crate::storage::open) replays what is left
+//! into the session it belongs to.
+//!
+//! The journal starts with the session name, followed by the samples, each encoded with bincode.
+//! A sample cut off by a power cut is discarded when the journal is read.
+
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::data::SleepData;
+
+/// Path of the journal of the data file `file_name` in `data_path`.
+pub(crate) fn path(data_path: &str, file_name: &str) -> PathBuf {
+    Path::new(data_path).join(format!("{file_name}.journal"))
+}
+
+/// Open journal of a session's buffered samples.
+#[derive(Debug)]
+pub(crate) struct Journal {
+    path: PathBuf,
+    file: File,
+    session: String,
+    /// Whether nothing, not even the session name, has been written since the journal was
+    /// created or cleared.
+    empty: bool,
+}
+
+impl Journal {
+    /// Creates the journal at `path` for the session `session`, replacing any previous journal,
+    /// which must have been replayed (see [`read`]).
+    pub(crate) fn create(path: impl Into<PathBuf>, session: &str) -> Result<Self, Box<dyn Error>> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).write(true).truncate(true).open(&path)
+            .map_err(|e| format!("Failed to create journal {}: {}", path.display(), e))?;
+        Ok(Self { path, file, session: session.to_string(), empty: true })
+    }
+
+    /// Appends `sample`, and waits until it is on disk.
+    pub(crate) fn append(&mut self, sample: &SleepData) -> Result<(), Box<dyn Error>> {
+        let mut record = Vec::new();
+        if self.empty {
+            bincode::serialize_into(&mut record, &self.session)?;
+        }
+        bincode::serialize_into(&mut record, sample)?;
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        self.empty = false;
+        Ok(())
+    }
+
+    /// Removes the journaled samples, once they are flushed to the data file.
+    pub(crate) fn clear(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.empty {
+            return Ok(());
+        }
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.sync_data()?;
+        self.empty = true;
+        Ok(())
+    }
+
+    /// Deletes the journal file, once the session's samples are all flushed.
+    pub(crate) fn remove(self) -> Result<(), Box<dyn Error>> {
+        Ok(fs::remove_file(&self.path)?)
+    }
+}
+
+/// Samples read from a journal.
+#[derive(Debug)]
+pub(crate) struct Journaled {
+    /// Name of the session the samples belong to.
+    pub session: String,
+    /// The samples, in the order they were appended.
+    pub samples: Vec<SleepData>,
+}
+
+/// Reads the journal at `path`. `None` if there is no journal, or it is empty.
+pub(crate) fn read(path: &Path) -> Result<Option<Journaled>, Box<dyn Error>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to open journal {}: {}", path.display(), e).into()),
+    };
+    if file.metadata()?.len() == 0 {
+        return Ok(None);
+    }
+    let mut reader = BufReader::new(file);
+    let session: String = bincode::deserialize_from(&mut reader)
+        .map_err(|e| format!("Failed to read the session of journal {}: {}", path.display(), e))?;
+    let mut samples = Vec::new();
+    while !reader.fill_buf()?.is_empty() {
+        match bincode::deserialize_from::<_, SleepData>(&mut reader) {
+            Ok(sample) => samples.push(sample),
+            Err(e) => {
+                warn!("Discarding the end of journal {}: {}", path.display(), e);
+                break;
+            }
+        }
+    }
+    Ok(Some(Journaled { session, samples }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_journal_round_trip() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = path(dir.path().to_str().unwrap(), "sleep_data.h5");
+        assert!(read(&path).unwrap().is_none());
+
+        let mut journal = Journal::create(&path, "2025-04-28_22-47-31").expect("Failed to create journal");
+        journal.append(&SleepData::builder(10).with_pir_motion(true).build()).unwrap();
+        journal.clear().unwrap();
+        // Cleared journals are empty until the next sample
+        assert!(read(&path).unwrap().is_none());
+        journal.append(&SleepData::builder(15).with_climate(21.5, 1013.0, 40.0).build()).unwrap();
+        journal.append(&SleepData::builder(20).build()).unwrap();
+
+        let Journaled { session, samples } = read(&path).unwrap().expect("Expected journaled samples");
+        assert_eq!(session, "2025-04-28_22-47-31");
+        assert_eq!(samples.iter().map(|s| s.timestamp_s).collect::<Vec<_>>(), vec![15, 20]);
+        assert_eq!(samples[0].temperature_c, 21.5);
+        assert!(samples[1].temperature_c.is_nan());
+
+        // A sample cut off while writing is discarded
+        let length = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 3).unwrap();
+        assert_eq!(read(&path).unwrap().unwrap().samples.len(), 1);
+
+        journal.remove().unwrap();
+        assert!(!path.exists());
+    }
+}
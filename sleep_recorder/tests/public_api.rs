@@ -0,0 +1,66 @@
+//! Pins the supported public API (see the crate documentation).
+//!
+//! These checks run without hardware: they only need to compile. The programs in
+//! `tests/public_api/` name every item of the supported modules, and the tests below pin the
+//! signatures of the prelude. If one of them stops compiling, the change is breaking for
+//! downstream users and needs a version bump.
+
+use std::error::Error;
+
+use sleep_recorder::prelude::*;
+use tokio_util::sync::CancellationToken;
+
+type Fallible<T> = Result<T, Box<dyn Error>>;
+
+#[test]
+fn exported_items() {
+    trybuild::TestCases::new().pass("tests/public_api/*.rs");
+}
+
+#[test]
+fn config_api() {
+    let _: fn(&str) -> Config = Config::new;
+    let _: fn(&str) -> Fallible<Config> = Config::from_toml_str;
+    let _: fn(&Config) -> Fallible<()> = Config::validate;
+
+    let config = Config::default();
+    let _: &str = &config.data_path;
+    let _: &str = &config.file_name;
+    let _: &AudioConfig = &config.audio;
+}
+
+#[test]
+fn recorder_api() {
+    let _: fn(Config) -> Recorder = Recorder::new;
+    let _: fn(&Recorder) -> &Config = Recorder::config;
+    let _: fn(&Recorder) -> CancellationToken = Recorder::cancel_token;
+    let _: fn(&Recorder) = Recorder::stop;
+
+    let recorder = Recorder::new(Config::new("/tmp"));
+    recorder.stop();
+    assert!(recorder.cancel_token().is_cancelled());
+}
+
+#[test]
+fn data_api() {
+    let _: fn(&str, &str) -> Fallible<SleepDataLogger> = SleepDataLogger::new;
+    let _: fn(&str, &str, &str) -> Fallible<SessionReader> = SessionReader::open;
+    let _: fn(&SessionReader) -> Fallible<Vec<u64>> = SessionReader::timestamps;
+    let _: fn(&SessionReader) -> Fallible<Vec<SleepData>> = SessionReader::samples;
+    let _: fn(u64) -> SleepDataBuilder = SleepData::builder;
+    let _: fn(SleepDataBuilder) -> SleepData = SleepDataBuilder::build;
+
+    let _: &AudioRecording = &AudioRecording {
+        path: String::new(),
+        duration: std::time::Duration::ZERO,
+        start_time_s: 0,
+    };
+}
+
+#[test]
+fn analysis_api() {
+    let _: fn() -> Pipeline = Pipeline::new;
+    let _: fn(Pipeline, bool) -> Pipeline = Pipeline::with_audio;
+    let _: fn(Pipeline, bool) -> Pipeline = Pipeline::with_motion;
+    let _: fn(&Pipeline, &str, &str, &str) -> Fallible<()> = Pipeline::run;
+}
@@ -0,0 +1,17 @@
+#![allow(unused_imports)]
+
+use sleep_recorder::analysis::Pipeline;
+use sleep_recorder::audio_analysis::{
+    analyze_audio_entries, analyze_audio_entries_with_key, analyze_audio_entries_with_memory, extract_event_clips,
+    recording_level_dbfs, trim_silent_audio, Band, BandMeter, EventClips, LevelMeter, SilentAudio, SilentAudioAction,
+    StreamAnalyzer, WindowFeatures, ANALYSIS_WINDOW_S, AUDIO_ERROR_CATEGORY, BANDS, BAND_TIMES_DATASET,
+    DEFAULT_ANALYSIS_MEMORY_MIB, EVENT_CLIP_CATEGORY,
+};
+use sleep_recorder::sleep_periods::{detect, SleepPeriod, SleepState, EPOCH_S, MOTION_THRESHOLD, SLEEP_ONSET_S};
+#[cfg(feature = "ml")]
+use sleep_recorder::sound_classifier::{
+    RecordingClassifier, SoundClassification, SoundClassifier, FEATURES, FLOOR_DB, FRAME_S, SOUND_CLASS_CATEGORY,
+    WINDOW_FRAMES,
+};
+
+fn main() {}
@@ -0,0 +1,18 @@
+#![allow(unused_imports)]
+
+use sleep_recorder::config::{
+    ActuatorConfig, AudioBackend, AudioConfig, AudioFormat, AudioWeighting, Bme280Config, CameraConfig,
+    ClassifierConfig, Compression, CompressionCodec, CompressionConfig, Config, DatasetCompression, Ds18b20Config,
+    EncryptionConfig, FileRotation, GpioLineConfig, Hx711Config, InfluxDbConfig, MotionClipConfig, MotionRoi,
+    NightModeConfig, OverlayConfig, PiezoConfig, RemoteConfig, RetentionConfig, SensorInitConfig, SessionConfig,
+    StorageFormat, ThermistorConfig, DEFAULT_SENSORS, I2C_SENSORS,
+};
+use sleep_recorder::calibration::{
+    Bme280Calibration, Calibration, Ens160Calibration, MicrophoneCalibration, PiezoCalibration, ThermistorCalibration,
+};
+use sleep_recorder::{
+    sleep_tracker, BcgEstimate, ImageArchive, NoiseStats, PmMeasurement, Recorder, Scd4xMeasurement, SoundClass,
+    SoundEvent,
+};
+
+fn main() {}
@@ -0,0 +1,20 @@
+#![allow(unused_imports)]
+
+use sleep_recorder::data::{
+    data_files, mark_purged, merge, night_file_name, schema_version, sensor_status_key, session_path, upgrade_session,
+    verify, verify_session, ActuatorEvent, AppendableColumn, AudioRecording, CameraAndMotionResult, CorruptChunk, Event,
+    H5AudioMetadata, H5Event, IntegrityIssue, IntegrityReport, MergeSummary, PlausibleRange, SensorState, SensorStatus,
+    SessionInfo, SessionMetadata, SessionReader, SleepData, SleepDataBuilder, SleepDataLogger, SleepField, Thumbnail,
+    LATEST, PLAUSIBLE_RANGES, SCHEMA_VERSION,
+};
+use sleep_recorder::data::export::edf::{to_edf, MAX_HOLD_S, RECORD_S};
+use sleep_recorder::data::export::health::to_apple_health;
+#[cfg(feature = "json")]
+use sleep_recorder::data::export::health::to_google_fit;
+#[cfg(feature = "parquet")]
+use sleep_recorder::data::export::to_parquet;
+use sleep_recorder::data::export::{sample_columns, Column};
+use sleep_recorder::data::summary::{group_name, Aggregate, Summary, INTERVALS_S};
+use sleep_recorder::data::units::{dataset_info, DatasetInfo};
+
+fn main() {}
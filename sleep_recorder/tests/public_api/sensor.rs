@@ -0,0 +1,13 @@
+#![allow(unused_imports)]
+
+use sleep_recorder::sensor::{
+    find_capture_device, list_capture_devices, shared_adc, shared_i2c, AdcInput, AudioChunk, AudioRecorder, AudioSource,
+    BH1750Wrapper, BME280Wrapper, CameraWrapper, CaptureDevice, Ds18b20Wrapper, ENS160Wrapper, HX711Wrapper, LightLevel,
+    PMS5003Wrapper, PiezoWrapper, PirWrapper, SCD4xWrapper, SGP40Wrapper, Sensor, SensorReader, SharedAdc, SharedI2c,
+    SystemStats, SystemStatsWrapper, ThermistorWrapper, DEFAULT_I2C_BUS, DEFAULT_SENSOR_TIMEOUT,
+};
+use sleep_recorder::actuator::{Actuator, ActuatorCommand, ActuatorHandle, Actuators, GpioRelay};
+#[cfg(feature = "simulation")]
+use sleep_recorder::simulation::{actuators, record_audio, sensor_reader, AUDIO_SAMPLE_RATE};
+
+fn main() {}
@@ -0,0 +1,19 @@
+#![allow(unused_imports)]
+
+use sleep_recorder::storage::{
+    mark_purged, open, StorageBackend, StorageWriter, Tee, WriterMetrics, DEFAULT_FLUSH_EVERY, DEFAULT_MAX_BUFFER_KIB,
+    DEFAULT_QUEUE_CAPACITY, MAX_BUFFER_KIB, MAX_FLUSH_EVERY,
+};
+use sleep_recorder::retention::{prune, Media, PruneReport};
+use sleep_recorder::encryption::{
+    decrypt, decrypt_file, encrypt, encrypt_file, is_encrypted, is_encrypted_path, read, EncryptingBackend, Key,
+    EXTENSION, MAGIC, STREAM_MAGIC,
+};
+#[cfg(feature = "sqlite")]
+use sleep_recorder::sqlite::SqliteLogger;
+#[cfg(feature = "influxdb")]
+use sleep_recorder::influxdb::{export_session, InfluxClient, InfluxSink};
+#[cfg(feature = "remote")]
+use sleep_recorder::remote::{RemoteSink, SPOOL_DIRECTORY};
+
+fn main() {}